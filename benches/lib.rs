@@ -6,14 +6,18 @@ extern crate test;
 extern crate rand;
 
 extern crate merkle;
-extern crate ring;
+
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
 
 use test::Bencher;
 use rand::Rng;
 
-use ring::digest::{Algorithm, SHA512};
+use merkle::digest::{Algorithm, SHA512};
 
-use merkle::MerkleTree;
+use merkle::{MerkleTree, MerkleTreeBuilder, DEFAULT_PARALLEL_THRESHOLD};
+#[cfg(feature = "metrics")]
+use merkle::MetricsObserver;
 
 #[allow(non_upper_case_globals)]
 static digest: &'static Algorithm = &SHA512;
@@ -117,3 +121,260 @@ fn bench_big_rnd_iter(b: &mut Bencher) {
         test::black_box(value);
     });
 }
+
+#[bench]
+fn bench_large_tree_build(b: &mut Bencher) {
+    let values = (0..1u64 << 16).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    b.iter(|| {
+        let tree = MerkleTree::from_vec(digest, values.clone());
+        test::black_box(tree)
+    });
+}
+
+/// Compare with and without the `metrics` feature: a tree without an observer costs the same.
+#[bench]
+fn bench_large_tree_build_unobserved(b: &mut Bencher) {
+    let values = (0..1u64 << 16).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    b.iter(|| {
+        let tree = MerkleTreeBuilder::new(digest).build_from_vec(values.clone());
+        test::black_box(tree)
+    });
+}
+
+#[cfg(feature = "metrics")]
+#[bench]
+fn bench_large_tree_build_observed(b: &mut Bencher) {
+    let values = (0..1u64 << 16).map(|i| i.to_string()).collect::<Vec<_>>();
+    let observer = Arc::new(MetricsObserver::new());
+
+    b.iter(|| {
+        let tree = MerkleTreeBuilder::new(digest).observer(observer.clone()).build_from_vec(values.clone());
+        test::black_box(tree)
+    });
+}
+
+#[bench]
+fn bench_large_tree_snapshot_write(b: &mut Bencher) {
+    let values = (0..1u64 << 16).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+
+    b.iter(|| {
+        let mut bytes = Vec::new();
+        tree.write_snapshot(&mut bytes).unwrap();
+        test::black_box(bytes)
+    });
+}
+
+#[bench]
+fn bench_large_tree_snapshot_read(b: &mut Bencher) {
+    let values = (0..1u64 << 16).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+
+    let mut bytes = Vec::new();
+    tree.write_snapshot(&mut bytes).unwrap();
+
+    b.iter(|| {
+        let tree = MerkleTree::<String>::read_snapshot(digest, &bytes[..]).unwrap();
+        test::black_box(tree)
+    });
+}
+
+#[bench]
+fn bench_large_tree_snapshot_read_nodes(b: &mut Bencher) {
+    let values = (0..1u64 << 16).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+
+    let mut bytes = Vec::new();
+    tree.write_snapshot_with_nodes(&mut bytes).unwrap();
+
+    b.iter(|| {
+        let tree = MerkleTree::<String>::read_snapshot(digest, &bytes[..]).unwrap();
+        test::black_box(tree)
+    });
+}
+
+#[bench]
+fn bench_big_rnd_all_proofs(b: &mut Bencher) {
+    let mut values = vec![vec![0u8; 256]; 160];
+    let mut rng = rand::IsaacRng::new_unseeded();
+
+    for mut v in &mut values {
+        rng.fill_bytes(&mut v);
+    }
+
+    let tree = MerkleTree::from_vec(digest, values);
+
+    b.iter(|| test::black_box(tree.all_proofs()));
+}
+
+#[bench]
+fn bench_big_rnd_nth_proofs(b: &mut Bencher) {
+    let mut values = vec![vec![0u8; 256]; 160];
+    let mut rng = rand::IsaacRng::new_unseeded();
+
+    for mut v in &mut values {
+        rng.fill_bytes(&mut v);
+    }
+
+    let tree = MerkleTree::from_vec(digest, values);
+
+    b.iter(|| {
+        let proofs = (0..tree.count()).map(|n| tree.gen_nth_proof(n)).collect::<Vec<_>>();
+        test::black_box(proofs)
+    });
+}
+
+#[bench]
+fn bench_large_tree_from_leaf_hashes(b: &mut Bencher) {
+    let tree = MerkleTree::from_vec(digest, (0..1u64 << 16).map(|i| i.to_string()).collect());
+    let hashes = tree.leaf_hashes_vec();
+
+    b.iter(|| {
+        let tree = MerkleTree::from_leaf_hashes(digest, hashes.clone()).unwrap();
+        test::black_box(tree)
+    });
+}
+
+/// The indices of 1024 updates to a tree of `2^16` leaves, either in a single run of leaves
+/// or spread across the whole tree.
+fn update_indices(clustered: bool) -> Vec<usize> {
+    (0..1024).map(|i| if clustered { 4096 + i } else { i * 64 + 17 }).collect()
+}
+
+fn bench_updates(b: &mut Bencher, clustered: bool, batched: bool) {
+    let mut tree = MerkleTree::from_vec(digest, (0..1u64 << 16).map(|i| i.to_string()).collect());
+    let indices = update_indices(clustered);
+    let mut round = 0u64;
+
+    b.iter(|| {
+        round += 1;
+        let updates = indices.iter().map(|&index| (index, format!("{}-{}", index, round)));
+
+        if batched {
+            test::black_box(tree.apply_updates(updates).unwrap());
+        } else {
+            for (index, value) in updates {
+                test::black_box(tree.update(index, value).unwrap());
+            }
+        }
+    });
+}
+
+#[bench]
+fn bench_large_tree_sequential_updates_clustered(b: &mut Bencher) {
+    bench_updates(b, true, false);
+}
+
+#[bench]
+fn bench_large_tree_apply_updates_clustered(b: &mut Bencher) {
+    bench_updates(b, true, true);
+}
+
+#[bench]
+fn bench_large_tree_sequential_updates_scattered(b: &mut Bencher) {
+    bench_updates(b, false, false);
+}
+
+#[bench]
+fn bench_large_tree_apply_updates_scattered(b: &mut Bencher) {
+    bench_updates(b, false, true);
+}
+
+/// Values of the given sizes: many small ones, fewer larger ones, or many small ones
+/// along with a few huge ones, which are uneven when split by count.
+fn sized_values(shape: &str) -> Vec<Vec<u8>> {
+    let sized = |count: usize, size: usize| (0..count).map(|i| vec![i as u8; size]).collect::<Vec<_>>();
+
+    match shape {
+        "small" => sized(1 << 16, 32),
+        "medium" => sized(1 << 12, 4096),
+        _ => {
+            let mut values = sized(1 << 12, 32);
+            values.extend(sized(8, 4 << 20));
+            values
+        }
+    }
+}
+
+/// Builds a tree over values of the given shape, sequentially, with the levels hashed
+/// in parallel from `threshold` nodes, or with the values hashed in parallel too.
+fn bench_parallel_build(b: &mut Bencher, shape: &str, threshold: Option<usize>, values_too: bool) {
+    let values = sized_values(shape);
+
+    b.iter(|| {
+        let builder = match threshold {
+            Some(threshold) => MerkleTreeBuilder::new(digest).parallel(true).parallel_threshold(threshold),
+            None => MerkleTreeBuilder::new(digest),
+        };
+
+        let tree = if values_too {
+            builder.build_from_vec_parallel(values.clone())
+        } else {
+            builder.build_from_vec(values.clone())
+        };
+
+        test::black_box(tree)
+    });
+}
+
+#[bench]
+fn bench_parallel_small_leaves_sequential(b: &mut Bencher) {
+    bench_parallel_build(b, "small", None, false);
+}
+
+#[bench]
+fn bench_parallel_small_leaves_every_level(b: &mut Bencher) {
+    bench_parallel_build(b, "small", Some(1), false);
+}
+
+#[bench]
+fn bench_parallel_small_leaves_default_threshold(b: &mut Bencher) {
+    bench_parallel_build(b, "small", Some(DEFAULT_PARALLEL_THRESHOLD), false);
+}
+
+#[bench]
+fn bench_parallel_small_leaves_hashed_in_parallel(b: &mut Bencher) {
+    bench_parallel_build(b, "small", Some(DEFAULT_PARALLEL_THRESHOLD), true);
+}
+
+#[bench]
+fn bench_parallel_medium_leaves_sequential(b: &mut Bencher) {
+    bench_parallel_build(b, "medium", None, false);
+}
+
+#[bench]
+fn bench_parallel_medium_leaves_every_level(b: &mut Bencher) {
+    bench_parallel_build(b, "medium", Some(1), false);
+}
+
+#[bench]
+fn bench_parallel_medium_leaves_default_threshold(b: &mut Bencher) {
+    bench_parallel_build(b, "medium", Some(DEFAULT_PARALLEL_THRESHOLD), false);
+}
+
+#[bench]
+fn bench_parallel_medium_leaves_hashed_in_parallel(b: &mut Bencher) {
+    bench_parallel_build(b, "medium", Some(DEFAULT_PARALLEL_THRESHOLD), true);
+}
+
+#[bench]
+fn bench_parallel_skewed_leaves_sequential(b: &mut Bencher) {
+    bench_parallel_build(b, "skewed", None, false);
+}
+
+#[bench]
+fn bench_parallel_skewed_leaves_every_level(b: &mut Bencher) {
+    bench_parallel_build(b, "skewed", Some(1), false);
+}
+
+#[bench]
+fn bench_parallel_skewed_leaves_default_threshold(b: &mut Bencher) {
+    bench_parallel_build(b, "skewed", Some(DEFAULT_PARALLEL_THRESHOLD), false);
+}
+
+#[bench]
+fn bench_parallel_skewed_leaves_hashed_in_parallel(b: &mut Bencher) {
+    bench_parallel_build(b, "skewed", Some(DEFAULT_PARALLEL_THRESHOLD), true);
+}
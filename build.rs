@@ -0,0 +1,91 @@
+//! Generates the Protobuf messages of the `proto` module from `protos/merkle.proto`,
+//! with the `serialization-protobuf` feature.
+//!
+//! The schema is compiled with the `protoc` found in the `PROTOC` environment variable,
+//! or in the `PATH`, unless the `protobuf-vendored-codegen` feature is enabled, in which case
+//! the one shipped by the `protoc-bin-vendored` crate is used.
+
+#[cfg(feature = "serialization-protobuf")]
+extern crate protobuf;
+#[cfg(feature = "serialization-protobuf")]
+extern crate protoc;
+#[cfg(feature = "protobuf-vendored-codegen")]
+extern crate protoc_bin_vendored;
+
+#[cfg(feature = "serialization-protobuf")]
+mod codegen {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use protobuf::{self, descriptor::FileDescriptorSet};
+    use protoc::{DescriptorSetOutArgs, Protoc};
+
+    const SCHEMA: &str = "protos/merkle.proto";
+
+    #[cfg(feature = "protobuf-vendored-codegen")]
+    fn protoc() -> Protoc {
+        let path = ::protoc_bin_vendored::protoc_bin_path().expect("protoc is vendored for this platform");
+        Protoc::from_path(path.to_str().expect("the path to protoc is valid UTF-8"))
+    }
+
+    #[cfg(not(feature = "protobuf-vendored-codegen"))]
+    fn protoc() -> Protoc {
+        match env::var("PROTOC") {
+            Ok(path) => Protoc::from_path(&path),
+            Err(_) => Protoc::from_env_path(),
+        }
+    }
+
+    pub fn run() {
+        println!("cargo:rerun-if-changed={}", SCHEMA);
+        println!("cargo:rerun-if-env-changed=PROTOC");
+
+        let protoc = protoc();
+
+        if let Err(err) = protoc.check() {
+            panic!(
+                "protoc is required to compile `{}` ({}): install it, point the `PROTOC` \
+                 environment variable to it, or enable the `protobuf-vendored-codegen` feature",
+                SCHEMA,
+                err
+            );
+        }
+
+        let out_dir = env::var("OUT_DIR").expect("cargo sets OUT_DIR");
+        let descriptors = Path::new(&out_dir).join("merkle.desc");
+
+        protoc
+            .write_descriptor_set(DescriptorSetOutArgs {
+                out: descriptors.to_str().expect("OUT_DIR is valid UTF-8"),
+                includes: &["protos"],
+                input: &[SCHEMA],
+                include_imports: true,
+            })
+            .expect("the schema compiles");
+
+        let bytes = fs::read(&descriptors).expect("protoc wrote the descriptors");
+        let descriptors: FileDescriptorSet = protobuf::parse_from_bytes(&bytes).expect("the descriptors are valid");
+
+        for file in protobuf::codegen::gen(descriptors.get_file(), &["merkle.proto".to_string()]) {
+            let code = String::from_utf8(file.content).expect("the generated code is valid UTF-8");
+
+            // The code is `include!`d, where inner attributes are not allowed: `proto::proof` sets them
+            let code = code
+                .lines()
+                .filter(|line| !line.starts_with("#!["))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            fs::write(Path::new(&out_dir).join(&file.name), code).expect("the generated code is written");
+        }
+    }
+}
+
+#[cfg(feature = "serialization-protobuf")]
+fn main() {
+    codegen::run();
+}
+
+#[cfg(not(feature = "serialization-protobuf"))]
+fn main() {}
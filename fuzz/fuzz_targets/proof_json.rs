@@ -0,0 +1,39 @@
+//! Feeds arbitrary JSON to the serde decoders of proofs, and the decoded proofs to
+//! `Proof::validate`, none of which may panic.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate merkle;
+extern crate serde_json;
+
+use merkle::digest::SHA256;
+
+use merkle::{ProofData, CanonicalProofData, ProofEnvelope};
+
+fuzz_target!(|data: &[u8]| {
+    let json = match serde_json::from_slice::<serde_json::Value>(data) {
+        Ok(json) => json,
+        Err(_) => return,
+    };
+
+    let root_hash = [0; 32];
+
+    if let Ok(data) = serde_json::from_value::<ProofData<Vec<u8>>>(json.clone()) {
+        let _ = data.clone().into_proof(&SHA256);
+        let proof = data.into_proof_unchecked(&SHA256);
+
+        let _ = proof.validate(&root_hash);
+        let _ = proof.validate_against(&root_hash, u64::MAX);
+        let _ = proof.to_indexed_path();
+    }
+
+    if let Ok(data) = serde_json::from_value::<CanonicalProofData<Vec<u8>>>(json.clone()) {
+        let _ = data.into_proof(&SHA256).validate(&root_hash);
+    }
+
+    if let Ok(envelope) = ProofEnvelope::<Vec<u8>>::from_json(json) {
+        let _ = envelope.into_proof_unchecked(&SHA256).validate(&root_hash);
+    }
+});
@@ -0,0 +1,42 @@
+//! Feeds arbitrary bytes to the decoders of every binary format, and the decoded proofs to
+//! `Proof::validate`, none of which may panic.
+//!
+//! The first byte selects the format, the rest is the proof.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate merkle;
+
+use merkle::digest::SHA256;
+
+use merkle::{Proof, ProofFormat, verify_proof_bytes};
+
+fuzz_target!(|data: &[u8]| {
+    let (format, bytes) = match data.split_first() {
+        Some((&format, bytes)) => (format, bytes),
+        None => return,
+    };
+
+    let root_hash = [0; 32];
+
+    let proof = match format % 2 {
+        0 => Proof::from_bytes(&SHA256, bytes),
+        _ => Proof::<Vec<u8>>::parse_protobuf(&SHA256, bytes),
+    };
+
+    if let Ok(proof) = proof {
+        let _ = proof.validate(&root_hash);
+        let _ = proof.validate_against(&root_hash, u64::MAX);
+        let _ = proof.to_indexed_path();
+    }
+
+    let format = match format % 3 {
+        0 => ProofFormat::Wire,
+        1 => ProofFormat::Protobuf,
+        _ => ProofFormat::Json,
+    };
+
+    let _ = verify_proof_bytes(&SHA256, bytes, format, &root_hash);
+});
@@ -0,0 +1,61 @@
+/*
+ * C interface for verifying merkle.rs proofs, available when the crate
+ * is built with the `ffi` feature.
+ *
+ * Proofs are handed over in the binary format described in the
+ * documentation of the `merkle::wire` module.
+ */
+
+#ifndef MERKLE_H
+#define MERKLE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Return codes of merkle_proof_validate. */
+#define MERKLE_PROOF_VALID          1
+#define MERKLE_PROOF_INVALID        0
+#define MERKLE_ERROR_NULL_POINTER (-1)
+#define MERKLE_ERROR_ALGORITHM    (-2)
+#define MERKLE_ERROR_PANIC        (-3)
+
+/* Algorithm tags. */
+#define MERKLE_ALGORITHM_SHA1        1
+#define MERKLE_ALGORITHM_SHA256      2
+#define MERKLE_ALGORITHM_SHA384      3
+#define MERKLE_ALGORITHM_SHA512      4
+#define MERKLE_ALGORITHM_SHA512_256  5
+
+/* An opaque handle on a parsed proof. */
+typedef struct ProofHandle ProofHandle;
+
+/*
+ * Parses the `len` bytes at `bytes` as a proof.
+ * Returns a handle to be released with merkle_proof_free,
+ * or NULL if `bytes` is NULL or does not hold a proof.
+ */
+ProofHandle *merkle_proof_parse(const uint8_t *bytes, size_t len);
+
+/*
+ * Checks whether the proof behind `handle` is valid with respect to the
+ * `root_len` bytes of root hash at `root`, and was produced with the
+ * algorithm identified by `algorithm_tag`.
+ * Returns MERKLE_PROOF_VALID, MERKLE_PROOF_INVALID, or a negative error code.
+ */
+int32_t merkle_proof_validate(const ProofHandle *handle,
+                              const uint8_t *root,
+                              size_t root_len,
+                              uint8_t algorithm_tag);
+
+/* Releases a handle returned by merkle_proof_parse. Does nothing if `handle` is NULL. */
+void merkle_proof_free(ProofHandle *handle);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MERKLE_H */
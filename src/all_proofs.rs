@@ -0,0 +1,153 @@
+use merkletree::MerkleTree;
+use proof::{Proof, Lemma, Positioned};
+use tree::{Tree, View};
+
+/// A node on the path from the root of a tree to a leaf, given by its position in the tree,
+/// along with the position of its sibling, unless it is the root.
+type Step = (usize, Option<Positioned<usize>>);
+
+/// The path from the root of a tree down to one of its leaves, which is moved on from leaf
+/// to leaf, from left to right, only visiting the nodes between both.
+///
+/// It holds the positions of the nodes rather than the nodes themselves, so that it can walk
+/// a tree it does not borrow, and is given the tree at every step.
+pub(crate) struct LeafPath {
+    steps: Vec<Step>,
+}
+
+impl LeafPath {
+    /// Starts at the leftmost leaf of `tree`, skipping the pruned subtrees, unless
+    /// it has no such leaf, as told by `count`, the number of leaves which are not pruned.
+    pub(crate) fn new<T>(tree: &Tree<T>, count: usize, height: usize) -> Self {
+        let mut path = LeafPath {
+            steps: Vec::with_capacity(height + 1),
+        };
+
+        if count > 0 {
+            path.steps.push((tree.root().position(), None));
+            path.descend(tree);
+        }
+
+        path
+    }
+
+    /// Returns the position of the leaf the path leads to, or `None` once past the last one.
+    pub(crate) fn leaf(&self) -> Option<usize> {
+        self.steps.last().map(|&(leaf, _)| leaf)
+    }
+
+    /// Returns the lemma chain from the root of `tree` down to the leaf the path leads to.
+    pub(crate) fn lemma<T>(&self, tree: &Tree<T>) -> Lemma {
+        // Each lemma holds the sibling of the node below it, and the lemma of the leaf none
+        let mut lemma = None;
+        let mut sibling_hash = None;
+
+        for &(node, ref sibling) in self.steps.iter().rev() {
+            lemma = Some(Lemma {
+                node_hash: tree.subtree(node).hash().to_vec(),
+                sibling_hash: sibling_hash.take(),
+                sub_lemma: lemma.map(Box::new),
+            });
+
+            sibling_hash = sibling.as_ref().map(|sibling| {
+                sibling.as_ref().map(|&sibling| tree.subtree(sibling).hash().to_vec())
+            });
+        }
+
+        lemma.expect("the path is not empty")
+    }
+
+    /// Moves the path on to the next leaf, skipping the `Empty` nodes and the pruned subtrees,
+    /// or clears it if the current leaf is the last one.
+    pub(crate) fn advance<T>(&mut self, tree: &Tree<T>) {
+        self.next_subtree(tree);
+        self.descend(tree);
+    }
+
+    /// Extends the path down to the leftmost leaf below its last node, moving on past the
+    /// pruned subtrees.
+    fn descend<T>(&mut self, tree: &Tree<T>) {
+        while let Some(&(node, _)) = self.steps.last() {
+            match tree.subtree(node).view() {
+                View::Node { left, right, .. } => {
+                    self.steps.push((left.position(), Some(Positioned::Right(right.position()))))
+                }
+                View::Pruned { .. } => self.next_subtree(tree),
+                _ => return,
+            }
+        }
+    }
+
+    /// Moves the path on to the right sibling of its last node, or of the closest ancestor of it
+    /// which is a left child, unless that sibling is an `Empty` node.
+    fn next_subtree<T>(&mut self, tree: &Tree<T>) {
+        while let Some((node, sibling)) = self.steps.pop() {
+            match sibling {
+                Some(Positioned::Right(right)) if !tree.subtree(right).is_empty() => {
+                    self.steps.push((right, Some(Positioned::Left(node))));
+                    return;
+                }
+
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Yields the inclusion proofs of all the leaves of a `MerkleTree`, from left to right,
+/// as `MerkleTree::gen_nth_proof` would. See `MerkleTree::iter_proofs`.
+///
+/// The tree is walked once, depth first, keeping the path from the root to the current leaf,
+/// so that moving on to the next leaf only visits the nodes between both, rather than
+/// descending from the root again. Each proof still owns a copy of the hashes on its path.
+#[allow(missing_debug_implementations)]
+pub struct AllProofs<'a, T: 'a> {
+    tree: &'a MerkleTree<T>,
+    path: LeafPath,
+    remaining: usize,
+}
+
+impl<'a, T> AllProofs<'a, T> {
+    /// Starts at the leftmost leaf of the given `tree`, which has `count` leaves
+    /// which are not pruned.
+    pub(crate) fn new(tree: &'a MerkleTree<T>, count: usize) -> Self {
+        AllProofs {
+            tree: tree,
+            path: LeafPath::new(tree.tree(), count, tree.height()),
+            remaining: count,
+        }
+    }
+}
+
+impl<'a, T: Clone> Iterator for AllProofs<'a, T> {
+    type Item = Proof<T>;
+
+    fn next(&mut self) -> Option<Proof<T>> {
+        let root = self.tree.tree();
+
+        let leaf = self.path.leaf()?;
+
+        let proof = self.tree.options().timed_proof(|| {
+            let value = match root.subtree(leaf).view() {
+                View::Leaf { value, .. } => value.clone(),
+                _ => unreachable!("the path always ends at a leaf"),
+            };
+
+            let lemma = self.path.lemma(root);
+            let root_hash = lemma.node_hash.clone();
+
+            Some(self.tree.new_proof(root_hash, lemma, value, self.tree.count()))
+        });
+
+        self.path.advance(root);
+        self.remaining -= 1;
+
+        proof
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T: Clone> ExactSizeIterator for AllProofs<'a, T> {}
@@ -0,0 +1,148 @@
+use std::mem;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use digest::Algorithm;
+
+use error::Error;
+use hashutils::Hashable;
+use merkletree::MerkleTree;
+use options::TreeOptions;
+use proof::Proof;
+use tree::Leaf;
+
+/// Appends values to a `MerkleTree` from several threads at once, while other threads read
+/// the last committed version of the tree.
+///
+/// `ConcurrentAppender::push` hashes the value on the calling thread, then stages the leaf
+/// in a queue without taking any lock. `ConcurrentAppender::commit` appends the staged leaves
+/// to a copy of the tree, as `MerkleTree::try_extend` does, then publishes the new tree, so that
+/// readers never wait for hashing: `ConcurrentAppender::snapshot` hands out the last committed
+/// tree, which stays the same for as long as it is held.
+///
+/// The staged leaves are appended in the order in which they were staged, so that the values
+/// pushed by a thread keep their order, and every value pushed before a commit starts is part
+/// of that commit. A value pushed while a commit is running goes into that commit or the next one.
+/// Each staged value is appended exactly once.
+///
+/// Committing copies the tree, so that batching many values per commit is cheaper than
+/// committing after each of them. Commits can be run from a background thread, and are
+/// serialized with one another.
+#[derive(Debug)]
+pub struct ConcurrentAppender<T> {
+    /// The hashing algorithm of the tree
+    pub algorithm: &'static Algorithm,
+
+    /// The options of the tree, which the values are hashed with
+    options: TreeOptions<T>,
+
+    /// The end of the queue the values are staged in
+    staged: Sender<Leaf<T>>,
+
+    /// The other end of the queue, along with the leaves of a commit which failed
+    committer: Mutex<Committer<T>>,
+
+    /// The last committed tree
+    snapshot: RwLock<Arc<MerkleTree<T>>>,
+}
+
+/// The state of `ConcurrentAppender::commit`.
+#[derive(Debug)]
+struct Committer<T> {
+    queue: Receiver<Leaf<T>>,
+
+    /// The staged leaves which were taken out of the queue but could not be committed
+    pending: Vec<Leaf<T>>,
+}
+
+impl<T: Clone> ConcurrentAppender<T> {
+    /// Starts appending to the given tree, which is the first committed version.
+    pub fn new(tree: MerkleTree<T>) -> Self {
+        let (staged, queue) = mpsc::channel();
+
+        ConcurrentAppender {
+            algorithm: tree.algorithm,
+            options: tree.options().clone(),
+            staged: staged,
+            committer: Mutex::new(Committer {
+                queue: queue,
+                pending: Vec::new(),
+            }),
+            snapshot: RwLock::new(Arc::new(tree)),
+        }
+    }
+
+    /// Hashes the given value and stages it, to be appended to the tree by the next commit.
+    pub fn push(&self, value: T)
+    where
+        T: Hashable,
+    {
+
+        let leaf = Leaf::new(self.options.hash_leaf(self.algorithm, &value), value);
+
+        self.staged.send(leaf).expect("the queue lives as long as the appender");
+    }
+
+    /// Appends the staged values to the tree, in the order they were staged, then publishes
+    /// the new tree. Returns the number of values added, which is less than the number of
+    /// staged values if the tree drops duplicates, see `MerkleTreeBuilder::dedup`.
+    ///
+    /// Fails as `MerkleTree::try_extend` does, leaving the last committed tree as it is.
+    /// The staged values are then kept, ahead of the values staged afterwards, for the next
+    /// commit to append, unless they are dropped with `ConcurrentAppender::discard_pending`.
+    pub fn commit(&self) -> Result<usize, Error> {
+        let mut committer = self.committer.lock().unwrap_or_else(PoisonError::into_inner);
+        let committer = &mut *committer;
+
+        committer.pending.extend(committer.queue.try_iter());
+
+        if committer.pending.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tree = MerkleTree::clone(&self.snapshot());
+        let added = tree.try_extend_leaves(committer.pending.iter().cloned())?;
+
+        committer.pending.clear();
+        *self.snapshot.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(tree);
+
+        Ok(added)
+    }
+
+    /// Drops the staged values which a failed commit could not append, returning them.
+    pub fn discard_pending(&self) -> Vec<T> {
+        let mut committer = self.committer.lock().unwrap_or_else(PoisonError::into_inner);
+
+        mem::take(&mut committer.pending).into_iter().map(|leaf| leaf.value).collect()
+    }
+
+    /// Returns the last committed tree, which later commits leave as it is.
+    pub fn snapshot(&self) -> Arc<MerkleTree<T>> {
+        self.snapshot.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Returns the root hash of the last committed tree.
+    pub fn root_hash(&self) -> Vec<u8> {
+        self.snapshot().root_hash().clone()
+    }
+
+    /// Returns the number of leaves of the last committed tree.
+    pub fn count(&self) -> usize {
+        self.snapshot().count()
+    }
+
+    /// Generates the inclusion proof of the `index`-th leaf of the last committed tree,
+    /// as `MerkleTree::gen_nth_proof` does.
+    pub fn gen_nth_proof(&self, index: usize) -> Option<Proof<T>> {
+        self.snapshot().gen_nth_proof(index)
+    }
+
+    /// Commits the staged values, then returns the tree.
+    pub fn into_tree(self) -> Result<MerkleTree<T>, Error> {
+        self.commit()?;
+
+        let tree = self.snapshot.into_inner().unwrap_or_else(PoisonError::into_inner);
+
+        Ok(Arc::try_unwrap(tree).unwrap_or_else(|tree| (*tree).clone()))
+    }
+}
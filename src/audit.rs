@@ -0,0 +1,131 @@
+use digest::Algorithm;
+
+use error::Error;
+use hash::AlgorithmTag;
+use hashutils::Hashable;
+use index;
+use merkletree::MerkleTree;
+use options::OddLeafPolicy;
+use prefix::PrefixProof;
+use proof::Proof;
+
+/// An append-only log of values, kept in a `MerkleTree` built with the default options,
+/// whose every append is acknowledged with an `AppendReceipt`, which proves both that the new
+/// entry is the last leaf of the new tree, and that the previous tree is a prefix of it.
+///
+/// Consumers holding the root of the log check each receipt against that root with
+/// `verify_receipt`, then move on to the root of the receipt, so that replaying the receipts
+/// from the empty log proves the whole history of its roots.
+///
+/// An append only hashes the new leaf and the nodes above it, see `MerkleTree::push`,
+/// and generating both proofs goes down a single path of the tree, so that appending takes
+/// `O(log n)` hashes, on top of moving the nodes of the tree.
+#[derive(Clone, Debug)]
+pub struct AuditLog<T> {
+    tree: MerkleTree<T>,
+}
+
+/// What `AuditLog::append` returns, to be checked with `verify_receipt` against the root
+/// of the log before the append.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppendReceipt<T> {
+    /// The number of entries of the log after the append
+    pub tree_size: u64,
+
+    /// The root hash of the log after the append
+    pub root_hash: Vec<u8>,
+
+    /// The inclusion proof of the new entry, the last one of the log
+    pub inclusion: Proof<T>,
+
+    /// The proof that the log before the append is a prefix of the log after it
+    pub consistency: PrefixProof,
+}
+
+impl<T> AuditLog<T> {
+    /// Creates an empty log, hashed with the given `algorithm`.
+    pub fn new(algorithm: &'static Algorithm) -> Self {
+        AuditLog { tree: MerkleTree::empty(algorithm) }
+    }
+
+    /// Returns the number of entries of the log.
+    pub fn len(&self) -> usize {
+        self.tree.count()
+    }
+
+    /// Returns whether the log has no entry.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns the root hash of the log, which is the hash of the empty string
+    /// for an empty log.
+    pub fn root_hash(&self) -> &Vec<u8> {
+        self.tree.root_hash()
+    }
+
+    /// Returns the tree over the entries of the log.
+    pub fn tree(&self) -> &MerkleTree<T> {
+        &self.tree
+    }
+
+    /// Appends the given value to the log, returning the receipt of the append.
+    pub fn append(&mut self, value: T) -> AppendReceipt<T>
+    where
+        T: Hashable + Clone,
+    {
+
+        let previous_len = self.tree.count();
+        self.tree.push(value);
+
+        let inclusion = self.tree.gen_nth_proof(previous_len).expect("the value was just appended");
+        let consistency = self.tree.prove_prefix(previous_len).expect("the log only grows");
+
+        AppendReceipt {
+            tree_size: self.tree.count() as u64,
+            root_hash: self.tree.root_hash().clone(),
+            inclusion: inclusion,
+            consistency: consistency,
+        }
+    }
+}
+
+/// Checks that the given receipt acknowledges the append of its value to the log whose root
+/// hash was `previous_root`, with `tree_size - 1` entries, the log then having the root hash
+/// of the receipt.
+///
+/// Fails with `Error::AlgorithmMismatch` if both proofs were not generated with the same
+/// algorithm, with `Error::InvalidPath` if the inclusion proof is not the path to the last leaf
+/// of a tree of `tree_size` leaves, and with `Error::InvalidProof` if either proof does not hold.
+pub fn verify_receipt<T>(previous_root: &[u8], receipt: &AppendReceipt<T>) -> Result<(), Error>
+where
+    T: Hashable,
+{
+
+    let inclusion = &receipt.inclusion;
+
+    if AlgorithmTag::of(receipt.consistency.algorithm) != AlgorithmTag::of(inclusion.algorithm) {
+        return Err(Error::AlgorithmMismatch);
+    }
+
+    let size = match index::to_index(receipt.tree_size)? {
+        0 => return Err(Error::InvalidPath("tree size is out of range")),
+        size => size,
+    };
+
+    if inclusion.lemma.leaf_index(size, OddLeafPolicy::Promote) != Some(size - 1) {
+        return Err(Error::InvalidPath("not the path to the last leaf of the tree"));
+    }
+
+    let leaf_hash = inclusion.scheme.hash_leaf(inclusion.algorithm, &inclusion.value);
+
+    if leaf_hash.as_ref() != inclusion.lemma.leaf_hash() || !inclusion.validate(&receipt.root_hash) {
+        return Err(Error::InvalidProof);
+    }
+
+    if !receipt.consistency.verify(previous_root, size - 1, &receipt.root_hash, size) {
+        return Err(Error::InvalidProof);
+    }
+
+    Ok(())
+}
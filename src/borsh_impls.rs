@@ -0,0 +1,111 @@
+
+use std::io::{self, Read, Write};
+
+use borsh::{BorshSerialize, BorshDeserialize};
+
+use options::HashingScheme;
+use proof::{ProofData, Lemma, Positioned, Direction, MAX_TREE_DEPTH};
+
+/// The maximum number of sub lemmas accepted when decoding a `Lemma` from borsh,
+/// which is `MAX_TREE_DEPTH`.
+///
+/// Decoding a deeper lemma chain fails with an error of kind `InvalidData`,
+/// rather than recursing without bound on untrusted input.
+pub const BORSH_MAX_LEMMA_DEPTH: usize = MAX_TREE_DEPTH;
+
+/// See the documentation of `ProofData` for the encoding.
+impl<T: BorshSerialize> BorshSerialize for ProofData<T> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.root_hash.serialize(writer)?;
+        self.lemma.serialize(writer)?;
+        self.value.serialize(writer)
+    }
+}
+
+impl<T: BorshDeserialize> BorshDeserialize for ProofData<T> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let root_hash = Vec::deserialize_reader(reader)?;
+        let lemma = Lemma::deserialize_reader(reader)?;
+        let value = T::deserialize_reader(reader)?;
+
+        Ok(ProofData {
+            algorithm: None,
+            scheme: HashingScheme::default(),
+            root_hash: root_hash,
+            lemma: lemma,
+            value: value,
+            tree_size: None,
+            leaf_index: None,
+            leaf_counts: None,
+        })
+    }
+}
+
+impl BorshSerialize for Lemma {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.node_hash.serialize(writer)?;
+        self.sibling_hash.serialize(writer)?;
+
+        match self.sub_lemma {
+            None => 0u8.serialize(writer),
+
+            Some(ref sub_lemma) => {
+                1u8.serialize(writer)?;
+                sub_lemma.serialize(writer)
+            }
+        }
+    }
+}
+
+impl BorshDeserialize for Lemma {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        read_lemma(reader, 0)
+    }
+}
+
+fn read_lemma<R: Read>(reader: &mut R, depth: usize) -> io::Result<Lemma> {
+    let node_hash = Vec::deserialize_reader(reader)?;
+    let sibling_hash = Option::deserialize_reader(reader)?;
+
+    let sub_lemma = match u8::deserialize_reader(reader)? {
+        0 => None,
+
+        1 if depth >= BORSH_MAX_LEMMA_DEPTH => {
+            return Err(invalid_data(
+                format!("lemma exceeds the maximum depth of {}", BORSH_MAX_LEMMA_DEPTH),
+            ));
+        }
+
+        1 => Some(Box::new(read_lemma(reader, depth + 1)?)),
+
+        tag => return Err(invalid_data(format!("invalid sub lemma tag: {}", tag))),
+    };
+
+    Ok(Lemma {
+        node_hash: node_hash,
+        sibling_hash: sibling_hash,
+        sub_lemma: sub_lemma,
+    })
+}
+
+impl BorshSerialize for Positioned<Vec<u8>> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.direction().to_byte().serialize(writer)?;
+        self.as_ref().into_inner().serialize(writer)
+    }
+}
+
+impl BorshDeserialize for Positioned<Vec<u8>> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let tag = u8::deserialize_reader(reader)?;
+
+        match Direction::from_byte(tag) {
+            Some(direction) => Ok(Positioned::new(direction, Vec::deserialize_reader(reader)?)),
+            None => Err(invalid_data(format!("invalid position tag: {}", tag))),
+        }
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,275 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use digest::{Algorithm, Context};
+
+use error::Error;
+use hasher::MerkleHasher;
+use options::HashingScheme;
+use policy::ValidationPolicy;
+use proof::{Proof, MAX_TREE_DEPTH};
+
+/// Caps on the work `Proof::validate_bounded` may do to validate a proof, and that
+/// `validate_batch_bounded` may do to validate a whole batch of proofs, such as proofs
+/// received from third parties, so that a pathological input cannot take more than its share.
+///
+/// The cost of a proof is known before anything is hashed, see `Proof::validation_cost`:
+/// a proof which would exceed the budget is rejected with `Error::BudgetExceeded` upfront.
+/// Like a `ValidationPolicy`, a budget is `Copy`, and checking it does not allocate.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use merkle::{MerkleTree, ValidationBudget, Error};
+/// # fn main() {
+/// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c", "d"]);
+/// let proof = tree.gen_nth_proof(1).unwrap();
+///
+/// // Two levels, each hashing a prefix byte and two hashes of 32 bytes
+/// assert!(proof.validate_bounded(tree.root_hash(), &ValidationBudget::new().max_hashes(2).max_bytes(130)).is_ok());
+///
+/// match proof.validate_bounded(tree.root_hash(), &ValidationBudget::new().max_bytes(129)) {
+///     Err(Error::BudgetExceeded { needed, .. }) => assert_eq!((needed.hashes, needed.bytes), (2, 130)),
+///     other => panic!("{:?}", other),
+/// }
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ValidationBudget {
+    max_hashes: u64,
+    max_bytes: u64,
+}
+
+impl Default for ValidationBudget {
+    fn default() -> Self {
+        ValidationBudget {
+            max_hashes: u64::MAX,
+            max_bytes: u64::MAX,
+        }
+    }
+}
+
+impl ValidationBudget {
+    /// Creates a budget without caps, which any proof fits.
+    pub fn new() -> Self {
+        ValidationBudget::default()
+    }
+
+    /// Caps the number of hashes computed.
+    pub fn max_hashes(mut self, hashes: u64) -> Self {
+        self.max_hashes = hashes;
+        self
+    }
+
+    /// Caps the total number of bytes hashed.
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Returns the caps of the budget, as the usage they allow.
+    pub fn limits(&self) -> BudgetUsage {
+        BudgetUsage {
+            hashes: self.max_hashes,
+            bytes: self.max_bytes,
+        }
+    }
+
+    /// Returns what remains of the budget once `spent` is.
+    fn remaining(&self, spent: BudgetUsage) -> BudgetUsage {
+        BudgetUsage {
+            hashes: self.max_hashes.saturating_sub(spent.hashes),
+            bytes: self.max_bytes.saturating_sub(spent.bytes),
+        }
+    }
+}
+
+/// A number of hashes and of bytes hashed, see `ValidationBudget`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BudgetUsage {
+    /// The number of hashes computed
+    pub hashes: u64,
+
+    /// The total number of bytes hashed
+    pub bytes: u64,
+}
+
+impl BudgetUsage {
+    /// Fails with `Error::BudgetExceeded` if this usage does not fit in `remaining`.
+    fn fits(self, remaining: BudgetUsage) -> Result<(), Error> {
+        if self.hashes > remaining.hashes || self.bytes > remaining.bytes {
+            return Err(Error::BudgetExceeded {
+                needed: self,
+                remaining: remaining,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// The outcome of `validate_batch_bounded`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchReport {
+    /// The number of proofs which were validated, from the start of the batch, which is all
+    /// of them unless the budget ran out
+    pub processed: usize,
+
+    /// The index and the error of each processed proof which did not validate, in order
+    pub failures: Vec<(usize, Error)>,
+
+    /// The hashes computed, and the bytes hashed, to validate the processed proofs
+    pub spent: BudgetUsage,
+
+    /// The `Error::BudgetExceeded` of the first proof which did not fit in what remained
+    /// of the budget, at index `processed`, if any
+    pub exhausted: Option<Error>,
+}
+
+impl BatchReport {
+    /// Returns whether every proof of the batch was processed and validated.
+    pub fn all_valid(&self) -> bool {
+        self.exhausted.is_none() && self.failures.is_empty()
+    }
+}
+
+impl<T> Proof<T> {
+    /// Returns the number of hashes, and of bytes hashed, that validating this proof takes,
+    /// with `Proof::validate` or `Proof::validate_bounded`: one hash per level of the lemma chain,
+    /// over the prefix of the `HashingScheme` of the proof, if any, the hashes of both children,
+    /// of the output length of its algorithm, and the leaf count of the node, if the proof has
+    /// `leaf_counts`. The value is not hashed, and neither is the input a hasher starts every
+    /// hash with, see `MerkleHasher::begin`.
+    ///
+    /// A proof rejected upfront, such as one with more than `MAX_TREE_DEPTH` levels or with
+    /// hashes of another length, hashes nothing, but is still given the cost of its depth.
+    pub fn validation_cost(&self) -> BudgetUsage {
+        let hashes = self.depth() as u64;
+
+        BudgetUsage {
+            hashes: hashes,
+            bytes: hashes.saturating_mul(self.bytes_per_hash()),
+        }
+    }
+
+    /// Returns the number of bytes each hash of the lemma chain hashes.
+    fn bytes_per_hash(&self) -> u64 {
+        let prefix = if self.scheme == HashingScheme::DomainSeparated { 1 } else { 0 };
+        let count = if self.leaf_counts.is_some() { 8 } else { 0 };
+
+        prefix + 2 * self.algorithm.output_len as u64 + count
+    }
+
+    /// Checks whether this inclusion proof is valid for the given `root_hash`, as with
+    /// `Proof::validate`, provided that doing so fits in the given `budget`.
+    ///
+    /// Fails with `Error::BudgetExceeded`, before anything is hashed, if the cost of the proof
+    /// exceeds the budget, see `Proof::validation_cost`, and with the errors
+    /// of `Proof::validate_with_policy` with the default policy otherwise, such as
+    /// `Error::InvalidProof` if the proof does not hold.
+    ///
+    /// `Proof::validate` does no accounting at all.
+    pub fn validate_bounded(&self, root_hash: &[u8], budget: &ValidationBudget) -> Result<(), Error> {
+        self.validate_bounded_with(self.hasher(), root_hash, budget)
+    }
+
+    /// Works like `Proof::validate_bounded`, recomputing the node hashes with the given `hasher`,
+    /// see `Proof::validate_with`.
+    pub fn validate_bounded_with(
+        &self,
+        hasher: &dyn MerkleHasher,
+        root_hash: &[u8],
+        budget: &ValidationBudget,
+    ) -> Result<(), Error> {
+
+        if !self.lemma.depth_exceeds(MAX_TREE_DEPTH) {
+            self.validation_cost().fits(budget.limits())?;
+        }
+
+        self.check_policy(hasher, root_hash, &ValidationPolicy::default())
+    }
+}
+
+/// Validates the given `proofs` against `root_hash` in order, as `Proof::validate_bounded`
+/// does, the budget being shared by the whole batch rather than given to each proof.
+///
+/// Stops at the first proof whose cost exceeds what remains of the budget, which is then
+/// reported as `BatchReport::exhausted`, so that `BatchReport::processed` is the number
+/// of proofs before it. A proof which does not validate is reported among the failures,
+/// and the next one is validated. Only the hashes actually computed are charged,
+/// so that a proof rejected before anything is hashed costs nothing.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use merkle::{MerkleTree, ValidationBudget, validate_batch_bounded};
+/// # fn main() {
+/// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c", "d"]);
+/// let proofs = (0..4).map(|index| tree.gen_nth_proof(index).unwrap()).collect::<Vec<_>>();
+///
+/// let report = validate_batch_bounded(&proofs, tree.root_hash(), &ValidationBudget::new().max_hashes(5));
+///
+/// assert_eq!(report.processed, 2);
+/// assert_eq!(report.spent.hashes, 4);
+/// assert!(report.failures.is_empty() && report.exhausted.is_some());
+/// # }
+/// ```
+pub fn validate_batch_bounded<T>(proofs: &[Proof<T>], root_hash: &[u8], budget: &ValidationBudget) -> BatchReport {
+    let mut report = BatchReport {
+        processed: 0,
+        failures: Vec::new(),
+        spent: BudgetUsage::default(),
+        exhausted: None,
+    };
+
+    for (index, proof) in proofs.iter().enumerate() {
+        if !proof.lemma.depth_exceeds(MAX_TREE_DEPTH) {
+            if let Err(err) = proof.validation_cost().fits(budget.remaining(report.spent)) {
+                report.exhausted = Some(err);
+                break;
+            }
+        }
+
+        let metered = Metered {
+            inner: proof.hasher(),
+            hashes: AtomicU64::new(0),
+        };
+
+        let result = proof.check_policy(&metered, root_hash, &ValidationPolicy::default());
+
+        let hashes = metered.hashes.into_inner();
+
+        report.spent.hashes += hashes;
+        report.spent.bytes += hashes * proof.bytes_per_hash();
+        report.processed += 1;
+
+        if let Err(err) = result {
+            report.failures.push((index, err));
+        }
+    }
+
+    report
+}
+
+/// A hasher which counts the hashes computed with another one.
+#[derive(Debug)]
+struct Metered<'a> {
+    inner: &'a dyn MerkleHasher,
+    hashes: AtomicU64,
+}
+
+impl<'a> MerkleHasher for Metered<'a> {
+    fn algorithm(&self) -> &'static Algorithm {
+        self.inner.algorithm()
+    }
+
+    fn begin(&self, context: &mut Context) {
+        self.inner.begin(context);
+    }
+
+    fn context(&self) -> Context {
+        self.hashes.fetch_add(1, Ordering::Relaxed);
+        self.inner.context()
+    }
+}
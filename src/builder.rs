@@ -0,0 +1,414 @@
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use digest::Algorithm;
+
+use error::Error;
+use hasher::MerkleHasher;
+use hashutils::Hashable;
+use merkletree::{MerkleTree, DedupOrder};
+#[cfg(feature = "metrics")]
+use observer::TreeObserver;
+use options::{HashingScheme, OddLeafPolicy, TreeOptions};
+use ordering::LeafOrdering;
+use proof::MAX_TREE_DEPTH;
+use tree::{Tree, Leaf};
+
+/// Builds a `MerkleTree` with non-default construction options.
+///
+/// The options are stored in the built tree, and `MerkleTree::push`, `MerkleTree::retain`
+/// and the proofs generated from the tree abide by them.
+/// With the default options, `build_from_vec` yields the same tree as `MerkleTree::from_vec`.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use merkle::{MerkleTree, MerkleTreeBuilder, LeafOrdering, DedupOrder, SHA256};
+/// # fn main() {
+/// let values = vec!["c", "a", "b", "a"];
+///
+/// let tree = MerkleTreeBuilder::new(SHA256)
+///     .sorted(LeafOrdering::by_value())
+///     .dedup(DedupOrder::FirstOccurrence)
+///     .build_from_vec(values);
+///
+/// assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), vec!["a", "b", "c"]);
+/// assert_eq!(tree.root_hash(), MerkleTree::from_vec(SHA256, vec!["a", "b", "c"]).root_hash());
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MerkleTreeBuilder<T> {
+    algorithm: &'static Algorithm,
+    options: TreeOptions<T>,
+    capacity: usize,
+}
+
+impl<T> MerkleTreeBuilder<T> {
+    /// Creates a builder for trees hashed with the given `algorithm`, with the default options.
+    pub fn new(algorithm: &'static Algorithm) -> Self {
+        MerkleTreeBuilder {
+            algorithm: algorithm,
+            options: TreeOptions::default(),
+            capacity: 0,
+        }
+    }
+
+    /// Creates a builder for trees hashed with the given `algorithm`, with the given options.
+    pub(crate) fn with_options(algorithm: &'static Algorithm, options: TreeOptions<T>) -> Self {
+        MerkleTreeBuilder {
+            algorithm: algorithm,
+            options: options,
+            capacity: 0,
+        }
+    }
+
+    /// Sets the hashing algorithm, dropping the hasher set with `MerkleTreeBuilder::hasher`.
+    pub fn algorithm(mut self, algorithm: &'static Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self.options.hasher = None;
+        self
+    }
+
+    /// Hashes the tree with the given hasher, whose algorithm becomes the one of the tree,
+    /// such as a `KeyedHasher` holding a key only known at runtime.
+    ///
+    /// The proofs, multiproofs and prefix proofs generated from the tree hold on to the hasher,
+    /// and are validated with it, as are the partial trees built from its multiproofs.
+    /// The hasher is not serialized though: deserialized proofs are validated with
+    /// `Proof::validate_with`, given the same hasher, compact multiproofs are read with
+    /// `MultiProof::from_compact_bytes_with`, and snapshots with `MerkleTree::read_snapshot_with`.
+    pub fn hasher<H>(mut self, hasher: H) -> Self
+    where
+        H: MerkleHasher + 'static,
+    {
+
+        self.algorithm = hasher.algorithm();
+        self.options.hasher = Some(Arc::new(hasher));
+        self
+    }
+
+    /// Sets the way leaves and internal nodes are hashed.
+    /// Defaults to `HashingScheme::DomainSeparated`.
+    pub fn hashing_scheme(mut self, scheme: HashingScheme) -> Self {
+        self.options.scheme = scheme;
+        self
+    }
+
+    /// Sets what to do with the last node of levels which have an odd number of nodes.
+    /// Defaults to `OddLeafPolicy::Promote`.
+    pub fn odd_leaf_policy(mut self, policy: OddLeafPolicy) -> Self {
+        self.options.odd_leaf_policy = policy;
+        self
+    }
+
+    /// Sets the order of the leaves. Defaults to `LeafOrdering::Input`.
+    pub fn sorted(mut self, ordering: LeafOrdering<T>) -> Self {
+        self.options.ordering = ordering;
+        self
+    }
+
+    /// Drops the values whose leaf hash is the same as the one of a previous value.
+    ///
+    /// The leaves are then in the given `order`, unless they are `sorted`,
+    /// in which case the sort is applied after deduplication.
+    pub fn dedup(mut self, order: DedupOrder) -> Self {
+        self.options.dedup = Some(order);
+        self
+    }
+
+    /// Reserves room for the given number of leaves when building from an iterator.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Hashes large levels of the tree on all the available cores. Defaults to `false`.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.options.parallel = parallel;
+        self
+    }
+
+    /// Only hashes the levels of at least `threshold` nodes across threads, when `parallel`,
+    /// the upper levels being too small for it to pay off. The values given to
+    /// `MerkleTreeBuilder::build_from_vec_parallel` are hashed across threads from as many
+    /// bytes as `threshold` empty values. Defaults to `DEFAULT_PARALLEL_THRESHOLD`.
+    ///
+    /// The tree is the same whatever the threshold.
+    pub fn parallel_threshold(mut self, threshold: usize) -> Self {
+        self.options.parallel_threshold = threshold;
+        self
+    }
+
+    /// Fails with `Error::TooManyLeaves` when building a tree over more than `max` values.
+    /// The tree is also limited to that many leaves when pushing values into it.
+    /// Defaults to no limit.
+    ///
+    /// The limit is checked as the values are consumed, so that it holds even if the iterator
+    /// passed to `try_build_from_iter` lies about its size.
+    pub fn max_leaves(mut self, max: usize) -> Self {
+        self.options.max_leaves = max;
+        self
+    }
+
+    /// Hashes the leaves `size` at a time through `MerkleHasher::hash_leaves_batch`, when building
+    /// the tree and pushing values into it, for hashers which are faster given many messages
+    /// at once. Leaves are hashed one by one by default.
+    ///
+    /// Panics if `size` is zero.
+    pub fn leaf_batch_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "batches cannot be empty");
+
+        self.options.leaf_batch_size = Some(size);
+        self
+    }
+
+    /// Gives the tree exactly `2^depth` leaves, as in deposit contract style trees:
+    /// the values fill the leftmost slots, and the other leaves are the zero hash,
+    /// ie. as many zero bytes as the output of the algorithm. Defaults to no fixed depth.
+    ///
+    /// The height of the tree is then always `depth`, and so is the depth of its proofs,
+    /// whose `tree_size` is `2^depth`. The tree is limited to `2^depth` values, on top of
+    /// `max_leaves`. The subtrees made only of zero leaves are not materialized, their hashes
+    /// being those returned by `default_hashes`, so that building the tree takes
+    /// `O(count * depth)` time.
+    ///
+    /// Multiproofs and prefix proofs cannot be generated from such a tree.
+    ///
+    /// Panics if `depth` is at least `MAX_TREE_DEPTH`.
+    pub fn fixed_depth(mut self, depth: usize) -> Self {
+        assert!(depth < MAX_TREE_DEPTH, "a tree cannot be deeper than `MAX_TREE_DEPTH`");
+
+        self.options.fixed_depth = Some(depth);
+        self
+    }
+
+    /// Hashes along with every internal node the number of leaves beneath it, as 8 little-endian
+    /// bytes after the hashes of its children, see `HashingScheme::hash_counted_nodes_with`,
+    /// so that the root hash commits to the number of leaves of the tree, and the hash of every
+    /// node to the size of its subtree. Defaults to `false`.
+    ///
+    /// The zero leaves of a tree of fixed depth are not counted, nor are the copies made by
+    /// `OddLeafPolicy::Duplicate`. The proofs generated from the tree carry the counts of the
+    /// nodes of their lemma chain, see `Proof::leaf_counts`, which `Proof::validate` checks
+    /// along with the hashes, and `Proof::leaf_count` then gives the size of the tree.
+    /// They are serialized with serde and in the format of the `wire` module, whose algorithm
+    /// tag then has the `wire::LEAF_COUNTS` bit, but not with borsh nor Protobuf.
+    ///
+    /// Multiproofs and prefix proofs cannot be generated from such a tree, and it cannot be
+    /// stitched with `MerkleTree::from_chunks`.
+    pub fn leaf_counts(mut self, leaf_counts: bool) -> Self {
+        self.options.leaf_counts = leaf_counts;
+        self
+    }
+
+    /// Defers hashing the tree until its hashes are first needed. Defaults to `false`.
+    ///
+    /// The tree is then hashed as a whole by the first call to a method which needs a hash,
+    /// a proof, or the tree structure, such as `MerkleTree::root_hash`, `gen_proof` or `iter`.
+    /// Every hash of the tree depends on every leaf beneath it, and a proof includes the root
+    /// hash, so no proof can be generated without hashing every leaf. Only `count`, `height` and
+    /// the option getters are available for free, and `MerkleTree::is_hashed` tells whether the
+    /// tree has been hashed yet. This is safe to do from several threads at once, the tree being
+    /// hashed by only one of them.
+    ///
+    /// This has no effect on trees built with `dedup`, whose leaves must be hashed upfront to
+    /// find the duplicates. The tree is the same as the one built eagerly with the same options.
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.options.lazy = lazy;
+        self
+    }
+
+    /// Notifies the given observer of the work done while building the tree, then while adding
+    /// values to it and generating or verifying its proofs, see `TreeObserver`.
+    ///
+    /// Only available with the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn observer(mut self, observer: Arc<dyn TreeObserver>) -> Self {
+        self.options.observer = Some(observer);
+        self
+    }
+
+    /// Builds a tree over the given values.
+    ///
+    /// Panics if there are more than `max_leaves` of them, see `try_build_from_vec`.
+    pub fn build_from_vec(self, values: Vec<T>) -> MerkleTree<T>
+    where
+        T: Hashable,
+    {
+
+        self.build_from_iter(values)
+    }
+
+    /// Builds a tree over the values yielded by the given iterator.
+    ///
+    /// Panics if there are more than `max_leaves` of them, see `try_build_from_iter`.
+    pub fn build_from_iter<I>(self, values: I) -> MerkleTree<T>
+    where
+        T: Hashable,
+        I: IntoIterator<Item = T>,
+    {
+
+        self.try_build_from_iter(values).expect("too many values for `max_leaves`")
+    }
+
+    /// Builds a tree over the given values, failing with `Error::TooManyLeaves`
+    /// if there are more than `max_leaves` of them.
+    pub fn try_build_from_vec(self, values: Vec<T>) -> Result<MerkleTree<T>, Error>
+    where
+        T: Hashable,
+    {
+
+        let max = self.options.leaf_limit();
+
+        if values.len() > max && self.options.dedup.is_none() {
+            return Err(Error::TooManyLeaves { max: max });
+        }
+
+        self.try_build_from_iter(values)
+    }
+
+    /// Works like `MerkleTreeBuilder::build_from_vec` with `parallel`, but also hashes the values
+    /// on all the available cores, each getting a run of values of about as many bytes,
+    /// so that a few large values are not hashed one after the other behind many small ones.
+    /// See `MerkleTreeBuilder::parallel_threshold`.
+    ///
+    /// Panics if there are more than `max_leaves` values, see `try_build_from_vec_parallel`.
+    pub fn build_from_vec_parallel(self, values: Vec<T>) -> MerkleTree<T>
+    where
+        T: Hashable + Sync,
+    {
+
+        self.try_build_from_vec_parallel(values).expect("too many values for `max_leaves`")
+    }
+
+    /// Works like `MerkleTreeBuilder::build_from_vec_parallel`, failing with
+    /// `Error::TooManyLeaves` if there are more than `max_leaves` values.
+    ///
+    /// Lazy trees hash their values on first access, on the accessing thread.
+    pub fn try_build_from_vec_parallel(mut self, values: Vec<T>) -> Result<MerkleTree<T>, Error>
+    where
+        T: Hashable + Sync,
+    {
+
+        self.options.parallel = true;
+
+        if self.options.lazy && self.options.dedup.is_none() {
+            return self.try_build_from_vec(values);
+        }
+
+        let max = self.options.leaf_limit();
+
+        if values.len() > max && self.options.dedup.is_none() {
+            return Err(Error::TooManyLeaves { max: max });
+        }
+
+        let capacity = values.len().min(max);
+        let leaves = collect_leaves(&self.options, capacity, self.options.leaves_parallel(self.algorithm, values))?;
+
+        Ok(self.build_from_leaves(|| Tree::with_capacity(0), leaves))
+    }
+
+    /// Builds a tree over the values yielded by the given iterator, failing with
+    /// `Error::TooManyLeaves` as soon as more than `max_leaves` of them have been yielded.
+    pub fn try_build_from_iter<I>(self, values: I) -> Result<MerkleTree<T>, Error>
+    where
+        T: Hashable,
+        I: IntoIterator<Item = T>,
+    {
+
+        self.try_build_in(|| Tree::with_capacity(0), values)
+    }
+
+    /// Works like `MerkleTreeBuilder::try_build_from_iter`, but lays the nodes of the tree out
+    /// in the allocation of the tree returned by `buffer`, unless the tree is lazy, see
+    /// `Tree::recycle`. It is only called once the values are hashed and sorted, which runs
+    /// code of their own which may panic.
+    pub(crate) fn try_build_in<B, I>(self, buffer: B, values: I) -> Result<MerkleTree<T>, Error>
+    where
+        T: Hashable,
+        B: FnOnce() -> Tree<T>,
+        I: IntoIterator<Item = T>,
+    {
+
+        let algorithm = self.algorithm;
+        let max = self.options.leaf_limit();
+
+        let values = values.into_iter();
+        let capacity = self.capacity.max(values.size_hint().0.min(MAX_HINTED_CAPACITY));
+
+        if self.options.lazy && self.options.dedup.is_none() {
+            let mut pending = Vec::with_capacity(capacity.min(max));
+
+            for value in values {
+                if pending.len() == max {
+                    return Err(Error::TooManyLeaves { max: max });
+                }
+
+                pending.push(value);
+            }
+
+            if self.options.ordering.is_sorted() {
+                let ordering = &self.options.ordering;
+                pending.sort_by(|a, b| ordering.compare(a, b));
+            }
+
+            return Ok(MerkleTree::from_pending(algorithm, pending, self.options));
+        }
+
+        let leaves = collect_leaves(&self.options, capacity.min(max), self.options.leaves(algorithm, values))?;
+
+        Ok(self.build_from_leaves(buffer, leaves))
+    }
+
+    /// Sorts the given leaves as the options tell, then builds the tree over them in the
+    /// allocation of the tree returned by `buffer`.
+    fn build_from_leaves<B>(self, buffer: B, mut leaves: Vec<Leaf<T>>) -> MerkleTree<T>
+    where
+        B: FnOnce() -> Tree<T>,
+    {
+
+        if self.options.dedup == Some(DedupOrder::ByHash) {
+            leaves.sort_by(|a, b| a.hash.cmp(&b.hash));
+        }
+
+        self.options.ordering.sort_leaves(&mut leaves);
+
+        MerkleTree::from_leaves_in(self.algorithm, buffer(), leaves, self.options)
+    }
+
+    /// Builds an empty tree, to be filled with `MerkleTree::push`.
+    pub fn build_empty(self) -> MerkleTree<T> {
+        MerkleTree::from_leaves_with(self.algorithm, Vec::new(), self.options)
+    }
+}
+
+/// Collects the given leaves, dropping the duplicate ones if the options tell so, and failing
+/// with `Error::TooManyLeaves` as soon as there are more than `max_leaves` of them.
+fn collect_leaves<T, L>(options: &TreeOptions<T>, capacity: usize, leaves: L) -> Result<Vec<Leaf<T>>, Error>
+where
+    L: IntoIterator<Item = Leaf<T>>,
+{
+
+    let max = options.leaf_limit();
+    let mut collected = Vec::with_capacity(capacity);
+    let mut seen = HashSet::new();
+
+    for leaf in leaves {
+        if options.dedup.is_some() && !seen.insert(leaf.hash) {
+            continue;
+        }
+
+        if collected.len() == max {
+            return Err(Error::TooManyLeaves { max: max });
+        }
+
+        collected.push(leaf);
+    }
+
+    Ok(collected)
+}
+
+/// The maximum number of leaves allocated upfront because of an iterator's `size_hint`.
+const MAX_HINTED_CAPACITY: usize = 1 << 16;
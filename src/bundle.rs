@@ -0,0 +1,353 @@
+//! Proofs bundled with the signed head of the tree they were generated from, so that they can
+//! be checked on their own against a `TrustPolicy`, see `ProofBundle`.
+
+#![deny(clippy::indexing_slicing, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::fmt;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+
+use error::Error;
+use hash::RootHash;
+use hashutils::Hashable;
+use merkletree::MerkleTree;
+use options::HashingScheme;
+use policy::ValidationPolicy;
+use proof::{Proof, ProofData};
+use tree_head::TreeHead;
+use wire::Reader;
+
+/// The magic bytes every encoded bundle starts with.
+pub const BUNDLE_MAGIC: &'static [u8; 4] = b"MKLB";
+
+/// The latest version of the bundle format, which is the one written by `ProofBundle::to_bytes`.
+pub const BUNDLE_VERSION: u8 = 1;
+
+/// The bit set in the flags of an encoded bundle whose proof has a `leaf_index`.
+const HAS_LEAF_INDEX: u8 = 0x01;
+
+/// The bit set in the flags of an encoded bundle whose proof has a `tree_size`.
+const HAS_TREE_SIZE: u8 = 0x02;
+
+/// The bit set in the flags of an encoded bundle which has a signature.
+const HAS_SIGNATURE: u8 = 0x04;
+
+/// Checks signatures made with a given key, such as the key a log signs its tree heads with.
+///
+/// The crate implements no signature scheme of its own: implement this trait over the
+/// public key of the scheme of your choice, see `TrustPolicy::signed_by`.
+pub trait SignatureVerifier: Send + Sync + fmt::Debug {
+    /// Returns whether `signature` is a valid signature of `message` by the key of this verifier.
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// What a `ProofBundle` must meet for its tree head to be trusted: a pinned root hash,
+/// a signature of the tree head by a given key, or both.
+#[derive(Clone, Debug)]
+pub struct TrustPolicy {
+    pinned_root: Option<RootHash>,
+    signer: Option<Arc<dyn SignatureVerifier>>,
+}
+
+// The verifier is only called through a shared reference, to check a signature.
+impl UnwindSafe for TrustPolicy {}
+impl RefUnwindSafe for TrustPolicy {}
+
+impl TrustPolicy {
+    /// Trusts the tree heads whose root hash is `root`, computed with the algorithm of `root`.
+    pub fn pinned_root(root: RootHash) -> Self {
+        TrustPolicy {
+            pinned_root: Some(root),
+            signer: None,
+        }
+    }
+
+    /// Trusts the tree heads signed by the key of `verifier`, the signature covering
+    /// `TreeHead::to_signable_bytes`.
+    pub fn signed_by(verifier: Arc<dyn SignatureVerifier>) -> Self {
+        TrustPolicy {
+            pinned_root: None,
+            signer: Some(verifier),
+        }
+    }
+
+    /// Trusts the tree heads whose root hash is `root` and which are signed by the key
+    /// of `verifier`, see `TrustPolicy::pinned_root` and `TrustPolicy::signed_by`.
+    pub fn pinned_and_signed_by(root: RootHash, verifier: Arc<dyn SignatureVerifier>) -> Self {
+        TrustPolicy {
+            pinned_root: Some(root),
+            signer: Some(verifier),
+        }
+    }
+}
+
+/// A proof along with the head of the tree it was generated from, and a signature
+/// of that tree head, if any, so that it can be checked with nothing but a `TrustPolicy`,
+/// see `ProofBundle::verify`.
+///
+/// Bundles are serialized with serde as their fields, and encoded in a compact binary format
+/// with `ProofBundle::to_bytes`.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use merkle::{MerkleTree, TrustPolicy};
+/// # fn main() {
+/// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+/// let bundle = tree.gen_bundle(1).unwrap();
+///
+/// assert!(bundle.verify(&TrustPolicy::pinned_root(tree.root())).is_ok());
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct ProofBundle<T> {
+    /// The proof
+    pub proof: ProofData<T>,
+
+    /// The head of the tree the proof was generated from
+    pub tree_head: TreeHead,
+
+    /// A signature of `TreeHead::to_signable_bytes`, if any
+    #[cfg_attr(feature = "serialization-serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub signature: Option<Vec<u8>>,
+}
+
+impl<T> ProofBundle<T> {
+    /// Bundles the given proof with the head of the tree it was generated from, unsigned.
+    pub fn new(proof: Proof<T>, tree_head: TreeHead) -> Self {
+        ProofBundle {
+            proof: proof.into_data(),
+            tree_head: tree_head,
+            signature: None,
+        }
+    }
+
+    /// Attaches the given signature of `TreeHead::to_signable_bytes` to this bundle.
+    pub fn with_signature(mut self, signature: Vec<u8>) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Checks that the tree head of this bundle is trusted by the given `policy`,
+    /// and that the proof holds for it, running the checks in the following order
+    /// and failing with the error of the first one which does not pass:
+    ///
+    /// 1. the root hash of the tree head must have the output length of its algorithm,
+    ///    or this fails with `Error::HashLengthMismatch`;
+    /// 2. if the policy pins a root, the tree head must have that root hash and algorithm,
+    ///    or this fails with `Error::UntrustedRoot`;
+    /// 3. if the policy requires a signature, the bundle must have one, or this fails
+    ///    with `Error::MissingField`, and it must verify, or this fails
+    ///    with `Error::InvalidSignature`;
+    /// 4. the proof must go with the algorithm of the tree head, as `ProofData::into_proof`
+    ///    checks, or this fails with `Error::AlgorithmMismatch`, `Error::HashLengthMismatch`
+    ///    or `Error::Decode`;
+    /// 5. the proof must be the proof of a leaf of a tree of the size of the tree head,
+    ///    see `ValidationPolicy::tree_size`, or this fails with `Error::PolicyViolation`;
+    /// 6. the proof must lead from the hash of its value to the root hash of the tree head,
+    ///    or this fails with `Error::InvalidProof`.
+    pub fn verify(&self, policy: &TrustPolicy) -> Result<(), Error>
+    where
+        T: Hashable + Clone,
+    {
+
+        let head = &self.tree_head;
+        let algorithm = head.algorithm.algorithm();
+
+        let root = RootHash::new(algorithm, head.root_hash.clone())?;
+
+        if let Some(ref pinned) = policy.pinned_root {
+            if *pinned != root {
+                return Err(Error::UntrustedRoot);
+            }
+        }
+
+        if let Some(ref signer) = policy.signer {
+            let signature = self.signature.as_ref().ok_or(Error::MissingField("signature"))?;
+
+            if !signer.verify(&head.to_signable_bytes(), signature) {
+                return Err(Error::InvalidSignature);
+            }
+        }
+
+        let proof = self.proof.clone().into_proof(algorithm)?;
+
+        let policy = ValidationPolicy::new()
+            .tree_size(head.tree_size)
+            .require_value_check(true);
+
+        proof.validate_with_policy(root.as_bytes(), &policy)
+    }
+
+    /// Encodes this bundle in a compact binary format, in which all integers are big-endian:
+    ///
+    /// | Size | Contents                                                       |
+    /// |------|----------------------------------------------------------------|
+    /// | 4    | the magic bytes `BUNDLE_MAGIC`, ie. `"MKLB"`                   |
+    /// | 1    | the format version, currently `BUNDLE_VERSION`                 |
+    /// | 1    | the flags: `0x01` if the proof has a leaf index, `0x02` if it has a tree size, `0x04` if the bundle has a signature |
+    /// | 4    | the length `t` of the tree head                                |
+    /// | `t`  | the tree head, encoded by `TreeHead::to_signable_bytes`        |
+    /// | 8    | with the `0x01` flag, the leaf index of the proof              |
+    /// | 8    | with the `0x02` flag, the tree size of the proof               |
+    /// | 4    | the length `p` of the proof                                    |
+    /// | `p`  | the proof, encoded by `Proof::to_bytes`                        |
+    /// | 4    | with the `0x04` flag, the length `s` of the signature          |
+    /// | `s`  | with the `0x04` flag, the signature                            |
+    ///
+    /// Like the format of `Proof::to_bytes`, this format only holds the proofs of trees
+    /// hashed with the default `HashingScheme`, and will never change without its version
+    /// being bumped.
+    ///
+    /// Panics if the proof is hashed with another scheme, if it cannot be encoded by
+    /// `Proof::to_bytes`, or if the signature is longer than `u32::MAX` bytes.
+    pub fn to_bytes(&self) -> Vec<u8>
+    where
+        T: AsRef<[u8]>,
+    {
+
+        assert!(self.proof.scheme == HashingScheme::default(), "only the default scheme can be encoded");
+
+        let algorithm = self.proof.algorithm.unwrap_or(self.tree_head.algorithm).algorithm();
+
+        let mut proof = Proof::new(
+            algorithm,
+            self.proof.root_hash.clone(),
+            self.proof.lemma.clone(),
+            self.proof.value.as_ref(),
+        );
+        proof.leaf_counts = self.proof.leaf_counts.clone();
+
+        let head = self.tree_head.to_signable_bytes();
+        let proof = proof.to_bytes();
+
+        let mut flags = 0;
+
+        if self.proof.leaf_index.is_some() {
+            flags |= HAS_LEAF_INDEX;
+        }
+
+        if self.proof.tree_size.is_some() {
+            flags |= HAS_TREE_SIZE;
+        }
+
+        if self.signature.is_some() {
+            flags |= HAS_SIGNATURE;
+        }
+
+        let mut bytes = Vec::with_capacity(6 + 4 + head.len() + 16 + 4 + proof.len());
+
+        bytes.extend_from_slice(BUNDLE_MAGIC);
+        bytes.push(BUNDLE_VERSION);
+        bytes.push(flags);
+        write_chunk(&mut bytes, &head);
+
+        for n in self.proof.leaf_index.iter().chain(&self.proof.tree_size) {
+            bytes.extend_from_slice(&n.to_be_bytes());
+        }
+
+        write_chunk(&mut bytes, &proof);
+
+        if let Some(ref signature) = self.signature {
+            write_chunk(&mut bytes, signature);
+        }
+
+        bytes
+    }
+}
+
+impl ProofBundle<Vec<u8>> {
+    /// Decodes a bundle encoded by `ProofBundle::to_bytes`, the proof being decoded with the
+    /// algorithm of the tree head, as `Proof::from_bytes` does.
+    ///
+    /// Fails with `Error::InvalidMagic` if the input does not start with `BUNDLE_MAGIC`,
+    /// with `Error::UnsupportedVersion` if it was encoded with a newer version than
+    /// `BUNDLE_VERSION`, with `Error::Decode` if it has unknown flags, with
+    /// `Error::TruncatedInput` or `Error::TrailingBytes` if it is shorter or longer than
+    /// it tells, and with the errors of `TreeHead::parse` and `Proof::from_bytes`.
+    /// The proof is not checked, see `ProofBundle::verify`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader { bytes: bytes };
+
+        if reader.take(BUNDLE_MAGIC.len())? != BUNDLE_MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+
+        let version = reader.byte()?;
+
+        if version > BUNDLE_VERSION {
+            return Err(Error::UnsupportedVersion {
+                found: u32::from(version),
+                max_supported: u32::from(BUNDLE_VERSION),
+            });
+        }
+
+        let flags = reader.byte()?;
+
+        if flags & !(HAS_LEAF_INDEX | HAS_TREE_SIZE | HAS_SIGNATURE) != 0 {
+            return Err(Error::Decode(format!("unknown bundle flags {:#04x}", flags)));
+        }
+
+        let tree_head = TreeHead::parse(read_chunk(&mut reader)?)?;
+
+        let leaf_index = if flags & HAS_LEAF_INDEX == 0 { None } else { Some(read_u64(&mut reader)?) };
+        let tree_size = if flags & HAS_TREE_SIZE == 0 { None } else { Some(read_u64(&mut reader)?) };
+
+        let mut proof = Proof::from_bytes(tree_head.algorithm.algorithm(), read_chunk(&mut reader)?)?;
+        proof.leaf_index = leaf_index;
+        proof.tree_size = tree_size;
+
+        let signature = if flags & HAS_SIGNATURE == 0 {
+            None
+        } else {
+            Some(read_chunk(&mut reader)?.to_vec())
+        };
+
+        if !reader.bytes.is_empty() {
+            return Err(Error::TrailingBytes);
+        }
+
+        Ok(ProofBundle {
+            proof: proof.into_data(),
+            tree_head: tree_head,
+            signature: signature,
+        })
+    }
+}
+
+impl<T> MerkleTree<T> {
+    /// Generates the proof of the `n`-th leaf bundled with the head of this tree, unsigned,
+    /// see `ProofBundle::with_signature`, or `None` if there is no such leaf.
+    pub fn gen_bundle(&self, n: usize) -> Option<ProofBundle<T>>
+    where
+        T: Clone,
+    {
+
+        self.gen_nth_proof(n).map(|proof| ProofBundle::new(proof, self.tree_head()))
+    }
+}
+
+/// Appends `chunk` preceded by its length as a `u32`.
+fn write_chunk(bytes: &mut Vec<u8>, chunk: &[u8]) {
+    assert!(chunk.len() <= u32::MAX as usize, "chunk is too long to be encoded");
+
+    bytes.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(chunk);
+}
+
+/// Reads a chunk written by `write_chunk`.
+fn read_chunk<'a>(reader: &mut Reader<'a>) -> Result<&'a [u8], Error> {
+    let mut len = [0; 4];
+    len.copy_from_slice(reader.take(4)?);
+
+    reader.take(u32::from_be_bytes(len) as usize)
+}
+
+fn read_u64(reader: &mut Reader) -> Result<u64, Error> {
+    let mut n = [0; 8];
+    n.copy_from_slice(reader.take(8)?);
+
+    Ok(u64::from_be_bytes(n))
+}
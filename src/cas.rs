@@ -0,0 +1,118 @@
+use std::error;
+use std::fmt;
+
+#[cfg(feature = "serialization-serde")]
+use serde::{Deserialize, Serialize, Serializer};
+
+use error::Error;
+use hashutils::Hashable;
+use merkletree::MerkleTree;
+use proof::Proof;
+
+/// What `MerkleTree::compare_and_update` returns, proving both the value the leaf held
+/// before the update and the one it holds after it.
+///
+/// When serialized, the proofs are written along with the name of their algorithm,
+/// see `Proof::serialize_tagged`, and read back with the algorithm they name.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialization-serde", serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>")))]
+pub struct UpdateReceipt<T> {
+    /// The root hash of the tree before the update
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    pub old_root: Vec<u8>,
+
+    /// The root hash of the tree after the update
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    pub new_root: Vec<u8>,
+
+    /// The inclusion proof of the previous value of the leaf, against `old_root`
+    #[cfg_attr(
+        feature = "serialization-serde",
+        serde(serialize_with = "serialize_proof", deserialize_with = "Proof::deserialize_tagged")
+    )]
+    pub old_proof: Proof<T>,
+
+    /// The inclusion proof of the new value of the leaf, against `new_root`
+    #[cfg_attr(
+        feature = "serialization-serde",
+        serde(serialize_with = "serialize_proof", deserialize_with = "Proof::deserialize_tagged")
+    )]
+    pub new_proof: Proof<T>,
+}
+
+/// Serializes a proof along with the name of its algorithm, see `Proof::serialize_tagged`.
+#[cfg(feature = "serialization-serde")]
+fn serialize_proof<S, T>(proof: &Proof<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+
+    proof.serialize_tagged().serialize(serializer)
+}
+
+/// Why `MerkleTree::compare_and_update` left the tree untouched.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CasError<T> {
+    /// The leaf does not hold the expected value
+    Mismatch {
+        /// The value the leaf holds
+        actual: T,
+    },
+
+    /// The leaf cannot be updated, as with `MerkleTree::update`
+    Update(Error),
+}
+
+impl<T> From<Error> for CasError<T> {
+    fn from(err: Error) -> Self {
+        CasError::Update(err)
+    }
+}
+
+impl<T> fmt::Display for CasError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CasError::Mismatch { .. } => write!(f, "the leaf does not hold the expected value"),
+            CasError::Update(ref err) => write!(f, "cannot update the leaf: {}", err),
+        }
+    }
+}
+
+impl<T: fmt::Debug> error::Error for CasError<T> {}
+
+impl<T: Hashable + Clone> MerkleTree<T> {
+    /// Replaces the value of the leaf at the given `index` with `new`, as `MerkleTree::update`
+    /// does, provided that the leaf hashes as `expected_old`, returning the proofs of its value
+    /// before and after the update.
+    ///
+    /// Fails with `CasError::Mismatch`, holding a copy of the value of the leaf, if it does not
+    /// hash as `expected_old`, and with `CasError::Update` in the cases `MerkleTree::update`
+    /// fails in, leaving the tree untouched either way.
+    pub fn compare_and_update(
+        &mut self,
+        index: usize,
+        expected_old: &T,
+        new: T,
+    ) -> Result<UpdateReceipt<T>, CasError<T>> {
+
+        self.check_updates(Some(index))?;
+
+        let old_proof = self.gen_nth_proof(index).expect("the leaf is in the tree and not pruned");
+
+        if self.options().hash_leaf(self.algorithm, expected_old).as_ref() != old_proof.lemma.leaf_hash() {
+            return Err(CasError::Mismatch { actual: old_proof.value });
+        }
+
+        let old_root = self.root_hash().clone();
+        self.update(index, new)?;
+
+        Ok(UpdateReceipt {
+            old_root: old_root,
+            new_root: self.root_hash().clone(),
+            old_proof: old_proof,
+            new_proof: self.gen_nth_proof(index).expect("the leaf was just updated"),
+        })
+    }
+}
@@ -0,0 +1,109 @@
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use tree::Tree;
+
+/// Copies a tree, before it is modified for the first time since a checkpoint.
+type CloneTree<T> = fn(&Tree<T>) -> Tree<T>;
+
+/// Identifies a version of a `MerkleTree` recorded by `MerkleTree::checkpoint`.
+///
+/// Versions are increasing, so that the checkpoints taken before a given one can be dropped
+/// with `MerkleTree::drop_checkpoints_before`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VersionId(u64);
+
+/// The checkpoints of a tree.
+///
+/// A checkpoint shares the tree until it is first modified, the tree being copied only then,
+/// once for all the checkpoints taken since the last modification.
+#[derive(Clone, Debug)]
+pub struct Checkpoints<T> {
+    next: u64,
+    snapshots: BTreeMap<VersionId, Snapshot<T>>,
+    clone_tree: Option<CloneTree<T>>,
+}
+
+#[derive(Clone, Debug)]
+struct Snapshot<T> {
+    root_hash: Vec<u8>,
+    count: usize,
+
+    /// The tree at this version, `None` as long as it has not been modified since
+    tree: Option<Arc<Tree<T>>>,
+}
+
+impl<T> Default for Checkpoints<T> {
+    fn default() -> Self {
+        Checkpoints {
+            next: 0,
+            snapshots: BTreeMap::new(),
+            clone_tree: None,
+        }
+    }
+}
+
+impl<T> Checkpoints<T> {
+    /// Records the current state of the tree, whose root is `live` and which has `count` leaves.
+    pub fn record(&mut self, live: &Tree<T>, count: usize) -> VersionId
+    where
+        T: Clone,
+    {
+
+        let version = VersionId(self.next);
+        self.next += 1;
+
+        let snapshot = Snapshot {
+            root_hash: live.hash().clone(),
+            count: count,
+            tree: None,
+        };
+
+        self.snapshots.insert(version, snapshot);
+        self.clone_tree = Some(Tree::clone);
+
+        version
+    }
+
+    /// Copies the tree, whose root is `live`, for the checkpoints which still share it,
+    /// before it gets modified.
+    pub fn preserve(&mut self, live: &Tree<T>) {
+        let clone_tree = match self.clone_tree {
+            Some(clone_tree) => clone_tree,
+            None => return,
+        };
+
+        let mut copy = None;
+
+        for snapshot in self.snapshots.values_mut().filter(|s| s.tree.is_none()) {
+            let tree = copy.get_or_insert_with(|| Arc::new(clone_tree(live)));
+
+            snapshot.tree = Some(tree.clone());
+        }
+    }
+
+    /// Returns the root hash of the tree at the given `version`.
+    pub fn root_hash(&self, version: VersionId) -> Option<&Vec<u8>> {
+        self.snapshots.get(&version).map(|snapshot| &snapshot.root_hash)
+    }
+
+    /// Returns the tree at the given `version` along with its number of leaves,
+    /// `live` being the current root of the tree.
+    pub fn tree<'a>(
+        &'a self,
+        version: VersionId,
+        live: &'a Tree<T>,
+    ) -> Option<(&'a Tree<T>, usize)> {
+
+        self.snapshots.get(&version).map(|snapshot| match snapshot.tree {
+            Some(ref tree) => (&**tree, snapshot.count),
+            None => (live, snapshot.count),
+        })
+    }
+
+    /// Drops the checkpoints taken before the given `version`.
+    pub fn drop_before(&mut self, version: VersionId) {
+        self.snapshots = self.snapshots.split_off(&version);
+    }
+}
@@ -0,0 +1,69 @@
+
+use std::sync::RwLock;
+
+use digest::Algorithm;
+
+use hash::AlgorithmTag;
+use options::HashingScheme;
+
+/// A sequence of default hashes, along with the algorithm, hashing scheme and empty leaf
+/// it was computed from.
+type Entry = (AlgorithmTag, HashingScheme, Vec<u8>, Vec<Vec<u8>>);
+
+/// The sequences of default hashes computed so far.
+static CACHE: RwLock<Vec<Entry>> = RwLock::new(Vec::new());
+
+/// Returns the hashes of the subtrees of `0` to `depth` levels whose leaves are all `empty_leaf`,
+/// as found in trees of a fixed depth (see `MerkleTree::with_fixed_depth`):
+/// `d0 = empty_leaf`, `d1 = hash_nodes(d0, d0)`, and so on up to `d[depth]`.
+///
+/// The nodes are hashed with `HashUtils::hash_nodes`, as in trees built with the default
+/// `HashingScheme`. The sequence is computed once per algorithm and empty leaf, and cached.
+pub fn default_hashes(algorithm: &'static Algorithm, depth: usize, empty_leaf: &[u8]) -> Vec<Vec<u8>> {
+    default_hashes_with(algorithm, HashingScheme::default(), depth, empty_leaf)
+}
+
+/// Works like `default_hashes`, with the nodes hashed according to the given `scheme`.
+pub(crate) fn default_hashes_with(
+    algorithm: &'static Algorithm,
+    scheme: HashingScheme,
+    depth: usize,
+    empty_leaf: &[u8],
+) -> Vec<Vec<u8>> {
+
+    let tag = AlgorithmTag::of(algorithm);
+    let matches = |entry: &Entry| entry.0 == tag && entry.1 == scheme && entry.2 == empty_leaf;
+
+    {
+        let cache = CACHE.read().unwrap_or_else(|err| err.into_inner());
+
+        if let Some(entry) = cache.iter().find(|entry| matches(entry)) {
+            if entry.3.len() > depth {
+                return entry.3[..depth + 1].to_vec();
+            }
+        }
+    }
+
+    let mut cache = CACHE.write().unwrap_or_else(|err| err.into_inner());
+
+    let position = match cache.iter().position(matches) {
+        Some(position) => position,
+        None => {
+            cache.push((tag, scheme, empty_leaf.to_vec(), vec![empty_leaf.to_vec()]));
+            cache.len() - 1
+        }
+    };
+
+    let hashes = &mut cache[position].3;
+
+    while hashes.len() <= depth {
+        let next = {
+            let last = &hashes[hashes.len() - 1];
+            scheme.hash_nodes(algorithm, last, last).as_ref().to_vec()
+        };
+
+        hashes.push(next);
+    }
+
+    hashes[..depth + 1].to_vec()
+}
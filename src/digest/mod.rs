@@ -0,0 +1,27 @@
+//! The hashing algorithms trees are built with, with the API of `ring::digest`.
+//!
+//! They are those of *ring* by default. With the `backend-rustcrypto` feature, they are
+//! implemented with the pure Rust `sha1` and `sha2` crates instead, so that the crate can be
+//! built without *ring*, with `default-features = false`, though it still needs `std`.
+//! The backend is chosen at compile time, the RustCrypto one taking precedence if both features
+//! are enabled. Either way, trees and proofs have the same hashes, and hence the same roots,
+//! byte for byte.
+//!
+//! The algorithms of this module are the ones to build trees with: with the RustCrypto backend,
+//! those of `ring::digest` are not accepted.
+//!
+//! `Algorithm`, `Context` and `Digest` are the only types of the backend in the public API,
+//! and should be named through this module, or the `prelude`, rather than through *ring*:
+//! only then does code keep building whichever backend is chosen.
+
+#[cfg(not(any(feature = "backend-ring", feature = "backend-rustcrypto")))]
+compile_error!("either the `backend-ring` or the `backend-rustcrypto` feature must be enabled");
+
+#[cfg(not(feature = "backend-rustcrypto"))]
+pub use ring::digest::{Algorithm, Context, Digest, digest, SHA1, SHA256, SHA384, SHA512, SHA512_256};
+
+#[cfg(feature = "backend-rustcrypto")]
+mod rustcrypto;
+
+#[cfg(feature = "backend-rustcrypto")]
+pub use self::rustcrypto::{Algorithm, Context, Digest, digest, SHA1, SHA256, SHA384, SHA512, SHA512_256};
@@ -0,0 +1,200 @@
+use std::fmt;
+
+use sha1::Sha1;
+use sha2::{Digest as _, Sha256, Sha384, Sha512, Sha512_256};
+
+/// The length of the longest output, the one of SHA-512.
+const MAX_OUTPUT_LEN: usize = 64;
+
+/// A hashing algorithm, as `ring::digest::Algorithm`.
+///
+/// Algorithms are told apart by their address, hence only used through the statics of this module.
+#[allow(missing_copy_implementations)]
+pub struct Algorithm {
+    /// The length of a finalized digest
+    pub output_len: usize,
+
+    /// The length of the internal state of the algorithm
+    pub chaining_len: usize,
+
+    /// The length of the blocks the input is split into
+    pub block_len: usize,
+
+    id: Id,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Id {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha512_256,
+}
+
+/// Shows the name of the algorithm, as *ring* does.
+impl fmt::Debug for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.id {
+            Id::Sha1 => "SHA1",
+            Id::Sha256 => "SHA256",
+            Id::Sha384 => "SHA384",
+            Id::Sha512 => "SHA512",
+            Id::Sha512_256 => "SHA512_256",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// SHA-1, which is only meant to read legacy trees.
+pub static SHA1: Algorithm = Algorithm {
+    output_len: 20,
+    chaining_len: 20,
+    block_len: 64,
+    id: Id::Sha1,
+};
+
+/// SHA-256.
+pub static SHA256: Algorithm = Algorithm {
+    output_len: 32,
+    chaining_len: 32,
+    block_len: 64,
+    id: Id::Sha256,
+};
+
+/// SHA-384.
+pub static SHA384: Algorithm = Algorithm {
+    output_len: 48,
+    chaining_len: 64,
+    block_len: 128,
+    id: Id::Sha384,
+};
+
+/// SHA-512.
+pub static SHA512: Algorithm = Algorithm {
+    output_len: 64,
+    chaining_len: 64,
+    block_len: 128,
+    id: Id::Sha512,
+};
+
+/// SHA-512/256.
+pub static SHA512_256: Algorithm = Algorithm {
+    output_len: 32,
+    chaining_len: 64,
+    block_len: 128,
+    id: Id::Sha512_256,
+};
+
+#[derive(Clone)]
+enum State {
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+    Sha512_256(Sha512_256),
+}
+
+/// A context for computing a digest in several steps, as `ring::digest::Context`.
+#[derive(Clone)]
+pub struct Context {
+    algorithm: &'static Algorithm,
+    state: State,
+}
+
+impl Context {
+    /// Starts a digest with the given algorithm.
+    pub fn new(algorithm: &'static Algorithm) -> Self {
+        let state = match algorithm.id {
+            Id::Sha1 => State::Sha1(Sha1::new()),
+            Id::Sha256 => State::Sha256(Sha256::new()),
+            Id::Sha384 => State::Sha384(Sha384::new()),
+            Id::Sha512 => State::Sha512(Sha512::new()),
+            Id::Sha512_256 => State::Sha512_256(Sha512_256::new()),
+        };
+
+        Context {
+            algorithm: algorithm,
+            state: state,
+        }
+    }
+
+    /// Feeds the given bytes to the digest.
+    pub fn update(&mut self, data: &[u8]) {
+        match self.state {
+            State::Sha1(ref mut state) => state.update(data),
+            State::Sha256(ref mut state) => state.update(data),
+            State::Sha384(ref mut state) => state.update(data),
+            State::Sha512(ref mut state) => state.update(data),
+            State::Sha512_256(ref mut state) => state.update(data),
+        }
+    }
+
+    /// Finalizes the digest.
+    pub fn finish(self) -> Digest {
+        let mut value = [0; MAX_OUTPUT_LEN];
+
+        {
+            let mut fill = |output: &[u8]| value[..output.len()].copy_from_slice(output);
+
+            match self.state {
+                State::Sha1(state) => fill(&state.finalize()),
+                State::Sha256(state) => fill(&state.finalize()),
+                State::Sha384(state) => fill(&state.finalize()),
+                State::Sha512(state) => fill(&state.finalize()),
+                State::Sha512_256(state) => fill(&state.finalize()),
+            }
+        }
+
+        Digest {
+            value: value,
+            algorithm: self.algorithm,
+        }
+    }
+
+    /// Returns the algorithm of the digest.
+    pub fn algorithm(&self) -> &'static Algorithm {
+        self.algorithm
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Context").field("algorithm", self.algorithm).finish_non_exhaustive()
+    }
+}
+
+/// A finalized digest, as `ring::digest::Digest`.
+#[derive(Clone, Copy)]
+pub struct Digest {
+    value: [u8; MAX_OUTPUT_LEN],
+    algorithm: &'static Algorithm,
+}
+
+impl Digest {
+    /// Returns the algorithm of the digest.
+    pub fn algorithm(&self) -> &'static Algorithm {
+        self.algorithm
+    }
+}
+
+impl AsRef<[u8]> for Digest {
+    fn as_ref(&self) -> &[u8] {
+        &self.value[..self.algorithm.output_len]
+    }
+}
+
+/// Shows the algorithm and the bytes of the digest.
+impl fmt::Debug for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}:{:?}", self.algorithm, self.as_ref())
+    }
+}
+
+/// Computes the digest of `data` with the given algorithm in one step.
+pub fn digest(algorithm: &'static Algorithm, data: &[u8]) -> Digest {
+    let mut context = Context::new(algorithm);
+    context.update(data);
+    context.finish()
+}
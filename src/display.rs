@@ -0,0 +1,300 @@
+
+use std::fmt::{self, Write};
+
+use digest::Algorithm;
+
+use hex::to_hex;
+use proof::{Proof, Positioned, Direction};
+use tree::{Tree, Subtree, View};
+use registry::algorithm_name;
+
+/// The number of levels of a tree printed by its `Debug` implementation.
+const DEBUG_DEPTH: usize = 3;
+
+/// The maximum length of the `Debug` output of a value of a tree, beyond which it is elided.
+const DEBUG_VALUE_LEN: usize = 32;
+
+/// How a `Proof` is rendered by `Proof::display`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DisplayOptions {
+    /// The number of hex digits kept of each hash, the middle ones being elided,
+    /// or `None` to print the hashes in full. Defaults to `Some(6)`.
+    pub hash_digits: Option<usize>,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        DisplayOptions { hash_digits: Some(6) }
+    }
+}
+
+/// A human-readable rendering of a `Proof`, returned by `Proof::display`
+/// and `Proof::display_with`.
+///
+/// It starts with the value, the hashing algorithm, the root hash and the depth of the leaf,
+/// followed by one line per level, from the root down to the leaf. For instance, for the proof
+/// of `"b"` in the SHA-256 tree over `["a", "b", "c"]`:
+///
+/// ```text
+/// proof of "b" (SHA-256)
+/// root: 3664…c1
+/// depth: 2
+/// level 2: node=3664…c1
+/// level 1: sibling=R 597f…d8, node=b137…eb
+/// level 0: sibling=L 022a…3c, node=57eb…31
+/// ```
+///
+/// The sibling of a node is tagged with `L` if it is on its left, and with `R` otherwise.
+pub struct ProofDisplay<'a, T: 'a, F> {
+    proof: &'a Proof<T>,
+    options: DisplayOptions,
+    display_value: F,
+}
+
+impl<'a, T, F> fmt::Debug for ProofDisplay<'a, T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProofDisplay")
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl<T> Proof<T> {
+    /// Renders this proof for humans, with the given `options`. See `ProofDisplay`.
+    pub fn display(
+        &self,
+        options: DisplayOptions,
+    ) -> ProofDisplay<T, fn(&T, &mut fmt::Formatter) -> fmt::Result>
+    where
+        T: fmt::Display,
+    {
+
+        let display_value: fn(&T, &mut fmt::Formatter) -> fmt::Result =
+            |value, f| write!(f, "{:?}", value.to_string());
+
+        self.display_with(options, display_value)
+    }
+
+    /// Renders this proof for humans, with the given `options`, writing the value
+    /// with `display_value`. See `ProofDisplay`.
+    pub fn display_with<F>(&self, options: DisplayOptions, display_value: F) -> ProofDisplay<T, F>
+    where
+        F: Fn(&T, &mut fmt::Formatter) -> fmt::Result,
+    {
+
+        ProofDisplay {
+            proof: self,
+            options: options,
+            display_value: display_value,
+        }
+    }
+}
+
+impl<'a, T, F> fmt::Display for ProofDisplay<'a, T, F>
+where
+    F: Fn(&T, &mut fmt::Formatter) -> fmt::Result,
+{
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let proof = self.proof;
+        let depth = proof.depth();
+
+        write!(f, "proof of ")?;
+        (self.display_value)(&proof.value, f)?;
+        writeln!(f, " ({})", algorithm_name(proof.algorithm))?;
+        writeln!(f, "root: {}", self.hash(&proof.root_hash))?;
+        write!(f, "depth: {}", depth)?;
+
+        let mut sibling: Option<&Positioned<Vec<u8>>> = None;
+
+        for (i, lemma) in proof.lemmas().enumerate() {
+            write!(f, "\nlevel {}: ", depth - i)?;
+
+            if let Some(sibling) = sibling {
+                let side = match sibling.direction() {
+                    Direction::Left => 'L',
+                    Direction::Right => 'R',
+                };
+
+                write!(f, "sibling={} {}, ", side, self.hash(sibling.as_ref().into_inner()))?;
+            }
+
+            write!(f, "node={}", self.hash(&lemma.node_hash))?;
+            sibling = lemma.sibling_hash.as_ref();
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, T, F> ProofDisplay<'a, T, F> {
+    /// Returns the hex encoding of `hash`, truncated as per the options.
+    fn hash(&self, hash: &[u8]) -> String {
+        short_hex(hash, self.options.hash_digits)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Proof<T> {
+    /// Renders this proof with the default `DisplayOptions`, see `Proof::display`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.display(DisplayOptions::default()).fmt(f)
+    }
+}
+
+/// Returns the hex encoding of `hash`, keeping only the given number of digits, if any.
+pub(crate) fn short_hex(hash: &[u8], digits: Option<usize>) -> String {
+    let hex = to_hex(hash);
+
+    match digits {
+        Some(digits) if digits < hex.len() => {
+            let tail = digits / 3;
+            format!("{}…{}", &hex[..digits - tail], &hex[hex.len() - tail..])
+        }
+
+        _ => hex,
+    }
+}
+
+/// The `Debug` output of a `MerkleTree`, in full, returned by `MerkleTree::debug_full`.
+///
+/// Unlike the `Debug` output of the tree itself, which only shows its first levels, every node
+/// is printed, along with every value, so that it grows with the size of the tree.
+pub struct FullDebug<'a, T: 'a> {
+    algorithm: &'static Algorithm,
+    count: usize,
+    height: usize,
+    tree: &'a Tree<T>,
+}
+
+impl<'a, T> FullDebug<'a, T> {
+    /// Prints the given `tree` in full, which has `count` leaves and the given `height`.
+    pub(crate) fn new(algorithm: &'static Algorithm, count: usize, height: usize, tree: &'a Tree<T>) -> Self {
+        FullDebug {
+            algorithm: algorithm,
+            count: count,
+            height: height,
+            tree: tree,
+        }
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for FullDebug<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MerkleTree")
+            .field("algorithm", &Raw(algorithm_name(self.algorithm)))
+            .field("count", &self.count)
+            .field("height", &self.height)
+            .field("root_hash", &Hash(self.tree.hash(), None))
+            .field("tree", &TreeDebug(self.tree.root(), None))
+            .finish()
+    }
+}
+
+/// Prints the given tree, down to the given number of levels, if any.
+pub(crate) struct TreeDebug<'a, T: 'a>(pub Subtree<'a, T>, pub Option<usize>);
+
+impl<'a, T: fmt::Debug> fmt::Debug for TreeDebug<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let TreeDebug(tree, levels) = *self;
+        let full = levels.is_none();
+        let digits = if full { None } else { Some(8) };
+
+        match tree.view() {
+            View::Empty { hash } => f.debug_tuple("Empty").field(&Hash(hash, digits)).finish(),
+
+            View::Pruned { hash, leaves } => {
+                f.debug_tuple("Pruned")
+                    .field(&Hash(hash, digits))
+                    .field(&leaves)
+                    .finish()
+            }
+
+            View::Leaf { hash, value } => {
+                f.debug_tuple("Leaf")
+                    .field(&Hash(hash, digits))
+                    .field(&Value(value, full))
+                    .finish()
+            }
+
+            View::Node { hash, left, right } => {
+                let mut node = f.debug_tuple("Node");
+                node.field(&Hash(hash, digits));
+
+                match levels {
+                    Some(0) => node.field(&Raw("…")),
+                    _ => {
+                        let levels = levels.map(|levels| levels - 1);
+                        node.field(&TreeDebug(left, levels)).field(&TreeDebug(right, levels))
+                    }
+                };
+
+                node.finish()
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Tree<T> {
+    /// Prints the first levels of this tree, along with its small values.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        TreeDebug(self.root(), Some(DEBUG_DEPTH)).fmt(f)
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for Subtree<'a, T> {
+    /// Prints the first levels of this subtree, as for a `Tree`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        TreeDebug(*self, Some(DEBUG_DEPTH)).fmt(f)
+    }
+}
+
+/// Prints a hash in hex, keeping only the given number of digits, if any.
+pub(crate) struct Hash<'a>(pub &'a [u8], pub Option<usize>);
+
+impl<'a> fmt::Debug for Hash<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&short_hex(self.0, self.1))
+    }
+}
+
+/// Prints a string as is.
+pub(crate) struct Raw(pub &'static str);
+
+impl fmt::Debug for Raw {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Prints a value, unless its `Debug` output is longer than `DEBUG_VALUE_LEN` and not `full`.
+struct Value<'a, T: 'a>(&'a T, bool);
+
+impl<'a, T: fmt::Debug> fmt::Debug for Value<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.1 {
+            return self.0.fmt(f);
+        }
+
+        let mut short = Bounded(String::new());
+
+        match write!(short, "{:?}", self.0) {
+            Ok(()) => f.write_str(&short.0),
+            Err(_) => f.write_str("…"),
+        }
+    }
+}
+
+/// A string which fails to grow beyond `DEBUG_VALUE_LEN` bytes, so that long values
+/// are not formatted in full only to be elided.
+struct Bounded(String);
+
+impl Write for Bounded {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.0.len() + s.len() > DEBUG_VALUE_LEN {
+            return Err(fmt::Error);
+        }
+
+        self.0.push_str(s);
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+
+use tree::Subtree;
+
+/// Finds the index of the first leaf of `tree` where it differs from a remote tree
+/// whose node hashes are returned by `oracle`. See `MerkleTree::find_first_divergence`.
+///
+/// `tree` has `count` leaves and the given `height`, and `padded` tells whether its odd nodes
+/// were paired with an `Empty` node, see `Subtree::node_at_level`.
+pub fn first_divergence<T, F, E>(
+    tree: Subtree<T>,
+    count: usize,
+    height: usize,
+    padded: bool,
+    mut oracle: F,
+) -> Result<Option<usize>, E>
+where
+    F: FnMut(usize, usize) -> Result<Option<Vec<u8>>, E>,
+{
+
+    let local = |level, index| tree.node_at_level(level, index, count, height, padded).map(|node| node.hash().as_slice());
+
+    let root = oracle(height, 0)?;
+
+    // The remote tree has more levels if it has a node next to the local root
+    if root.as_deref() == local(height, 0) {
+        return oracle(height, 1).map(|next| next.map(|_| count));
+    }
+
+    let mut level = height;
+    let mut index = 0;
+
+    // Descend towards the leftmost node which differs, knowing that the current one does
+    while level > 0 {
+        level -= 1;
+        index <<= 1;
+
+        if oracle(level, index)?.as_deref() == local(level, index) {
+            index |= 1;
+        }
+    }
+
+    Ok(Some(index))
+}
@@ -0,0 +1,208 @@
+use digest::Algorithm;
+
+use error::Error;
+use hash::AlgorithmTag;
+use hashutils::Hashable;
+use merkletree::MerkleTree;
+use proof::{Lemma, Proof};
+use tree::{LeavesIterator, Leaf};
+
+/// A tree committed to with two hashing algorithms at once, such as during a migration from
+/// one to the other, whose root hashes are those of `MerkleTree::from_vec` over its values
+/// with either algorithm.
+///
+/// The values are only held once, by the tree of the primary algorithm, the other one only
+/// holding the hashes of the secondary algorithm. Every modification hashes the values with
+/// both algorithms before changing either tree, so that they are always over the same values.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use merkle::{MerkleTree, DualAlgorithmTree, SHA256, SHA512_256};
+/// # fn main() {
+/// let mut tree = DualAlgorithmTree::from_vec(SHA256, SHA512_256, vec!["a", "b"]);
+/// tree.push("c");
+///
+/// let sha512_256 = MerkleTree::from_vec(SHA512_256, vec!["a", "b", "c"]);
+/// assert_eq!(tree.root_hash_for(SHA512_256), Some(sha512_256.root_hash()));
+///
+/// let proof = tree.gen_proof_for(SHA512_256, 2).unwrap();
+/// assert!(proof.validate(sha512_256.root_hash()));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DualAlgorithmTree<T> {
+    /// The tree of the primary algorithm, which holds the values
+    primary: MerkleTree<T>,
+
+    /// The tree of the secondary algorithm, over the hashes of the same values
+    secondary: MerkleTree<()>,
+}
+
+impl<T> DualAlgorithmTree<T> {
+    /// Creates an empty tree, hashed with both the `primary` and the `secondary` algorithm.
+    ///
+    /// Panics if both are the same algorithm.
+    pub fn new(primary: &'static Algorithm, secondary: &'static Algorithm) -> Self {
+        assert!(AlgorithmTag::of(primary) != AlgorithmTag::of(secondary), "both algorithms are the same");
+
+        DualAlgorithmTree {
+            primary: MerkleTree::empty(primary),
+            secondary: MerkleTree::empty(secondary),
+        }
+    }
+
+    /// Creates the tree over the given values, hashed with both the `primary` and the `secondary`
+    /// algorithm.
+    ///
+    /// Panics if both are the same algorithm.
+    pub fn from_vec(primary: &'static Algorithm, secondary: &'static Algorithm, values: Vec<T>) -> Self
+    where
+        T: Hashable,
+    {
+
+        let mut tree = DualAlgorithmTree::new(primary, secondary);
+        tree.extend(values);
+        tree
+    }
+
+    /// Returns the primary and the secondary algorithm of the tree.
+    pub fn algorithms(&self) -> (&'static Algorithm, &'static Algorithm) {
+        (self.primary.algorithm, self.secondary.algorithm)
+    }
+
+    /// Returns the tree of the primary algorithm, which holds the values.
+    pub fn primary(&self) -> &MerkleTree<T> {
+        &self.primary
+    }
+
+    /// Returns the number of leaves of the tree.
+    pub fn count(&self) -> usize {
+        self.primary.count()
+    }
+
+    /// Returns whether the tree has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.primary.is_empty()
+    }
+
+    /// Creates an `Iterator` over the values of the tree.
+    pub fn iter(&self) -> LeavesIterator<T> {
+        self.primary.iter()
+    }
+
+    /// Returns the root hash of the tree with the given `algorithm`, or `None` if it is
+    /// neither of the algorithms of the tree.
+    pub fn root_hash_for(&self, algorithm: &'static Algorithm) -> Option<&Vec<u8>> {
+        match self.which(algorithm)? {
+            Which::Primary => Some(self.primary.root_hash()),
+            Which::Secondary => Some(self.secondary.root_hash()),
+        }
+    }
+
+    /// Generates the proof of the `index`-th value against the root hash of the tree with
+    /// the given `algorithm`, which is the `algorithm` of the proof, as with
+    /// `MerkleTree::gen_nth_proof`. Returns `None` if the tree has at most `index` leaves,
+    /// or if `algorithm` is neither of the algorithms of the tree.
+    pub fn gen_proof_for(&self, algorithm: &'static Algorithm, index: usize) -> Option<Proof<T>>
+    where
+        T: Clone,
+    {
+
+        match self.which(algorithm)? {
+            Which::Primary => self.primary.gen_nth_proof(index),
+            Which::Secondary => {
+                let count = self.count();
+                let value = self.primary.tree().root().nth_value(index, count)?.clone();
+                let (lemma, _) = Lemma::new_by_index(self.secondary.tree().root(), index, count)?;
+
+                Some(self.secondary.new_proof(self.secondary.root_hash().clone(), lemma, value, count))
+            }
+        }
+    }
+
+    /// Appends the given value, rehashing both trees.
+    pub fn push(&mut self, value: T)
+    where
+        T: Hashable,
+    {
+
+        self.extend(Some(value));
+    }
+
+    /// Appends the given values, rehashing the internal nodes of both trees only once.
+    pub fn extend<I>(&mut self, values: I)
+    where
+        T: Hashable,
+        I: IntoIterator<Item = T>,
+    {
+
+        let values = values.into_iter().collect::<Vec<_>>();
+        let hashes = self.secondary_leaves(&values);
+
+        self.primary.try_extend(values).expect("the tree has no limit and no pruned subtrees");
+        self.secondary.try_extend_leaves(hashes).expect("the tree has no limit and no pruned subtrees");
+    }
+
+    /// Replaces the value of the leaf at the given `index`, returning the previous value,
+    /// as with `MerkleTree::update`.
+    ///
+    /// Fails with `Error::LeafIndexOutOfRange`, leaving the tree untouched, if the tree has
+    /// at most `index` leaves.
+    pub fn update(&mut self, index: usize, value: T) -> Result<T, Error>
+    where
+        T: Hashable,
+    {
+
+        self.primary.check_updates(Some(index))?;
+
+        let leaf = self.secondary_leaves(::std::slice::from_ref(&value)).remove(0);
+        let previous = self.primary.update(index, value)?;
+        self.secondary.replace_leaves(vec![(index, leaf)]);
+
+        Ok(previous)
+    }
+
+    /// Removes the last value of the tree and returns it, or `None` if the tree is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        let value = self.primary.pop()?;
+        self.secondary.pop();
+
+        Some(value)
+    }
+
+    /// Returns which of the trees is the one of the given `algorithm`, if any.
+    fn which(&self, algorithm: &'static Algorithm) -> Option<Which> {
+        let tag = AlgorithmTag::of(algorithm);
+
+        if tag == AlgorithmTag::of(self.primary.algorithm) {
+            Some(Which::Primary)
+        } else if tag == AlgorithmTag::of(self.secondary.algorithm) {
+            Some(Which::Secondary)
+        } else {
+            None
+        }
+    }
+
+    /// Hashes the given values with the secondary algorithm, as leaves of the secondary tree.
+    fn secondary_leaves(&self, values: &[T]) -> Vec<Leaf<()>>
+    where
+        T: Hashable,
+    {
+
+        let scheme = self.secondary.hashing_scheme();
+
+        values
+            .iter()
+            .map(|value| Leaf::new(scheme.hash_leaf(self.secondary.algorithm, value), ()))
+            .collect()
+    }
+}
+
+/// One of the trees of a `DualAlgorithmTree`.
+#[derive(Clone, Copy)]
+enum Which {
+    Primary,
+    Secondary,
+}
@@ -0,0 +1,130 @@
+#![deny(clippy::indexing_slicing, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::cmp;
+
+use digest::Algorithm;
+
+use serde::{Deserialize, Deserializer};
+use serde::de::{self, DeserializeOwned};
+use serde_json::{self, Map, Value};
+
+use error::{Error, ConversionError};
+use proof::{Proof, ProofData};
+
+/// The latest version of the `ProofEnvelope` format supported by this crate.
+pub const ENVELOPE_VERSION: u16 = 1;
+
+/// A `ProofData` along with the version of the format it was written with, and extensions,
+/// so that fields can be added to serialized proofs without being lost or misread by
+/// readers and writers which do not know of them.
+///
+/// It is serialized as
+///
+/// ```json
+/// { "version": 1, "proof": { "algorithm": "SHA-256", "root_hash": ..., ... }, "extensions": { ... } }
+/// ```
+///
+/// where the `extensions` are skipped if there are none. It is deserialized from either this
+/// shape, or a bare `ProofData` as written before envelopes were introduced, which is read
+/// as version `0` without extensions. Extensions are kept as they were read, whether or not
+/// they are known to this crate, so that they are written back unchanged.
+///
+/// An envelope of a version newer than `ENVELOPE_VERSION` fails to deserialize,
+/// see `ProofEnvelope::from_json`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ProofEnvelope<T> {
+    /// The version of the format, `0` for a bare `ProofData`
+    pub version: u16,
+
+    /// The proof itself
+    pub proof: ProofData<T>,
+
+    /// The extension fields, by name
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub extensions: Map<String, Value>,
+}
+
+impl<T> ProofEnvelope<T> {
+    /// Reads an envelope, or a bare `ProofData`, from the given JSON `value`.
+    ///
+    /// Fails with `Error::UnsupportedVersion` if the envelope was written with a newer version
+    /// of the format than `ENVELOPE_VERSION`, and with `Error::Decode` if it is malformed.
+    pub fn from_json(value: Value) -> Result<Self, Error>
+    where
+        T: DeserializeOwned,
+    {
+
+        let mut fields = match value {
+            Value::Object(fields) => fields,
+            _ => return Err(Error::Decode("proof envelope is not an object".to_string())),
+        };
+
+        let version = match fields.remove("version") {
+            Some(version) => version,
+            None => return Ok(decode_proof(Value::Object(fields))?.into_envelope_version(0)),
+        };
+
+        let version = version
+            .as_u64()
+            .ok_or_else(|| Error::Decode("version is not an integer".to_string()))?;
+
+        if version > u64::from(ENVELOPE_VERSION) {
+            return Err(Error::UnsupportedVersion {
+                found: cmp::min(version, u64::from(u32::MAX)) as u32,
+                max_supported: u32::from(ENVELOPE_VERSION),
+            });
+        }
+
+        let proof = fields.remove("proof").ok_or(Error::MissingField("proof"))?;
+
+        let extensions = match fields.remove("extensions") {
+            Some(Value::Object(extensions)) => extensions,
+            Some(_) => return Err(Error::Decode("extensions are not an object".to_string())),
+            None => Map::new(),
+        };
+
+        Ok(ProofEnvelope {
+            version: version as u16,
+            proof: decode_proof(proof)?,
+            extensions: extensions,
+        })
+    }
+
+    /// Constructs a `Proof` from the data of this envelope, using the given hashing `algorithm`,
+    /// as `ProofData::into_proof` does.
+    pub fn into_proof(self, algorithm: &'static Algorithm) -> Result<Proof<T>, ConversionError> {
+        self.proof.into_proof(algorithm)
+    }
+
+    /// Works like `ProofEnvelope::into_proof`, without any check,
+    /// as `ProofData::into_proof_unchecked` does.
+    pub fn into_proof_unchecked(self, algorithm: &'static Algorithm) -> Proof<T> {
+        self.proof.into_proof_unchecked(algorithm)
+    }
+}
+
+impl<T> ProofData<T> {
+    /// Wraps this data in a `ProofEnvelope` of the latest version, without extensions.
+    pub fn into_envelope(self) -> ProofEnvelope<T> {
+        self.into_envelope_version(ENVELOPE_VERSION)
+    }
+
+    fn into_envelope_version(self, version: u16) -> ProofEnvelope<T> {
+        ProofEnvelope {
+            version: version,
+            proof: self,
+            extensions: Map::new(),
+        }
+    }
+}
+
+fn decode_proof<T: DeserializeOwned>(value: Value) -> Result<ProofData<T>, Error> {
+    serde_json::from_value(value).map_err(|err| Error::Decode(err.to_string()))
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for ProofEnvelope<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        ProofEnvelope::from_json(value).map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,414 @@
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+
+use budget::BudgetUsage;
+use hash::AlgorithmTag;
+use hex::to_hex;
+use options::HashingScheme;
+
+/// The errors which can occur when decoding or checking proofs and trees.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(missing_copy_implementations)]
+pub enum Error {
+    /// The input ended before the structure being decoded was complete
+    TruncatedInput,
+
+    /// The input continued after the structure being decoded was complete
+    TrailingBytes,
+
+    /// The input does not start with the expected magic bytes
+    InvalidMagic,
+
+    /// The input was encoded with a newer version of the format than this crate supports
+    UnsupportedVersion {
+        /// The version found in the input
+        found: u32,
+
+        /// The latest version supported by this crate
+        max_supported: u32,
+    },
+
+    /// The algorithm tag does not correspond to any known hashing algorithm
+    UnknownAlgorithm(u8),
+
+    /// The algorithm name is neither a known one nor a registered one, see `algorithm_from_name`
+    UnknownAlgorithmName(String),
+
+    /// The input was produced with another hashing algorithm than the expected one
+    AlgorithmMismatch,
+
+    /// A hash does not have the length of the output of the hashing algorithm
+    HashLengthMismatch {
+        /// The output length of the hashing algorithm
+        expected: usize,
+
+        /// The length of the offending hash
+        found: usize,
+    },
+
+    /// A direction byte is neither `0` (left) nor `1` (right)
+    InvalidDirection(u8),
+
+    /// A field required to decode the structure is absent from the input
+    MissingField(&'static str),
+
+    /// The input is not a valid encoding, for the given reason
+    Decode(String),
+
+    /// A hash stored in a tree does not match the one computed from its value or children.
+    ///
+    /// The node lies `depth` levels below the root, and is reached by following
+    /// the `depth` lowest bits of `index`, from the most significant one,
+    /// where `0` means left and `1` means right.
+    HashMismatch {
+        /// The depth of the node, `0` being the root
+        depth: usize,
+
+        /// The path from the root to the node
+        index: usize,
+    },
+
+    /// A tree does not have the shape of a tree built by this crate, for the given reason
+    MalformedTree(&'static str),
+
+    /// A tree would have more leaves than the limit set with `MerkleTreeBuilder::max_leaves`,
+    /// or than its slots if it has a fixed depth, see `MerkleTreeBuilder::fixed_depth`
+    TooManyLeaves {
+        /// The maximum number of leaves
+        max: usize,
+    },
+
+    /// The leaf at the given index is not part of a `PartialTree`
+    LeafNotInWitness(usize),
+
+    /// A proof does not hold for the root hash it was checked against
+    InvalidProof,
+
+    /// An audit path is not the path to a leaf of a tree of the given size, for the given reason,
+    /// see `Proof::from_indexed_path`
+    InvalidPath(&'static str),
+
+    /// A chunk cannot be stitched with the others into a single tree, see `MerkleTree::from_chunks`
+    InvalidChunk {
+        /// The position of the chunk among the others
+        index: usize,
+
+        /// Why the chunk does not fit
+        reason: &'static str,
+    },
+
+    /// The leaves needed by an operation are part of a subtree pruned by `MerkleTree::prune_below`
+    Pruned {
+        /// The root hash of the pruned subtree
+        subtree_root: Vec<u8>,
+    },
+
+    /// A subtree cannot replace a pruned one, see `MerkleTree::graft`, for the given reason
+    InvalidGraft(&'static str),
+
+    /// A proof does not follow a path of a tree, see `MerkleTree::verify_proof`
+    ProofMismatch {
+        /// The depth of the first node of the path which differs, `0` being the root
+        depth: usize,
+
+        /// How the node differs
+        reason: &'static str,
+    },
+
+    /// There is no leaf at the given index of a tree of `count` leaves
+    LeafIndexOutOfRange {
+        /// The offending index
+        index: usize,
+
+        /// The number of leaves of the tree
+        count: usize,
+    },
+
+    /// The leaves of a tree cannot be updated in place, for the given reason,
+    /// see `MerkleTree::apply_updates`
+    InvalidUpdate(&'static str),
+
+    /// A tree snapshot is truncated or corrupt, see `MerkleTree::read_snapshot`
+    InvalidSnapshot {
+        /// The position in the snapshot of the field which could not be read
+        offset: u64,
+
+        /// Why the field could not be read
+        cause: Box<Error>,
+    },
+
+    /// A proof does not meet a `ValidationPolicy`, for the given reason
+    PolicyViolation(&'static str),
+
+    /// A value could not be hashed into a leaf, see `TryHashable`
+    LeafHash {
+        /// The index of the leaf the value was to be hashed into
+        index: usize,
+
+        /// Why the value could not be hashed
+        cause: HashError,
+    },
+
+    /// A range of leaves is not the range of a node of the tree, see `MerkleTree::subtree`
+    UnalignedRange {
+        /// The smallest range of a node of the tree which holds the requested one
+        nearest_aligned: Range<usize>,
+    },
+
+    /// A proof is hashed with another scheme than the one it is checked with,
+    /// see `ValidationPolicy::required_scheme`
+    SchemeMismatch {
+        /// The scheme the proof is hashed with
+        proof: HashingScheme,
+
+        /// The scheme the proof is checked with
+        verifier: HashingScheme,
+    },
+
+    /// Indexed values do not give exactly one value per leaf,
+    /// see `MerkleTree::from_indexed_unordered`
+    InvalidIndices {
+        /// The indices without a value, in order
+        missing: Vec<usize>,
+
+        /// The indices given more than one value, in order
+        duplicates: Vec<usize>,
+    },
+
+    /// A leaf index or a number of leaves does not fit in a `usize` on this target,
+    /// such as one of more than 32 bits on a 32-bit target
+    IndexOverflow {
+        /// The offending index or number of leaves
+        index: u64,
+    },
+
+    /// The root hash of a tree head is not the one pinned by a `TrustPolicy`
+    UntrustedRoot,
+
+    /// The signature of a tree head does not verify with the key of a `TrustPolicy`
+    InvalidSignature,
+
+    /// The value of a leaf could not be read to be hashed, see `MerkleTree::push_streaming`
+    LeafIo {
+        /// The index of the leaf the value was to be hashed into
+        index: usize,
+
+        /// Why the value could not be read
+        source: IoError,
+    },
+
+    /// Validating a proof takes more than what remains of a `ValidationBudget`,
+    /// see `Proof::validate_bounded`
+    BudgetExceeded {
+        /// What validating the proof takes
+        needed: BudgetUsage,
+
+        /// What remained of the budget
+        remaining: BudgetUsage,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::TruncatedInput => write!(f, "input is truncated"),
+            Error::TrailingBytes => write!(f, "input has trailing bytes"),
+            Error::InvalidMagic => write!(f, "input does not start with the expected magic bytes"),
+            Error::UnsupportedVersion {
+                found,
+                max_supported,
+            } => {
+                write!(
+                    f,
+                    "unsupported format version {} (latest supported is {})",
+                    found,
+                    max_supported
+                )
+            }
+            Error::UnknownAlgorithm(tag) => write!(f, "unknown algorithm tag {}", tag),
+            Error::UnknownAlgorithmName(ref name) => write!(f, "unknown algorithm `{}`", name),
+            Error::AlgorithmMismatch => write!(f, "input was produced with another algorithm"),
+            Error::HashLengthMismatch { expected, found } => {
+                write!(f, "hash has length {}, expected {}", found, expected)
+            }
+            Error::InvalidDirection(byte) => write!(f, "invalid direction byte {}", byte),
+            Error::MissingField(field) => write!(f, "missing field `{}`", field),
+            Error::Decode(ref reason) => write!(f, "malformed input: {}", reason),
+            Error::HashMismatch { depth, index } => {
+                write!(f, "hash mismatch at depth {}, index {}", depth, index)
+            }
+            Error::MalformedTree(reason) => write!(f, "malformed tree: {}", reason),
+            Error::TooManyLeaves { max } => write!(f, "tree would have more than {} leaves", max),
+            Error::LeafNotInWitness(index) => write!(f, "leaf {} is not in the witness", index),
+            Error::InvalidProof => write!(f, "proof does not hold for the root hash"),
+            Error::InvalidPath(reason) => write!(f, "invalid audit path: {}", reason),
+            Error::InvalidChunk { index, reason } => write!(f, "invalid chunk {}: {}", index, reason),
+            Error::Pruned { ref subtree_root } => {
+                write!(f, "the leaves are in the pruned subtree {}", to_hex(subtree_root))
+            }
+            Error::InvalidGraft(reason) => write!(f, "invalid graft: {}", reason),
+            Error::ProofMismatch { depth, reason } => {
+                write!(f, "proof does not match the tree at depth {}: {}", depth, reason)
+            }
+            Error::LeafIndexOutOfRange { index, count } => {
+                write!(f, "leaf index {} is out of range for a tree of {} leaves", index, count)
+            }
+            Error::InvalidUpdate(reason) => write!(f, "invalid update: {}", reason),
+            Error::InvalidSnapshot { offset, ref cause } => {
+                write!(f, "invalid snapshot at byte {}: {}", offset, cause)
+            }
+            Error::PolicyViolation(reason) => write!(f, "proof violates the validation policy: {}", reason),
+            Error::LeafHash { index, ref cause } => write!(f, "cannot hash leaf {}: {}", index, cause),
+            Error::UnalignedRange { ref nearest_aligned } => {
+                write!(
+                    f,
+                    "range of leaves is not that of a node, the nearest one is {}..{}",
+                    nearest_aligned.start,
+                    nearest_aligned.end
+                )
+            }
+            Error::SchemeMismatch { proof, verifier } => {
+                write!(f, "proof is hashed with the {:?} scheme, expected {:?}", proof, verifier)
+            }
+            Error::InvalidIndices { ref missing, ref duplicates } => {
+                write!(f, "{} indices have no value and {} have several", missing.len(), duplicates.len())
+            }
+            Error::IndexOverflow { index } => {
+                write!(f, "leaf index {} does not fit in the address space of this target", index)
+            }
+            Error::UntrustedRoot => write!(f, "root hash is not the pinned one"),
+            Error::InvalidSignature => write!(f, "tree head signature does not verify"),
+            Error::LeafIo { index, ref source } => write!(f, "cannot read leaf {}: {}", index, source),
+            Error::BudgetExceeded { needed, remaining } => {
+                write!(
+                    f,
+                    "validation takes {} hashes over {} bytes, but only {} hashes over {} bytes remain",
+                    needed.hashes, needed.bytes, remaining.hashes, remaining.bytes
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// The error returned by `TryHashable::try_update_context` when a value cannot be hashed,
+/// such as a value hashed as its serialized form which fails to serialize, or one whose bytes
+/// are read from a storage which is unavailable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashError {
+    /// Why the value could not be hashed
+    pub reason: String,
+}
+
+impl HashError {
+    /// Creates an error for the given reason, which is typically the error the value failed with.
+    pub fn new<R: fmt::Display>(reason: R) -> Self {
+        HashError { reason: reason.to_string() }
+    }
+}
+
+impl fmt::Display for HashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.reason)
+    }
+}
+
+impl error::Error for HashError {}
+
+/// An I/O error a value failed to be read with, see `Error::LeafIo`, which keeps its kind
+/// and its message, so that it can be cloned and compared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IoError {
+    /// The kind of the error
+    pub kind: io::ErrorKind,
+
+    /// The message of the error
+    pub reason: String,
+}
+
+impl From<io::Error> for IoError {
+    fn from(err: io::Error) -> Self {
+        IoError {
+            kind: err.kind(),
+            reason: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.reason)
+    }
+}
+
+impl error::Error for IoError {}
+
+/// The error returned by `ProofData::into_proof` when the data does not go with the hashing
+/// algorithm it is paired with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(missing_copy_implementations)]
+pub enum ConversionError {
+    /// The data names another hashing algorithm than the one it is paired with
+    AlgorithmMismatch {
+        /// The tag of the algorithm the data is paired with
+        expected: AlgorithmTag,
+
+        /// The tag of the algorithm the data names
+        named: AlgorithmTag,
+    },
+
+    /// A hash of the lemma at the given level of the chain, the root one being at level `0`,
+    /// or the root hash, at level `0` as well, does not have the output length of the algorithm
+    WrongHashLength {
+        /// The level of the lemma holding the hash
+        level: usize,
+
+        /// The output length of the hashing algorithm
+        expected: usize,
+
+        /// The length of the offending hash
+        got: usize,
+    },
+
+    /// The lemma at the given level of the chain has a sibling hash without a sub lemma,
+    /// or the other way round, or lies deeper than `MAX_TREE_DEPTH`
+    MalformedLemma {
+        /// The level of the offending lemma, the root one being at level `0`
+        level: usize,
+    },
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConversionError::AlgorithmMismatch { expected, named } => {
+                write!(f, "data names the {} algorithm, expected {}", named, expected)
+            }
+            ConversionError::WrongHashLength { level, expected, got } => {
+                write!(f, "hash at level {} has length {}, expected {}", level, got, expected)
+            }
+            ConversionError::MalformedLemma { level } => write!(f, "malformed lemma at level {}", level),
+        }
+    }
+}
+
+impl error::Error for ConversionError {}
+
+impl From<ConversionError> for Error {
+    fn from(err: ConversionError) -> Self {
+        match err {
+            ConversionError::AlgorithmMismatch { .. } => Error::AlgorithmMismatch,
+            ConversionError::WrongHashLength { expected, got, .. } => {
+                Error::HashLengthMismatch {
+                    expected: expected,
+                    found: got,
+                }
+            }
+            ConversionError::MalformedLemma { .. } => Error::Decode(err.to_string()),
+        }
+    }
+}
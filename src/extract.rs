@@ -0,0 +1,136 @@
+use std::ops::Range;
+
+use builder::MerkleTreeBuilder;
+use error::Error;
+use hashutils::Hashable;
+use merkletree::MerkleTree;
+use tree::{self, Tree, View};
+
+impl<T: Clone> MerkleTree<T> {
+    /// Returns a tree of its own over the leaves of this tree in the given `range`, whose root
+    /// is the node of this tree over those leaves, so that nothing is hashed: the nodes below it
+    /// are copied, along with the values of its leaves. It is built with the options of this
+    /// tree, a tree of a fixed depth yielding a tree as deep as the node.
+    ///
+    /// Fails with `Error::UnalignedRange` if no node of this tree is over these very leaves,
+    /// holding the range of the smallest node over all of them, which can be extracted instead,
+    /// see `MerkleTree::subtree_rebuild` for any range. Fails with `Error::LeafIndexOutOfRange`
+    /// if the range goes past the last leaf, and with `Error::Pruned` if the node is part
+    /// of a pruned subtree.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::{MerkleTree, Error};
+    /// # fn main() {
+    /// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c", "d", "e"]);
+    ///
+    /// let subtree = tree.subtree(2..4).unwrap();
+    /// assert_eq!(subtree.root_hash().as_slice(), tree.node_hash(1, 1).unwrap());
+    ///
+    /// assert_eq!(tree.subtree(1..3), Err(Error::UnalignedRange { nearest_aligned: 0..4 }));
+    /// # }
+    /// ```
+    pub fn subtree(&self, range: Range<usize>) -> Result<MerkleTree<T>, Error> {
+        if range.end > self.count() {
+            return Err(Error::LeafIndexOutOfRange {
+                index: range.end - 1,
+                count: self.count(),
+            });
+        }
+
+        let fixed = self.fixed_depth().is_some();
+        let padded = self.is_padded();
+
+        let mut node = self.tree().root();
+        let mut offset = 0;
+        let mut count = self.count();
+        let mut level = self.height();
+        let mut nearest_aligned = 0..count;
+
+        loop {
+            // A node is the root of a tree of its own built the same way if it is as high
+            // as that tree would be, which always holds when the odd nodes are promoted.
+            // The root of a tree of a fixed depth may be higher.
+            if level == tree::ceil_log2(count) || fixed && level == self.height() {
+                if offset == range.start && offset + count == range.end {
+                    break;
+                }
+
+                nearest_aligned = offset..offset + count;
+            }
+
+            match node.view() {
+                View::Node { left, right, .. } => {
+                    let left_count = tree::left_count(right, count);
+                    let middle = offset + left_count;
+
+                    if range.end <= middle {
+                        node = left;
+                        count = left_count;
+                        level -= 1;
+                    } else if range.start >= middle {
+                        node = right;
+                        offset = middle;
+                        count -= left_count;
+                        level = if padded { level - 1 } else { tree::ceil_log2(count) };
+                    } else {
+                        return Err(Error::UnalignedRange { nearest_aligned: nearest_aligned });
+                    }
+                }
+
+                View::Pruned { hash, .. } => return Err(Error::Pruned { subtree_root: hash.to_vec() }),
+
+                View::Empty { .. } | View::Leaf { .. } => {
+                    return Err(Error::UnalignedRange { nearest_aligned: nearest_aligned });
+                }
+            }
+        }
+
+        let mut options = self.options().clone();
+
+        if fixed {
+            options.fixed_depth = Some(level);
+        }
+
+        let mut root = Tree::with_capacity(2 * count + level + 1);
+        node.copy_into(&mut root);
+
+        Ok(MerkleTree::from_tree(self.algorithm, root, count, options))
+    }
+
+    /// Builds a tree over the leaves of this tree in the given `range`, with the options of this
+    /// tree, hashing their values anew. Unlike `MerkleTree::subtree`, the range can be any range
+    /// of leaves, in which case the root of the tree is not a node of this tree.
+    ///
+    /// Fails with `Error::LeafIndexOutOfRange` if the range goes past the last leaf,
+    /// and with `Error::Pruned` if one of its leaves is pruned.
+    pub fn subtree_rebuild(&self, range: Range<usize>) -> Result<MerkleTree<T>, Error>
+    where
+        T: Hashable,
+    {
+
+        if range.end > self.count() {
+            return Err(Error::LeafIndexOutOfRange {
+                index: range.end - 1,
+                count: self.count(),
+            });
+        }
+
+        let root = self.tree().root();
+        let mut values = Vec::with_capacity(range.len());
+
+        for index in range {
+            match root.nth_value(index, self.count()) {
+                Some(value) => values.push(value.clone()),
+                None => {
+                    let hash = root.pruned_subtree(index, self.count()).expect("the leaf is pruned");
+                    return Err(Error::Pruned { subtree_root: hash.to_vec() });
+                }
+            }
+        }
+
+        MerkleTreeBuilder::with_options(self.algorithm, self.options().clone()).try_build_from_vec(values)
+    }
+}
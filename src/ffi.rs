@@ -0,0 +1,117 @@
+//! A C interface for verifying proofs, see `include/merkle.h`.
+//!
+//! Proofs are handed over in the binary format described in the `wire` module.
+//! None of these functions unwind into the caller: panics are caught and
+//! reported as errors.
+
+#![allow(unsafe_code)]
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use proof::Proof;
+use wire::{algorithm_tag, algorithm_from_tag};
+
+/// Returned by `merkle_proof_validate` when the proof is valid.
+pub const MERKLE_PROOF_VALID: i32 = 1;
+
+/// Returned by `merkle_proof_validate` when the proof is not valid for the given root hash.
+pub const MERKLE_PROOF_INVALID: i32 = 0;
+
+/// Returned when a required pointer argument is null.
+pub const MERKLE_ERROR_NULL_POINTER: i32 = -1;
+
+/// Returned when the algorithm tag is unknown, or differs from the one of the proof.
+pub const MERKLE_ERROR_ALGORITHM: i32 = -2;
+
+/// Returned when a panic occurred while handling the call.
+pub const MERKLE_ERROR_PANIC: i32 = -3;
+
+/// An opaque handle on a parsed proof.
+#[derive(Debug)]
+pub struct ProofHandle {
+    proof: Proof<Vec<u8>>,
+}
+
+/// Parses the `len` bytes at `bytes` as a proof in the binary format of the `wire` module.
+///
+/// Returns a handle to be released with `merkle_proof_free`,
+/// or null if `bytes` is null or does not hold a proof.
+///
+/// ## Safety
+///
+/// Unless null, `bytes` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_parse(bytes: *const u8, len: usize) -> *mut ProofHandle {
+    if bytes.is_null() {
+        return ptr::null_mut();
+    }
+
+    let parsed = panic::catch_unwind(|| {
+        let bytes = slice::from_raw_parts(bytes, len);
+
+        bytes
+            .get(5)
+            .and_then(|&tag| algorithm_from_tag(tag))
+            .and_then(|algorithm| Proof::from_bytes(algorithm, bytes).ok())
+    });
+
+    match parsed {
+        Ok(Some(proof)) => Box::into_raw(Box::new(ProofHandle { proof: proof })),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Checks whether the proof behind `handle` is valid with respect to the `root_len`
+/// bytes of root hash at `root`, and was produced with the algorithm identified by `algorithm_tag`.
+///
+/// Returns `MERKLE_PROOF_VALID`, `MERKLE_PROOF_INVALID`, or one of the negative error codes.
+///
+/// ## Safety
+///
+/// Unless null, `handle` must have been returned by `merkle_proof_parse` and not yet freed,
+/// and `root` must point to `root_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_validate(
+    handle: *const ProofHandle,
+    root: *const u8,
+    root_len: usize,
+    algorithm_tag: u8,
+) -> i32 {
+
+    if handle.is_null() || root.is_null() {
+        return MERKLE_ERROR_NULL_POINTER;
+    }
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let proof = &(*handle).proof;
+        let root_hash = slice::from_raw_parts(root, root_len);
+
+        if self::algorithm_tag(proof.algorithm) != algorithm_tag {
+            MERKLE_ERROR_ALGORITHM
+        } else if proof.validate(root_hash) {
+            MERKLE_PROOF_VALID
+        } else {
+            MERKLE_PROOF_INVALID
+        }
+    }));
+
+    result.unwrap_or(MERKLE_ERROR_PANIC)
+}
+
+/// Releases a handle returned by `merkle_proof_parse`. Does nothing if `handle` is null.
+///
+/// ## Safety
+///
+/// Unless null, `handle` must have been returned by `merkle_proof_parse` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_free(handle: *mut ProofHandle) {
+    if handle.is_null() {
+        return;
+    }
+
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        drop(Box::from_raw(handle));
+    }));
+}
@@ -0,0 +1,256 @@
+use std::cmp;
+use std::ops::Range;
+
+#[cfg(feature = "serialization-serde")]
+use serde::{Deserialize, Deserializer};
+#[cfg(feature = "serialization-serde")]
+use serde::de;
+
+#[cfg(feature = "serialization-serde")]
+use error::Error;
+use hash::AlgorithmTag;
+use levels::{BucketRange, nodes_at_level, leaves_under};
+use merkletree::MerkleTree;
+#[cfg(feature = "serialization-serde")]
+use proof::MAX_TREE_DEPTH;
+
+/// The hashes of the nodes of a `MerkleTree` a fixed number of levels below its root, taken
+/// with `MerkleTree::fingerprint`, which split its leaves into up to `2^depth` buckets, so that
+/// `MerkleTree::diff_roots_since` tells which buckets changed since, whatever the number
+/// of leaves.
+///
+/// Unlike a `LevelExport`, it only holds the level of the buckets, and it is meant to be kept
+/// by the owner of the tree, such as between runs of a job, to check that a batch of updates
+/// only changed the leaves it was meant to. When deserialized, the number of hashes is checked
+/// against the number of leaves, and every hash against the output length of the algorithm.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize))]
+pub struct TreeFingerprint {
+    algorithm: AlgorithmTag,
+
+    count: usize,
+
+    height: usize,
+
+    /// The level of the buckets, the leaves being at level `0`
+    level: usize,
+
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    root: Vec<u8>,
+
+    /// The hashes of the buckets, from left to right
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding::hashes"))]
+    hashes: Vec<Vec<u8>>,
+}
+
+impl TreeFingerprint {
+    /// Returns the tag of the algorithm the tree was built with.
+    pub fn algorithm(&self) -> AlgorithmTag {
+        self.algorithm
+    }
+
+    /// Returns the number of leaves of the tree.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the number of levels of the buckets below the root, which is at most
+    /// the height of the tree.
+    pub fn depth(&self) -> usize {
+        self.height - self.level
+    }
+
+    /// Returns the root hash of the tree.
+    pub fn root_hash(&self) -> &Vec<u8> {
+        &self.root
+    }
+
+    /// Returns the hashes of the buckets, from left to right.
+    pub fn hashes(&self) -> &[Vec<u8>] {
+        &self.hashes
+    }
+}
+
+/// The buckets of leaves of a `TreeFingerprint` which changed, see `MerkleTree::diff_roots_since`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct RegionDiff {
+    /// The buckets whose hash changed, or which only one of the trees has, in order
+    pub changed: Vec<BucketRange>,
+
+    /// Whether the number of leaves changed, in which case the buckets over the leaves which
+    /// were added or removed changed too
+    pub resized: bool,
+}
+
+impl RegionDiff {
+    /// Returns whether no leaf changed.
+    pub fn is_unchanged(&self) -> bool {
+        self.changed.is_empty() && !self.resized
+    }
+
+    /// Returns whether every bucket which changed holds some of the given `leaves`, so that
+    /// the leaves outside of the buckets holding them are known to be unchanged.
+    pub fn only_within(&self, leaves: Range<usize>) -> bool {
+        self.changed.iter().all(|bucket| bucket.start < leaves.end && leaves.start < bucket.end)
+    }
+}
+
+impl<T> MerkleTree<T> {
+    /// Takes the fingerprint of the tree `depth` levels below its root, the hashes of the nodes
+    /// which split its leaves into up to `2^depth` buckets, see `MerkleTree::diff_roots_since`.
+    /// A `depth` greater than the height of the tree takes the hashes of the leaves.
+    ///
+    /// Panics if the level reaches below a pruned subtree, see `MerkleTree::prune_below`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::{MerkleTree, BucketRange};
+    /// # fn main() {
+    /// let values = (0..1000).map(|i| i.to_string()).collect::<Vec<_>>();
+    /// let mut tree = MerkleTree::from_vec(&merkle::digest::SHA256, values);
+    ///
+    /// let fingerprint = tree.fingerprint(4);
+    /// tree.apply_updates(vec![(600, "six hundred".to_string()), (610, "six hundred ten".to_string())]).unwrap();
+    ///
+    /// let diff = tree.diff_roots_since(&fingerprint);
+    ///
+    /// assert_eq!(diff.changed, vec![BucketRange { level: 6, index: 9, start: 576, end: 640 }]);
+    /// assert!(diff.only_within(600..611));
+    /// # }
+    /// ```
+    pub fn fingerprint(&self, depth: usize) -> TreeFingerprint {
+        let level = self.height() - cmp::min(depth, self.height());
+
+        let hashes = (0..nodes_at_level(self.count(), level))
+            .map(|index| {
+                self.node_hash(level, index)
+                    .expect("every leaf is under a node of each level above the pruned subtrees")
+                    .to_vec()
+            })
+            .collect();
+
+        TreeFingerprint {
+            algorithm: AlgorithmTag::of(self.algorithm),
+            count: self.count(),
+            height: self.height(),
+            level: level,
+            root: self.root_hash().clone(),
+            hashes: hashes,
+        }
+    }
+
+    /// Returns the buckets of leaves of the given fingerprint of this tree whose hash changed
+    /// since it was taken, in order, or nothing if the tree has the same root and number
+    /// of leaves, comparing one hash per bucket, whatever the number of leaves.
+    ///
+    /// The buckets cover the same leaves as long as the tree has the same number of leaves.
+    /// Otherwise, those which only one of the trees has, and the last one either tree has,
+    /// changed too. Every bucket changed if the tree is hashed with another algorithm.
+    ///
+    /// Panics if the level of the buckets reaches below a pruned subtree,
+    /// see `MerkleTree::prune_below`.
+    pub fn diff_roots_since(&self, snapshot: &TreeFingerprint) -> RegionDiff {
+        let same_algorithm = snapshot.algorithm == AlgorithmTag::of(self.algorithm);
+        let resized = snapshot.count != self.count();
+
+        if same_algorithm && !resized && snapshot.root == *self.root_hash() {
+            return RegionDiff {
+                changed: Vec::new(),
+                resized: false,
+            };
+        }
+
+        let level = snapshot.level;
+        let count = cmp::max(self.count(), snapshot.count);
+
+        let changed = (0..nodes_at_level(count, level))
+            .filter(|&index| {
+                !same_algorithm || self.node_hash(level, index) != snapshot.hashes.get(index).map(Vec::as_slice)
+            })
+            .map(|index| {
+                let (start, end) = leaves_under(level, index, count);
+
+                BucketRange {
+                    level: level,
+                    index: index,
+                    start: start,
+                    end: end,
+                }
+            })
+            .collect();
+
+        RegionDiff {
+            changed: changed,
+            resized: resized,
+        }
+    }
+}
+
+/// The serialized fields of a `TreeFingerprint`, before they are checked.
+#[cfg(feature = "serialization-serde")]
+#[derive(Deserialize)]
+struct TreeFingerprintData {
+    algorithm: AlgorithmTag,
+
+    count: usize,
+
+    height: usize,
+
+    level: usize,
+
+    #[serde(with = "::hash_encoding")]
+    root: Vec<u8>,
+
+    #[serde(with = "::hash_encoding::hashes")]
+    hashes: Vec<Vec<u8>>,
+}
+
+#[cfg(feature = "serialization-serde")]
+impl TreeFingerprintData {
+    fn check(&self) -> Result<(), Error> {
+        let hash_len = self.algorithm.algorithm().output_len;
+
+        if self.height > MAX_TREE_DEPTH || self.level > self.height {
+            return Err(Error::MalformedTree("too many levels"));
+        }
+
+        if nodes_at_level(self.count, self.height) > 1 {
+            return Err(Error::MalformedTree("too many leaves for the height"));
+        }
+
+        if self.hashes.len() != nodes_at_level(self.count, self.level) {
+            return Err(Error::MalformedTree("buckets do not match the leaf count"));
+        }
+
+        match Some(&self.root).into_iter().chain(&self.hashes).find(|hash| hash.len() != hash_len) {
+            Some(hash) => {
+                Err(Error::HashLengthMismatch {
+                    expected: hash_len,
+                    found: hash.len(),
+                })
+            }
+
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<'de> Deserialize<'de> for TreeFingerprint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = TreeFingerprintData::deserialize(deserializer)?;
+        data.check().map_err(de::Error::custom)?;
+
+        Ok(TreeFingerprint {
+            algorithm: data.algorithm,
+            count: data.count,
+            height: data.height,
+            level: data.level,
+            root: data.root,
+            hashes: data.hashes,
+        })
+    }
+}
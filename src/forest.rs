@@ -0,0 +1,130 @@
+use digest::Algorithm;
+
+use error::Error;
+use hashutils::Hashable;
+use merkletree::MerkleTree;
+use proof::{Proof, Lemma};
+use small_hash::SmallHash;
+use tree::Leaf;
+
+/// A fixed number of `MerkleTree`s, the shards, under a single super root: the root of a top
+/// tree whose leaves are the root hashes of the shards, taken as they are rather than hashed
+/// again, so that the top tree continues the shards upwards.
+///
+/// The proofs of the values of the shards are hence the proofs of their own shard spliced under
+/// the proof of its root in the top tree, which validate against the super root as any other
+/// proof does, see `Forest::prove`. An empty shard has the hash of the empty string as root.
+///
+/// Modifying a shard only rehashes that shard and the path of its root in the top tree.
+#[derive(Clone, Debug)]
+pub struct Forest<T> {
+    /// The hashing algorithm of the shards and of the top tree
+    pub algorithm: &'static Algorithm,
+
+    shards: Vec<MerkleTree<T>>,
+
+    /// The tree over the root hashes of the shards
+    top: MerkleTree<()>,
+}
+
+impl<T> Forest<T> {
+    /// Creates a forest of `num_shards` empty shards.
+    pub fn new(algorithm: &'static Algorithm, num_shards: usize) -> Self {
+        let shards = (0..num_shards).map(|_| MerkleTree::empty(algorithm)).collect::<Vec<_>>();
+        let roots = shards.iter().map(|shard| shard.root_hash().clone()).collect();
+
+        Forest {
+            algorithm: algorithm,
+            shards: shards,
+            top: MerkleTree::from_leaf_hashes(algorithm, roots).expect("the roots have the output length"),
+        }
+    }
+
+    /// Returns the number of shards of the forest.
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the given shard, if any.
+    pub fn shard(&self, shard: usize) -> Option<&MerkleTree<T>> {
+        self.shards.get(shard)
+    }
+
+    /// Returns the root hash of the top tree, over the root hashes of the shards,
+    /// which is the hash of the empty string if the forest has no shard.
+    pub fn super_root(&self) -> &Vec<u8> {
+        self.top.root_hash()
+    }
+
+    /// Appends the given value to the given shard, as with `MerkleTree::try_push`.
+    ///
+    /// Fails with `Error::LeafIndexOutOfRange` if there is no such shard.
+    pub fn push_to(&mut self, shard: usize, value: T) -> Result<(), Error>
+    where
+        T: Hashable,
+    {
+
+        self.modify(shard, |tree| tree.try_push(value).map(|_| ()))
+    }
+
+    /// Replaces the value of the leaf at the given `index` of the given shard, as with
+    /// `MerkleTree::update`, returning the previous value.
+    ///
+    /// Fails with `Error::LeafIndexOutOfRange` if there is no such shard, and as
+    /// `MerkleTree::update` does otherwise.
+    pub fn update(&mut self, shard: usize, index: usize, value: T) -> Result<T, Error>
+    where
+        T: Hashable,
+    {
+
+        self.modify(shard, |tree| tree.update(index, value))
+    }
+
+    /// Generates the proof of the `index`-th value of the given shard against the super root,
+    /// or `None` if there is no such value.
+    ///
+    /// Its lemma chain goes down the top tree to the root of the shard, then down the shard,
+    /// and it knows neither the size of the tree nor the index of the leaf. It only validates
+    /// if the chain has at most `MAX_TREE_DEPTH` nested sub lemmas.
+    pub fn prove(&self, shard: usize, index: usize) -> Option<Proof<T>>
+    where
+        T: Clone,
+    {
+
+        let inner = self.shards.get(shard)?.gen_nth_proof(index)?;
+        let mut lemma = self.top.gen_nth_proof(shard)?.lemma;
+
+        splice(&mut lemma, inner.lemma);
+
+        Some(Proof::new(self.algorithm, self.super_root().clone(), lemma, inner.value))
+    }
+
+    /// Applies `modify` to the given shard, then updates its root in the top tree
+    /// if it changed.
+    fn modify<F, U>(&mut self, shard: usize, modify: F) -> Result<U, Error>
+    where
+        F: FnOnce(&mut MerkleTree<T>) -> Result<U, Error>,
+    {
+
+        self.top.check_updates(Some(shard))?;
+
+        let tree = &mut self.shards[shard];
+        let result = modify(tree)?;
+        let root = SmallHash::from(tree.root_hash().as_slice());
+
+        if self.top.node_hash(0, shard) != Some(root.as_slice()) {
+            self.top.replace_leaves(vec![(shard, Leaf { hash: root, value: () })]);
+        }
+
+        Ok(result)
+    }
+}
+
+/// Replaces the innermost lemma of `lemma`, the one of the root of a shard, by `shard`,
+/// the lemma chain from that root down.
+fn splice(lemma: &mut Lemma, shard: Lemma) {
+    match lemma.sub_lemma {
+        Some(ref mut sub_lemma) => splice(sub_lemma, shard),
+        None => *lemma = shard,
+    }
+}
@@ -0,0 +1,370 @@
+use std::sync::OnceLock;
+
+use digest::Algorithm;
+
+#[cfg(feature = "serialization-serde")]
+use serde::{Deserialize, Deserializer};
+#[cfg(feature = "serialization-serde")]
+use serde::de;
+
+use error::Error;
+use hash::AlgorithmTag;
+use hashutils::Hashable;
+use index;
+use merkletree::MerkleTree;
+use options::{HashingScheme, OddLeafPolicy, TreeOptions};
+#[cfg(feature = "serialization-serde")]
+use proof::MAX_TREE_DEPTH;
+
+/// What it takes to keep appending to a `MerkleTree` without its leaves, see
+/// `MerkleTree::export_frontier`: its number of leaves and the hashes of the perfect subtrees
+/// they split into, one per bit set in the number of leaves, along with the options the hashes
+/// depend on.
+///
+/// When deserialized, the number of hashes is checked against the number of leaves, and every
+/// hash against the output length of the algorithm. The number of leaves is a `u64`, which
+/// `MerkleTree::from_frontier` checks fits in a `usize`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize))]
+pub struct Frontier {
+    algorithm: AlgorithmTag,
+
+    count: u64,
+
+    /// The hashes of the perfect subtrees, from the largest one on the left to the smallest one
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding::hashes"))]
+    hashes: Vec<Vec<u8>>,
+
+    scheme: HashingScheme,
+
+    odd_leaf_policy: OddLeafPolicy,
+
+    leaf_counts: bool,
+
+    fixed_depth: Option<usize>,
+}
+
+impl Frontier {
+    /// Returns the tag of the algorithm the tree was built with.
+    pub fn algorithm(&self) -> AlgorithmTag {
+        self.algorithm
+    }
+
+    /// Returns the number of leaves of the tree.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the hashes of the perfect subtrees the leaves of the tree split into,
+    /// from the largest one on the left to the smallest one on the right.
+    pub fn hashes(&self) -> &[Vec<u8>] {
+        &self.hashes
+    }
+}
+
+impl<T> MerkleTree<T> {
+    /// Exports the frontier of this tree, from which `MerkleTree::from_frontier` resumes
+    /// appending to it elsewhere, without its values. It holds one hash per bit set
+    /// in the number of leaves.
+    ///
+    /// Panics if the leaves of this tree are sorted or deduplicated, if it has a hasher
+    /// of its own, or if it has pruned subtrees, as it cannot be resumed from its frontier then.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::MerkleTree;
+    /// # fn main() {
+    /// let mut tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c"]);
+    ///
+    /// let frontier = tree.export_frontier();
+    /// assert_eq!(frontier.hashes().len(), 2);
+    ///
+    /// let mut resumed = MerkleTree::from_frontier(&merkle::digest::SHA256, frontier).unwrap();
+    ///
+    /// tree.push("d");
+    /// resumed.push("d").unwrap();
+    ///
+    /// assert_eq!(resumed.root_hash(), tree.root_hash());
+    /// # }
+    /// ```
+    pub fn export_frontier(&self) -> Frontier {
+        let options = self.options();
+
+        assert!(!options.ordering.is_sorted(), "the leaves of the tree are sorted");
+        assert!(options.dedup.is_none(), "the leaves of the tree are deduplicated");
+        assert!(options.hasher.is_none(), "the tree has a hasher of its own");
+        assert_eq!(self.tree().pruned_leaves(), 0, "the tree has pruned subtrees");
+
+        let count = self.count();
+        let mut start = 0;
+
+        let hashes = (0..usize::BITS as usize)
+            .rev()
+            .filter(|&level| count >> level & 1 == 1)
+            .map(|level| {
+                let hash = self.node_hash(level, start >> level).expect("every perfect subtree is a node of the tree");
+                start += 1 << level;
+
+                hash.to_vec()
+            })
+            .collect();
+
+        Frontier {
+            algorithm: AlgorithmTag::of(self.algorithm),
+            count: count as u64,
+            hashes: hashes,
+            scheme: options.scheme,
+            odd_leaf_policy: options.odd_leaf_policy,
+            leaf_counts: options.leaf_counts,
+            fixed_depth: options.fixed_depth,
+        }
+    }
+
+    /// Resumes appending to the tree the given `frontier` was exported from, see
+    /// `MerkleTree::export_frontier`, with the options it was built with.
+    ///
+    /// Fails with `Error::AlgorithmMismatch` if the tree was not built with `algorithm`,
+    /// and with `Error::IndexOverflow` if its number of leaves does not fit in a `usize`.
+    pub fn from_frontier(algorithm: &'static Algorithm, frontier: Frontier) -> Result<AppendOnlyTree<T>, Error> {
+        if frontier.algorithm != AlgorithmTag::of(algorithm) {
+            return Err(Error::AlgorithmMismatch);
+        }
+
+        let count = index::to_index(frontier.count)?;
+
+        let options = TreeOptions {
+            scheme: frontier.scheme,
+            odd_leaf_policy: frontier.odd_leaf_policy,
+            leaf_counts: frontier.leaf_counts,
+            fixed_depth: frontier.fixed_depth,
+            ..TreeOptions::default()
+        };
+
+        Ok(AppendOnlyTree {
+            algorithm: algorithm,
+            options: options,
+            count: count,
+            frontier: frontier.hashes,
+            root_hash: OnceLock::new(),
+        })
+    }
+}
+
+/// A tree which only keeps its frontier, see `MerkleTree::from_frontier`, so that values can be
+/// appended to it and its root hash computed, but no proof generated, as its leaves are gone.
+#[derive(Clone, Debug)]
+pub struct AppendOnlyTree<T> {
+    /// The hashing algorithm of the tree
+    pub algorithm: &'static Algorithm,
+
+    /// The options of the tree, those recorded by the frontier
+    options: TreeOptions<T>,
+
+    /// The number of leaves of the tree
+    count: usize,
+
+    /// The hashes of the perfect subtrees, from the largest one on the left to the smallest one
+    frontier: Vec<Vec<u8>>,
+
+    /// The root hash, computed on the first call to `AppendOnlyTree::root_hash`
+    /// since the last append
+    root_hash: OnceLock<Vec<u8>>,
+}
+
+impl<T> AppendOnlyTree<T> {
+    /// Returns the number of leaves of the tree.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Hashes the given value into a leaf, and appends it to the tree.
+    ///
+    /// Fails with `Error::TooManyLeaves` if all the slots of a tree of fixed depth are filled.
+    pub fn push(&mut self, value: T) -> Result<(), Error>
+    where
+        T: Hashable,
+    {
+
+        let hash = self.options.hash_leaf(self.algorithm, &value);
+        self.push_leaf_hash(hash.as_ref())
+    }
+
+    /// Appends a leaf of the given hash to the tree, as hashed by `AppendOnlyTree::push`.
+    ///
+    /// Fails with `Error::HashLengthMismatch` if it is not as long as the output
+    /// of the algorithm, and with `Error::TooManyLeaves` if all the slots of a tree
+    /// of fixed depth are filled.
+    pub fn push_leaf_hash(&mut self, hash: &[u8]) -> Result<(), Error> {
+        if hash.len() != self.algorithm.output_len {
+            return Err(Error::HashLengthMismatch {
+                expected: self.algorithm.output_len,
+                found: hash.len(),
+            });
+        }
+
+        let max = self.options.leaf_limit();
+
+        if self.count >= max {
+            return Err(Error::TooManyLeaves { max: max });
+        }
+
+        // The new leaf merges with the perfect subtrees of the same size, as a binary carry
+        let mut node = hash.to_vec();
+        let mut level = 0;
+
+        while self.count >> level & 1 == 1 {
+            let left = self.frontier.pop().expect("one hash per bit set in the number of leaves");
+            node = self.options.hash_subtree(self.algorithm, &left, &node, 2 << level).as_ref().to_vec();
+            level += 1;
+        }
+
+        self.frontier.push(node);
+        self.count += 1;
+        self.root_hash = OnceLock::new();
+
+        Ok(())
+    }
+
+    /// Returns the root hash of the tree, the same as that of a `MerkleTree` over all
+    /// its leaves.
+    pub fn root_hash(&self) -> &Vec<u8> {
+        self.root_hash.get_or_init(|| self.compute_root())
+    }
+
+    /// Exports the frontier of the tree, as `MerkleTree::export_frontier` does.
+    pub fn export_frontier(&self) -> Frontier {
+        Frontier {
+            algorithm: AlgorithmTag::of(self.algorithm),
+            count: self.count as u64,
+            hashes: self.frontier.clone(),
+            scheme: self.options.scheme,
+            odd_leaf_policy: self.options.odd_leaf_policy,
+            leaf_counts: self.options.leaf_counts,
+            fixed_depth: self.options.fixed_depth,
+        }
+    }
+
+    /// Combines the perfect subtrees into the root, from the smallest one up, the way
+    /// the odd nodes of the tree are built.
+    fn compute_root(&self) -> Vec<u8> {
+        let algorithm = self.algorithm;
+        let options = &self.options;
+
+        let defaults = options.fixed_depth.map(|depth| options.default_hashes(algorithm, depth));
+
+        let levels = (0..usize::BITS as usize).filter(|&level| self.count >> level & 1 == 1);
+        let mut peaks = self.frontier.iter().rev().zip(levels);
+
+        let (mut root, mut level) = match peaks.next() {
+            Some((hash, level)) => (hash.clone(), level),
+            None => {
+                return match defaults {
+                    Some(ref defaults) => defaults[defaults.len() - 1].clone(),
+                    None => options.hash_empty(algorithm).as_ref().to_vec(),
+                };
+            }
+        };
+
+        let mut leaves = 1u64 << level;
+
+        // Brings the root up to the given level, pairing it with an empty subtree
+        // or a copy of itself, unless it is promoted
+        let raise = |root: &mut Vec<u8>, level: &mut usize, target: usize, leaves: u64| {
+            while *level < target {
+                let right = match (&defaults, options.odd_leaf_policy) {
+                    (&Some(ref defaults), _) => defaults[*level].clone(),
+                    (&None, OddLeafPolicy::Duplicate) => root.clone(),
+                    (&None, OddLeafPolicy::Promote) => {
+                        *level = target;
+                        break;
+                    }
+                };
+
+                *root = options.hash_subtree(algorithm, root, &right, leaves).as_ref().to_vec();
+                *level += 1;
+            }
+        };
+
+        for (hash, peak_level) in peaks {
+            raise(&mut root, &mut level, peak_level, leaves);
+
+            leaves += 1 << peak_level;
+            root = options.hash_subtree(algorithm, hash, &root, leaves).as_ref().to_vec();
+            level = peak_level + 1;
+        }
+
+        if let Some(depth) = options.fixed_depth {
+            raise(&mut root, &mut level, depth, leaves);
+        }
+
+        root
+    }
+}
+
+/// The serialized fields of a `Frontier`, before they are checked.
+#[cfg(feature = "serialization-serde")]
+#[derive(Deserialize)]
+struct FrontierData {
+    algorithm: AlgorithmTag,
+
+    count: u64,
+
+    #[serde(with = "::hash_encoding::hashes")]
+    hashes: Vec<Vec<u8>>,
+
+    scheme: HashingScheme,
+
+    odd_leaf_policy: OddLeafPolicy,
+
+    leaf_counts: bool,
+
+    fixed_depth: Option<usize>,
+}
+
+#[cfg(feature = "serialization-serde")]
+impl FrontierData {
+    fn check(&self) -> Result<(), Error> {
+        let hash_len = self.algorithm.algorithm().output_len;
+
+        if self.hashes.len() != self.count.count_ones() as usize {
+            return Err(Error::MalformedTree("not one hash per bit set in the number of leaves"));
+        }
+
+        if let Some(depth) = self.fixed_depth {
+            if depth > MAX_TREE_DEPTH || depth < u64::BITS as usize && self.count > 1 << depth {
+                return Err(Error::MalformedTree("too many leaves for the depth"));
+            }
+        }
+
+        match self.hashes.iter().find(|hash| hash.len() != hash_len) {
+            Some(hash) => {
+                Err(Error::HashLengthMismatch {
+                    expected: hash_len,
+                    found: hash.len(),
+                })
+            }
+
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<'de> Deserialize<'de> for Frontier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = FrontierData::deserialize(deserializer)?;
+        data.check().map_err(de::Error::custom)?;
+
+        Ok(Frontier {
+            algorithm: data.algorithm,
+            count: data.count,
+            hashes: data.hashes,
+            scheme: data.scheme,
+            odd_leaf_policy: data.odd_leaf_policy,
+            leaf_counts: data.leaf_counts,
+            fixed_depth: data.fixed_depth,
+        })
+    }
+}
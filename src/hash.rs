@@ -0,0 +1,231 @@
+
+use std::fmt;
+
+use digest::Algorithm;
+
+#[cfg(feature = "serialization-serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serialization-serde")]
+use serde::de;
+
+use error::Error;
+use hex::to_hex;
+use registry::algorithm_name;
+#[cfg(feature = "serialization-serde")]
+use registry::algorithm_from_name;
+use wire::{algorithm_tag, algorithm_from_tag};
+
+/// Identifies the hashing algorithm a hash was computed with, by its tag in encoded proofs,
+/// see `wire::algorithm_tag`.
+///
+/// It is displayed and serialized as the name of the algorithm, see `algorithm_name`,
+/// and deserialized from any name known to `algorithm_from_name`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AlgorithmTag(u8);
+
+impl AlgorithmTag {
+    /// Returns the tag of the given hashing algorithm.
+    pub fn of(algorithm: &'static Algorithm) -> Self {
+        AlgorithmTag(algorithm_tag(algorithm))
+    }
+
+    /// Returns the tag encoded by the given byte, if it identifies a hashing algorithm.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        algorithm_from_tag(byte).map(|_| AlgorithmTag(byte))
+    }
+
+    /// Returns the byte encoding this tag, see `wire::algorithm_tag`.
+    pub fn to_byte(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the hashing algorithm identified by this tag.
+    pub fn algorithm(self) -> &'static Algorithm {
+        algorithm_from_tag(self.0).expect("tags are only built for known algorithms")
+    }
+}
+
+impl fmt::Display for AlgorithmTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(algorithm_name(self.algorithm()))
+    }
+}
+
+/// The hash of a node of a `MerkleTree`, along with the algorithm it was computed with.
+///
+/// Two hashes are only equal if they were computed with the same algorithm,
+/// and a hash always has the output length of its algorithm.
+/// It is displayed in lowercase hex.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize))]
+pub struct NodeHash {
+    algorithm: AlgorithmTag,
+
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    hash: Vec<u8>,
+}
+
+impl NodeHash {
+    /// Tags the given hash with the algorithm it was computed with.
+    ///
+    /// Fails with `Error::HashLengthMismatch` if `hash` does not have the output length
+    /// of `algorithm`.
+    pub fn new(algorithm: &'static Algorithm, hash: Vec<u8>) -> Result<Self, Error> {
+        if hash.len() != algorithm.output_len {
+            return Err(Error::HashLengthMismatch {
+                expected: algorithm.output_len,
+                found: hash.len(),
+            });
+        }
+
+        Ok(NodeHash::new_unchecked(algorithm, hash))
+    }
+
+    /// Tags a hash known to have been computed with the given algorithm.
+    pub(crate) fn new_unchecked(algorithm: &'static Algorithm, hash: Vec<u8>) -> Self {
+        NodeHash {
+            algorithm: AlgorithmTag::of(algorithm),
+            hash: hash,
+        }
+    }
+
+    /// Returns the tag of the algorithm this hash was computed with.
+    pub fn algorithm(&self) -> AlgorithmTag {
+        self.algorithm
+    }
+
+    /// Returns the bytes of this hash.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.hash
+    }
+
+    /// Returns the bytes of this hash, dropping its algorithm.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.hash
+    }
+}
+
+impl AsRef<[u8]> for NodeHash {
+    fn as_ref(&self) -> &[u8] {
+        &self.hash
+    }
+}
+
+impl fmt::Display for NodeHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&to_hex(&self.hash))
+    }
+}
+
+/// The root hash of a `MerkleTree`, along with the algorithm it was computed with,
+/// see `NodeHash`.
+///
+/// Proofs are checked against a `RootHash` with `Proof::verify`, which tells apart a root
+/// computed with another algorithm from a proof which does not hold.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RootHash(NodeHash);
+
+impl RootHash {
+    /// Tags the given root hash with the algorithm it was computed with, see `NodeHash::new`.
+    pub fn new(algorithm: &'static Algorithm, hash: Vec<u8>) -> Result<Self, Error> {
+        NodeHash::new(algorithm, hash).map(RootHash)
+    }
+
+    /// Tags a root hash known to have been computed with the given algorithm.
+    pub(crate) fn new_unchecked(algorithm: &'static Algorithm, hash: Vec<u8>) -> Self {
+        RootHash(NodeHash::new_unchecked(algorithm, hash))
+    }
+
+    /// Returns the tag of the algorithm this hash was computed with.
+    pub fn algorithm(&self) -> AlgorithmTag {
+        self.0.algorithm()
+    }
+
+    /// Returns the bytes of this hash.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    /// Returns the bytes of this hash, dropping its algorithm.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0.into_bytes()
+    }
+}
+
+impl From<NodeHash> for RootHash {
+    /// Makes the given node the root of the tree below it.
+    fn from(hash: NodeHash) -> Self {
+        RootHash(hash)
+    }
+}
+
+impl From<RootHash> for NodeHash {
+    fn from(hash: RootHash) -> Self {
+        hash.0
+    }
+}
+
+impl AsRef<[u8]> for RootHash {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl fmt::Display for RootHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl Serialize for AlgorithmTag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(algorithm_name(self.algorithm()))
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<'de> Deserialize<'de> for AlgorithmTag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        match algorithm_from_name(&name) {
+            Some(algorithm) => Ok(AlgorithmTag::of(algorithm)),
+            None => Err(de::Error::custom(Error::UnknownAlgorithmName(name))),
+        }
+    }
+}
+
+/// The serialized fields of a `NodeHash`, before its length is checked.
+#[cfg(feature = "serialization-serde")]
+#[derive(Deserialize)]
+struct NodeHashData {
+    algorithm: AlgorithmTag,
+
+    #[serde(with = "::hash_encoding")]
+    hash: Vec<u8>,
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<'de> Deserialize<'de> for NodeHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = NodeHashData::deserialize(deserializer)?;
+
+        NodeHash::new(data.algorithm.algorithm(), data.hash).map_err(de::Error::custom)
+    }
+}
+
+/// Serialized as a `NodeHash`.
+#[cfg(feature = "serialization-serde")]
+impl Serialize for RootHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<'de> Deserialize<'de> for RootHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        NodeHash::deserialize(deserializer).map(RootHash)
+    }
+}
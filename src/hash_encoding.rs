@@ -0,0 +1,375 @@
+
+use std::cell::Cell;
+use std::fmt;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, Visitor, SeqAccess, Unexpected};
+
+use hex::{to_hex, from_hex, is_hex};
+
+/// The encoding of the hashes contained in a `ProofData` (the root hash,
+/// the node hashes and the sibling hashes), when serialized to a
+/// human-readable format such as JSON.
+///
+/// Binary formats always carry the raw bytes, whatever encoding is selected.
+///
+/// Deserialization accepts any of the three encodings: strings made of an even
+/// number of hexadecimal digits are decoded as hex, and any other string as base64
+/// (with either the standard or the URL-safe alphabet, padded or not).
+/// Since `Base64` output is padded, it can only consist of hexadecimal digits
+/// for hashes whose length is a multiple of three, and then with negligible probability.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HashEncoding {
+    /// An array of bytes, eg. `[159, 163, 12]`. This is the default.
+    #[default]
+    Bytes,
+
+    /// A lowercase hexadecimal string, eg. `"9fa30c"`.
+    Hex,
+
+    /// A padded base64 string with the URL-safe alphabet, eg. `"n6MM"`.
+    Base64,
+}
+
+thread_local! {
+    static ENCODING: Cell<HashEncoding> = const { Cell::new(HashEncoding::Bytes) };
+}
+
+/// Wraps a reference to a value (typically a `ProofData` or a `Proof`)
+/// so that the hashes it contains get serialized with the given `HashEncoding`.
+///
+/// ## Example
+///
+/// ```ignore
+/// let json = serde_json::to_string(&Encoded::new(&proof, HashEncoding::Hex))?;
+/// let data: ProofData<String> = serde_json::from_str(&json)?;
+/// ```
+#[derive(Debug)]
+pub struct Encoded<'a, T: 'a> {
+    value: &'a T,
+    encoding: HashEncoding,
+}
+
+impl<'a, T> Encoded<'a, T> {
+    /// Wraps `value` so that its hashes are serialized with `encoding`.
+    pub fn new(value: &'a T, encoding: HashEncoding) -> Self {
+        Encoded {
+            value: value,
+            encoding: encoding,
+        }
+    }
+}
+
+impl<'a, T: Serialize> Serialize for Encoded<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let previous = ENCODING.with(|enc| enc.replace(self.encoding));
+        let _restore = RestoreEncoding(previous);
+
+        self.value.serialize(serializer)
+    }
+}
+
+/// Restores the previously selected encoding, even if serialization panics.
+struct RestoreEncoding(HashEncoding);
+
+impl Drop for RestoreEncoding {
+    fn drop(&mut self) {
+        ENCODING.with(|enc| enc.set(self.0));
+    }
+}
+
+/// Serializes a hash with the currently selected `HashEncoding`.
+pub fn serialize<S: Serializer>(hash: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    if !serializer.is_human_readable() {
+        return hash.serialize(serializer);
+    }
+
+    match ENCODING.with(|enc| enc.get()) {
+        HashEncoding::Bytes => hash.serialize(serializer),
+        HashEncoding::Hex => serializer.serialize_str(&to_hex(hash)),
+        HashEncoding::Base64 => serializer.serialize_str(&to_base64(hash)),
+    }
+}
+
+/// Deserializes a hash, whichever `HashEncoding` was used to serialize it.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(HashVisitor)
+    } else {
+        Vec::deserialize(deserializer)
+    }
+}
+
+struct HashVisitor;
+
+impl<'de> Visitor<'de> for HashVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a hash, as an array of bytes, a hex string or a base64 string")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+        decode(v).ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut hash = Vec::new();
+
+        while let Some(byte) = seq.next_element()? {
+            hash.push(byte);
+        }
+
+        Ok(hash)
+    }
+}
+
+/// Serializes a hash with the currently selected `HashEncoding`.
+pub(crate) struct HashRef<'a>(pub &'a [u8]);
+
+impl<'a> Serialize for HashRef<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(self.0, serializer)
+    }
+}
+
+struct HashBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for HashBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(HashBuf)
+    }
+}
+
+/// (De)serialization of sibling hashes, with the same encoding as the other hashes.
+pub mod sibling {
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+    use proof::Positioned;
+    use super::{HashRef, HashBuf};
+
+    /// Serializes a sibling hash with the currently selected `HashEncoding`.
+    pub fn serialize<S: Serializer>(
+        sibling: &Option<Positioned<Vec<u8>>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+
+        let sibling = sibling.as_ref().map(|sibling| sibling.as_ref().map(|hash| HashRef(hash)));
+
+        sibling.serialize(serializer)
+    }
+
+    /// Deserializes a sibling hash, whichever `HashEncoding` was used to serialize it.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Positioned<Vec<u8>>>, D::Error> {
+
+        let sibling = Option::<Positioned<HashBuf>>::deserialize(deserializer)?;
+
+        Ok(sibling.map(|sibling| sibling.map(|HashBuf(hash)| hash)))
+    }
+}
+
+/// (De)serialization of sequences of sibling hashes, with the same encoding as the other hashes.
+pub mod siblings {
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+    use serde::de;
+
+    use proof::{Positioned, MAX_TREE_DEPTH};
+    use super::{HashRef, HashBuf};
+
+    /// Serializes sibling hashes with the currently selected `HashEncoding`.
+    pub fn serialize<S: Serializer>(
+        siblings: &[Positioned<Vec<u8>>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+
+        let siblings = siblings
+            .iter()
+            .map(|sibling| sibling.as_ref().map(|hash| HashRef(hash)))
+            .collect::<Vec<_>>();
+
+        siblings.serialize(serializer)
+    }
+
+    /// Deserializes sibling hashes, whichever `HashEncoding` was used to serialize them.
+    ///
+    /// Fails on more than `MAX_TREE_DEPTH` sibling hashes, which no path of a tree has.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Positioned<Vec<u8>>>, D::Error> {
+
+        let siblings = Vec::<Positioned<HashBuf>>::deserialize(deserializer)?;
+
+        if siblings.len() > MAX_TREE_DEPTH {
+            return Err(de::Error::invalid_length(siblings.len(), &"at most `MAX_TREE_DEPTH` sibling hashes"));
+        }
+
+        Ok(siblings
+            .into_iter()
+            .map(|sibling| sibling.map(|HashBuf(hash)| hash))
+            .collect())
+    }
+}
+
+/// (De)serialization of sequences of hashes, with the same encoding as the other hashes.
+pub mod hashes {
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+    use super::{HashRef, HashBuf};
+
+    /// Serializes hashes with the currently selected `HashEncoding`.
+    pub fn serialize<S: Serializer>(hashes: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        let hashes = hashes.iter().map(|hash| HashRef(hash)).collect::<Vec<_>>();
+
+        hashes.serialize(serializer)
+    }
+
+    /// Deserializes hashes, whichever `HashEncoding` was used to serialize them.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        let hashes = Vec::<HashBuf>::deserialize(deserializer)?;
+
+        Ok(hashes.into_iter().map(|HashBuf(hash)| hash).collect())
+    }
+}
+
+/// (De)serialization of the hashes of a `PrefixProof`, with the same encoding as the other hashes.
+pub mod prefix {
+    use serde::{Deserialize, Deserializer};
+    use serde::de;
+
+    use proof::MAX_TREE_DEPTH;
+    use super::HashBuf;
+
+    pub use super::hashes::serialize;
+
+    /// Deserializes the hashes of a `PrefixProof`, whichever `HashEncoding` was used to
+    /// serialize them.
+    ///
+    /// Fails on more than `MAX_TREE_DEPTH + 1` hashes, one per level and one for the subtree
+    /// the older tree ends with, which no proof holds.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        let hashes = Vec::<HashBuf>::deserialize(deserializer)?;
+
+        if hashes.len() > MAX_TREE_DEPTH + 1 {
+            return Err(de::Error::invalid_length(hashes.len(), &"at most `MAX_TREE_DEPTH + 1` hashes"));
+        }
+
+        Ok(hashes.into_iter().map(|HashBuf(hash)| hash).collect())
+    }
+}
+
+/// (De)serialization of the levels of hashes of a `LevelExport`, with the same encoding as the
+/// other hashes.
+pub mod levels {
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+    use super::{HashRef, HashBuf};
+
+    /// Serializes levels of hashes with the currently selected `HashEncoding`.
+    pub fn serialize<S: Serializer>(levels: &[Vec<Vec<u8>>], serializer: S) -> Result<S::Ok, S::Error> {
+        let levels = levels
+            .iter()
+            .map(|hashes| hashes.iter().map(|hash| HashRef(hash)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        levels.serialize(serializer)
+    }
+
+    /// Deserializes levels of hashes, whichever `HashEncoding` was used to serialize them.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<Vec<u8>>>, D::Error> {
+        let levels = Vec::<Vec<HashBuf>>::deserialize(deserializer)?;
+
+        Ok(levels
+            .into_iter()
+            .map(|hashes| hashes.into_iter().map(|HashBuf(hash)| hash).collect())
+            .collect())
+    }
+}
+
+static BASE64_URL_ALPHABET: &'static [u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes `bytes` as a padded base64 string, with the URL-safe alphabet.
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut base64 = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b0 = chunk[0] as usize;
+        let b1 = chunk.get(1).cloned().unwrap_or(0) as usize;
+        let b2 = chunk.get(2).cloned().unwrap_or(0) as usize;
+
+        base64.push(BASE64_URL_ALPHABET[b0 >> 2] as char);
+        base64.push(BASE64_URL_ALPHABET[((b0 & 0x03) << 4) | (b1 >> 4)] as char);
+
+        if chunk.len() > 1 {
+            base64.push(BASE64_URL_ALPHABET[((b1 & 0x0f) << 2) | (b2 >> 6)] as char);
+        } else {
+            base64.push('=');
+        }
+
+        if chunk.len() > 2 {
+            base64.push(BASE64_URL_ALPHABET[b2 & 0x3f] as char);
+        } else {
+            base64.push('=');
+        }
+    }
+
+    base64
+}
+
+/// Decodes a base64 string, with either the standard or the URL-safe alphabet,
+/// and with or without padding.
+#[deny(clippy::indexing_slicing, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+pub fn from_base64(base64: &str) -> Option<Vec<u8>> {
+    let digits = base64.trim_end_matches('=').as_bytes();
+
+    if digits.len() % 4 == 1 || base64.len() - digits.len() > 2 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+
+    for &digit in digits {
+        acc = (acc << 6) | base64_value(digit)? as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    Some(bytes)
+}
+
+fn base64_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'A'..=b'Z' => Some(digit - b'A'),
+        b'a'..=b'z' => Some(digit - b'a' + 26),
+        b'0'..=b'9' => Some(digit - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decodes a hash from either its hex or its base64 encoding.
+fn decode(encoded: &str) -> Option<Vec<u8>> {
+    if is_hex(encoded) {
+        from_hex(encoded)
+    } else {
+        from_base64(encoded)
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::sync::Arc;
+
+use digest::{Algorithm, Context, Digest};
+
+use display::Raw;
+use registry::algorithm_name;
+
+/// A hasher which nodes of a `MerkleTree` are hashed with, whose state is only known at runtime,
+/// such as a secret key, and need not live for `'static`.
+///
+/// The hashes are those of `algorithm`, over the input fed by `begin` followed by the one
+/// fed by the `HashingScheme`. A `&'static Algorithm` is a hasher which feeds nothing,
+/// so that every tree and proof built from a bare algorithm hashes as before.
+///
+/// It must not panic: the nodes of a tree are rehashed in place, and a tree whose hasher
+/// panicked while it was being modified is to be dropped.
+pub trait MerkleHasher: Send + Sync + fmt::Debug {
+    /// Returns the underlying hashing algorithm.
+    fn algorithm(&self) -> &'static Algorithm;
+
+    /// Feeds the context of every hash with the input it starts with. Feeds nothing by default.
+    fn begin(&self, _context: &mut Context) {}
+
+    /// Returns a new context, fed by `begin`.
+    fn context(&self) -> Context {
+        let mut context = Context::new(self.algorithm());
+        self.begin(&mut context);
+        context
+    }
+
+    /// Computes the hash of the empty string.
+    fn hash_empty(&self) -> Digest {
+        self.context().finish()
+    }
+
+    /// Computes the hashes of several leaves at once, each given as the whole input it is hashed
+    /// from after the one fed by `begin`, including the prefix of the `HashingScheme`, if any.
+    /// There must be one hash per leaf, in the same order.
+    ///
+    /// Trees go through this method when built with `MerkleTreeBuilder::leaf_batch_size`,
+    /// so that hashers which are faster given many independent messages can override it.
+    /// Hashes the leaves one by one by default.
+    fn hash_leaves_batch(&self, leaves: &[&[u8]]) -> Vec<Vec<u8>> {
+        leaves
+            .iter()
+            .map(|leaf| {
+                let mut context = self.context();
+                context.update(leaf);
+                context.finish().as_ref().to_vec()
+            })
+            .collect()
+    }
+}
+
+impl MerkleHasher for &'static Algorithm {
+    fn algorithm(&self) -> &'static Algorithm {
+        self
+    }
+}
+
+/// Returns the given hasher, or `algorithm` itself if there is none, for the trees and proofs
+/// which only hold a hasher when it is not their bare algorithm.
+pub(crate) fn or_algorithm<'a>(
+    hasher: &'a Option<Arc<dyn MerkleHasher>>,
+    algorithm: &'a &'static Algorithm,
+) -> &'a dyn MerkleHasher {
+
+    match *hasher {
+        Some(ref hasher) => &**hasher,
+        None => algorithm,
+    }
+}
+
+/// A hasher which prefixes every hash input with a secret key, so that only the holders of the
+/// key can compute the hashes of a tree, and validate its proofs, see `Proof::validate_with`.
+///
+/// This is a plain `H(key || input)` construction rather than an HMAC: it is only meant to keep
+/// the hashes of a tree from being recomputed without the key, not to be a MAC on its own.
+#[derive(Clone)]
+pub struct KeyedHasher {
+    algorithm: &'static Algorithm,
+    key: Vec<u8>,
+}
+
+impl KeyedHasher {
+    /// Creates a hasher which hashes with `algorithm`, the given `key` first.
+    pub fn new(algorithm: &'static Algorithm, key: Vec<u8>) -> Self {
+        KeyedHasher {
+            algorithm: algorithm,
+            key: key,
+        }
+    }
+}
+
+impl MerkleHasher for KeyedHasher {
+    fn algorithm(&self) -> &'static Algorithm {
+        self.algorithm
+    }
+
+    fn begin(&self, context: &mut Context) {
+        context.update(&self.key);
+    }
+}
+
+/// Leaves the key out.
+impl fmt::Debug for KeyedHasher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyedHasher")
+            .field("algorithm", &Raw(algorithm_name(self.algorithm)))
+            .finish_non_exhaustive()
+    }
+}
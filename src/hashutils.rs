@@ -1,17 +1,25 @@
 
-use ring::digest::{Algorithm, Context, Digest, digest};
+use std::borrow::{Cow, ToOwned};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use digest::{Algorithm, Context, Digest, digest};
+
+use error::HashError;
+use hash::{NodeHash, RootHash};
 
 /// The type of values stored in a `MerkleTree` must implement
 /// this trait, in order for them to be able to be fed
 /// to a Ring `Context` when computing the hash of a leaf.
 ///
-/// A default instance for types that already implements
-/// `AsRef<[u8]>` is provided.
+/// It is implemented for byte slices, vectors and arrays, for strings, and for digests and hashes,
+/// which are hashed as their bytes, as well as for references, `Box`, `Rc`, `Arc` and `Cow`
+/// of any `Hashable` type, which are hashed as the value they point to.
 ///
 /// ## Example
 ///
 /// Here is an example of how to implement `Hashable` for a type
-/// that does not (or cannot) implement `AsRef<[u8]>`:
+/// which is hashed as its serialized bytes:
 ///
 /// ```ignore
 /// impl Hashable for PublicKey {
@@ -24,13 +32,151 @@ use ring::digest::{Algorithm, Context, Digest, digest};
 pub trait Hashable {
     /// Update the given `context` with `self`.
     ///
-    /// See `ring::digest::Context::update` for more information.
+    /// See `digest::Context::update` for more information.
     fn update_context(&self, context: &mut Context);
+
+    /// Returns the bytes `update_context` feeds the context with, if they are at hand as a single
+    /// slice, so that leaves can be hashed in batches, see `MerkleHasher::hash_leaves_batch`.
+    /// Returns `None` by default.
+    fn hashed_bytes(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+/// A fallible companion of `Hashable`, for values which may fail to be hashed, such as values
+/// hashed as their serialized form, or whose bytes are read from an external storage.
+///
+/// It is implemented for every `Hashable` type, whose hashing never fails, so that the fallible
+/// constructors and methods, such as `MerkleTree::try_from_vec` and `MerkleTree::try_push`,
+/// accept any of them, and report the leaf whose value failed to be hashed with
+/// `Error::LeafHash` rather than panicking.
+///
+/// ## Example
+///
+/// ```ignore
+/// impl TryHashable for StoredBlock {
+///     fn try_update_context(&self, context: &mut Context) -> Result<(), HashError> {
+///         let bytes = self.storage.read(self.key).map_err(HashError::new)?;
+///         context.update(&bytes);
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait TryHashable {
+    /// Update the given `context` with `self`, or fail if `self` cannot be hashed,
+    /// in which case the context is dropped.
+    fn try_update_context(&self, context: &mut Context) -> Result<(), HashError>;
 }
 
-impl<T: AsRef<[u8]>> Hashable for T {
+impl<T: Hashable + ?Sized> TryHashable for T {
+    fn try_update_context(&self, context: &mut Context) -> Result<(), HashError> {
+        self.update_context(context);
+        Ok(())
+    }
+}
+
+/// Implements `Hashable` for types which are hashed as the bytes they dereference to.
+macro_rules! hashable_as_bytes {
+    ($($ty:ty),*) => {
+        $(
+            impl Hashable for $ty {
+                fn update_context(&self, context: &mut Context) {
+                    context.update(self.as_ref());
+                }
+
+                fn hashed_bytes(&self) -> Option<&[u8]> {
+                    Some(self.as_ref())
+                }
+            }
+        )*
+    }
+}
+
+hashable_as_bytes!([u8], Vec<u8>, str, String, Digest, NodeHash, RootHash);
+
+impl<const N: usize> Hashable for [u8; N] {
     fn update_context(&self, context: &mut Context) {
-        context.update(self.as_ref());
+        context.update(self);
+    }
+
+    fn hashed_bytes(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+}
+
+/// Implements `Hashable` for pointer types, which are hashed as the value they point to.
+macro_rules! hashable_through {
+    ($($ty:ident),*) => {
+        $(
+            impl<T: Hashable + ?Sized> Hashable for $ty<T> {
+                fn update_context(&self, context: &mut Context) {
+                    (**self).update_context(context);
+                }
+
+                fn hashed_bytes(&self) -> Option<&[u8]> {
+                    (**self).hashed_bytes()
+                }
+            }
+        )*
+    }
+}
+
+hashable_through!(Box, Rc, Arc);
+
+impl<'a, T: Hashable + ?Sized> Hashable for &'a T {
+    fn update_context(&self, context: &mut Context) {
+        (**self).update_context(context);
+    }
+
+    fn hashed_bytes(&self) -> Option<&[u8]> {
+        (**self).hashed_bytes()
+    }
+}
+
+impl<'a, T: Hashable + ToOwned + ?Sized> Hashable for Cow<'a, T> {
+    fn update_context(&self, context: &mut Context) {
+        (**self).update_context(context);
+    }
+
+    fn hashed_bytes(&self) -> Option<&[u8]> {
+        (**self).hashed_bytes()
+    }
+}
+
+/// An object-safe companion of `Hashable` for values which can also be cloned, so that a tree
+/// can hold values of different types, as `MerkleTree<Box<dyn DynHashable>>`.
+///
+/// It is implemented for every `Hashable` type which is `Clone` and `'static`, and boxed values
+/// are hashed through their vtable as their own type would be, so that a tree of boxed values
+/// has the same hashes as one of the values themselves.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use merkle::{MerkleTree, DynHashable};
+/// # fn main() {
+/// let values: Vec<Box<dyn DynHashable>> = vec![Box::new("event".to_string()), Box::new(vec![1u8, 2, 3])];
+/// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, values);
+///
+/// assert!(tree.gen_nth_proof(1).unwrap().validate(tree.root_hash()));
+/// # }
+/// ```
+pub trait DynHashable: Hashable {
+    /// Returns a clone of `self`, in a box.
+    fn clone_boxed(&self) -> Box<dyn DynHashable>;
+}
+
+impl<T: Hashable + Clone + 'static> DynHashable for T {
+    fn clone_boxed(&self) -> Box<dyn DynHashable> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn DynHashable> {
+    fn clone(&self) -> Self {
+        // Cloning the box itself, which is `DynHashable` too, would recurse
+        (**self).clone_boxed()
     }
 }
 
@@ -0,0 +1,44 @@
+
+static HEX_DIGITS: &'static [u8; 16] = b"0123456789abcdef";
+
+/// Encodes `bytes` as a lowercase hex string.
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+
+    for byte in bytes {
+        hex.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+        hex.push(HEX_DIGITS[(byte & 0x0f) as usize] as char);
+    }
+
+    hex
+}
+
+/// Decodes a hex string, in either case.
+#[deny(clippy::indexing_slicing, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match *pair {
+            [high, low] => Some((hex_value(high)? << 4) | hex_value(low)?),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns whether `hex` is made of an even number of hexadecimal digits, in either case.
+pub fn is_hex(hex: &str) -> bool {
+    hex.len().is_multiple_of(2) && hex.bytes().all(|b| hex_value(b).is_some())
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
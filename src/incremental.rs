@@ -0,0 +1,257 @@
+
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+
+use digest::Algorithm;
+
+use error::Error;
+use hasher::{self, MerkleHasher};
+use options::HashingScheme;
+use proof::{Positioned, MAX_TREE_DEPTH};
+use tree::{self, Subtree, View};
+
+/// Checks an inclusion proof whose levels arrive one at a time, from the leaf up,
+/// without holding on to them: each sibling hash is folded into the hash of the path
+/// as soon as it is pushed, so that memory use does not depend on the depth of the proof.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use merkle::{MerkleTree, IncrementalVerify, SHA256};
+/// # fn main() {
+/// let tree = MerkleTree::from_vec(SHA256, vec!["a", "b", "c", "d", "e"]);
+///
+/// // The levels of the proof of the third leaf, from the leaf up
+/// let siblings = tree.proof_writer(2).unwrap();
+/// let leaf_hash = siblings.leaf_hash();
+///
+/// let mut verify = IncrementalVerify::start(SHA256, leaf_hash);
+///
+/// for sibling in siblings {
+///     verify.push_level(sibling).unwrap();
+/// }
+///
+/// assert_eq!(verify.depth(), 3);
+/// assert!(verify.finish(tree.root_hash()));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct IncrementalVerify {
+    algorithm: &'static Algorithm,
+    hasher: Option<Arc<dyn MerkleHasher>>,
+    scheme: HashingScheme,
+    hash: Vec<u8>,
+    depth: usize,
+}
+
+// The hasher is only ever called through shared references, and keeps no state of the verifier.
+impl UnwindSafe for IncrementalVerify {}
+impl RefUnwindSafe for IncrementalVerify {}
+
+impl IncrementalVerify {
+    /// Starts checking the proof of the leaf with hash `leaf_hash`, in a tree hashed with
+    /// `algorithm` and the default `HashingScheme`.
+    pub fn start(algorithm: &'static Algorithm, leaf_hash: &[u8]) -> Self {
+        IncrementalVerify::start_with_scheme(algorithm, HashingScheme::default(), leaf_hash)
+    }
+
+    /// Works like `IncrementalVerify::start`, for a tree hashed with the given `scheme`.
+    pub fn start_with_scheme(
+        algorithm: &'static Algorithm,
+        scheme: HashingScheme,
+        leaf_hash: &[u8],
+    ) -> Self {
+
+        IncrementalVerify {
+            algorithm: algorithm,
+            hasher: None,
+            scheme: scheme,
+            hash: leaf_hash.to_vec(),
+            depth: 0,
+        }
+    }
+
+    /// Works like `IncrementalVerify::start_with_scheme`, for a tree hashed with the given
+    /// `hasher`, see `MerkleTreeBuilder::hasher`.
+    pub fn start_with_hasher<H>(hasher: H, scheme: HashingScheme, leaf_hash: &[u8]) -> Self
+    where
+        H: MerkleHasher + 'static,
+    {
+
+        let mut verify = IncrementalVerify::start_with_scheme(hasher.algorithm(), scheme, leaf_hash);
+        verify.hasher = Some(Arc::new(hasher));
+        verify
+    }
+
+    /// Combines the hash of the path so far with the given sibling hash, one level up.
+    ///
+    /// Fails with `Error::HashLengthMismatch` if the sibling hash does not have the output length
+    /// of the algorithm, and with `Error::InvalidPath` if the proof gets deeper than
+    /// `MAX_TREE_DEPTH`, leaving the verifier untouched, so that the transfer can be aborted.
+    pub fn push_level(&mut self, sibling: Positioned<&[u8]>) -> Result<(), Error> {
+        let expected = self.algorithm.output_len;
+        let found = sibling.as_ref().into_inner().len();
+
+        if found != expected {
+            return Err(Error::HashLengthMismatch {
+                expected: expected,
+                found: found,
+            });
+        }
+
+        if self.depth == MAX_TREE_DEPTH {
+            return Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"));
+        }
+
+        let parent = {
+            let hash = self.hash.as_slice();
+            let hasher = hasher::or_algorithm(&self.hasher, &self.algorithm);
+
+            match sibling {
+                Positioned::Left(left) => self.scheme.hash_nodes_with(hasher, &left, &hash),
+                Positioned::Right(right) => self.scheme.hash_nodes_with(hasher, &hash, &right),
+            }
+        };
+
+        self.hash.clear();
+        self.hash.extend_from_slice(parent.as_ref());
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    /// Returns the number of levels pushed so far.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the hash of the node reached so far, which is the leaf hash if no level was pushed.
+    pub fn current_hash(&self) -> &[u8] {
+        &self.hash
+    }
+
+    /// Checks whether the levels pushed lead from the leaf to the given `root_hash`.
+    pub fn finish(self, root_hash: &[u8]) -> bool {
+        self.hash.as_slice() == root_hash
+    }
+}
+
+/// Yields the levels of the inclusion proof of a leaf of a `MerkleTree`, from the leaf up,
+/// as consumed by `IncrementalVerify`, without building the proof: each sibling hash is looked
+/// up in the tree when it is requested. See `MerkleTree::proof_writer`.
+///
+/// Each level takes a descent from the root to look up, and the writer only holds
+/// the position of the leaf.
+#[allow(missing_debug_implementations)]
+pub struct IncrementalProofWriter<'a, T: 'a> {
+    tree: Subtree<'a, T>,
+    index: usize,
+    count: usize,
+    leaf_hash: &'a [u8],
+    remaining: usize,
+}
+
+impl<'a, T> IncrementalProofWriter<'a, T> {
+    /// Starts writing the proof of the leaf at the given `index` of `tree`, which has `count`
+    /// leaves, or returns `None` if there is no such leaf.
+    pub(crate) fn new(tree: Subtree<'a, T>, index: usize, count: usize) -> Option<Self> {
+        if index >= count {
+            return None;
+        }
+
+        let mut writer = IncrementalProofWriter {
+            tree: tree,
+            index: index,
+            count: count,
+            leaf_hash: tree.hash(),
+            remaining: 0,
+        };
+
+        let (leaf, _, _, depth) = writer.descend(usize::MAX);
+
+        match leaf.view() {
+            View::Leaf { hash, .. } => writer.leaf_hash = hash,
+            _ => return None,
+        }
+
+        writer.remaining = depth;
+        Some(writer)
+    }
+
+    /// Returns the hash of the leaf, to start an `IncrementalVerify` with.
+    pub fn leaf_hash(&self) -> &'a [u8] {
+        self.leaf_hash
+    }
+
+    /// Returns the root hash of the tree, for `IncrementalVerify::finish`.
+    pub fn root_hash(&self) -> &'a [u8] {
+        self.tree.hash()
+    }
+
+    /// Descends at most `levels` levels towards the leaf, stopping at a leaf or an empty tree.
+    ///
+    /// Returns the node reached, the index of the leaf among the leaves of that node,
+    /// their number, and the number of levels descended.
+    fn descend(&self, levels: usize) -> (Subtree<'a, T>, usize, usize, usize) {
+        let mut tree = self.tree;
+        let mut index = self.index;
+        let mut count = self.count;
+        let mut depth = 0;
+
+        while depth < levels {
+            match tree.view() {
+                View::Node { left, right, .. } => {
+                    let left_count = tree::left_count(right, count);
+
+                    if index < left_count {
+                        tree = left;
+                        count = left_count;
+                    } else {
+                        tree = right;
+                        index -= left_count;
+                        count -= left_count;
+                    }
+
+                    depth += 1;
+                }
+
+                _ => break,
+            }
+        }
+
+        (tree, index, count, depth)
+    }
+}
+
+impl<'a, T> Iterator for IncrementalProofWriter<'a, T> {
+    type Item = Positioned<&'a [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        self.remaining -= 1;
+
+        let (parent, index, count, _) = self.descend(self.remaining);
+
+        match parent.view() {
+            View::Node { left, right, .. } => {
+                if index < tree::left_count(right, count) {
+                    Some(Positioned::Right(right.hash()))
+                } else {
+                    Some(Positioned::Left(left.hash()))
+                }
+            }
+
+            _ => unreachable!("the nodes above the leaf are internal nodes"),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IncrementalProofWriter<'a, T> {}
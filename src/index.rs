@@ -0,0 +1,54 @@
+use error::Error;
+use hashutils::Hashable;
+use merkletree::MerkleTree;
+use prefix::PrefixProof;
+use proof::Proof;
+
+/// Converts a leaf index or a number of leaves, as held by the serialized formats and the
+/// proofs, which are `u64`, into a `usize`, failing with `Error::IndexOverflow` rather than
+/// truncating it if it does not fit, as on 32-bit targets.
+pub(crate) fn to_index(index: u64) -> Result<usize, Error> {
+    narrow(index, usize::MAX)
+}
+
+/// Works like `to_index` on a target whose `usize` goes up to `max`.
+pub(crate) fn narrow(index: u64, max: usize) -> Result<usize, Error> {
+    if index > max as u64 {
+        return Err(Error::IndexOverflow { index: index });
+    }
+
+    Ok(index as usize)
+}
+
+/// The methods taking or returning leaf indices and numbers of leaves as `u64`, as the proofs
+/// and the serialized formats do, so that the indices of trees of more than `2^32` leaves
+/// are not truncated on 32-bit targets: they fail with `Error::IndexOverflow` instead.
+impl<T> MerkleTree<T> {
+    /// Returns the number of leaves of the Merkle tree, as `MerkleTree::count` does.
+    pub fn count_u64(&self) -> u64 {
+        self.count() as u64
+    }
+
+    /// Works like `MerkleTree::try_gen_nth_proof`, given the index of the leaf as a `u64`.
+    pub fn gen_nth_proof_u64(&self, n: u64) -> Result<Option<Proof<T>>, Error>
+    where
+        T: Clone,
+    {
+
+        self.try_gen_nth_proof(to_index(n)?)
+    }
+
+    /// Works like `MerkleTree::update`, given the index of the leaf as a `u64`.
+    pub fn update_u64(&mut self, index: u64, value: T) -> Result<T, Error>
+    where
+        T: Hashable,
+    {
+
+        self.update(to_index(index)?, value)
+    }
+
+    /// Works like `MerkleTree::prove_prefix`, given the number of leaves of the prefix as a `u64`.
+    pub fn prove_prefix_u64(&self, prefix_len: u64) -> Result<Option<PrefixProof>, Error> {
+        Ok(self.prove_prefix(to_index(prefix_len)?))
+    }
+}
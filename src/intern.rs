@@ -0,0 +1,247 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use digest::Context;
+
+#[cfg(feature = "serialization-serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use builder::MerkleTreeBuilder;
+use hashutils::Hashable;
+use memory::HeapSize;
+use merkletree::MerkleTree;
+
+/// A value held once by an `InternTable`, shared by all the leaves holding an equal value.
+///
+/// It dereferences to the value, and is hashed, compared and serialized as the value,
+/// so that a tree of interned values has the same root hash as the tree of the values.
+/// `Interned::into_inner` gets the value back, cloning it if it is still shared.
+pub struct Interned<T>(Arc<T>);
+
+impl<T> Interned<T> {
+    /// Returns the value, cloning it unless this is the last handle on it.
+    pub fn into_inner(this: Self) -> T
+    where
+        T: Clone,
+    {
+
+        Arc::try_unwrap(this.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+
+    /// Returns whether both handles are on the same interned value.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(&this.0, &other.0)
+    }
+}
+
+impl<T> Clone for Interned<T> {
+    fn clone(&self) -> Self {
+        Interned(self.0.clone())
+    }
+}
+
+impl<T> Deref for Interned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> AsRef<T> for Interned<T> {
+    fn as_ref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> Borrow<T> for Interned<T> {
+    fn borrow(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Shows the value, as it would be shown without being interned.
+impl<T: fmt::Debug> fmt::Debug for Interned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (*self.0).fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Interned<T> {
+    fn eq(&self, other: &Interned<T>) -> bool {
+        Interned::ptr_eq(self, other) || *self.0 == *other.0
+    }
+}
+
+impl<T: Eq> Eq for Interned<T> {}
+
+impl<T: PartialOrd> PartialOrd for Interned<T> {
+    fn partial_cmp(&self, other: &Interned<T>) -> Option<Ordering> {
+        (*self.0).partial_cmp(&*other.0)
+    }
+}
+
+impl<T: Ord> Ord for Interned<T> {
+    fn cmp(&self, other: &Interned<T>) -> Ordering {
+        (*self.0).cmp(&*other.0)
+    }
+}
+
+impl<T: Hash> Hash for Interned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (*self.0).hash(state)
+    }
+}
+
+impl<T: Hashable> Hashable for Interned<T> {
+    fn update_context(&self, context: &mut Context) {
+        (*self.0).update_context(context);
+    }
+
+    fn hashed_bytes(&self) -> Option<&[u8]> {
+        (*self.0).hashed_bytes()
+    }
+}
+
+/// The handles on a value share the memory it holds, along with the counters of the `Arc`:
+/// each of them accounts for its share, so that the heap sizes of all the handles add up
+/// to the memory held by the value.
+impl<T: HeapSize> HeapSize for Interned<T> {
+    fn heap_size(&self) -> usize {
+        let shared = 2 * size_of::<usize>() + size_of::<T>() + self.0.heap_size();
+        shared / Arc::strong_count(&self.0)
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<T: Serialize> Serialize for Interned<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (*self.0).serialize(serializer)
+    }
+}
+
+/// Deserialized values are not interned, as there is no table to intern them into.
+#[cfg(feature = "serialization-serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Interned<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(|value| Interned(Arc::new(value)))
+    }
+}
+
+/// Holds one copy of every distinct value interned in it, see `InternTable::intern`.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use merkle::{MerkleTree, InternTable, Interned};
+/// # fn main() {
+/// let mut table = InternTable::new();
+/// let values = vec!["template".to_string(), "other".to_string(), "template".to_string()];
+///
+/// let mut tree = MerkleTree::from_vec(
+///     &merkle::digest::SHA256,
+///     values.iter().cloned().map(|value| table.intern(value)).collect(),
+/// );
+///
+/// tree.push(table.intern("template".to_string()));
+/// assert_eq!(table.len(), 2);
+///
+/// let leaves = tree.iter().collect::<Vec<_>>();
+/// assert!(Interned::ptr_eq(leaves[0], leaves[2]));
+/// assert!(Interned::ptr_eq(leaves[0], leaves[3]));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct InternTable<T> {
+    values: HashSet<Arc<T>>,
+}
+
+impl<T> Default for InternTable<T> {
+    fn default() -> Self {
+        InternTable {
+            values: HashSet::new(),
+        }
+    }
+}
+
+impl<T> InternTable<T> {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        InternTable::default()
+    }
+
+    /// Returns the number of distinct values in the table.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether the table holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns a handle on the copy of `value` held by the table, which is dropped
+    /// if the table already held an equal value, or which becomes the one held by the table.
+    pub fn intern(&mut self, value: T) -> Interned<T>
+    where
+        T: Eq + Hash,
+    {
+
+        if let Some(shared) = self.values.get(&value) {
+            return Interned(shared.clone());
+        }
+
+        let shared = Arc::new(value);
+        self.values.insert(shared.clone());
+
+        Interned(shared)
+    }
+
+    /// Drops the values which are no longer held by any handle but the table.
+    pub fn purge(&mut self) {
+        self.values.retain(|shared| Arc::strong_count(shared) > 1);
+    }
+}
+
+impl<T> MerkleTreeBuilder<Interned<T>> {
+    /// Builds a tree over the given values, holding one copy of every distinct value,
+    /// which the leaves holding an equal value share, see `Interned`.
+    ///
+    /// The leaves are hashed as their values, so that the tree has the same root hash
+    /// and the same proofs as the tree built over the values themselves. Their hashes
+    /// are held inline in the nodes, so that they take no allocation of their own.
+    ///
+    /// Panics if there are more than `max_leaves` values, see `try_build_from_iter`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::{MerkleTree, MerkleTreeBuilder, Interned};
+    /// # fn main() {
+    /// let values = vec!["template", "other", "template"];
+    ///
+    /// let tree = MerkleTreeBuilder::new(&merkle::digest::SHA256).build_interned(values.clone());
+    /// let plain = MerkleTree::from_vec(&merkle::digest::SHA256, values);
+    ///
+    /// assert_eq!(tree.root_hash(), plain.root_hash());
+    /// assert_eq!(tree.iter().map(|value| **value).collect::<Vec<_>>(), plain.iter().cloned().collect::<Vec<_>>());
+    /// # }
+    /// ```
+    pub fn build_interned<I>(self, values: I) -> MerkleTree<Interned<T>>
+    where
+        T: Hashable + Eq + Hash,
+        I: IntoIterator<Item = T>,
+    {
+
+        let mut table = InternTable::new();
+        self.build_from_iter(values.into_iter().map(|value| table.intern(value)))
+    }
+}
@@ -0,0 +1,236 @@
+//! Hashing of JSON values by their canonical encoding, so that two documents holding the same
+//! data hash the same whatever the order of their keys, their whitespace or the way their
+//! numbers are written.
+//!
+//! The canonical encoding follows the JSON Canonicalization Scheme of RFC 8785:
+//!
+//! - there is no whitespace between tokens;
+//! - the members of an object are sorted by key, the keys being compared as sequences
+//!   of UTF-16 code units;
+//! - strings are written as UTF-8, escaping `"`, `\` and the control characters only,
+//!   as `\b`, `\t`, `\n`, `\f` and `\r` where such escapes exist, and as `\u00xx`,
+//!   in lower case hexadecimal, otherwise;
+//! - numbers are read as IEEE 754 doubles, then written as ECMAScript writes them,
+//!   with the fewest digits reading back as the same double: `1`, `1.0` and `1e0` are all
+//!   written `1`, `-0` is written `0`, `0.000001` is written as such but `1e-7` is written
+//!   `1e-7`, and `1e21` is written `1e+21`.
+//!
+//! Integers beyond `2^53` in magnitude are hence rounded to the nearest double, as they are
+//! in JavaScript, so that `9007199254740993` is written `9007199254740992`. The feature enables
+//! the `float_roundtrip` feature of `serde_json`, without which parsing a number may give
+//! a neighbour of the nearest double, and hence another encoding.
+
+use std::fmt;
+
+use digest::Context;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::Error as SerError;
+use serde_json::Value;
+
+use error::Error;
+use hashutils::Hashable;
+
+/// Returns the canonical encoding of the given value, see the `json` module.
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match *value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(true) => out.push_str("true"),
+        Value::Bool(false) => out.push_str("false"),
+        Value::Number(ref number) => {
+            write_number(out, number.as_f64().expect("numbers are read as doubles or integers"));
+        }
+        Value::String(ref string) => write_string(out, string),
+        Value::Array(ref values) => {
+            out.push('[');
+
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+
+                write_value(out, value);
+            }
+
+            out.push(']');
+        }
+        Value::Object(ref members) => {
+            let mut members = members.iter().collect::<Vec<_>>();
+            members.sort_by(|&(a, _), &(b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+
+            out.push('{');
+
+            for (i, (key, value)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+
+                write_string(out, key);
+                out.push(':');
+                write_value(out, value);
+            }
+
+            out.push('}');
+        }
+    }
+}
+
+fn write_string(out: &mut String, string: &str) {
+    out.push('"');
+
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\u{c}' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+}
+
+/// Writes the given double as ECMAScript's `Number.prototype.toString` does.
+fn write_number(out: &mut String, number: f64) {
+    if number == 0.0 {
+        out.push('0');
+        return;
+    }
+
+    if number < 0.0 {
+        out.push('-');
+    }
+
+    // The shortest digits reading back as the number, as `d.ddde±x`
+    let shortest = format!("{:e}", number.abs());
+    let (mantissa, exponent) = shortest.split_at(shortest.find('e').expect("always has an exponent"));
+    let digits = mantissa.replace('.', "");
+    let exponent = exponent[1..].parse::<i32>().expect("the exponent is an integer");
+
+    // The number is `0.digits * 10^point`
+    let count = digits.len() as i32;
+    let point = exponent + 1;
+
+    if count <= point && point <= 21 {
+        out.push_str(&digits);
+        out.extend((count..point).map(|_| '0'));
+    } else if 0 < point && point <= 21 {
+        out.push_str(&digits[..point as usize]);
+        out.push('.');
+        out.push_str(&digits[point as usize..]);
+    } else if -6 < point && point <= 0 {
+        out.push_str("0.");
+        out.extend((point..0).map(|_| '0'));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+
+        if count > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+
+        out.push_str(&format!("e{}{}", if exponent < 0 { '-' } else { '+' }, exponent.abs()));
+    }
+}
+
+/// Hashes the value as its canonical encoding, see the `json` module.
+impl Hashable for Value {
+    fn update_context(&self, context: &mut Context) {
+        context.update(canonical_json(self).as_bytes());
+    }
+}
+
+/// A JSON value held as its canonical encoding, which is what it is hashed as, so that a tree
+/// of `JsonLeaf` values has the same hashes as one of the `serde_json::Value`s themselves,
+/// while hashing them in batches, see `Hashable::hashed_bytes`.
+///
+/// It converts to and from the bytes of the encoding, so that it fits in the value slot of
+/// the Protobuf messages, and is (de)serialized with serde as the JSON value it holds.
+/// Bytes read back are kept as they are, without being canonicalized, so that a proof of
+/// a value whose encoding was tampered with does not hold.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JsonLeaf {
+    bytes: Vec<u8>,
+}
+
+impl JsonLeaf {
+    /// Canonicalizes the given value.
+    pub fn new(value: &Value) -> Self {
+        JsonLeaf { bytes: canonical_json(value).into_bytes() }
+    }
+
+    /// Returns the encoding of the value, which is canonical unless it was read back from
+    /// bytes which are not.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Parses the value back, failing with `Error::Decode` if the bytes are not valid JSON.
+    pub fn to_value(&self) -> Result<Value, Error> {
+        serde_json::from_slice(&self.bytes).map_err(|err| Error::Decode(err.to_string()))
+    }
+}
+
+impl From<Value> for JsonLeaf {
+    fn from(value: Value) -> Self {
+        JsonLeaf::new(&value)
+    }
+}
+
+impl From<Vec<u8>> for JsonLeaf {
+    fn from(bytes: Vec<u8>) -> Self {
+        JsonLeaf { bytes: bytes }
+    }
+}
+
+impl From<JsonLeaf> for Vec<u8> {
+    fn from(leaf: JsonLeaf) -> Self {
+        leaf.bytes
+    }
+}
+
+impl AsRef<[u8]> for JsonLeaf {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Display for JsonLeaf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.bytes))
+    }
+}
+
+impl Hashable for JsonLeaf {
+    fn update_context(&self, context: &mut Context) {
+        context.update(&self.bytes);
+    }
+
+    fn hashed_bytes(&self) -> Option<&[u8]> {
+        Some(&self.bytes)
+    }
+}
+
+impl Serialize for JsonLeaf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value().map_err(S::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonLeaf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(JsonLeaf::from)
+    }
+}
@@ -0,0 +1,104 @@
+//! Trees and proofs hashed with Keccak-256, as used by Ethereum, which *ring* does not provide.
+//!
+//! Such trees are hashed as with `HashingScheme::SortedPairs`, so that the paths returned by
+//! `sorted_path` can be checked on chain by the `MerkleProof` library of OpenZeppelin.
+//! They have the shape of a `MerkleTree` built with the default `OddLeafPolicy`.
+//!
+//! Keccak-256 is known by the name `KECCAK256_NAME`, which is reserved in the registry of
+//! algorithm names, so that a proof naming it is never read as one of another algorithm,
+//! see `algorithm_from_name`.
+
+use tiny_keccak::{Hasher, Keccak};
+
+/// The output length of Keccak-256, in bytes.
+pub const OUTPUT_LEN: usize = 32;
+
+/// A Keccak-256 hash.
+pub type KeccakHash = [u8; OUTPUT_LEN];
+
+/// Computes the Keccak-256 hash of the given bytes, ie. the `keccak256` of Solidity.
+pub fn keccak256(bytes: &[u8]) -> KeccakHash {
+    let mut hasher = Keccak::v256();
+    let mut hash = [0; OUTPUT_LEN];
+
+    hasher.update(bytes);
+    hasher.finalize(&mut hash);
+    hash
+}
+
+/// Computes the hash of an internal node from the hashes of its children, in sorted order.
+pub fn hash_nodes(left: &KeccakHash, right: &KeccakHash) -> KeccakHash {
+    let (first, second) = if left <= right { (left, right) } else { (right, left) };
+
+    let mut hasher = Keccak::v256();
+    let mut hash = [0; OUTPUT_LEN];
+
+    hasher.update(first);
+    hasher.update(second);
+    hasher.finalize(&mut hash);
+    hash
+}
+
+/// Returns the levels of the tree over the given leaf hashes, from the leaves up to the root.
+fn levels(leaf_hashes: &[KeccakHash]) -> Vec<Vec<KeccakHash>> {
+    let mut levels = vec![leaf_hashes.to_vec()];
+
+    while levels[levels.len() - 1].len() > 1 {
+        let next = levels[levels.len() - 1]
+            .chunks(2)
+            .map(|pair| match *pair {
+                [ref left, ref right] => hash_nodes(left, right),
+                [ref node] => *node,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        levels.push(next);
+    }
+
+    levels
+}
+
+/// Returns the root hash of the tree over the given leaf hashes, or `None` if there are none.
+///
+/// The leaves are taken as is, as the leaves of a `SimpleMerkleTree` of OpenZeppelin:
+/// hash the values with `keccak256` beforehand if need be.
+pub fn root(leaf_hashes: &[KeccakHash]) -> Option<KeccakHash> {
+    if leaf_hashes.is_empty() {
+        return None;
+    }
+
+    levels(leaf_hashes).pop().and_then(|root| root.first().cloned())
+}
+
+/// Returns the proof of the leaf at the given `index` of the tree over the given leaf hashes:
+/// the sibling hashes from the leaf up, without their sides, see `Proof::sorted_path`.
+/// Returns `None` if there are at most `index` leaves.
+pub fn sorted_path(leaf_hashes: &[KeccakHash], index: usize) -> Option<Vec<KeccakHash>> {
+    if index >= leaf_hashes.len() {
+        return None;
+    }
+
+    let mut index = index;
+    let mut path = Vec::new();
+
+    for level in levels(leaf_hashes) {
+        // The last node of a level with an odd number of nodes is promoted, with no sibling
+        if let Some(sibling) = level.get(index ^ 1) {
+            path.push(*sibling);
+        }
+
+        index >>= 1;
+    }
+
+    Some(path)
+}
+
+/// Checks whether `path` is the proof that the leaf with hash `leaf_hash` is part of the tree
+/// with root hash `root_hash`, as `MerkleProof.verify` of OpenZeppelin does,
+/// see `validate_sorted_path`.
+pub fn validate_sorted_path(root_hash: &KeccakHash, leaf_hash: &KeccakHash, path: &[KeccakHash]) -> bool {
+    let computed = path.iter().fold(*leaf_hash, |hash, sibling| hash_nodes(&hash, sibling));
+
+    computed == *root_hash
+}
@@ -0,0 +1,137 @@
+
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use digest::Algorithm;
+
+use hashutils::Hashable;
+use options::TreeOptions;
+use tree::{Tree, Leaf};
+
+/// Hashes values into leaves, according to the options of the tree.
+type HashLeaves<T> = fn(&TreeOptions<T>, &'static Algorithm, Vec<T>) -> Vec<Leaf<T>>;
+
+/// The root of a `MerkleTree`, which is only built on first access for lazy trees.
+///
+/// The tree is built at most once, even when it is first accessed from several threads
+/// at the same time, the other threads waiting for it to be built.
+#[derive(Debug)]
+pub struct LazyRoot<T> {
+    tree: OnceLock<Tree<T>>,
+    pending: Mutex<Option<Pending<T>>>,
+}
+
+/// The values of a lazy tree which has not been built yet.
+#[derive(Clone, Debug)]
+pub struct Pending<T> {
+    pub values: Vec<T>,
+    pub hash_leaves: HashLeaves<T>,
+}
+
+impl<T> Pending<T> {
+    /// Holds the given values, to be hashed as leaves later on.
+    pub fn new(values: Vec<T>) -> Self
+    where
+        T: Hashable,
+    {
+
+        Pending {
+            values: values,
+            hash_leaves: |options, algorithm, values| options.leaves(algorithm, values).collect(),
+        }
+    }
+
+    /// Hashes the values into leaves.
+    pub fn into_leaves(self, options: &TreeOptions<T>, algorithm: &'static Algorithm) -> Vec<Leaf<T>> {
+        (self.hash_leaves)(options, algorithm, self.values)
+    }
+}
+
+impl<T> LazyRoot<T> {
+    /// Wraps an already built tree.
+    pub fn built(tree: Tree<T>) -> Self {
+        LazyRoot {
+            tree: OnceLock::from(tree),
+            pending: Mutex::new(None),
+        }
+    }
+
+    /// Defers building the tree over the given values until it is first accessed.
+    pub fn pending(pending: Pending<T>) -> Self {
+        LazyRoot {
+            tree: OnceLock::new(),
+            pending: Mutex::new(Some(pending)),
+        }
+    }
+
+    /// Returns whether the tree has been built.
+    pub fn is_built(&self) -> bool {
+        self.tree.get().is_some()
+    }
+
+    /// Returns the tree, if it has been built.
+    pub fn get_built(&self) -> Option<&Tree<T>> {
+        self.tree.get()
+    }
+
+    /// Returns the tree, building it with `build` if needed.
+    pub fn get<F>(&self, build: F) -> &Tree<T>
+    where
+        F: FnOnce(Pending<T>) -> Tree<T>,
+    {
+
+        self.tree.get_or_init(|| build(self.take_pending()))
+    }
+
+    /// Returns the tree, building it with `build` if needed.
+    pub fn get_mut<F>(&mut self, build: F) -> &mut Tree<T>
+    where
+        F: FnOnce(Pending<T>) -> Tree<T>,
+    {
+
+        self.get(build);
+        self.tree.get_mut().expect("the tree was just built")
+    }
+
+    /// Takes the tree out if it has been built, leaving this root to be replaced.
+    pub fn take_built(&mut self) -> Option<Tree<T>> {
+        self.tree.take()
+    }
+
+    /// Returns the tree, building it with `build` if needed.
+    pub fn into_tree<F>(self, build: F) -> Tree<T>
+    where
+        F: FnOnce(Pending<T>) -> Tree<T>,
+    {
+
+        let pending = self.pending;
+
+        self.tree.into_inner().unwrap_or_else(|| {
+            let pending = pending.into_inner().unwrap_or_else(PoisonError::into_inner);
+            build(pending.expect("the tree of a lazy `MerkleTree` failed to build"))
+        })
+    }
+
+    fn take_pending(&self) -> Pending<T> {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+            .expect("the tree of a lazy `MerkleTree` failed to build")
+    }
+}
+
+impl<T: Clone> Clone for LazyRoot<T> {
+    fn clone(&self) -> Self {
+        if let Some(ref pending) = *self.pending.lock().unwrap_or_else(PoisonError::into_inner) {
+            return LazyRoot::pending(pending.clone());
+        }
+
+        // The values are only taken out to build the tree, which is either built
+        // or being built by another thread, in which case `get_or_init` waits for it.
+        let tree = self.tree.get_or_init(|| {
+            panic!("the tree of a lazy `MerkleTree` failed to build")
+        });
+
+        LazyRoot::built(tree.clone())
+    }
+}
@@ -0,0 +1,73 @@
+#![deny(clippy::indexing_slicing, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::cell::Cell;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer};
+use serde::de::{self, Visitor};
+
+use multiproof::MultiProofNode;
+use proof::{Lemma, MAX_TREE_DEPTH};
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Deserializes the sub lemma of a `Lemma`, failing on a lemma chain with more than
+/// `MAX_TREE_DEPTH` nested sub lemmas, rather than recursing without bound on untrusted input,
+/// whatever the recursion limit of the format, if any.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Box<Lemma>>, D::Error> {
+    deserializer.deserialize_option(SubLemmaVisitor)
+}
+
+struct SubLemmaVisitor;
+
+impl<'de> Visitor<'de> for SubLemmaVisitor {
+    type Value = Option<Box<Lemma>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an optional sub lemma")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let _restore = descend("lemma")?;
+
+        Lemma::deserialize(deserializer).map(|lemma| Some(Box::new(lemma)))
+    }
+}
+
+/// Deserializes a child of a `MultiProofNode`, failing on nodes nested more than
+/// `MAX_TREE_DEPTH` levels below the root, as for the sub lemmas of a `Lemma`.
+pub fn node<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Box<MultiProofNode>, D::Error> {
+    let _restore = descend("multiproof node")?;
+
+    MultiProofNode::deserialize(deserializer).map(Box::new)
+}
+
+/// Goes one level deeper, failing beyond `MAX_TREE_DEPTH`, until the returned guard is dropped.
+fn descend<E: de::Error>(what: &str) -> Result<RestoreDepth, E> {
+    let depth = DEPTH.with(|depth| depth.get()) + 1;
+
+    if depth > MAX_TREE_DEPTH {
+        return Err(E::custom(format_args!("{} exceeds the maximum depth of {}", what, MAX_TREE_DEPTH)));
+    }
+
+    Ok(RestoreDepth(DEPTH.with(|current| current.replace(depth))))
+}
+
+/// Restores the depth of the enclosing lemma, even if deserialization fails or panics.
+struct RestoreDepth(usize);
+
+impl Drop for RestoreDepth {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(self.0));
+    }
+}
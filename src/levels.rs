@@ -0,0 +1,271 @@
+use std::cmp;
+use std::slice;
+
+use digest::Algorithm;
+
+#[cfg(feature = "serialization-serde")]
+use serde::{Deserialize, Deserializer};
+#[cfg(feature = "serialization-serde")]
+use serde::de;
+
+#[cfg(feature = "serialization-serde")]
+use error::Error;
+use hash::AlgorithmTag;
+#[cfg(feature = "serialization-serde")]
+use proof::MAX_TREE_DEPTH;
+use tree::Subtree;
+
+/// The hashes of the top levels of a `MerkleTree`, see `MerkleTree::export_levels`,
+/// which tell apart the buckets of leaves under the nodes of the lowest exported level
+/// that differ from those of another tree, see `LevelExport::diff`.
+///
+/// It records the number of leaves and the height of the tree, which give the leaves under
+/// each node, whatever the shape of the tree. When deserialized, the number of hashes
+/// of each level is checked against them, and every hash against the output length
+/// of the algorithm.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize))]
+pub struct LevelExport {
+    algorithm: AlgorithmTag,
+
+    count: usize,
+
+    height: usize,
+
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    root: Vec<u8>,
+
+    /// The hashes of the levels below the root, from the top down
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding::levels"))]
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+/// The leaves under a node of a `LevelExport` which differs from the same node of another one,
+/// see `LevelExport::diff`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct BucketRange {
+    /// The level of the node, the leaves being at level `0`, see `MerkleTree::node_hash`
+    pub level: usize,
+
+    /// The position of the node in its level
+    pub index: usize,
+
+    /// The index of the first leaf under the node
+    pub start: usize,
+
+    /// The index past the last leaf under the node, in the larger of both trees
+    pub end: usize,
+}
+
+impl LevelExport {
+    /// Exports the hashes of the nodes of the given `tree`, which has `count` leaves and the
+    /// given `height`, down to `depth` levels below the root, see `Subtree::node_at_level`.
+    pub(crate) fn new<T>(
+        algorithm: &'static Algorithm,
+        tree: Subtree<T>,
+        count: usize,
+        height: usize,
+        padded: bool,
+        depth: usize,
+    ) -> Self {
+
+        let levels = (1..cmp::min(depth, height) + 1)
+            .map(|depth| {
+                let level = height - depth;
+
+                (0..nodes_at_level(count, level))
+                    .map(|index| {
+                        tree.node_at_level(level, index, count, height, padded)
+                            .expect("every leaf is under a node of each level above the pruned subtrees")
+                            .hash()
+                            .to_vec()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        LevelExport {
+            algorithm: AlgorithmTag::of(algorithm),
+            count: count,
+            height: height,
+            root: tree.hash().to_vec(),
+            levels: levels,
+        }
+    }
+
+    /// Returns the tag of the algorithm the tree was built with.
+    pub fn algorithm(&self) -> AlgorithmTag {
+        self.algorithm
+    }
+
+    /// Returns the number of leaves of the tree.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the height of the tree.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the number of levels exported below the root, which is at most the height
+    /// of the tree.
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the root hash of the tree.
+    pub fn root_hash(&self) -> &Vec<u8> {
+        &self.root
+    }
+
+    /// Returns the hashes of the nodes `depth` levels below the root, from left to right,
+    /// or `None` if that level was not exported.
+    pub fn level(&self, depth: usize) -> Option<&[Vec<u8>]> {
+        match depth {
+            0 if self.count == 0 => Some(&[]),
+            0 => Some(slice::from_ref(&self.root)),
+            _ => self.levels.get(depth - 1).map(|hashes| hashes.as_slice()),
+        }
+    }
+
+    /// Returns the buckets of leaves under the nodes of this export which differ from those of
+    /// `other`, in order, or nothing if both trees have the same root and number of leaves.
+    ///
+    /// The nodes compared are those of the lowest level exported by both, counted from the
+    /// leaves up, so that each bucket covers the same leaves in both trees. A node which only
+    /// one of the trees has differs, and so does every node of trees built with different
+    /// algorithms or options.
+    pub fn diff(&self, other: &LevelExport) -> Vec<BucketRange> {
+        if self.algorithm == other.algorithm && self.count == other.count && self.root == other.root {
+            return Vec::new();
+        }
+
+        let level = cmp::max(self.lowest_level(), other.lowest_level());
+        let count = cmp::max(self.count, other.count);
+
+        (0..nodes_at_level(count, level))
+            .filter(|&index| self.hash_at(level, index) != other.hash_at(level, index))
+            .map(|index| {
+                let (start, end) = leaves_under(level, index, count);
+
+                BucketRange {
+                    level: level,
+                    index: index,
+                    start: start,
+                    end: end,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the level of the lowest exported nodes, the leaves being at level `0`.
+    fn lowest_level(&self) -> usize {
+        self.height - self.levels.len()
+    }
+
+    /// Returns the hash of the node at the given `level` and `index`, if it was exported,
+    /// as `MerkleTree::node_hash` would. The root is at every level above the height of the tree.
+    fn hash_at(&self, level: usize, index: usize) -> Option<&Vec<u8>> {
+        if self.count == 0 {
+            return None;
+        }
+
+        if level >= self.height {
+            return if index == 0 { Some(&self.root) } else { None };
+        }
+
+        self.levels
+            .get(self.height - level - 1)
+            .and_then(|hashes| hashes.get(index))
+    }
+}
+
+/// Returns the number of nodes at the given `level` of a tree with `count` leaves.
+pub(crate) fn nodes_at_level(count: usize, level: usize) -> usize {
+    match count {
+        0 => 0,
+        _ => (count - 1).checked_shr(level as u32).unwrap_or(0) + 1,
+    }
+}
+
+/// Returns the range of the leaves under the node at the given `level` and `index`
+/// of a tree with `count` leaves.
+pub(crate) fn leaves_under(level: usize, index: usize, count: usize) -> (usize, usize) {
+    match 1usize.checked_shl(level as u32) {
+        Some(width) => {
+            let start = index.saturating_mul(width);
+            (start, cmp::min(count, start.saturating_add(width)))
+        }
+
+        None => (0, count),
+    }
+}
+
+/// The serialized fields of a `LevelExport`, before they are checked.
+#[cfg(feature = "serialization-serde")]
+#[derive(Deserialize)]
+struct LevelExportData {
+    algorithm: AlgorithmTag,
+
+    count: usize,
+
+    height: usize,
+
+    #[serde(with = "::hash_encoding")]
+    root: Vec<u8>,
+
+    #[serde(with = "::hash_encoding::levels")]
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+#[cfg(feature = "serialization-serde")]
+impl LevelExportData {
+    fn check(&self) -> Result<(), Error> {
+        let hash_len = self.algorithm.algorithm().output_len;
+
+        if self.height > MAX_TREE_DEPTH || self.levels.len() > self.height {
+            return Err(Error::MalformedTree("too many levels"));
+        }
+
+        if nodes_at_level(self.count, self.height) > 1 {
+            return Err(Error::MalformedTree("too many leaves for the height"));
+        }
+
+        for (depth, hashes) in self.levels.iter().enumerate() {
+            if hashes.len() != nodes_at_level(self.count, self.height - depth - 1) {
+                return Err(Error::MalformedTree("level does not match the leaf count"));
+            }
+        }
+
+        let hashes = self.levels.iter().flatten();
+
+        match Some(&self.root).into_iter().chain(hashes).find(|hash| hash.len() != hash_len) {
+            Some(hash) => {
+                Err(Error::HashLengthMismatch {
+                    expected: hash_len,
+                    found: hash.len(),
+                })
+            }
+
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<'de> Deserialize<'de> for LevelExport {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = LevelExportData::deserialize(deserializer)?;
+        data.check().map_err(de::Error::custom)?;
+
+        Ok(LevelExport {
+            algorithm: data.algorithm,
+            count: data.count,
+            height: data.height,
+            root: data.root,
+            levels: data.levels,
+        })
+    }
+}
@@ -8,26 +8,276 @@
 
 //! *merkle* implements a Merkle Tree in Rust.
 
+#[cfg(feature = "backend-ring")]
 extern crate ring;
 
+#[cfg(feature = "backend-rustcrypto")]
+extern crate sha1;
+
+#[cfg(feature = "backend-rustcrypto")]
+extern crate sha2;
+
 #[cfg(feature = "serialization-protobuf")]
 extern crate protobuf;
 
+#[cfg(feature = "serialization-prost")]
+extern crate prost;
+
+#[cfg(any(feature = "serialization-serde", feature = "json"))]
+extern crate serde;
+
+#[cfg(feature = "serialization-serde")]
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(any(feature = "serialization-serde", feature = "json"))]
+extern crate serde_json;
+
+#[cfg(feature = "schemars")]
+#[macro_use]
+extern crate schemars;
+
+#[cfg(feature = "serialization-borsh")]
+extern crate borsh;
+
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+
+#[cfg(feature = "keccak")]
+extern crate tiny_keccak;
+
+#[cfg(feature = "metrics-log")]
+extern crate log;
+
+#[cfg(feature = "metrics-tracing")]
+extern crate tracing;
+
+pub mod digest;
+
+pub mod prelude;
+pub use prelude::{SHA1, SHA256, SHA384, SHA512, SHA512_256};
+
 mod merkletree;
-pub use merkletree::MerkleTree;
+pub use merkletree::{MerkleTree, DedupOrder, UpdateSummary};
+
+mod builder;
+pub use builder::MerkleTreeBuilder;
+
+mod options;
+pub use options::{HashingScheme, OddLeafPolicy, DEFAULT_PARALLEL_THRESHOLD};
+
+mod ordering;
+pub use ordering::LeafOrdering;
+
+mod lazy;
+
+mod memory;
+pub use memory::{MemoryReport, HeapSize};
+
+mod stats;
+pub use stats::TreeStats;
+
+mod visitor;
+pub use visitor::{TreeVisitor, TraversalOrder};
+
+mod nodes;
+pub use nodes::{NodeRef, PreOrderNodes, PostOrderNodes};
 
 mod proof;
-pub use proof::Proof;
+pub use proof::{Proof, ProofData, CanonicalProofData, Lemma, LemmaIter, Positioned, Direction, MAX_TREE_DEPTH,
+                validate_sorted_path};
+
+mod policy;
+pub use policy::ValidationPolicy;
+
+mod budget;
+pub use budget::{ValidationBudget, BudgetUsage, BatchReport, validate_batch_bounded};
+
+mod divergence;
+
+mod levels;
+pub use levels::{LevelExport, BucketRange};
+
+mod fingerprint;
+pub use fingerprint::{TreeFingerprint, RegionDiff};
+
+mod multiproof;
+pub use multiproof::{MultiProof, MultiProofNode, MultiProofReport};
+
+mod partial;
+pub use partial::PartialTree;
+
+mod prefix;
+pub use prefix::PrefixProof;
+
+mod checkpoint;
+pub use checkpoint::VersionId;
+
+mod proof_cache;
+pub use proof_cache::CacheStats;
+
+mod verifier;
+pub use verifier::{CachingVerifier, VerifierStats};
+
+mod verify;
+pub use verify::{ProofFormat, verify_proof_bytes};
+#[cfg(feature = "serialization-serde")]
+pub use verify::verify_json_proof;
+
+mod all_proofs;
+pub use all_proofs::AllProofs;
+
+mod proven;
+pub use proven::{ProvenIter, IntoProvenIter};
+
+mod incremental;
+pub use incremental::{IncrementalVerify, IncrementalProofWriter};
+
+mod display;
+pub use display::{DisplayOptions, ProofDisplay, FullDebug};
 
 mod hashutils;
-pub use hashutils::Hashable;
+pub use hashutils::{Hashable, TryHashable, DynHashable};
+
+mod hash;
+pub use hash::{AlgorithmTag, NodeHash, RootHash};
+
+mod small_hash;
+
+mod hasher;
+pub use hasher::{MerkleHasher, KeyedHasher};
+
+mod defaults;
+pub use defaults::default_hashes;
+
+mod registry;
+pub use registry::{algorithm_name, algorithm_from_name, register_algorithm, KECCAK256_NAME};
+
+mod meta;
+pub use meta::WithMeta;
+
+mod forest;
+pub use forest::Forest;
+
+mod dual;
+pub use dual::DualAlgorithmTree;
+
+mod appender;
+pub use appender::ConcurrentAppender;
+
+mod audit;
+pub use audit::{AuditLog, AppendReceipt, verify_receipt};
+
+mod tree_head;
+pub use tree_head::{TreeHead, TREE_HEAD_VERSION};
+
+mod streaming;
+pub use streaming::{StreamingLeaf, StreamSource, STREAMING_CHUNK_SIZE};
+
+mod bundle;
+pub use bundle::{ProofBundle, TrustPolicy, SignatureVerifier, BUNDLE_MAGIC, BUNDLE_VERSION};
+
+mod cas;
+pub use cas::{UpdateReceipt, CasError};
+
+mod extract;
+
+mod recovery;
+pub use recovery::RebuildError;
+
+mod stale;
+
+mod manifest;
+
+mod index;
+pub use manifest::{ManifestReport, ManifestStatus};
+
+mod frontier;
+pub use frontier::{Frontier, AppendOnlyTree};
+
+mod txn;
+pub use txn::TreeTxn;
+
+mod window;
+pub use window::SlidingWindowTree;
+
+mod intern;
+pub use intern::{InternTable, Interned};
+
+mod map;
+pub use map::{MerkleMap, KvProof, KvAbsenceProof, KvNeighbor};
 
 mod tree;
-pub use tree::{LeavesIterator, LeavesIntoIterator};
+pub use tree::{LeavesIterator, LeavesIntoIterator, LeafHashes};
+
+mod error;
+pub use error::{Error, HashError, IoError, ConversionError};
+
+pub mod wire;
+
+pub mod snapshot;
+
+#[cfg_attr(not(any(feature = "serialization-serde", feature = "wasm")), allow(dead_code))]
+mod hex;
 
 #[cfg(feature = "serialization-protobuf")]
 #[allow(unused_qualifications)]
-mod proto;
+pub mod proto;
+
+#[cfg(feature = "serialization-prost")]
+pub mod prost_proto;
+
+#[cfg(feature = "serialization-serde")]
+mod hash_encoding;
+#[cfg(feature = "serialization-serde")]
+pub use hash_encoding::{HashEncoding, Encoded};
+
+#[cfg(feature = "serialization-serde")]
+mod lemma_depth;
+
+#[cfg(feature = "schemars")]
+mod schema;
+
+#[cfg(feature = "serialization-serde")]
+mod envelope;
+#[cfg(feature = "serialization-serde")]
+pub use envelope::{ProofEnvelope, ENVELOPE_VERSION};
+
+#[cfg(feature = "serialization-serde")]
+mod tagged;
+#[cfg(feature = "serialization-serde")]
+pub use tagged::TaggedProof;
+
+#[cfg(feature = "serialization-serde")]
+pub mod mtjs;
+
+#[cfg(feature = "serialization-borsh")]
+mod borsh_impls;
+#[cfg(feature = "serialization-borsh")]
+pub use borsh_impls::BORSH_MAX_LEMMA_DEPTH;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "keccak")]
+pub mod keccak;
+
+#[cfg(feature = "metrics")]
+mod observer;
+#[cfg(feature = "metrics")]
+pub use observer::{TreeObserver, Metrics, MetricsObserver};
+#[cfg(feature = "metrics-log")]
+pub use observer::LogObserver;
+#[cfg(feature = "metrics-tracing")]
+pub use observer::TracingObserver;
+
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "json")]
+pub use json::JsonLeaf;
 
 #[cfg(test)]
 mod tests;
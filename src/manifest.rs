@@ -0,0 +1,200 @@
+use std::thread;
+
+use merkletree::{MerkleTree, PARALLEL_THRESHOLD};
+use small_hash::SmallHash;
+
+/// How an entry of a manifest compares to the leaf of a `MerkleTree` it names,
+/// see `MerkleTree::verify_manifest`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ManifestStatus {
+    /// The leaf has the expected hash
+    Match,
+
+    /// The leaf has another hash, such as one of another length than the expected one
+    Mismatch {
+        /// The hash of the leaf
+        found: Vec<u8>,
+    },
+
+    /// The tree has no leaf at the index of the entry
+    OutOfRange,
+
+    /// The leaf belongs to a pruned subtree, so that its hash is not known,
+    /// see `MerkleTree::prune_below`
+    Pruned,
+}
+
+/// The result of checking the leaf hashes of a `MerkleTree` against a manifest,
+/// see `MerkleTree::verify_manifest`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ManifestReport {
+    /// The index and the status of every entry, in the order of the manifest
+    pub entries: Vec<(usize, ManifestStatus)>,
+
+    /// The number of entries matching their leaf
+    pub matched: usize,
+
+    /// The number of entries not matching their leaf
+    pub mismatched: usize,
+
+    /// The number of entries naming a leaf the tree does not have
+    pub out_of_range: usize,
+
+    /// The number of entries naming a leaf of a pruned subtree
+    pub pruned: usize,
+
+    /// The indices of the leaves no entry names, in increasing order,
+    /// only filled in by `MerkleTree::verify_manifest_strict`
+    pub missing: Vec<usize>,
+
+    /// The indices named by several entries, in increasing order,
+    /// only filled in by `MerkleTree::verify_manifest_strict`
+    pub duplicated: Vec<usize>,
+}
+
+impl ManifestReport {
+    /// Returns whether every entry matches its leaf, and, for the reports of
+    /// `MerkleTree::verify_manifest_strict`, whether every leaf is named by exactly one entry.
+    pub fn is_valid(&self) -> bool {
+        self.matched == self.entries.len() && self.missing.is_empty() && self.duplicated.is_empty()
+    }
+
+    /// Returns the entries which do not match their leaf, in the order of the manifest.
+    pub fn failures(&self) -> Vec<&(usize, ManifestStatus)> {
+        self.entries
+            .iter()
+            .filter(|&&(_, ref status)| *status != ManifestStatus::Match)
+            .collect()
+    }
+}
+
+impl<T> MerkleTree<T> {
+    /// Checks every `(index, expected_leaf_hash)` entry of the given manifest against the hash
+    /// of the leaf at that index, reporting the status of all the entries rather than stopping
+    /// at the first mismatch, see `ManifestReport`.
+    ///
+    /// The manifest need not name every leaf, nor name them in order, see
+    /// `MerkleTree::verify_manifest_strict` to require it to name every leaf exactly once.
+    /// The entries are checked across as many threads as are available if the tree was built
+    /// with `MerkleTreeBuilder::parallel` and the manifest is large enough for it to pay off.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::{MerkleTree, ManifestStatus};
+    /// # fn main() {
+    /// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c"]);
+    /// let hashes = tree.leaf_hashes_vec();
+    ///
+    /// let report = tree.verify_manifest(vec![(2, hashes[2].clone()), (0, hashes[1].clone()), (3, hashes[0].clone())]);
+    ///
+    /// assert_eq!(report.matched, 1);
+    /// assert_eq!(report.entries[1], (0, ManifestStatus::Mismatch { found: hashes[0].clone() }));
+    /// assert_eq!(report.entries[2], (3, ManifestStatus::OutOfRange));
+    /// assert!(!report.is_valid());
+    /// # }
+    /// ```
+    pub fn verify_manifest<I>(&self, entries: I) -> ManifestReport
+    where
+        I: IntoIterator<Item = (usize, Vec<u8>)>,
+    {
+
+        let entries = entries.into_iter().collect::<Vec<_>>();
+
+        let mut leaves = vec![None; self.count()];
+
+        for (index, hash) in self.tree().indexed_leaf_hashes() {
+            leaves[index] = Some(hash);
+        }
+
+        let leaves = &leaves;
+
+        let check = |entries: &[(usize, Vec<u8>)]| {
+            entries
+                .iter()
+                .map(|&(index, ref expected)| (index, status(leaves, index, expected)))
+                .collect::<Vec<_>>()
+        };
+
+        let statuses = if !self.options().parallel || entries.len() < PARALLEL_THRESHOLD {
+            check(&entries)
+        } else {
+            let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+            let chunk_len = entries.len() / threads + 1;
+
+            let check = &check;
+
+            thread::scope(|scope| {
+                let handles = entries
+                    .chunks(chunk_len)
+                    .map(|chunk| scope.spawn(move || check(chunk)))
+                    .collect::<Vec<_>>();
+
+                handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().expect("comparing hashes does not panic"))
+                    .collect()
+            })
+        };
+
+        let mut report = ManifestReport::default();
+
+        for &(_, ref status) in &statuses {
+            match *status {
+                ManifestStatus::Match => report.matched += 1,
+                ManifestStatus::Mismatch { .. } => report.mismatched += 1,
+                ManifestStatus::OutOfRange => report.out_of_range += 1,
+                ManifestStatus::Pruned => report.pruned += 1,
+            }
+        }
+
+        report.entries = statuses;
+        report
+    }
+
+    /// Works like `MerkleTree::verify_manifest`, but also requires the manifest to name every
+    /// leaf exactly once, listing the leaves it leaves out in `ManifestReport::missing`,
+    /// and the indices it names more than once in `ManifestReport::duplicated`.
+    pub fn verify_manifest_strict<I>(&self, entries: I) -> ManifestReport
+    where
+        I: IntoIterator<Item = (usize, Vec<u8>)>,
+    {
+
+        let mut report = self.verify_manifest(entries);
+
+        let mut indices = report.entries.iter().map(|&(index, _)| index).collect::<Vec<_>>();
+        indices.sort_unstable();
+
+        for pair in indices.windows(2) {
+            if pair[0] == pair[1] && report.duplicated.last() != Some(&pair[0]) {
+                report.duplicated.push(pair[0]);
+            }
+        }
+
+        indices.dedup();
+
+        let mut named = indices.into_iter().peekable();
+
+        for index in 0..self.count() {
+            if named.peek() == Some(&index) {
+                named.next();
+            } else {
+                report.missing.push(index);
+            }
+        }
+
+        report
+    }
+}
+
+/// Returns the status of the entry naming the leaf at `index` among the given `leaves`,
+/// `None` standing for the leaves of pruned subtrees.
+fn status(leaves: &[Option<&SmallHash>], index: usize, expected: &[u8]) -> ManifestStatus {
+    match leaves.get(index) {
+        None => ManifestStatus::OutOfRange,
+        Some(&None) => ManifestStatus::Pruned,
+        Some(&Some(hash)) if hash.as_slice() == expected => ManifestStatus::Match,
+        Some(&Some(hash)) => ManifestStatus::Mismatch { found: hash.to_vec() },
+    }
+}
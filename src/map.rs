@@ -0,0 +1,341 @@
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+
+use digest::{Algorithm, Context, Digest};
+
+use builder::MerkleTreeBuilder;
+use error::Error;
+use hash::RootHash;
+use hashutils::{Hashable, HashUtils};
+use merkletree::MerkleTree;
+use proof::{Proof, Lemma, Direction};
+
+/// An authenticated key-value map, whose entries are the leaves of a `MerkleTree`,
+/// sorted by key, so that both the presence of a key with its value and the absence of a key
+/// can be proven, see `MerkleMap::prove` and `MerkleMap::prove_absent`.
+///
+/// The leaf of an entry binds its key and its value together: its value is the hash of the key
+/// followed by the hash of the value, both computed with the algorithm of the map over the input
+/// fed by `Hashable::update_context`, without any prefix. As these hashes have a fixed length,
+/// no two entries have the same leaf, whatever their keys and values. The leaves are then hashed
+/// into the tree as those of any `MerkleTree` built with the default options.
+///
+/// The tree is rehashed on the first access to a hash or a proof following a modification.
+#[derive(Clone)]
+pub struct MerkleMap<K, V> {
+    algorithm: &'static Algorithm,
+
+    /// The entries, along with their leaf
+    entries: BTreeMap<K, (V, Vec<u8>)>,
+
+    /// The tree over the leaves of the entries, in the order of their keys
+    tree: MerkleTree<Vec<u8>>,
+}
+
+/// The proof that a key of a `MerkleMap` maps to a value, see `MerkleMap::prove`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct KvProof<K, V> {
+    /// The key the proof was generated for
+    pub key: K,
+
+    /// The value of the key when the proof was generated
+    pub value: V,
+
+    /// The path from the root of the map to the leaf of the entry
+    pub lemma: Lemma,
+}
+
+/// The proof that a key is absent from a `MerkleMap`, see `MerkleMap::prove_absent`.
+///
+/// It holds the entries right before and right after the key in the map, if any, which are
+/// proven to be adjacent leaves of the tree. The proof of a key absent from an empty map holds
+/// neither of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct KvAbsenceProof<K> {
+    /// The entry with the greatest key lower than the absent key, if any
+    pub lower: Option<KvNeighbor<K>>,
+
+    /// The entry with the lowest key greater than the absent key, if any
+    pub upper: Option<KvNeighbor<K>>,
+}
+
+/// An entry of a `MerkleMap` next to an absent key, see `KvAbsenceProof`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct KvNeighbor<K> {
+    /// The key of the entry
+    pub key: K,
+
+    /// The hash of the value of the entry, which is all its leaf needs
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    pub value_hash: Vec<u8>,
+
+    /// The path from the root of the map to the leaf of the entry
+    pub lemma: Lemma,
+}
+
+impl<K: Ord + Hashable, V: Hashable> MerkleMap<K, V> {
+    /// Creates an empty map, whose root hash is the hash of the empty string.
+    pub fn new(algorithm: &'static Algorithm) -> Self {
+        MerkleMap {
+            algorithm: algorithm,
+            entries: BTreeMap::new(),
+            tree: MerkleTree::empty(algorithm),
+        }
+    }
+
+    /// Returns the number of entries of the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the map has no entry.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the value of the given key, if any.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+
+        self.entries.get(key).map(|(value, _)| value)
+    }
+
+    /// Maps the given key to the given value, returning the previous value of the key, if any.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let leaf = leaf(self.algorithm, &key, &value);
+        let previous = self.entries.insert(key, (value, leaf));
+
+        self.rebuild();
+        previous.map(|(value, _)| value)
+    }
+
+    /// Removes the given key from the map, returning its value, if any.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+
+        let removed = self.entries.remove(key)?;
+
+        self.rebuild();
+        Some(removed.0)
+    }
+
+    /// Creates an `Iterator` over the entries of the map, in the order of their keys.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, (value, _))| (key, value))
+    }
+
+    /// Returns the root hash of the map, which is the hash of the empty string if it is empty.
+    pub fn root_hash(&self) -> &Vec<u8> {
+        self.tree.root_hash()
+    }
+
+    /// Returns the root hash of the map, along with its algorithm.
+    pub fn root(&self) -> RootHash {
+        self.tree.root()
+    }
+
+    /// Generates the proof that the given key maps to its value,
+    /// or `None` if the key is absent from the map, see `MerkleMap::prove_absent`.
+    pub fn prove(&self, key: &K) -> Option<KvProof<K, V>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+
+        let value = self.get(key)?.clone();
+
+        Some(KvProof {
+            key: key.clone(),
+            value: value,
+            lemma: self.lemma(self.index_of(key))?,
+        })
+    }
+
+    /// Generates the proof that the given key is absent from the map,
+    /// or `None` if the key is in the map, see `MerkleMap::prove`.
+    pub fn prove_absent(&self, key: &K) -> Option<KvAbsenceProof<K>>
+    where
+        K: Clone,
+    {
+
+        if self.entries.contains_key(key) {
+            return None;
+        }
+
+        let index = self.index_of(key);
+        let below = self.entries.range((Bound::Unbounded, Bound::Excluded(key))).next_back();
+        let above = self.entries.range((Bound::Excluded(key), Bound::Unbounded)).next();
+
+        let lower = match below {
+            Some((key, (_, leaf))) => Some(self.neighbor(key, leaf, index - 1)?),
+            None => None,
+        };
+
+        let upper = match above {
+            Some((key, (_, leaf))) => Some(self.neighbor(key, leaf, index)?),
+            None => None,
+        };
+
+        Some(KvAbsenceProof {
+            lower: lower,
+            upper: upper,
+        })
+    }
+
+    /// Returns the index of the leaf of the given key, or the one it would have if inserted.
+    fn index_of(&self, key: &K) -> usize {
+        self.entries.range((Bound::Unbounded, Bound::Excluded(key))).count()
+    }
+
+    fn lemma(&self, index: usize) -> Option<Lemma> {
+        self.tree.gen_nth_proof(index).map(|proof| proof.lemma)
+    }
+
+    fn neighbor(&self, key: &K, leaf: &[u8], index: usize) -> Option<KvNeighbor<K>>
+    where
+        K: Clone,
+    {
+
+        Some(KvNeighbor {
+            key: key.clone(),
+            value_hash: leaf[self.algorithm.output_len..].to_vec(),
+            lemma: self.lemma(index)?,
+        })
+    }
+
+    /// Rebuilds the tree over the leaves of the entries, which is only hashed when next needed.
+    fn rebuild(&mut self) {
+        let leaves = self.entries.values().map(|(_, leaf)| leaf.clone()).collect();
+        self.tree = MerkleTreeBuilder::new(self.algorithm).lazy(true).build_from_vec(leaves);
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for MerkleMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|(key, (value, _))| (key, value)))
+            .finish()
+    }
+}
+
+impl<K: Hashable, V: Hashable> KvProof<K, V> {
+    /// Checks that `key` maps to `expected_value` in the map of the given root hash,
+    /// hashing them rather than the key and the value of this proof.
+    ///
+    /// Fails with `Error::HashLengthMismatch` if a hash of this proof does not have the output
+    /// length of the algorithm of `root_hash`, and with `Error::InvalidProof` if the proof does
+    /// not hold, such as when the key maps to another value.
+    pub fn verify(&self, root_hash: &RootHash, key: &K, expected_value: &V) -> Result<(), Error> {
+        let algorithm = root_hash.algorithm().algorithm();
+
+        verify_leaf(root_hash, &self.lemma, &leaf(algorithm, key, expected_value))
+    }
+}
+
+impl<K: Ord + Hashable> KvAbsenceProof<K> {
+    /// Checks that `key` is absent from the map of the given root hash: that the neighbors
+    /// of this proof are entries of the map, on either side of `key`, whose leaves are adjacent,
+    /// or that the map is empty if there are none.
+    ///
+    /// Fails as `KvProof::verify` does.
+    pub fn verify(&self, root_hash: &RootHash, key: &K) -> Result<(), Error> {
+        let algorithm = root_hash.algorithm().algorithm();
+
+        for neighbor in self.lower.iter().chain(self.upper.iter()) {
+            if neighbor.value_hash.len() != algorithm.output_len {
+                return Err(Error::HashLengthMismatch {
+                    expected: algorithm.output_len,
+                    found: neighbor.value_hash.len(),
+                });
+            }
+
+            let mut leaf = hash(algorithm, &neighbor.key).as_ref().to_vec();
+            leaf.extend_from_slice(&neighbor.value_hash);
+
+            verify_leaf(root_hash, &neighbor.lemma, &leaf)?;
+        }
+
+        let lower = self.lower.as_ref().map(|neighbor| (&neighbor.key, path(&neighbor.lemma)));
+        let upper = self.upper.as_ref().map(|neighbor| (&neighbor.key, path(&neighbor.lemma)));
+
+        let adjacent = match (lower, upper) {
+            (None, None) => root_hash.as_bytes() == algorithm.hash_empty().as_ref(),
+
+            (Some((lower, ref path)), None) => {
+                lower < key && path.iter().all(|&direction| direction == Direction::Right)
+            }
+
+            (None, Some((upper, ref path))) => {
+                key < upper && path.iter().all(|&direction| direction == Direction::Left)
+            }
+
+            (Some((lower, ref lower_path)), Some((upper, ref upper_path))) => {
+                lower < key && key < upper && are_adjacent(lower_path, upper_path)
+            }
+        };
+
+        if !adjacent {
+            return Err(Error::InvalidProof);
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes the hash of the given value, without any prefix.
+fn hash<T: Hashable>(algorithm: &'static Algorithm, value: &T) -> Digest {
+    let mut context = Context::new(algorithm);
+    value.update_context(&mut context);
+    context.finish()
+}
+
+/// Returns the value of the leaf of an entry: the hash of its key followed by the hash
+/// of its value.
+fn leaf<K: Hashable, V: Hashable>(algorithm: &'static Algorithm, key: &K, value: &V) -> Vec<u8> {
+    let mut leaf = hash(algorithm, key).as_ref().to_vec();
+    leaf.extend_from_slice(hash(algorithm, value).as_ref());
+    leaf
+}
+
+/// Checks that the given lemma leads from `root_hash` to the leaf with the given value.
+fn verify_leaf(root_hash: &RootHash, lemma: &Lemma, leaf: &[u8]) -> Result<(), Error> {
+    let algorithm = root_hash.algorithm().algorithm();
+
+    if lemma.leaf_hash() != algorithm.hash_leaf(&leaf).as_ref() {
+        return Err(Error::InvalidProof);
+    }
+
+    Proof::new(algorithm, root_hash.as_bytes().to_vec(), lemma.clone(), ()).verify(root_hash)
+}
+
+/// Returns the branches taken by the given lemma chain, from the root down.
+fn path(lemma: &Lemma) -> Vec<Direction> {
+    lemma.siblings().map(|sibling| sibling.direction().flip()).collect()
+}
+
+/// Tells whether the paths lead to adjacent leaves: the lower one must be the rightmost leaf
+/// of the left child of the node where they part, and the upper one the leftmost leaf
+/// of its right child.
+fn are_adjacent(lower: &[Direction], upper: &[Direction]) -> bool {
+    let common = lower.iter().zip(upper).take_while(|&(a, b)| a == b).count();
+
+    match (lower.get(common), upper.get(common)) {
+        (Some(&Direction::Left), Some(&Direction::Right)) => {
+            lower[common + 1..].iter().all(|&direction| direction == Direction::Right) &&
+                upper[common + 1..].iter().all(|&direction| direction == Direction::Left)
+        }
+
+        _ => false,
+    }
+}
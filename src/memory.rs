@@ -0,0 +1,103 @@
+
+use tree::{Tree, View};
+
+/// An estimate of the memory held by a `MerkleTree`, as returned by `MerkleTree::memory_footprint`.
+///
+/// Allocator overhead and the memory held by the tree options are not accounted for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MemoryReport {
+    /// The number of nodes in the tree, leaves included.
+    pub nodes: usize,
+
+    /// The number of leaves in the tree, leaving out those of the pruned subtrees,
+    /// see `MerkleTree::prune_below`.
+    pub leaves: usize,
+
+    /// The number of bytes taken by the hashes of the nodes, which are held inline in them.
+    pub hash_bytes: usize,
+
+    /// The number of bytes allocated on the heap by the values.
+    /// Their inline size is part of `node_bytes`.
+    pub value_bytes: usize,
+
+    /// The number of bytes taken by the nodes themselves, and by the `MerkleTree` holding them,
+    /// leaving out `hash_bytes`.
+    pub node_bytes: usize,
+}
+
+impl MemoryReport {
+    /// Returns the total number of bytes held by the tree.
+    pub fn total(&self) -> usize {
+        self.hash_bytes + self.value_bytes + self.node_bytes
+    }
+}
+
+/// The number of bytes a value holds on the heap, used by `MerkleTree::memory_footprint`.
+pub trait HeapSize {
+    /// Returns the number of bytes allocated on the heap by this value,
+    /// not including `mem::size_of_val(self)`.
+    fn heap_size(&self) -> usize;
+}
+
+impl HeapSize for Vec<u8> {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl HeapSize for Box<[u8]> {
+    fn heap_size(&self) -> usize {
+        self.len()
+    }
+}
+
+/// References do not own the memory they point to.
+impl<'a, T: ?Sized> HeapSize for &'a T {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for () {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+/// Walks the given tree, which is held inline in a structure of `inline_size` bytes.
+pub(crate) fn measure<T, F>(tree: &Tree<T>, inline_size: usize, value_heap: F) -> MemoryReport
+where
+    F: Fn(&T) -> usize,
+{
+
+    let mut report = MemoryReport::default();
+    let mut stack = vec![tree.root()];
+
+    while let Some(node) = stack.pop() {
+        report.nodes += 1;
+        report.hash_bytes += node.hash().len();
+
+        match node.view() {
+            View::Empty { .. } | View::Pruned { .. } => {}
+
+            View::Leaf { value, .. } => {
+                report.leaves += 1;
+                report.value_bytes += value_heap(value);
+            }
+
+            View::Node { left, right, .. } => {
+                stack.push(right);
+                stack.push(left);
+            }
+        }
+    }
+
+    report.node_bytes = inline_size + tree.node_bytes() - report.hash_bytes;
+    report
+}
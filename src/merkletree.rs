@@ -1,35 +1,118 @@
 
 use std::hash::{Hash, Hasher};
 use std::cmp::Ordering;
+use std::fmt;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead};
+use std::mem::{self, size_of};
+use std::thread;
 
-use ring::digest::Algorithm;
+use digest::{Algorithm, Digest};
 
-use tree::{Tree, LeavesIterator, LeavesIntoIterator};
-use hashutils::{Hashable, HashUtils};
+#[cfg(feature = "serialization-serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use proof::{Proof, Lemma};
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use observer::TreeObserver;
+
+use error::Error;
+use tree::{self, Tree, Leaf, NodeId, LeavesIterator, LeavesIntoIterator, LeafHashes, ceil_log2};
+use hashutils::{Hashable, HashUtils, TryHashable};
+use ordering::LeafOrdering;
+use options::{HashingScheme, OddLeafPolicy, TreeOptions};
+use builder::MerkleTreeBuilder;
+use memory::{self, HeapSize, MemoryReport};
+use stats::{self, TreeStats};
+use visitor::{self, TreeVisitor, TraversalOrder};
+use nodes::{PreOrderNodes, PostOrderNodes};
+use lazy::{LazyRoot, Pending};
+use proof_cache::{CacheStats, ProofCache};
+use checkpoint::{Checkpoints, VersionId};
+use multiproof::MultiProof;
+use incremental::IncrementalProofWriter;
+use all_proofs::AllProofs;
+use prefix::{self, PrefixProof};
+use divergence;
+use levels::LevelExport;
+use display::{self, FullDebug, Raw};
+use hash::{NodeHash, RootHash};
+use small_hash::SmallHash;
+use registry::algorithm_name;
+use wire::algorithm_tag;
+
+use proof::{Proof, Lemma, Positioned};
 
 /// A Merkle tree is a binary tree, with values of type `T` at the leafs,
 /// and where every internal node holds the hash of the concatenation of the hashes of its children nodes.
-#[derive(Clone, Debug)]
+///
+/// When serialized, the `algorithm` is skipped, and must be supplied back when deserializing
+/// with `MerkleTree::deserialize_verified` or `MerkleTree::deserialize_unverified`.
+/// The options set with `MerkleTreeBuilder` are skipped too, and deserialized trees have the
+/// default ones.
+///
+/// The `Debug` output only shows the first levels of the tree, with shortened hashes,
+/// eliding the values with a long `Debug` output, see `MerkleTree::debug_full`.
+///
+/// The tree is `Send` and `Sync` when `T` is, and `UnwindSafe` when `T` is. The mutating methods
+/// hash and compare the values before modifying the tree, so that a panic in code of the values,
+/// such as a `Hashable` implementation, or in a closure such as the one of `MerkleTree::retain`,
+/// leaves the tree untouched. A lazy tree whose values panic while being hashed on first access
+/// panics again on every later access. A `MerkleHasher` must not panic though, as the nodes are
+/// rehashed in place.
+#[derive(Clone)]
 pub struct MerkleTree<T> {
     /// The hashing algorithm used by this Merkle tree
     pub algorithm: &'static Algorithm,
 
-    /// The root of the inner binary tree
-    root: Tree<T>,
+    /// The root of the inner binary tree, only built on first access for lazy trees
+    root: LazyRoot<T>,
 
     /// The height of the tree
     height: usize,
 
     /// The number of leaf nodes in the tree
     count: usize,
+
+    /// The options the tree was built with
+    options: TreeOptions<T>,
+
+    /// The lemmas of the most requested leaves, if enabled
+    cache: ProofCache,
+
+    /// The past versions of the tree recorded by `MerkleTree::checkpoint`
+    checkpoints: Checkpoints<T>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for MerkleTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("MerkleTree");
+
+        debug
+            .field("algorithm", &Raw(algorithm_name(self.algorithm)))
+            .field("count", &self.count)
+            .field("height", &self.height);
+
+        // Lazy trees are not hashed just to be printed
+        match self.root.get_built() {
+            Some(tree) => {
+                debug
+                    .field("root_hash", &display::Hash(tree.hash(), Some(8)))
+                    .field("tree", tree)
+            }
+
+            None => debug.field("tree", &Raw("<not hashed>")),
+        };
+
+        debug.finish()
+    }
 }
 
 impl<T: PartialEq> PartialEq for MerkleTree<T> {
     #[allow(trivial_casts)]
     fn eq(&self, other: &MerkleTree<T>) -> bool {
-        self.root == other.root && self.height == other.height && self.count == other.count &&
+        self.tree() == other.tree() && self.height == other.height && self.count == other.count &&
             (self.algorithm as *const Algorithm) == (other.algorithm as *const Algorithm)
     }
 }
@@ -52,87 +135,504 @@ impl<T: Ord> Ord for MerkleTree<T> {
                 &(other.algorithm as
                       *const Algorithm),
             ))
-            .then_with(|| self.root.cmp(&other.root))
+            .then_with(|| self.tree().cmp(other.tree()))
     }
 }
 
 impl<T: Hash> Hash for MerkleTree<T> {
     #[allow(trivial_casts)]
     fn hash<H: Hasher>(&self, state: &mut H) {
-        <Tree<T> as Hash>::hash(&self.root, state);
+        <Tree<T> as Hash>::hash(self.tree(), state);
         self.height.hash(state);
         self.count.hash(state);
         (self.algorithm as *const Algorithm).hash(state);
     }
 }
 
+impl<'a> MerkleTree<&'a [u8]> {
+    /// Constructs a Merkle tree over the given byte slices without copying them, such as
+    /// subslices of one large buffer, which the tree borrows.
+    ///
+    /// The tree has the same hashes as one built with `from_vec` over the corresponding
+    /// `Vec<u8>`s, and its proofs hold the slices as well, see `Proof::to_owned_value`.
+    pub fn from_slices(algorithm: &'static Algorithm, slices: &'a [&'a [u8]]) -> Self {
+        MerkleTree::from_vec(algorithm, slices.to_vec())
+    }
+}
+
 impl<T> MerkleTree<T> {
     /// Constructs a Merkle Tree from a vector of data blocks.
-    /// Returns `None` if `values` is empty.
+    /// An empty `values` yields the same tree as `MerkleTree::empty`.
     pub fn from_vec(algorithm: &'static Algorithm, values: Vec<T>) -> Self
     where
         T: Hashable,
     {
 
-        if values.is_empty() {
-            return MerkleTree {
-                algorithm: algorithm,
-                root: Tree::empty(algorithm.hash_empty()),
-                height: 0,
-                count: 0,
-            };
+        let leaves = values
+            .into_iter()
+            .map(|v| Leaf::from_value(algorithm, v))
+            .collect();
+
+        MerkleTree::from_leaves(algorithm, leaves)
+    }
+
+    /// Works like `MerkleTree::from_vec`, but fails with `Error::LeafHash` on the first value
+    /// which cannot be hashed, see `TryHashable`.
+    pub fn try_from_vec(algorithm: &'static Algorithm, values: Vec<T>) -> Result<Self, Error>
+    where
+        T: TryHashable,
+    {
+
+        let scheme = HashingScheme::default();
+
+        let leaves = values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| match scheme.try_hash_leaf_with(&algorithm, &value) {
+                Ok(hash) => Ok(Leaf::new(hash, value)),
+                Err(cause) => Err(Error::LeafHash { index: index, cause: cause }),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(MerkleTree::from_leaves(algorithm, leaves))
+    }
+
+    /// Constructs a Merkle tree over `len` values given along with their index, in any order,
+    /// such as those computed by parallel workers. The tree is the one `MerkleTree::from_vec`
+    /// builds over the values in the order of their indices.
+    ///
+    /// Each value is hashed as it arrives, and each internal node as soon as both its children
+    /// are hashed, so that the hashing follows the values, and nothing but the tree itself
+    /// is held.
+    ///
+    /// Fails with `Error::LeafIndexOutOfRange` on the first index which is not lower than `len`,
+    /// and once all the values are placed, with `Error::InvalidIndices` listing the indices
+    /// without a value and those given several, the first of which is kept.
+    pub fn from_indexed_unordered<I>(algorithm: &'static Algorithm, len: usize, pairs: I) -> Result<Self, Error>
+    where
+        T: Hashable,
+        I: IntoIterator<Item = (usize, T)>,
+    {
+
+        let mut values = (0..len).map(|_| None).collect::<Vec<Option<T>>>();
+        let mut duplicates = Vec::new();
+
+        // The hashes of each level, from the leaves up, promoted nodes included
+        let mut levels = vec![vec![None; len]];
+
+        while levels[levels.len() - 1].len() > 1 {
+            let width = levels[levels.len() - 1].len().div_ceil(2);
+            levels.push(vec![None; width]);
+        }
+
+        for (index, value) in pairs {
+            if index >= len {
+                return Err(Error::LeafIndexOutOfRange {
+                    index: index,
+                    count: len,
+                });
+            }
+
+            if values[index].is_some() {
+                duplicates.push(index);
+                continue;
+            }
+
+            levels[0][index] = Some(SmallHash::from(algorithm.hash_leaf(&value).as_ref()));
+            values[index] = Some(value);
+
+            // Hashes the nodes above the leaf whose other child is already there
+            let mut index = index;
+
+            for level in 0..levels.len() - 1 {
+                let node = levels[level][index].expect("the node was just hashed");
+
+                let parent = match levels[level].get(index ^ 1) {
+                    Some(&Some(sibling)) if index % 2 == 0 => algorithm.hash_nodes(&node, &sibling).as_ref().into(),
+                    Some(&Some(sibling)) => algorithm.hash_nodes(&sibling, &node).as_ref().into(),
+                    Some(&None) => break,
+                    None => node,
+                };
+
+                index /= 2;
+                levels[level + 1][index] = Some(parent);
+            }
+        }
+
+        let missing = values
+            .iter()
+            .enumerate()
+            .filter(|&(_, value)| value.is_none())
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() || !duplicates.is_empty() {
+            duplicates.sort();
+            duplicates.dedup();
+
+            return Err(Error::InvalidIndices {
+                missing: missing,
+                duplicates: duplicates,
+            });
         }
 
+        let leaves = values
+            .into_iter()
+            .zip(&levels[0])
+            .map(|(value, hash)| {
+                Leaf {
+                    hash: hash.expect("every leaf is hashed"),
+                    value: value.expect("every index has a value"),
+                }
+            })
+            .collect();
+
+        // The promoted nodes are left out, as they are not hashed
+        let hashes = levels
+            .windows(2)
+            .flat_map(|pair| pair[1][..pair[0].len() / 2].iter())
+            .map(|hash| hash.expect("every node is hashed").to_vec())
+            .collect();
+
+        Ok(MerkleTree::from_leaves_and_hashes(algorithm, leaves, hashes, TreeOptions::default()))
+    }
+
+    /// Constructs a Merkle tree without any leaf, to be filled with `MerkleTree::push`.
+    ///
+    /// Its height is `0`, and its root hash is the hash of the empty string,
+    /// see `HashUtils::hash_empty`. No proof can be generated from it, and iterating over
+    /// it yields nothing. Use `MerkleTreeBuilder::build_empty` to set the options of the tree.
+    pub fn empty(algorithm: &'static Algorithm) -> Self {
+        MerkleTree::from_leaves(algorithm, Vec::new())
+    }
+
+    /// Constructs a Merkle tree with exactly `2^depth` leaves, all of them the zero hash
+    /// until filled in order with `MerkleTree::push`, see `MerkleTreeBuilder::fixed_depth`.
+    ///
+    /// Panics if `depth` is at least `MAX_TREE_DEPTH`.
+    pub fn with_fixed_depth(algorithm: &'static Algorithm, depth: usize) -> Self {
+        MerkleTreeBuilder::new(algorithm).fixed_depth(depth).build_empty()
+    }
+
+    /// Constructs a Merkle Tree committing once to each distinct value of `values`,
+    /// two values being the same if they have the same leaf hash.
+    ///
+    /// Returns the tree along with the number of duplicates which were dropped.
+    pub fn from_vec_dedup(
+        algorithm: &'static Algorithm,
+        values: Vec<T>,
+        order: DedupOrder,
+    ) -> (Self, usize)
+    where
+        T: Hashable,
+    {
+
         let count = values.len();
-        let mut height = 0;
-        let mut cur = Vec::with_capacity(count);
+        let tree = MerkleTreeBuilder::new(algorithm).dedup(order).build_from_vec(values);
+        let duplicates = count - tree.count();
+
+        (tree, duplicates)
+    }
+
+    /// Constructs a Merkle Tree from a vector of data blocks, laid out in the given `ordering`.
+    pub fn from_vec_ordered(
+        algorithm: &'static Algorithm,
+        values: Vec<T>,
+        ordering: LeafOrdering<T>,
+    ) -> Self
+    where
+        T: Hashable,
+    {
+
+        MerkleTreeBuilder::new(algorithm).sorted(ordering).build_from_vec(values)
+    }
+
+    /// Stitches Merkle trees built independently over consecutive runs of values, in order,
+    /// into the tree `MerkleTree::from_vec` would build over all the values, without hashing
+    /// the leaves again.
+    ///
+    /// This only works if the chunks are subtrees of that tree: every chunk but the last must
+    /// have the same number of leaves, a power of two, and the last one at most as many.
+    /// The chunks must have been built with `algorithm`, and with the same hashing scheme and
+    /// odd leaf policy, which the stitched tree is built with too, and none of them can be empty,
+    /// have a fixed depth, leaf counts, or deduplicated or sorted leaves.
+    ///
+    /// Fails with `Error::AlgorithmMismatch` if a chunk was built with another algorithm,
+    /// and with `Error::InvalidChunk` if it does not fit otherwise. An empty `chunks`
+    /// yields the same tree as `MerkleTree::empty`.
+    pub fn from_chunks(
+        algorithm: &'static Algorithm,
+        chunks: Vec<MerkleTree<T>>,
+    ) -> Result<Self, Error> {
+
+        let (chunk_len, scheme, policy, hasher) = match chunks.first() {
+            Some(first) => (
+                first.count,
+                first.options.scheme,
+                first.options.odd_leaf_policy,
+                first.options.hasher.clone(),
+            ),
+            None => return Ok(MerkleTree::empty(algorithm)),
+        };
+
+        let options = TreeOptions {
+            scheme: scheme,
+            odd_leaf_policy: policy,
+            hasher: hasher,
+            ..TreeOptions::default()
+        };
+
+        let last = chunks.len() - 1;
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            let invalid = |reason| Err(Error::InvalidChunk { index: index, reason: reason });
+
+            if algorithm_tag(chunk.algorithm) != algorithm_tag(algorithm) {
+                return Err(Error::AlgorithmMismatch);
+            }
+
+            if chunk.options.scheme != scheme || chunk.options.odd_leaf_policy != policy {
+                return invalid("built with another hashing scheme or odd leaf policy");
+            }
 
-        for v in values {
-            let leaf = Tree::new_leaf(algorithm, v);
-            cur.push(leaf);
+            if !chunk.options.same_hasher(&options) {
+                return invalid("built with another hasher");
+            }
+
+            if chunk.options.fixed_depth.is_some() || chunk.options.dedup.is_some() ||
+                chunk.options.ordering.is_sorted()
+            {
+                return invalid("built with a fixed depth, or with deduplicated or sorted leaves");
+            }
+
+            if chunk.options.leaf_counts {
+                return invalid("built with leaf counts");
+            }
+
+            if chunk.is_empty() {
+                return invalid("chunk is empty");
+            }
+
+            if index == 0 && last > 0 && !chunk_len.is_power_of_two() {
+                return invalid("number of leaves is not a power of two");
+            }
+
+            if index < last && chunk.count != chunk_len {
+                return invalid("not as many leaves as the first chunk");
+            }
+
+            if chunk.count > chunk_len {
+                return invalid("more leaves than the first chunk");
+            }
         }
 
-        while cur.len() > 1 {
-            let mut next = Vec::new();
-            while !cur.is_empty() {
-                if cur.len() == 1 {
-                    next.push(cur.remove(0));
-                } else {
-                    let left = cur.remove(0);
-                    let right = cur.remove(0);
+        let count = chunks.iter().map(|chunk| chunk.count).sum();
+        let chunk_height = ceil_log2(chunk_len);
+        let capacity = chunks.iter().map(|chunk| chunk.tree().node_count()).sum::<usize>();
+
+        let mut tree = Tree::with_capacity(capacity + 2 * chunks.len() + chunk_height);
+        let mut roots = Vec::with_capacity(chunks.len());
 
-                    let combined_hash = algorithm.hash_nodes(left.hash(), right.hash());
+        for mut chunk in chunks {
+            let mut root = tree.append(chunk.take_root());
 
-                    let node = Tree::Node {
-                        hash: combined_hash.as_ref().into(),
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    };
+            // The odd nodes of the last chunk are paired with a copy of themselves
+            // up to the height of the other chunks, as in the stitched tree
+            if policy == OddLeafPolicy::Duplicate {
+                for _ in chunk.height..chunk_height {
+                    let hash = *tree.node_hash(root);
+                    let parent = options.hash_nodes(algorithm, &hash, &hash);
+                    let copy = tree.push_empty(hash);
 
-                    next.push(node);
+                    root = tree.push_node(parent.as_ref().into(), root, copy);
                 }
             }
 
+            roots.push(root);
+        }
+
+        let mut height = chunk_height;
+
+        build_levels(&options, &mut tree, roots, chunk_height, |tree, level| {
             height += 1;
+            level_hashes(algorithm, &options, tree, level, height, count)
+        });
+
+        Ok(MerkleTree {
+            algorithm: algorithm,
+            root: LazyRoot::built(tree),
+            height: balanced_height(count),
+            count: count,
+            options: options,
+            cache: ProofCache::new(0),
+            checkpoints: Checkpoints::default(),
+        })
+    }
+
+    /// Builds the internal levels of a Merkle tree on top of the given leaves.
+    fn from_leaves(algorithm: &'static Algorithm, leaves: Vec<Leaf<T>>) -> Self {
+        MerkleTree::from_leaves_with(algorithm, leaves, TreeOptions::default())
+    }
+
+    /// Builds the internal levels of a Merkle tree on top of the given leaves,
+    /// which must already be deduplicated and ordered according to `options`.
+    pub(crate) fn from_leaves_with(
+        algorithm: &'static Algorithm,
+        leaves: Vec<Leaf<T>>,
+        options: TreeOptions<T>,
+    ) -> Self {
+
+        MerkleTree::from_leaves_in(algorithm, Tree::with_capacity(0), leaves, options)
+    }
+
+    /// Works like `MerkleTree::from_leaves_with`, but lays the nodes out in the allocation
+    /// of `buffer`, see `Tree::recycle`.
+    pub(crate) fn from_leaves_in(
+        algorithm: &'static Algorithm,
+        buffer: Tree<T>,
+        leaves: Vec<Leaf<T>>,
+        options: TreeOptions<T>,
+    ) -> Self {
+
+        let count = leaves.len();
+        let root = build_root(algorithm, buffer, leaves, &options);
+
+        MerkleTree {
+            algorithm: algorithm,
+            root: LazyRoot::built(root),
+            height: options.fixed_depth.unwrap_or(balanced_height(count)),
+            count: count,
+            options: options,
+            cache: ProofCache::new(0),
+            checkpoints: Checkpoints::default(),
+        }
+    }
+
+    /// Works like `MerkleTree::from_leaves_with`, but takes the hashes of the internal nodes
+    /// from `hashes`, level by level from the leaves up, rather than computing them,
+    /// see `Subtree::internal_hashes`. There must be `internal_node_count` of them.
+    pub(crate) fn from_leaves_and_hashes(
+        algorithm: &'static Algorithm,
+        leaves: Vec<Leaf<T>>,
+        hashes: Vec<Vec<u8>>,
+        options: TreeOptions<T>,
+    ) -> Self {
+
+        let count = leaves.len();
+        let policy = options.odd_leaf_policy;
+        let mut hashes = hashes.into_iter();
+
+        let root = build_root_with(algorithm, Tree::with_capacity(0), leaves, &options, |_, level| {
+            let pairs = match policy {
+                OddLeafPolicy::Promote => level.len() / 2,
+                OddLeafPolicy::Duplicate => level.len().div_ceil(2),
+            };
+
+            hashes.by_ref().take(pairs).collect()
+        });
 
-            cur = next;
+        debug_assert!(hashes.next().is_none());
+
+        MerkleTree {
+            algorithm: algorithm,
+            root: LazyRoot::built(root),
+            height: options.fixed_depth.unwrap_or(balanced_height(count)),
+            count: count,
+            options: options,
+            cache: ProofCache::new(0),
+            checkpoints: Checkpoints::default(),
         }
+    }
+
+    /// Defers hashing the given values, which must already be ordered according to `options`,
+    /// until the tree is first accessed.
+    pub(crate) fn from_pending(
+        algorithm: &'static Algorithm,
+        values: Vec<T>,
+        options: TreeOptions<T>,
+    ) -> Self
+    where
+        T: Hashable,
+    {
+
+        let count = values.len();
 
-        debug_assert!(cur.len() == 1);
+        MerkleTree {
+            algorithm: algorithm,
+            root: LazyRoot::pending(Pending::new(values)),
+            height: options.fixed_depth.unwrap_or(balanced_height(count)),
+            count: count,
+            options: options,
+            cache: ProofCache::new(0),
+            checkpoints: Checkpoints::default(),
+        }
+    }
 
-        let root = cur.remove(0);
+    /// Wraps a tree of `count` leaves, already built according to `options`.
+    pub(crate) fn from_tree(
+        algorithm: &'static Algorithm,
+        root: Tree<T>,
+        count: usize,
+        options: TreeOptions<T>,
+    ) -> Self {
 
         MerkleTree {
             algorithm: algorithm,
-            root: root,
-            height: height,
+            root: LazyRoot::built(root),
+            height: options.fixed_depth.unwrap_or(balanced_height(count)),
             count: count,
+            options: options,
+            cache: ProofCache::new(0),
+            checkpoints: Checkpoints::default(),
+        }
+    }
+
+    /// Returns the root of the inner binary tree, hashing it first if the tree is lazy.
+    pub(crate) fn tree(&self) -> &Tree<T> {
+        let algorithm = self.algorithm;
+        let options = &self.options;
+
+        self.root.get(|pending| build_pending(algorithm, options, pending))
+    }
+
+    /// Moves out the root of the inner binary tree, leaving an empty tree in its place.
+    /// The checkpoints which still share the tree get a copy of it first.
+    pub(crate) fn take_root(&mut self) -> Tree<T> {
+        let algorithm = self.algorithm;
+        let options = &self.options;
+        let root = self.root.get_mut(|pending| build_pending(algorithm, options, pending));
+
+        self.checkpoints.preserve(root);
+
+        mem::replace(root, Tree::empty(algorithm.hash_empty()))
+    }
+
+    /// Returns the value of the leaf at the given `index` for modifying it in place, which must
+    /// keep hashing the same, or `None` if there is no such leaf or if it is pruned.
+    /// The checkpoints which still share the tree get a copy of it first.
+    pub(crate) fn nth_value_mut(&mut self, index: usize) -> Option<&mut T> {
+        let algorithm = self.algorithm;
+        let options = &self.options;
+        let root = self.root.get_mut(|pending| build_pending(algorithm, options, pending));
+
+        if index >= self.count {
+            return None;
         }
+
+        self.checkpoints.preserve(root);
+        root.nth_value_mut(index)
     }
 
-    /// Returns the root hash of Merkle tree
+    /// Returns the root hash of Merkle tree,
+    /// which is the hash of the empty string if the tree is empty.
     pub fn root_hash(&self) -> &Vec<u8> {
-        self.root.hash()
+        self.tree().hash()
+    }
+
+    /// Returns the root hash of Merkle tree, tagged with the algorithm it was computed with,
+    /// to check proofs with `Proof::verify`.
+    pub fn root(&self) -> RootHash {
+        RootHash::new_unchecked(self.algorithm, self.root_hash().clone())
     }
 
     /// Returns the height of Merkle tree
@@ -145,49 +645,2002 @@ impl<T> MerkleTree<T> {
         self.count
     }
 
-    /// Returns whether the Merkle tree is empty or not
+    /// Returns the order of the leaves of the Merkle tree
+    pub fn ordering(&self) -> &LeafOrdering<T> {
+        &self.options.ordering
+    }
+
+    /// Returns the way the nodes of the Merkle tree are hashed
+    pub fn hashing_scheme(&self) -> HashingScheme {
+        self.options.scheme
+    }
+
+    /// Returns what is done with the last node of levels which have an odd number of nodes
+    pub fn odd_leaf_policy(&self) -> OddLeafPolicy {
+        self.options.odd_leaf_policy
+    }
+
+    /// Returns the number of levels of the Merkle tree, if it has a fixed depth,
+    /// see `MerkleTreeBuilder::fixed_depth`
+    pub fn fixed_depth(&self) -> Option<usize> {
+        self.options.fixed_depth
+    }
+
+    /// Returns whether the hash of every internal node of the Merkle tree commits to the number
+    /// of leaves beneath it, see `MerkleTreeBuilder::leaf_counts`
+    pub fn has_leaf_counts(&self) -> bool {
+        self.options.leaf_counts
+    }
+
+    /// Returns the order in which duplicate leaves are dropped, if they are
+    pub fn dedup(&self) -> Option<DedupOrder> {
+        self.options.dedup
+    }
+
+    /// Installs the given observer on the tree, or removes the current one, returning it,
+    /// see `MerkleTreeBuilder::observer`.
+    ///
+    /// Only available with the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn TreeObserver>>) -> Option<Arc<dyn TreeObserver>> {
+        mem::replace(&mut self.options.observer, observer)
+    }
+
+    /// Returns whether the Merkle tree is empty or not, see `MerkleTree::empty`
     pub fn is_empty(&self) -> bool {
         self.count() == 0
     }
 
+    /// Returns whether the hashes of the Merkle tree have been computed yet,
+    /// which is always the case unless it was built with `MerkleTreeBuilder::lazy`.
+    pub fn is_hashed(&self) -> bool {
+        self.root.is_built()
+    }
+
+    /// Returns an adapter whose `Debug` output shows every node and every value of the
+    /// Merkle tree, hashing it first if the tree is lazy. Beware: it grows with the tree.
+    pub fn debug_full(&self) -> FullDebug<T> {
+        FullDebug::new(self.algorithm, self.count, self.height, self.tree())
+    }
+
+    /// Estimates the memory held by the Merkle tree, including the heap allocations of its values.
+    pub fn memory_footprint(&self) -> MemoryReport
+    where
+        T: HeapSize,
+    {
+
+        self.memory_footprint_with(HeapSize::heap_size)
+    }
+
+    /// Estimates the memory held by the Merkle tree, `value_heap` returning the number of bytes
+    /// allocated on the heap by a value.
+    pub fn memory_footprint_with<F>(&self, value_heap: F) -> MemoryReport
+    where
+        F: Fn(&T) -> usize,
+    {
+
+        memory::measure(self.tree(), size_of::<Self>(), value_heap)
+    }
+
+    /// Summarizes the shape of the Merkle tree in a single walk over its nodes,
+    /// hashing it first if the tree is lazy.
+    pub fn stats(&self) -> TreeStats {
+        stats::collect(self.algorithm, self.tree(), self.height)
+    }
+
+    /// Walks the Merkle tree in the given `order`, running the callbacks of `visitor` at each of
+    /// its nodes, see `TreeVisitor`. Hashes the tree first if it is lazy.
+    pub fn walk<V>(&self, visitor: &mut V, order: TraversalOrder)
+    where
+        V: TreeVisitor<T> + ?Sized,
+    {
+
+        visitor::walk(self.tree(), visitor, order)
+    }
+
     /// Generate an inclusion proof for the given value.
-    /// Returns `None` if the given value is not found in the tree.
+    /// Returns `None` if the given value is not found in the tree, or only in pruned subtrees.
     pub fn gen_proof(&self, value: T) -> Option<Proof<T>>
     where
         T: Hashable,
     {
 
-        let root_hash = self.root_hash().clone();
-        let leaf_hash = self.algorithm.hash_leaf(&value);
+        self.options.timed_proof(|| {
+            let leaf_hash = self.hash_leaf(&value);
 
-        Lemma::new(&self.root, leaf_hash.as_ref()).map(|lemma| {
-            Proof::new(self.algorithm, root_hash, lemma, value)
+            self.find_lemma(leaf_hash.as_ref())
+                .map(|lemma| self.new_proof(self.root_hash().clone(), lemma, value, self.count))
         })
     }
 
-    /// Creates an `Iterator` over the values contained in this Merkle tree.
-    pub fn iter(&self) -> LeavesIterator<T> {
-        self.root.iter()
+    /// Works like `MerkleTree::gen_proof`, but borrows the value, which is only cloned
+    /// into the proof if it is found in the tree.
+    pub fn gen_proof_ref(&self, value: &T) -> Option<Proof<T>>
+    where
+        T: Hashable + Clone,
+    {
+
+        self.options.timed_proof(|| {
+            let leaf_hash = self.hash_leaf(value);
+
+            self.find_lemma(leaf_hash.as_ref())
+                .map(|lemma| self.new_proof(self.root_hash().clone(), lemma, value.clone(), self.count))
+        })
     }
-}
 
-impl<T> IntoIterator for MerkleTree<T> {
-    type Item = T;
-    type IntoIter = LeavesIntoIterator<T>;
+    /// Works like `MerkleTree::gen_proof`, but returns a proof without the value, which is
+    /// never cloned. The proof holds the hash of the leaf, and validates as the full one does.
+    pub fn gen_proof_detached(&self, value: &T) -> Option<Proof<()>>
+    where
+        T: Hashable,
+    {
 
-    /// Creates a consuming iterator, that is, one that moves each value out of the Merkle tree.
-    /// The tree cannot be used after calling this.
-    fn into_iter(self) -> Self::IntoIter {
-        self.root.into_iter()
+        self.options.timed_proof(|| {
+            let leaf_hash = self.hash_leaf(value);
+
+            self.find_lemma(leaf_hash.as_ref())
+                .map(|lemma| self.new_proof(self.root_hash().clone(), lemma, (), self.count))
+        })
     }
-}
 
-impl<'a, T> IntoIterator for &'a MerkleTree<T> {
-    type Item = &'a T;
-    type IntoIter = LeavesIterator<'a, T>;
+    /// Generates an inclusion proof for the leftmost leaf with the given hash, without its value,
+    /// as `MerkleTree::gen_proof_detached` would for a value with that hash. The leaf is looked
+    /// up in the proof cache if it is enabled, see `MerkleTree::enable_proof_cache`,
+    /// and searched for otherwise.
+    ///
+    /// Returns `None` if no leaf has that hash, and right away if `leaf_hash` does not have
+    /// the output length of the hashing algorithm.
+    pub fn gen_proof_by_hash(&self, leaf_hash: &[u8]) -> Option<Proof<()>> {
+        if leaf_hash.len() != self.algorithm.output_len {
+            return None;
+        }
 
-    /// Creates a borrowing `Iterator` over the values contained in this Merkle tree.
-    fn into_iter(self) -> Self::IntoIter {
-        self.root.iter()
+        self.options.timed_proof(|| {
+            self.find_lemma(leaf_hash)
+                .map(|lemma| self.new_proof(self.root_hash().clone(), lemma, (), self.count))
+        })
+    }
+
+    /// Works like `MerkleTree::gen_proof_by_hash`, but generates a proof for every leaf with
+    /// the given hash, from left to right.
+    ///
+    /// The lemmas are all looked up at once: the observer of the tree, if any, is notified
+    /// of each proof, timed from its lemma on, see `TreeObserver::on_proof_generated`.
+    pub fn gen_proofs_by_hash(&self, leaf_hash: &[u8]) -> Vec<Proof<()>> {
+        if leaf_hash.len() != self.algorithm.output_len {
+            return Vec::new();
+        }
+
+        Lemma::new_all(self.tree().root(), leaf_hash)
+            .into_iter()
+            .filter_map(|lemma| {
+                self.options.timed_proof(|| Some(self.new_proof(self.root_hash().clone(), lemma, (), self.count)))
+            })
+            .collect()
+    }
+
+    /// Returns, for each of the given leaf hashes, whether a leaf of the tree has that hash,
+    /// see `MerkleTree::positions_of_hashes`.
+    pub fn contains_hashes(&self, hashes: &[Vec<u8>]) -> Vec<bool> {
+        self.positions_of_hashes(hashes).into_iter().map(|position| position.is_some()).collect()
+    }
+
+    /// Returns, for each of the given leaf hashes, the index of the leftmost leaf with that hash,
+    /// or `None` if no leaf has it, in the same order as the hashes.
+    ///
+    /// The hashes are looked up all at once, in a single walk over the leaves, rather than
+    /// one walk per hash as with `MerkleTree::gen_proof_by_hash`. A hash which does not have
+    /// the output length of the hashing algorithm is not the hash of any leaf, and is hence
+    /// mapped to `None`, without failing the other lookups.
+    pub fn positions_of_hashes(&self, hashes: &[Vec<u8>]) -> Vec<Option<usize>> {
+        let mut positions = hashes
+            .iter()
+            .filter(|hash| hash.len() == self.algorithm.output_len)
+            .map(|hash| (hash.as_slice(), None))
+            .collect::<HashMap<&[u8], Option<usize>>>();
+
+        if !positions.is_empty() {
+            for (index, hash) in self.tree().indexed_leaf_hashes() {
+                if let Some(position) = positions.get_mut(hash.as_slice()) {
+                    position.get_or_insert(index);
+                }
+            }
+        }
+
+        hashes
+            .iter()
+            .map(|hash| positions.get(hash.as_slice()).and_then(|&position| position))
+            .collect()
+    }
+
+    /// Generates the lemma of the leftmost leaf with the given hash, through the proof cache
+    /// if it is enabled.
+    fn find_lemma(&self, leaf_hash: &[u8]) -> Option<Lemma> {
+        let tree = self.tree();
+        let root = tree.root();
+
+        if !self.cache.is_enabled() {
+            return Lemma::new(root, leaf_hash);
+        }
+
+        self.cache.get_by_hash(leaf_hash).or_else(|| {
+            let index = tree.position(leaf_hash)?;
+
+            self.cache.get_or_insert(index, Some(leaf_hash), || {
+                Lemma::new_by_index(root, index, self.count).map(|(lemma, _)| lemma)
+            })
+        })
+    }
+
+    /// Generate an inclusion proof for the given value, in the form taken by the `MerkleProof`
+    /// library of OpenZeppelin: the sibling hashes from the leaf up, see `Proof::sorted_path`.
+    /// Returns `None` if the given value is not found in the tree, or if the tree was not built
+    /// with `HashingScheme::SortedPairs`.
+    pub fn gen_sorted_proof(&self, value: T) -> Option<Vec<Vec<u8>>>
+    where
+        T: Hashable,
+    {
+
+        if self.options.scheme != HashingScheme::SortedPairs {
+            return None;
+        }
+
+        self.gen_proof(value).map(|proof| proof.sorted_path())
     }
+
+    /// Generate an inclusion proof for the `n`-th leaf (starting from `0`) of the tree.
+    /// Returns `None` if the tree has at most `n` leaves, or if the leaf was pruned,
+    /// see `MerkleTree::try_gen_nth_proof`.
+    pub fn gen_nth_proof(&self, n: usize) -> Option<Proof<T>>
+    where
+        T: Clone,
+    {
+
+        self.options.timed_proof(|| self.gen_nth_proof_untimed(n))
+    }
+
+    /// Works like `MerkleTree::gen_nth_proof`, without notifying the observer of the tree.
+    fn gen_nth_proof_untimed(&self, n: usize) -> Option<Proof<T>>
+    where
+        T: Clone,
+    {
+
+        let root = self.tree().root();
+        let root_hash = root.hash().to_vec();
+
+        if !self.cache.is_enabled() {
+            return Lemma::new_by_index(root, n, self.count).map(|(lemma, value)| {
+                self.new_proof(root_hash, lemma, value.clone(), self.count)
+            });
+        }
+
+        let value = root.nth_value(n, self.count)?.clone();
+
+        self.cache
+            .get_or_insert(n, None, || {
+                Lemma::new_by_index(root, n, self.count).map(|(lemma, _)| lemma)
+            })
+            .map(|lemma| self.new_proof(root_hash, lemma, value, self.count))
+    }
+
+    /// Works like `MerkleTree::gen_nth_proof`, but fails with `Error::Pruned`, holding the root
+    /// hash of the subtree to graft back, if the `n`-th leaf was pruned by `MerkleTree::prune_below`.
+    pub fn try_gen_nth_proof(&self, n: usize) -> Result<Option<Proof<T>>, Error>
+    where
+        T: Clone,
+    {
+
+        match self.tree().root().pruned_subtree(n, self.count) {
+            Some(hash) => Err(Error::Pruned { subtree_root: hash.to_vec() }),
+            None => Ok(self.gen_nth_proof(n)),
+        }
+    }
+
+    /// Checks that the given proof follows a path of this tree, returning the index of the leaf
+    /// it ends at. Unlike `Proof::validate`, which only checks that the proof hashes up to
+    /// a root hash, every node hash and sibling hash of the proof must be the one of the
+    /// corresponding node of this tree, and the path must end at a leaf, not above or below it.
+    /// The value of the proof is not hashed, only the leaf hash is compared.
+    ///
+    /// Fails with `Error::AlgorithmMismatch` if the proof was built with another hashing
+    /// algorithm, with `Error::HashLengthMismatch` if one of its hashes does not have the length
+    /// of its output, with `Error::ProofMismatch` if its root hash or its path differs from
+    /// this tree, and with `Error::Pruned` if the path leads into a pruned subtree.
+    ///
+    /// The length of the path is checked against the height of this tree first,
+    /// see `Lemma::leaf_depths`.
+    pub fn verify_proof(&self, proof: &Proof<T>) -> Result<usize, Error> {
+        self.options.timed_validation(|| self.verify_proof_untimed(proof))
+    }
+
+    /// Works like `MerkleTree::verify_proof`, without notifying the observer of the tree.
+    fn verify_proof_untimed(&self, proof: &Proof<T>) -> Result<usize, Error> {
+        if algorithm_tag(proof.algorithm) != algorithm_tag(self.algorithm) {
+            return Err(Error::AlgorithmMismatch);
+        }
+
+        proof.check_algorithm()?;
+
+        let root = self.tree().root();
+
+        if proof.root_hash != *root.hash() {
+            return Err(Error::ProofMismatch {
+                depth: 0,
+                reason: "the root hash differs",
+            });
+        }
+
+        // Fails early on a proof whose length gives it away, as `locate` would further down
+        let policy = if self.is_padded() { OddLeafPolicy::Duplicate } else { OddLeafPolicy::Promote };
+        let (min, max) = proof.lemma.leaf_depths(self.height, policy);
+        let depth = proof.depth();
+
+        if depth < min {
+            return Err(Error::ProofMismatch {
+                depth: depth,
+                reason: "the path ends above a leaf",
+            });
+        }
+
+        if depth > max {
+            return Err(Error::ProofMismatch {
+                depth: max,
+                reason: "the path goes below a leaf",
+            });
+        }
+
+        proof.lemma.locate(root, self.count)
+    }
+
+    /// Generates an inclusion proof for each of the given values, as `MerkleTree::gen_proof`
+    /// would, in a single walk over the tree rather than one per value.
+    ///
+    /// The proofs are in the same order as the values, a value which is not found in the tree
+    /// yielding `None`, and a value requested several times getting a proof each time.
+    /// The observer of the tree, if any, is notified of each proof, as with
+    /// `MerkleTree::gen_proofs_by_hash`.
+    pub fn prove_many(&self, values: &[T]) -> Vec<Option<Proof<T>>>
+    where
+        T: Hashable + Clone,
+    {
+
+        let leaf_hashes = values.iter().map(|value| self.hash_leaf(value)).collect::<Vec<_>>();
+
+        let mut positions = leaf_hashes
+            .iter()
+            .map(|hash| (hash.as_ref(), None))
+            .collect::<HashMap<&[u8], Option<usize>>>();
+
+        for (index, hash) in self.tree().indexed_leaf_hashes() {
+            if let Some(position) = positions.get_mut(hash.as_slice()) {
+                position.get_or_insert(index);
+            }
+        }
+
+        let indices = leaf_hashes
+            .iter()
+            .map(|hash| positions[hash.as_ref()])
+            .collect::<Vec<_>>();
+
+        self.prove_indices_with(&indices, |i, _| values[i].clone())
+    }
+
+    /// Generates an inclusion proof for the leaf at each of the given `indices`,
+    /// as `MerkleTree::gen_nth_proof` would, in a single descent of the tree.
+    ///
+    /// The proofs are in the same order as the indices, an index which is out of range
+    /// yielding `None`, and an index requested several times getting a proof each time.
+    /// The observer of the tree, if any, is notified of each proof, as with
+    /// `MerkleTree::gen_proofs_by_hash`.
+    pub fn prove_indices(&self, indices: &[usize]) -> Vec<Option<Proof<T>>>
+    where
+        T: Clone,
+    {
+
+        let indices = indices.iter().map(|&index| Some(index)).collect::<Vec<_>>();
+
+        self.prove_indices_with(&indices, |_, value| value.clone())
+    }
+
+    /// Generates the inclusion proof of every leaf, from left to right, as `MerkleTree::gen_nth_proof`
+    /// would, in a single walk over the tree. See `MerkleTree::iter_proofs`.
+    pub fn all_proofs(&self) -> Vec<Proof<T>>
+    where
+        T: Clone,
+    {
+
+        self.iter_proofs().collect()
+    }
+
+    /// Returns an iterator over the inclusion proofs of every leaf, from left to right,
+    /// which walks the tree once rather than descending from the root for each leaf,
+    /// and only holds the path to the current leaf. The pruned leaves are skipped.
+    /// The observer of the tree, if any, is notified of each proof as it is yielded.
+    pub fn iter_proofs(&self) -> AllProofs<T> {
+        AllProofs::new(self, self.count - self.tree().pruned_leaves())
+    }
+
+    /// Generates a proof for each of the given leaf indices, if any, the value of the `i`-th
+    /// proof being returned by `value` given `i` and the value of the leaf.
+    fn prove_indices_with<F>(&self, indices: &[Option<usize>], value: F) -> Vec<Option<Proof<T>>>
+    where
+        F: Fn(usize, &T) -> T,
+    {
+
+        let mut wanted = indices
+            .iter()
+            .filter_map(|&index| index.filter(|&index| index < self.count))
+            .collect::<Vec<_>>();
+
+        wanted.sort();
+        wanted.dedup();
+
+        let root = self.tree().root();
+        let lemmas = Lemma::new_by_indices(root, &wanted, self.count);
+
+        indices
+            .iter()
+            .enumerate()
+            .map(|(i, &index)| {
+                let position = wanted.binary_search(&index?).ok()?;
+                let (ref lemma, leaf_value) = lemmas[position];
+
+                self.options.timed_proof(|| {
+                    Some(self.new_proof(root.hash().to_vec(), lemma.clone(), value(i, leaf_value), self.count))
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a writer of the levels of the inclusion proof of the `n`-th leaf (starting from `0`)
+    /// of the tree, from the leaf up, to be sent to an `IncrementalVerify` as they are looked up.
+    /// Returns `None` if the tree has at most `n` leaves.
+    pub fn proof_writer(&self, n: usize) -> Option<IncrementalProofWriter<T>> {
+        IncrementalProofWriter::new(self.tree().root(), n, self.count)
+    }
+
+    /// Generates a single proof of the leaves at the given `indices`, which may be in any order.
+    ///
+    /// Returns `None` if any of the indices is out of range or pruned, see `MerkleTree::prune_below`,
+    /// or if the tree has a fixed depth or leaf counts.
+    pub fn gen_multi_proof(&self, indices: &[usize]) -> Option<MultiProof> {
+        if self.options.fixed_depth.is_some() || self.options.leaf_counts ||
+            indices.iter().any(|&index| index >= self.count)
+        {
+            return None;
+        }
+
+        let root = self.tree().root();
+
+        if indices.iter().any(|&index| root.pruned_subtree(index, self.count).is_some()) {
+            return None;
+        }
+
+        let mut indices = indices.to_vec();
+        indices.sort();
+        indices.dedup();
+
+        let mut proof = MultiProof::new(
+            self.algorithm,
+            self.options.scheme,
+            self.tree().root(),
+            self.count,
+            &indices,
+        );
+
+        proof.hasher = self.options.hasher.clone();
+        Some(proof)
+    }
+
+    /// Generates a proof that the tree over the first `prefix_len` values of this tree,
+    /// built with the same options, is a prefix of this tree. See `PrefixProof::verify`.
+    ///
+    /// Returns `None` if the tree has fewer than `prefix_len` leaves, or if it was built with
+    /// `OddLeafPolicy::Duplicate` or with a fixed depth, under which the root of a prefix
+    /// is not that of a subtree, or with leaf counts, or if it has pruned subtrees,
+    /// see `MerkleTree::prune_below`.
+    pub fn prove_prefix(&self, prefix_len: usize) -> Option<PrefixProof> {
+        if prefix_len > self.count || self.is_padded() || self.options.leaf_counts ||
+            self.tree().pruned_leaves() > 0
+        {
+            return None;
+        }
+
+        let mut proof = PrefixProof::new(
+            self.algorithm,
+            self.options.scheme,
+            self.tree().root(),
+            prefix_len,
+            self.count,
+        );
+
+        proof.hasher = self.options.hasher.clone();
+        Some(proof)
+    }
+
+    /// Returns the sibling hashes on the path from the root down to the leaf at `index`,
+    /// those of its proof, see `Proof::siblings`, without the rest of the proof, or `None`
+    /// if there is no such leaf or if it is pruned. Reversed, they are the audit path
+    /// of RFC 6962, from the leaf up.
+    pub fn audit_path(&self, index: usize) -> Option<Vec<Positioned<Vec<u8>>>> {
+        let (lemma, _) = Lemma::new_by_index(self.tree().root(), index, self.count)?;
+
+        Some(lemma.siblings().cloned().collect())
+    }
+
+    /// Returns the sibling hashes on the path from the root down to the leaf at `index`
+    /// of the tree over the first `size` values of this tree, built with the same options,
+    /// as `MerkleTree::audit_path` returns them for that tree, such as the audit path
+    /// of an older size of a log. The nodes over leaves past the first `size` ones
+    /// are hashed anew over those leaves, the others are those of this tree.
+    ///
+    /// Returns `None` if `index` is not below `size`, if the tree has fewer than `size` leaves,
+    /// if it was built with `OddLeafPolicy::Duplicate` or with a fixed depth, under which
+    /// the nodes of the smaller tree are not those of this tree, or if the path goes through
+    /// a pruned subtree, see `MerkleTree::prune_below`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::MerkleTree;
+    /// # fn main() {
+    /// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c", "d", "e"]);
+    /// let older = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c"]);
+    ///
+    /// assert_eq!(tree.audit_path_at_size(1, 3), older.audit_path(1));
+    /// # }
+    /// ```
+    pub fn audit_path_at_size(&self, index: usize, size: usize) -> Option<Vec<Positioned<Vec<u8>>>> {
+        if index >= size || size > self.count || self.is_padded() {
+            return None;
+        }
+
+        let mut path = Vec::new();
+        prefix::prefix_path(self.algorithm, &self.options, self.tree().root(), self.count, size, index, &mut path)?;
+
+        Some(path)
+    }
+
+    /// Returns the hash of the node at the given `level` and `index`, or `None` if there is no
+    /// such node.
+    ///
+    /// The leaves are at level `0`, and the nodes of a level are built by pairing those of the
+    /// level below, up to the root at level `height`. `index` is the position of a node in its
+    /// level, so that the node at level `l` and index `i` is over the leaves from `i * 2^l`
+    /// onwards. A node promoted to the next level is at both levels, and the root is at every
+    /// level above `height`. An empty tree has no node at all.
+    pub fn node_hash(&self, level: usize, index: usize) -> Option<&[u8]> {
+        self.tree()
+            .root()
+            .node_at_level(level, index, self.count, self.height, self.is_padded())
+            .map(|node| node.hash().as_slice())
+    }
+
+    /// Works like `MerkleTree::node_hash`, but tags the hash with the algorithm it was computed with.
+    pub fn node(&self, level: usize, index: usize) -> Option<NodeHash> {
+        self.node_hash(level, index)
+            .map(|hash| NodeHash::new_unchecked(self.algorithm, hash.to_vec()))
+    }
+
+    /// Finds the index of the first leaf where this tree differs from a remote one,
+    /// whose hashes are queried with `oracle`, or `None` if both trees are the same.
+    ///
+    /// `oracle(level, index)` returns the hash of the remote node at the given position,
+    /// as `MerkleTree::node_hash` would, or `None` if there is no such node. The search descends
+    /// from the root down the leftmost branch which differs, querying one node per level,
+    /// and fails with the first error returned by `oracle`.
+    ///
+    /// If one of the trees has the leaves of the other followed by some more, the index returned
+    /// is the number of leaves of the shorter one.
+    ///
+    /// Panics if the tree has pruned subtrees, see `MerkleTree::prune_below`.
+    pub fn find_first_divergence<F, E>(&self, oracle: F) -> Result<Option<usize>, E>
+    where
+        F: FnMut(usize, usize) -> Result<Option<Vec<u8>>, E>,
+    {
+
+        assert_eq!(self.tree().pruned_leaves(), 0, "the tree has pruned subtrees");
+
+        divergence::first_divergence(self.tree().root(), self.count, self.height, self.is_padded(), oracle)
+    }
+
+    /// Exports the root hash and the hashes of the nodes down to `depth` levels below the root,
+    /// along with the number of leaves, see `LevelExport`. A `depth` greater than the height
+    /// of the tree exports every level but the leaves.
+    ///
+    /// The nodes `depth` levels below the root split the leaves into up to `2^depth` buckets,
+    /// and comparing the exports of two trees with `LevelExport::diff` tells which buckets
+    /// differ, without shipping the whole trees.
+    ///
+    /// Panics if the levels reach below a pruned subtree, see `MerkleTree::prune_below`.
+    pub fn export_levels(&self, depth: usize) -> LevelExport {
+        LevelExport::new(
+            self.algorithm,
+            self.tree().root(),
+            self.count,
+            self.height,
+            self.is_padded(),
+            depth,
+        )
+    }
+
+    /// Caches the lemmas of up to `capacity` of the most recently proven leaves,
+    /// to be reused by `MerkleTree::gen_proof` and `gen_nth_proof`.
+    ///
+    /// The cache is cleared whenever the tree is modified, since all the lemmas then change.
+    /// Calling this again drops the cached lemmas, and a `capacity` of `0` disables the cache.
+    pub fn enable_proof_cache(&mut self, capacity: usize) {
+        self.cache.set_capacity(capacity);
+    }
+
+    /// Returns the counters of the proof cache, which are all `0` unless it was enabled
+    /// with `MerkleTree::enable_proof_cache`.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Records the current version of the tree, so that proofs can still be generated against
+    /// its current root after the tree is modified.
+    ///
+    /// This does not copy the tree right away: it is only copied when it is next modified,
+    /// once for all the checkpoints taken since the previous modification.
+    /// Use `MerkleTree::drop_checkpoints_before` to reclaim the memory held by old versions.
+    pub fn checkpoint(&mut self) -> VersionId
+    where
+        T: Clone,
+    {
+
+        let count = self.count;
+        let algorithm = self.algorithm;
+        let options = &self.options;
+        let root = self.root.get(|pending| build_pending(algorithm, options, pending));
+
+        self.checkpoints.record(root, count)
+    }
+
+    /// Returns the root hash of the tree at the given `version`,
+    /// or `None` if its checkpoint was dropped.
+    pub fn root_at(&self, version: VersionId) -> Option<&Vec<u8>> {
+        self.checkpoints.root_hash(version)
+    }
+
+    /// Generate an inclusion proof for the `index`-th leaf (starting from `0`) of the tree
+    /// at the given `version`. Returns `None` if its checkpoint was dropped,
+    /// or if the tree had at most `index` leaves at that version.
+    pub fn gen_proof_at(&self, version: VersionId, index: usize) -> Option<Proof<T>>
+    where
+        T: Clone,
+    {
+
+        self.options.timed_proof(|| {
+            let (tree, count) = self.checkpoints.tree(version, self.tree())?;
+
+            Lemma::new_by_index(tree.root(), index, count).map(|(lemma, value)| {
+                self.new_proof(tree.hash().to_vec(), lemma, value.clone(), count)
+            })
+        })
+    }
+
+    /// Drops the checkpoints taken before the given `version`, along with the trees they hold.
+    pub fn drop_checkpoints_before(&mut self, version: VersionId) {
+        self.checkpoints.drop_before(version);
+    }
+
+    /// Generate an inclusion proof for each occurrence of the given value,
+    /// from the leftmost to the rightmost leaf holding it.
+    /// Returns an empty vector if the given value is not found in the tree.
+    /// The observer of the tree, if any, is notified of each proof, as with
+    /// `MerkleTree::gen_proofs_by_hash`.
+    pub fn gen_proofs_for_value(&self, value: T) -> Vec<Proof<T>>
+    where
+        T: Hashable + Clone,
+    {
+
+        let root_hash = self.root_hash();
+        let leaf_hash = self.hash_leaf(&value);
+
+        Lemma::new_all(self.tree().root(), leaf_hash.as_ref())
+            .into_iter()
+            .filter_map(|lemma| {
+                self.options.timed_proof(|| Some(self.new_proof(root_hash.clone(), lemma, value.clone(), self.count)))
+            })
+            .collect()
+    }
+
+    /// Generate an inclusion proof for the `k`-th occurrence (starting from `0`) of the given value.
+    /// Returns `None` if the value occurs at most `k` times in the tree.
+    pub fn gen_proof_for_occurrence(&self, value: T, k: usize) -> Option<Proof<T>>
+    where
+        T: Hashable,
+    {
+
+        self.options.timed_proof(|| {
+            let root_hash = self.root_hash().clone();
+            let leaf_hash = self.hash_leaf(&value);
+            let mut k = k;
+
+            Lemma::new_occurrence(self.tree().root(), leaf_hash.as_ref(), &mut k).map(|lemma| {
+                self.new_proof(root_hash, lemma, value, self.count)
+            })
+        })
+    }
+
+    /// Adds a value to the Merkle tree, and rebuilds its internal levels.
+    ///
+    /// If the leaves are sorted (see `MerkleTree::ordering`), the value is inserted at its sorted
+    /// position, after the values which compare equal to it. Otherwise, it is appended, except
+    /// for trees deduplicated with `DedupOrder::ByHash`, where it is inserted in leaf hash order.
+    /// An appended value only gets the nodes above it hashed, at most one per level, the other
+    /// nodes keeping their hash, but all the nodes are still moved into a new tree.
+    ///
+    /// Returns `false`, leaving the tree untouched, if the tree is deduplicated
+    /// and already has a leaf with the same hash.
+    ///
+    /// Panics if the tree already has as many leaves as allowed by `MerkleTreeBuilder::max_leaves`,
+    /// see `MerkleTree::try_push`.
+    pub fn push(&mut self, value: T) -> bool
+    where
+        T: Hashable,
+    {
+
+        self.try_push(value).unwrap_or_else(|err| panic!("cannot push the value: {}", err))
+    }
+
+    /// Works like `MerkleTree::push`, but fails with `Error::TooManyLeaves`, leaving the tree
+    /// untouched, if the tree already has as many leaves as allowed by `MerkleTreeBuilder::max_leaves`,
+    /// or if all the slots of a tree of fixed depth are filled.
+    ///
+    /// Also fails with `Error::LeafHash`, leaving the tree untouched, if the value cannot be
+    /// hashed, see `TryHashable`.
+    pub fn try_push(&mut self, value: T) -> Result<bool, Error>
+    where
+        T: TryHashable,
+    {
+
+        self.check_unpruned()?;
+
+        let hash = self.options.try_hash_leaf(self.algorithm, &value).map_err(|cause| {
+            Error::LeafHash {
+                index: self.count,
+                cause: cause,
+            }
+        })?;
+
+        self.push_hashed(hash, value)
+    }
+
+    /// Adds the given value as a new leaf of the given hash, once checked that the tree
+    /// is not pruned, as `MerkleTree::try_push` does.
+    pub(crate) fn push_hashed(&mut self, hash: Digest, value: T) -> Result<bool, Error> {
+        self.options.leaf_hashed(0);
+        self.try_extend_leaves(Some(Leaf::new(hash, value))).map(|added| added == 1)
+    }
+
+    /// Adds the given values to the Merkle tree as with `MerkleTree::push`,
+    /// but rebuilds its internal levels only once. Returns the number of values added.
+    ///
+    /// Fails with `Error::TooManyLeaves`, leaving the tree untouched, as soon as the tree would
+    /// get more leaves than allowed by `MerkleTreeBuilder::max_leaves`.
+    pub fn try_extend<I>(&mut self, values: I) -> Result<usize, Error>
+    where
+        T: Hashable,
+        I: IntoIterator<Item = T>,
+    {
+
+        self.check_unpruned()?;
+
+        let leaves = self.options.leaves(self.algorithm, values).collect::<Vec<_>>();
+        self.try_extend_leaves(leaves)
+    }
+
+    /// Works like `MerkleTree::try_extend`, but takes leaves whose values are already hashed
+    /// with the hashing scheme and hasher of the tree.
+    pub(crate) fn try_extend_leaves<I>(&mut self, leaves: I) -> Result<usize, Error>
+    where
+        I: IntoIterator<Item = Leaf<T>>,
+    {
+
+        self.check_unpruned()?;
+
+        let max = self.options.leaf_limit();
+        let mut new_leaves = Vec::new();
+
+        {
+            let mut seen = match self.options.dedup {
+                Some(_) => self.tree().leaf_hashes().into_iter().cloned().collect(),
+                None => HashSet::new(),
+            };
+
+            for leaf in leaves {
+                if self.options.dedup.is_some() && !seen.insert(leaf.hash) {
+                    continue;
+                }
+
+                if self.count + new_leaves.len() >= max {
+                    return Err(Error::TooManyLeaves { max: max });
+                }
+
+                new_leaves.push(leaf);
+            }
+        }
+
+        let added = new_leaves.len();
+
+        if added == 0 {
+            return Ok(0);
+        }
+
+        // Appended values leave the nodes above the previous leaves as they are
+        if !self.options.ordering.is_sorted() && self.options.dedup != Some(DedupOrder::ByHash) {
+            let unchanged = self.count;
+            let (mut leaves, levels) = self.take_leaves_and_levels();
+            leaves.append(&mut new_leaves);
+
+            self.rebuild_keeping(leaves, levels, unchanged);
+
+            return Ok(added);
+        }
+
+        // The values are compared before the tree is taken apart, as comparing them runs code
+        // of their own, which may panic
+        let positions = if self.options.ordering.is_sorted() {
+            let ordering = &self.options.ordering;
+            ordering.sort_leaves(&mut new_leaves);
+
+            let values = self.iter().collect::<Vec<_>>();
+
+            let positions = new_leaves
+                .iter()
+                .map(|leaf| values.partition_point(|value| ordering.compare(value, &leaf.value) != Ordering::Greater))
+                .collect::<Vec<_>>();
+
+            Some(positions)
+        } else {
+            None
+        };
+
+        let root = self.take_root();
+        let options = mem::take(&mut self.options);
+
+        let mut leaves = root.into_leaves();
+
+        match positions {
+            Some(positions) => leaves = merge_at(leaves, new_leaves, positions),
+            None => {
+                leaves.append(&mut new_leaves);
+                leaves.sort_by_key(|leaf| leaf.hash);
+            }
+        }
+
+        self.rebuild(leaves, options);
+
+        Ok(added)
+    }
+
+    /// Removes the last leaf of the Merkle tree, returning its value, so that the tree is the one
+    /// built with the same options over the remaining values, which undoes `MerkleTree::push`
+    /// for trees whose leaves are neither sorted nor deduplicated by hash.
+    ///
+    /// Only the nodes above the last leaf are rehashed, at most one per level, the others
+    /// keeping their hash, including when the tree loses a level. The leaves are moved into
+    /// the new tree though, as when pushing.
+    ///
+    /// Returns `None`, leaving the tree untouched, if the tree is empty or has pruned subtrees,
+    /// see `MerkleTree::prune_below`.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.count == 0 || self.check_unpruned().is_err() {
+            return None;
+        }
+
+        let (mut leaves, levels) = self.take_leaves_and_levels();
+        let last = leaves.pop().expect("the tree is not empty");
+        let unchanged = leaves.len();
+
+        self.rebuild_keeping(leaves, levels, unchanged);
+
+        Some(last.value)
+    }
+
+    /// Inserts the given value at the given `index`, shifting the values after it to the right,
+    /// so that the tree is the one built with the same options over the new sequence of values.
+    /// Inserting at `MerkleTree::count` appends the value, as `MerkleTree::push` does.
+    ///
+    /// The nodes above the leaves before `index` keep their hash, but every node above the new
+    /// leaf or the leaves after it is rehashed, as they are all shifted: the leaves keep their
+    /// hash, but inserting in front of `k` leaves still takes about `k` node hashes, and moving
+    /// all the nodes into a new tree, as when pushing.
+    ///
+    /// Fails, leaving the tree untouched, with `Error::LeafIndexOutOfRange` if `index` is greater
+    /// than the number of leaves, with `Error::TooManyLeaves` if the tree already has as many
+    /// leaves as allowed, and as `MerkleTree::apply_updates` does if the tree has pruned subtrees
+    /// or sorted or deduplicated leaves, whose order the value could break.
+    pub fn insert(&mut self, index: usize, value: T) -> Result<(), Error>
+    where
+        T: Hashable,
+    {
+
+        if index > self.count {
+            return Err(Error::LeafIndexOutOfRange {
+                index: index,
+                count: self.count,
+            });
+        }
+
+        self.check_updates(None)?;
+
+        let max = self.options.leaf_limit();
+
+        if self.count >= max {
+            return Err(Error::TooManyLeaves { max: max });
+        }
+
+        let leaf = Leaf::new(self.hash_leaf(&value), value);
+        let (mut leaves, levels) = self.take_leaves_and_levels();
+        leaves.insert(index, leaf);
+
+        self.rebuild_keeping(leaves, levels, index);
+
+        Ok(())
+    }
+
+    /// Removes the value at the given `index`, shifting the values after it to the left,
+    /// so that the tree is the one built with the same options over the new sequence of values,
+    /// and returns it. Removing the last value is the same as `MerkleTree::pop`.
+    ///
+    /// As with `MerkleTree::insert`, the nodes above the leaves before `index` keep their hash,
+    /// but every node above the leaves after it is rehashed.
+    ///
+    /// Fails, leaving the tree untouched, with `Error::LeafIndexOutOfRange` if `index` is not
+    /// lower than the number of leaves, and with `Error::Pruned` if the tree has pruned subtrees.
+    pub fn remove(&mut self, index: usize) -> Result<T, Error> {
+        if index >= self.count {
+            return Err(Error::LeafIndexOutOfRange {
+                index: index,
+                count: self.count,
+            });
+        }
+
+        self.check_unpruned()?;
+
+        let (mut leaves, levels) = self.take_leaves_and_levels();
+        let removed = leaves.remove(index);
+
+        self.rebuild_keeping(leaves, levels, index);
+
+        Ok(removed.value)
+    }
+
+    /// Removes all the leaves of the tree, which then has the root hash and height
+    /// of the empty tree built with the same options, as `MerkleTreeBuilder::build_empty` does.
+    ///
+    /// The allocation of the nodes is kept for the tree to be filled again with
+    /// `MerkleTree::rebuild_from_vec`. The checkpoints are kept too.
+    pub fn clear(&mut self) {
+        let buffer = self.take_buffer();
+        let root = build_root(self.algorithm, buffer, Vec::new(), &self.options);
+
+        self.root = LazyRoot::built(root);
+        self.height = self.options.fixed_depth.unwrap_or(0);
+        self.count = 0;
+        self.cache.clear();
+    }
+
+    /// Replaces the leaves of the tree by the given values, so that the tree is the one
+    /// `MerkleTreeBuilder::build_from_vec` builds over them with the same options,
+    /// laying its nodes out in the allocation of the previous ones, so that building trees
+    /// of similar sizes one after the other only allocates while hashing.
+    ///
+    /// Lazy trees do not reuse the allocation, as they only build their nodes when first
+    /// accessed. The checkpoints are kept.
+    ///
+    /// Panics if there are more than `max_leaves` values, see `MerkleTreeBuilder::max_leaves`.
+    /// The tree is left untouched if hashing or sorting the values panics.
+    pub fn rebuild_from_vec(&mut self, values: Vec<T>)
+    where
+        T: Hashable,
+    {
+
+        let max = self.options.leaf_limit();
+
+        if values.len() > max && self.options.dedup.is_none() {
+            panic!("too many values for `max_leaves`");
+        }
+
+        let algorithm = self.algorithm;
+
+        let tree = MerkleTreeBuilder::with_options(algorithm, self.options.clone())
+            .try_build_in(|| self.take_buffer(), values)
+            .expect("too many values for `max_leaves`");
+
+        // Lazy trees leave the previous nodes, which the checkpoints may still need
+        self.take_buffer();
+
+        self.root = tree.root;
+        self.height = tree.height;
+        self.count = tree.count;
+        self.options = tree.options;
+        self.cache.clear();
+    }
+
+    /// Takes the leaves of the tree out, along with the hashes of its internal nodes,
+    /// level by level, see `Subtree::internal_hash_levels`.
+    fn take_leaves_and_levels(&mut self) -> (Vec<Leaf<T>>, Vec<Vec<SmallHash>>) {
+        let root = self.take_root();
+
+        let levels = root
+            .root()
+            .internal_hash_levels()
+            .into_iter()
+            .map(|level| level.into_iter().cloned().collect())
+            .collect();
+
+        (root.into_leaves(), levels)
+    }
+
+    /// Rebuilds the tree over the given leaves, whose first `unchanged` ones are those
+    /// of the tree which had the internal hashes `levels`, taking from them the hashes of the
+    /// nodes above complete blocks of those leaves, rather than recomputing them.
+    fn rebuild_keeping(&mut self, leaves: Vec<Leaf<T>>, levels: Vec<Vec<SmallHash>>, unchanged: usize) {
+        let algorithm = self.algorithm;
+        let options = mem::replace(&mut self.options, TreeOptions::default());
+        let count = leaves.len();
+        let duplicate = options.odd_leaf_policy == OddLeafPolicy::Duplicate;
+        let mut height = 0;
+
+        // Only the nodes above complete blocks of `2^height` unchanged leaves keep their hash
+        let tree = build_root_with(algorithm, Tree::with_capacity(0), leaves, &options, |tree, level| {
+            height += 1;
+
+            let kept = unchanged >> height;
+            let mut hashes = levels.get(height - 1).map_or(Vec::new(), |hashes| hashes[..kept].to_vec());
+
+            for (index, pair) in level.chunks(2).enumerate().skip(kept) {
+                let (left, right) = match *pair {
+                    [left, right] => (left, right),
+                    [node] if duplicate => (node, node),
+                    _ => continue,
+                };
+
+                let leaves = tree::subtree_leaves(height, index, count);
+                let (left, right) = (tree.node_hash(left), tree.node_hash(right));
+
+                hashes.push(options.hash_subtree(algorithm, left, right, leaves).as_ref().into());
+            }
+
+            hashes
+        });
+
+        self.root = LazyRoot::built(tree);
+        self.height = options.fixed_depth.unwrap_or(balanced_height(count));
+        self.count = count;
+        self.options = options;
+        self.cache.clear();
+    }
+
+    /// Takes the nodes of the tree out, if they have been built, to build another tree in their
+    /// allocation, see `Tree::recycle`. The root of the tree must then be replaced.
+    fn take_buffer(&mut self) -> Tree<T> {
+        match self.root.take_built() {
+            Some(tree) => {
+                self.checkpoints.preserve(&tree);
+                tree
+            }
+
+            None => Tree::with_capacity(0),
+        }
+    }
+
+    /// Keeps only the values for which `f` returns `true`, in order, and rebuilds the tree over them.
+    /// Returns the number of values which were removed.
+    ///
+    /// The leaf hashes of the retained values are reused rather than recomputed,
+    /// and the resulting tree is the same as one built from scratch over those values.
+    /// If `f` panics, the tree is left untouched.
+    pub fn retain<F>(&mut self, f: F) -> usize
+    where
+        F: FnMut(&T) -> bool,
+    {
+
+        let mut removed = 0;
+        self.retain_with(f, |_| removed += 1);
+        removed
+    }
+
+    /// Works like `MerkleTree::retain`, but returns the removed values, in order.
+    pub fn retain_collect<F>(&mut self, f: F) -> Vec<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+
+        let mut removed = Vec::new();
+        self.retain_with(f, |value| removed.push(value));
+        removed
+    }
+
+    /// Replaces the subtrees `depth` levels below the root by nodes which only keep their hash
+    /// and their number of leaves, dropping their values and the nodes below them. The leaves
+    /// above that depth are kept. The root hash of the tree does not change, and the subtrees
+    /// can be put back with `MerkleTree::graft`.
+    ///
+    /// The pruned leaves are still counted by `MerkleTree::count`, but not yielded by the
+    /// iterators over the leaves, and their proofs cannot be generated anymore, see
+    /// `MerkleTree::try_gen_nth_proof`. Adding or removing values, serializing the tree
+    /// and generating multiproofs or prefix proofs over pruned leaves fail.
+    pub fn prune_below(&mut self, depth: usize) {
+        let root = self.take_root();
+        self.root = LazyRoot::built(root.prune_below(depth));
+    }
+
+    /// Puts back `subtree` in place of the leftmost subtree pruned by `MerkleTree::prune_below`
+    /// whose root hash is `subtree_root_hash`. The root hash of the tree does not change.
+    ///
+    /// Fails with `Error::InvalidGraft`, leaving the tree untouched, if there is no such pruned
+    /// subtree with as many leaves as `subtree`, or if `subtree` does not have that root hash,
+    /// or was not built with the same hashing algorithm, scheme and hasher as this tree.
+    pub fn graft(&mut self, subtree_root_hash: &[u8], subtree: MerkleTree<T>) -> Result<(), Error> {
+        if algorithm_tag(subtree.algorithm) != algorithm_tag(self.algorithm) ||
+            subtree.options.scheme != self.options.scheme
+        {
+            return Err(Error::InvalidGraft("the subtree was built with another algorithm or scheme"));
+        }
+
+        if !subtree.options.same_hasher(&self.options) {
+            return Err(Error::InvalidGraft("the subtree was built with another hasher"));
+        }
+
+        if subtree.root_hash().as_slice() != subtree_root_hash {
+            return Err(Error::InvalidGraft("the subtree does not have the given root hash"));
+        }
+
+        let mut subtree = subtree;
+        let root = self.take_root();
+
+        match root.graft(subtree_root_hash, subtree.take_root()) {
+            Ok(root) => {
+                self.root = LazyRoot::built(root);
+                Ok(())
+            }
+
+            Err(rejected) => {
+                self.root = LazyRoot::built(rejected.0);
+                Err(Error::InvalidGraft("no pruned subtree has this root hash and as many leaves"))
+            }
+        }
+    }
+
+    /// Replaces the value of the leaf at the given `index`, rehashing the nodes on its path
+    /// to the root, and returns the previous value. See `MerkleTree::apply_updates`, which fails
+    /// in the same cases, to update several leaves at once.
+    pub fn update(&mut self, index: usize, value: T) -> Result<T, Error>
+    where
+        T: Hashable,
+    {
+
+        let (mut replaced, _) = self.update_leaves(vec![(index, value)])?;
+        Ok(replaced.remove(0))
+    }
+
+    /// Replaces the values of the leaves at the given indices, in order, then rehashes every
+    /// internal node above them exactly once, from the leaves up, rather than once per update
+    /// as successive calls to `MerkleTree::update` do. The tree is then the same as after those
+    /// calls, a leaf updated several times getting the last of its values.
+    ///
+    /// Fails, leaving the tree untouched, with `Error::LeafIndexOutOfRange` if any index is not
+    /// lower than the number of leaves, with `Error::Pruned` if the tree has pruned subtrees,
+    /// and with `Error::InvalidUpdate` if its leaves are sorted or deduplicated, as an update
+    /// could break their order.
+    pub fn apply_updates<I>(&mut self, updates: I) -> Result<UpdateSummary, Error>
+    where
+        T: Hashable,
+        I: IntoIterator<Item = (usize, T)>,
+    {
+
+        self.update_leaves(updates.into_iter().collect()).map(|(_, summary)| summary)
+    }
+
+    /// Replaces the values of the given leaves, returning the previous values, in order,
+    /// along with what was done, see `Tree::update_leaves`.
+    fn update_leaves(&mut self, updates: Vec<(usize, T)>) -> Result<(Vec<T>, UpdateSummary), Error>
+    where
+        T: Hashable,
+    {
+
+        self.check_updates(updates.iter().map(|&(index, _)| index))?;
+
+        let (indices, values): (Vec<_>, Vec<_>) = updates.into_iter().unzip();
+        let leaves = indices.into_iter().zip(self.options.leaves(self.algorithm, values)).collect();
+
+        Ok(self.replace_leaves(leaves))
+    }
+
+    /// Fails as `MerkleTree::apply_updates` does if the leaves at the given indices
+    /// cannot be updated.
+    pub(crate) fn check_updates<I>(&self, indices: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = usize>,
+    {
+
+        if let Some(index) = indices.into_iter().find(|&index| index >= self.count) {
+            return Err(Error::LeafIndexOutOfRange {
+                index: index,
+                count: self.count,
+            });
+        }
+
+        self.check_unpruned()?;
+
+        if self.options.ordering.is_sorted() {
+            return Err(Error::InvalidUpdate("the leaves of the tree are sorted"));
+        }
+
+        if self.options.dedup.is_some() {
+            return Err(Error::InvalidUpdate("the leaves of the tree are deduplicated"));
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the leaves at the given indices, which must have been checked with
+    /// `MerkleTree::check_updates`, along with their hashes, and rehashes the nodes above them.
+    pub(crate) fn replace_leaves(&mut self, leaves: Vec<(usize, Leaf<T>)>) -> (Vec<T>, UpdateSummary) {
+        let mut indices = leaves.iter().map(|&(index, _)| index).collect::<Vec<_>>();
+        indices.sort();
+        indices.dedup();
+
+        let (replaced, recomputed) = self.rehash_with(|root, copy_odd, hash_nodes| {
+            root.update_leaves(leaves, copy_odd, hash_nodes)
+        });
+
+        let summary = UpdateSummary {
+            leaves: indices,
+            recomputed: recomputed,
+        };
+
+        (replaced, summary)
+    }
+
+    /// Exchanges the values of the leaves at the given indices, along with their hashes,
+    /// and rehashes the nodes on both their paths to the root, those they share only once.
+    /// The tree is then the one built over the values in their new order.
+    ///
+    /// Does nothing if `i` and `j` are the same index, and fails, leaving the tree untouched,
+    /// as `MerkleTree::apply_updates` does otherwise.
+    pub fn swap(&mut self, i: usize, j: usize) -> Result<(), Error> {
+        self.check_updates(vec![i, j])?;
+
+        if i != j {
+            self.rehash_with(|root, copy_odd, hash_nodes| root.swap_leaves(i, j, copy_odd, hash_nodes));
+        }
+
+        Ok(())
+    }
+
+    /// Hands the tree over to `modify`, which changes some of its leaves and rehashes the nodes
+    /// above them, along with whether the odd nodes are paired with a copy and the way to hash
+    /// a node given its level and index. The checkpoints which still share the tree get a copy
+    /// of it first, and the proof cache is cleared.
+    fn rehash_with<F, U>(&mut self, modify: F) -> U
+    where
+        F: FnOnce(&mut Tree<T>, bool, &mut dyn FnMut((usize, usize), &[u8], &[u8]) -> SmallHash) -> U,
+    {
+
+        let algorithm = self.algorithm;
+        let options = &self.options;
+        let count = self.count;
+        let copy_odd = options.odd_leaf_policy == OddLeafPolicy::Duplicate && options.fixed_depth.is_none();
+        let root = self.root.get_mut(|pending| build_pending(algorithm, options, pending));
+
+        self.checkpoints.preserve(root);
+        self.cache.clear();
+
+        modify(root, copy_odd, &mut |(level, index), left, right| {
+            let leaves = tree::subtree_leaves(level, index, count);
+            options.hash_subtree(algorithm, &left, &right, leaves).as_ref().into()
+        })
+    }
+
+    /// Fails with `Error::Pruned` if the tree has pruned subtrees, see `MerkleTree::prune_below`.
+    pub(crate) fn check_unpruned(&self) -> Result<(), Error> {
+        match self.tree().first_pruned() {
+            Some(hash) => Err(Error::Pruned { subtree_root: hash.to_vec() }),
+            None => Ok(()),
+        }
+    }
+
+    fn retain_with<F, G>(&mut self, mut keep: F, mut remove: G)
+    where
+        F: FnMut(&T) -> bool,
+        G: FnMut(T),
+    {
+
+        if let Err(err) = self.check_unpruned() {
+            panic!("cannot retain values: {}", err);
+        }
+
+        // `keep` is called before the tree is taken apart, as it may panic
+        let kept = self.iter().map(&mut keep).collect::<Vec<_>>();
+        let root = self.take_root();
+
+        let mut leaves = Vec::with_capacity(self.count);
+
+        for (leaf, kept) in root.into_leaves().into_iter().zip(kept) {
+            if kept {
+                leaves.push(leaf);
+            } else {
+                remove(leaf.value);
+            }
+        }
+
+        let options = mem::take(&mut self.options);
+
+        self.rebuild(leaves, options);
+    }
+
+    /// Rebuilds the tree over the given leaves, clearing the proof cache.
+    fn rebuild(&mut self, leaves: Vec<Leaf<T>>, options: TreeOptions<T>) {
+        let mut cache = mem::replace(&mut self.cache, ProofCache::new(0));
+        let checkpoints = mem::take(&mut self.checkpoints);
+        cache.clear();
+
+        *self = MerkleTree::from_leaves_with(self.algorithm, leaves, options);
+        self.cache = cache;
+        self.checkpoints = checkpoints;
+    }
+
+    /// Returns the options the tree was built with.
+    pub(crate) fn options(&self) -> &TreeOptions<T> {
+        &self.options
+    }
+
+    fn hash_leaf(&self, value: &T) -> Digest
+    where
+        T: Hashable,
+    {
+
+        self.options.hash_leaf(self.algorithm, value)
+    }
+
+    /// Tells whether the odd nodes of the tree are paired with an `Empty` node, rather than
+    /// being promoted, which is the case with `OddLeafPolicy::Duplicate` and with a fixed depth.
+    pub(crate) fn is_padded(&self) -> bool {
+        self.options.odd_leaf_policy == OddLeafPolicy::Duplicate || self.options.fixed_depth.is_some()
+    }
+
+    /// Wraps the lemma of a leaf of a tree with `count` leaves, built with the options
+    /// of this tree, into a proof of the given value.
+    pub(crate) fn new_proof<U>(&self, root_hash: Vec<u8>, lemma: Lemma, value: U, count: usize) -> Proof<U> {
+        let (size, policy) = match self.options.fixed_depth {
+            // Every slot of a tree of fixed depth is a leaf, filled or not
+            Some(depth) => (1 << depth, OddLeafPolicy::Promote),
+            None => (count, self.options.odd_leaf_policy),
+        };
+
+        let index = lemma.leaf_index(size, policy);
+
+        let leaf_counts = if self.options.leaf_counts {
+            lemma.leaf_counts(size, policy, count)
+        } else {
+            None
+        };
+
+        let mut proof = Proof::new(self.algorithm, root_hash, lemma, value);
+        proof.hasher = self.options.hasher.clone();
+        proof.scheme = self.options.scheme;
+        proof.odd_leaf_policy = policy;
+        proof.tree_size = Some(size as u64);
+        proof.leaf_index = index.map(|index| index as u64);
+        proof.leaf_counts = leaf_counts;
+        proof
+    }
+
+    /// Creates an `Iterator` over the values contained in this Merkle tree.
+    pub fn iter(&self) -> LeavesIterator<T> {
+        self.tree().iter()
+    }
+
+    /// Creates an `Iterator` over the hashes of the leaves of this Merkle tree, from left to right,
+    /// without going through their values, leaving out the pruned ones. Hashes the tree first
+    /// if it is lazy.
+    pub fn iter_leaf_hashes(&self) -> LeafHashes<T> {
+        let tree = self.tree();
+        tree.iter_leaf_hashes(self.count - tree.pruned_leaves())
+    }
+
+    /// Creates an `Iterator` over the nodes of this Merkle tree, each before its children,
+    /// without recursion. Hashes the tree first if it is lazy.
+    pub fn iter_nodes_preorder(&self) -> PreOrderNodes<T> {
+        PreOrderNodes::new(self.tree())
+    }
+
+    /// Creates an `Iterator` over the nodes of this Merkle tree, each after its children,
+    /// without recursion. Hashes the tree first if it is lazy.
+    pub fn iter_nodes_postorder(&self) -> PostOrderNodes<T> {
+        PostOrderNodes::new(self.tree())
+    }
+
+    /// Returns the hashes of the leaves of this Merkle tree, from left to right,
+    /// see `MerkleTree::iter_leaf_hashes`.
+    pub fn leaf_hashes_vec(&self) -> Vec<Vec<u8>> {
+        self.iter_leaf_hashes().map(|hash| hash.to_vec()).collect()
+    }
+}
+
+/// What `MerkleTree::apply_updates` did to a tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct UpdateSummary {
+    /// The indices of the updated leaves, in increasing order
+    pub leaves: Vec<usize>,
+
+    /// The level and the index of every rehashed internal node, as in `MerkleTree::node_hash`,
+    /// each listed once, below its ancestors
+    pub recomputed: Vec<(usize, usize)>,
+}
+
+/// The order of the leaves of a tree built by `MerkleTree::from_vec_dedup`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DedupOrder {
+    /// The leaves are in the order of the first occurrence of their value in the input
+    FirstOccurrence,
+
+    /// The leaves are sorted by leaf hash, so that the root only depends on the set of values
+    ByHash,
+}
+
+/// The serialized fields of a `MerkleTree`.
+#[cfg(feature = "serialization-serde")]
+#[derive(Deserialize)]
+struct MerkleTreeData<T> {
+    root: Tree<T>,
+    height: usize,
+    count: usize,
+}
+
+/// The fields of a `MerkleTree` to serialize, once it is hashed.
+#[cfg(feature = "serialization-serde")]
+#[derive(Serialize)]
+#[serde(rename = "MerkleTree")]
+struct MerkleTreeDataRef<'a, T: 'a> {
+    root: &'a Tree<T>,
+    height: usize,
+    count: usize,
+}
+
+/// Lazy trees are hashed before being serialized.
+#[cfg(feature = "serialization-serde")]
+impl<T: Serialize> Serialize for MerkleTree<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+
+        let data = MerkleTreeDataRef {
+            root: self.tree(),
+            height: self.height,
+            count: self.count,
+        };
+
+        data.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<T> MerkleTree<T> {
+    /// Deserializes a Merkle tree built with the given hashing `algorithm`, and checks it.
+    ///
+    /// Every leaf hash is recomputed from its value, and every internal hash from
+    /// the hashes of its children. The first mismatch is reported as `Error::HashMismatch`,
+    /// and a tree whose shape could not have been built by this crate (eg. whose height
+    /// or leaf count is not the stored one, or whose nodes do not split their leaves as
+    /// `MerkleTree::from_vec` does) as `Error::MalformedTree`.
+    ///
+    /// Use this for snapshots coming from untrusted sources.
+    pub fn deserialize_verified<'de, D>(
+        algorithm: &'static Algorithm,
+        deserializer: D,
+    ) -> Result<Self, Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de> + Hashable,
+    {
+
+        let tree = MerkleTree::deserialize_unverified(algorithm, deserializer)?;
+
+        let (count, height) = tree.tree().root().shape()?;
+
+        if count != tree.count {
+            return Err(Error::MalformedTree("leaf count does not match the tree"));
+        }
+
+        if height != tree.height {
+            return Err(Error::MalformedTree("height does not match the tree"));
+        }
+
+        if height != balanced_height(count) {
+            return Err(Error::MalformedTree("tree is not balanced"));
+        }
+
+        tree.tree().root().check_hashes(algorithm, 0, 0)?;
+
+        Ok(tree)
+    }
+
+    /// Deserializes a Merkle tree built with the given hashing `algorithm`,
+    /// trusting the hashes it contains.
+    ///
+    /// This is faster than `MerkleTree::deserialize_verified`, but must only be used
+    /// for trusted snapshots, since an inconsistent tree would yield invalid proofs.
+    pub fn deserialize_unverified<'de, D>(
+        algorithm: &'static Algorithm,
+        deserializer: D,
+    ) -> Result<Self, Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+
+        let data = MerkleTreeData::deserialize(deserializer).map_err(|err| {
+            Error::Decode(err.to_string())
+        })?;
+
+        Ok(MerkleTree {
+            algorithm: algorithm,
+            root: LazyRoot::built(data.root),
+            height: data.height,
+            count: data.count,
+            options: TreeOptions::default(),
+            cache: ProofCache::new(0),
+            checkpoints: Checkpoints::default(),
+        })
+    }
+}
+
+/// Builds the internal levels of a Merkle tree on top of the given leaves, returning the tree,
+/// whose nodes are laid out in the allocation of `buffer`, see `Tree::recycle`.
+fn build_root<T>(
+    algorithm: &'static Algorithm,
+    buffer: Tree<T>,
+    leaves: Vec<Leaf<T>>,
+    options: &TreeOptions<T>,
+) -> Tree<T> {
+
+    let count = leaves.len();
+    let mut height = 0;
+
+    build_root_with(algorithm, buffer, leaves, options, |tree, level| {
+        height += 1;
+        level_hashes(algorithm, options, tree, level, height, count)
+    })
+}
+
+/// Works like `build_root`, but takes the hashes of each internal level from `hash_level`,
+/// which is given the nodes of the level below, rather than computing them with `level_hashes`.
+fn build_root_with<T, F, H>(
+    algorithm: &'static Algorithm,
+    buffer: Tree<T>,
+    leaves: Vec<Leaf<T>>,
+    options: &TreeOptions<T>,
+    hash_level: F,
+) -> Tree<T>
+where
+    F: FnMut(&Tree<T>, &[NodeId]) -> Vec<H>,
+    H: AsRef<[u8]>,
+{
+
+    if let Some(depth) = options.fixed_depth {
+        return build_fixed_depth(algorithm, buffer, leaves, options, depth, hash_level);
+    }
+
+    if leaves.is_empty() {
+        let mut tree = buffer.recycle(1);
+        tree.push_empty(options.hash_empty(algorithm).as_ref().into());
+        return tree;
+    }
+
+    // Every node but the root has a sibling, save for at most one promoted or duplicated node
+    // per level
+    let mut tree = buffer.recycle(2 * leaves.len() + ceil_log2(leaves.len()));
+    let cur = leaves.into_iter().map(|leaf| tree.push_leaf(leaf)).collect::<Vec<_>>();
+    options.level_built(0, cur.len());
+
+    build_levels(options, &mut tree, cur, 0, hash_level);
+
+    tree
+}
+
+/// Adds to `tree` the levels above the nodes of `cur`, which lie at the given `level`, up to
+/// the root, whose hashes are given by `hash_level`, see `level_hashes`.
+fn build_levels<T, F, H>(
+    options: &TreeOptions<T>,
+    tree: &mut Tree<T>,
+    mut cur: Vec<NodeId>,
+    mut level: usize,
+    mut hash_level: F,
+) where
+    F: FnMut(&Tree<T>, &[NodeId]) -> Vec<H>,
+    H: AsRef<[u8]>,
+{
+
+    while cur.len() > 1 {
+        let mut hashes = hash_level(tree, &cur).into_iter();
+        let mut next = Vec::with_capacity(cur.len().div_ceil(2));
+        let mut nodes = cur.into_iter();
+
+        while let Some(left) = nodes.next() {
+            let right = match (nodes.next(), options.odd_leaf_policy) {
+                (Some(right), _) => right,
+                (None, OddLeafPolicy::Promote) => {
+                    next.push(left);
+                    break;
+                }
+                (None, OddLeafPolicy::Duplicate) => {
+                    let hash = *tree.node_hash(left);
+                    tree.push_empty(hash)
+                }
+            };
+
+            let combined_hash = hashes.next().expect("one hash per pair of nodes");
+
+            next.push(tree.push_node(combined_hash.as_ref().into(), left, right));
+        }
+
+        cur = next;
+        level += 1;
+        options.level_built(level, cur.len());
+    }
+
+    debug_assert!(cur.len() == 1);
+}
+
+/// Builds the `depth` internal levels of a tree of fixed depth on top of the given leaves,
+/// which fill its leftmost slots, returning the tree. Their hashes are given by `hash_level`,
+/// see `level_hashes`.
+///
+/// The last node of a level with an odd number of nodes is paired with an `Empty` node holding
+/// the hash of a subtree of zero leaves, and so is the root of a tree without any leaf.
+fn build_fixed_depth<T, F, H>(
+    algorithm: &'static Algorithm,
+    buffer: Tree<T>,
+    leaves: Vec<Leaf<T>>,
+    options: &TreeOptions<T>,
+    depth: usize,
+    mut hash_level: F,
+) -> Tree<T>
+where
+    F: FnMut(&Tree<T>, &[NodeId]) -> Vec<H>,
+    H: AsRef<[u8]>,
+{
+
+    let default_hashes = options.default_hashes(algorithm, depth);
+
+    let mut tree = buffer.recycle(2 * leaves.len() + depth + 1);
+
+    if leaves.is_empty() {
+        tree.push_empty(SmallHash::from(&default_hashes[depth]));
+        return tree;
+    }
+
+    let mut cur = leaves.into_iter().map(|leaf| tree.push_leaf(leaf)).collect::<Vec<_>>();
+    options.level_built(0, cur.len());
+
+    for (level, default_hash) in default_hashes[..depth].iter().enumerate() {
+        if cur.len() % 2 == 1 {
+            cur.push(tree.push_empty(SmallHash::from(default_hash)));
+        }
+
+        let hashes = hash_level(&tree, &cur);
+        let mut nodes = cur.into_iter();
+
+        cur = hashes
+            .into_iter()
+            .map(|hash| {
+                let left = nodes.next().expect("two nodes per hash");
+                let right = nodes.next().expect("two nodes per hash");
+
+                tree.push_node(hash.as_ref().into(), left, right)
+            })
+            .collect();
+
+        options.level_built(level + 1, cur.len());
+    }
+
+    debug_assert!(cur.len() == 1);
+
+    tree
+}
+
+/// Hashes the values of a lazy tree, and builds the tree on top of them.
+fn build_pending<T>(
+    algorithm: &'static Algorithm,
+    options: &TreeOptions<T>,
+    pending: Pending<T>,
+) -> Tree<T> {
+
+    build_root(algorithm, Tree::with_capacity(0), pending.into_leaves(options, algorithm), options)
+}
+
+/// Inserts the given new leaves among `leaves`, each one at the position of `positions`
+/// among the latter, which go in increasing order.
+fn merge_at<T>(leaves: Vec<Leaf<T>>, new_leaves: Vec<Leaf<T>>, positions: Vec<usize>) -> Vec<Leaf<T>> {
+    let mut merged = Vec::with_capacity(leaves.len() + new_leaves.len());
+    let mut leaves = leaves.into_iter();
+    let mut taken = 0;
+
+    for (leaf, position) in new_leaves.into_iter().zip(positions) {
+        merged.extend(leaves.by_ref().take(position - taken));
+        merged.push(leaf);
+        taken = position;
+    }
+
+    merged.extend(leaves);
+    merged
+}
+
+/// The values checked by `MerkleTree::find_stale_leaves_parallel` are hashed in parallel from
+/// this many, and so are the entries checked by `MerkleTree::verify_manifest`, when enabled.
+pub(crate) const PARALLEL_THRESHOLD: usize = 1 << 12;
+
+/// Computes the hashes of the nodes of the level above `level`, a level of `tree`,
+/// save for the promoted ones. Those nodes lie at the given `height` of a tree of `count`
+/// leaves, which tells how many leaves they have beneath them when the tree has `leaf_counts`.
+fn level_hashes<T>(
+    algorithm: &'static Algorithm,
+    options: &TreeOptions<T>,
+    tree: &Tree<T>,
+    level: &[NodeId],
+    height: usize,
+    count: usize,
+) -> Vec<Digest> {
+
+    let hasher = options.hasher(&algorithm);
+    let scheme = options.scheme;
+    let counted = options.leaf_counts;
+    let duplicate = options.odd_leaf_policy == OddLeafPolicy::Duplicate;
+    let hashes = level.iter().map(|&node| tree.node_hash(node).as_slice()).collect::<Vec<_>>();
+
+    let hash_node = move |left: &[u8], right: &[u8], index: usize| if counted {
+        let leaves = tree::subtree_leaves(height, index, count);
+        scheme.hash_counted_nodes_with(hasher, &left, &right, leaves)
+    } else {
+        scheme.hash_nodes_with(hasher, &left, &right)
+    };
+
+    // The pairs are given along with the index of the first of their parents
+    let hash_pairs = |pairs: &[&[u8]], first: usize| {
+        let mut hashes = Vec::with_capacity(pairs.len().div_ceil(2));
+
+        hashes.extend(pairs.chunks(2).zip(first..).filter_map(|(pair, index)| match *pair {
+            [left, right] => Some(hash_node(left, right, index)),
+            [node] if duplicate => Some(hash_node(node, node, index)),
+            _ => None,
+        }));
+
+        hashes
+    };
+
+    if !options.parallel || hashes.len() < options.parallel_threshold {
+        return hash_pairs(&hashes, 0);
+    }
+
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+
+    // Each thread gets an even number of nodes, so that no pair is split across threads
+    let chunk_len = (hashes.len() / threads + 2) & !1;
+
+    let hash_pairs = &hash_pairs;
+
+    thread::scope(|scope| {
+        let handles = hashes
+            .chunks(chunk_len)
+            .enumerate()
+            .map(|(i, chunk)| scope.spawn(move || hash_pairs(chunk, i * chunk_len / 2)))
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("hashing does not panic"))
+            .collect()
+    })
+}
+
+/// Returns the number of internal nodes of the tree built over `count` leaves with the given
+/// options, leaving out the `Empty` ones.
+pub(crate) fn internal_node_count<T>(count: usize, options: &TreeOptions<T>) -> usize {
+    let mut len = count;
+    let mut total = 0;
+
+    match options.fixed_depth {
+        Some(_) if count == 0 => {}
+
+        Some(depth) => {
+            for _ in 0..depth {
+                len = len.div_ceil(2);
+                total += len;
+            }
+        }
+
+        None => {
+            while len > 1 {
+                total += match options.odd_leaf_policy {
+                    OddLeafPolicy::Promote => len / 2,
+                    OddLeafPolicy::Duplicate => len.div_ceil(2),
+                };
+
+                len = len.div_ceil(2);
+            }
+        }
+    }
+
+    total
+}
+
+/// Returns the height of the tree built by `MerkleTree::from_leaves` over `count` leaves.
+fn balanced_height(count: usize) -> usize {
+    let mut len = count;
+    let mut height = 0;
+
+    while len > 1 {
+        len = len.div_ceil(2);
+        height += 1;
+    }
+
+    height
+}
+
+impl<T> IntoIterator for MerkleTree<T> {
+    type Item = T;
+    type IntoIter = LeavesIntoIterator<T>;
+
+    /// Creates a consuming iterator, that is, one that moves each value out of the Merkle tree.
+    /// The tree cannot be used after calling this.
+    fn into_iter(self) -> Self::IntoIter {
+        let algorithm = self.algorithm;
+        let options = self.options;
+
+        self.root
+            .into_tree(|pending| build_pending(algorithm, &options, pending))
+            .into_iter()
+    }
+}
+
+impl<T: Hashable> Extend<T> for MerkleTree<T> {
+    /// Adds the given values to the Merkle tree, see `MerkleTree::try_extend`.
+    ///
+    /// Panics if the tree would get more leaves than allowed by `MerkleTreeBuilder::max_leaves`.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        self.try_extend(values).expect("too many values for `max_leaves`");
+    }
+}
+
+impl<'a, T> IntoIterator for &'a MerkleTree<T> {
+    type Item = &'a T;
+    type IntoIter = LeavesIterator<'a, T>;
+
+    /// Creates a borrowing `Iterator` over the values contained in this Merkle tree.
+    fn into_iter(self) -> Self::IntoIter {
+        self.tree().iter()
+    }
+}
+
+impl MerkleTree<String> {
+    /// Constructs a Merkle tree whose leaves are the lines read from `reader`.
+    ///
+    /// The input is split on `\n`, and the terminating `\n` or `\r\n` is not part
+    /// of the hashed line. A final line without a terminating newline is treated like
+    /// any other line, so `"a\nb"` and `"a\nb\r\n"` yield the same tree, whereas a `\r`
+    /// that is not followed by `\n` is kept. These are the semantics of `BufRead::lines`.
+    ///
+    /// Returns an error of kind `InvalidData` if a line is not valid UTF-8.
+    pub fn from_lines<R>(algorithm: &'static Algorithm, mut reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+
+        let mut leaves = Vec::new();
+        let mut buf = Vec::new();
+
+        while read_line_bytes(&mut reader, &mut buf)? {
+            let bytes = mem::take(&mut buf);
+            let line = String::from_utf8(bytes).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, e)
+            })?;
+
+            leaves.push(Leaf::from_value(algorithm, line));
+        }
+
+        Ok(MerkleTree::from_leaves(algorithm, leaves))
+    }
+}
+
+impl MerkleTree<Vec<u8>> {
+    /// Constructs a Merkle tree whose leaves are the raw bytes of the lines read from `reader`.
+    ///
+    /// Lines are split as in `MerkleTree::from_lines`, but need not be valid UTF-8.
+    pub fn from_byte_lines<R>(algorithm: &'static Algorithm, mut reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+
+        let mut leaves = Vec::new();
+        let mut buf = Vec::new();
+
+        while read_line_bytes(&mut reader, &mut buf)? {
+            let line = mem::take(&mut buf);
+            leaves.push(Leaf::from_value(algorithm, line));
+        }
+
+        Ok(MerkleTree::from_leaves(algorithm, leaves))
+    }
+}
+
+impl MerkleTree<()> {
+    /// Constructs a Merkle tree, with the default options, over leaves of which only the hashes
+    /// are known, as returned by `MerkleTree::leaf_hashes_vec`.
+    ///
+    /// Fails with `Error::HashLengthMismatch` if a hash does not have the output length
+    /// of `algorithm`.
+    pub fn from_leaf_hashes(algorithm: &'static Algorithm, hashes: Vec<Vec<u8>>) -> Result<Self, Error> {
+        let leaves = hashes
+            .into_iter()
+            .map(|hash| {
+                if hash.len() != algorithm.output_len {
+                    return Err(Error::HashLengthMismatch {
+                        expected: algorithm.output_len,
+                        found: hash.len(),
+                    });
+                }
+
+                Ok(Leaf {
+                    hash: SmallHash::from(hash),
+                    value: (),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(MerkleTree::from_leaves(algorithm, leaves))
+    }
+
+    /// Constructs a Merkle tree over the lines read from `reader`, retaining only their hashes.
+    ///
+    /// Lines are split as in `MerkleTree::from_lines`, and the root hash is the same as the
+    /// one of the tree built by `MerkleTree::from_byte_lines` over the same input, but the
+    /// lines themselves are dropped as soon as they have been hashed.
+    pub fn from_lines_hashed<R>(algorithm: &'static Algorithm, mut reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+
+        let mut leaves = Vec::new();
+        let mut buf = Vec::new();
+
+        while read_line_bytes(&mut reader, &mut buf)? {
+            leaves.push(Leaf::new(algorithm.hash_leaf(&buf), ()));
+        }
+
+        Ok(MerkleTree::from_leaves(algorithm, leaves))
+    }
+}
+
+/// Reads the next line from `reader` into `buf`, without its terminating `\n` or `\r\n`.
+/// Returns `false` once the end of the input has been reached.
+fn read_line_bytes<R>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<bool>
+where
+    R: BufRead,
+{
+
+    buf.clear();
+
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(false);
+    }
+
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+    }
+
+    Ok(true)
 }
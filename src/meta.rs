@@ -0,0 +1,119 @@
+use std::mem;
+
+use digest::{Algorithm, Context};
+
+use hashutils::Hashable;
+use merkletree::MerkleTree;
+use proof::Proof;
+
+/// A value along with auxiliary metadata, such as a timestamp or the origin of the value,
+/// which is carried by a leaf of a `MerkleTree` without being hashed.
+///
+/// Only the `value` goes into the hash of the leaf, so that the metadata can be changed with
+/// `MerkleTree::set_meta` without changing any hash of the tree. It is hence not authenticated:
+/// a proof which holds it, such as one from `MerkleTree::gen_nth_proof`, holds whatever
+/// the metadata, which must not be trusted on the word of the proof, see `Proof::split_meta`.
+///
+/// When a tree of `WithMeta` values is serialized, the metadata is serialized along with
+/// each value, and deserializing the tree gives it back as it was.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct WithMeta<T, M> {
+    /// The value, which is hashed into the leaf
+    pub value: T,
+
+    /// The metadata, which is not hashed
+    pub meta: M,
+}
+
+impl<T, M> WithMeta<T, M> {
+    /// Attaches the given metadata to the value.
+    pub fn new(value: T, meta: M) -> Self {
+        WithMeta {
+            value: value,
+            meta: meta,
+        }
+    }
+}
+
+/// Hashes the value alone, leaving the metadata out.
+impl<T: Hashable, M> Hashable for WithMeta<T, M> {
+    fn update_context(&self, context: &mut Context) {
+        self.value.update_context(context);
+    }
+
+    fn hashed_bytes(&self) -> Option<&[u8]> {
+        self.value.hashed_bytes()
+    }
+}
+
+impl<T, M> MerkleTree<WithMeta<T, M>> {
+    /// Constructs a Merkle tree over the given values, each of them along with its metadata,
+    /// which is not hashed. The tree has the same hashes as one built with `from_vec` over
+    /// the values alone.
+    pub fn from_vec_with_meta(algorithm: &'static Algorithm, values: Vec<(T, M)>) -> Self
+    where
+        T: Hashable,
+    {
+
+        let values = values
+            .into_iter()
+            .map(|(value, meta)| WithMeta::new(value, meta))
+            .collect();
+
+        MerkleTree::from_vec(algorithm, values)
+    }
+
+    /// Returns the metadata of the leaf at the given `index`,
+    /// or `None` if there is no such leaf or if it is pruned.
+    pub fn meta(&self, index: usize) -> Option<&M> {
+        self.tree().root().nth_value(index, self.count()).map(|leaf| &leaf.meta)
+    }
+
+    /// Replaces the metadata of the leaf at the given `index`, returning the previous one,
+    /// or `None`, leaving the tree untouched, if there is no such leaf or if it is pruned.
+    ///
+    /// Nothing is rehashed, and the root hash stays the same.
+    pub fn set_meta(&mut self, index: usize, meta: M) -> Option<M> {
+        self.nth_value_mut(index).map(|leaf| mem::replace(&mut leaf.meta, meta))
+    }
+
+    /// Creates an `Iterator` over the values of the tree along with their metadata.
+    pub fn iter_with_meta(&self) -> impl Iterator<Item = (&T, &M)> {
+        self.iter().map(|leaf| (&leaf.value, &leaf.meta))
+    }
+
+    /// Generates the proof of the value at the given `index` without its metadata,
+    /// as `MerkleTree::gen_nth_proof` does for a tree built over the values alone.
+    pub fn gen_nth_proof_without_meta(&self, index: usize) -> Option<Proof<T>>
+    where
+        T: Clone,
+        M: Clone,
+    {
+
+        self.gen_nth_proof(index).map(|proof| proof.split_meta().0)
+    }
+}
+
+impl<T, M> Proof<WithMeta<T, M>> {
+    /// Splits this proof into the proof of the value alone and the metadata it holds.
+    ///
+    /// The proof of the value holds just as this one does, as the metadata is not hashed,
+    /// which is also why the metadata is not authenticated by the proof.
+    pub fn split_meta(self) -> (Proof<T>, M) {
+        let proof = Proof {
+            algorithm: self.algorithm,
+            hasher: self.hasher,
+            root_hash: self.root_hash,
+            lemma: self.lemma,
+            value: self.value.value,
+            scheme: self.scheme,
+            tree_size: self.tree_size,
+            leaf_index: self.leaf_index,
+            odd_leaf_policy: self.odd_leaf_policy,
+            leaf_counts: self.leaf_counts,
+        };
+
+        (proof, self.value.meta)
+    }
+}
@@ -0,0 +1,203 @@
+//! Proofs in the JSON shape of merkletreejs, the JavaScript library, whose `getProof` returns
+//! the siblings of the path to a leaf from the leaf up, as
+//! `[{"position": "left", "data": "<hex>"}, ...]`, `position` being the side of the sibling,
+//! and whose `getHexRoot` returns the root hash as `0x`-prefixed hex.
+//!
+//! Only the shape of the proofs is shared: for a proof of this crate to verify with
+//! merkletreejs, and the other way around, both trees must hash the same way. With its default
+//! options, merkletreejs hashes internal nodes as `H(left || right)` and carries an odd node
+//! up to the next level as is, which is a tree of this crate hashed with
+//! `HashingScheme::Unprefixed` and the default `OddLeafPolicy::Promote`, and with the same
+//! algorithm, such as SHA-256, provided that merkletreejs hashes the leaves itself,
+//! with `hashLeaves: true`:
+//!
+//! - `sortPairs: true` is `HashingScheme::SortedPairs`, which ignores the positions;
+//! - `duplicateOdd: true`, `isBitcoinTree: true`, `sortLeaves: true` and `fillDefaultHash`
+//!   change the shape of the tree, and are not supported;
+//! - `keccak256`, which is how Ethereum projects use merkletreejs, along with `sortPairs: true`,
+//!   is not an `Algorithm` of this crate, so that such proofs are not read as `Proof`s:
+//!   they are checked by `keccak::validate_sorted_path`, the hex of their `data` in order
+//!   being the path, see the `keccak` module.
+//!
+//! The default `HashingScheme::DomainSeparated` prefixes leaves and nodes, which merkletreejs
+//! does not do, so that such proofs can be converted but do not verify there.
+
+use serde_json::{Map, Value};
+
+use digest::Algorithm;
+
+use error::Error;
+use hashutils::Hashable;
+use hex::{from_hex, to_hex};
+use options::HashingScheme;
+use proof::{CanonicalProofData, Positioned, Proof, MAX_TREE_DEPTH};
+
+impl<T> Proof<T> {
+    /// Returns the siblings of the path to the value, in the JSON shape of the `getProof` of
+    /// merkletreejs, from the leaf up, their hashes being lowercase hex without prefix,
+    /// as `data.toString('hex')` writes them, see the `mtjs` module.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::{MerkleTreeBuilder, HashingScheme, SHA256};
+    /// # fn main() {
+    /// let tree = MerkleTreeBuilder::new(SHA256)
+    ///     .hashing_scheme(HashingScheme::Unprefixed)
+    ///     .build_from_vec(vec!["a", "b", "c"]);
+    ///
+    /// let json = tree.gen_nth_proof(0).unwrap().to_mtjs_json();
+    ///
+    /// assert_eq!(json[0]["position"], "right");
+    /// assert_eq!(json[1]["position"], "right");
+    /// assert_eq!(json.as_array().unwrap().len(), 2);
+    /// # }
+    /// ```
+    pub fn to_mtjs_json(&self) -> Value {
+        let mut siblings = self
+            .lemmas()
+            .filter_map(|lemma| lemma.sibling_hash.as_ref())
+            .map(|sibling| {
+                let (position, hash) = match *sibling {
+                    Positioned::Left(ref hash) => ("left", hash),
+                    Positioned::Right(ref hash) => ("right", hash),
+                };
+
+                let mut object = Map::new();
+                object.insert("position".to_string(), Value::String(position.to_string()));
+                object.insert("data".to_string(), Value::String(to_hex(hash)));
+
+                Value::Object(object)
+            })
+            .collect::<Vec<_>>();
+
+        siblings.reverse();
+        Value::Array(siblings)
+    }
+
+    /// Returns the root hash of the proof as `0x`-prefixed lowercase hex, as the `getHexRoot`
+    /// of merkletreejs writes it.
+    pub fn mtjs_root(&self) -> String {
+        format!("0x{}", to_hex(&self.root_hash))
+    }
+}
+
+impl<T: Hashable> Proof<T> {
+    /// Reads a proof of `value` in the JSON shape of the `getProof` of merkletreejs against
+    /// the given hex `root`, as hashed by merkletreejs with its default options and
+    /// `hashLeaves: true`, ie. with `HashingScheme::Unprefixed`, see the `mtjs` module.
+    ///
+    /// The proof is not validated: call `Proof::validate` with the root it is expected to have.
+    ///
+    /// Fails with `Error::Decode` if `json` is not an array of objects with a `position`
+    /// of either `"left"` or `"right"` and a `data` which is either a hex string or
+    /// a serialized `Buffer`, as `JSON.stringify` writes them, with
+    /// `Error::HashLengthMismatch` if a hash is not as long as the output of `algorithm`,
+    /// and with `Error::InvalidPath` if it has more than `MAX_TREE_DEPTH` siblings.
+    /// Hex strings may have a `0x` prefix.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # extern crate serde_json;
+    /// # use merkle::{Proof, SHA256};
+    /// # fn main() {
+    /// let json = serde_json::json!([
+    ///     { "position": "left", "data": "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb" },
+    /// ]);
+    /// let root = "0xe5a01fee14e0ed5c48714f22180f25ad8365b53f9779f79dc4a3d7e93963f94a";
+    ///
+    /// let proof = Proof::from_mtjs_json(SHA256, "b", root, &json).unwrap();
+    /// assert!(proof.validate(&proof.root_hash));
+    /// # }
+    /// ```
+    pub fn from_mtjs_json(algorithm: &'static Algorithm, value: T, root: &str, json: &Value) -> Result<Self, Error> {
+        Proof::from_mtjs_json_with_scheme(algorithm, HashingScheme::Unprefixed, value, root, json)
+    }
+
+    /// Reads a proof as `Proof::from_mtjs_json` does, hashed with the given `scheme`, such as
+    /// `HashingScheme::SortedPairs` for a tree built by merkletreejs with `sortPairs: true`.
+    pub fn from_mtjs_json_with_scheme(
+        algorithm: &'static Algorithm,
+        scheme: HashingScheme,
+        value: T,
+        root: &str,
+        json: &Value,
+    ) -> Result<Self, Error> {
+
+        let entries = json.as_array().ok_or_else(|| decode_error("the proof is not an array"))?;
+
+        if entries.len() > MAX_TREE_DEPTH {
+            return Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"));
+        }
+
+        let root_hash = hash_of(algorithm, &Value::String(root.to_string()))?;
+
+        // merkletreejs lists the siblings from the leaf up, and lemmas run from the root down
+        let siblings = entries
+            .iter()
+            .rev()
+            .map(|entry| {
+                let position = entry.get("position").and_then(Value::as_str);
+                let data = entry.get("data").ok_or_else(|| decode_error("a sibling has no `data`"))?;
+                let hash = hash_of(algorithm, data)?;
+
+                match position {
+                    Some("left") => Ok(Positioned::Left(hash)),
+                    Some("right") => Ok(Positioned::Right(hash)),
+                    _ => Err(decode_error("the `position` of a sibling is neither `left` nor `right`")),
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let data = CanonicalProofData {
+            root_hash: root_hash,
+            leaf_hash: scheme.hash_leaf(algorithm, &value).as_ref().to_vec(),
+            siblings: siblings,
+            value: value,
+        };
+
+        Ok(data.into_proof_with_scheme(algorithm, scheme))
+    }
+}
+
+/// Reads a hash of the given `algorithm` written as hex, with or without a `0x` prefix,
+/// or as a serialized `Buffer`, ie. `{"type": "Buffer", "data": [<bytes>]}`.
+fn hash_of(algorithm: &'static Algorithm, data: &Value) -> Result<Vec<u8>, Error> {
+    let hash = match *data {
+        Value::String(ref hex) => {
+            let digits = if hex.starts_with("0x") || hex.starts_with("0X") { &hex[2..] } else { &hex[..] };
+            from_hex(digits).ok_or_else(|| decode_error("a hash is not hex"))?
+        }
+
+        Value::Object(ref object) if object.get("type").and_then(Value::as_str) == Some("Buffer") => {
+            object
+                .get("data")
+                .and_then(Value::as_array)
+                .and_then(|bytes| {
+                    bytes
+                        .iter()
+                        .map(|byte| byte.as_u64().filter(|&byte| byte <= 0xff).map(|byte| byte as u8))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| decode_error("a `Buffer` does not hold bytes"))?
+        }
+
+        _ => return Err(decode_error("a hash is neither a hex string nor a `Buffer`")),
+    };
+
+    if hash.len() != algorithm.output_len {
+        return Err(Error::HashLengthMismatch {
+            expected: algorithm.output_len,
+            found: hash.len(),
+        });
+    }
+
+    Ok(hash)
+}
+
+fn decode_error(reason: &str) -> Error {
+    Error::Decode(reason.to_string())
+}
@@ -0,0 +1,662 @@
+
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+
+use digest::Algorithm;
+
+use error::Error;
+use hash::{AlgorithmTag, RootHash};
+use hasher::{self, MerkleHasher};
+use options::HashingScheme;
+use tree::{self, Subtree, View};
+use wire::{self, Reader, MULTIPROOF_MAGIC, MULTIPROOF_VERSION};
+
+/// An inclusion proof of several leaves of a `MerkleTree` at once, see
+/// `MerkleTree::gen_multi_proof`.
+///
+/// It holds the part of the tree made of the paths from the root to the proven leaves,
+/// along with the hashes of the siblings of the nodes on these paths, so that the hashes
+/// shared by several paths are only included once.
+///
+/// When serialized, the `algorithm` is named as in `algorithm_name`, and the `hasher` is skipped,
+/// as is the `scheme` if it is the default one, as for a `Proof`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct MultiProof {
+    /// The hashing algorithm used in the original `MerkleTree`
+    #[cfg_attr(
+        feature = "serialization-serde",
+        serde(
+            serialize_with = "::registry::serialize_algorithm",
+            deserialize_with = "::registry::deserialize_algorithm"
+        )
+    )]
+    pub algorithm: &'static Algorithm,
+
+    /// The hasher of the original `MerkleTree`, if it has one of its own, see `Proof::hasher`
+    #[cfg_attr(feature = "serialization-serde", serde(skip))]
+    pub hasher: Option<Arc<dyn MerkleHasher>>,
+
+    /// The way the nodes of the original `MerkleTree` are hashed
+    #[cfg_attr(
+        feature = "serialization-serde",
+        serde(default, skip_serializing_if = "HashingScheme::is_default")
+    )]
+    pub scheme: HashingScheme,
+
+    /// The number of leaves of the original `MerkleTree`, which gives the indices of the leaves
+    pub count: usize,
+
+    /// The root of the part of the original `MerkleTree` included in the proof
+    pub root: MultiProofNode,
+}
+
+/// A node of the part of a tree included in a `MultiProof`.
+///
+/// Deserializing nodes nested more than `MAX_TREE_DEPTH` levels below the root fails.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub enum MultiProofNode {
+    /// A node whose subtree has no proven leaf, only included for its hash
+    Pruned {
+        /// The hash of the node
+        #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+        hash: Vec<u8>,
+    },
+
+    /// A copy of its left sibling, added by `OddLeafPolicy::Duplicate`
+    Empty {
+        /// The hash of the node, which is that of its left sibling
+        #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+        hash: Vec<u8>,
+    },
+
+    /// A proven leaf
+    Leaf {
+        /// The hash of the leaf
+        #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+        hash: Vec<u8>,
+    },
+
+    /// A node on the path to at least one proven leaf
+    Node {
+        /// The hash of the node
+        #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+        hash: Vec<u8>,
+
+        /// The left child of the node
+        #[cfg_attr(feature = "serialization-serde", serde(deserialize_with = "::lemma_depth::node"))]
+        left: Box<MultiProofNode>,
+
+        /// The right child of the node
+        #[cfg_attr(feature = "serialization-serde", serde(deserialize_with = "::lemma_depth::node"))]
+        right: Box<MultiProofNode>,
+    },
+}
+
+/// The detailed result of checking a `MultiProof`, see `MultiProof::verify_detailed`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiProofReport {
+    /// Whether each proven leaf, in the order of `MultiProof::indices`, is verified,
+    /// ie. whether the hash of every node on its path is that of its children,
+    /// up to the expected root hash
+    pub verified: Vec<bool>,
+
+    /// The root hash computed from the hashes of the proven leaves and of the pruned nodes,
+    /// ignoring the hashes of the nodes on the paths to the leaves
+    pub computed_root: Vec<u8>,
+
+    /// Whether the root hash held by the proof is the expected one
+    pub root_matches: bool,
+
+    /// The nodes whose hash is not that of their children, at the given depth below the root
+    /// and path from the root, as in `Error::HashMismatch`
+    pub mismatched_nodes: Vec<(usize, usize)>,
+}
+
+impl PartialEq for MultiProof {
+    fn eq(&self, other: &MultiProof) -> bool {
+        self.scheme == other.scheme && self.count == other.count && self.root == other.root
+    }
+}
+
+impl Eq for MultiProof {}
+
+// The hasher is only ever called through shared references, and a proof keeps none of its state.
+impl UnwindSafe for MultiProof {}
+impl RefUnwindSafe for MultiProof {}
+
+impl MultiProofNode {
+    /// Returns the hash of the node.
+    pub fn hash(&self) -> &Vec<u8> {
+        match *self {
+            MultiProofNode::Pruned { ref hash } => hash,
+            MultiProofNode::Empty { ref hash } => hash,
+            MultiProofNode::Leaf { ref hash } => hash,
+            MultiProofNode::Node { ref hash, .. } => hash,
+        }
+    }
+}
+
+#[cfg(any(feature = "serialization-protobuf", feature = "serialization-prost"))]
+impl MultiProofNode {
+    /// Returns the kind of the node in Protobuf messages, see `protos/merkle.proto`.
+    pub(crate) fn proto_kind(&self) -> u32 {
+        match *self {
+            MultiProofNode::Pruned { .. } => 0,
+            MultiProofNode::Empty { .. } => 1,
+            MultiProofNode::Leaf { .. } => 2,
+            MultiProofNode::Node { .. } => 3,
+        }
+    }
+
+    /// Rebuilds a node of the given kind from its hash and children, decoded
+    /// from a Protobuf message.
+    pub(crate) fn from_proto(
+        kind: u32,
+        hash: Vec<u8>,
+        children: Option<(MultiProofNode, MultiProofNode)>,
+    ) -> Result<Self, Error> {
+
+        match (kind, children) {
+            (0, None) => Ok(MultiProofNode::Pruned { hash: hash }),
+            (1, None) => Ok(MultiProofNode::Empty { hash: hash }),
+            (2, None) => Ok(MultiProofNode::Leaf { hash: hash }),
+            (3, Some((left, right))) => Ok(MultiProofNode::Node {
+                hash: hash,
+                left: Box::new(left),
+                right: Box::new(right),
+            }),
+            (3, None) => Err(Error::MissingField("left")),
+            (0..=2, Some(_)) => Err(Error::Decode("only the nodes on the paths to the leaves have children".to_string())),
+            (kind, _) => Err(Error::Decode(format!("unknown multiproof node kind {}", kind))),
+        }
+    }
+}
+
+impl MultiProofReport {
+    /// Returns whether the whole proof is valid: every leaf is verified,
+    /// and the computed root is the expected one.
+    pub fn is_valid(&self) -> bool {
+        self.root_matches && self.mismatched_nodes.is_empty() && self.verified.iter().all(|&v| v)
+    }
+}
+
+impl MultiProof {
+    /// Generates the proof of the leaves at the given `indices`, which must be sorted and distinct,
+    /// of the given `tree`, which has `count` leaves.
+    pub(crate) fn new<T>(
+        algorithm: &'static Algorithm,
+        scheme: HashingScheme,
+        tree: Subtree<T>,
+        count: usize,
+        indices: &[usize],
+    ) -> Self {
+
+        MultiProof {
+            algorithm: algorithm,
+            hasher: None,
+            scheme: scheme,
+            count: count,
+            root: prune(tree, 0, count, indices),
+        }
+    }
+
+    /// Returns the hasher the node hashes of this proof are recomputed with: the `hasher`
+    /// of the original tree, or its bare `algorithm` if it has none.
+    pub fn hasher(&self) -> &dyn MerkleHasher {
+        hasher::or_algorithm(&self.hasher, &self.algorithm)
+    }
+
+    /// Returns the indices of the proven leaves, in increasing order.
+    pub fn indices(&self) -> Vec<usize> {
+        let mut indices = Vec::new();
+        let mut stack = vec![(&self.root, 0, self.count)];
+
+        while let Some((node, offset, count)) = stack.pop() {
+            match *node {
+                MultiProofNode::Pruned { .. } | MultiProofNode::Empty { .. } => {}
+
+                MultiProofNode::Leaf { .. } => indices.push(offset),
+
+                MultiProofNode::Node {
+                    ref left,
+                    ref right,
+                    ..
+                } => {
+                    let left_count = match **right {
+                        MultiProofNode::Empty { .. } => count,
+                        _ => count.next_power_of_two() / 2,
+                    };
+
+                    stack.push((right, offset + left_count, count - left_count));
+                    stack.push((left, offset, left_count));
+                }
+            }
+        }
+
+        indices
+    }
+
+    /// Checks whether this proof is valid for a tree with the given root hash.
+    pub fn validate(&self, root_hash: &[u8]) -> bool {
+        self.verify_detailed(root_hash).is_valid()
+    }
+
+    /// Checks whether this proof is valid for a tree with the given root hash, like
+    /// `MultiProof::validate`, failing with `Error::AlgorithmMismatch` if `root_hash` was
+    /// computed with another algorithm than this proof, and with `Error::InvalidProof`
+    /// if the proof does not hold.
+    pub fn verify(&self, root_hash: &RootHash) -> Result<(), Error> {
+        if root_hash.algorithm() != AlgorithmTag::of(self.algorithm) {
+            return Err(Error::AlgorithmMismatch);
+        }
+
+        if !self.validate(root_hash.as_bytes()) {
+            return Err(Error::InvalidProof);
+        }
+
+        Ok(())
+    }
+
+    /// Checks this proof against the given root hash, telling which leaves are verified,
+    /// so that only the other ones need to be proven again.
+    ///
+    /// A leaf whose hash was altered makes its parent mismatch, along with every leaf
+    /// under that parent, but not the leaves on other paths.
+    pub fn verify_detailed(&self, root_hash: &[u8]) -> MultiProofReport {
+        let root_matches = self.root.hash().as_slice() == root_hash;
+
+        let mut report = MultiProofReport {
+            verified: Vec::new(),
+            computed_root: Vec::new(),
+            root_matches: root_matches,
+            mismatched_nodes: Vec::new(),
+        };
+
+        report.computed_root = self.check(&self.root, 0, 0, root_matches, &mut report);
+        report.root_matches = root_matches && report.computed_root.as_slice() == root_hash;
+        report
+    }
+
+    /// Checks the given node, which lies `depth` levels below the root at the given `path`,
+    /// and returns its hash computed from the proven leaves and the pruned nodes.
+    fn check(
+        &self,
+        node: &MultiProofNode,
+        depth: usize,
+        path: usize,
+        verified: bool,
+        report: &mut MultiProofReport,
+    ) -> Vec<u8> {
+
+        match *node {
+            MultiProofNode::Pruned { ref hash } | MultiProofNode::Empty { ref hash } => hash.clone(),
+
+            MultiProofNode::Leaf { ref hash } => {
+                report.verified.push(verified);
+                hash.clone()
+            }
+
+            MultiProofNode::Node {
+                ref hash,
+                ref left,
+                ref right,
+            } => {
+                let expected = self.hash_nodes(left.hash(), right.hash());
+                let consistent = expected == *hash;
+
+                if !consistent {
+                    report.mismatched_nodes.push((depth, path));
+                }
+
+                let verified = verified && consistent;
+                let left = self.check(left, depth + 1, path << 1, verified, report);
+                let right = self.check(right, depth + 1, path << 1 | 1, verified, report);
+
+                self.hash_nodes(&left, &right)
+            }
+        }
+    }
+
+    fn hash_nodes(&self, left: &[u8], right: &[u8]) -> Vec<u8> {
+        self.scheme.hash_nodes_with(self.hasher(), &left, &right).as_ref().into()
+    }
+
+    /// Encodes this proof in a compact binary format, where the hashes of the nodes
+    /// on the paths to the leaves are left out, to be recomputed when decoding.
+    ///
+    /// Varints are unsigned LEB128. A proof is laid out as follows:
+    ///
+    /// | Size         | Contents                                                          |
+    /// |--------------|-------------------------------------------------------------------|
+    /// | 4            | the magic bytes `MULTIPROOF_MAGIC`, ie. `"MKLM"`                  |
+    /// | 1            | the format version, currently `MULTIPROOF_VERSION`                |
+    /// | 1            | the algorithm tag, see `wire::algorithm_tag`                      |
+    /// | 1            | the hash length `h`, ie. the algorithm output length              |
+    /// | 1            | the hashing scheme: `0` if domain separated, `1` if unprefixed, `2` if sorted pairs |
+    /// | 1            | `1` if the odd nodes are duplicated, `0` if they are promoted     |
+    /// | varint       | the number of leaves of the tree                                  |
+    /// | varint       | the number `k` of proven leaves                                   |
+    /// | `k` varints  | the index of the first proven leaf, then the difference between each index and the previous one |
+    /// | varint       | the number `b` of bits of the bitfield                            |
+    /// | `(b + 7) / 8`| the bitfield, from the least significant bit of each byte, telling for each node from the root down, left before right, whether it is computed (`1`) or supplied (`0`), the copies of `OddLeafPolicy::Duplicate` being left out |
+    /// | `n * h`      | the hashes of the supplied nodes and of the proven leaves, in the same order |
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let indices = self.indices();
+        let mut encoder = Encoder::default();
+
+        if self.count > 0 {
+            encoder.node(&self.root);
+        }
+
+        let hash_len = self.algorithm.output_len;
+        let mut bytes = Vec::with_capacity(16 + indices.len() * 2 + encoder.hashes.len());
+
+        bytes.extend_from_slice(MULTIPROOF_MAGIC);
+        bytes.push(MULTIPROOF_VERSION);
+        bytes.push(wire::algorithm_tag(self.algorithm));
+        bytes.push(hash_len as u8);
+        bytes.push(wire::scheme_tag(self.scheme));
+        bytes.push(encoder.duplicate as u8);
+
+        wire::write_varint(&mut bytes, self.count as u64);
+        wire::write_varint(&mut bytes, indices.len() as u64);
+
+        let mut previous = 0;
+
+        for index in indices {
+            wire::write_varint(&mut bytes, (index - previous) as u64);
+            previous = index;
+        }
+
+        wire::write_varint(&mut bytes, encoder.bits.len() as u64);
+
+        for chunk in encoder.bits.chunks(8) {
+            bytes.push(chunk.iter().enumerate().fold(0, |byte, (i, &bit)| {
+                byte | (bit as u8) << i
+            }));
+        }
+
+        bytes.extend_from_slice(&encoder.hashes);
+        bytes
+    }
+
+    /// Decodes a proof encoded with `to_compact_bytes`, which must have been produced
+    /// with the given hashing `algorithm`.
+    pub fn from_compact_bytes(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error> {
+        MultiProof::decode_compact(algorithm, None, bytes)
+    }
+
+    /// Works like `MultiProof::from_compact_bytes`, for the proof of a tree hashed with
+    /// the given `hasher`, see `MerkleTreeBuilder::hasher`, which the hashes of the nodes
+    /// on the paths to the leaves are recomputed with, and which the proof then holds.
+    pub fn from_compact_bytes_with<H>(hasher: H, bytes: &[u8]) -> Result<Self, Error>
+    where
+        H: MerkleHasher + 'static,
+    {
+
+        MultiProof::decode_compact(hasher.algorithm(), Some(Arc::new(hasher)), bytes)
+    }
+
+    fn decode_compact(
+        algorithm: &'static Algorithm,
+        hasher: Option<Arc<dyn MerkleHasher>>,
+        bytes: &[u8],
+    ) -> Result<Self, Error> {
+
+        let mut reader = Reader { bytes: bytes };
+
+        if reader.take(MULTIPROOF_MAGIC.len())? != MULTIPROOF_MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+
+        let version = reader.byte()?;
+
+        if version > MULTIPROOF_VERSION {
+            return Err(Error::UnsupportedVersion {
+                found: u32::from(version),
+                max_supported: u32::from(MULTIPROOF_VERSION),
+            });
+        }
+
+        let tag = reader.byte()?;
+
+        match wire::algorithm_from_tag(tag) {
+            None => return Err(Error::UnknownAlgorithm(tag)),
+            Some(algo) if wire::algorithm_tag(algo) != wire::algorithm_tag(algorithm) => {
+                return Err(Error::AlgorithmMismatch);
+            }
+            Some(_) => {}
+        }
+
+        let hash_len = reader.byte()? as usize;
+
+        if hash_len != algorithm.output_len {
+            return Err(Error::HashLengthMismatch {
+                expected: algorithm.output_len,
+                found: hash_len,
+            });
+        }
+
+        let scheme_tag = reader.byte()?;
+        let scheme = wire::scheme_from_tag(scheme_tag).ok_or_else(|| {
+            Error::Decode(format!("unknown hashing scheme {}", scheme_tag))
+        })?;
+
+        let duplicate = match reader.byte()? {
+            0 => false,
+            1 => true,
+            byte => return Err(Error::Decode(format!("unknown odd leaf policy {}", byte))),
+        };
+
+        let count = varint_usize(&mut reader)?;
+        let len = varint_usize(&mut reader)?;
+        let mut indices = Vec::with_capacity(len.min(reader.bytes.len()));
+        let mut previous = 0usize;
+
+        for i in 0..len {
+            let delta = varint_usize(&mut reader)?;
+
+            if i > 0 && delta == 0 {
+                return Err(Error::Decode("leaf indices are not increasing".to_string()));
+            }
+
+            previous = previous.checked_add(delta).ok_or_else(|| {
+                Error::Decode("leaf index overflows".to_string())
+            })?;
+
+            indices.push(previous);
+        }
+
+        let bit_count = varint_usize(&mut reader)?;
+        let bitfield = reader.take(bit_count / 8 + (bit_count % 8 != 0) as usize)?;
+
+        if bit_count % 8 != 0 && bitfield[bitfield.len() - 1] >> (bit_count % 8) != 0 {
+            return Err(Error::Decode("bitfield padding is not zero".to_string()));
+        }
+
+        let mut decoder = Decoder {
+            hasher: hasher::or_algorithm(&hasher, &algorithm),
+            scheme: scheme,
+            duplicate: duplicate,
+            bitfield: bitfield,
+            bit_count: bit_count,
+            next_bit: 0,
+            hash_len: hash_len,
+            reader: reader,
+        };
+
+        let root = if count == 0 {
+            MultiProofNode::Empty { hash: decoder.hasher.hash_empty().as_ref().into() }
+        } else {
+            decoder.node(count, tree::ceil_log2(count))?
+        };
+
+        if decoder.next_bit != bit_count {
+            return Err(Error::Decode(
+                "bitfield has more bits than the proof has nodes".to_string(),
+            ));
+        }
+
+        if !decoder.reader.bytes.is_empty() {
+            return Err(Error::TrailingBytes);
+        }
+
+        let proof = MultiProof {
+            algorithm: algorithm,
+            hasher: hasher,
+            scheme: scheme,
+            count: count,
+            root: root,
+        };
+
+        if proof.indices() != indices {
+            return Err(Error::Decode(
+                "leaf indices do not match the bitfield".to_string(),
+            ));
+        }
+
+        Ok(proof)
+    }
+}
+
+/// Collects the bitfield and the hashes of a compact multiproof.
+#[derive(Default)]
+struct Encoder {
+    bits: Vec<bool>,
+    hashes: Vec<u8>,
+    duplicate: bool,
+}
+
+impl Encoder {
+    fn node(&mut self, node: &MultiProofNode) {
+        match *node {
+            MultiProofNode::Pruned { ref hash } => {
+                self.bits.push(false);
+                self.hashes.extend_from_slice(hash);
+            }
+
+            MultiProofNode::Empty { .. } => self.duplicate = true,
+
+            MultiProofNode::Leaf { ref hash } => {
+                self.bits.push(true);
+                self.hashes.extend_from_slice(hash);
+            }
+
+            MultiProofNode::Node {
+                ref left,
+                ref right,
+                ..
+            } => {
+                self.bits.push(true);
+                self.node(left);
+                self.node(right);
+            }
+        }
+    }
+}
+
+/// Rebuilds the nodes of a compact multiproof from its bitfield and hashes.
+struct Decoder<'a> {
+    hasher: &'a dyn MerkleHasher,
+    scheme: HashingScheme,
+    duplicate: bool,
+    bitfield: &'a [u8],
+    bit_count: usize,
+    next_bit: usize,
+    hash_len: usize,
+    reader: Reader<'a>,
+}
+
+impl<'a> Decoder<'a> {
+    /// Decodes the node over `count` leaves, which is at the given `height` above the leaves
+    /// if the odd nodes are duplicated.
+    fn node(&mut self, count: usize, height: usize) -> Result<MultiProofNode, Error> {
+        if !self.bit()? {
+            return Ok(MultiProofNode::Pruned { hash: self.hash()? });
+        }
+
+        let is_leaf = if self.duplicate { height == 0 } else { count == 1 };
+
+        if is_leaf {
+            return Ok(MultiProofNode::Leaf { hash: self.hash()? });
+        }
+
+        let left_count = if self.duplicate {
+            count.min(1 << (height - 1))
+        } else {
+            count.next_power_of_two() / 2
+        };
+
+        let left = self.node(left_count, height.saturating_sub(1))?;
+
+        let right = if left_count == count {
+            MultiProofNode::Empty { hash: left.hash().to_vec() }
+        } else {
+            self.node(count - left_count, height.saturating_sub(1))?
+        };
+
+        let hash = self.scheme.hash_nodes_with(self.hasher, left.hash(), right.hash());
+
+        Ok(MultiProofNode::Node {
+            hash: hash.as_ref().into(),
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+    }
+
+    fn bit(&mut self) -> Result<bool, Error> {
+        if self.next_bit == self.bit_count {
+            return Err(Error::Decode(
+                "bitfield has fewer bits than the proof has nodes".to_string(),
+            ));
+        }
+
+        let bit = self.bitfield[self.next_bit / 8] >> (self.next_bit % 8) & 1 == 1;
+        self.next_bit += 1;
+
+        Ok(bit)
+    }
+
+    fn hash(&mut self) -> Result<Vec<u8>, Error> {
+        self.reader.take(self.hash_len).map(|hash| hash.to_vec())
+    }
+}
+
+fn varint_usize(reader: &mut Reader) -> Result<usize, Error> {
+    let n = reader.varint()?;
+
+    if n > usize::MAX as u64 {
+        return Err(Error::Decode("varint overflows usize".to_string()));
+    }
+
+    Ok(n as usize)
+}
+
+/// Keeps the part of `tree` on the paths to the leaves at the given `indices`,
+/// `tree` having `count` leaves from the `offset`-th one on.
+fn prune<T>(tree: Subtree<T>, offset: usize, count: usize, indices: &[usize]) -> MultiProofNode {
+    match tree.view() {
+        View::Empty { hash } => MultiProofNode::Empty { hash: hash.to_vec() },
+
+        _ if indices.is_empty() => MultiProofNode::Pruned { hash: tree.hash().to_vec() },
+
+        View::Leaf { hash, .. } => MultiProofNode::Leaf { hash: hash.to_vec() },
+
+        View::Pruned { .. } => unreachable!("the proven leaves are not pruned"),
+
+        View::Node { hash, left, right } => {
+            let left_count = tree::left_count(right, count);
+            let split = indices.iter().position(|&i| i >= offset + left_count).unwrap_or(
+                indices.len(),
+            );
+
+            MultiProofNode::Node {
+                hash: hash.to_vec(),
+                left: Box::new(prune(left, offset, left_count, &indices[..split])),
+                right: Box::new(prune(right, offset + left_count, count - left_count, &indices[split..])),
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::iter::FusedIterator;
+
+use tree::{Tree, Subtree, View};
+
+/// A node of a `MerkleTree`, as yielded by `MerkleTree::iter_nodes_preorder` and
+/// `MerkleTree::iter_nodes_postorder`, the root being at depth `0`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NodeRef<'a, T: 'a> {
+    /// An internal node
+    Internal {
+        /// The depth of the node
+        depth: usize,
+
+        /// The hash of the node
+        hash: &'a [u8],
+    },
+
+    /// A leaf
+    Leaf {
+        /// The depth of the leaf
+        depth: usize,
+
+        /// The index of the leaf in the tree
+        index: usize,
+
+        /// The hash of the leaf
+        hash: &'a [u8],
+
+        /// The value of the leaf
+        value: &'a T,
+    },
+
+    /// A subtree pruned by `MerkleTree::prune_below`
+    Pruned {
+        /// The depth of the root of the subtree
+        depth: usize,
+
+        /// The hash of the root of the subtree
+        hash: &'a [u8],
+
+        /// The number of leaves of the subtree
+        leaves: usize,
+    },
+}
+
+impl<'a, T> Clone for NodeRef<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for NodeRef<'a, T> {}
+
+impl<'a, T> NodeRef<'a, T> {
+    /// Returns the depth of the node.
+    pub fn depth(&self) -> usize {
+        match *self {
+            NodeRef::Internal { depth, .. } | NodeRef::Leaf { depth, .. } | NodeRef::Pruned { depth, .. } => depth,
+        }
+    }
+
+    /// Returns the hash of the node.
+    pub fn hash(&self) -> &'a [u8] {
+        match *self {
+            NodeRef::Internal { hash, .. } | NodeRef::Leaf { hash, .. } | NodeRef::Pruned { hash, .. } => hash,
+        }
+    }
+}
+
+/// An iterator over the nodes of a `MerkleTree`, each before its children, the left one first,
+/// as returned by `MerkleTree::iter_nodes_preorder`.
+///
+/// `Empty` nodes, which pad trees of a fixed depth and stand for the copies of odd nodes,
+/// are skipped.
+#[allow(missing_debug_implementations)]
+pub struct PreOrderNodes<'a, T: 'a> {
+    stack: Vec<(Subtree<'a, T>, usize)>,
+    index: usize,
+}
+
+impl<'a, T> PreOrderNodes<'a, T> {
+    pub(crate) fn new(tree: &'a Tree<T>) -> Self {
+        PreOrderNodes {
+            stack: vec![(tree.root(), 0)],
+            index: 0,
+        }
+    }
+}
+
+impl<'a, T> Iterator for PreOrderNodes<'a, T> {
+    type Item = NodeRef<'a, T>;
+
+    fn next(&mut self) -> Option<NodeRef<'a, T>> {
+        while let Some((node, depth)) = self.stack.pop() {
+            match node.view() {
+                View::Empty { .. } => {}
+
+                View::Pruned { hash, leaves } => {
+                    self.index += leaves;
+
+                    return Some(NodeRef::Pruned {
+                        depth: depth,
+                        hash: hash,
+                        leaves: leaves,
+                    });
+                }
+
+                View::Leaf { hash, value } => {
+                    self.index += 1;
+
+                    return Some(NodeRef::Leaf {
+                        depth: depth,
+                        index: self.index - 1,
+                        hash: hash,
+                        value: value,
+                    });
+                }
+
+                View::Node { hash, left, right } => {
+                    self.stack.push((right, depth + 1));
+                    self.stack.push((left, depth + 1));
+
+                    return Some(NodeRef::Internal {
+                        depth: depth,
+                        hash: hash,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a, T> FusedIterator for PreOrderNodes<'a, T> {}
+
+/// An iterator over the nodes of a `MerkleTree`, each after its children, the left one first,
+/// as returned by `MerkleTree::iter_nodes_postorder`.
+///
+/// `Empty` nodes, which pad trees of a fixed depth and stand for the copies of odd nodes,
+/// are skipped.
+#[allow(missing_debug_implementations)]
+pub struct PostOrderNodes<'a, T: 'a> {
+    /// The nodes to come, along with their depth and whether their children have been pushed
+    stack: Vec<(Subtree<'a, T>, usize, bool)>,
+    index: usize,
+}
+
+impl<'a, T> PostOrderNodes<'a, T> {
+    pub(crate) fn new(tree: &'a Tree<T>) -> Self {
+        PostOrderNodes {
+            stack: vec![(tree.root(), 0, false)],
+            index: 0,
+        }
+    }
+}
+
+impl<'a, T> Iterator for PostOrderNodes<'a, T> {
+    type Item = NodeRef<'a, T>;
+
+    fn next(&mut self) -> Option<NodeRef<'a, T>> {
+        while let Some((node, depth, expanded)) = self.stack.pop() {
+            match node.view() {
+                View::Empty { .. } => {}
+
+                View::Pruned { hash, leaves } => {
+                    self.index += leaves;
+
+                    return Some(NodeRef::Pruned {
+                        depth: depth,
+                        hash: hash,
+                        leaves: leaves,
+                    });
+                }
+
+                View::Leaf { hash, value } => {
+                    self.index += 1;
+
+                    return Some(NodeRef::Leaf {
+                        depth: depth,
+                        index: self.index - 1,
+                        hash: hash,
+                        value: value,
+                    });
+                }
+
+                View::Node { hash, .. } if expanded => {
+                    return Some(NodeRef::Internal {
+                        depth: depth,
+                        hash: hash,
+                    });
+                }
+
+                View::Node { left, right, .. } => {
+                    self.stack.push((node, depth, true));
+                    self.stack.push((right, depth + 1, false));
+                    self.stack.push((left, depth + 1, false));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a, T> FusedIterator for PostOrderNodes<'a, T> {}
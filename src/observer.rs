@@ -0,0 +1,235 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use proof::Proof;
+
+/// Gets notified of the work done while building a `MerkleTree` and generating or validating its
+/// proofs, to find out where the time goes, see `MerkleTreeBuilder::observer`.
+///
+/// The methods are called on the thread doing the work, in the middle of it, and should return
+/// quickly, and must not panic, as a tree whose observer panicked while it was being
+/// modified is to be dropped. They all do nothing by default.
+///
+/// Only available with the `metrics` feature. Trees without an observer do not look at the clock.
+pub trait TreeObserver: Send + Sync + fmt::Debug {
+    /// Called once the value at the given `index` of the values being added to the tree
+    /// is hashed into a leaf.
+    fn on_leaf_hashed(&self, index: usize) {
+        let _ = index;
+    }
+
+    /// Called once the `nodes` nodes of the given `level` are built, the leaves being level `0`.
+    fn on_level_built(&self, level: usize, nodes: usize) {
+        let _ = (level, nodes);
+    }
+
+    /// Called once a proof whose lemma chain is `depth` levels deep is generated, which took
+    /// `duration`. The methods which generate several proofs call it once per proof.
+    fn on_proof_generated(&self, depth: usize, duration: Duration) {
+        let _ = (depth, duration);
+    }
+
+    /// Called once a proof is validated, with the outcome, which took `duration`.
+    fn on_validation(&self, valid: bool, duration: Duration) {
+        let _ = (valid, duration);
+    }
+}
+
+/// Counts and timings aggregated by a `MetricsObserver`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// The number of values hashed into leaves
+    pub leaves_hashed: u64,
+
+    /// The number of internal levels built
+    pub levels_built: u64,
+
+    /// The number of nodes of the internal levels built, a promoted node counting once
+    /// per level it lies on
+    pub nodes_built: u64,
+
+    /// The number of proofs generated
+    pub proofs_generated: u64,
+
+    /// The total time spent generating proofs
+    pub proof_time: Duration,
+
+    /// The number of proofs validated
+    pub validations: u64,
+
+    /// The number of proofs which did not validate
+    pub failed_validations: u64,
+
+    /// The total time spent validating proofs
+    pub validation_time: Duration,
+}
+
+/// A `TreeObserver` aggregating what it is notified of into `Metrics`, which can be installed
+/// on several trees at once, from several threads.
+#[derive(Debug, Default)]
+pub struct MetricsObserver {
+    leaves_hashed: AtomicU64,
+    levels_built: AtomicU64,
+    nodes_built: AtomicU64,
+    proofs_generated: AtomicU64,
+    proof_nanos: AtomicU64,
+    validations: AtomicU64,
+    failed_validations: AtomicU64,
+    validation_nanos: AtomicU64,
+}
+
+impl MetricsObserver {
+    /// Creates an observer which has not been notified of anything yet.
+    pub fn new() -> Self {
+        MetricsObserver::default()
+    }
+
+    /// Returns what the observer was notified of so far.
+    pub fn metrics(&self) -> Metrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        Metrics {
+            leaves_hashed: load(&self.leaves_hashed),
+            levels_built: load(&self.levels_built),
+            nodes_built: load(&self.nodes_built),
+            proofs_generated: load(&self.proofs_generated),
+            proof_time: Duration::from_nanos(load(&self.proof_nanos)),
+            validations: load(&self.validations),
+            failed_validations: load(&self.failed_validations),
+            validation_time: Duration::from_nanos(load(&self.validation_nanos)),
+        }
+    }
+
+    /// Returns what the observer was notified of so far, and starts over from zero.
+    pub fn take_metrics(&self) -> Metrics {
+        let take = |counter: &AtomicU64| counter.swap(0, Ordering::Relaxed);
+
+        Metrics {
+            leaves_hashed: take(&self.leaves_hashed),
+            levels_built: take(&self.levels_built),
+            nodes_built: take(&self.nodes_built),
+            proofs_generated: take(&self.proofs_generated),
+            proof_time: Duration::from_nanos(take(&self.proof_nanos)),
+            validations: take(&self.validations),
+            failed_validations: take(&self.failed_validations),
+            validation_time: Duration::from_nanos(take(&self.validation_nanos)),
+        }
+    }
+}
+
+impl TreeObserver for MetricsObserver {
+    fn on_leaf_hashed(&self, _index: usize) {
+        self.leaves_hashed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_level_built(&self, level: usize, nodes: usize) {
+        if level > 0 {
+            self.levels_built.fetch_add(1, Ordering::Relaxed);
+            self.nodes_built.fetch_add(nodes as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn on_proof_generated(&self, _depth: usize, duration: Duration) {
+        self.proofs_generated.fetch_add(1, Ordering::Relaxed);
+        self.proof_nanos.fetch_add(nanos(duration), Ordering::Relaxed);
+    }
+
+    fn on_validation(&self, valid: bool, duration: Duration) {
+        self.validations.fetch_add(1, Ordering::Relaxed);
+        self.validation_nanos.fetch_add(nanos(duration), Ordering::Relaxed);
+
+        if !valid {
+            self.failed_validations.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Saturates durations of over five centuries.
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().min(u64::MAX as u128) as u64
+}
+
+/// A `TreeObserver` forwarding what it is notified of to the `log` crate, under the `merkle`
+/// target: the leaves at the trace level, and the rest at the debug level.
+///
+/// Only available with the `metrics-log` feature.
+#[cfg(feature = "metrics-log")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogObserver;
+
+#[cfg(feature = "metrics-log")]
+impl TreeObserver for LogObserver {
+    fn on_leaf_hashed(&self, index: usize) {
+        ::log::trace!(target: "merkle", "hashed leaf {}", index);
+    }
+
+    fn on_level_built(&self, level: usize, nodes: usize) {
+        ::log::debug!(target: "merkle", "built level {} of {} nodes", level, nodes);
+    }
+
+    fn on_proof_generated(&self, depth: usize, duration: Duration) {
+        ::log::debug!(target: "merkle", "generated a proof of depth {} in {:?}", depth, duration);
+    }
+
+    fn on_validation(&self, valid: bool, duration: Duration) {
+        ::log::debug!(target: "merkle", "validated a proof in {:?}: {}", duration, if valid { "valid" } else { "invalid" });
+    }
+}
+
+/// A `TreeObserver` forwarding what it is notified of to the `tracing` crate as events,
+/// under the `merkle` target: the leaves at the trace level, and the rest at the debug level.
+///
+/// Only available with the `metrics-tracing` feature.
+#[cfg(feature = "metrics-tracing")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingObserver;
+
+#[cfg(feature = "metrics-tracing")]
+impl TreeObserver for TracingObserver {
+    fn on_leaf_hashed(&self, index: usize) {
+        ::tracing::trace!(target: "merkle", index = index, "hashed leaf");
+    }
+
+    fn on_level_built(&self, level: usize, nodes: usize) {
+        ::tracing::debug!(target: "merkle", level = level, nodes = nodes, "built level");
+    }
+
+    fn on_proof_generated(&self, depth: usize, duration: Duration) {
+        ::tracing::debug!(target: "merkle", depth = depth, ?duration, "generated proof");
+    }
+
+    fn on_validation(&self, valid: bool, duration: Duration) {
+        ::tracing::debug!(target: "merkle", valid = valid, ?duration, "validated proof");
+    }
+}
+
+impl<T> Proof<T> {
+    /// Works like `Proof::validate`, but reports how long it took and its outcome to the given
+    /// observer, for proofs which are validated away from their tree.
+    pub fn validate_observed(&self, root_hash: &[u8], observer: &dyn TreeObserver) -> bool {
+        timed(Some(observer), || self.validate(root_hash), |observer, &valid, duration| {
+            observer.on_validation(valid, duration);
+        })
+    }
+}
+
+/// Runs `f`, then reports how long it took along with its result to `report`, if there is
+/// an observer, see `TreeOptions::observer`.
+pub(crate) fn timed<R, F, G>(observer: Option<&dyn TreeObserver>, f: F, report: G) -> R
+where
+    F: FnOnce() -> R,
+    G: FnOnce(&dyn TreeObserver, &R, Duration),
+{
+
+    let observer = match observer {
+        Some(observer) => observer,
+        None => return f(),
+    };
+
+    let start = Instant::now();
+    let result = f();
+
+    report(observer, &result, start.elapsed());
+    result
+}
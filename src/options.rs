@@ -0,0 +1,611 @@
+
+use std::cmp;
+use std::iter;
+use std::ops::Range;
+use std::panic::{self, RefUnwindSafe, UnwindSafe};
+
+use std::sync::Arc;
+use std::thread;
+
+use digest::{Algorithm, Context, Digest};
+
+#[cfg(feature = "metrics")]
+use observer::{self, TreeObserver};
+
+use defaults::default_hashes_with;
+use error::{Error, HashError};
+use hasher::{self, MerkleHasher};
+use hashutils::{Hashable, HashUtils, TryHashable};
+use merkletree::DedupOrder;
+use proof::Proof;
+use ordering::LeafOrdering;
+use small_hash::SmallHash;
+use tree::Leaf;
+
+/// The way leaves and internal nodes are hashed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub enum HashingScheme {
+    /// Leaves are hashed as `H(0x00 || value)` and internal nodes as `H(0x01 || left || right)`,
+    /// as in RFC 6962, so that a leaf can never be mistaken for an internal node.
+    /// This is the default.
+    #[default]
+    DomainSeparated,
+
+    /// Leaves are hashed as `H(value)` and internal nodes as `H(left || right)`.
+    ///
+    /// This is only meant for interoperability with other implementations: without domain
+    /// separation, the concatenation of two hashes can be passed off as a leaf value.
+    Unprefixed,
+
+    /// Leaves are hashed as `H(value)` and internal nodes as `H(min(a, b) || max(a, b))`,
+    /// the children hashes `a` and `b` being sorted bytewise, as in the `MerkleProof` library
+    /// of OpenZeppelin, so that proofs need not tell on which side each sibling lies,
+    /// see `Proof::sorted_path` and `validate_sorted_path`.
+    ///
+    /// Beware that, on top of the lack of domain separation of `Unprefixed`, swapping the
+    /// children of any internal node leaves the root unchanged, so that a root does not commit
+    /// to the order of the leaves, and a proof does not tell the index of its leaf.
+    SortedPairs,
+}
+
+impl HashingScheme {
+    /// Returns whether this is the default scheme, which serialized proofs only name
+    /// if they are hashed with another one.
+    #[cfg(feature = "serialization-serde")]
+    pub(crate) fn is_default(&self) -> bool {
+        *self == HashingScheme::default()
+    }
+
+    /// Computes the hash of the given leaf.
+    pub fn hash_leaf<T>(&self, algorithm: &'static Algorithm, leaf: &T) -> Digest
+    where
+        T: Hashable,
+    {
+
+        match *self {
+            HashingScheme::DomainSeparated => algorithm.hash_leaf(leaf),
+            _ => self.hash_leaf_with(&algorithm, leaf),
+        }
+    }
+
+    /// Computes the hash of an internal node from the hashes of its children.
+    pub fn hash_nodes<T>(&self, algorithm: &'static Algorithm, left: &T, right: &T) -> Digest
+    where
+        T: AsRef<[u8]>,
+    {
+
+        match *self {
+            HashingScheme::DomainSeparated => algorithm.hash_nodes(&left.as_ref(), &right.as_ref()),
+            _ => self.hash_nodes_with(&algorithm, left, right),
+        }
+    }
+
+    /// Works like `HashingScheme::hash_leaf`, hashing with the given `hasher`.
+    pub fn hash_leaf_with<T>(&self, hasher: &dyn MerkleHasher, leaf: &T) -> Digest
+    where
+        T: Hashable,
+    {
+
+        let mut ctx = self.leaf_context(hasher);
+
+        leaf.update_context(&mut ctx);
+        ctx.finish()
+    }
+
+    /// Works like `HashingScheme::hash_leaf_with`, but fails if the leaf cannot be hashed,
+    /// see `TryHashable`.
+    pub fn try_hash_leaf_with<T>(&self, hasher: &dyn MerkleHasher, leaf: &T) -> Result<Digest, HashError>
+    where
+        T: TryHashable + ?Sized,
+    {
+
+        let mut ctx = self.leaf_context(hasher);
+
+        leaf.try_update_context(&mut ctx)?;
+        Ok(ctx.finish())
+    }
+
+    /// Returns a context fed with the prefix of the leaves, if any.
+    fn leaf_context(&self, hasher: &dyn MerkleHasher) -> Context {
+        let mut ctx = hasher.context();
+
+        if *self == HashingScheme::DomainSeparated {
+            ctx.update(&[0x00]);
+        }
+
+        ctx
+    }
+
+    /// Works like `HashingScheme::hash_nodes`, hashing with the given `hasher`.
+    pub fn hash_nodes_with<T>(&self, hasher: &dyn MerkleHasher, left: &T, right: &T) -> Digest
+    where
+        T: AsRef<[u8]>,
+    {
+
+        self.nodes_context(hasher, left.as_ref(), right.as_ref()).finish()
+    }
+
+    /// Computes the hash of an internal node of a tree built with `MerkleTreeBuilder::leaf_counts`
+    /// from the hashes of its children and the number of leaves beneath it, `count`, which is
+    /// hashed after the children as 8 little-endian bytes, eg. `H(0x01 || left || right || count)`
+    /// with `HashingScheme::DomainSeparated`.
+    pub fn hash_counted_nodes_with<T>(
+        &self,
+        hasher: &dyn MerkleHasher,
+        left: &T,
+        right: &T,
+        count: u64,
+    ) -> Digest
+    where
+        T: AsRef<[u8]>,
+    {
+
+        let mut ctx = self.nodes_context(hasher, left.as_ref(), right.as_ref());
+        ctx.update(&count.to_le_bytes());
+        ctx.finish()
+    }
+
+    /// Returns a context fed with the hashes of the children of an internal node.
+    fn nodes_context(&self, hasher: &dyn MerkleHasher, left: &[u8], right: &[u8]) -> Context {
+        let mut ctx = hasher.context();
+
+        match *self {
+            HashingScheme::DomainSeparated => {
+                ctx.update(&[0x01]);
+                ctx.update(left);
+                ctx.update(right);
+            }
+
+            HashingScheme::Unprefixed => {
+                ctx.update(left);
+                ctx.update(right);
+            }
+
+            HashingScheme::SortedPairs => {
+                let (first, second) = if left <= right { (left, right) } else { (right, left) };
+
+                ctx.update(first);
+                ctx.update(second);
+            }
+        }
+
+        ctx
+    }
+}
+
+/// What to do with the last node of a level which has an odd number of nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub enum OddLeafPolicy {
+    /// The node is moved up to the next level as is. This is the default.
+    #[default]
+    Promote,
+
+    /// The node is paired with a copy of itself, as in Bitcoin.
+    ///
+    /// Beware that a tree over `[a, b, c]` then has the same root as one over `[a, b, c, c]`.
+    Duplicate,
+}
+
+/// The number of nodes from which the levels of a tree built with `MerkleTreeBuilder::parallel`
+/// are hashed across threads, unless told otherwise with `MerkleTreeBuilder::parallel_threshold`.
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 1 << 10;
+
+/// The number of bytes a value weighs on top of those it is hashed as, when the values are
+/// split across threads by weight: the block hashing the leaf prefix and the padding.
+const LEAF_WEIGHT: usize = 64;
+
+/// The construction options of a `MerkleTree`, which its mutating methods abide by.
+#[derive(Debug)]
+pub struct TreeOptions<T> {
+    pub scheme: HashingScheme,
+    pub odd_leaf_policy: OddLeafPolicy,
+    pub ordering: LeafOrdering<T>,
+    pub dedup: Option<DedupOrder>,
+    pub parallel: bool,
+    pub parallel_threshold: usize,
+    pub max_leaves: usize,
+    pub lazy: bool,
+    pub fixed_depth: Option<usize>,
+    pub hasher: Option<Arc<dyn MerkleHasher>>,
+    pub leaf_batch_size: Option<usize>,
+    pub leaf_counts: bool,
+    #[cfg(feature = "metrics")]
+    pub observer: Option<Arc<dyn TreeObserver>>,
+}
+
+impl<T> Default for TreeOptions<T> {
+    fn default() -> Self {
+        TreeOptions {
+            scheme: HashingScheme::default(),
+            odd_leaf_policy: OddLeafPolicy::default(),
+            ordering: LeafOrdering::default(),
+            dedup: None,
+            parallel: false,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+            max_leaves: usize::MAX,
+            lazy: false,
+            fixed_depth: None,
+            hasher: None,
+            leaf_batch_size: None,
+            leaf_counts: false,
+            #[cfg(feature = "metrics")]
+            observer: None,
+        }
+    }
+}
+
+impl<T> Clone for TreeOptions<T> {
+    fn clone(&self) -> Self {
+        TreeOptions {
+            scheme: self.scheme,
+            odd_leaf_policy: self.odd_leaf_policy,
+            ordering: self.ordering.clone(),
+            dedup: self.dedup,
+            parallel: self.parallel,
+            parallel_threshold: self.parallel_threshold,
+            max_leaves: self.max_leaves,
+            lazy: self.lazy,
+            fixed_depth: self.fixed_depth,
+            hasher: self.hasher.clone(),
+            leaf_batch_size: self.leaf_batch_size,
+            leaf_counts: self.leaf_counts,
+            #[cfg(feature = "metrics")]
+            observer: self.observer.clone(),
+        }
+    }
+}
+
+// The hasher and the observer are only ever called through shared references, and the tree
+// keeps none of their state, so that a tree is left as consistent by a panic in either
+// as by any other one.
+impl<T> UnwindSafe for TreeOptions<T> {}
+impl<T> RefUnwindSafe for TreeOptions<T> {}
+
+impl<T> TreeOptions<T> {
+    /// Returns the maximum number of leaves of the tree: `max_leaves`, further limited to
+    /// `2^depth` leaves for trees of a fixed depth.
+    pub fn leaf_limit(&self) -> usize {
+        match self.fixed_depth {
+            Some(depth) if depth < usize::BITS as usize => cmp::min(self.max_leaves, 1 << depth),
+            _ => self.max_leaves,
+        }
+    }
+    /// Tells whether both trees are hashed with the same hasher, the very same one if they have
+    /// a hasher of their own.
+    pub fn same_hasher<U>(&self, other: &TreeOptions<U>) -> bool {
+        match (&self.hasher, &other.hasher) {
+            (&Some(ref a), &Some(ref b)) => Arc::ptr_eq(a, b),
+            (&None, &None) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the hasher of the tree, which is `algorithm` itself unless one was given
+    /// to `MerkleTreeBuilder::hasher`.
+    pub fn hasher<'a>(&'a self, algorithm: &'a &'static Algorithm) -> &'a dyn MerkleHasher {
+        hasher::or_algorithm(&self.hasher, algorithm)
+    }
+
+    /// Computes the hash of the given leaf, with the hasher and scheme of the tree.
+    pub fn hash_leaf<V>(&self, algorithm: &'static Algorithm, leaf: &V) -> Digest
+    where
+        V: Hashable,
+    {
+
+        self.scheme.hash_leaf_with(self.hasher(&algorithm), leaf)
+    }
+
+    /// Works like `TreeOptions::hash_leaf`, but fails if the leaf cannot be hashed,
+    /// see `TryHashable`.
+    pub fn try_hash_leaf<V>(&self, algorithm: &'static Algorithm, leaf: &V) -> Result<Digest, HashError>
+    where
+        V: TryHashable,
+    {
+
+        self.scheme.try_hash_leaf_with(self.hasher(&algorithm), leaf)
+    }
+
+    /// Computes the hash of an internal node, with the hasher and scheme of the tree.
+    pub fn hash_nodes<N>(&self, algorithm: &'static Algorithm, left: &N, right: &N) -> Digest
+    where
+        N: AsRef<[u8]>,
+    {
+
+        self.scheme.hash_nodes_with(self.hasher(&algorithm), left, right)
+    }
+
+    /// Computes the hash of an internal node with `count` leaves beneath it, with the hasher
+    /// and scheme of the tree, hashing `count` along with the children if the tree has
+    /// `leaf_counts`.
+    pub fn hash_subtree<N>(&self, algorithm: &'static Algorithm, left: &N, right: &N, count: u64) -> Digest
+    where
+        N: AsRef<[u8]>,
+    {
+
+        if self.leaf_counts {
+            self.scheme.hash_counted_nodes_with(self.hasher(&algorithm), left, right, count)
+        } else {
+            self.hash_nodes(algorithm, left, right)
+        }
+    }
+
+    /// Notifies the observer of the tree, if any, that the value at the given `index` of those
+    /// being added to the tree was hashed into a leaf, see `TreeObserver::on_leaf_hashed`.
+    #[inline]
+    pub fn leaf_hashed(&self, index: usize) {
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(ref observer) = self.observer {
+                observer.on_leaf_hashed(index);
+            }
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        let _ = index;
+    }
+
+    /// Notifies the observer of the tree, if any, that the `nodes` nodes of the given `level`
+    /// were built, see `TreeObserver::on_level_built`.
+    #[inline]
+    pub fn level_built(&self, level: usize, nodes: usize) {
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(ref observer) = self.observer {
+                observer.on_level_built(level, nodes);
+            }
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        let _ = (level, nodes);
+    }
+
+    /// Generates a proof with `generate`, reporting how long it took to the observer
+    /// of the tree, if any, see `TreeObserver::on_proof_generated`.
+    #[inline]
+    pub fn timed_proof<U, F>(&self, generate: F) -> Option<Proof<U>>
+    where
+        F: FnOnce() -> Option<Proof<U>>,
+    {
+
+        #[cfg(feature = "metrics")]
+        {
+            observer::timed(self.observer(), generate, |observer, proof, duration| {
+                if let Some(ref proof) = *proof {
+                    observer.on_proof_generated(proof.depth(), duration);
+                }
+            })
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        generate()
+    }
+
+    /// Checks a proof with `validate`, reporting how long it took and whether it succeeded
+    /// to the observer of the tree, if any, see `TreeObserver::on_validation`.
+    #[inline]
+    pub fn timed_validation<R, F>(&self, validate: F) -> Result<R, Error>
+    where
+        F: FnOnce() -> Result<R, Error>,
+    {
+
+        #[cfg(feature = "metrics")]
+        {
+            observer::timed(self.observer(), validate, |observer, result, duration| {
+                observer.on_validation(result.is_ok(), duration);
+            })
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        validate()
+    }
+
+    /// Returns the observer of the tree, if any.
+    #[cfg(feature = "metrics")]
+    pub fn observer(&self) -> Option<&dyn TreeObserver> {
+        self.observer.as_deref()
+    }
+
+    /// Computes the root hash of an empty tree, with the hasher of the tree.
+    pub fn hash_empty(&self, algorithm: &'static Algorithm) -> Digest {
+        self.hasher(&algorithm).hash_empty()
+    }
+
+    /// Returns the hashes of the subtrees of `0` to `depth` zero leaves, see `default_hashes`.
+    /// They are only cached for trees without a hasher of their own, nor `leaf_counts`,
+    /// under which the zero leaves are not counted.
+    pub fn default_hashes(&self, algorithm: &'static Algorithm, depth: usize) -> Vec<Vec<u8>> {
+        let zero = vec![0; algorithm.output_len];
+
+        if self.hasher.is_none() && !self.leaf_counts {
+            return default_hashes_with(algorithm, self.scheme, depth, &zero);
+        }
+
+        let mut hashes = vec![zero];
+
+        while hashes.len() <= depth {
+            let next = {
+                let last = &hashes[hashes.len() - 1];
+                self.hash_subtree(algorithm, last, last, 0).as_ref().to_vec()
+            };
+
+            hashes.push(next);
+        }
+
+        hashes
+    }
+    /// Hashes the given values into leaves, in order, with the hasher and scheme of the tree.
+    ///
+    /// With a `leaf_batch_size`, the values are hashed that many at a time through
+    /// `MerkleHasher::hash_leaves_batch`, save for batches with a value which does not give
+    /// its `Hashable::hashed_bytes`, which are hashed one by one.
+    pub fn leaves<'a, I>(&'a self, algorithm: &'static Algorithm, values: I) -> Box<dyn Iterator<Item = Leaf<T>> + 'a>
+    where
+        T: Hashable + 'a,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: 'a,
+    {
+
+        let mut values = values.into_iter();
+
+        let size = match self.leaf_batch_size {
+            Some(size) => size,
+            None => {
+                return self.observed(values.map(move |value| Leaf::new(self.hash_leaf(algorithm, &value), value)));
+            }
+        };
+
+        let batches = iter::from_fn(move || {
+            let batch = values.by_ref().take(size).collect::<Vec<_>>();
+
+            if batch.is_empty() {
+                None
+            } else {
+                Some(self.hash_batch(algorithm, batch))
+            }
+        });
+
+        self.observed(batches.flatten())
+    }
+
+    /// Works like `TreeOptions::leaves`, but hashes the values across as many threads as are
+    /// available, each getting a run of values of about the same weight: the number of bytes
+    /// they are hashed as, when at hand, see `Hashable::hashed_bytes`, plus `LEAF_WEIGHT`.
+    ///
+    /// The values are hashed on the calling thread if they weigh less than `parallel_threshold`
+    /// values of no bytes, or if they are hashed in batches, see `TreeOptions::leaf_batch_size`.
+    pub fn leaves_parallel<'a>(
+        &'a self,
+        algorithm: &'static Algorithm,
+        values: Vec<T>,
+    ) -> Box<dyn Iterator<Item = Leaf<T>> + 'a>
+    where
+        T: Hashable + Sync + 'a,
+    {
+
+        let weights = values
+            .iter()
+            .map(|value| value.hashed_bytes().map_or(0, <[u8]>::len).saturating_add(LEAF_WEIGHT))
+            .collect::<Vec<_>>();
+
+        let total = weights.iter().fold(0usize, |total, &weight| total.saturating_add(weight));
+
+        if self.leaf_batch_size.is_some() || total < self.parallel_threshold.saturating_mul(LEAF_WEIGHT) {
+            return self.leaves(algorithm, values);
+        }
+
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let runs = weighted_runs(&weights, threads);
+
+        let hashes = thread::scope(|scope| {
+            let values = &values;
+
+            let handles = runs
+                .into_iter()
+                .map(|run| {
+                    scope.spawn(move || {
+                        values[run]
+                            .iter()
+                            .map(|value| self.hash_leaf(algorithm, value))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap_or_else(|cause| panic::resume_unwind(cause)))
+                .collect::<Vec<_>>()
+        });
+
+        self.observed(hashes.into_iter().zip(values).map(|(hash, value)| Leaf::new(hash, value)))
+    }
+
+    /// Notifies the observer of the tree, if any, of each of the given leaves as they are yielded,
+    /// see `TreeOptions::leaf_hashed`.
+    fn observed<'a, L>(&'a self, leaves: L) -> Box<dyn Iterator<Item = Leaf<T>> + 'a>
+    where
+        L: Iterator<Item = Leaf<T>> + 'a,
+    {
+
+        Box::new(leaves.enumerate().map(move |(index, leaf)| {
+            self.leaf_hashed(index);
+            leaf
+        }))
+    }
+
+    /// Hashes a batch of values into leaves, see `TreeOptions::leaves`.
+    fn hash_batch(&self, algorithm: &'static Algorithm, values: Vec<T>) -> Vec<Leaf<T>>
+    where
+        T: Hashable,
+    {
+
+        let prefix: &[u8] = match self.scheme {
+            HashingScheme::DomainSeparated => &[0x00],
+            HashingScheme::Unprefixed | HashingScheme::SortedPairs => &[],
+        };
+
+        let messages = values
+            .iter()
+            .map(|value| value.hashed_bytes().map(|bytes| [prefix, bytes].concat()))
+            .collect::<Option<Vec<_>>>();
+
+        let messages = match messages {
+            Some(messages) => messages,
+            None => {
+                return values
+                    .into_iter()
+                    .map(|value| Leaf::new(self.hash_leaf(algorithm, &value), value))
+                    .collect();
+            }
+        };
+
+        let slices = messages.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let hashes = self.hasher(&algorithm).hash_leaves_batch(&slices);
+
+        assert!(
+            hashes.len() == values.len() && hashes.iter().all(|hash| hash.len() == algorithm.output_len),
+            "the hasher must return one hash of the output length of its algorithm per leaf"
+        );
+
+        hashes
+            .into_iter()
+            .zip(values)
+            .map(|(hash, value)| {
+                Leaf {
+                    hash: SmallHash::from(hash),
+                    value: value,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Splits the indices of the given weights into at most `runs` consecutive runs,
+/// each of about the same total weight.
+pub(crate) fn weighted_runs(weights: &[usize], runs: usize) -> Vec<Range<usize>> {
+    let total = weights.iter().fold(0usize, |total, &weight| total.saturating_add(weight));
+    let target = total / runs.max(1) + 1;
+
+    let mut split = Vec::with_capacity(runs);
+    let mut start = 0;
+    let mut weight = 0usize;
+
+    for (index, &w) in weights.iter().enumerate() {
+        weight = weight.saturating_add(w);
+
+        if weight >= target {
+            split.push(start..index + 1);
+            start = index + 1;
+            weight = 0;
+        }
+    }
+
+    if start < weights.len() {
+        split.push(start..weights.len());
+    }
+
+    split
+}
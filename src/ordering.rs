@@ -0,0 +1,99 @@
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+
+use tree::Leaf;
+
+/// Compares two values by the keys extracted from them.
+type KeyComparator<T> = Arc<dyn Fn(&T, &T) -> Ordering + Send + Sync>;
+
+/// The order in which values are laid out as the leaves of a `MerkleTree`.
+///
+/// With `ByValue` or `ByKey`, the root hash only depends on the multiset of values,
+/// and a tree can be reproduced from its values sorted beforehand.
+/// Values which compare equal keep their relative order from the input.
+#[derive(Default)]
+pub enum LeafOrdering<T> {
+    /// The leaves are in the order of the input. This is the default.
+    #[default]
+    Input,
+
+    /// The leaves are sorted by value, see `LeafOrdering::by_value`
+    ByValue(fn(&T, &T) -> Ordering),
+
+    /// The leaves are sorted by a key extracted from the values, see `LeafOrdering::by_key`
+    ByKey(KeyComparator<T>),
+}
+
+impl<T> LeafOrdering<T> {
+    /// Sorts the leaves according to the natural order of the values.
+    pub fn by_value() -> Self
+    where
+        T: Ord,
+    {
+
+        LeafOrdering::ByValue(Ord::cmp)
+    }
+
+    /// Sorts the leaves according to the key returned by `key` for each value.
+    pub fn by_key<K, F>(key: F) -> Self
+    where
+        K: Ord,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+
+        LeafOrdering::ByKey(Arc::new(move |a, b| key(a).cmp(&key(b))))
+    }
+
+    /// Returns whether the leaves are sorted, ie. whether this is not `LeafOrdering::Input`.
+    pub fn is_sorted(&self) -> bool {
+        !matches!(*self, LeafOrdering::Input)
+    }
+
+    /// Compares two values according to this ordering, `Input` considering all values equal.
+    pub fn compare(&self, a: &T, b: &T) -> Ordering {
+        match *self {
+            LeafOrdering::Input => Ordering::Equal,
+            LeafOrdering::ByValue(cmp) => cmp(a, b),
+            LeafOrdering::ByKey(ref cmp) => cmp(a, b),
+        }
+    }
+}
+
+impl<T> LeafOrdering<T> {
+    /// Sorts the given leaves, keeping the relative order of those which compare equal.
+    pub(crate) fn sort_leaves(&self, leaves: &mut [Leaf<T>]) {
+        if !self.is_sorted() {
+            return;
+        }
+
+        leaves.sort_by(|a, b| self.compare(&a.value, &b.value));
+    }
+}
+
+impl<T> Clone for LeafOrdering<T> {
+    fn clone(&self) -> Self {
+        match *self {
+            LeafOrdering::Input => LeafOrdering::Input,
+            LeafOrdering::ByValue(cmp) => LeafOrdering::ByValue(cmp),
+            LeafOrdering::ByKey(ref cmp) => LeafOrdering::ByKey(cmp.clone()),
+        }
+    }
+}
+
+// Comparing values only goes through shared references, and a key extractor which panics
+// leaves no state behind, the leaves being sorted before the tree is modified.
+impl<T> UnwindSafe for LeafOrdering<T> {}
+impl<T> RefUnwindSafe for LeafOrdering<T> {}
+
+impl<T> fmt::Debug for LeafOrdering<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LeafOrdering::Input => f.write_str("Input"),
+            LeafOrdering::ByValue(_) => f.write_str("ByValue"),
+            LeafOrdering::ByKey(_) => f.write_str("ByKey"),
+        }
+    }
+}
@@ -0,0 +1,381 @@
+
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+
+use digest::Algorithm;
+
+use error::Error;
+use hasher::{self, MerkleHasher};
+use hashutils::Hashable;
+use multiproof::{MultiProof, MultiProofNode};
+use options::HashingScheme;
+use proof::{Proof, Lemma, Positioned, MAX_TREE_DEPTH};
+
+/// The part of a `MerkleTree` known from a `MultiProof`, also called a witness, where the
+/// subtrees without any proven leaf are only known by their hashes.
+///
+/// It generates the proofs of its leaves once their values are inserted with `insert_leaf`,
+/// without ever holding the whole tree.
+#[derive(Clone, Debug)]
+pub struct PartialTree<T> {
+    algorithm: &'static Algorithm,
+    hasher: Option<Arc<dyn MerkleHasher>>,
+    scheme: HashingScheme,
+    count: usize,
+    root: PartialNode<T>,
+}
+
+#[derive(Clone, Debug)]
+enum PartialNode<T> {
+    /// A subtree only known by its hash
+    Opaque { hash: Vec<u8> },
+
+    /// A copy of its left sibling, added by `OddLeafPolicy::Duplicate`
+    Empty { hash: Vec<u8> },
+
+    /// A leaf, whose value is only known once inserted
+    Leaf { hash: Vec<u8>, value: Option<T> },
+
+    Node {
+        hash: Vec<u8>,
+        left: Box<PartialNode<T>>,
+        right: Box<PartialNode<T>>,
+    },
+}
+
+// The hasher is only ever called through shared references, and a witness keeps none of its state.
+impl<T: UnwindSafe> UnwindSafe for PartialTree<T> {}
+impl<T: RefUnwindSafe> RefUnwindSafe for PartialTree<T> {}
+
+impl<T> PartialNode<T> {
+    fn hash(&self) -> &Vec<u8> {
+        match *self {
+            PartialNode::Opaque { ref hash } |
+            PartialNode::Empty { ref hash } |
+            PartialNode::Leaf { ref hash, .. } |
+            PartialNode::Node { ref hash, .. } => hash,
+        }
+    }
+}
+
+impl<T> PartialTree<T> {
+    /// Builds the part of a tree known from the given `proof`, checking that the hash of every
+    /// node on the paths to its leaves is that of its children.
+    ///
+    /// The root hash is left for the caller to compare to a trusted one, see `root_hash`.
+    /// The hashes are recomputed with the hasher of the proof, see `MultiProof::hasher`.
+    pub fn from_multiproof(proof: MultiProof) -> Result<Self, Error> {
+        let MultiProof {
+            algorithm,
+            hasher,
+            scheme,
+            count,
+            root,
+        } = proof;
+
+        let root = match root {
+            MultiProofNode::Empty { hash } if count == 0 => PartialNode::Empty { hash: hash },
+            _ if count == 0 => return Err(Error::MalformedTree("the proof of an empty tree has nodes")),
+            root => convert(hasher::or_algorithm(&hasher, &algorithm), scheme, root, count, 0, 0)?,
+        };
+
+        Ok(PartialTree {
+            algorithm: algorithm,
+            hasher: hasher,
+            scheme: scheme,
+            count: count,
+            root: root,
+        })
+    }
+
+    /// Returns the root hash of the original tree.
+    pub fn root_hash(&self) -> &Vec<u8> {
+        self.root.hash()
+    }
+
+    /// Returns the number of leaves of the original tree.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns whether the leaf at the given `index` is in the witness,
+    /// ie. it was proven by the multiproof or inserted since.
+    pub fn is_known(&self, index: usize) -> bool {
+        match self.path(index) {
+            Some((&PartialNode::Leaf { .. }, _)) => true,
+            _ => false,
+        }
+    }
+
+    /// Generates a proof of the leaf at the given `index`, which is the one
+    /// `MerkleTree::gen_nth_proof` generates from the original tree.
+    ///
+    /// Returns `None` if the leaf is not in the witness, or if its value has not been inserted.
+    pub fn gen_proof(&self, index: usize) -> Option<Proof<T>>
+    where
+        T: Clone,
+    {
+
+        let (leaf, path) = self.path(index)?;
+
+        let value = match *leaf {
+            PartialNode::Leaf { value: Some(ref value), .. } => value.clone(),
+            _ => return None,
+        };
+
+        let mut lemma = Lemma {
+            node_hash: leaf.hash().clone(),
+            sibling_hash: None,
+            sub_lemma: None,
+        };
+
+        for (hash, sibling_hash) in path.into_iter().rev() {
+            lemma = Lemma {
+                node_hash: hash.clone(),
+                sibling_hash: Some(sibling_hash),
+                sub_lemma: Some(Box::new(lemma)),
+            };
+        }
+
+        let mut proof = Proof::new(self.algorithm, self.root_hash().clone(), lemma, value);
+        proof.hasher = self.hasher.clone();
+        proof.scheme = self.scheme;
+        Some(proof)
+    }
+
+    /// Inserts the value of the leaf at the given `index`, which must hash to the hash of
+    /// that leaf. The leaf need not have been proven, as long as its hash is in the witness,
+    /// ie. it is the sibling of a node on the path to a proven leaf.
+    ///
+    /// Fails with `Error::LeafNotInWitness` if the hash of the leaf is unknown,
+    /// and with `Error::HashMismatch` if it is not the hash of `value`.
+    pub fn insert_leaf(&mut self, index: usize, value: T) -> Result<(), Error>
+    where
+        T: Hashable,
+    {
+
+        if index >= self.count {
+            return Err(Error::LeafNotInWitness(index));
+        }
+
+        let hasher = hasher::or_algorithm(&self.hasher, &self.algorithm);
+        let hash = self.scheme.hash_leaf_with(hasher, &value).as_ref().into();
+        let leaf = Leaf {
+            hasher: hasher,
+            scheme: self.scheme,
+            index: index,
+            hash: hash,
+            value: value,
+        };
+
+        insert(&mut self.root, leaf, 0, self.count, 0, 0)
+    }
+
+    /// Returns the node of the leaf at the given `index`, which is either the leaf itself
+    /// or the subtree it belongs to, along with the hash and the sibling of every node
+    /// on the path to that node, from the root down.
+    fn path(&self, index: usize) -> Option<(&PartialNode<T>, Vec<PathStep>)> {
+        if index >= self.count {
+            return None;
+        }
+
+        let mut path = Vec::new();
+        let mut node = &self.root;
+        let mut index = index;
+        let mut count = self.count;
+
+        while let PartialNode::Node {
+            ref hash,
+            ref left,
+            ref right,
+        } = *node
+        {
+            let left_count = left_count(right, count);
+
+            if index < left_count {
+                path.push((hash, Positioned::Right(right.hash().clone())));
+                node = left;
+                count = left_count;
+            } else {
+                path.push((hash, Positioned::Left(left.hash().clone())));
+                node = right;
+                index -= left_count;
+                count -= left_count;
+            }
+        }
+
+        Some((node, path))
+    }
+}
+
+/// The hash of a node on the path to a leaf, along with its sibling on the path.
+type PathStep<'a> = (&'a Vec<u8>, Positioned<Vec<u8>>);
+
+/// A leaf being inserted into a witness.
+struct Leaf<'a, T> {
+    hasher: &'a dyn MerkleHasher,
+    scheme: HashingScheme,
+    index: usize,
+    hash: Vec<u8>,
+    value: T,
+}
+
+/// Returns the number of leaves under the left child of a node over `count` leaves,
+/// whose right child is `right`, as `tree::left_count`.
+fn left_count<T>(right: &PartialNode<T>, count: usize) -> usize {
+    match *right {
+        PartialNode::Empty { .. } => count,
+        _ => count.next_power_of_two() / 2,
+    }
+}
+
+/// Converts the given node of a multiproof, over `count` leaves, which lies `depth` levels
+/// below the root at the given `path`, as in `Error::HashMismatch`.
+fn convert<T>(
+    hasher: &dyn MerkleHasher,
+    scheme: HashingScheme,
+    node: MultiProofNode,
+    count: usize,
+    depth: usize,
+    path: usize,
+) -> Result<PartialNode<T>, Error> {
+
+    if depth > MAX_TREE_DEPTH {
+        return Err(Error::MalformedTree("the proof is deeper than `MAX_TREE_DEPTH`"));
+    }
+
+    match node {
+        MultiProofNode::Pruned { hash } => Ok(PartialNode::Opaque { hash: hash }),
+
+        MultiProofNode::Empty { .. } => Err(Error::MalformedTree("a copy of a node is not a right child")),
+
+        MultiProofNode::Leaf { hash } => {
+            if count != 1 {
+                return Err(Error::MalformedTree("a leaf is not at the position of a leaf"));
+            }
+
+            Ok(PartialNode::Leaf {
+                hash: hash,
+                value: None,
+            })
+        }
+
+        MultiProofNode::Node { hash, left, right } => {
+            let left_count = match *right {
+                MultiProofNode::Empty { .. } => count,
+                _ if count < 2 => return Err(Error::MalformedTree("a node is at the position of a leaf")),
+                _ => count.next_power_of_two() / 2,
+            };
+
+            let left = convert(hasher, scheme, *left, left_count, depth + 1, path << 1)?;
+
+            let right = match *right {
+                MultiProofNode::Empty { hash } => {
+                    if hash != *left.hash() {
+                        return Err(Error::HashMismatch {
+                            depth: depth + 1,
+                            index: path << 1 | 1,
+                        });
+                    }
+
+                    PartialNode::Empty { hash: hash }
+                }
+
+                right => convert(hasher, scheme, right, count - left_count, depth + 1, path << 1 | 1)?,
+            };
+
+            if scheme.hash_nodes_with(hasher, left.hash(), right.hash()).as_ref() != hash.as_slice() {
+                return Err(Error::HashMismatch {
+                    depth: depth,
+                    index: path,
+                });
+            }
+
+            Ok(PartialNode::Node {
+                hash: hash,
+                left: Box::new(left),
+                right: Box::new(right),
+            })
+        }
+    }
+}
+
+/// Inserts the given `leaf` into the given node over `count` leaves from the `offset`-th one on,
+/// which lies `depth` levels below the root at the given `path`, as in `Error::HashMismatch`.
+fn insert<T>(
+    node: &mut PartialNode<T>,
+    leaf: Leaf<T>,
+    offset: usize,
+    count: usize,
+    depth: usize,
+    path: usize,
+) -> Result<(), Error> {
+
+    let hash = match *node {
+        PartialNode::Node {
+            ref mut left,
+            ref mut right,
+            ..
+        } => {
+            let left_count = left_count(right, count);
+
+            return if leaf.index < offset + left_count {
+                insert(left, leaf, offset, left_count, depth + 1, path << 1)
+            } else {
+                let right_offset = offset + left_count;
+                insert(right, leaf, right_offset, count - left_count, depth + 1, path << 1 | 1)
+            };
+        }
+
+        PartialNode::Leaf { ref hash, ref mut value } => {
+            if *hash != leaf.hash {
+                return Err(Error::HashMismatch {
+                    depth: depth,
+                    index: path,
+                });
+            }
+
+            *value = Some(leaf.value);
+            return Ok(());
+        }
+
+        PartialNode::Opaque { ref hash } if count == 1 => hash.clone(),
+
+        _ => return Err(Error::LeafNotInWitness(leaf.index)),
+    };
+
+    // With `OddLeafPolicy::Duplicate`, a single leaf may have been paired with copies of itself
+    let mut copies = 0;
+    let mut expected = leaf.hash.clone();
+
+    while expected != hash {
+        if depth + copies == MAX_TREE_DEPTH {
+            return Err(Error::HashMismatch {
+                depth: depth,
+                index: path,
+            });
+        }
+
+        expected = leaf.scheme.hash_nodes_with(leaf.hasher, &expected, &expected).as_ref().into();
+        copies += 1;
+    }
+
+    let mut expanded = PartialNode::Leaf {
+        hash: leaf.hash,
+        value: Some(leaf.value),
+    };
+
+    for _ in 0..copies {
+        let hash = leaf.scheme.hash_nodes_with(leaf.hasher, expanded.hash(), expanded.hash());
+        let copy = PartialNode::Empty { hash: expanded.hash().clone() };
+
+        expanded = PartialNode::Node {
+            hash: hash.as_ref().into(),
+            left: Box::new(expanded),
+            right: Box::new(copy),
+        };
+    }
+
+    *node = expanded;
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use std::hint;
+
+use options::HashingScheme;
+use proof::MAX_TREE_DEPTH;
+
+/// How strictly `Proof::validate_with_policy` checks a proof, on top of what `Proof::validate`
+/// checks, which is what the default policy does.
+///
+/// A policy is built once, then used to validate any number of proofs: it is `Copy`,
+/// and validating with it does not allocate.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use merkle::{MerkleTree, HashingScheme, ValidationPolicy};
+/// # fn main() {
+/// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c", "d"]);
+///
+/// let policy = ValidationPolicy::new()
+///     .max_depth(40)
+///     .required_depth(2)
+///     .required_scheme(HashingScheme::DomainSeparated)
+///     .require_value_check(true);
+///
+/// for index in 0..4 {
+///     let proof = tree.gen_nth_proof(index).unwrap();
+///     assert!(proof.validate_with_policy(tree.root_hash(), &policy).is_ok());
+/// }
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ValidationPolicy {
+    pub(crate) max_depth: usize,
+    pub(crate) required_depth: Option<usize>,
+    pub(crate) required_scheme: Option<HashingScheme>,
+    pub(crate) tree_size: Option<u64>,
+    pub(crate) strict_leaf_count: Option<u64>,
+    pub(crate) require_value_check: bool,
+    pub(crate) constant_time: bool,
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        ValidationPolicy {
+            max_depth: MAX_TREE_DEPTH,
+            required_depth: None,
+            required_scheme: None,
+            tree_size: None,
+            strict_leaf_count: None,
+            require_value_check: false,
+            constant_time: false,
+        }
+    }
+}
+
+impl ValidationPolicy {
+    /// Creates the default policy, which checks what `Proof::validate` checks.
+    pub fn new() -> Self {
+        ValidationPolicy::default()
+    }
+
+    /// Rejects the proofs whose lemma chain is more than `depth` levels deep, which is capped
+    /// at `MAX_TREE_DEPTH`, the default.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth.min(MAX_TREE_DEPTH);
+        self
+    }
+
+    /// Rejects the proofs whose lemma chain is not exactly `depth` levels deep.
+    pub fn required_depth(mut self, depth: usize) -> Self {
+        self.required_depth = Some(depth);
+        self
+    }
+
+    /// Rejects the proofs which are not hashed with the given `scheme`, such as those
+    /// of trees hashed without domain separation, with `HashingScheme::DomainSeparated`,
+    /// with `Error::SchemeMismatch`.
+    pub fn required_scheme(mut self, scheme: HashingScheme) -> Self {
+        self.required_scheme = Some(scheme);
+        self
+    }
+
+    /// Rejects the proofs which are not the proof of a leaf of a tree of `tree_size` leaves,
+    /// built with the `odd_leaf_policy` of the proof: the depth of the lemma chain and the side
+    /// of every sibling must be those of the path to a leaf of such a tree, and the `tree_size`
+    /// and `leaf_index` of the proof, if known, must be `tree_size` and the index of that leaf.
+    /// See `Lemma::leaf_index`.
+    pub fn tree_size(mut self, tree_size: u64) -> Self {
+        self.tree_size = Some(tree_size);
+        self
+    }
+
+    /// Rejects, before anything is hashed, the proofs which are deeper than the deepest leaf
+    /// of any tree of `leaf_count` leaves, and those whose lemma chain is malformed: a lemma
+    /// with a sibling but no sub lemma, or the other way round, in particular the last one.
+    ///
+    /// Without domain separation, a lemma chain can be padded with levels below its leaf,
+    /// the leaf hash being passed off as the hash of a node over two chosen children, and still
+    /// lead to the root hash: checking hashes alone cannot tell such a proof from the proof
+    /// of a leaf of a deeper tree, so that only a bound on the size of the tree, known to the
+    /// verifier, rejects it. `ValidationPolicy::tree_size` is stricter still, requiring the exact
+    /// path to a leaf of a tree of the given size.
+    pub fn strict(mut self, leaf_count: u64) -> Self {
+        self.strict_leaf_count = Some(leaf_count);
+        self
+    }
+
+    /// Whether to check that the lemma chain starts from the hash of the value of the proof,
+    /// see `Proof::validate_value`. Off by default.
+    pub fn require_value_check(mut self, check: bool) -> Self {
+        self.require_value_check = check;
+        self
+    }
+
+    /// Whether to compare hashes in constant time, and to check every level of the lemma chain
+    /// rather than stopping at the first one which does not hold, so that the time taken does
+    /// not tell how much of a forged proof is right. Off by default.
+    pub fn constant_time(mut self, constant_time: bool) -> Self {
+        self.constant_time = constant_time;
+        self
+    }
+
+    /// Returns whether both hashes are equal, in constant time with respect to their contents
+    /// if the policy asks for it.
+    pub(crate) fn equal(&self, left: &[u8], right: &[u8]) -> bool {
+        if !self.constant_time {
+            return left == right;
+        }
+
+        if left.len() != right.len() {
+            return false;
+        }
+
+        let diff = left.iter().zip(right).fold(0, |diff, (a, b)| diff | (a ^ b));
+
+        hint::black_box(diff) == 0
+    }
+}
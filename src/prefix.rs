@@ -0,0 +1,261 @@
+
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+
+use digest::Algorithm;
+
+use hasher::{self, MerkleHasher};
+use options::{HashingScheme, TreeOptions};
+use proof::Positioned;
+use tree::{self, Subtree, View};
+
+/// A proof that a `MerkleTree` over `old_len` values is a prefix of one over `new_len` values,
+/// ie. that the newer tree was obtained by appending values to the older one.
+///
+/// This is the consistency proof of RFC 6962, whose tree shape is the one `MerkleTree`
+/// gets with `OddLeafPolicy::Promote`. See `MerkleTree::prove_prefix`.
+///
+/// When serialized, the `algorithm` is named as in `algorithm_name`, and the `hasher` is skipped,
+/// as is the `scheme` if it is the default one, as for a `Proof`. Deserializing more than
+/// `MAX_TREE_DEPTH + 1` hashes, which no proof holds, fails.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct PrefixProof {
+    /// The hashing algorithm used in the original `MerkleTree`
+    #[cfg_attr(
+        feature = "serialization-serde",
+        serde(
+            serialize_with = "::registry::serialize_algorithm",
+            deserialize_with = "::registry::deserialize_algorithm"
+        )
+    )]
+    pub algorithm: &'static Algorithm,
+
+    /// The hasher of the original `MerkleTree`, if it has one of its own, see `Proof::hasher`
+    #[cfg_attr(feature = "serialization-serde", serde(skip))]
+    pub hasher: Option<Arc<dyn MerkleHasher>>,
+
+    /// The way the nodes of the original `MerkleTree` are hashed
+    #[cfg_attr(
+        feature = "serialization-serde",
+        serde(default, skip_serializing_if = "HashingScheme::is_default")
+    )]
+    pub scheme: HashingScheme,
+
+    /// The hashes of the subtrees needed to recompute both roots, in the order of RFC 6962
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding::prefix"))]
+    pub hashes: Vec<Vec<u8>>,
+}
+
+impl PartialEq for PrefixProof {
+    fn eq(&self, other: &PrefixProof) -> bool {
+        self.hashes == other.hashes && self.scheme == other.scheme
+    }
+}
+
+impl Eq for PrefixProof {}
+
+// The hasher is only ever called through shared references, and a proof keeps none of its state.
+impl UnwindSafe for PrefixProof {}
+impl RefUnwindSafe for PrefixProof {}
+
+impl PrefixProof {
+    /// Generates the proof that the first `prefix_len` leaves of the given `tree`,
+    /// which has `count` leaves, are the leaves of a tree of their own.
+    pub(crate) fn new<T>(
+        algorithm: &'static Algorithm,
+        scheme: HashingScheme,
+        tree: Subtree<T>,
+        prefix_len: usize,
+        count: usize,
+    ) -> Self {
+
+        let mut hashes = Vec::new();
+
+        if prefix_len > 0 && prefix_len < count {
+            subproof(tree, prefix_len, count, true, &mut hashes);
+        }
+
+        PrefixProof {
+            algorithm: algorithm,
+            hasher: None,
+            scheme: scheme,
+            hashes: hashes,
+        }
+    }
+
+    /// Returns the hasher the roots are recomputed with: the `hasher` of the original tree,
+    /// or its bare `algorithm` if it has none.
+    pub fn hasher(&self) -> &dyn MerkleHasher {
+        hasher::or_algorithm(&self.hasher, &self.algorithm)
+    }
+
+    /// Checks that a tree over `old_len` values with root hash `old_root` is a prefix
+    /// of the tree over `new_len` values with root hash `new_root`.
+    ///
+    /// The roots do not commit to the number of leaves, so the lengths must be published
+    /// along with the roots they belong to.
+    pub fn verify(&self, old_root: &[u8], old_len: usize, new_root: &[u8], new_len: usize) -> bool {
+        self.verify_u64(old_root, old_len as u64, new_root, new_len as u64)
+    }
+
+    /// Works like `PrefixProof::verify`, given the numbers of leaves as `u64`, as published
+    /// along with tree heads, so that the proofs of trees of more than `2^32` leaves
+    /// are verified on 32-bit targets as well.
+    pub fn verify_u64(&self, old_root: &[u8], old_len: u64, new_root: &[u8], new_len: u64) -> bool {
+        if old_len > new_len {
+            return false;
+        }
+
+        if old_len == 0 {
+            return self.hashes.is_empty() && old_root == self.hasher().hash_empty().as_ref();
+        }
+
+        if old_len == new_len {
+            return self.hashes.is_empty() && old_root == new_root;
+        }
+
+        let mut hashes = self.hashes.iter().map(|hash| hash.as_slice());
+
+        // The old root is omitted from the proof when it is the root of a subtree of the newer tree
+        let first = if old_len.is_power_of_two() {
+            Some(old_root)
+        } else {
+            hashes.next()
+        };
+
+        let first = match first {
+            Some(first) => first,
+            None => return false,
+        };
+
+        let mut old_index = old_len - 1;
+        let mut new_index = new_len - 1;
+
+        while old_index & 1 == 1 {
+            old_index >>= 1;
+            new_index >>= 1;
+        }
+
+        let mut old_hash = first.to_vec();
+        let mut new_hash = first.to_vec();
+
+        for hash in hashes {
+            if new_index == 0 {
+                return false;
+            }
+
+            if old_index & 1 == 1 || old_index == new_index {
+                old_hash = self.hash_nodes(hash, &old_hash);
+                new_hash = self.hash_nodes(hash, &new_hash);
+
+                while old_index & 1 == 0 && old_index != 0 {
+                    old_index >>= 1;
+                    new_index >>= 1;
+                }
+            } else {
+                new_hash = self.hash_nodes(&new_hash, hash);
+            }
+
+            old_index >>= 1;
+            new_index >>= 1;
+        }
+
+        new_index == 0 && old_hash == old_root && new_hash == new_root
+    }
+
+    fn hash_nodes(&self, left: &[u8], right: &[u8]) -> Vec<u8> {
+        self.scheme.hash_nodes_with(self.hasher(), &left, &right).as_ref().into()
+    }
+}
+
+/// Appends to `hashes` the subtree hashes proving that the first `m` leaves of `tree`,
+/// which has `n` leaves, form a tree of their own, as in the `SUBPROOF` of RFC 6962.
+/// `whole` tells whether the subtree over these `m` leaves is the one of the older tree.
+fn subproof<T>(tree: Subtree<T>, m: usize, n: usize, whole: bool, hashes: &mut Vec<Vec<u8>>) {
+    if m == n {
+        if !whole {
+            hashes.push(tree.hash().to_vec());
+        }
+
+        return;
+    }
+
+    if let View::Node { left, right, .. } = tree.view() {
+        let k = n.next_power_of_two() / 2;
+
+        if m <= k {
+            subproof(left, m, k, whole, hashes);
+            hashes.push(right.hash().to_vec());
+        } else {
+            subproof(right, m - k, n - k, false, hashes);
+            hashes.push(left.hash().to_vec());
+        }
+    }
+}
+
+/// Appends to `path` the sibling hashes, from the root down, on the path to the leaf at `index`
+/// of the tree over the first `m` leaves of `tree`, which has `n` leaves, both trees being built
+/// by promoting the odd nodes. Fails if the path goes through a pruned subtree.
+pub(crate) fn prefix_path<T>(
+    algorithm: &'static Algorithm,
+    options: &TreeOptions<T>,
+    tree: Subtree<T>,
+    n: usize,
+    m: usize,
+    index: usize,
+    path: &mut Vec<Positioned<Vec<u8>>>,
+) -> Option<()> {
+
+    match tree.view() {
+        View::Node { left, right, .. } => {
+            let k = tree::left_count(right, n);
+
+            if m <= k {
+                // The root of the smaller tree lies under the left child
+                prefix_path(algorithm, options, left, k, m, index, path)
+            } else if index < k {
+                path.push(Positioned::Right(prefix_hash(algorithm, options, right, n - k, m - k)?));
+                prefix_path(algorithm, options, left, k, k, index, path)
+            } else {
+                path.push(Positioned::Left(left.hash().to_vec()));
+                prefix_path(algorithm, options, right, n - k, m - k, index - k, path)
+            }
+        }
+
+        View::Leaf { .. } => Some(()),
+
+        View::Empty { .. } | View::Pruned { .. } => None,
+    }
+}
+
+/// Returns the root hash of the tree over the first `m` leaves of `tree`, which has `n` leaves,
+/// both trees being built by promoting the odd nodes, only hashing the nodes over leaves past
+/// the `m` first ones anew. Fails if one of them is pruned.
+fn prefix_hash<T>(
+    algorithm: &'static Algorithm,
+    options: &TreeOptions<T>,
+    tree: Subtree<T>,
+    n: usize,
+    m: usize,
+) -> Option<Vec<u8>> {
+
+    if m == n {
+        return Some(tree.hash().to_vec());
+    }
+
+    match tree.view() {
+        View::Node { left, right, .. } => {
+            let k = tree::left_count(right, n);
+
+            if m <= k {
+                return prefix_hash(algorithm, options, left, k, m);
+            }
+
+            let right = prefix_hash(algorithm, options, right, n - k, m - k)?;
+            Some(options.hash_subtree(algorithm, &left.hash().to_vec(), &right, m as u64).as_ref().to_vec())
+        }
+
+        _ => None,
+    }
+}
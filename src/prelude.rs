@@ -0,0 +1,65 @@
+//! The types and functions most code using trees needs, brought in with a single `use` line.
+//!
+//! Along with the trees and proofs, it provides the hashing algorithms as the
+//! `&'static Algorithm` trees are built with, such as `SHA256`, so that no backend crate
+//! has to be imported: they are also available at the root of the crate, as `merkle::SHA256`.
+//!
+//! ## Example
+//!
+//! ```
+//! # extern crate merkle;
+//! use merkle::prelude::*;
+//!
+//! # fn main() { run().unwrap() }
+//! # fn run() -> Result<(), Error> {
+//! let tree = MerkleTree::from_vec(SHA256, vec!["alpha", "beta", "gamma"]);
+//!
+//! let proof = tree.gen_proof("beta").unwrap();
+//! assert!(proof.validate(tree.root_hash()));
+//!
+//! // Proofs travel as data, naming the algorithm of the tree
+//! let data: ProofData<&str> = proof.into_data();
+//! assert_eq!(algorithm_name(SHA256), "SHA-256");
+//!
+//! let proof: Proof<&str> = data.into_named_proof()?;
+//! assert!(proof.validate(tree.root_hash()));
+//!
+//! // Each lemma holds the sibling of the node of the next one, from the root down to the value
+//! let sides = proof
+//!     .lemmas()
+//!     .filter_map(|lemma: &Lemma| lemma.sibling_hash.as_ref())
+//!     .map(|sibling| match *sibling {
+//!         Positioned::Left(_) => "left",
+//!         Positioned::Right(_) => "right",
+//!     })
+//!     .collect::<Vec<_>>();
+//!
+//! assert_eq!(sides, ["right", "left"]);
+//! # Ok(())
+//! # }
+//! ```
+
+use digest;
+
+pub use digest::Algorithm;
+pub use error::Error;
+pub use hashutils::Hashable;
+pub use merkletree::MerkleTree;
+pub use builder::MerkleTreeBuilder;
+pub use proof::{Proof, ProofData, Lemma, Positioned};
+pub use registry::{algorithm_name, algorithm_from_name, register_algorithm};
+
+/// SHA-1, which is only fit to check trees built with it: it is not collision resistant.
+pub static SHA1: &'static Algorithm = &digest::SHA1;
+
+/// SHA-256.
+pub static SHA256: &'static Algorithm = &digest::SHA256;
+
+/// SHA-384.
+pub static SHA384: &'static Algorithm = &digest::SHA384;
+
+/// SHA-512.
+pub static SHA512: &'static Algorithm = &digest::SHA512;
+
+/// SHA-512/256, SHA-512 truncated to 256 bits.
+pub static SHA512_256: &'static Algorithm = &digest::SHA512_256;
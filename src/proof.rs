@@ -1,20 +1,57 @@
+#![deny(clippy::indexing_slicing, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
 
 use std::hash::{Hash, Hasher};
-use std::cmp::Ordering;
+use std::cmp::{self, Ordering};
+use std::convert::TryFrom;
+use std::mem;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+
+use digest::{Algorithm, Context, Digest};
 
-use ring::digest::Algorithm;
+use tree::{self, Subtree, View};
+use error::{Error, HashError, ConversionError};
+use hasher::{self, MerkleHasher};
+use hashutils::{Hashable, TryHashable};
+use hash::{AlgorithmTag, RootHash};
+use options::{HashingScheme, OddLeafPolicy};
+use policy::ValidationPolicy;
+use index;
 
-use tree::Tree;
-use hashutils::HashUtils;
+/// The maximum height of a `MerkleTree`, and thus the maximum number of sub lemmas
+/// in the lemma chain of a valid `Proof`: a tree with at most `usize::MAX` leaves
+/// is at most 64 levels high.
+pub const MAX_TREE_DEPTH: usize = 64;
 
 /// An inclusion proof represent the fact that a `value` is a member
 /// of a `MerkleTree` with root hash `root_hash`, and hash function `algorithm`.
+///
+/// When serialized, the `algorithm` and the `hasher` are skipped, the `tree_size` and `leaf_index`
+/// are skipped if unknown, the `scheme` is skipped if it is the default one, and the `odd_leaf_policy`
+/// is skipped, which makes the output that of the corresponding `ProofData` without
+/// an algorithm. See `Proof::serialize_tagged` for the form which names the algorithm.
+///
+/// Proofs are compared and hashed by their `root_hash`, `lemma`, `value`, `scheme` and
+/// `leaf_counts`, which are what the root hash is computed from. The `tree_size` and
+/// `leaf_index` are left out, as hints which the wire, Protobuf and canonical forms do not
+/// carry, so that a proof equals its round trip through any of them, and so are the
+/// `algorithm`, `hasher` and `odd_leaf_policy`, which are not serialized at all.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize))]
 pub struct Proof<T> {
     /// The hashing algorithm used in the original `MerkleTree`
+    #[cfg_attr(feature = "serialization-serde", serde(skip))]
     pub algorithm: &'static Algorithm,
 
+    /// The hasher of the original `MerkleTree`, if it has one of its own, see
+    /// `MerkleTreeBuilder::hasher`, which the node hashes are recomputed with rather than
+    /// with the bare `algorithm`
+    #[cfg_attr(feature = "serialization-serde", serde(skip))]
+    pub hasher: Option<Arc<dyn MerkleHasher>>,
+
     /// The hash of the root of the original `MerkleTree`
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
     pub root_hash: Vec<u8>,
 
     /// The first `Lemma` of the `Proof`
@@ -22,11 +59,41 @@ pub struct Proof<T> {
 
     /// The value concerned by this `Proof`
     pub value: T,
+
+    /// The way the nodes of the original `MerkleTree` are hashed
+    #[cfg_attr(feature = "serialization-serde", serde(skip_serializing_if = "HashingScheme::is_default"))]
+    pub scheme: HashingScheme,
+
+    /// The number of leaves of the original `MerkleTree`, if known
+    #[cfg_attr(feature = "serialization-serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub tree_size: Option<u64>,
+
+    /// The index of the leaf holding the value in the original `MerkleTree`, if known
+    #[cfg_attr(feature = "serialization-serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub leaf_index: Option<u64>,
+
+    /// What the original `MerkleTree` does with odd nodes, which gives the position of its leaves
+    #[cfg_attr(feature = "serialization-serde", serde(skip))]
+    pub odd_leaf_policy: OddLeafPolicy,
+
+    /// The number of leaves beneath each node of the lemma chain which has a sub lemma,
+    /// from the root down, for the proofs of trees built with `MerkleTreeBuilder::leaf_counts`,
+    /// whose node hashes commit to them, see `Proof::leaf_count`
+    #[cfg_attr(feature = "serialization-serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub leaf_counts: Option<Vec<u64>>,
 }
 
+// The hasher is only ever called through shared references, and a proof keeps none of its state.
+impl<T: UnwindSafe> UnwindSafe for Proof<T> {}
+impl<T: RefUnwindSafe> RefUnwindSafe for Proof<T> {}
+
 impl<T: PartialEq> PartialEq for Proof<T> {
     fn eq(&self, other: &Proof<T>) -> bool {
-        self.root_hash == other.root_hash && self.lemma == other.lemma && self.value == other.value
+        self.root_hash == other.root_hash
+            && self.lemma == other.lemma
+            && self.value == other.value
+            && self.scheme == other.scheme
+            && self.leaf_counts == other.leaf_counts
     }
 }
 
@@ -44,6 +111,8 @@ impl<T: Ord> Ord for Proof<T> {
             .cmp(&other.root_hash)
             .then(self.value.cmp(&other.value))
             .then_with(|| self.lemma.cmp(&other.lemma))
+            .then(self.scheme.cmp(&other.scheme))
+            .then_with(|| self.leaf_counts.cmp(&other.leaf_counts))
     }
 }
 
@@ -52,84 +121,1330 @@ impl<T: Hash> Hash for Proof<T> {
         self.root_hash.hash(state);
         self.lemma.hash(state);
         self.value.hash(state);
+        self.scheme.hash(state);
+        self.leaf_counts.hash(state);
     }
 }
 
 impl<T> Proof<T> {
-    /// Constructs a new `Proof`
+    /// Constructs a new `Proof`, for a tree of unknown size built with the default
+    /// `HashingScheme` and `OddLeafPolicy`
     pub fn new(algo: &'static Algorithm, root_hash: Vec<u8>, lemma: Lemma, value: T) -> Self {
         Proof {
             algorithm: algo,
+            hasher: None,
             root_hash: root_hash,
             lemma: lemma,
             value: value,
+            scheme: HashingScheme::default(),
+            tree_size: None,
+            leaf_index: None,
+            odd_leaf_policy: OddLeafPolicy::default(),
+            leaf_counts: None,
+        }
+    }
+
+    /// Drops the `OddLeafPolicy` and the hasher, leaving the serializable `ProofData`,
+    /// which only names the hashing algorithm.
+    pub fn into_data(self) -> ProofData<T> {
+        ProofData {
+            algorithm: Some(AlgorithmTag::of(self.algorithm)),
+            scheme: self.scheme,
+            root_hash: self.root_hash,
+            lemma: self.lemma,
+            value: self.value,
+            tree_size: self.tree_size,
+            leaf_index: self.leaf_index,
+            leaf_counts: self.leaf_counts,
+        }
+    }
+
+    /// Returns the hasher the node hashes of this proof are recomputed with: the `hasher`
+    /// of the original tree, or its bare `algorithm` if it has none.
+    pub fn hasher(&self) -> &dyn MerkleHasher {
+        hasher::or_algorithm(&self.hasher, &self.algorithm)
+    }
+
+    /// Returns an iterator over the lemma chain of this proof, from the root down to the leaf.
+    /// See `Lemma::iter`.
+    pub fn lemmas(&self) -> LemmaIter {
+        self.lemma.iter()
+    }
+
+    /// Returns the depth of the leaf below the root. See `Lemma::depth`.
+    pub fn depth(&self) -> usize {
+        self.lemma.depth()
+    }
+
+    /// Returns the hash of the leaf. See `Lemma::leaf_hash`.
+    pub fn leaf_hash(&self) -> &[u8] {
+        self.lemma.leaf_hash()
+    }
+
+    /// Returns an iterator over the sibling hashes, from the root down to the leaf.
+    /// See `Lemma::siblings`.
+    pub fn siblings(&self) -> impl Iterator<Item = &Positioned<Vec<u8>>> {
+        self.lemma.siblings()
+    }
+
+    /// Checks whether this proof and `other` have the same root hash and the same lemma chain,
+    /// whatever their values, which `PartialEq` compares as well.
+    pub fn same_path<U>(&self, other: &Proof<U>) -> bool {
+        self.root_hash == other.root_hash && self.lemma == other.lemma
+    }
+
+    /// Orders this proof and `other` by their root hash, then by their lemma chain,
+    /// leaving their values out, unlike `Ord`, which compares the values before the lemma chains.
+    pub fn path_cmp<U>(&self, other: &Proof<U>) -> Ordering {
+        self.root_hash.cmp(&other.root_hash).then_with(|| self.lemma.cmp(&other.lemma))
+    }
+
+    /// Returns a fingerprint of the root hash and the lemma chain of this proof, hashed
+    /// with its algorithm, the same for two proofs if and only if `Proof::same_path` holds,
+    /// so that proofs can be told apart by path in a `HashMap`.
+    pub fn path_key(&self) -> Vec<u8> {
+        // Every hash is prefixed with its length, and every sibling with its side,
+        // so that no two paths hash the same bytes
+        fn update(context: &mut Context, hash: &[u8]) {
+            context.update(&(hash.len() as u64).to_be_bytes());
+            context.update(hash);
+        }
+
+        let mut context = Context::new(self.algorithm);
+        update(&mut context, &self.root_hash);
+
+        for lemma in self.lemmas() {
+            update(&mut context, &lemma.node_hash);
+
+            match lemma.sibling_hash {
+                None => context.update(&[0]),
+                Some(Positioned::Left(ref hash)) => {
+                    context.update(&[1]);
+                    update(&mut context, hash);
+                }
+                Some(Positioned::Right(ref hash)) => {
+                    context.update(&[2]);
+                    update(&mut context, hash);
+                }
+            }
+        }
+
+        context.finish().as_ref().to_vec()
+    }
+
+    /// Drops the hashing algorithm and the node hashes, leaving the serializable
+    /// `CanonicalProofData`.
+    pub fn into_canonical_data(self) -> CanonicalProofData<T> {
+        let mut siblings = Vec::new();
+        let mut lemma = self.lemma;
+
+        while let (Some(sibling), Some(sub_lemma)) = (lemma.sibling_hash, lemma.sub_lemma) {
+            siblings.push(sibling);
+            lemma = *sub_lemma;
+        }
+
+        CanonicalProofData {
+            root_hash: self.root_hash,
+            leaf_hash: lemma.node_hash,
+            siblings: siblings,
+            value: self.value,
         }
     }
 
     /// Checks whether this inclusion proof is well-formed,
     /// and whether its root hash matches the given `root_hash`.
+    ///
+    /// Every node hash of the lemma chain must be the one recomputed from the level below
+    /// and the sibling hash, so that a proof has a single valid form.
+    ///
+    /// Proofs whose lemma chain has more than `MAX_TREE_DEPTH` sub lemmas are rejected
+    /// upfront, as no tree can produce them, and so are proofs with a hash which does not
+    /// have the output length of the algorithm, see `Proof::check_algorithm`.
+    ///
+    /// Checking hashes alone does not bound the depth of the proof: without domain separation,
+    /// a lemma chain padded with levels below its leaf still holds. Validate against the size
+    /// of the tree, when known, to reject it, see `ValidationPolicy::strict`.
+    ///
+    /// The node hashes are recomputed with the hasher of this proof, see `Proof::hasher`.
     pub fn validate(&self, root_hash: &[u8]) -> bool {
-        if self.root_hash != root_hash || self.lemma.node_hash != root_hash {
+        self.validate_with(self.hasher(), root_hash)
+    }
+
+    /// Works like `Proof::validate`, recomputing the node hashes with the given `hasher`,
+    /// which must be the one the tree was built with, see `MerkleTreeBuilder::hasher`,
+    /// rather than with the hasher of this proof, which a deserialized proof does not hold.
+    ///
+    /// Fails if the algorithm of `hasher` is not the one of this proof.
+    pub fn validate_with(&self, hasher: &dyn MerkleHasher, root_hash: &[u8]) -> bool {
+        self.check_policy(hasher, root_hash, &ValidationPolicy::default()).is_ok()
+    }
+
+    /// Checks whether this inclusion proof is valid for the given `root_hash`, as with
+    /// `Proof::validate`, which validates with the default policy, and whether it meets
+    /// the given `policy`.
+    ///
+    /// Fails with `Error::InvalidProof` if the proof does not hold, or if the policy requires
+    /// checking its value and the lemma chain does not start from its hash, with
+    /// `Error::AlgorithmMismatch` and `Error::HashLengthMismatch` as `Proof::verify` does,
+    /// with `Error::PolicyViolation` if it is deeper than the policy allows, is malformed
+    /// or deeper than any tree of the size a strict policy allows, does not have
+    /// the depth the policy requires, or does not lead to the leaf at its `leaf_index` in a tree
+    /// of the size the policy requires, and with `Error::SchemeMismatch` if it is not hashed
+    /// with the scheme the policy requires, which is checked before anything is hashed.
+    pub fn validate_with_policy(&self, root_hash: &[u8], policy: &ValidationPolicy) -> Result<(), Error>
+    where
+        T: Hashable,
+    {
+
+        match policy.required_scheme {
+            Some(scheme) if scheme != self.scheme => {
+                return Err(Error::SchemeMismatch {
+                    proof: self.scheme,
+                    verifier: scheme,
+                });
+            }
+            _ => {}
+        }
+
+        if policy.require_value_check {
+            let leaf_hash = self.scheme.hash_leaf_with(self.hasher(), &self.value);
+
+            if !policy.equal(leaf_hash.as_ref(), self.lemma.leaf_hash()) {
+                return Err(Error::InvalidProof);
+            }
+        }
+
+        self.check_policy(self.hasher(), root_hash, policy)
+    }
+
+    /// Checks this proof against `root_hash` as `Proof::validate_with_policy` does,
+    /// leaving its value out, recomputing the node hashes with the given `hasher`.
+    pub(crate) fn check_policy(
+        &self,
+        hasher: &dyn MerkleHasher,
+        root_hash: &[u8],
+        policy: &ValidationPolicy,
+    ) -> Result<(), Error> {
+
+        if !(policy.equal(&self.root_hash, root_hash) & policy.equal(&self.lemma.node_hash, root_hash)) {
+            return Err(Error::InvalidProof);
+        }
+
+        if AlgorithmTag::of(hasher.algorithm()) != AlgorithmTag::of(self.algorithm) {
+            return Err(Error::AlgorithmMismatch);
+        }
+
+        if self.lemma.depth_exceeds(policy.max_depth) {
+            return Err(Error::PolicyViolation("path is deeper than the maximum depth"));
+        }
+
+        self.check_algorithm()?;
+
+        if let Some(leaf_count) = policy.strict_leaf_count {
+            self.check_minimal(leaf_count)?;
+        }
+
+        if policy.required_depth.map_or(false, |depth| depth != self.depth()) {
+            return Err(Error::PolicyViolation("path does not have the required depth"));
+        }
+
+        if let Some(tree_size) = policy.tree_size {
+            self.check_leaf_index(tree_size)?;
+        }
+
+        let counts = self.leaf_counts.as_deref();
+        let (computed, nodes_hold) = self
+            .fold_lemma(hasher, policy, &self.lemma, counts)
+            .map_err(|_| Error::InvalidProof)?;
+        let computed = computed.as_ref().map_or(self.lemma.node_hash.as_slice(), Digest::as_ref);
+
+        if !(policy.equal(computed, root_hash) & nodes_hold) {
+            return Err(Error::InvalidProof);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the root hash the lemma chain of this proof leads to, recomputed from the hash
+    /// of its leaf up with the siblings, without comparing it to any root hash, nor to the node
+    /// hashes of the chain, so that a proof can be checked once the expected root hash is known.
+    /// The value is not hashed, see `Proof::validate_value`.
+    ///
+    /// `Proof::validate` holds for exactly the root hash this returns, provided that the root
+    /// hash and the node hashes of this proof are the recomputed ones.
+    ///
+    /// Only fails if the proof is malformed: with `Error::HashLengthMismatch` as
+    /// `Proof::check_algorithm` does, and with `Error::InvalidPath` if the lemma chain has more
+    /// than `MAX_TREE_DEPTH` sub lemmas, if a lemma has a sub lemma without a sibling or the other
+    /// way around, or if the leaf counts of the proof do not fit the chain.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::MerkleTree;
+    /// # fn main() {
+    /// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c"]);
+    /// let proof = tree.gen_nth_proof(2).unwrap();
+    ///
+    /// assert_eq!(proof.compute_root().unwrap(), *tree.root_hash());
+    /// # }
+    /// ```
+    pub fn compute_root(&self) -> Result<Vec<u8>, Error> {
+        if self.lemma.depth_exceeds(MAX_TREE_DEPTH) {
+            return Err(Error::InvalidPath("path is deeper than any tree"));
+        }
+
+        self.check_algorithm()?;
+
+        let counts = self.leaf_counts.as_deref();
+        let (root_hash, _) = self.fold_lemma(self.hasher(), &ValidationPolicy::default(), &self.lemma, counts)?;
+
+        Ok(root_hash.map_or_else(|| self.lemma.node_hash.clone(), |root_hash| root_hash.as_ref().to_vec()))
+    }
+
+    /// Checks whether this inclusion proof is valid for the given `root_hash`, as with
+    /// `Proof::validate`, and whether its lemma chain starts from the hash of its value,
+    /// hashed with the `scheme` of this proof.
+    pub fn validate_value(&self, root_hash: &[u8]) -> bool
+    where
+        T: Hashable,
+    {
+
+        self.try_validate_value(root_hash).unwrap_or(false)
+    }
+
+    /// Works like `Proof::validate_value`, but fails if the value cannot be hashed,
+    /// see `TryHashable`.
+    pub fn try_validate_value(&self, root_hash: &[u8]) -> Result<bool, HashError>
+    where
+        T: TryHashable,
+    {
+
+        let leaf_hash = self.scheme.try_hash_leaf_with(self.hasher(), &self.value)?;
+
+        Ok(leaf_hash.as_ref() == self.lemma.leaf_hash() && self.validate(root_hash))
+    }
+
+    /// Returns the number of leaves of the original `MerkleTree`, as committed to by its root
+    /// hash if the tree was built with `MerkleTreeBuilder::leaf_counts`, or `None` if this proof
+    /// has no `leaf_counts`.
+    ///
+    /// This is only the size of the tree once the proof is validated, see `Proof::validate`.
+    pub fn leaf_count(&self) -> Option<u64> {
+        self.leaf_counts.as_ref().map(|counts| counts.first().map_or(1, |&count| count))
+    }
+
+    /// Checks that the lemma chain is no deeper than the deepest leaf of a tree of `leaf_count`
+    /// leaves, and that every lemma has a sibling exactly when it has a sub lemma, as required
+    /// by `ValidationPolicy::strict`.
+    fn check_minimal(&self, leaf_count: u64) -> Result<(), Error> {
+        if leaf_count == 0 {
+            return Err(Error::PolicyViolation("no proof holds for an empty tree"));
+        }
+
+        let max_depth = (u64::BITS - (leaf_count - 1).leading_zeros()) as usize;
+
+        if self.lemma.depth_exceeds(max_depth) {
+            return Err(Error::PolicyViolation("path is deeper than any tree of the given size"));
+        }
+
+        if self.lemmas().any(|lemma| lemma.sibling_hash.is_some() != lemma.sub_lemma.is_some()) {
+            return Err(Error::PolicyViolation("lemma chain is malformed"));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the sides of the siblings of the lemma chain lead to a leaf of a tree
+    /// of `tree_size` leaves, which is the one at `leaf_index` if known, as required by
+    /// `ValidationPolicy::tree_size`.
+    fn check_leaf_index(&self, tree_size: u64) -> Result<(), Error> {
+        if self.tree_size.map_or(false, |size| size != tree_size) {
+            return Err(Error::PolicyViolation("proof is not from a tree of the required size"));
+        }
+
+        match self.lemma.leaf_index(index::to_index(tree_size)?, self.odd_leaf_policy) {
+            None => Err(Error::PolicyViolation("path does not lead to a leaf of a tree of the required size")),
+            Some(index) if self.leaf_index.map_or(false, |claimed| claimed != index as u64) => {
+                Err(Error::PolicyViolation("path does not lead to the claimed leaf index"))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Checks whether this inclusion proof is valid for the given `root_hash`, as with
+    /// `Proof::validate`, and whether it is the proof of a leaf of a tree with `expected_size`
+    /// leaves, built with the `odd_leaf_policy` of this proof.
+    ///
+    /// The depth of the lemma chain and the side of every sibling must be those of the path
+    /// to a leaf of such a tree, and the `tree_size` and `leaf_index` of this proof, if known,
+    /// must be `expected_size` and the index of that leaf. See `Lemma::leaf_index`.
+    pub fn validate_against(&self, root_hash: &[u8], expected_size: u64) -> bool {
+        if self.tree_size.is_some_and(|size| size != expected_size) {
             return false;
         }
 
-        self.validate_lemma(&self.lemma)
+        let index = match usize::try_from(expected_size) {
+            Ok(size) => self.lemma.leaf_index(size, self.odd_leaf_policy),
+            Err(_) => None,
+        };
+
+        match index {
+            Some(index) if self.leaf_index.is_none_or(|claimed| claimed == index as u64) => {
+                self.validate(root_hash)
+            }
+
+            _ => false,
+        }
     }
 
-    fn validate_lemma(&self, lemma: &Lemma) -> bool {
-        match lemma.sub_lemma {
+    /// Checks whether this inclusion proof is valid for the given `root_hash`, as with
+    /// `Proof::validate`, and whether its lemma chain ends at the depth of a leaf of a tree
+    /// of height `tree_height`, built with the `odd_leaf_policy` of this proof.
+    ///
+    /// Without `HashingScheme::DomainSeparated`, an internal node is hashed as a leaf whose value
+    /// is the concatenation of the hashes of its children, so that a proof which stops at an
+    /// internal node still hashes up to the root. See `Lemma::leaf_depths` for the depths
+    /// of the leaves of a tree: only its size tells the depth of every leaf,
+    /// see `Proof::validate_against`.
+    ///
+    /// Fails with `Error::InvalidPath` if the lemma chain does not have such a depth,
+    /// with `Error::HashLengthMismatch` if a hash of the proof does not have the output length
+    /// of its algorithm, and with `Error::InvalidProof` if the proof does not hold.
+    pub fn validate_with_height(&self, root_hash: &[u8], tree_height: usize) -> Result<(), Error> {
+        let (min, max) = self.lemma.leaf_depths(tree_height, self.odd_leaf_policy);
+        let depth = self.depth();
 
-            None => lemma.sibling_hash.is_none(),
+        if depth < min || depth > max {
+            return Err(Error::InvalidPath("path does not end at the depth of a leaf of the tree"));
+        }
 
-            Some(ref sub) => {
-                match lemma.sibling_hash {
-                    None => false,
+        self.check_algorithm()?;
 
-                    Some(Positioned::Left(ref hash)) => {
-                        let combined = self.algorithm.hash_nodes(hash, &sub.node_hash);
-                        let hashes_match = combined.as_ref() == lemma.node_hash.as_slice();
-                        hashes_match && self.validate_lemma(sub)
-                    }
+        if !self.validate(root_hash) {
+            return Err(Error::InvalidProof);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the root hash, and every node hash and sibling hash of the lemma chain,
+    /// have the output length of the hashing algorithm of this proof, without hashing anything.
+    ///
+    /// Fails with `Error::HashLengthMismatch` on the first hash which does not, from the root
+    /// down, which is typically the case of a proof combined with the wrong algorithm.
+    pub fn check_algorithm(&self) -> Result<(), Error> {
+        let expected = self.algorithm.output_len;
+
+        let check = |hash: &[u8]| if hash.len() == expected {
+            Ok(())
+        } else {
+            Err(Error::HashLengthMismatch {
+                expected: expected,
+                found: hash.len(),
+            })
+        };
+
+        check(&self.root_hash)?;
+
+        for lemma in self.lemmas() {
+            check(&lemma.node_hash)?;
+
+            if let Some(ref sibling) = lemma.sibling_hash {
+                check(sibling.as_ref().into_inner())?;
+            }
+        }
+
+        Ok(())
+    }
 
-                    Some(Positioned::Right(ref hash)) => {
-                        let combined = self.algorithm.hash_nodes(&sub.node_hash, hash);
-                        let hashes_match = combined.as_ref() == lemma.node_hash.as_slice();
-                        hashes_match && self.validate_lemma(sub)
-                    }
+    /// Checks this inclusion proof against the given root hash, like `Proof::validate`.
+    ///
+    /// Fails with `Error::AlgorithmMismatch` if `root_hash` was computed with another algorithm
+    /// than this proof, with `Error::HashLengthMismatch` if a hash of the proof does not have
+    /// the length of its output (see `Proof::check_algorithm`), and with `Error::InvalidProof`
+    /// if the proof does not hold.
+    pub fn verify(&self, root_hash: &RootHash) -> Result<(), Error> {
+        if root_hash.algorithm() != AlgorithmTag::of(self.algorithm) {
+            return Err(Error::AlgorithmMismatch);
+        }
+
+        self.check_algorithm()?;
+
+        if !self.validate(root_hash.as_bytes()) {
+            return Err(Error::InvalidProof);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the index of the leaf this proof is about, along with its audit path:
+    /// the sibling hashes from the leaf up to the root, as in the inclusion proofs
+    /// of Certificate Transparency (RFC 6962).
+    ///
+    /// The index is the `leaf_index` of this proof, or the one derived from its `tree_size`
+    /// (see `Lemma::leaf_index`). Fails with `Error::MissingField` if neither is known,
+    /// with `Error::InvalidPath` if the lemma chain is not the path to that leaf
+    /// of a tree of `tree_size` leaves, and with `Error::IndexOverflow` if `tree_size`
+    /// does not fit in a `usize`.
+    pub fn to_indexed_path(&self) -> Result<(u64, Vec<Vec<u8>>), Error> {
+        let derived = match self.tree_size {
+            Some(size) => {
+                let size = index::to_index(size)?;
 
+                match self.lemma.leaf_index(size, self.odd_leaf_policy) {
+                    Some(index) => Some(index as u64),
+                    None => return Err(Error::InvalidPath("not the path to a leaf of the tree")),
                 }
             }
+
+            None => None,
+        };
+
+        let index = match (self.leaf_index, derived) {
+            (Some(claimed), Some(index)) if claimed != index => {
+                return Err(Error::InvalidPath("leaf index does not match the path"));
+            }
+
+            (Some(index), _) | (None, Some(index)) => index,
+            (None, None) => return Err(Error::MissingField("leaf_index")),
+        };
+
+        let mut path: Vec<Vec<u8>> = self.siblings()
+            .map(|sibling| sibling.as_ref().into_inner().clone())
+            .collect();
+
+        path.reverse();
+
+        Ok((index, path))
+    }
+
+    /// Builds the proof that `value` is the leaf at the given `index` of a tree of `tree_size`
+    /// leaves, from its audit path: the sibling hashes from the leaf up to the root,
+    /// as returned by `Proof::to_indexed_path`.
+    ///
+    /// The tree is assumed to have the shape of one built with the default options, which is
+    /// that of Certificate Transparency (RFC 6962), so that the side of every sibling follows
+    /// from `index` and `tree_size`.
+    ///
+    /// Fails with `Error::InvalidPath` if `index` is not that of a leaf of the tree, or if `path`
+    /// does not have the length of the path to that leaf, with `Error::HashLengthMismatch`
+    /// if a hash does not have the output length of `algorithm`, and with `Error::IndexOverflow`
+    /// if `index` or `tree_size` does not fit in a `usize`. The resulting proof still has
+    /// to be checked against `root_hash`, with `Proof::validate`.
+    pub fn from_indexed_path(
+        algorithm: &'static Algorithm,
+        root_hash: Vec<u8>,
+        tree_size: u64,
+        index: u64,
+        path: Vec<Vec<u8>>,
+        value: T,
+    ) -> Result<Proof<T>, Error>
+    where
+        T: Hashable,
+    {
+
+        let policy = OddLeafPolicy::default();
+        let scheme = HashingScheme::default();
+
+        let sides = sibling_sides(index::to_index(index)?, index::to_index(tree_size)?, policy);
+        let sides = sides.ok_or(Error::InvalidPath("index is out of the bounds of the tree"))?;
+
+        if path.len() != sides.len() {
+            return Err(Error::InvalidPath("path does not have the depth of the leaf"));
+        }
+
+        let siblings = sides
+            .into_iter()
+            .zip(path.into_iter().rev())
+            .map(|(side, hash)| Positioned::new(side, hash))
+            .collect();
+
+        let leaf_hash = scheme.hash_leaf(algorithm, &value).as_ref().into();
+        let lemma = Lemma::from_siblings(algorithm, scheme, leaf_hash, siblings);
+
+        let mut proof = Proof::new(algorithm, root_hash, lemma, value);
+        proof.scheme = scheme;
+        proof.odd_leaf_policy = policy;
+        proof.tree_size = Some(tree_size);
+        proof.leaf_index = Some(index);
+
+        proof.check_algorithm()?;
+
+        Ok(proof)
+    }
+
+    /// Returns the sibling hashes of this proof from the leaf up to the root, without their
+    /// sides, as taken by the `MerkleProof` library of OpenZeppelin.
+    ///
+    /// This is only a proof for trees built with `HashingScheme::SortedPairs`,
+    /// to be checked with `validate_sorted_path`.
+    pub fn sorted_path(&self) -> Vec<Vec<u8>> {
+        let mut path: Vec<Vec<u8>> = self.siblings()
+            .map(|sibling| sibling.as_ref().into_inner().clone())
+            .collect();
+
+        path.reverse();
+        path
+    }
+
+    /// Recomputes the node hash of `lemma` from the hash of the leaf of its chain up, the first
+    /// of `counts`, if any, being the number of leaves beneath its node, which must be at least
+    /// the number beneath the node of its sub lemma. Returns the recomputed hash, which is none
+    /// for a leaf, along with whether every node hash of the chain is the recomputed one.
+    ///
+    /// Every level is hashed and compared, so that the first one which does not hold
+    /// is not given away. Fails with `Error::InvalidPath` if the chain is malformed,
+    /// see `Proof::compute_root`.
+    fn fold_lemma(
+        &self,
+        hasher: &dyn MerkleHasher,
+        policy: &ValidationPolicy,
+        lemma: &Lemma,
+        counts: Option<&[u64]>,
+    ) -> Result<(Option<Digest>, bool), Error> {
+
+        let (sub, sibling) = match (lemma.sub_lemma.as_ref(), lemma.sibling_hash.as_ref()) {
+            (None, None) if counts.map_or(true, |counts| counts.is_empty()) => return Ok((None, true)),
+            (None, None) => return Err(Error::InvalidPath("leaf counts do not match the depth of the path")),
+            (Some(sub), Some(sibling)) => (sub, sibling),
+            (None, Some(_)) => return Err(Error::InvalidPath("leaf has a sibling but no sub lemma")),
+            (Some(_), None) => return Err(Error::InvalidPath("lemma has a sub lemma but no sibling")),
+        };
+
+        let (count, sub_counts) = match counts.map(|counts| counts.split_first()) {
+            None => (None, None),
+            Some(None) => return Err(Error::InvalidPath("leaf counts do not match the depth of the path")),
+            Some(Some((&count, sub_counts))) => {
+                if count < sub_counts.first().map_or(1, |&sub_count| sub_count) {
+                    return Err(Error::InvalidPath("node has fewer leaves than its child"));
+                }
+
+                (Some(count), Some(sub_counts))
+            }
+        };
+
+        let (sub_hash, sub_holds) = self.fold_lemma(hasher, policy, sub, sub_counts)?;
+        let sub_hash = sub_hash.as_ref().map_or(sub.node_hash.as_slice(), Digest::as_ref);
+
+        let parent = match count {
+            Some(count) => sibling.hash_counted_parent(hasher, self.scheme, sub_hash, count),
+            None => sibling.hash_parent(hasher, self.scheme, sub_hash),
+        };
+
+        let holds = sub_holds & policy.equal(parent.as_ref(), &lemma.node_hash);
+
+        Ok((Some(parent), holds))
+    }
+}
+
+impl<'a, T: ToOwned + ?Sized> Proof<&'a T> {
+    /// Returns a copy of this proof which owns its value, such as a `Proof<Vec<u8>>` for the proof
+    /// of a leaf of a tree built with `MerkleTree::from_slices`, so that it outlives the slices.
+    pub fn to_owned_value(&self) -> Proof<T::Owned> {
+        Proof {
+            algorithm: self.algorithm,
+            hasher: self.hasher.clone(),
+            root_hash: self.root_hash.clone(),
+            lemma: self.lemma.clone(),
+            value: self.value.to_owned(),
+            scheme: self.scheme,
+            tree_size: self.tree_size,
+            leaf_index: self.leaf_index,
+            odd_leaf_policy: self.odd_leaf_policy,
+            leaf_counts: self.leaf_counts.clone(),
+        }
+    }
+}
+
+/// The contents of a `Proof`, without the hashing algorithm.
+///
+/// This is the form in which proofs are (de)serialized with serde, the algorithm being
+/// supplied back when calling `into_proof`, or looked up by name with `into_named_proof`.
+/// A name which is neither known nor registered with `register_algorithm` fails to deserialize,
+/// and data serialized without a name is deserialized without an algorithm.
+///
+/// ## Borsh encoding
+///
+/// With the `serialization-borsh` feature, a `ProofData` is encoded as its fields in order,
+/// save for the `algorithm`, the `scheme`, the `tree_size`, the `leaf_index` and the `leaf_counts`,
+/// which are not part of the encoding, and absent once decoded, the scheme being the default one:
+///
+/// - `root_hash`: a little-endian `u32` length, followed by the hash bytes;
+/// - `lemma`: the `Lemma` encoding;
+/// - `value`: the borsh encoding of `T`.
+///
+/// A `Lemma` is encoded as:
+///
+/// - `node_hash`: a little-endian `u32` length, followed by the hash bytes;
+/// - `sibling_hash`: `0u8` if absent, otherwise `1u8` followed by the `Positioned` encoding;
+/// - `sub_lemma`: `0u8` if absent, otherwise `1u8` followed by the `Lemma` encoding,
+///   with at most `BORSH_MAX_LEMMA_DEPTH` nested sub lemmas accepted on decode.
+///
+/// A `Positioned<Vec<u8>>` is encoded as `0u8` for `Left` or `1u8` for `Right`,
+/// followed by a little-endian `u32` length and the hash bytes.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub struct ProofData<T> {
+    /// The hashing algorithm used in the original `MerkleTree`, if known
+    #[cfg_attr(feature = "serialization-serde", serde(default))]
+    pub algorithm: Option<AlgorithmTag>,
+
+    /// The way the nodes of the original `MerkleTree` are hashed, only serialized if it is
+    /// not the default one, so that data serialized without it, as it was before it was
+    /// recorded, is read as hashed with the default `HashingScheme::DomainSeparated`
+    #[cfg_attr(
+        feature = "serialization-serde",
+        serde(default, skip_serializing_if = "HashingScheme::is_default")
+    )]
+    pub scheme: HashingScheme,
+
+    /// The hash of the root of the original `MerkleTree`
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    #[cfg_attr(feature = "schemars", schemars(with = "::schema::Hash"))]
+    pub root_hash: Vec<u8>,
+
+    /// The first `Lemma` of the `Proof`
+    pub lemma: Lemma,
+
+    /// The value concerned by this `Proof`
+    pub value: T,
+
+    /// The number of leaves of the original `MerkleTree`, if known
+    #[cfg_attr(feature = "serialization-serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub tree_size: Option<u64>,
+
+    /// The index of the leaf holding the value in the original `MerkleTree`, if known
+    #[cfg_attr(feature = "serialization-serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub leaf_index: Option<u64>,
+
+    /// The number of leaves beneath the nodes of the lemma chain, for the proofs of trees
+    /// built with `MerkleTreeBuilder::leaf_counts`, see `Proof::leaf_counts`
+    #[cfg_attr(feature = "serialization-serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub leaf_counts: Option<Vec<u64>>,
+}
+
+impl<T> ProofData<T> {
+    /// Constructs a `Proof` from this data, using the given hashing `algorithm`, the `scheme`
+    /// of this data and the default `OddLeafPolicy`, once checked that the data goes
+    /// with the algorithm, without hashing anything: whether the proof holds is left
+    /// to `Proof::validate`.
+    ///
+    /// Fails with `ConversionError::AlgorithmMismatch` if the data names another algorithm,
+    /// with `ConversionError::WrongHashLength` on the first hash, from the root down, which
+    /// does not have the output length of `algorithm`, and with `ConversionError::MalformedLemma`
+    /// if a lemma has a sibling hash without a sub lemma, or the other way round, or lies
+    /// deeper than `MAX_TREE_DEPTH`.
+    pub fn into_proof(self, algorithm: &'static Algorithm) -> Result<Proof<T>, ConversionError> {
+        self.check(algorithm)?;
+
+        Ok(self.into_proof_unchecked(algorithm))
+    }
+
+    /// Works like `ProofData::into_proof`, without any check, so that data which does not go
+    /// with `algorithm` only shows once the proof fails to validate.
+    pub fn into_proof_unchecked(self, algorithm: &'static Algorithm) -> Proof<T> {
+        let mut proof = Proof::new(algorithm, self.root_hash, self.lemma, self.value);
+        proof.scheme = self.scheme;
+        proof.tree_size = self.tree_size;
+        proof.leaf_index = self.leaf_index;
+        proof.leaf_counts = self.leaf_counts;
+        proof
+    }
+
+    /// Constructs a `Proof` from this data, using the hashing algorithm it names,
+    /// as `ProofData::into_proof` does.
+    ///
+    /// Fails with `Error::MissingField` if the data does not name its algorithm,
+    /// and with the errors converted from those of `ProofData::into_proof`.
+    pub fn into_named_proof(self) -> Result<Proof<T>, Error> {
+        match self.algorithm {
+            Some(algorithm) => Ok(self.into_proof(algorithm.algorithm())?),
+            None => Err(Error::MissingField("algorithm")),
+        }
+    }
+
+    /// Checks that this data goes with the given `algorithm`, see `ProofData::into_proof`.
+    fn check(&self, algorithm: &'static Algorithm) -> Result<(), ConversionError> {
+        let expected = AlgorithmTag::of(algorithm);
+
+        match self.algorithm {
+            Some(named) if named != expected => {
+                return Err(ConversionError::AlgorithmMismatch {
+                    expected: expected,
+                    named: named,
+                });
+            }
+            _ => {}
+        }
+
+        let check = |level: usize, hash: &[u8]| if hash.len() == algorithm.output_len {
+            Ok(())
+        } else {
+            Err(ConversionError::WrongHashLength {
+                level: level,
+                expected: algorithm.output_len,
+                got: hash.len(),
+            })
+        };
+
+        check(0, &self.root_hash)?;
+
+        for (level, lemma) in self.lemma.iter().enumerate() {
+            if level > MAX_TREE_DEPTH {
+                return Err(ConversionError::MalformedLemma { level: level });
+            }
+
+            check(level, &lemma.node_hash)?;
+
+            match (&lemma.sibling_hash, &lemma.sub_lemma) {
+                (&Some(ref sibling), &Some(_)) => check(level, sibling.as_ref().into_inner())?,
+                (&None, &None) => {}
+                _ => return Err(ConversionError::MalformedLemma { level: level }),
+            }
         }
+
+        Ok(())
     }
 }
 
+/// The contents of a `Proof` without the hashing algorithm, nor the node hashes
+/// of the lemma chain, which are recomputed from the leaf hash and the sibling hashes
+/// by `into_proof`.
+///
+/// Unlike `ProofData`, this form cannot carry node hashes which do not match the sibling
+/// hashes, so that every proof has a single serialization.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
+pub struct CanonicalProofData<T> {
+    /// The hash of the root of the original `MerkleTree`
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    #[cfg_attr(feature = "schemars", schemars(with = "::schema::Hash"))]
+    pub root_hash: Vec<u8>,
+
+    /// The hash of the leaf holding the value
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    #[cfg_attr(feature = "schemars", schemars(with = "::schema::Hash"))]
+    pub leaf_hash: Vec<u8>,
+
+    /// The sibling hashes on the path to the leaf, from the root down
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding::siblings"))]
+    #[cfg_attr(feature = "schemars", schemars(with = "::schema::Siblings"))]
+    pub siblings: Vec<Positioned<Vec<u8>>>,
+
+    /// The value concerned by this `Proof`
+    pub value: T,
+}
+
+impl<T> CanonicalProofData<T> {
+    /// Constructs a `Proof` from this data, using the given hashing `algorithm`
+    /// and the default `HashingScheme`.
+    pub fn into_proof(self, algorithm: &'static Algorithm) -> Proof<T> {
+        self.into_proof_with_scheme(algorithm, HashingScheme::default())
+    }
+
+    /// Constructs a `Proof` from this data, using the given hashing `algorithm` and `scheme`.
+    pub fn into_proof_with_scheme(
+        self,
+        algorithm: &'static Algorithm,
+        scheme: HashingScheme,
+    ) -> Proof<T> {
+
+        let lemma = Lemma::from_siblings(algorithm, scheme, self.leaf_hash, self.siblings);
+
+        let mut proof = Proof::new(algorithm, self.root_hash, lemma, self.value);
+        proof.scheme = scheme;
+        proof
+    }
+}
 
 /// A `Lemma` holds the hash of a node, the hash of its sibling node,
 /// and a sub lemma, whose `node_hash`, when combined with this `sibling_hash`
 /// must be equal to this `node_hash`.
+///
+/// Deserializing a lemma chain with more than `MAX_TREE_DEPTH` nested sub lemmas fails.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
 pub struct Lemma {
+    /// The hash of the node this lemma is about
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    #[cfg_attr(feature = "schemars", schemars(with = "::schema::Hash"))]
     pub node_hash: Vec<u8>,
+
+    /// The hash of the sibling of that node, if any
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding::sibling"))]
+    #[cfg_attr(feature = "schemars", schemars(with = "::schema::Sibling"))]
     pub sibling_hash: Option<Positioned<Vec<u8>>>,
+
+    /// The lemma about the child of that node which lies on the path to the value
+    #[cfg_attr(feature = "serialization-serde", serde(default, deserialize_with = "::lemma_depth::deserialize"))]
     pub sub_lemma: Option<Box<Lemma>>,
 }
 
 impl Lemma {
     /// Attempts to generate a proof that the a value with hash `needle` is a member of the given `tree`.
-    pub fn new<T>(tree: &Tree<T>, needle: &[u8]) -> Option<Lemma> {
-        match *tree {
-            Tree::Empty { .. } => None,
+    pub fn new<T>(tree: Subtree<T>, needle: &[u8]) -> Option<Lemma> {
+        match tree.view() {
+            View::Empty { .. } | View::Pruned { .. } => None,
+
+            View::Leaf { hash, .. } => Lemma::new_leaf_proof(hash, needle),
+
+            View::Node { hash, left, right } => Lemma::new_tree_proof(hash, needle, left, right),
+        }
+    }
+
+    /// Generates a lemma for every leaf of the given `tree` whose hash is `needle`,
+    /// from the leftmost to the rightmost one.
+    pub fn new_all<T>(tree: Subtree<T>, needle: &[u8]) -> Vec<Lemma> {
+        match tree.view() {
+            View::Empty { .. } | View::Pruned { .. } => Vec::new(),
+
+            View::Leaf { hash, .. } => Lemma::new_leaf_proof(hash, needle).into_iter().collect(),
+
+            View::Node { hash, left, right } => {
+                let lefts = Lemma::new_all(left, needle).into_iter().map(|sub_lemma| {
+                    (sub_lemma, Positioned::Right(right.hash().to_vec()))
+                });
+
+                let rights = Lemma::new_all(right, needle).into_iter().map(|sub_lemma| {
+                    (sub_lemma, Positioned::Left(left.hash().to_vec()))
+                });
+
+                lefts
+                    .chain(rights)
+                    .map(|(sub_lemma, sibling_hash)| {
+                        Lemma {
+                            node_hash: hash.to_vec(),
+                            sibling_hash: Some(sibling_hash),
+                            sub_lemma: Some(Box::new(sub_lemma)),
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Attempts to generate a lemma for the `k`-th leaf (starting from `0`) of the given `tree`
+    /// whose hash is `needle`. On failure, `k` is decreased by the number of such leaves.
+    pub fn new_occurrence<T>(tree: Subtree<T>, needle: &[u8], k: &mut usize) -> Option<Lemma> {
+        match tree.view() {
+            View::Empty { .. } | View::Pruned { .. } => None,
+
+            View::Leaf { hash, .. } => {
+                Lemma::new_leaf_proof(hash, needle).and_then(|lemma| if *k == 0 {
+                    Some(lemma)
+                } else {
+                    *k -= 1;
+                    None
+                })
+            }
+
+            View::Node { hash, left, right } => {
+                Lemma::new_occurrence(left, needle, k)
+                    .map(|lemma| (lemma, Positioned::Right(right.hash().to_vec())))
+                    .or_else(|| {
+                        Lemma::new_occurrence(right, needle, k)
+                            .map(|lemma| (lemma, Positioned::Left(left.hash().to_vec())))
+                    })
+                    .map(|(sub_lemma, sibling_hash)| {
+                        Lemma {
+                            node_hash: hash.to_vec(),
+                            sibling_hash: Some(sibling_hash),
+                            sub_lemma: Some(Box::new(sub_lemma)),
+                        }
+                    })
+            }
+        }
+    }
+
+    /// Builds the lemma chain of a leaf from its hash and the sibling hashes on its path,
+    /// from the root down, recomputing the node hashes.
+    pub(crate) fn from_siblings(
+        algorithm: &'static Algorithm,
+        scheme: HashingScheme,
+        leaf_hash: Vec<u8>,
+        siblings: Vec<Positioned<Vec<u8>>>,
+    ) -> Lemma {
+
+        let mut lemma = Lemma {
+            node_hash: leaf_hash,
+            sibling_hash: None,
+            sub_lemma: None,
+        };
 
-            Tree::Leaf { ref hash, .. } => Lemma::new_leaf_proof(hash, needle),
+        for sibling in siblings.into_iter().rev() {
+            let node_hash = sibling.hash_parent(&algorithm, scheme, &lemma.node_hash);
 
-            Tree::Node {
-                ref hash,
-                ref left,
-                ref right,
-            } => Lemma::new_tree_proof(hash, needle, left, right),
+            lemma = Lemma {
+                node_hash: node_hash.as_ref().into(),
+                sibling_hash: Some(sibling),
+                sub_lemma: Some(Box::new(lemma)),
+            };
+        }
+
+        lemma
+    }
+
+    /// Works like `Lemma::from_siblings`, for the lemma chain of a tree with leaf counts,
+    /// `counts` being the number of leaves beneath each node which has a sub lemma,
+    /// from the root down. There must be as many of them as siblings.
+    pub(crate) fn from_counted_siblings(
+        algorithm: &'static Algorithm,
+        scheme: HashingScheme,
+        leaf_hash: Vec<u8>,
+        siblings: Vec<Positioned<Vec<u8>>>,
+        counts: &[u64],
+    ) -> Lemma {
+
+        debug_assert!(siblings.len() == counts.len());
+
+        let mut lemma = Lemma {
+            node_hash: leaf_hash,
+            sibling_hash: None,
+            sub_lemma: None,
+        };
+
+        for (sibling, &count) in siblings.into_iter().rev().zip(counts.iter().rev()) {
+            let node_hash = sibling.hash_counted_parent(&algorithm, scheme, &lemma.node_hash, count);
+
+            lemma = Lemma {
+                node_hash: node_hash.as_ref().into(),
+                sibling_hash: Some(sibling),
+                sub_lemma: Some(Box::new(lemma)),
+            };
+        }
+
+        lemma
+    }
+
+    /// Attempts to generate a lemma for the leaf at the given `index` of the given `tree`,
+    /// which has `count` leaves, returning it along with the value of that leaf.
+    pub fn new_by_index<T>(tree: Subtree<T>, index: usize, count: usize) -> Option<(Lemma, &T)> {
+        if index >= count {
+            return None;
+        }
+
+        match tree.view() {
+            View::Empty { .. } | View::Pruned { .. } => None,
+
+            View::Leaf { hash, value } => {
+                let lemma = Lemma {
+                    node_hash: hash.to_vec(),
+                    sibling_hash: None,
+                    sub_lemma: None,
+                };
+
+                Some((lemma, value))
+            }
+
+            View::Node { hash, left, right } => {
+                let left_count = tree::left_count(right, count);
+
+                let (sub_lemma, sibling_hash) = if index < left_count {
+                    let sub_lemma = Lemma::new_by_index(left, index, left_count);
+                    (sub_lemma, Positioned::Right(right.hash().to_vec()))
+                } else {
+                    let sub_lemma = Lemma::new_by_index(right, index - left_count, count - left_count);
+                    (sub_lemma, Positioned::Left(left.hash().to_vec()))
+                };
+
+                sub_lemma.map(|(sub_lemma, value)| {
+                    let lemma = Lemma {
+                        node_hash: hash.to_vec(),
+                        sibling_hash: Some(sibling_hash),
+                        sub_lemma: Some(Box::new(sub_lemma)),
+                    };
+
+                    (lemma, value)
+                })
+            }
         }
     }
 
+    /// Follows this lemma chain down the given `tree`, which has `count` leaves, checking that
+    /// every node hash and sibling hash is the one of the corresponding node of the tree,
+    /// and that the chain ends at a leaf, whose index is returned. See `MerkleTree::verify_proof`.
+    pub(crate) fn locate<T>(&self, tree: Subtree<T>, count: usize) -> Result<usize, Error> {
+        let mismatch = |depth, reason| Error::ProofMismatch {
+            depth: depth,
+            reason: reason,
+        };
+
+        let (mut lemma, mut tree, mut count) = (self, tree, count);
+        let (mut index, mut depth) = (0, 0);
+
+        loop {
+            if lemma.node_hash != *tree.hash() {
+                return Err(mismatch(depth, "the node hash differs"));
+            }
+
+            let (left, right) = match tree.view() {
+                View::Leaf { .. } if lemma.sub_lemma.is_none() => return Ok(index),
+                View::Leaf { .. } => return Err(mismatch(depth, "the path goes below a leaf")),
+                View::Empty { .. } => return Err(mismatch(depth, "the path leads to an empty node")),
+                View::Pruned { hash, .. } => return Err(Error::Pruned { subtree_root: hash.to_vec() }),
+                View::Node { left, right, .. } => (left, right),
+            };
+
+            let (sibling, sub_lemma) = match (lemma.sibling_hash.as_ref(), lemma.sub_lemma.as_ref()) {
+                (Some(sibling), Some(sub_lemma)) => (sibling, sub_lemma),
+                _ => return Err(mismatch(depth, "the path ends above a leaf")),
+            };
+
+            let left_count = tree::left_count(right, count);
+            depth += 1;
+
+            match *sibling {
+                Positioned::Right(ref hash) if hash == right.hash() => {
+                    tree = left;
+                    count = left_count;
+                }
+
+                Positioned::Left(ref hash) if hash == left.hash() => {
+                    tree = right;
+                    index += left_count;
+                    count -= left_count;
+                }
+
+                _ => return Err(mismatch(depth, "the sibling hash differs")),
+            }
+
+            lemma = sub_lemma;
+        }
+    }
+
+    /// Generates the lemmas for the leaves at the given `indices` of the given `tree`,
+    /// which has `count` leaves, in a single descent, along with the values of those leaves.
+    ///
+    /// The indices must be sorted, without duplicates, and less than `count`.
+    /// The lemmas are in the same order.
+    pub(crate) fn new_by_indices<'a, T>(
+        tree: Subtree<'a, T>,
+        indices: &[usize],
+        count: usize,
+    ) -> Vec<(Lemma, &'a T)> {
+
+        let mut lemmas = Vec::with_capacity(indices.len());
+        Lemma::collect_by_indices(tree, indices, 0, count, &mut lemmas);
+        lemmas
+    }
+
+    /// Pushes the lemmas for the leaves at the given `indices` of `tree`, whose leaves
+    /// are those from `offset` to `offset + count`, onto `lemmas`.
+    #[allow(clippy::indexing_slicing)]
+    fn collect_by_indices<'a, T>(
+        tree: Subtree<'a, T>,
+        indices: &[usize],
+        offset: usize,
+        count: usize,
+        lemmas: &mut Vec<(Lemma, &'a T)>,
+    ) {
+
+        if indices.is_empty() {
+            return;
+        }
+
+        match tree.view() {
+            View::Empty { .. } | View::Pruned { .. } => {}
+
+            View::Leaf { hash, value } => {
+                let lemma = Lemma {
+                    node_hash: hash.to_vec(),
+                    sibling_hash: None,
+                    sub_lemma: None,
+                };
+
+                lemmas.push((lemma, value));
+            }
+
+            View::Node { hash, left, right } => {
+                let left_count = tree::left_count(right, count);
+                let split = indices.partition_point(|&index| index < offset + left_count);
+                let start = lemmas.len();
+
+                Lemma::collect_by_indices(left, &indices[..split], offset, left_count, lemmas);
+                let middle = lemmas.len();
+
+                Lemma::collect_by_indices(
+                    right,
+                    &indices[split..],
+                    offset + left_count,
+                    count - left_count,
+                    lemmas,
+                );
+
+                for (i, &mut (ref mut lemma, _)) in lemmas[start..].iter_mut().enumerate() {
+                    let sibling_hash = if start + i < middle {
+                        Positioned::Right(right.hash().to_vec())
+                    } else {
+                        Positioned::Left(left.hash().to_vec())
+                    };
+
+                    let sub_lemma = mem::replace(lemma, Lemma {
+                        node_hash: hash.to_vec(),
+                        sibling_hash: Some(sibling_hash),
+                        sub_lemma: None,
+                    });
+
+                    lemma.sub_lemma = Some(Box::new(sub_lemma));
+                }
+            }
+        }
+    }
+
+    /// Returns an iterator over this lemma and its nested sub lemmas, from this one,
+    /// which is about the root of the tree in a `Proof`, down to the one about the leaf.
+    pub fn iter(&self) -> LemmaIter {
+        LemmaIter {
+            next: Some(self),
+            len: self.depth() + 1,
+        }
+    }
+
+    /// Returns the number of nested sub lemmas of this lemma, which is the depth of the leaf
+    /// below the root in a `Proof`.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut lemma = self;
+
+        while let Some(ref sub_lemma) = lemma.sub_lemma {
+            depth += 1;
+            lemma = sub_lemma;
+        }
+
+        depth
+    }
+
+    /// Returns the index of the leaf this lemma chain leads to, in a tree with `tree_size` leaves
+    /// built with the given `policy`, or `None` if the chain does not lead to a leaf of such
+    /// a tree, because of its depth or of the side of one of the siblings.
+    ///
+    /// The left child of a node is a perfect tree, save for the empty copies made by
+    /// `OddLeafPolicy::Duplicate`, under which every leaf lies at the same depth.
+    pub fn leaf_index(&self, tree_size: usize, policy: OddLeafPolicy) -> Option<usize> {
+        if tree_size == 0 {
+            return None;
+        }
+
+        let mut count = tree_size;
+        let mut levels = tree::ceil_log2(tree_size);
+        let mut index = 0;
+
+        for sibling in self.siblings() {
+            if levels == 0 {
+                return None;
+            }
+
+            let left_count = left_leaves(count, levels);
+
+            match sibling.direction() {
+                Direction::Right => count = left_count,
+
+                // A copy is always on the right
+                Direction::Left if left_count == count => return None,
+
+                Direction::Left => {
+                    index += left_count;
+                    count -= left_count;
+                }
+            }
+
+            levels = child_levels(count, levels, policy);
+        }
+
+        if levels == 0 { Some(index) } else { None }
+    }
+
+    /// Returns the number of leaves beneath each node of this lemma chain which has a sub lemma,
+    /// from the root down, in a tree with `tree_size` leaves built with the given `policy`,
+    /// only counting the first `filled` ones, as the other slots of a tree of fixed depth are
+    /// zero leaves. Returns `None` if the chain does not lead to a leaf of such a tree,
+    /// see `Lemma::leaf_index`.
+    pub(crate) fn leaf_counts(&self, tree_size: usize, policy: OddLeafPolicy, filled: usize) -> Option<Vec<u64>> {
+        if tree_size == 0 {
+            return None;
+        }
+
+        let mut count = tree_size;
+        let mut levels = tree::ceil_log2(tree_size);
+        let mut index = 0;
+        let mut counts = Vec::with_capacity(levels);
+
+        for sibling in self.siblings() {
+            if levels == 0 {
+                return None;
+            }
+
+            counts.push(cmp::min(count, filled.saturating_sub(index)) as u64);
+
+            let left_count = left_leaves(count, levels);
+
+            match sibling.direction() {
+                Direction::Right => count = left_count,
+                Direction::Left if left_count == count => return None,
+
+                Direction::Left => {
+                    index += left_count;
+                    count -= left_count;
+                }
+            }
+
+            levels = child_levels(count, levels, policy);
+        }
+
+        if levels == 0 { Some(counts) } else { None }
+    }
+
+    /// Returns the hash of the node of the innermost sub lemma, ie. the leaf hash in a `Proof`.
+    pub fn leaf_hash(&self) -> &[u8] {
+        let leaf = self.iter().last().unwrap_or(self);
+        &leaf.node_hash
+    }
+
+    /// Returns an iterator over the sibling hashes of this lemma and its nested sub lemmas,
+    /// in the same order as `iter`.
+    pub fn siblings(&self) -> impl Iterator<Item = &Positioned<Vec<u8>>> {
+        self.iter().filter_map(|lemma| lemma.sibling_hash.as_ref())
+    }
+
+    /// Returns the least and the greatest depth of the leaf this lemma chain may lead to,
+    /// in a tree of height `tree_height` built with the given `policy`.
+    ///
+    /// Every leaf of a tree built with `OddLeafPolicy::Duplicate` lies `tree_height` levels
+    /// below the root. Under `OddLeafPolicy::Promote`, so does every leaf of the left child of
+    /// the root, which is a perfect tree, while the leaves of its right child may have been
+    /// promoted up to any level below the root.
+    pub fn leaf_depths(&self, tree_height: usize, policy: OddLeafPolicy) -> (usize, usize) {
+        let goes_left = self.sibling_hash.as_ref().map_or(false, Positioned::is_right);
+
+        match policy {
+            OddLeafPolicy::Promote if !goes_left && tree_height > 0 => (1, tree_height),
+            _ => (tree_height, tree_height),
+        }
+    }
+
+    /// Returns whether this lemma has more than `max` nested sub lemmas.
+    pub(crate) fn depth_exceeds(&self, max: usize) -> bool {
+        let mut depth = 0;
+        let mut lemma = self;
+
+        while let Some(ref sub_lemma) = lemma.sub_lemma {
+            depth += 1;
+
+            if depth > max {
+                return true;
+            }
+
+            lemma = sub_lemma;
+        }
+
+        false
+    }
+
     fn new_leaf_proof(hash: &[u8], needle: &[u8]) -> Option<Lemma> {
         if *hash == *needle {
             Some(Lemma {
@@ -145,19 +1460,19 @@ impl Lemma {
     fn new_tree_proof<T>(
         hash: &[u8],
         needle: &[u8],
-        left: &Tree<T>,
-        right: &Tree<T>,
+        left: Subtree<T>,
+        right: Subtree<T>,
     ) -> Option<Lemma> {
         Lemma::new(left, needle)
             .map(|lemma| {
-                let right_hash = right.hash().clone();
+                let right_hash = right.hash().to_vec();
                 let sub_lemma = Some(Positioned::Right(right_hash));
                 (lemma, sub_lemma)
             })
             .or_else(|| {
                 let sub_lemma = Lemma::new(right, needle);
                 sub_lemma.map(|lemma| {
-                    let left_hash = left.hash().clone();
+                    let left_hash = left.hash().to_vec();
                     let sub_lemma = Some(Positioned::Left(left_hash));
                     (lemma, sub_lemma)
                 })
@@ -172,8 +1487,37 @@ impl Lemma {
     }
 }
 
+/// An iterator over a `Lemma` and its nested sub lemmas, returned by `Lemma::iter`.
+#[derive(Clone, Debug)]
+#[allow(missing_copy_implementations)]
+pub struct LemmaIter<'a> {
+    next: Option<&'a Lemma>,
+    len: usize,
+}
+
+impl<'a> Iterator for LemmaIter<'a> {
+    type Item = &'a Lemma;
+
+    fn next(&mut self) -> Option<&'a Lemma> {
+        let lemma = self.next?;
+
+        self.next = lemma.sub_lemma.as_deref();
+        self.len -= 1;
+
+        Some(lemma)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a> ExactSizeIterator for LemmaIter<'a> {}
+
 /// Tags a value so that we know from which branch of a `Tree` (if any) it was found.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(JsonSchema))]
 pub enum Positioned<T> {
     /// The value was found in the left branch
     Left(T),
@@ -181,3 +1525,206 @@ pub enum Positioned<T> {
     /// The value was found in the right branch
     Right(T),
 }
+
+/// A branch of a `Tree`, ie. the side of a `Positioned` value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    /// The left branch
+    Left,
+
+    /// The right branch
+    Right,
+}
+
+impl Direction {
+    /// Returns the other direction.
+    pub fn flip(self) -> Direction {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    /// Returns the byte encoding this direction in binary formats, `0` for left and `1` for right.
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Direction::Left => 0,
+            Direction::Right => 1,
+        }
+    }
+
+    /// Returns the direction encoded by the given byte, if any, see `to_byte`.
+    pub fn from_byte(byte: u8) -> Option<Direction> {
+        match byte {
+            0 => Some(Direction::Left),
+            1 => Some(Direction::Right),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Positioned<T> {
+    /// Tags `value` with the given `direction`.
+    pub fn new(direction: Direction, value: T) -> Self {
+        match direction {
+            Direction::Left => Positioned::Left(value),
+            Direction::Right => Positioned::Right(value),
+        }
+    }
+
+    /// Returns the branch in which the value was found.
+    pub fn direction(&self) -> Direction {
+        match *self {
+            Positioned::Left(_) => Direction::Left,
+            Positioned::Right(_) => Direction::Right,
+        }
+    }
+
+    /// Returns whether the value was found in the left branch.
+    pub fn is_left(&self) -> bool {
+        self.direction() == Direction::Left
+    }
+
+    /// Returns whether the value was found in the right branch.
+    pub fn is_right(&self) -> bool {
+        self.direction() == Direction::Right
+    }
+
+    /// Returns the value, whatever its branch.
+    pub fn into_inner(self) -> T {
+        match self {
+            Positioned::Left(value) | Positioned::Right(value) => value,
+        }
+    }
+
+    /// Returns a reference to the value, in the same branch.
+    pub fn as_ref(&self) -> Positioned<&T> {
+        match *self {
+            Positioned::Left(ref value) => Positioned::Left(value),
+            Positioned::Right(ref value) => Positioned::Right(value),
+        }
+    }
+
+    /// Applies `f` to the value, keeping its branch.
+    pub fn map<U, F>(self, f: F) -> Positioned<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+
+        let direction = self.direction();
+        Positioned::new(direction, f(self.into_inner()))
+    }
+
+    /// Moves the value to the other branch.
+    pub fn flip(self) -> Positioned<T> {
+        let direction = self.direction().flip();
+        Positioned::new(direction, self.into_inner())
+    }
+}
+
+impl Positioned<Vec<u8>> {
+    /// Returns the hash of the parent of the node with the given hash and of this sibling hash,
+    /// which lies on its side.
+    pub(crate) fn hash_parent(
+        &self,
+        hasher: &dyn MerkleHasher,
+        scheme: HashingScheme,
+        node_hash: &[u8],
+    ) -> Digest {
+
+        match *self {
+            Positioned::Left(ref sibling) => scheme.hash_nodes_with(hasher, &sibling.as_slice(), &node_hash),
+            Positioned::Right(ref sibling) => scheme.hash_nodes_with(hasher, &node_hash, &sibling.as_slice()),
+        }
+    }
+
+    /// Works like `Positioned::hash_parent`, for a parent with `count` leaves beneath it
+    /// in a tree with leaf counts.
+    pub(crate) fn hash_counted_parent(
+        &self,
+        hasher: &dyn MerkleHasher,
+        scheme: HashingScheme,
+        node_hash: &[u8],
+        count: u64,
+    ) -> Digest {
+
+        match *self {
+            Positioned::Left(ref sibling) => {
+                scheme.hash_counted_nodes_with(hasher, &sibling.as_slice(), &node_hash, count)
+            }
+            Positioned::Right(ref sibling) => {
+                scheme.hash_counted_nodes_with(hasher, &node_hash, &sibling.as_slice(), count)
+            }
+        }
+    }
+}
+
+/// Checks whether `path`, as returned by `Proof::sorted_path`, is the proof that the leaf
+/// with hash `leaf_hash` is part of a tree built with `HashingScheme::SortedPairs`
+/// whose root hash is `root_hash`.
+///
+/// Each sibling hash of `path`, from the leaf up, is combined with the hash computed so far
+/// in sorted order, as in `MerkleProof.verify` of OpenZeppelin.
+pub fn validate_sorted_path(
+    algorithm: &'static Algorithm,
+    root_hash: &[u8],
+    leaf_hash: &[u8],
+    path: &[Vec<u8>],
+) -> bool {
+
+    if path.len() > MAX_TREE_DEPTH {
+        return false;
+    }
+
+    let computed = path.iter().fold(leaf_hash.to_vec(), |hash, sibling| {
+        HashingScheme::SortedPairs.hash_nodes(algorithm, &hash, sibling).as_ref().into()
+    });
+
+    computed.as_slice() == root_hash
+}
+
+/// Returns the number of leaves under the left child of a node with `count` leaves,
+/// which lies `levels` levels above the leaves.
+fn left_leaves(count: usize, levels: usize) -> usize {
+    cmp::min(count, 1 << (levels - 1))
+}
+
+/// Returns the number of levels above the leaves of a child with `count` leaves
+/// of a node which lies `levels` levels above the leaves, in a tree built with `policy`.
+fn child_levels(count: usize, levels: usize, policy: OddLeafPolicy) -> usize {
+    match policy {
+        OddLeafPolicy::Promote => tree::ceil_log2(count),
+        OddLeafPolicy::Duplicate => levels - 1,
+    }
+}
+
+/// Returns the sides of the siblings on the path to the leaf at the given `index` of a tree
+/// with `tree_size` leaves built with `policy`, from the root down, see `Lemma::leaf_index`.
+/// Returns `None` if the tree has at most `index` leaves.
+fn sibling_sides(index: usize, tree_size: usize, policy: OddLeafPolicy) -> Option<Vec<Direction>> {
+    if index >= tree_size {
+        return None;
+    }
+
+    let mut count = tree_size;
+    let mut levels = tree::ceil_log2(tree_size);
+    let mut index = index;
+    let mut sides = Vec::with_capacity(levels);
+
+    while levels > 0 {
+        let left_count = left_leaves(count, levels);
+
+        if index < left_count {
+            sides.push(Direction::Right);
+            count = left_count;
+        } else {
+            sides.push(Direction::Left);
+            index -= left_count;
+            count -= left_count;
+        }
+
+        levels = child_levels(count, levels, policy);
+    }
+
+    Some(sides)
+}
@@ -0,0 +1,176 @@
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use proof::Lemma;
+
+/// The counters of the proof cache of a `MerkleTree`, see `MerkleTree::enable_proof_cache`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CacheStats {
+    /// The number of proofs served from the cache.
+    pub hits: u64,
+
+    /// The number of proofs which had to be generated, not counting those of missing values.
+    pub misses: u64,
+
+    /// The number of cached lemmas dropped to make room for other ones.
+    pub evictions: u64,
+}
+
+/// A least recently used cache of the lemmas of a tree, by leaf index.
+///
+/// The lemmas are behind a lock, so that they can be cached while generating proofs
+/// from a shared tree.
+#[derive(Debug)]
+pub struct ProofCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct CacheState {
+    entries: HashMap<usize, Entry>,
+    by_hash: HashMap<Vec<u8>, usize>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    lemma: Lemma,
+    last_used: u64,
+}
+
+impl ProofCache {
+    /// Creates a cache holding up to `capacity` lemmas, which is disabled if `capacity` is `0`.
+    pub fn new(capacity: usize) -> Self {
+        ProofCache {
+            capacity: capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Returns whether lemmas are cached at all.
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Returns the counters of the cache.
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    /// Changes the capacity of the cache, dropping the cached lemmas and keeping the counters.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.clear();
+    }
+
+    /// Drops the cached lemmas, keeping the counters.
+    pub fn clear(&mut self) {
+        let state = self.state.get_mut().unwrap_or_else(PoisonError::into_inner);
+        state.entries.clear();
+        state.by_hash.clear();
+    }
+
+    /// Returns the cached lemma of the leftmost leaf whose hash is `leaf_hash`, if any.
+    pub fn get_by_hash(&self, leaf_hash: &[u8]) -> Option<Lemma> {
+        let mut state = self.lock();
+        let index = state.by_hash.get(leaf_hash).cloned();
+
+        index.and_then(|index| state.hit(index))
+    }
+
+    /// Returns the lemma of the leaf at the given `index`, generating it with `generate`
+    /// if it is not cached. The leftmost leaf whose hash is `leaf_hash`, if given, is that one.
+    pub fn get_or_insert<F>(
+        &self,
+        index: usize,
+        leaf_hash: Option<&[u8]>,
+        generate: F,
+    ) -> Option<Lemma>
+    where
+        F: FnOnce() -> Option<Lemma>,
+    {
+
+        {
+            let mut state = self.lock();
+
+            if let Some(lemma) = state.hit(index) {
+                if let Some(leaf_hash) = leaf_hash {
+                    state.by_hash.insert(leaf_hash.to_vec(), index);
+                }
+
+                return Some(lemma);
+            }
+        }
+
+        let lemma = generate()?;
+        self.lock().insert(self.capacity, index, leaf_hash, lemma.clone());
+        Some(lemma)
+    }
+
+    fn lock(&self) -> MutexGuard<CacheState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl CacheState {
+    fn hit(&mut self, index: usize) -> Option<Lemma> {
+        self.clock += 1;
+
+        match self.entries.get_mut(&index) {
+            Some(entry) => {
+                entry.last_used = self.clock;
+                self.stats.hits += 1;
+                Some(entry.lemma.clone())
+            }
+
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, capacity: usize, index: usize, leaf_hash: Option<&[u8]>, lemma: Lemma) {
+        if !self.entries.contains_key(&index) && self.entries.len() >= capacity {
+            self.evict();
+        }
+
+        if let Some(leaf_hash) = leaf_hash {
+            self.by_hash.insert(leaf_hash.to_vec(), index);
+        }
+
+        let entry = Entry {
+            lemma: lemma,
+            last_used: self.clock,
+        };
+
+        self.entries.insert(index, entry);
+    }
+
+    /// Drops the least recently used lemma. This is linear in the capacity of the cache,
+    /// which is meant to hold a handful of hot leaves.
+    fn evict(&mut self) {
+        let oldest = self.entries
+            .iter()
+            .min_by_key(|&(_, entry)| entry.last_used)
+            .map(|(&index, _)| index);
+
+        if let Some(index) = oldest {
+            self.entries.remove(&index);
+            self.by_hash.retain(|_, cached| *cached != index);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+impl Clone for ProofCache {
+    fn clone(&self) -> Self {
+        ProofCache {
+            capacity: self.capacity,
+            state: Mutex::new(self.lock().clone()),
+        }
+    }
+}
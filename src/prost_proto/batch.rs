@@ -0,0 +1,265 @@
+use std::convert::TryFrom;
+
+use digest::Algorithm;
+
+use prost::Message;
+
+use cas::UpdateReceipt;
+use error::Error;
+use multiproof::{MultiProof, MultiProofNode};
+use prefix::PrefixProof;
+use proof::MAX_TREE_DEPTH;
+use wire::{algorithm_tag, scheme_tag, check_proto_algorithm_tag, decode_proto_scheme, check_hash_len};
+use super::{check_version, VERSION};
+use super::proof::{ProofProto, MultiProofProto, MultiProofNodeProto, PrefixProofProto, UpdateReceiptProto};
+
+/// Encodes the given multiproof with prost.
+pub fn write_multi_proof(proof: &MultiProof) -> Vec<u8> {
+    MultiProofProto::from_multi_proof(proof).encode_to_vec()
+}
+
+/// Decodes a multiproof encoded with the given hashing `algorithm` with prost.
+pub fn parse_multi_proof(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<MultiProof, Error> {
+    MultiProofProto::decode(bytes)
+        .map_err(|err| Error::Decode(err.to_string()))
+        .and_then(|proto| proto.try_into_multi_proof(algorithm))
+}
+
+/// Encodes the given prefix proof with prost.
+pub fn write_prefix_proof(proof: &PrefixProof) -> Vec<u8> {
+    PrefixProofProto::from_prefix_proof(proof).encode_to_vec()
+}
+
+/// Decodes a prefix proof encoded with the given hashing `algorithm` with prost.
+pub fn parse_prefix_proof(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<PrefixProof, Error> {
+    PrefixProofProto::decode(bytes)
+        .map_err(|err| Error::Decode(err.to_string()))
+        .and_then(|proto| proto.try_into_prefix_proof(algorithm))
+}
+
+/// Encodes the given update receipt with prost.
+pub fn write_update_receipt<T>(receipt: &UpdateReceipt<T>) -> Vec<u8>
+where
+    T: Clone + Into<Vec<u8>>,
+{
+
+    UpdateReceiptProto::from_receipt(receipt.clone()).encode_to_vec()
+}
+
+/// Decodes an update receipt whose proofs have the given hashing `algorithm` with prost.
+pub fn parse_update_receipt<T>(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<UpdateReceipt<T>, Error>
+where
+    T: From<Vec<u8>>,
+{
+
+    UpdateReceiptProto::decode(bytes)
+        .map_err(|err| Error::Decode(err.to_string()))
+        .and_then(|proto| proto.try_into_receipt(algorithm))
+}
+
+#[cfg(not(feature = "serialization-protobuf"))]
+impl MultiProof {
+    /// Parse a `MultiProof` from its Protobuf binary representation, which must have been
+    /// produced with the given hashing `algorithm`.
+    pub fn parse_protobuf(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error> {
+        parse_multi_proof(algorithm, bytes)
+    }
+
+    /// Serialize this `MultiProof` with Protobuf, tagged with the current schema `VERSION`.
+    pub fn write_protobuf(&self) -> Vec<u8> {
+        write_multi_proof(self)
+    }
+}
+
+#[cfg(not(feature = "serialization-protobuf"))]
+impl PrefixProof {
+    /// Parse a `PrefixProof` from its Protobuf binary representation, which must have been
+    /// produced with the given hashing `algorithm`.
+    pub fn parse_protobuf(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error> {
+        parse_prefix_proof(algorithm, bytes)
+    }
+
+    /// Serialize this `PrefixProof` with Protobuf, tagged with the current schema `VERSION`.
+    pub fn write_protobuf(&self) -> Vec<u8> {
+        write_prefix_proof(self)
+    }
+}
+
+#[cfg(not(feature = "serialization-protobuf"))]
+impl<T> UpdateReceipt<T> {
+    /// Parse an `UpdateReceipt` from its Protobuf binary representation, whose proofs
+    /// must have been produced with the given hashing `algorithm`.
+    pub fn parse_protobuf(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error>
+    where
+        T: From<Vec<u8>>,
+    {
+
+        parse_update_receipt(algorithm, bytes)
+    }
+
+    /// Serialize this `UpdateReceipt` with Protobuf, tagged with the current schema `VERSION`.
+    pub fn write_protobuf(&self) -> Vec<u8>
+    where
+        T: Clone + Into<Vec<u8>>,
+    {
+
+        write_update_receipt(self)
+    }
+}
+
+impl MultiProofProto {
+    /// Encodes the given `proof`, tagged with the current schema `VERSION`.
+    pub fn from_multi_proof(proof: &MultiProof) -> Self {
+        MultiProofProto {
+            version: VERSION,
+            algorithm_tag: u32::from(algorithm_tag(proof.algorithm)),
+            scheme: u32::from(scheme_tag(proof.scheme)),
+            count: proof.count as u64,
+            root: Some(MultiProofNodeProto::from_node(&proof.root)),
+        }
+    }
+
+    /// Decodes this message into a `MultiProof` with the given hashing `algorithm`.
+    pub fn try_into_multi_proof(self, algorithm: &'static Algorithm) -> Result<MultiProof, Error> {
+        check_version(self.version)?;
+        check_proto_algorithm_tag(algorithm, self.algorithm_tag)?;
+
+        let root = match self.root {
+            Some(root) => root.try_into_node(algorithm, 0)?,
+            None => return Err(Error::MissingField("root")),
+        };
+
+        Ok(MultiProof {
+            algorithm: algorithm,
+            hasher: None,
+            scheme: decode_proto_scheme(self.scheme)?,
+            count: usize::try_from(self.count).map_err(|_| Error::Decode("leaf count overflows usize".to_string()))?,
+            root: root,
+        })
+    }
+}
+
+impl MultiProofNodeProto {
+    /// Encodes the given `node`, along with its children.
+    pub fn from_node(node: &MultiProofNode) -> Self {
+        let (left, right) = match *node {
+            MultiProofNode::Node { ref left, ref right, .. } => {
+                (Some(Box::new(Self::from_node(left))), Some(Box::new(Self::from_node(right))))
+            }
+            _ => (None, None),
+        };
+
+        MultiProofNodeProto {
+            hash: node.hash().clone(),
+            kind: node.proto_kind(),
+            left: left,
+            right: right,
+        }
+    }
+
+    /// Decodes this message into the node `depth` levels below the root of a `MultiProof`
+    /// with the given hashing `algorithm`.
+    fn try_into_node(self, algorithm: &'static Algorithm, depth: usize) -> Result<MultiProofNode, Error> {
+        if depth > MAX_TREE_DEPTH {
+            return Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"));
+        }
+
+        check_hash_len(algorithm, &self.hash)?;
+
+        let children = match (self.left, self.right) {
+            (None, None) => None,
+            (Some(left), Some(right)) => Some((
+                left.try_into_node(algorithm, depth + 1)?,
+                right.try_into_node(algorithm, depth + 1)?,
+            )),
+            (None, Some(_)) => return Err(Error::MissingField("left")),
+            (Some(_), None) => return Err(Error::MissingField("right")),
+        };
+
+        MultiProofNode::from_proto(self.kind, self.hash, children)
+    }
+}
+
+impl PrefixProofProto {
+    /// Encodes the given `proof`, tagged with the current schema `VERSION`.
+    pub fn from_prefix_proof(proof: &PrefixProof) -> Self {
+        PrefixProofProto {
+            version: VERSION,
+            algorithm_tag: u32::from(algorithm_tag(proof.algorithm)),
+            scheme: u32::from(scheme_tag(proof.scheme)),
+            hashes: proof.hashes.clone(),
+        }
+    }
+
+    /// Decodes this message into a `PrefixProof` with the given hashing `algorithm`.
+    pub fn try_into_prefix_proof(self, algorithm: &'static Algorithm) -> Result<PrefixProof, Error> {
+        check_version(self.version)?;
+        check_proto_algorithm_tag(algorithm, self.algorithm_tag)?;
+
+        if self.hashes.len() > MAX_TREE_DEPTH + 1 {
+            return Err(Error::InvalidPath("proof has more hashes than any tree needs"));
+        }
+
+        for hash in &self.hashes {
+            check_hash_len(algorithm, hash)?;
+        }
+
+        Ok(PrefixProof {
+            algorithm: algorithm,
+            hasher: None,
+            scheme: decode_proto_scheme(self.scheme)?,
+            hashes: self.hashes,
+        })
+    }
+}
+
+impl UpdateReceiptProto {
+    /// Encodes the given `receipt`, tagged with the current schema `VERSION`.
+    pub fn from_receipt<T>(receipt: UpdateReceipt<T>) -> Self
+    where
+        T: Into<Vec<u8>>,
+    {
+
+        UpdateReceiptProto {
+            version: VERSION,
+            old_root: receipt.old_root,
+            new_root: receipt.new_root,
+            old_proof: Some(ProofProto::from_proof(receipt.old_proof)),
+            new_proof: Some(ProofProto::from_proof(receipt.new_proof)),
+        }
+    }
+
+    /// Decodes this message into an `UpdateReceipt` whose proofs have the given
+    /// hashing `algorithm`.
+    pub fn try_into_receipt<T>(self, algorithm: &'static Algorithm) -> Result<UpdateReceipt<T>, Error>
+    where
+        T: From<Vec<u8>>,
+    {
+
+        check_version(self.version)?;
+
+        if self.old_root.is_empty() {
+            return Err(Error::MissingField("old_root"));
+        }
+
+        if self.new_root.is_empty() {
+            return Err(Error::MissingField("new_root"));
+        }
+
+        let (old_proof, new_proof) = match (self.old_proof, self.new_proof) {
+            (Some(old_proof), Some(new_proof)) => (old_proof, new_proof),
+            (None, _) => return Err(Error::MissingField("old_proof")),
+            (_, None) => return Err(Error::MissingField("new_proof")),
+        };
+
+        check_hash_len(algorithm, &self.old_root)?;
+        check_hash_len(algorithm, &self.new_root)?;
+
+        Ok(UpdateReceipt {
+            old_root: self.old_root,
+            new_root: self.new_root,
+            old_proof: old_proof.try_into_proof(algorithm)?,
+            new_proof: new_proof.try_into_proof(algorithm)?,
+        })
+    }
+}
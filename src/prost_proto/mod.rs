@@ -0,0 +1,209 @@
+//! Protobuf (de)serialization of proofs with prost, following the schema in `protos/merkle.proto`.
+//!
+//! The encoding is the same as that of the `proto` module, so proofs written by one
+//! backend can be read by the other. Versions are handled the same way, but unlike
+//! the `proto` module, unknown fields are skipped rather than preserved.
+//!
+//! Unless the `serialization-protobuf` feature is enabled too, this module provides
+//! `Proof::parse_protobuf` and `Proof::write_protobuf`, and the methods of the same name
+//! of `MultiProof`, `PrefixProof` and `UpdateReceipt`.
+
+mod batch;
+mod proof;
+
+use digest::Algorithm;
+
+use prost::Message;
+
+use error::Error;
+use proof::{Proof, Lemma, Positioned};
+use wire::{algorithm_tag, encode_siblings, decode_compact_lemma};
+pub use self::proof::{ProofProto, LemmaProto, SiblingHash};
+pub use self::proof::{MultiProofProto, MultiProofNodeProto, PrefixProofProto, UpdateReceiptProto};
+pub use self::batch::{write_multi_proof, parse_multi_proof, write_prefix_proof, parse_prefix_proof};
+pub use self::batch::{write_update_receipt, parse_update_receipt};
+
+/// The version of the schema implied by messages which have no `version` field.
+pub const LEGACY_VERSION: u32 = 0;
+
+/// The latest version of the schema, which is the one written by this crate.
+pub const VERSION: u32 = 1;
+
+/// Encodes the given `proof` with prost.
+pub fn write_proof<T>(proof: &Proof<T>) -> Vec<u8>
+where
+    T: Clone + Into<Vec<u8>>,
+{
+
+    ProofProto::from_proof(proof.clone()).encode_to_vec()
+}
+
+/// Encodes the given `proof` in compact form with prost, see `Proof::write_compact_protobuf`.
+pub fn write_compact_proof<T>(proof: &Proof<T>) -> Vec<u8>
+where
+    T: Clone + Into<Vec<u8>>,
+{
+
+    ProofProto::from_proof_compact(proof.clone()).encode_to_vec()
+}
+
+/// Decodes a proof encoded with the given hashing `algorithm` with prost.
+pub fn parse_proof<T>(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Proof<T>, Error>
+where
+    T: From<Vec<u8>>,
+{
+
+    ProofProto::decode(bytes)
+        .map_err(|err| Error::Decode(err.to_string()))
+        .and_then(|proto| proto.try_into_proof(algorithm))
+}
+
+#[cfg(not(feature = "serialization-protobuf"))]
+impl<T> Proof<T> {
+    /// Parse a `Proof` from its Protobuf binary representation,
+    /// failing with `Error::UnsupportedVersion` if it was written with a newer schema.
+    pub fn parse_protobuf(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error>
+    where
+        T: From<Vec<u8>>,
+    {
+
+        parse_proof(algorithm, bytes)
+    }
+
+    /// Serialize this `Proof` with Protobuf, tagged with the current schema `VERSION`.
+    pub fn write_protobuf(&self) -> Vec<u8>
+    where
+        T: Clone + Into<Vec<u8>>,
+    {
+
+        write_proof(self)
+    }
+
+    /// Works like `Proof::write_protobuf`, but writes the compact form of the proof: its hashing
+    /// algorithm, leaf hash and sibling hashes, as laid out by `wire::encode_siblings`,
+    /// rather than its lemma chain. The node hashes are recomputed when parsing it,
+    /// with the default `HashingScheme`.
+    pub fn write_compact_protobuf(&self) -> Vec<u8>
+    where
+        T: Clone + Into<Vec<u8>>,
+    {
+
+        write_compact_proof(self)
+    }
+}
+
+impl ProofProto {
+    /// Encodes the given `proof`, tagged with the current schema `VERSION`.
+    pub fn from_proof<T>(proof: Proof<T>) -> Self
+    where
+        T: Into<Vec<u8>>,
+    {
+
+        ProofProto {
+            root_hash: proof.root_hash,
+            lemma: Some(LemmaProto::from_lemma(proof.lemma)),
+            value: proof.value.into(),
+            version: VERSION,
+            ..ProofProto::default()
+        }
+    }
+
+    /// Encodes the given `proof` in compact form, see `Proof::write_compact_protobuf`.
+    pub fn from_proof_compact<T>(proof: Proof<T>) -> Self
+    where
+        T: Into<Vec<u8>>,
+    {
+
+        let leaf_hash = proof.leaf_hash().to_vec();
+        let siblings = encode_siblings(proof.siblings());
+
+        ProofProto {
+            root_hash: proof.root_hash,
+            lemma: None,
+            value: proof.value.into(),
+            version: VERSION,
+            algorithm_tag: u32::from(algorithm_tag(proof.algorithm)),
+            leaf_hash: leaf_hash,
+            siblings: siblings,
+        }
+    }
+
+    /// Checks that this message was written with a supported version of the schema.
+    pub fn check_version(&self) -> Result<(), Error> {
+        check_version(self.version)
+    }
+
+    /// Decodes this message into a `Proof` with the given hashing `algorithm`.
+    pub fn try_into_proof<T>(self, algorithm: &'static Algorithm) -> Result<Proof<T>, Error>
+    where
+        T: From<Vec<u8>>,
+    {
+
+        self.check_version()?;
+
+        if self.root_hash.is_empty() {
+            return Err(Error::MissingField("root_hash"));
+        }
+
+        let lemma = match self.lemma {
+            Some(lemma) => lemma.try_into_lemma()?,
+            None if self.algorithm_tag != 0 => {
+                decode_compact_lemma(algorithm, self.algorithm_tag, self.leaf_hash, &self.siblings)?
+            }
+            None => return Err(Error::MissingField("lemma")),
+        };
+
+        Ok(Proof::new(algorithm, self.root_hash, lemma, self.value.into()))
+    }
+}
+
+/// Checks that a message was written with a supported `version` of the schema.
+fn check_version(version: u32) -> Result<(), Error> {
+    if version > VERSION {
+        return Err(Error::UnsupportedVersion {
+            found: version,
+            max_supported: VERSION,
+        });
+    }
+
+    Ok(())
+}
+
+impl LemmaProto {
+    /// Encodes the given `lemma`, along with its sub lemmas.
+    pub fn from_lemma(lemma: Lemma) -> Self {
+        let sibling_hash = lemma.sibling_hash.map(|sibling| match sibling {
+            Positioned::Left(hash) => SiblingHash::LeftSiblingHash(hash),
+            Positioned::Right(hash) => SiblingHash::RightSiblingHash(hash),
+        });
+
+        LemmaProto {
+            node_hash: lemma.node_hash,
+            sub_lemma: lemma.sub_lemma.map(|l| Box::new(Self::from_lemma(*l))),
+            sibling_hash: sibling_hash,
+        }
+    }
+
+    /// Decodes this message into a `Lemma`.
+    pub fn try_into_lemma(self) -> Result<Lemma, Error> {
+        if self.node_hash.is_empty() {
+            return Err(Error::MissingField("node_hash"));
+        }
+
+        let sibling_hash = self.sibling_hash.map(|sibling| match sibling {
+            SiblingHash::LeftSiblingHash(hash) => Positioned::Left(hash),
+            SiblingHash::RightSiblingHash(hash) => Positioned::Right(hash),
+        });
+
+        let sub_lemma = match self.sub_lemma {
+            Some(sub_lemma) => Some(Box::new(sub_lemma.try_into_lemma()?)),
+            None => None,
+        };
+
+        Ok(Lemma {
+            node_hash: self.node_hash,
+            sibling_hash: sibling_hash,
+            sub_lemma: sub_lemma,
+        })
+    }
+}
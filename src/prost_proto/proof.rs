@@ -0,0 +1,122 @@
+// The messages of `protos/merkle.proto`, for prost.
+// Keep in sync with the schema, as `tests/prost.rs` checks against the `proto` module.
+
+#![allow(missing_docs)]
+
+/// See `protos/merkle.proto`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ProofProto {
+    #[prost(bytes = "vec", tag = "1")]
+    pub root_hash: Vec<u8>,
+
+    #[prost(message, optional, tag = "2")]
+    pub lemma: Option<LemmaProto>,
+
+    #[prost(bytes = "vec", tag = "3")]
+    pub value: Vec<u8>,
+
+    #[prost(uint32, tag = "4")]
+    pub version: u32,
+
+    #[prost(uint32, tag = "5")]
+    pub algorithm_tag: u32,
+
+    #[prost(bytes = "vec", tag = "6")]
+    pub leaf_hash: Vec<u8>,
+
+    #[prost(bytes = "vec", tag = "7")]
+    pub siblings: Vec<u8>,
+}
+
+/// See `protos/merkle.proto`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LemmaProto {
+    #[prost(bytes = "vec", tag = "1")]
+    pub node_hash: Vec<u8>,
+
+    #[prost(message, optional, boxed, tag = "2")]
+    pub sub_lemma: Option<Box<LemmaProto>>,
+
+    #[prost(oneof = "SiblingHash", tags = "3, 4")]
+    pub sibling_hash: Option<SiblingHash>,
+}
+
+/// The `sibling_hash` oneof of `LemmaProto`.
+#[derive(Clone, PartialEq, ::prost::Oneof)]
+pub enum SiblingHash {
+    #[prost(bytes, tag = "3")]
+    LeftSiblingHash(Vec<u8>),
+
+    #[prost(bytes, tag = "4")]
+    RightSiblingHash(Vec<u8>),
+}
+
+/// See `protos/merkle.proto`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MultiProofProto {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+
+    #[prost(uint32, tag = "2")]
+    pub algorithm_tag: u32,
+
+    #[prost(uint32, tag = "3")]
+    pub scheme: u32,
+
+    #[prost(uint64, tag = "4")]
+    pub count: u64,
+
+    #[prost(message, optional, tag = "5")]
+    pub root: Option<MultiProofNodeProto>,
+}
+
+/// See `protos/merkle.proto`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MultiProofNodeProto {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+
+    #[prost(uint32, tag = "2")]
+    pub kind: u32,
+
+    #[prost(message, optional, boxed, tag = "3")]
+    pub left: Option<Box<MultiProofNodeProto>>,
+
+    #[prost(message, optional, boxed, tag = "4")]
+    pub right: Option<Box<MultiProofNodeProto>>,
+}
+
+/// See `protos/merkle.proto`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PrefixProofProto {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+
+    #[prost(uint32, tag = "2")]
+    pub algorithm_tag: u32,
+
+    #[prost(uint32, tag = "3")]
+    pub scheme: u32,
+
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub hashes: Vec<Vec<u8>>,
+}
+
+/// See `protos/merkle.proto`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateReceiptProto {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+
+    #[prost(bytes = "vec", tag = "2")]
+    pub old_root: Vec<u8>,
+
+    #[prost(bytes = "vec", tag = "3")]
+    pub new_root: Vec<u8>,
+
+    #[prost(message, optional, tag = "4")]
+    pub old_proof: Option<ProofProto>,
+
+    #[prost(message, optional, tag = "5")]
+    pub new_proof: Option<ProofProto>,
+}
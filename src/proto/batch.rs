@@ -0,0 +1,237 @@
+use std::convert::TryFrom;
+
+use digest::Algorithm;
+
+use protobuf::{Message, RepeatedField};
+use protobuf::core::parse_from_bytes;
+
+use cas::UpdateReceipt;
+use error::Error;
+use multiproof::{MultiProof, MultiProofNode};
+use prefix::PrefixProof;
+use proof::MAX_TREE_DEPTH;
+use wire::{algorithm_tag, scheme_tag, check_proto_algorithm_tag, decode_proto_scheme, check_hash_len};
+use super::{check_version, VERSION};
+use super::proof::{ProofProto, MultiProofProto, MultiProofNodeProto, PrefixProofProto, UpdateReceiptProto};
+
+impl MultiProof {
+    /// Parse a `MultiProof` from its Protobuf binary representation, which must have been
+    /// produced with the given hashing `algorithm`.
+    pub fn parse_protobuf(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error> {
+        parse_from_bytes::<MultiProofProto>(bytes)
+            .map_err(|err| Error::Decode(err.to_string()))
+            .and_then(|proto| proto.try_into_multi_proof(algorithm))
+    }
+
+    /// Serialize this `MultiProof` with Protobuf, tagged with the current schema `VERSION`.
+    pub fn write_protobuf(&self) -> Vec<u8> {
+        MultiProofProto::from_multi_proof(self)
+            .write_to_bytes()
+            .expect("a multiproof always has every field required by the schema")
+    }
+}
+
+impl PrefixProof {
+    /// Parse a `PrefixProof` from its Protobuf binary representation, which must have been
+    /// produced with the given hashing `algorithm`.
+    pub fn parse_protobuf(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error> {
+        parse_from_bytes::<PrefixProofProto>(bytes)
+            .map_err(|err| Error::Decode(err.to_string()))
+            .and_then(|proto| proto.try_into_prefix_proof(algorithm))
+    }
+
+    /// Serialize this `PrefixProof` with Protobuf, tagged with the current schema `VERSION`.
+    pub fn write_protobuf(&self) -> Vec<u8> {
+        PrefixProofProto::from_prefix_proof(self)
+            .write_to_bytes()
+            .expect("a prefix proof always has every field required by the schema")
+    }
+}
+
+impl<T> UpdateReceipt<T> {
+    /// Parse an `UpdateReceipt` from its Protobuf binary representation, whose proofs
+    /// must have been produced with the given hashing `algorithm`.
+    pub fn parse_protobuf(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error>
+    where
+        T: From<Vec<u8>>,
+    {
+
+        parse_from_bytes::<UpdateReceiptProto>(bytes)
+            .map_err(|err| Error::Decode(err.to_string()))
+            .and_then(|proto| proto.try_into_receipt(algorithm))
+    }
+
+    /// Serialize this `UpdateReceipt` with Protobuf, tagged with the current schema `VERSION`.
+    pub fn write_protobuf(&self) -> Vec<u8>
+    where
+        T: Clone + Into<Vec<u8>>,
+    {
+
+        UpdateReceiptProto::from_receipt(self.clone())
+            .write_to_bytes()
+            .expect("a receipt always has every field required by the schema")
+    }
+}
+
+impl MultiProofProto {
+    /// Encodes the given `proof`, tagged with the current schema `VERSION`.
+    pub fn from_multi_proof(proof: &MultiProof) -> Self {
+        let mut proto = Self::new();
+
+        proto.set_version(VERSION);
+        proto.set_algorithm_tag(u32::from(algorithm_tag(proof.algorithm)));
+        proto.set_scheme(u32::from(scheme_tag(proof.scheme)));
+        proto.set_count(proof.count as u64);
+        proto.set_root(MultiProofNodeProto::from_node(&proof.root));
+
+        proto
+    }
+
+    /// Decodes this message into a `MultiProof` with the given hashing `algorithm`.
+    pub fn try_into_multi_proof(mut self, algorithm: &'static Algorithm) -> Result<MultiProof, Error> {
+        check_version(self.get_version())?;
+        check_proto_algorithm_tag(algorithm, self.get_algorithm_tag())?;
+
+        if !self.has_root() {
+            return Err(Error::MissingField("root"));
+        }
+
+        Ok(MultiProof {
+            algorithm: algorithm,
+            hasher: None,
+            scheme: decode_proto_scheme(self.get_scheme())?,
+            count: usize::try_from(self.get_count())
+                .map_err(|_| Error::Decode("leaf count overflows usize".to_string()))?,
+            root: self.take_root().try_into_node(algorithm, 0)?,
+        })
+    }
+}
+
+impl MultiProofNodeProto {
+    /// Encodes the given `node`, along with its children.
+    pub fn from_node(node: &MultiProofNode) -> Self {
+        let mut proto = Self::new();
+
+        proto.set_hash(node.hash().clone());
+        proto.set_kind(node.proto_kind());
+
+        if let MultiProofNode::Node { ref left, ref right, .. } = *node {
+            proto.set_left(Self::from_node(left));
+            proto.set_right(Self::from_node(right));
+        }
+
+        proto
+    }
+
+    /// Decodes this message into the node `depth` levels below the root of a `MultiProof`
+    /// with the given hashing `algorithm`.
+    fn try_into_node(mut self, algorithm: &'static Algorithm, depth: usize) -> Result<MultiProofNode, Error> {
+        if depth > MAX_TREE_DEPTH {
+            return Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"));
+        }
+
+        check_hash_len(algorithm, self.get_hash())?;
+
+        let children = match (self.has_left(), self.has_right()) {
+            (false, false) => None,
+            (true, true) => Some((
+                self.take_left().try_into_node(algorithm, depth + 1)?,
+                self.take_right().try_into_node(algorithm, depth + 1)?,
+            )),
+            (false, true) => return Err(Error::MissingField("left")),
+            (true, false) => return Err(Error::MissingField("right")),
+        };
+
+        MultiProofNode::from_proto(self.get_kind(), self.take_hash(), children)
+    }
+}
+
+impl PrefixProofProto {
+    /// Encodes the given `proof`, tagged with the current schema `VERSION`.
+    pub fn from_prefix_proof(proof: &PrefixProof) -> Self {
+        let mut proto = Self::new();
+
+        proto.set_version(VERSION);
+        proto.set_algorithm_tag(u32::from(algorithm_tag(proof.algorithm)));
+        proto.set_scheme(u32::from(scheme_tag(proof.scheme)));
+        proto.set_hashes(RepeatedField::from_vec(proof.hashes.clone()));
+
+        proto
+    }
+
+    /// Decodes this message into a `PrefixProof` with the given hashing `algorithm`.
+    pub fn try_into_prefix_proof(mut self, algorithm: &'static Algorithm) -> Result<PrefixProof, Error> {
+        check_version(self.get_version())?;
+        check_proto_algorithm_tag(algorithm, self.get_algorithm_tag())?;
+
+        if self.get_hashes().len() > MAX_TREE_DEPTH + 1 {
+            return Err(Error::InvalidPath("proof has more hashes than any tree needs"));
+        }
+
+        for hash in self.get_hashes() {
+            check_hash_len(algorithm, hash)?;
+        }
+
+        Ok(PrefixProof {
+            algorithm: algorithm,
+            hasher: None,
+            scheme: decode_proto_scheme(self.get_scheme())?,
+            hashes: self.take_hashes().into_vec(),
+        })
+    }
+}
+
+impl UpdateReceiptProto {
+    /// Encodes the given `receipt`, tagged with the current schema `VERSION`.
+    pub fn from_receipt<T>(receipt: UpdateReceipt<T>) -> Self
+    where
+        T: Into<Vec<u8>>,
+    {
+
+        let mut proto = Self::new();
+
+        proto.set_version(VERSION);
+        proto.set_old_root(receipt.old_root);
+        proto.set_new_root(receipt.new_root);
+        proto.set_old_proof(ProofProto::from_proof(receipt.old_proof));
+        proto.set_new_proof(ProofProto::from_proof(receipt.new_proof));
+
+        proto
+    }
+
+    /// Decodes this message into an `UpdateReceipt` whose proofs have the given
+    /// hashing `algorithm`.
+    pub fn try_into_receipt<T>(mut self, algorithm: &'static Algorithm) -> Result<UpdateReceipt<T>, Error>
+    where
+        T: From<Vec<u8>>,
+    {
+
+        check_version(self.get_version())?;
+
+        if self.get_old_root().is_empty() {
+            return Err(Error::MissingField("old_root"));
+        }
+
+        if self.get_new_root().is_empty() {
+            return Err(Error::MissingField("new_root"));
+        }
+
+        if !self.has_old_proof() {
+            return Err(Error::MissingField("old_proof"));
+        }
+
+        if !self.has_new_proof() {
+            return Err(Error::MissingField("new_proof"));
+        }
+
+        check_hash_len(algorithm, self.get_old_root())?;
+        check_hash_len(algorithm, self.get_new_root())?;
+
+        Ok(UpdateReceipt {
+            old_root: self.take_old_root(),
+            new_root: self.take_new_root(),
+            old_proof: self.take_old_proof().try_into_proof(algorithm)?,
+            new_proof: self.take_new_proof().try_into_proof(algorithm)?,
+        })
+    }
+}
@@ -1,15 +1,43 @@
+//! Protobuf (de)serialization of proofs, following the schema in `protos/merkle.proto`.
+//!
+//! Every `ProofProto` written by this crate carries the schema `VERSION`.
+//! Messages without a `version` field were written before it was introduced,
+//! and are read as `LEGACY_VERSION`. Messages with a version greater than `VERSION`
+//! are rejected with `Error::UnsupportedVersion` rather than being misparsed.
+//!
+//! Proofs are written with their whole lemma chain by default, or in the compact form of
+//! `Proof::write_compact_protobuf`, where each level takes a direction byte and a sibling hash.
+//! Both forms are read by the same methods.
+//!
+//! Fields unknown to this schema are accepted, and kept by `ProofProto` so that
+//! decoding then re-encoding a message does not lose them. Converting a `ProofProto`
+//! into a `Proof` drops them.
+//!
+//! `MultiProof`, `PrefixProof` and `UpdateReceipt` are encoded with the messages of the same
+//! name, which are versioned the same way. Their hashes must have the output length of the
+//! hashing algorithm, and their paths be at most `MAX_TREE_DEPTH` levels deep.
 
+mod batch;
 mod proof;
 
-use ring::digest::Algorithm;
+use digest::Algorithm;
 
+use error::Error;
 use proof::{Proof, Lemma, Positioned};
+use wire::{algorithm_tag, encode_siblings, decode_compact_lemma};
 pub use self::proof::{ProofProto, LemmaProto};
+pub use self::proof::{MultiProofProto, MultiProofNodeProto, PrefixProofProto, UpdateReceiptProto};
 
 use protobuf::Message;
 use protobuf::error::ProtobufResult;
 use protobuf::core::parse_from_bytes;
 
+/// The version of the schema implied by messages which have no `version` field.
+pub const LEGACY_VERSION: u32 = 0;
+
+/// The latest version of the schema, which is the one written by this crate.
+pub const VERSION: u32 = 1;
+
 impl<T> Proof<T> {
     /// Constructs a `Proof` struct from its Protobuf representation.
     pub fn from_protobuf(algorithm: &'static Algorithm, proto: ProofProto) -> Option<Self>
@@ -30,6 +58,9 @@ impl<T> Proof<T> {
     }
 
     /// Parse a `Proof` from its Protobuf binary representation.
+    ///
+    /// Returns `Ok(None)` if the message is incomplete or has an unsupported version;
+    /// use `parse_protobuf` to find out why.
     pub fn parse_from_bytes(
         bytes: &[u8],
         algorithm: &'static Algorithm,
@@ -49,9 +80,47 @@ impl<T> Proof<T> {
 
         self.into_protobuf().write_to_bytes()
     }
+
+    /// Parse a `Proof` from its Protobuf binary representation,
+    /// failing with `Error::UnsupportedVersion` if it was written with a newer schema.
+    pub fn parse_protobuf(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error>
+    where
+        T: From<Vec<u8>>,
+    {
+
+        parse_from_bytes::<ProofProto>(bytes)
+            .map_err(|err| Error::Decode(err.to_string()))
+            .and_then(|proto| proto.try_into_proof(algorithm))
+    }
+
+    /// Serialize this `Proof` with Protobuf, tagged with the current schema `VERSION`.
+    pub fn write_protobuf(&self) -> Vec<u8>
+    where
+        T: Clone + Into<Vec<u8>>,
+    {
+
+        self.clone()
+            .write_to_bytes()
+            .expect("a proof always has every field required by the schema")
+    }
+
+    /// Works like `Proof::write_protobuf`, but writes the compact form of the proof: its hashing
+    /// algorithm, leaf hash and sibling hashes, as laid out by `wire::encode_siblings`,
+    /// rather than its lemma chain. The node hashes are recomputed when parsing it,
+    /// with the default `HashingScheme`.
+    pub fn write_compact_protobuf(&self) -> Vec<u8>
+    where
+        T: Clone + Into<Vec<u8>>,
+    {
+
+        ProofProto::from_proof_compact(self.clone())
+            .write_to_bytes()
+            .expect("a proof always has every field required by the schema")
+    }
 }
 
 impl ProofProto {
+    /// Encodes the given `proof`, tagged with the current schema `VERSION`.
     pub fn from_proof<T>(proof: Proof<T>) -> Self
     where
         T: Into<Vec<u8>>,
@@ -69,22 +138,74 @@ impl ProofProto {
                 proto.set_root_hash(root_hash);
                 proto.set_lemma(LemmaProto::from_lemma(lemma));
                 proto.set_value(value.into());
+                proto.set_version(VERSION);
             }
         }
 
         proto
     }
 
-    pub fn into_proof<T>(mut self, algorithm: &'static Algorithm) -> Option<Proof<T>>
+    /// Encodes the given `proof` in compact form, see `Proof::write_compact_protobuf`.
+    pub fn from_proof_compact<T>(proof: Proof<T>) -> Self
+    where
+        T: Into<Vec<u8>>,
+    {
+
+        let mut proto = Self::new();
+
+        proto.set_algorithm_tag(u32::from(algorithm_tag(proof.algorithm)));
+        proto.set_leaf_hash(proof.leaf_hash().to_vec());
+        proto.set_siblings(encode_siblings(proof.siblings()));
+        proto.set_root_hash(proof.root_hash);
+        proto.set_value(proof.value.into());
+        proto.set_version(VERSION);
+
+        proto
+    }
+
+    /// Checks that this message was written with a supported version of the schema.
+    pub fn check_version(&self) -> Result<(), Error> {
+        check_version(self.get_version())
+    }
+
+    /// Decodes this message into a `Proof` with the given hashing `algorithm`,
+    /// or returns `None` if it is incomplete or has an unsupported version.
+    pub fn into_proof<T>(self, algorithm: &'static Algorithm) -> Option<Proof<T>>
+    where
+        T: From<Vec<u8>>,
+    {
+
+        self.try_into_proof(algorithm).ok()
+    }
+
+    /// Decodes this message into a `Proof` with the given hashing `algorithm`.
+    pub fn try_into_proof<T>(mut self, algorithm: &'static Algorithm) -> Result<Proof<T>, Error>
     where
         T: From<Vec<u8>>,
     {
 
-        if !self.has_root_hash() || !self.has_lemma() {
-            return None;
+        self.check_version()?;
+
+        if self.get_root_hash().is_empty() {
+            return Err(Error::MissingField("root_hash"));
         }
 
-        self.take_lemma().into_lemma().map(|lemma| {
+        if !self.has_lemma() && self.get_algorithm_tag() != 0 {
+            let lemma = decode_compact_lemma(
+                algorithm,
+                self.get_algorithm_tag(),
+                self.take_leaf_hash(),
+                self.get_siblings(),
+            )?;
+
+            return Ok(Proof::new(algorithm, self.take_root_hash(), lemma, self.take_value().into()));
+        }
+
+        if !self.has_lemma() {
+            return Err(Error::MissingField("lemma"));
+        }
+
+        self.take_lemma().try_into_lemma().map(|lemma| {
             Proof::new(
                 algorithm,
                 self.take_root_hash(),
@@ -95,7 +216,20 @@ impl ProofProto {
     }
 }
 
+/// Checks that a message was written with a supported `version` of the schema.
+fn check_version(version: u32) -> Result<(), Error> {
+    if version > VERSION {
+        return Err(Error::UnsupportedVersion {
+            found: version,
+            max_supported: VERSION,
+        });
+    }
+
+    Ok(())
+}
+
 impl LemmaProto {
+    /// Encodes the given `lemma`, along with its sub lemmas.
     pub fn from_lemma(lemma: Lemma) -> Self {
         let mut proto = Self::new();
 
@@ -124,9 +258,15 @@ impl LemmaProto {
         proto
     }
 
-    pub fn into_lemma(mut self) -> Option<Lemma> {
-        if !self.has_node_hash() {
-            return None;
+    /// Decodes this message into a `Lemma`, or returns `None` if it is incomplete.
+    pub fn into_lemma(self) -> Option<Lemma> {
+        self.try_into_lemma().ok()
+    }
+
+    /// Decodes this message into a `Lemma`.
+    pub fn try_into_lemma(mut self) -> Result<Lemma, Error> {
+        if self.get_node_hash().is_empty() {
+            return Err(Error::MissingField("node_hash"));
         }
 
         let node_hash = self.take_node_hash();
@@ -142,8 +282,8 @@ impl LemmaProto {
         if self.has_sub_lemma() {
             // If a `sub_lemma` is present is the Protobuf,
             // then we expect it to unserialize to a valid `Lemma`,
-            // otherwise we return `None`
-            self.take_sub_lemma().into_lemma().map(|sub_lemma| {
+            // otherwise we fail
+            self.take_sub_lemma().try_into_lemma().map(|sub_lemma| {
                 Lemma {
                     node_hash: node_hash,
                     sibling_hash: sibling_hash,
@@ -154,7 +294,7 @@ impl LemmaProto {
             // We might very well not have a sub_lemma,
             // in which case we just set it to `None`,
             // but still return a potentially valid `Lemma`.
-            Some(Lemma {
+            Ok(Lemma {
                 node_hash: node_hash,
                 sibling_hash: sibling_hash,
                 sub_lemma: None,
@@ -0,0 +1,117 @@
+use all_proofs::{AllProofs, LeafPath};
+use merkletree::MerkleTree;
+use proof::Proof;
+use tree::Tree;
+
+/// Yields the value of every leaf of a `MerkleTree` along with its inclusion proof,
+/// from left to right. See `MerkleTree::proven_iter`.
+#[allow(missing_debug_implementations)]
+pub struct ProvenIter<'a, T: 'a> {
+    proofs: AllProofs<'a, T>,
+}
+
+impl<'a, T: Clone> Iterator for ProvenIter<'a, T> {
+    type Item = (T, Proof<T>);
+
+    fn next(&mut self) -> Option<(T, Proof<T>)> {
+        self.proofs.next().map(|proof| (proof.value.clone(), proof))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.proofs.size_hint()
+    }
+}
+
+impl<'a, T: Clone> ExactSizeIterator for ProvenIter<'a, T> {}
+
+/// Moves the value of every leaf out of a `MerkleTree`, from left to right, along with
+/// its inclusion proof. See `MerkleTree::into_proven_iter`.
+#[allow(missing_debug_implementations)]
+pub struct IntoProvenIter<T> {
+    /// The tree the proofs are made for, whose inner tree is moved out into `root`
+    tree: MerkleTree<T>,
+
+    /// The inner tree, whose leaves are pruned as their values are moved out
+    root: Tree<T>,
+
+    path: LeafPath,
+    remaining: usize,
+}
+
+impl<T: Clone> Iterator for IntoProvenIter<T> {
+    type Item = (T, Proof<T>);
+
+    fn next(&mut self) -> Option<(T, Proof<T>)> {
+        let leaf = self.path.leaf()?;
+        let lemma = self.path.lemma(&self.root);
+
+        self.path.advance(&self.root);
+        self.remaining -= 1;
+
+        let value = self.root.take_value(leaf).expect("the path always ends at a leaf");
+        let root_hash = lemma.node_hash.clone();
+        let proof = self.tree.new_proof(root_hash, lemma, value.clone(), self.tree.count());
+
+        Some((value, proof))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T: Clone> ExactSizeIterator for IntoProvenIter<T> {}
+
+impl<T> MerkleTree<T> {
+    /// Returns an iterator over the values of the leaves along with their inclusion proofs,
+    /// from left to right, as `MerkleTree::iter_proofs` yields the proofs: only the path
+    /// to the current leaf is held, rather than every proof. The pruned leaves are skipped.
+    ///
+    /// Every value is cloned twice, once into its proof and once into the pair,
+    /// see `MerkleTree::into_proven_iter` to clone them only once.
+    pub fn proven_iter(&self) -> ProvenIter<T>
+    where
+        T: Clone,
+    {
+
+        ProvenIter {
+            proofs: self.iter_proofs(),
+        }
+    }
+
+    /// Works like `MerkleTree::proven_iter`, consuming the tree: the values are moved out
+    /// of it, and only cloned into their proof, the hash of each leaf being kept for the proofs
+    /// of the leaves after it.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::MerkleTree;
+    /// # fn main() {
+    /// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c"]);
+    /// let root_hash = tree.root_hash().clone();
+    ///
+    /// for (value, proof) in tree.into_proven_iter() {
+    ///     assert_eq!(proof.value, value);
+    ///     assert!(proof.validate(&root_hash));
+    /// }
+    /// # }
+    /// ```
+    pub fn into_proven_iter(mut self) -> IntoProvenIter<T>
+    where
+        T: Clone,
+    {
+
+        let root = self.take_root();
+        let count = self.count() - root.pruned_leaves();
+        let path = LeafPath::new(&root, count, self.height());
+
+        IntoProvenIter {
+            tree: self,
+            root: root,
+            path: path,
+            remaining: count,
+        }
+    }
+}
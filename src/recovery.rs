@@ -0,0 +1,151 @@
+use std::error;
+use std::fmt;
+use std::ops::Range;
+
+use digest::Algorithm;
+
+use hashutils::Hashable;
+use hex::to_hex;
+use merkletree::MerkleTree;
+
+/// Why `MerkleTree::rebuild_and_verify` did not return the rebuilt tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RebuildError {
+    /// The root hash of the tree rebuilt over the values is not the trusted one
+    RootMismatch {
+        /// The root hash the values were trusted to have
+        trusted_root: Vec<u8>,
+
+        /// The root hash of the tree rebuilt over the values
+        computed_root: Vec<u8>,
+
+        /// The smallest range of leaves found to hold every value which no longer matches
+        /// the hashes of the oracle, if one was given and the search got below the root
+        mismatched: Option<Range<usize>>,
+    },
+}
+
+impl fmt::Display for RebuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RebuildError::RootMismatch { ref trusted_root, ref computed_root, ref mismatched } => {
+                write!(
+                    f,
+                    "rebuilt root {} is not the trusted root {}",
+                    to_hex(computed_root),
+                    to_hex(trusted_root)
+                )?;
+
+                match *mismatched {
+                    Some(ref range) => write!(f, ", leaves {}..{} no longer match", range.start, range.end),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl error::Error for RebuildError {}
+
+impl<T: Hashable> MerkleTree<T> {
+    /// Rebuilds the tree over the given values, as `MerkleTree::from_vec` does, such as when
+    /// the snapshot of its internal nodes is damaged, and checks that it has the given
+    /// `trusted_root` hash, which is known from elsewhere, such as a published tree head.
+    ///
+    /// Fails with `RebuildError::RootMismatch` if the root hash of the rebuilt tree is another.
+    /// Given an `oracle` of the trusted hashes of the nodes of the tree, by level and index
+    /// as in `MerkleTree::node_hash`, such as those of a past snapshot, and `None` for the nodes
+    /// it does not know, the search for the
+    /// values which no longer match then bisects the tree from the root down, querying the two
+    /// children of a node which differs and going down the one which differs, as long as only
+    /// one does, at most two nodes per level: the values found to differ are all in the range
+    /// of leaves it ends at, which is a single leaf if only one value changed.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::{MerkleTree, RebuildError};
+    /// # fn main() {
+    /// let original = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c", "d", "e"]);
+    /// let oracle = |level, index| original.node_hash(level, index).map(|hash| hash.to_vec());
+    ///
+    /// let rebuilt = MerkleTree::rebuild_and_verify(
+    ///     &merkle::digest::SHA256,
+    ///     vec!["a", "b", "c", "d", "e"],
+    ///     original.root_hash(),
+    ///     Some(&oracle),
+    /// );
+    /// assert_eq!(rebuilt.unwrap(), original);
+    ///
+    /// let corrupted = MerkleTree::rebuild_and_verify(
+    ///     &merkle::digest::SHA256,
+    ///     vec!["a", "b", "C", "d", "e"],
+    ///     original.root_hash(),
+    ///     Some(&oracle),
+    /// );
+    ///
+    /// match corrupted {
+    ///     Err(RebuildError::RootMismatch { mismatched, .. }) => assert_eq!(mismatched, Some(2..3)),
+    ///     Ok(_) => panic!("a value changed"),
+    /// }
+    /// # }
+    /// ```
+    pub fn rebuild_and_verify(
+        algorithm: &'static Algorithm,
+        values: Vec<T>,
+        trusted_root: &[u8],
+        oracle: Option<&dyn Fn(usize, usize) -> Option<Vec<u8>>>,
+    ) -> Result<Self, RebuildError> {
+
+        let tree = MerkleTree::from_vec(algorithm, values);
+
+        if tree.root_hash().as_slice() == trusted_root {
+            return Ok(tree);
+        }
+
+        Err(RebuildError::RootMismatch {
+            trusted_root: trusted_root.to_vec(),
+            computed_root: tree.root_hash().clone(),
+            mismatched: oracle.and_then(|oracle| tree.bisect_mismatch(oracle)),
+        })
+    }
+}
+
+impl<T> MerkleTree<T> {
+    /// Descends from the root, which differs from the one of `oracle`, down the child which
+    /// differs from the node of `oracle` at the same position, as long as only one is known to,
+    /// and returns the range of leaves below the node it stops at, or `None` if the tree is empty.
+    fn bisect_mismatch(&self, oracle: &dyn Fn(usize, usize) -> Option<Vec<u8>>) -> Option<Range<usize>> {
+        if self.count() == 0 {
+            return None;
+        }
+
+        // `None` if either tree has no such node
+        let differs = |level, index| match (self.node_hash(level, index), oracle(level, index)) {
+            (Some(local), Some(trusted)) => Some(local != trusted.as_slice()),
+            _ => None,
+        };
+
+        let mut level = self.height();
+        let mut index = 0;
+
+        while level > 0 {
+            let left = differs(level - 1, 2 * index);
+            let right = differs(level - 1, 2 * index + 1);
+
+            index = match (left, right) {
+                (Some(true), Some(false)) | (Some(true), None) => 2 * index,
+                (Some(false), Some(true)) | (None, Some(true)) => 2 * index + 1,
+                _ => break,
+            };
+
+            level -= 1;
+        }
+
+        let start = index << level;
+        let end = ((index + 1) << level).min(self.count());
+
+        Some(start..end)
+    }
+}
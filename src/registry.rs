@@ -0,0 +1,96 @@
+
+use std::sync::RwLock;
+
+use digest::Algorithm;
+
+#[cfg(feature = "serialization-serde")]
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+use hash::AlgorithmTag;
+use wire::algorithm_tag;
+
+/// The names under which the *ring* digest algorithms are known, by tag.
+static NAMES: [(u8, &'static str); 5] = [
+    (1, "SHA-1"),
+    (2, "SHA-256"),
+    (3, "SHA-384"),
+    (4, "SHA-512"),
+    (5, "SHA-512/256"),
+];
+
+/// The name of Keccak-256, which *ring* does not provide, see the `keccak` module.
+///
+/// It is reserved: no *ring* algorithm can be registered under it, so that a proof naming it
+/// fails to be deserialized rather than being read as one of another algorithm.
+pub const KECCAK256_NAME: &'static str = "Keccak-256";
+
+/// The additional names registered with `register_algorithm`.
+static REGISTRY: RwLock<Vec<(String, AlgorithmTag)>> = RwLock::new(Vec::new());
+
+/// Returns the name of the given hashing algorithm, one of `"SHA-1"`, `"SHA-256"`,
+/// `"SHA-384"`, `"SHA-512"` and `"SHA-512/256"`.
+pub fn algorithm_name(algorithm: &'static Algorithm) -> &'static str {
+    let tag = algorithm_tag(algorithm);
+
+    NAMES
+        .iter()
+        .find(|&&(t, _)| t == tag)
+        .map(|&(_, name)| name)
+        .expect("every ring digest algorithm has a name")
+}
+
+/// Returns the hashing algorithm with the given name, if any: either one of the names
+/// returned by `algorithm_name`, or one registered with `register_algorithm`.
+/// Names are case-sensitive.
+pub fn algorithm_from_name(name: &str) -> Option<&'static Algorithm> {
+    let builtin = NAMES.iter().find(|&&(_, n)| n == name).map(|&(tag, _)| tag);
+
+    if let Some(tag) = builtin {
+        return AlgorithmTag::from_byte(tag).map(AlgorithmTag::algorithm);
+    }
+
+    let registry = REGISTRY.read().unwrap_or_else(|err| err.into_inner());
+
+    registry
+        .iter()
+        .find(|&&(ref n, _)| n == name)
+        .map(|&(_, tag)| tag.algorithm())
+}
+
+/// Registers an additional name for the given hashing algorithm, to be looked up by
+/// `algorithm_from_name`, eg. the name used for it in a configuration file.
+///
+/// Only the *ring* digest algorithms can be used to build trees, so this only adds names
+/// for them, and proofs are still serialized with the name returned by `algorithm_name`.
+///
+/// Returns `false`, leaving the registry untouched, if the name is already taken,
+/// or reserved as `KECCAK256_NAME` is.
+pub fn register_algorithm(name: &str, algorithm: &'static Algorithm) -> bool {
+    let mut registry = REGISTRY.write().unwrap_or_else(|err| err.into_inner());
+
+    let taken = name == KECCAK256_NAME || NAMES.iter().any(|&(_, n)| n == name) ||
+        registry.iter().any(|&(ref n, _)| n == name);
+
+    if taken {
+        return false;
+    }
+
+    registry.push((name.to_string(), AlgorithmTag::of(algorithm)));
+    true
+}
+
+/// Serializes a hashing algorithm by name, see `AlgorithmTag`.
+#[cfg(feature = "serialization-serde")]
+pub fn serialize_algorithm<S: Serializer>(
+    algorithm: &&'static Algorithm,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+
+    AlgorithmTag::of(algorithm).serialize(serializer)
+}
+
+/// Deserializes a hashing algorithm by name, see `AlgorithmTag`.
+#[cfg(feature = "serialization-serde")]
+pub fn deserialize_algorithm<'de, D: Deserializer<'de>>(deserializer: D) -> Result<&'static Algorithm, D::Error> {
+    AlgorithmTag::deserialize(deserializer).map(|tag| tag.algorithm())
+}
@@ -0,0 +1,62 @@
+//! JSON Schemas of the serialized proofs, with the `schemars` feature.
+//!
+//! The schemas describe the serde representation, whichever `HashEncoding` the hashes
+//! were serialized with: a hash is either an array of bytes or a hex or base64 string.
+
+use schemars::JsonSchema;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use serde_json;
+
+use hash::AlgorithmTag;
+use proof::Positioned;
+
+/// Builds a schema from its JSON form.
+fn schema(json: serde_json::Value) -> Schema {
+    serde_json::from_value(json).expect("the schema is valid")
+}
+
+/// Stands for a hash in the schemas, as serialized by `hash_encoding`.
+pub(crate) struct Hash;
+
+impl JsonSchema for Hash {
+    fn schema_name() -> String {
+        "Hash".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        schema(serde_json::json!({
+            "description": "A hash, as an array of bytes, a hex string or a base64 string",
+            "anyOf": [
+                {
+                    "type": "array",
+                    "items": { "type": "integer", "minimum": 0, "maximum": 255 }
+                },
+                {
+                    "type": "string",
+                    "pattern": "^[0-9A-Za-z+/_-]*={0,2}$"
+                }
+            ]
+        }))
+    }
+}
+
+impl JsonSchema for AlgorithmTag {
+    fn schema_name() -> String {
+        "Algorithm".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        // Names registered with `register_algorithm` are accepted too, so any string may be one
+        schema(serde_json::json!({
+            "description": "The name of a hashing algorithm, eg. \"SHA-256\"",
+            "type": "string"
+        }))
+    }
+}
+
+/// Stands for a sibling hash in the schemas, as serialized by `hash_encoding::sibling`.
+pub(crate) type Sibling = Option<Positioned<Hash>>;
+
+/// Stands for sibling hashes in the schemas, as serialized by `hash_encoding::siblings`.
+pub(crate) type Siblings = Vec<Positioned<Hash>>;
@@ -0,0 +1,140 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+use digest::{Context, Digest};
+
+use hashutils::Hashable;
+
+/// The length of the longest hash a `SmallHash` holds, which is the output length of SHA-512,
+/// the longest one of the hashing algorithms.
+pub const MAX_OUTPUT_LEN: usize = 64;
+
+/// A hash of at most `MAX_OUTPUT_LEN` bytes, held inline rather than on the heap.
+///
+/// The nodes of a `Tree` hold their hash as a `SmallHash`, since all of them have the output
+/// length of the hashing algorithm of the tree, so that building a tree does not allocate
+/// once per node. Hashes are converted to and from `Vec<u8>` at the public API.
+#[derive(Clone, Copy)]
+pub struct SmallHash {
+    bytes: [u8; MAX_OUTPUT_LEN],
+    len: u8,
+}
+
+impl SmallHash {
+    /// Copies the given hash.
+    ///
+    /// Panics if it is longer than `MAX_OUTPUT_LEN` bytes, which no hashing algorithm outputs.
+    pub fn from_slice(hash: &[u8]) -> Self {
+        assert!(hash.len() <= MAX_OUTPUT_LEN, "hash is longer than `MAX_OUTPUT_LEN`");
+
+        let mut bytes = [0; MAX_OUTPUT_LEN];
+        bytes[..hash.len()].copy_from_slice(hash);
+
+        SmallHash {
+            bytes: bytes,
+            len: hash.len() as u8,
+        }
+    }
+
+    /// Returns the bytes of the hash.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+impl Deref for SmallHash {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for SmallHash {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl<'a> From<&'a [u8]> for SmallHash {
+    fn from(hash: &'a [u8]) -> Self {
+        SmallHash::from_slice(hash)
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for SmallHash {
+    fn from(hash: &'a Vec<u8>) -> Self {
+        SmallHash::from_slice(hash)
+    }
+}
+
+impl From<Vec<u8>> for SmallHash {
+    fn from(hash: Vec<u8>) -> Self {
+        SmallHash::from_slice(&hash)
+    }
+}
+
+impl From<Digest> for SmallHash {
+    fn from(hash: Digest) -> Self {
+        SmallHash::from_slice(hash.as_ref())
+    }
+}
+
+impl PartialEq for SmallHash {
+    fn eq(&self, other: &SmallHash) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for SmallHash {}
+
+impl PartialEq<[u8]> for SmallHash {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+impl PartialEq<Vec<u8>> for SmallHash {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl PartialEq<SmallHash> for Vec<u8> {
+    fn eq(&self, other: &SmallHash) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl PartialOrd for SmallHash {
+    fn partial_cmp(&self, other: &SmallHash) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SmallHash {
+    fn cmp(&self, other: &SmallHash) -> Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+impl Hash for SmallHash {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state)
+    }
+}
+
+impl Hashable for SmallHash {
+    fn update_context(&self, context: &mut Context) {
+        context.update(self.as_slice());
+    }
+}
+
+/// Shows the bytes of the hash, as a `Vec<u8>` would.
+impl fmt::Debug for SmallHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_slice().fmt(f)
+    }
+}
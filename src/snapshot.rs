@@ -0,0 +1,499 @@
+//! A compact binary format for snapshots of whole trees, produced by `MerkleTree::write_snapshot`
+//! and read back by `MerkleTree::read_snapshot`.
+//!
+//! All integers are big-endian. A snapshot is laid out as follows:
+//!
+//! | Size           | Contents                                                       |
+//! |----------------|----------------------------------------------------------------|
+//! | 4              | the magic bytes `MAGIC`, ie. `"MKLT"`                          |
+//! | 1              | the format version, currently `VERSION`                        |
+//! | 1              | the algorithm tag, see `wire::algorithm_tag`                   |
+//! | 1              | the hash length `h`, ie. the algorithm output length           |
+//! | 1              | the flags: `1` if the values are stored, `2` if the internal nodes are, `4` if the nodes are hashed with their leaf counts |
+//! | 1              | the hashing scheme: `0` for `DomainSeparated`, `1` for `Unprefixed`, `2` for `SortedPairs` |
+//! | 1              | the odd leaf policy: `0` for `Promote`, `1` for `Duplicate`    |
+//! | 1              | the fixed depth plus one, or `0` if the tree has none          |
+//! | 8              | the number of leaves `n`                                       |
+//! | `h`            | the root hash                                                  |
+//! | `n * h`        | the leaf hashes, from left to right                            |
+//! |                | if the values are stored, for each leaf from left to right: the length of the value on 4 bytes, followed by the value |
+//! |                | if the internal nodes are stored, their hashes, see below      |
+//!
+//! The internal nodes are stored level by level from the leaves up, and from left to right
+//! within a level, leaving out the nodes which only pad a level, which are recomputed.
+//! There are as many of them as the tree built over `n` leaves with the given options has.
+//!
+//! The order of the leaves, as set with `MerkleTreeBuilder::sorted` or `MerkleTreeBuilder::dedup`,
+//! is not part of the format, and trees read from a snapshot keep their leaves in the stored
+//! order, with the default ordering, as deserialized trees do.
+//!
+//! Inputs with a version greater than `VERSION` are rejected with `Error::UnsupportedVersion`,
+//! and every error is reported along with the position of the offending field, see
+//! `Error::InvalidSnapshot`. This format will never change without its version being bumped.
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use digest::Algorithm;
+
+use error::Error;
+use hasher::{self, MerkleHasher};
+use merkletree::{MerkleTree, internal_node_count};
+use options::{HashingScheme, OddLeafPolicy, TreeOptions};
+use proof::MAX_TREE_DEPTH;
+use small_hash::SmallHash;
+use tree::Leaf;
+use wire::{algorithm_tag, algorithm_from_tag};
+
+/// The magic bytes every snapshot starts with.
+pub const MAGIC: &'static [u8; 4] = b"MKLT";
+
+/// The latest version of the format, which is the one written by `MerkleTree::write_snapshot`.
+pub const VERSION: u8 = 1;
+
+/// The flag telling that the values are stored.
+const VALUES: u8 = 1;
+
+/// The flag telling that the hashes of the internal nodes are stored.
+const NODES: u8 = 2;
+
+/// The flag telling that the tree is built with `MerkleTreeBuilder::leaf_counts`.
+const LEAF_COUNTS: u8 = 4;
+
+/// The position of the root hash in a snapshot.
+const ROOT_OFFSET: u64 = 19;
+
+/// A value which can be stored in a snapshot, see `MerkleTree::write_snapshot`.
+pub trait SnapshotValue: Sized {
+    /// Whether the values are stored, which is not the case for `()`,
+    /// the value of trees which only hold hashes, see `MerkleTree::from_lines_hashed`
+    const STORED: bool;
+
+    /// Returns the bytes stored for this value.
+    fn snapshot_bytes(&self) -> &[u8];
+
+    /// Rebuilds a value from the bytes stored for it, failing with the reason they are invalid.
+    fn from_snapshot_bytes(bytes: Vec<u8>) -> Result<Self, &'static str>;
+}
+
+impl SnapshotValue for () {
+    const STORED: bool = false;
+
+    fn snapshot_bytes(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Drops the stored bytes, if any.
+    fn from_snapshot_bytes(_: Vec<u8>) -> Result<Self, &'static str> {
+        Ok(())
+    }
+}
+
+impl SnapshotValue for Vec<u8> {
+    const STORED: bool = true;
+
+    fn snapshot_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn from_snapshot_bytes(bytes: Vec<u8>) -> Result<Self, &'static str> {
+        Ok(bytes)
+    }
+}
+
+impl SnapshotValue for String {
+    const STORED: bool = true;
+
+    fn snapshot_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+
+    fn from_snapshot_bytes(bytes: Vec<u8>) -> Result<Self, &'static str> {
+        String::from_utf8(bytes).map_err(|_| "value is not valid UTF-8")
+    }
+}
+
+impl<T: SnapshotValue> MerkleTree<T> {
+    /// Writes a snapshot of this Merkle tree in the format described in the `snapshot` module,
+    /// storing its leaf hashes and values, but not its internal nodes, which are recomputed
+    /// by `MerkleTree::read_snapshot`.
+    ///
+    /// The hashes are written one by one, so `writer` had better be buffered.
+    /// Lazy trees are hashed first. Fails with an error of kind `InvalidInput` if a value
+    /// is longer than `u32::MAX` bytes, or if the tree has pruned subtrees.
+    pub fn write_snapshot<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_snapshot_with(writer, false)
+    }
+
+    /// Works like `MerkleTree::write_snapshot`, but stores the hashes of the internal nodes too,
+    /// so that `MerkleTree::read_snapshot` does not recompute them, at the cost of a snapshot
+    /// about twice as large.
+    pub fn write_snapshot_with_nodes<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_snapshot_with(writer, true)
+    }
+
+    fn write_snapshot_with<W: Write>(&self, mut writer: W, nodes: bool) -> io::Result<()> {
+        let tree = self.tree();
+
+        if tree.pruned_leaves() > 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot snapshot a pruned tree"));
+        }
+        let mut flags = 0;
+
+        if T::STORED {
+            flags |= VALUES;
+        }
+
+        if nodes {
+            flags |= NODES;
+        }
+
+        if self.has_leaf_counts() {
+            flags |= LEAF_COUNTS;
+        }
+
+        let depth = self.fixed_depth().map_or(0, |depth| depth as u8 + 1);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[
+            VERSION,
+            algorithm_tag(self.algorithm),
+            self.algorithm.output_len as u8,
+            flags,
+            scheme_to_byte(self.hashing_scheme()),
+            policy_to_byte(self.odd_leaf_policy()),
+            depth,
+        ])?;
+        writer.write_all(&u64_to_bytes(self.count() as u64))?;
+        writer.write_all(tree.hash())?;
+
+        for hash in tree.leaf_hashes() {
+            writer.write_all(hash)?;
+        }
+
+        if T::STORED {
+            for value in tree.iter() {
+                let bytes = value.snapshot_bytes();
+
+                if bytes.len() > u32::MAX as usize {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "value is too long to be stored in a snapshot",
+                    ));
+                }
+
+                writer.write_all(&u32_to_bytes(bytes.len() as u32))?;
+                writer.write_all(bytes)?;
+            }
+        }
+
+        if nodes {
+            for hash in tree.root().internal_hashes() {
+                writer.write_all(hash)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads a snapshot in the format described in the `snapshot` module, written from a tree
+    /// built with the given hashing `algorithm`, stopping at its end.
+    ///
+    /// Unless the snapshot stores them, the hashes of the internal nodes are recomputed,
+    /// and the leaf hashes are checked against the values, if these are stored.
+    /// Otherwise, the stored hashes are trusted, as with `MerkleTree::deserialize_unverified`.
+    /// Either way, the root hash must be the stored one. The values of a snapshot can be
+    /// dropped by reading it as a `MerkleTree<()>`, whereas a snapshot without values can only
+    /// be read as such.
+    ///
+    /// Every error is an `Error::InvalidSnapshot`, telling which field could not be read,
+    /// and why, eg. `Error::TruncatedInput` if the snapshot ends before it. The hashes are read
+    /// one by one, so `reader` had better be buffered.
+    ///
+    /// The snapshot of a tree with a hasher of its own is read with `MerkleTree::read_snapshot_with`.
+    pub fn read_snapshot<R: Read>(algorithm: &'static Algorithm, reader: R) -> Result<Self, Error> {
+        MerkleTree::read_snapshot_from(algorithm, None, reader)
+    }
+
+    /// Works like `MerkleTree::read_snapshot`, for the snapshot of a tree hashed with the given
+    /// `hasher`, see `MerkleTreeBuilder::hasher`, which the hashes are recomputed with, and which
+    /// the tree read then hashes with. The stored hashes of the internal nodes, if any, are trusted
+    /// all the same, so that the snapshot of a tree with another hasher is only told apart when
+    /// they are recomputed.
+    pub fn read_snapshot_with<H, R>(hasher: H, reader: R) -> Result<Self, Error>
+    where
+        H: MerkleHasher + 'static,
+        R: Read,
+    {
+
+        MerkleTree::read_snapshot_from(hasher.algorithm(), Some(Arc::new(hasher)), reader)
+    }
+
+    fn read_snapshot_from<R: Read>(
+        algorithm: &'static Algorithm,
+        hasher: Option<Arc<dyn MerkleHasher>>,
+        reader: R,
+    ) -> Result<Self, Error> {
+
+        let mut reader = SnapshotReader {
+            reader: reader,
+            offset: 0,
+        };
+
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(invalid(0, Error::InvalidMagic));
+        }
+
+        let version = reader.byte()?;
+
+        if version > VERSION {
+            return Err(invalid(
+                reader.offset - 1,
+                Error::UnsupportedVersion {
+                    found: u32::from(version),
+                    max_supported: u32::from(VERSION),
+                },
+            ));
+        }
+
+        let tag = reader.byte()?;
+
+        match algorithm_from_tag(tag) {
+            None => return Err(invalid(reader.offset - 1, Error::UnknownAlgorithm(tag))),
+            Some(algo) if algorithm_tag(algo) != algorithm_tag(algorithm) => {
+                return Err(invalid(reader.offset - 1, Error::AlgorithmMismatch));
+            }
+            Some(_) => {}
+        }
+
+        let hash_len = reader.byte()? as usize;
+
+        if hash_len != algorithm.output_len {
+            return Err(invalid(
+                reader.offset - 1,
+                Error::HashLengthMismatch {
+                    expected: algorithm.output_len,
+                    found: hash_len,
+                },
+            ));
+        }
+
+        let flags = reader.byte()?;
+
+        if flags & !(VALUES | NODES | LEAF_COUNTS) != 0 {
+            return Err(reader.decode_error(1, "unknown flags"));
+        }
+
+        if T::STORED && flags & VALUES == 0 {
+            return Err(reader.decode_error(1, "snapshot holds no values"));
+        }
+
+        let scheme = scheme_from_byte(reader.byte()?).ok_or_else(|| {
+            reader.decode_error(1, "unknown hashing scheme")
+        })?;
+
+        let policy = policy_from_byte(reader.byte()?).ok_or_else(|| {
+            reader.decode_error(1, "unknown odd leaf policy")
+        })?;
+
+        let fixed_depth = match reader.byte()? {
+            0 => None,
+            depth if depth as usize > MAX_TREE_DEPTH => {
+                return Err(reader.decode_error(1, "fixed depth is too large"));
+            }
+            depth => Some(depth as usize - 1),
+        };
+
+        let count = reader.u64()?;
+
+        if let Some(depth) = fixed_depth {
+            if count > 1 << depth {
+                return Err(invalid(
+                    reader.offset - 8,
+                    Error::TooManyLeaves { max: 1 << depth },
+                ));
+            }
+        }
+
+        if count > usize::MAX as u64 {
+            return Err(reader.decode_error(8, "leaf count does not fit in memory"));
+        }
+
+        let count = count as usize;
+        let root_hash = reader.bytes(hash_len)?;
+
+        // The leaf count is not trusted with more memory than the snapshot holds
+        let mut leaves = Vec::with_capacity(count.min(1 << 16));
+
+        for _ in 0..count {
+            leaves.push(reader.bytes(hash_len)?);
+        }
+
+        let verify = flags & NODES == 0;
+        let leaf_hasher = hasher::or_algorithm(&hasher, &algorithm);
+        let mut values = Vec::with_capacity(leaves.len());
+
+        for hash in &leaves {
+            if flags & VALUES == 0 {
+                values.push(T::from_snapshot_bytes(Vec::new()).map_err(|reason| {
+                    reader.decode_error(0, reason)
+                })?);
+
+                continue;
+            }
+
+            let offset = reader.offset;
+            let len = reader.u32()? as usize;
+            let bytes = reader.bytes(len)?;
+
+            if verify && scheme.hash_leaf_with(leaf_hasher, &bytes).as_ref() != hash.as_slice() {
+                return Err(invalid(
+                    offset,
+                    Error::Decode("leaf hash does not match its value".to_string()),
+                ));
+            }
+
+            values.push(T::from_snapshot_bytes(bytes).map_err(|reason| {
+                invalid(offset, Error::Decode(reason.to_string()))
+            })?);
+        }
+
+        let leaves = leaves
+            .into_iter()
+            .zip(values)
+            .map(|(hash, value)| {
+                Leaf {
+                    hash: SmallHash::from(hash),
+                    value: value,
+                }
+            })
+            .collect();
+
+        let options = TreeOptions {
+            scheme: scheme,
+            odd_leaf_policy: policy,
+            fixed_depth: fixed_depth,
+            leaf_counts: flags & LEAF_COUNTS != 0,
+            hasher: hasher,
+            ..TreeOptions::default()
+        };
+
+        let tree = if verify {
+            MerkleTree::from_leaves_with(algorithm, leaves, options)
+        } else {
+            let mut hashes = Vec::new();
+
+            for _ in 0..internal_node_count(count, &options) {
+                hashes.push(reader.bytes(hash_len)?);
+            }
+
+            MerkleTree::from_leaves_and_hashes(algorithm, leaves, hashes, options)
+        };
+
+        if *tree.root_hash() != root_hash {
+            return Err(invalid(ROOT_OFFSET, Error::HashMismatch { depth: 0, index: 0 }));
+        }
+
+        Ok(tree)
+    }
+}
+
+/// Reads the fields of a snapshot, keeping track of its position in it.
+struct SnapshotReader<R> {
+    reader: R,
+    offset: u64,
+}
+
+impl<R: Read> SnapshotReader<R> {
+    /// Reads the next `len` bytes, without allocating them upfront, since `len` comes from
+    /// the snapshot itself.
+    fn bytes(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::with_capacity(len.min(1 << 16));
+
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut bytes)
+            .map_err(|err| invalid(self.offset, Error::Decode(err.to_string())))?;
+
+        if bytes.len() < len {
+            return Err(invalid(self.offset, Error::TruncatedInput));
+        }
+
+        self.offset += len as u64;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        self.bytes(4).map(|bytes| {
+            bytes.iter().fold(0, |acc, &b| (acc << 8) | u32::from(b))
+        })
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        self.bytes(8).map(|bytes| {
+            bytes.iter().fold(0, |acc, &b| (acc << 8) | u64::from(b))
+        })
+    }
+
+    /// Returns an `Error::Decode` for the field of `len` bytes which was just read.
+    fn decode_error(&self, len: u64, reason: &str) -> Error {
+        invalid(self.offset - len, Error::Decode(reason.to_string()))
+    }
+}
+
+fn invalid(offset: u64, cause: Error) -> Error {
+    Error::InvalidSnapshot {
+        offset: offset,
+        cause: Box::new(cause),
+    }
+}
+
+fn scheme_to_byte(scheme: HashingScheme) -> u8 {
+    match scheme {
+        HashingScheme::DomainSeparated => 0,
+        HashingScheme::Unprefixed => 1,
+        HashingScheme::SortedPairs => 2,
+    }
+}
+
+fn scheme_from_byte(byte: u8) -> Option<HashingScheme> {
+    match byte {
+        0 => Some(HashingScheme::DomainSeparated),
+        1 => Some(HashingScheme::Unprefixed),
+        2 => Some(HashingScheme::SortedPairs),
+        _ => None,
+    }
+}
+
+fn policy_to_byte(policy: OddLeafPolicy) -> u8 {
+    match policy {
+        OddLeafPolicy::Promote => 0,
+        OddLeafPolicy::Duplicate => 1,
+    }
+}
+
+fn policy_from_byte(byte: u8) -> Option<OddLeafPolicy> {
+    match byte {
+        0 => Some(OddLeafPolicy::Promote),
+        1 => Some(OddLeafPolicy::Duplicate),
+        _ => None,
+    }
+}
+
+fn u32_to_bytes(n: u32) -> [u8; 4] {
+    [(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]
+}
+
+fn u64_to_bytes(n: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (n >> (56 - 8 * i)) as u8;
+    }
+
+    bytes
+}
@@ -0,0 +1,89 @@
+use std::thread;
+
+use hashutils::Hashable;
+use merkletree::{MerkleTree, PARALLEL_THRESHOLD};
+use small_hash::SmallHash;
+
+impl<T> MerkleTree<T> {
+    /// Hashes the values of the leaves anew, and returns the indices, in increasing order,
+    /// of those whose hash is no longer the one stored in the tree, such as values holding
+    /// a `Cell` which were changed in place rather than with `MerkleTree::update`.
+    /// The leaves of pruned subtrees are left out.
+    ///
+    /// See `MerkleTree::find_stale_leaves_parallel` to hash the values across threads.
+    pub fn find_stale_leaves(&self) -> Vec<usize>
+    where
+        T: Hashable,
+    {
+
+        let options = self.options();
+        let algorithm = self.algorithm;
+
+        self.tree()
+            .indexed_leaves()
+            .into_iter()
+            .filter(|&(_, hash, value)| hash.as_slice() != options.hash_leaf(algorithm, value).as_ref())
+            .map(|(index, _, _)| index)
+            .collect()
+    }
+
+    /// Works like `MerkleTree::find_stale_leaves`, hashing the values across as many threads
+    /// as are available when the tree has enough leaves for it to pay off.
+    pub fn find_stale_leaves_parallel(&self) -> Vec<usize>
+    where
+        T: Hashable + Sync,
+    {
+
+        let options = self.options();
+        let hasher = options.hasher(&self.algorithm);
+        let scheme = options.scheme;
+        let leaves = self.tree().indexed_leaves();
+
+        let stale = |leaves: &[(usize, &SmallHash, &T)]| {
+            leaves
+                .iter()
+                .filter(|&&(_, hash, value)| hash.as_slice() != scheme.hash_leaf_with(hasher, value).as_ref())
+                .map(|&(index, _, _)| index)
+                .collect::<Vec<_>>()
+        };
+
+        if leaves.len() < PARALLEL_THRESHOLD {
+            return stale(&leaves);
+        }
+
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let chunk_len = leaves.len() / threads + 1;
+
+        let stale = &stale;
+
+        thread::scope(|scope| {
+            let handles = leaves
+                .chunks(chunk_len)
+                .map(|chunk| scope.spawn(move || stale(chunk)))
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("hashing does not panic"))
+                .collect()
+        })
+    }
+
+    /// Panics, in debug builds only, if the values of some leaves no longer have the hash
+    /// stored in the tree, listing their indices, see `MerkleTree::find_stale_leaves`.
+    pub fn debug_assert_consistent(&self)
+    where
+        T: Hashable,
+    {
+
+        if cfg!(debug_assertions) {
+            let stale = self.find_stale_leaves();
+
+            assert!(
+                stale.is_empty(),
+                "the values of the leaves {:?} were changed without updating the tree",
+                stale
+            );
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::ops::ControlFlow;
+
+use digest::Algorithm;
+
+use hex::to_hex;
+use registry::algorithm_name;
+use tree::Tree;
+use visitor::{self, TreeVisitor, TraversalOrder};
+
+/// A summary of the shape of a `MerkleTree`, as returned by `MerkleTree::stats`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct TreeStats {
+    /// The number of leaves in the tree, leaving out the pruned ones.
+    pub leaf_count: usize,
+
+    /// The height of the tree, see `MerkleTree::height`.
+    pub height: usize,
+
+    /// The number of internal nodes in the tree, leaving out the `Empty` ones.
+    pub internal_nodes: usize,
+
+    /// The depth of the shallowest leaf, the root being at depth `0`.
+    pub min_leaf_depth: usize,
+
+    /// The depth of the deepest leaf, the root being at depth `0`.
+    pub max_leaf_depth: usize,
+
+    /// Whether the tree is a perfect binary tree: every leaf is at the same depth, and no node
+    /// is an `Empty` one. An empty tree is not perfect.
+    pub is_perfect: bool,
+
+    /// The name of the hashing algorithm of the tree, see `algorithm_name`.
+    pub algorithm_name: String,
+
+    /// The root hash of the tree, as a lowercase hex string.
+    pub root_hex: String,
+}
+
+/// Counts the nodes of a tree, and the depths of its leaves, into the stats.
+struct Counter<'a>(&'a mut TreeStats);
+
+impl<'a, T> TreeVisitor<T> for Counter<'a> {
+    fn visit_internal(&mut self, _: usize, _: &[u8], _: &[u8], _: &[u8]) -> ControlFlow<()> {
+        self.0.internal_nodes += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn visit_leaf(&mut self, depth: usize, _: usize, _: &[u8], _: &T) {
+        let stats = &mut *self.0;
+
+        if stats.leaf_count == 0 {
+            stats.min_leaf_depth = depth;
+        }
+
+        stats.leaf_count += 1;
+        stats.min_leaf_depth = stats.min_leaf_depth.min(depth);
+        stats.max_leaf_depth = stats.max_leaf_depth.max(depth);
+    }
+}
+
+/// Walks the given tree, of the given `height`, once.
+pub(crate) fn collect<T>(algorithm: &'static Algorithm, tree: &Tree<T>, height: usize) -> TreeStats {
+    let mut stats = TreeStats {
+        leaf_count: 0,
+        height: height,
+        internal_nodes: 0,
+        min_leaf_depth: 0,
+        max_leaf_depth: 0,
+        is_perfect: false,
+        algorithm_name: algorithm_name(algorithm).to_string(),
+        root_hex: to_hex(tree.hash()),
+    };
+
+    visitor::walk(tree, &mut Counter(&mut stats), TraversalOrder::PreOrder);
+
+    // Without `Empty` nodes, every internal node has two children which are counted,
+    // so that there is one internal node less than leaves
+    stats.is_perfect = stats.leaf_count > 0 &&
+        stats.internal_nodes + 1 == stats.leaf_count &&
+        stats.min_leaf_depth == stats.max_leaf_depth;
+
+    stats
+}
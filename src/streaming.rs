@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Read};
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+
+use digest::Context;
+
+use error::{Error, HashError};
+use hashutils::TryHashable;
+use merkletree::MerkleTree;
+
+/// The number of bytes a `StreamingLeaf` reads at once, unless told otherwise
+/// with `StreamingLeaf::chunk_size`.
+pub const STREAMING_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Opens a new reader over the bytes of the value of a `StreamingLeaf`,
+/// every time the value is to be hashed.
+pub type StreamSource = dyn Fn() -> io::Result<Box<dyn Read>> + Send + Sync;
+
+/// A leaf value too large to be held in memory, such as a file, which is hashed as the bytes
+/// read from its source, one chunk at a time, along with metadata of its own, which is not hashed.
+///
+/// As reading may fail, it implements `TryHashable` rather than `Hashable`: the fallible
+/// constructors and methods, such as `MerkleTree::try_push`, fail with `Error::LeafHash`
+/// when the source cannot be read, and `MerkleTree::push_streaming` with `Error::LeafIo`.
+/// The source is opened anew whenever the value is hashed, such as by
+/// `Proof::try_validate_value`, and must yield the same bytes every time.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # use std::io::{self, Read};
+/// # use merkle::{MerkleTree, MerkleTreeBuilder};
+/// # fn main() {
+/// let mut tree = MerkleTreeBuilder::new(&merkle::digest::SHA256).build_empty();
+///
+/// tree.push_streaming(|| Ok(Box::new(io::repeat(7).take(1 << 20)) as Box<dyn Read>), "sevens").unwrap();
+///
+/// let plain = MerkleTree::from_vec(&merkle::digest::SHA256, vec![vec![7u8; 1 << 20]]);
+/// assert_eq!(tree.root_hash(), plain.root_hash());
+/// assert_eq!(*tree.iter().next().unwrap().metadata(), "sevens");
+/// # }
+/// ```
+pub struct StreamingLeaf<M> {
+    source: Arc<StreamSource>,
+    chunk_size: usize,
+    metadata: M,
+}
+
+impl<M> StreamingLeaf<M> {
+    /// Creates a leaf whose value is read from the readers opened by `source`,
+    /// in chunks of `STREAMING_CHUNK_SIZE` bytes.
+    pub fn new<F>(source: F, metadata: M) -> Self
+    where
+        F: Fn() -> io::Result<Box<dyn Read>> + Send + Sync + 'static,
+    {
+
+        StreamingLeaf {
+            source: Arc::new(source),
+            chunk_size: STREAMING_CHUNK_SIZE,
+            metadata: metadata,
+        }
+    }
+
+    /// Reads the value in chunks of `chunk_size` bytes, which does not change its hash.
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunks cannot be empty");
+
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Returns the metadata of this leaf.
+    pub fn metadata(&self) -> &M {
+        &self.metadata
+    }
+
+    /// Returns the metadata of this leaf, dropping its source.
+    pub fn into_metadata(self) -> M {
+        self.metadata
+    }
+
+    /// Opens a new reader over the value of this leaf.
+    pub fn open(&self) -> io::Result<Box<dyn Read>> {
+        (self.source)()
+    }
+
+    /// Feeds the given context with the value of this leaf, one chunk at a time.
+    fn feed(&self, context: &mut Context) -> io::Result<()> {
+        let mut reader = self.open()?;
+        let mut chunk = vec![0; self.chunk_size];
+
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(len) => context.update(&chunk[..len]),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<M: Clone> Clone for StreamingLeaf<M> {
+    fn clone(&self) -> Self {
+        StreamingLeaf {
+            source: self.source.clone(),
+            chunk_size: self.chunk_size,
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+// The source is only called to open a new reader, which is dropped once the value is hashed.
+impl<M: UnwindSafe> UnwindSafe for StreamingLeaf<M> {}
+impl<M: RefUnwindSafe> RefUnwindSafe for StreamingLeaf<M> {}
+
+/// Shows the metadata and the chunk size, the source being opaque.
+impl<M: fmt::Debug> fmt::Debug for StreamingLeaf<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StreamingLeaf")
+            .field("chunk_size", &self.chunk_size)
+            .field("metadata", &self.metadata)
+            .finish()
+    }
+}
+
+impl<M> TryHashable for StreamingLeaf<M> {
+    fn try_update_context(&self, context: &mut Context) -> Result<(), HashError> {
+        self.feed(context).map_err(HashError::new)
+    }
+}
+
+/// A `StreamingLeaf` being hashed, which keeps the error its source failed with, if any.
+struct Reading<'a, M: 'a> {
+    leaf: &'a StreamingLeaf<M>,
+    error: RefCell<Option<io::Error>>,
+}
+
+impl<'a, M> TryHashable for Reading<'a, M> {
+    fn try_update_context(&self, context: &mut Context) -> Result<(), HashError> {
+        self.leaf.feed(context).map_err(|err| {
+            let cause = HashError::new(&err);
+            *self.error.borrow_mut() = Some(err);
+            cause
+        })
+    }
+}
+
+impl<M> MerkleTree<StreamingLeaf<M>> {
+    /// Adds a leaf whose value is read from the readers opened by `source`, in chunks
+    /// of `STREAMING_CHUNK_SIZE` bytes, see `StreamingLeaf`. The tree only holds the hash
+    /// of the value, its source and its `metadata`.
+    ///
+    /// Fails with `Error::LeafIo`, leaving the tree untouched, if the source cannot be read,
+    /// and otherwise as `MerkleTree::try_push` does.
+    pub fn push_streaming<F>(&mut self, source: F, metadata: M) -> Result<bool, Error>
+    where
+        F: Fn() -> io::Result<Box<dyn Read>> + Send + Sync + 'static,
+    {
+
+        self.push_streaming_leaf(StreamingLeaf::new(source, metadata))
+    }
+
+    /// Works like `MerkleTree::push_streaming`, given the leaf, such as one read in chunks
+    /// of another size, see `StreamingLeaf::chunk_size`.
+    pub fn push_streaming_leaf(&mut self, leaf: StreamingLeaf<M>) -> Result<bool, Error> {
+        self.check_unpruned()?;
+
+        let index = self.count();
+
+        let reading = Reading {
+            leaf: &leaf,
+            error: RefCell::new(None),
+        };
+
+        let hash = match self.options().try_hash_leaf(self.algorithm, &reading) {
+            Ok(hash) => hash,
+            Err(cause) => {
+                return Err(match reading.error.into_inner() {
+                    Some(err) => Error::LeafIo {
+                        index: index,
+                        source: err.into(),
+                    },
+                    None => Error::LeafHash {
+                        index: index,
+                        cause: cause,
+                    },
+                });
+            }
+        };
+
+        self.push_hashed(hash, leaf)
+    }
+}
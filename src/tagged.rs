@@ -0,0 +1,129 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de;
+
+use digest::Algorithm;
+
+use options::HashingScheme;
+use proof::{Lemma, Proof, ProofData};
+
+/// A `Proof` serialized along with the name of its hashing algorithm, as in `algorithm_name`,
+/// see `Proof::serialize_tagged`, so that it can be read back without carrying
+/// the algorithm out of band, with `Proof::deserialize_tagged`.
+///
+/// A `Proof` serialized as such skips its algorithm. The tagged form is that of the `ProofData`
+/// of the proof, which names it:
+///
+/// ```json
+/// { "algorithm": "SHA-256", "root_hash": ..., "lemma": ..., "value": ... }
+/// ```
+///
+/// along with the `scheme` if it is not the default one, and the `tree_size`, `leaf_index`
+/// and `leaf_counts` if known.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate merkle;
+/// # extern crate serde_json;
+/// # use merkle::digest::SHA256;
+/// # use merkle::{MerkleTree, Proof};
+/// # fn main() {
+/// let tree = MerkleTree::from_vec(&SHA256, vec!["a".to_string(), "b".to_string()]);
+/// let proof = tree.gen_proof("b".to_string()).unwrap();
+///
+/// let json = serde_json::to_value(proof.serialize_tagged()).unwrap();
+/// assert_eq!(json["algorithm"], "SHA-256");
+///
+/// let read: Proof<String> = Proof::deserialize_tagged(&json).unwrap();
+/// assert!(read.validate(tree.root_hash()));
+///
+/// // The proof itself does not name its algorithm
+/// assert!(serde_json::to_value(&proof).unwrap().get("algorithm").is_none());
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TaggedProof<'a, T: 'a>(&'a Proof<T>);
+
+impl<'a, T: 'a> Clone for TaggedProof<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: 'a> Copy for TaggedProof<'a, T> {}
+
+impl<'a, T: 'a> TaggedProof<'a, T> {
+    /// Returns the proof which is serialized.
+    pub fn proof(&self) -> &'a Proof<T> {
+        self.0
+    }
+}
+
+/// The fields of a `ProofData`, borrowed from a `Proof`, in the same order.
+#[derive(Serialize)]
+struct TaggedFields<'a, T: 'a> {
+    #[serde(serialize_with = "::registry::serialize_algorithm")]
+    algorithm: &'static Algorithm,
+
+    #[serde(skip_serializing_if = "HashingScheme::is_default")]
+    scheme: HashingScheme,
+
+    #[serde(serialize_with = "::hash_encoding::serialize")]
+    root_hash: &'a [u8],
+
+    lemma: &'a Lemma,
+
+    value: &'a T,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tree_size: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leaf_index: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leaf_counts: Option<&'a Vec<u64>>,
+}
+
+impl<'a, T: Serialize + 'a> Serialize for TaggedProof<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let proof = self.0;
+
+        let fields = TaggedFields {
+            algorithm: proof.algorithm,
+            scheme: proof.scheme,
+            root_hash: &proof.root_hash,
+            lemma: &proof.lemma,
+            value: &proof.value,
+            tree_size: proof.tree_size,
+            leaf_index: proof.leaf_index,
+            leaf_counts: proof.leaf_counts.as_ref(),
+        };
+
+        fields.serialize(serializer)
+    }
+}
+
+impl<T> Proof<T> {
+    /// Returns this proof, to be serialized along with the name of its hashing algorithm,
+    /// see `TaggedProof`.
+    pub fn serialize_tagged(&self) -> TaggedProof<T> {
+        TaggedProof(self)
+    }
+
+    /// Deserializes a proof serialized with `Proof::serialize_tagged`, with the hashing
+    /// algorithm it names, as `ProofData::into_named_proof` does, such as with
+    /// `#[serde(deserialize_with = "Proof::deserialize_tagged")]`.
+    ///
+    /// Fails if the proof does not name its algorithm, with `Error::MissingField`, if it names
+    /// an algorithm which is neither known nor registered, see `algorithm_from_name`, with
+    /// `Error::UnknownAlgorithmName`, or if its hashes are not those of that algorithm.
+    pub fn deserialize_tagged<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+
+        ProofData::deserialize(deserializer)?.into_named_proof().map_err(de::Error::custom)
+    }
+}
@@ -1,11 +1,51 @@
 
 #![cfg(test)]
 
-use ring::digest::{Algorithm, Context, SHA512};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::ops::ControlFlow;
+use std::thread;
 
-use merkletree::MerkleTree;
-use hashutils::{Hashable, HashUtils};
-use proof::Positioned;
+use digest::{Algorithm, Context, SHA256, SHA512, SHA512_256};
+
+use merkletree::{MerkleTree, DedupOrder, UpdateSummary};
+use map::MerkleMap;
+use forest::Forest;
+use dual::DualAlgorithmTree;
+use recovery::RebuildError;
+use audit::{AuditLog, verify_receipt};
+use cas::CasError;
+use frontier::AppendOnlyTree;
+use window::SlidingWindowTree;
+use intern::{InternTable, Interned};
+use manifest::ManifestStatus;
+use index::{narrow, to_index};
+use prefix::PrefixProof;
+use appender::ConcurrentAppender;
+use ordering::LeafOrdering;
+use builder::MerkleTreeBuilder;
+use options::{HashingScheme, OddLeafPolicy, DEFAULT_PARALLEL_THRESHOLD, weighted_runs};
+use hashutils::{Hashable, DynHashable, HashUtils};
+use policy::ValidationPolicy;
+use budget::{ValidationBudget, BudgetUsage, validate_batch_bounded};
+use proof::{Proof, Lemma, Positioned, Direction, MAX_TREE_DEPTH, validate_sorted_path};
+use multiproof::{MultiProof, MultiProofNode};
+use partial::PartialTree;
+use display::DisplayOptions;
+use hex::{to_hex, from_hex};
+use hash::{AlgorithmTag, RootHash};
+use defaults::{default_hashes, default_hashes_with};
+use error::{Error, ConversionError};
+use verifier::CachingVerifier;
+use incremental::IncrementalVerify;
+use levels::BucketRange;
+use stats::TreeStats;
+use visitor::{TreeVisitor, TraversalOrder};
+use nodes::NodeRef;
+use hasher::{MerkleHasher, KeyedHasher};
 
 #[allow(non_upper_case_globals)]
 static digest: &'static Algorithm = &SHA512;
@@ -45,6 +85,53 @@ fn test_from_vec_empty() {
     assert_eq!(root_hash, empty_hash);
 }
 
+#[test]
+fn test_empty_tree() {
+    let tree = MerkleTree::<&str>::empty(digest);
+
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec![]));
+    assert!(tree.is_empty());
+    assert_eq!(tree.count(), 0);
+    assert_eq!(tree.height(), 0);
+    assert_eq!(tree.root_hash().as_slice(), digest.hash_empty().as_ref());
+
+    assert!(tree.gen_proof("a").is_none());
+    assert!(tree.gen_nth_proof(0).is_none());
+    assert!(tree.gen_proof_for_occurrence("a", 0).is_none());
+    assert!(tree.gen_multi_proof(&[0]).is_none());
+    assert_eq!(tree.node_hash(0, 0), None);
+    assert_eq!(tree.iter().next(), None);
+    assert_eq!(tree.clone().into_iter().next(), None);
+
+    let prefix = tree.prove_prefix(0).unwrap();
+    assert!(prefix.verify(tree.root_hash(), 0, tree.root_hash(), 0));
+
+    let no_divergence: Result<_, ()> = tree.find_first_divergence(|_, _| Ok(None));
+    assert_eq!(no_divergence, Ok(None));
+
+    let mut cached = tree.clone();
+    cached.enable_proof_cache(4);
+    assert!(cached.gen_proof("a").is_none());
+    assert!(cached.gen_nth_proof(0).is_none());
+
+    let mut tree = tree;
+    let version = tree.checkpoint();
+
+    assert!(tree.push("a"));
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["a"]));
+    assert_eq!(tree.height(), 0);
+    assert_eq!(tree.root_hash().as_slice(), digest.hash_leaf(&"a").as_ref());
+    assert!(tree.gen_proof("a").unwrap().validate(tree.root_hash()));
+
+    assert_eq!(tree.root_at(version).unwrap().as_slice(), digest.hash_empty().as_ref());
+    assert!(tree.gen_proof_at(version, 0).is_none());
+
+    let lazy = MerkleTreeBuilder::new(digest).lazy(true).build_from_vec(Vec::<&str>::new());
+    assert_eq!(lazy.height(), 0);
+    assert_eq!(lazy.iter().count(), 0);
+    assert!(lazy.gen_nth_proof(0).is_none());
+}
+
 #[test]
 fn test_from_vec1() {
     let values = vec!["hello, world".to_string()];
@@ -275,3 +362,5903 @@ fn test_custom_hashable_impl() {
     assert_eq!(tree.count(), 10);
     assert_eq!(tree.height(), 4);
 }
+
+#[test]
+fn test_hashable_pointers() {
+    let value = "value".to_string();
+    let key = PublicKey::new(vec![vec![1]], vec![vec![2]]);
+    let hash = digest.hash_leaf(&value);
+
+    assert_eq!(digest.hash_leaf(&&value).as_ref(), hash.as_ref());
+    assert_eq!(digest.hash_leaf(&value.as_str()).as_ref(), hash.as_ref());
+    assert_eq!(digest.hash_leaf(&value.as_bytes()).as_ref(), hash.as_ref());
+    assert_eq!(digest.hash_leaf(&Box::new(value.clone())).as_ref(), hash.as_ref());
+    assert_eq!(digest.hash_leaf(&Rc::new(value.clone())).as_ref(), hash.as_ref());
+    assert_eq!(digest.hash_leaf(&Arc::new(value.clone())).as_ref(), hash.as_ref());
+    assert_eq!(digest.hash_leaf(&Cow::Borrowed(value.as_str())).as_ref(), hash.as_ref());
+    assert_eq!(digest.hash_leaf(&Cow::<str>::Owned(value.clone())).as_ref(), hash.as_ref());
+
+    let boxed: Box<str> = value.clone().into_boxed_str();
+    let shared: Arc<[u8]> = Arc::from(value.as_bytes());
+    assert_eq!(digest.hash_leaf(&boxed).as_ref(), hash.as_ref());
+    assert_eq!(digest.hash_leaf(&shared).as_ref(), hash.as_ref());
+
+    // Custom implementations are forwarded to as well
+    assert_eq!(digest.hash_leaf(&Rc::new(&key)).as_ref(), digest.hash_leaf(&key).as_ref());
+}
+
+#[test]
+fn test_tree_of_arcs() {
+    let values = (0..7).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+    let shared = values.iter().cloned().map(Arc::new).collect::<Vec<_>>();
+
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let arcs = MerkleTree::from_vec(digest, shared.clone());
+
+    assert_eq!(arcs.root_hash(), tree.root_hash());
+
+    for (i, value) in shared.iter().enumerate() {
+        let proof = arcs.gen_proof(value.clone()).unwrap();
+
+        assert!(proof.validate(tree.root_hash()));
+        assert_eq!(proof.lemma, tree.gen_nth_proof(i).unwrap().lemma);
+    }
+
+    // The trees share the values rather than cloning them
+    assert_eq!(Arc::strong_count(&shared[0]), 2);
+}
+
+/// A value of a type of its own, to be mixed with strings in a tree.
+#[derive(Clone, Debug, PartialEq)]
+struct Transfer {
+    amount: u64,
+}
+
+impl Hashable for Transfer {
+    fn update_context(&self, context: &mut Context) {
+        context.update(b"transfer");
+        context.update(&self.amount.to_be_bytes());
+    }
+}
+
+#[test]
+fn test_dyn_hashable_tree() {
+    let values: Vec<Box<dyn DynHashable>> = vec![
+        Box::new("opened".to_string()),
+        Box::new(Transfer { amount: 10 }),
+        Box::new(Transfer { amount: 20 }),
+        Box::new("closed".to_string()),
+        Box::new(b"audit".to_vec()),
+    ];
+
+    let tree = MerkleTree::from_vec(digest, values);
+
+    let mut transfer = Context::new(digest);
+    transfer.update(&[0x00]);
+    transfer.update(b"transfer");
+    transfer.update(&[0, 0, 0, 0, 0, 0, 0, 10]);
+
+    let hashes = vec![
+        digest.hash_leaf(&"opened"),
+        transfer.finish(),
+        digest.hash_leaf(&Transfer { amount: 20 }),
+        digest.hash_leaf(&"closed"),
+        digest.hash_leaf(&b"audit"),
+    ];
+
+    let h01 = digest.hash_nodes(&hashes[0], &hashes[1]);
+    let h23 = digest.hash_nodes(&hashes[2], &hashes[3]);
+    let h0123 = digest.hash_nodes(&h01, &h23);
+    let root_hash = digest.hash_nodes(&h0123, &hashes[4]);
+
+    assert_eq!(tree.root_hash().as_slice(), root_hash.as_ref());
+
+    let hashed = tree.iter().map(|value| digest.hash_leaf(value).as_ref().to_vec()).collect::<Vec<_>>();
+    assert_eq!(hashed, hashes.iter().map(|hash| hash.as_ref().to_vec()).collect::<Vec<_>>());
+
+    for i in 0..tree.count() {
+        let proof: Proof<Box<dyn DynHashable>> = tree.gen_nth_proof(i).unwrap();
+
+        assert!(proof.validate(tree.root_hash()));
+        assert_eq!(digest.hash_leaf(&proof.value).as_ref(), hashes[i].as_ref());
+        assert_eq!(proof.lemma, proof.clone().lemma);
+    }
+}
+
+#[test]
+fn test_from_slices() {
+    let owned_proofs = {
+        let buffer = (0..1000).map(|i| (i * 7) as u8).collect::<Vec<u8>>();
+        let slices = buffer.chunks(37).collect::<Vec<&[u8]>>();
+
+        let mut tree = MerkleTree::from_slices(digest, &slices);
+        let owned = MerkleTree::from_vec(digest, slices.iter().map(|slice| slice.to_vec()).collect());
+
+        assert_eq!(tree.root_hash(), owned.root_hash());
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), slices);
+
+        for (i, &slice) in slices.iter().enumerate() {
+            let proof: Proof<&[u8]> = tree.gen_proof(slice).unwrap();
+
+            assert!(proof.validate(tree.root_hash()));
+            assert_eq!(tree.verify_proof(&proof), Ok(i));
+            assert_eq!(proof.value.as_ptr(), slice.as_ptr());
+            assert_eq!(proof.to_owned_value(), owned.gen_nth_proof(i).unwrap());
+        }
+
+        assert_eq!(tree.prove_many(&slices[..3]).len(), 3);
+        assert_eq!(tree.iter_proofs().count(), slices.len());
+
+        tree.push(&buffer[..10]);
+        tree.retain(|slice| slice.len() > 10);
+        assert_eq!(tree.count(), slices.len() - 1);
+
+        tree.iter_proofs().map(|proof| proof.to_owned_value()).collect::<Vec<Proof<Vec<u8>>>>()
+    };
+
+    // The proofs outlive the buffer
+    let root_hash = owned_proofs[0].root_hash.clone();
+    assert!(owned_proofs.iter().all(|proof| proof.validate(&root_hash)));
+}
+
+#[test]
+fn test_apply_updates() {
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).fixed_depth(5),
+        MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::SortedPairs),
+        MerkleTreeBuilder::new(digest).lazy(true),
+    ];
+
+    for builder in builders {
+        for count in vec![1, 2, 7, 13, 32] {
+            let values = (0..count).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+            let updates = vec![(count - 1, "a".to_string()), (0, "b".to_string()), (count / 2, "c".to_string()), (0, "d".to_string())];
+
+            let mut expected = values.clone();
+            let mut sequential = builder.clone().build_from_vec(values.clone());
+            sequential.enable_proof_cache(4);
+            sequential.gen_nth_proof(0).unwrap();
+
+            for &(index, ref value) in &updates {
+                let previous = expected[index].clone();
+                expected[index] = value.clone();
+
+                assert_eq!(sequential.update(index, value.clone()), Ok(previous));
+            }
+
+            let rebuilt = builder.clone().build_from_vec(expected.clone());
+            let mut batched = builder.clone().build_from_vec(values.clone());
+            let summary = batched.apply_updates(updates.clone()).unwrap();
+
+            assert_eq!(sequential, rebuilt);
+            assert_eq!(batched, rebuilt);
+            assert_eq!(batched.root_hash(), rebuilt.root_hash());
+            assert_eq!(sequential.gen_nth_proof(0), rebuilt.gen_nth_proof(0));
+
+            let mut leaves = vec![0, count / 2, count - 1];
+            leaves.dedup();
+            assert_eq!(summary.leaves, leaves);
+
+            // Every rehashed node is rehashed once, and has the hash of the rebuilt tree
+            let mut recomputed = summary.recomputed.clone();
+            recomputed.sort();
+            recomputed.dedup();
+            assert_eq!(recomputed.len(), summary.recomputed.len());
+
+            for (level, index) in summary.recomputed {
+                assert!(level > 0);
+                assert_eq!(batched.node_hash(level, index), rebuilt.node_hash(level, index));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_apply_updates_rehashes_shared_nodes_once() {
+    let values = (0..64).map(|i| i.to_string()).collect::<Vec<_>>();
+    let mut tree = MerkleTree::from_vec(digest, values);
+
+    // The paths of neighboring leaves share all but their lowest node
+    let summary = tree.apply_updates((0..4).map(|i| (i, "x".to_string()))).unwrap();
+    assert_eq!(summary.recomputed, vec![(1, 0), (1, 1), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0)]);
+
+    let summary = tree.apply_updates(vec![(0, "y".to_string()), (63, "z".to_string())]).unwrap();
+    assert_eq!(summary.recomputed.len(), 11);
+    assert_eq!(summary.recomputed.last(), Some(&(6, 0)));
+
+    assert_eq!(tree.apply_updates(Vec::new()), Ok(UpdateSummary::default()));
+}
+
+#[test]
+fn test_apply_updates_failures() {
+    let values = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+    let mut tree = MerkleTree::from_vec(digest, values.clone());
+    let original = tree.clone();
+
+    // The whole batch is rejected
+    let res = tree.apply_updates(vec![(0, "a".to_string()), (10, "b".to_string())]);
+    assert_eq!(res, Err(Error::LeafIndexOutOfRange { index: 10, count: 10 }));
+    assert_eq!(tree, original);
+    assert_eq!(tree.update(10, "b".to_string()), Err(Error::LeafIndexOutOfRange { index: 10, count: 10 }));
+
+    let mut sorted = MerkleTree::from_vec_ordered(digest, values.clone(), LeafOrdering::by_value());
+    assert_eq!(sorted.update(0, "z".to_string()), Err(Error::InvalidUpdate("the leaves of the tree are sorted")));
+
+    let (mut deduplicated, _) = MerkleTree::from_vec_dedup(digest, values.clone(), DedupOrder::FirstOccurrence);
+    assert_eq!(deduplicated.update(0, "1".to_string()), Err(Error::InvalidUpdate("the leaves of the tree are deduplicated")));
+
+    tree.prune_below(2);
+    assert!(match tree.update(0, "a".to_string()) {
+        Err(Error::Pruned { .. }) => true,
+        _ => false,
+    });
+
+    // Checkpoints keep the tree as it was
+    let mut tree = original.clone();
+    let version = tree.checkpoint();
+    tree.update(3, "updated".to_string()).unwrap();
+
+    assert_eq!(tree.root_at(version), Some(original.root_hash()));
+    assert_eq!(tree.gen_proof_at(version, 3), original.gen_nth_proof(3));
+}
+
+#[test]
+fn test_swap() {
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).fixed_depth(5),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+    ];
+
+    for builder in builders {
+        for count in vec![2, 8, 11] {
+            let mut values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+            let mut tree = builder.clone().build_from_vec(values.clone());
+
+            // Siblings, then leaves in different halves of the tree, then the last leaf
+            for &(i, j) in &[(0, 1), (1, count - 2), (count - 1, 0)] {
+                tree.swap(i, j).unwrap();
+                values.swap(i, j);
+
+                let expected = builder.clone().build_from_vec(values.clone());
+                assert_eq!(tree, expected);
+                assert_eq!(tree.root_hash(), expected.root_hash());
+
+                for index in 0..count {
+                    assert!(tree.gen_nth_proof(index).unwrap().validate(expected.root_hash()));
+                }
+            }
+        }
+    }
+
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    let original = tree.clone();
+
+    assert_eq!(tree.swap(2, 2), Ok(()));
+    assert_eq!(tree, original);
+    assert_eq!(tree.swap(0, 3), Err(Error::LeafIndexOutOfRange { index: 3, count: 3 }));
+    assert_eq!(tree.swap(3, 3), Err(Error::LeafIndexOutOfRange { index: 3, count: 3 }));
+    assert_eq!(tree, original);
+
+    let mut sorted = MerkleTree::from_vec_ordered(digest, vec!["a", "b"], LeafOrdering::by_value());
+    assert_eq!(sorted.swap(0, 1), Err(Error::InvalidUpdate("the leaves of the tree are sorted")));
+
+    // Checkpoints keep the tree as it was
+    let version = tree.checkpoint();
+    tree.swap(0, 2).unwrap();
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["c", "b", "a"]));
+    assert_eq!(tree.root_at(version), Some(original.root_hash()));
+}
+
+#[test]
+fn test_forest() {
+    let mut forest = Forest::new(digest, 5);
+    let empty = forest.super_root().clone();
+
+    assert_eq!(forest.num_shards(), 5);
+    assert!(forest.prove(0, 0).is_none());
+
+    let roots = vec![digest.hash_empty().as_ref().to_vec(); 5];
+    assert_eq!(empty, *MerkleTree::from_leaf_hashes(digest, roots).unwrap().root_hash());
+
+    for i in 0..23 {
+        let before = forest.super_root().clone();
+        forest.push_to(i % 3, format!("value {}", i)).unwrap();
+        assert_ne!(*forest.super_root(), before);
+    }
+
+    // The super root is the root of the tree over the roots of the shards
+    let roots = (0..5).map(|shard| forest.shard(shard).unwrap().root_hash().clone()).collect();
+    assert_eq!(forest.super_root(), MerkleTree::from_leaf_hashes(digest, roots).unwrap().root_hash());
+
+    for shard in 0..5 {
+        let count = forest.shard(shard).unwrap().count();
+
+        for index in 0..count {
+            let proof = forest.prove(shard, index).unwrap();
+            let inner = forest.shard(shard).unwrap().gen_nth_proof(index).unwrap();
+
+            assert!(proof.validate(forest.super_root()));
+            assert!(!proof.validate(forest.shard(shard).unwrap().root_hash()));
+            assert_eq!(proof.value, inner.value);
+            assert_eq!(proof.lemma.leaf_hash(), inner.lemma.leaf_hash());
+        }
+
+        assert!(forest.prove(shard, count).is_none());
+    }
+
+    // The super root only changes with the root of a shard
+    let before = forest.super_root().clone();
+    assert_eq!(forest.update(1, 2, "value 7".to_string()), Ok("value 7".to_string()));
+    assert_eq!(*forest.super_root(), before);
+
+    assert_eq!(forest.update(1, 2, "changed".to_string()), Ok("value 7".to_string()));
+    assert_ne!(*forest.super_root(), before);
+    assert!(forest.prove(1, 2).unwrap().validate(forest.super_root()));
+    assert!(!forest.prove(0, 0).unwrap().validate(&before));
+
+    assert_eq!(forest.push_to(5, "none".to_string()), Err(Error::LeafIndexOutOfRange { index: 5, count: 5 }));
+    assert_eq!(forest.update(0, 8, "none".to_string()), Err(Error::LeafIndexOutOfRange { index: 8, count: 8 }));
+
+    let mut single = Forest::new(digest, 1);
+    single.push_to(0, "a".to_string()).unwrap();
+    assert_eq!(single.super_root(), single.shard(0).unwrap().root_hash());
+    assert!(single.prove(0, 0).unwrap().validate(single.super_root()));
+}
+
+#[test]
+fn test_rebuild_and_verify() {
+    let rebuild = |values: &[String], root: &[u8], oracle: Option<&dyn Fn(usize, usize) -> Option<Vec<u8>>>| {
+        MerkleTree::rebuild_and_verify(digest, values.to_vec(), root, oracle)
+    };
+    let mismatched = |result: Result<MerkleTree<String>, RebuildError>| match result {
+        Err(RebuildError::RootMismatch { mismatched, .. }) => mismatched,
+        Ok(_) => panic!("the root hash is not the trusted one"),
+    };
+
+    for count in 1..20 {
+        let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+        let original = MerkleTree::from_vec(digest, values.clone());
+        let root = original.root_hash();
+        let oracle = |level, index| original.node_hash(level, index).map(<[u8]>::to_vec);
+
+        assert_eq!(rebuild(&values, root, None), Ok(original.clone()));
+        assert_eq!(rebuild(&values, root, Some(&oracle)), Ok(original.clone()));
+
+        // A single changed value is found
+        for index in 0..count {
+            let mut corrupted = values.clone();
+            corrupted[index] = "corrupted".to_string();
+
+            assert_eq!(mismatched(rebuild(&corrupted, root, Some(&oracle))), Some(index..index + 1));
+            assert_eq!(mismatched(rebuild(&corrupted, root, None)), None);
+        }
+
+        // Two are found to be in the range of their lowest common ancestor
+        if count > 2 {
+            let mut corrupted = values.clone();
+            corrupted[1] = "one".to_string();
+            corrupted[2] = "two".to_string();
+
+            let range = mismatched(rebuild(&corrupted, root, Some(&oracle))).unwrap();
+            assert_eq!(range, 0..4.min(count));
+        }
+    }
+
+    let values = (0..11).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let unrelated = MerkleTree::from_vec(digest, (0..11).map(|i| format!("other {}", i)).collect());
+    let wrong_root = vec![7; digest.output_len];
+
+    let err = rebuild(&values, &wrong_root, None).unwrap_err();
+    assert_eq!(
+        err,
+        RebuildError::RootMismatch {
+            trusted_root: wrong_root.clone(),
+            computed_root: tree.root_hash().clone(),
+            mismatched: None,
+        }
+    );
+    assert_eq!(err.to_string(), format!("rebuilt root {} is not the trusted root {}", to_hex(tree.root_hash()), to_hex(&wrong_root)));
+
+    // Against a root hash, and nodes, which have nothing to do with the values, every leaf differs
+    let oracle = |level, index| unrelated.node_hash(level, index).map(<[u8]>::to_vec);
+    let err = rebuild(&values, unrelated.root_hash(), Some(&oracle)).unwrap_err();
+
+    assert_eq!(mismatched(Err(err.clone())), Some(0..11));
+    assert!(err.to_string().ends_with(", leaves 0..11 no longer match"), "{}", err);
+
+    // An oracle which knows nothing does not tell anything either
+    let nothing = |_, _| None;
+    assert_eq!(mismatched(rebuild(&values, &wrong_root, Some(&nothing))), Some(0..11));
+
+    let empty = MerkleTree::<String>::empty(digest);
+    assert_eq!(rebuild(&[], empty.root_hash(), Some(&nothing)), Ok(empty));
+    assert_eq!(mismatched(rebuild(&[], &wrong_root, Some(&nothing))), None);
+}
+
+#[test]
+fn test_dual_algorithm_tree() {
+    let sha256: &'static Algorithm = &SHA256;
+    let sha512_256: &'static Algorithm = &SHA512_256;
+
+    // Both roots are those of the trees of a single algorithm over the same values
+    let check = |tree: &DualAlgorithmTree<String>, values: &[String]| {
+        assert_eq!(tree.count(), values.len());
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), values);
+
+        for &algorithm in &[sha256, sha512_256] {
+            let single = MerkleTree::from_vec(algorithm, values.to_vec());
+            assert_eq!(tree.root_hash_for(algorithm), Some(single.root_hash()));
+
+            for index in 0..values.len() {
+                let proof = tree.gen_proof_for(algorithm, index).unwrap();
+
+                assert_eq!(proof, single.gen_nth_proof(index).unwrap());
+                assert_eq!(proof.tree_size, Some(values.len() as u64));
+                assert_eq!(proof.leaf_index, Some(index as u64));
+                assert!(proof.validate_value(single.root_hash()));
+            }
+
+            assert!(tree.gen_proof_for(algorithm, values.len()).is_none());
+        }
+    };
+
+    let mut values = (0..5).map(|i| i.to_string()).collect::<Vec<_>>();
+    let mut tree = DualAlgorithmTree::from_vec(sha256, sha512_256, values.clone());
+    assert_eq!(tree.algorithms().0.output_len, 32);
+    check(&tree, &values);
+
+    tree.push("5".to_string());
+    tree.extend(vec!["6".to_string(), "7".to_string()]);
+    values.extend((5..8).map(|i| i.to_string()));
+    check(&tree, &values);
+
+    assert_eq!(tree.update(3, "three".to_string()), Ok("3".to_string()));
+    values[3] = "three".to_string();
+    check(&tree, &values);
+
+    assert_eq!(
+        tree.update(8, "none".to_string()),
+        Err(Error::LeafIndexOutOfRange { index: 8, count: 8 })
+    );
+    check(&tree, &values);
+
+    assert_eq!(tree.pop(), values.pop());
+    check(&tree, &values);
+
+    // A proof only validates against the root of its own algorithm
+    let sha256_root = tree.root_hash_for(sha256).unwrap();
+    let sha512_256_root = tree.root_hash_for(sha512_256).unwrap();
+
+    for index in 0..tree.count() {
+        let sha256_proof = tree.gen_proof_for(sha256, index).unwrap();
+        let sha512_256_proof = tree.gen_proof_for(sha512_256, index).unwrap();
+
+        assert_eq!(AlgorithmTag::of(sha256_proof.algorithm), AlgorithmTag::of(sha256));
+        assert_eq!(AlgorithmTag::of(sha512_256_proof.algorithm), AlgorithmTag::of(sha512_256));
+        assert_eq!(sha256_proof.value, sha512_256_proof.value);
+
+        assert!(sha256_proof.validate(sha256_root));
+        assert!(sha512_256_proof.validate(sha512_256_root));
+        assert!(!sha256_proof.validate(sha512_256_root));
+        assert!(!sha512_256_proof.validate(sha256_root));
+    }
+
+    // Both algorithms have 32 bytes hashes, which does not make their proofs interchangeable
+    let mut relabelled = tree.gen_proof_for(sha512_256, 0).unwrap();
+    relabelled.algorithm = sha256;
+    assert!(!relabelled.validate(sha512_256_root));
+
+    assert_eq!(tree.root_hash_for(digest), None);
+    assert!(tree.gen_proof_for(digest, 0).is_none());
+
+    while tree.pop().is_some() {
+        values.pop();
+    }
+
+    check(&tree, &values);
+    assert!(tree.is_empty());
+    assert_eq!(tree.primary().root_hash(), MerkleTree::<String>::empty(sha256).root_hash());
+}
+
+#[test]
+fn test_leaf_counts() {
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).fixed_depth(4),
+    ];
+
+    for builder in builders {
+        for count in 1..13 {
+            let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+            let plain = builder.clone().build_from_vec(values.clone());
+            let tree = builder.clone().leaf_counts(true).build_from_vec(values.clone());
+
+            assert!(tree.has_leaf_counts());
+            assert_eq!(tree.root_hash() == plain.root_hash(), count == 1 && tree.fixed_depth().is_none());
+
+            for index in 0..count {
+                let proof = tree.gen_nth_proof(index).unwrap();
+                let counts = proof.leaf_counts.clone().unwrap();
+
+                assert!(proof.validate(tree.root_hash()));
+                assert_eq!(proof.leaf_count(), Some(count as u64));
+                assert_eq!(counts.len(), proof.depth());
+                assert!(counts.windows(2).all(|pair| pair[0] >= pair[1]));
+
+                // The counts are part of the hashes
+                let mut uncounted = proof.clone();
+                uncounted.leaf_counts = None;
+                assert_eq!(uncounted.validate(tree.root_hash()), count == 1 && tree.fixed_depth().is_none());
+            }
+
+            // Modifying the tree keeps the counts up to date
+            let mut updated = tree.clone();
+            let mut expected = values.clone();
+            updated.update(count / 2, "updated".to_string()).unwrap();
+            expected[count / 2] = "updated".to_string();
+            assert_eq!(updated.root_hash(), builder.clone().leaf_counts(true).build_from_vec(expected.clone()).root_hash());
+
+            if count < 12 {
+                updated.push("pushed".to_string());
+                expected.push("pushed".to_string());
+                assert_eq!(updated.root_hash(), builder.clone().leaf_counts(true).build_from_vec(expected).root_hash());
+            }
+        }
+    }
+
+    let values = (0..7).map(|i| vec![i]).collect::<Vec<_>>();
+    let tree = MerkleTreeBuilder::new(digest).leaf_counts(true).build_from_vec(values);
+    assert!(tree.gen_multi_proof(&[0, 1]).is_none());
+    assert!(tree.prove_prefix(4).is_none());
+
+    let mut snapshot = Vec::new();
+    tree.write_snapshot(&mut snapshot).unwrap();
+    let restored = MerkleTree::<Vec<u8>>::read_snapshot(digest, &snapshot[..]).unwrap();
+    assert!(restored.has_leaf_counts());
+    assert_eq!(restored.root_hash(), tree.root_hash());
+    assert!(restored.gen_nth_proof(5).unwrap().validate(tree.root_hash()));
+
+    // The proof of the leaf 5 goes through nodes of 7, 3 and 2 leaves
+    let proof = tree.gen_nth_proof(5).unwrap();
+    assert_eq!(proof.leaf_counts, Some(vec![7, 3, 2]));
+
+    let mut tampered = proof.clone();
+    tampered.leaf_counts = Some(vec![8, 3, 2]);
+    assert!(!tampered.validate(tree.root_hash()));
+
+    // Recomputing the hash of a node for a forged count breaks the level above it
+    let mut tampered = proof.clone();
+    tampered.leaf_counts = Some(vec![7, 4, 2]);
+    {
+        let node = tampered.lemma.sub_lemma.as_mut().unwrap();
+        let child = node.sub_lemma.as_ref().unwrap();
+        let sibling = child.sibling_hash.as_ref().unwrap();
+        let forged = sibling.hash_counted_parent(&digest, HashingScheme::DomainSeparated, &child.node_hash, 4);
+        node.node_hash = forged.as_ref().to_vec();
+    }
+    assert!(!tampered.validate(tree.root_hash()));
+
+    let mut tampered = proof.clone();
+    tampered.leaf_counts = Some(vec![7, 3]);
+    assert!(!tampered.validate(tree.root_hash()));
+
+    tampered.leaf_counts = Some(vec![7, 3, 4]);
+    assert!(!tampered.validate(tree.root_hash()));
+}
+
+#[test]
+fn test_leaf_meta() {
+    let values = (0..11).map(|i| (vec![i as u8], format!("meta {}", i))).collect::<Vec<_>>();
+    let mut tree = MerkleTree::from_vec_with_meta(digest, values.clone());
+    let plain = MerkleTree::from_vec(digest, values.iter().map(|&(ref value, _)| value.clone()).collect());
+    let root_hash = tree.root_hash().clone();
+
+    assert_eq!(root_hash, *plain.root_hash());
+    assert_eq!(tree.meta(3).map(String::as_str), Some("meta 3"));
+    assert_eq!(tree.meta(11), None);
+
+    let pairs = tree.iter_with_meta().map(|(value, meta)| (value.clone(), meta.clone())).collect::<Vec<_>>();
+    assert_eq!(pairs, values);
+
+    let version = tree.checkpoint();
+
+    // Changing the metadata rehashes nothing
+    assert_eq!(tree.set_meta(3, "changed".to_string()), Some("meta 3".to_string()));
+    assert_eq!(tree.set_meta(11, "missing".to_string()), None);
+    assert_eq!(tree.meta(3).map(String::as_str), Some("changed"));
+    assert_eq!(*tree.root_hash(), root_hash);
+    assert_eq!(tree.gen_proof_at(version, 3).unwrap().value.meta, "meta 3");
+
+    // The proofs carry the metadata, which they do not authenticate
+    let (proof, meta) = tree.gen_nth_proof(3).unwrap().split_meta();
+    assert_eq!(meta, "changed");
+    assert!(proof.validate(&root_hash));
+    assert_eq!(proof, plain.gen_nth_proof(3).unwrap());
+    assert_eq!(tree.gen_nth_proof_without_meta(3), Some(proof));
+
+    let mut forged = tree.gen_nth_proof(3).unwrap();
+    forged.value.meta = "forged".to_string();
+    assert_eq!(tree.verify_proof(&forged), Ok(3));
+
+    // Whereas changing a value changes the root
+    let mut updated = values.clone();
+    updated[3].0 = vec![42];
+    assert_ne!(MerkleTree::from_vec_with_meta(digest, updated).root_hash(), &root_hash);
+
+    // The metadata of pruned leaves is gone
+    tree.prune_below(1);
+    assert_eq!(tree.meta(3), None);
+    assert_eq!(tree.set_meta(3, "pruned".to_string()), None);
+}
+
+#[test]
+fn test_merkle_map() {
+    let mut map = MerkleMap::new(digest);
+    let empty = map.root();
+
+    assert_eq!(empty.as_bytes(), digest.hash_empty().as_ref());
+    assert!(map.prove_absent(&"a".to_string()).unwrap().verify(&empty, &"a".to_string()).is_ok());
+
+    for i in 0..9 {
+        assert_eq!(map.insert(format!("key {}", i * 2), vec![i as u8]), None);
+    }
+
+    let root = map.root();
+    let key = "key 4".to_string();
+    let proof = map.prove(&key).unwrap();
+
+    assert_eq!(proof.value, vec![2]);
+    assert_eq!(proof.verify(&root, &key, &vec![2]), Ok(()));
+    assert_eq!(proof.verify(&root, &key, &vec![3]), Err(Error::InvalidProof));
+    assert_eq!(proof.verify(&root, &"key 6".to_string(), &vec![2]), Err(Error::InvalidProof));
+    assert!(map.prove_absent(&key).is_none());
+
+    // Updating a value changes the root
+    assert_eq!(map.insert(key.clone(), vec![42]), Some(vec![2]));
+    assert_ne!(map.root(), root);
+    assert_eq!(proof.verify(&map.root(), &key, &vec![2]), Err(Error::InvalidProof));
+    assert_eq!(map.prove(&key).unwrap().verify(&map.root(), &key, &vec![42]), Ok(()));
+
+    // Below the first key, between two keys, and above the last key
+    for absent in vec!["a", "key 3", "z"] {
+        let absent = absent.to_string();
+        let proof = map.prove_absent(&absent).unwrap();
+
+        assert!(map.prove(&absent).is_none());
+        assert_eq!(proof.verify(&map.root(), &absent), Ok(()));
+        assert_eq!(proof.verify(&root, &absent), Err(Error::InvalidProof));
+        assert_eq!(proof.verify(&map.root(), &key), Err(Error::InvalidProof));
+    }
+
+    // The neighbors must be adjacent
+    let mut proof = map.prove_absent(&"key 3".to_string()).unwrap();
+    proof.upper = map.prove_absent(&"key 5".to_string()).unwrap().upper;
+    assert_eq!(proof.verify(&map.root(), &"key 3".to_string()), Err(Error::InvalidProof));
+
+    proof.upper = None;
+    assert_eq!(proof.verify(&map.root(), &"key 3".to_string()), Err(Error::InvalidProof));
+
+    // Removing a key proves it absent, and gives back the previous root
+    let removed = map.root();
+    assert_eq!(map.remove("key 16"), Some(vec![8]));
+    assert_eq!(map.remove("key 16"), None);
+    assert_ne!(map.root(), removed);
+    assert_eq!(map.prove_absent(&"key 16".to_string()).unwrap().verify(&map.root(), &"key 16".to_string()), Ok(()));
+
+    map.insert("key 16".to_string(), vec![8]);
+    assert_eq!(map.root(), removed);
+    assert_eq!(map.len(), 9);
+    assert_eq!(map.get("key 16"), Some(&vec![8]));
+    assert_eq!(map.iter().next(), Some((&"key 0".to_string(), &vec![0])));
+}
+
+#[cfg(feature = "serialization-serde")]
+#[test]
+fn test_hash_encoding_codecs() {
+    use hex::{to_hex, from_hex};
+    use hash_encoding::{to_base64, from_base64};
+
+    let inputs: Vec<&[u8]> = vec![b"", b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar", &[0xfb, 0xff, 0xbf]];
+    let base64 = vec!["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE=", "Zm9vYmFy", "-_-_"];
+
+    for (input, expected) in inputs.iter().zip(base64) {
+        assert_eq!(to_base64(input), expected);
+        assert_eq!(from_base64(expected).unwrap(), *input);
+        assert_eq!(from_base64(expected.trim_end_matches('=')).unwrap(), *input);
+        assert_eq!(from_hex(&to_hex(input)).unwrap(), *input);
+    }
+
+    assert_eq!(from_base64("+/+/").unwrap(), vec![0xfb, 0xff, 0xbf]);
+    assert_eq!(to_hex(&[0x9f, 0xa3, 0x0c]), "9fa30c");
+    assert_eq!(from_hex("9FA30C").unwrap(), vec![0x9f, 0xa3, 0x0c]);
+    assert!(from_hex("9fa").is_none());
+    assert!(from_hex("zz").is_none());
+    assert!(from_base64("Z").is_none());
+    assert!(from_base64("Zg===").is_none());
+}
+
+#[test]
+fn test_from_vec_dedup_first_occurrence() {
+    let values = vec!["b", "a", "b", "c", "a", "b"];
+    let (tree, duplicates) = MerkleTree::from_vec_dedup(digest, values, DedupOrder::FirstOccurrence);
+
+    let expected = MerkleTree::from_vec(digest, vec!["b", "a", "c"]);
+
+    assert_eq!(duplicates, 3);
+    assert_eq!(tree.count(), 3);
+    assert_eq!(tree.root_hash(), expected.root_hash());
+
+    for value in &["a", "b", "c"] {
+        let proof = tree.gen_proof(*value).unwrap();
+        assert!(proof.validate(tree.root_hash()));
+    }
+}
+
+#[test]
+fn test_from_vec_dedup_by_hash() {
+    let values = vec!["b", "a", "b", "c", "a", "b"];
+    let (tree, duplicates) = MerkleTree::from_vec_dedup(digest, values, DedupOrder::ByHash);
+
+    let mut distinct = vec!["a", "b", "c"];
+    distinct.sort_by_key(|v| digest.hash_leaf(v).as_ref().to_vec());
+    let expected = MerkleTree::from_vec(digest, distinct);
+
+    assert_eq!(duplicates, 3);
+    assert_eq!(tree.root_hash(), expected.root_hash());
+
+    let (permuted, _) = MerkleTree::from_vec_dedup(digest, vec!["c", "c", "a", "b"], DedupOrder::ByHash);
+    assert_eq!(permuted.root_hash(), tree.root_hash());
+}
+
+#[test]
+fn test_from_vec_dedup_without_duplicates() {
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let (tree, duplicates) = MerkleTree::from_vec_dedup(digest, values.clone(), DedupOrder::FirstOccurrence);
+
+    assert_eq!(duplicates, 0);
+    assert_eq!(tree, MerkleTree::from_vec(digest, values));
+}
+
+#[test]
+fn test_gen_proofs_for_value() {
+    let values = vec!["a", "x", "b", "x", "c", "d", "x", "e"];
+    let tree = MerkleTree::from_vec(digest, values);
+
+    let proofs = tree.gen_proofs_for_value("x");
+
+    assert_eq!(proofs.len(), 3);
+
+    for (k, proof) in proofs.iter().enumerate() {
+        assert!(proof.validate(tree.root_hash()));
+        assert_eq!(Some(proof), tree.gen_proof_for_occurrence("x", k).as_ref());
+    }
+
+    assert!(proofs[0].lemma != proofs[1].lemma);
+    assert!(proofs[1].lemma != proofs[2].lemma);
+    assert!(proofs[0].lemma != proofs[2].lemma);
+
+    assert_eq!(Some(&proofs[0]), tree.gen_proof("x").as_ref());
+    assert!(tree.gen_proof_for_occurrence("x", 3).is_none());
+}
+
+#[test]
+fn test_gen_proofs_for_absent_value() {
+    let values = vec!["a", "b", "c"];
+    let tree = MerkleTree::from_vec(digest, values);
+
+    assert!(tree.gen_proofs_for_value("z").is_empty());
+    assert!(tree.gen_proof_for_occurrence("z", 0).is_none());
+
+    let empty = MerkleTree::<&str>::from_vec(digest, vec![]);
+    assert!(empty.gen_proofs_for_value("a").is_empty());
+}
+
+#[test]
+fn test_retain() {
+    let values = (0..13).collect::<Vec<u8>>();
+
+    let predicates: Vec<fn(&u8) -> bool> = vec![
+        |x| *x >= 4,
+        |x| *x < 9,
+        |x| x % 3 != 1,
+        |x| *x == 7,
+        |_| true,
+    ];
+
+    for keep in predicates {
+        let mut tree = MerkleTree::from_vec(digest, values.iter().map(|x| vec![*x]).collect());
+
+        let kept = values
+            .iter()
+            .filter(|x| keep(x))
+            .map(|x| vec![*x])
+            .collect::<Vec<_>>();
+
+        let removed = tree.retain(|v| keep(&v[0]));
+
+        assert_eq!(removed, values.len() - kept.len());
+        assert_eq!(tree, MerkleTree::from_vec(digest, kept.clone()));
+
+        for value in kept {
+            assert!(tree.gen_proof(value).unwrap().validate(tree.root_hash()));
+        }
+    }
+}
+
+#[test]
+fn test_retain_collect() {
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d", "e"]);
+
+    let removed = tree.retain_collect(|v| *v != "b" && *v != "d");
+
+    assert_eq!(removed, vec!["b", "d"]);
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["a", "c", "e"]));
+}
+
+#[test]
+fn test_retain_nothing() {
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+
+    assert_eq!(tree.retain(|_| false), 3);
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec![]));
+    assert!(tree.is_empty());
+
+    assert_eq!(tree.retain(|_| true), 0);
+    assert_eq!(tree.root_hash().as_slice(), digest.hash_empty().as_ref());
+}
+
+#[test]
+fn test_from_vec_ordered_by_value() {
+    let permutations = vec![
+        vec!["d", "a", "c", "b", "a"],
+        vec!["a", "a", "b", "c", "d"],
+        vec!["c", "b", "a", "d", "a"],
+    ];
+
+    let sorted = MerkleTree::from_vec(digest, vec!["a", "a", "b", "c", "d"]);
+
+    for values in permutations {
+        let tree = MerkleTree::from_vec_ordered(digest, values, LeafOrdering::by_value());
+
+        assert!(tree.ordering().is_sorted());
+        assert_eq!(tree, sorted);
+    }
+}
+
+#[test]
+fn test_from_vec_ordered_by_key() {
+    let values = vec!["ccc", "a", "bb", "dddd"];
+    let tree = MerkleTree::from_vec_ordered(digest, values, LeafOrdering::by_key(|v: &&str| v.len()));
+
+    let expected = MerkleTree::from_vec(digest, vec!["a", "bb", "ccc", "dddd"]);
+    assert_eq!(tree, expected);
+
+    let input = MerkleTree::from_vec_ordered(digest, vec!["b", "a"], LeafOrdering::Input);
+    assert!(!input.ordering().is_sorted());
+    assert_eq!(input, MerkleTree::from_vec(digest, vec!["b", "a"]));
+}
+
+#[test]
+fn test_push() {
+    let mut tree = MerkleTree::from_vec(digest, vec![]);
+
+    for i in 0..10 {
+        tree.push(vec![i]);
+
+        let expected = (0..i + 1).map(|x| vec![x]).collect::<Vec<_>>();
+        assert_eq!(tree, MerkleTree::from_vec(digest, expected));
+    }
+}
+
+#[test]
+fn test_pop() {
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).fixed_depth(6),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+    ];
+
+    for builder in builders {
+        for n in 1..65 {
+            let mut tree = builder.clone().build_empty();
+
+            for i in 0..n {
+                tree.push(vec![i]);
+            }
+
+            for len in (0..n).rev() {
+                assert_eq!(tree.pop(), Some(vec![len]));
+
+                let expected = builder.clone().build_from_vec((0..len).map(|x| vec![x]).collect());
+                assert_eq!(tree, expected);
+                assert_eq!(tree.root_hash(), expected.root_hash());
+                assert_eq!(tree.height(), expected.height());
+            }
+
+            assert_eq!(tree.pop(), None);
+            assert!(tree.is_empty());
+        }
+    }
+
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d"]);
+    tree.prune_below(1);
+    assert_eq!(tree.pop(), None);
+    assert_eq!(tree.count(), 4);
+}
+
+#[test]
+fn test_insert() {
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).fixed_depth(5),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+    ];
+
+    for builder in builders {
+        for count in 0..18 {
+            let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+            let tree = builder.clone().build_from_vec(values.clone());
+
+            for index in 0..count + 1 {
+                let mut inserted = tree.clone();
+                inserted.insert(index, "new".to_string()).unwrap();
+
+                let mut expected = values.clone();
+                expected.insert(index, "new".to_string());
+                let expected = builder.clone().build_from_vec(expected);
+
+                assert_eq!(inserted, expected);
+                assert_eq!(inserted.root_hash(), expected.root_hash());
+                assert_eq!(inserted.height(), expected.height());
+            }
+
+            // Inserting at the end is pushing
+            let mut inserted = tree.clone();
+            let mut pushed = tree.clone();
+            inserted.insert(count, "new".to_string()).unwrap();
+            pushed.push("new".to_string());
+            assert_eq!(inserted, pushed);
+
+            let mut unchanged = tree.clone();
+            let res = unchanged.insert(count + 1, "new".to_string());
+            assert_eq!(res, Err(Error::LeafIndexOutOfRange { index: count + 1, count: count }));
+            assert_eq!(unchanged, tree);
+        }
+    }
+
+    let mut full = MerkleTreeBuilder::new(digest).fixed_depth(1).build_from_vec(vec!["a", "b"]);
+    assert_eq!(full.insert(0, "c"), Err(Error::TooManyLeaves { max: 2 }));
+
+    let mut sorted = MerkleTree::from_vec_ordered(digest, vec!["a", "c"], LeafOrdering::by_value());
+    assert_eq!(sorted.insert(1, "b"), Err(Error::InvalidUpdate("the leaves of the tree are sorted")));
+}
+
+#[test]
+fn test_from_indexed_unordered() {
+    for len in (0..70).chain(vec![127, 128, 129, 1000]) {
+        let values = (0..len).map(|i| i.to_string()).collect::<Vec<_>>();
+        let expected = MerkleTree::from_vec(digest, values.clone());
+
+        // Shuffled, as multiplying by a prime larger than `len` permutes the indices
+        let shuffled = (0..len).map(|i| i * 7919 % len).map(|i| (i, values[i].clone()));
+        let tree = MerkleTree::from_indexed_unordered(digest, len, shuffled).unwrap();
+
+        assert_eq!(tree, expected);
+        assert_eq!(tree.root_hash(), expected.root_hash());
+
+        let reversed = values.iter().cloned().enumerate().rev();
+        assert_eq!(MerkleTree::from_indexed_unordered(digest, len, reversed).unwrap(), expected);
+    }
+
+    let values = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    let missing = (0..10).filter(|&i| i != 3 && i != 7).map(|i| (i, values[i].clone()));
+    let res = MerkleTree::from_indexed_unordered(digest, 10, missing);
+    assert_eq!(res, Err(Error::InvalidIndices { missing: vec![3, 7], duplicates: vec![] }));
+
+    let duplicated = (0..10).chain(vec![5, 2, 5]).map(|i| (i, values[i].clone()));
+    let res = MerkleTree::from_indexed_unordered(digest, 10, duplicated);
+    assert_eq!(res, Err(Error::InvalidIndices { missing: vec![], duplicates: vec![2, 5] }));
+
+    let both = vec![(1, "1".to_string()), (1, "one".to_string())];
+    let err = MerkleTree::from_indexed_unordered(digest, 2, both).unwrap_err();
+    assert_eq!(err, Error::InvalidIndices { missing: vec![0], duplicates: vec![1] });
+    assert_eq!(err.to_string(), "1 indices have no value and 1 have several");
+
+    let out_of_range = vec![(0, "0".to_string()), (2, "2".to_string())];
+    let res = MerkleTree::from_indexed_unordered(digest, 2, out_of_range);
+    assert_eq!(res, Err(Error::LeafIndexOutOfRange { index: 2, count: 2 }));
+}
+
+#[test]
+fn test_remove() {
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).fixed_depth(5),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+        MerkleTreeBuilder::new(digest).sorted(LeafOrdering::by_value()),
+    ];
+
+    for builder in builders {
+        for count in 0..18 {
+            let tree = builder.clone().build_from_vec((0..count).map(|i| i.to_string()).collect());
+            let values = tree.iter().cloned().collect::<Vec<_>>();
+
+            for index in 0..count {
+                let mut removed = tree.clone();
+                assert_eq!(removed.remove(index), Ok(values[index].clone()));
+
+                let mut expected = values.clone();
+                expected.remove(index);
+                let expected = builder.clone().build_from_vec(expected);
+
+                assert_eq!(removed, expected);
+                assert_eq!(removed.root_hash(), expected.root_hash());
+                assert_eq!(removed.height(), expected.height());
+            }
+
+            let mut unchanged = tree.clone();
+            let res = unchanged.remove(count);
+            assert_eq!(res, Err(Error::LeafIndexOutOfRange { index: count, count: count }));
+            assert_eq!(unchanged, tree);
+        }
+    }
+
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d"]);
+    tree.prune_below(1);
+    assert!(tree.remove(0).is_err());
+    assert_eq!(tree.count(), 4);
+}
+
+#[test]
+fn test_clear_and_rebuild() {
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).fixed_depth(5),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+        MerkleTreeBuilder::new(digest).sorted(LeafOrdering::by_value()),
+        MerkleTreeBuilder::new(digest).dedup(DedupOrder::ByHash),
+        MerkleTreeBuilder::new(digest).lazy(true),
+    ];
+
+    for builder in builders {
+        let mut tree = builder.clone().build_from_vec((0..20).map(|i| i.to_string()).collect());
+
+        for count in (0..21).rev() {
+            let values = (0..count).map(|i| (i * 7 % 13).to_string()).collect::<Vec<_>>();
+            let expected = builder.clone().build_from_vec(values.clone());
+
+            tree.rebuild_from_vec(values);
+            assert_eq!(tree, expected);
+            assert_eq!(tree.root_hash(), expected.root_hash());
+            assert_eq!(tree.height(), expected.height());
+        }
+
+        tree.rebuild_from_vec((0..20).map(|i| i.to_string()).collect());
+        let report = tree.memory_footprint();
+        let node_bytes = report.node_bytes + report.hash_bytes;
+
+        tree.clear();
+        let empty = builder.clone().build_empty();
+
+        assert_eq!(tree, empty);
+        assert_eq!(tree.root_hash(), empty.root_hash());
+        assert_eq!(tree.height(), empty.height());
+        assert_eq!(tree.count(), 0);
+
+        // The allocation of the nodes is kept
+        let report = tree.memory_footprint();
+        assert_eq!(report.node_bytes + report.hash_bytes, node_bytes);
+
+        tree.push("a".to_string());
+        assert_eq!(tree, builder.clone().build_from_vec(vec!["a".to_string()]));
+    }
+
+    // The checkpoints outlive the leaves
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    let root = tree.root_hash().clone();
+    let version = tree.checkpoint();
+
+    tree.clear();
+    assert_eq!(tree.root_at(version), Some(&root));
+    assert_eq!(tree.gen_proof_at(version, 1).map(|proof| proof.value), Some("b"));
+
+    tree.rebuild_from_vec(vec!["d"]);
+    assert_eq!(tree.root_at(version), Some(&root));
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["d"]));
+}
+
+#[test]
+fn test_audit_log() {
+    let mut log = AuditLog::new(digest);
+    let mut root = digest.hash_empty().as_ref().to_vec();
+    let mut receipts = vec![];
+
+    assert!(log.is_empty());
+    assert_eq!(log.root_hash(), &root);
+
+    for i in 0..40 {
+        let receipt = log.append(i.to_string());
+
+        assert_eq!(receipt.tree_size, i + 1);
+        assert_eq!(&receipt.root_hash, log.root_hash());
+        assert_eq!(verify_receipt(&root, &receipt), Ok(()));
+
+        root = receipt.root_hash.clone();
+        receipts.push(receipt);
+    }
+
+    assert_eq!(log.len(), 40);
+    assert_eq!(log.tree(), &MerkleTree::from_vec(digest, (0..40).map(|i: u64| i.to_string()).collect()));
+
+    // Replaying the receipts from the empty log verifies the whole chain of roots
+    let last = receipts.iter().try_fold(digest.hash_empty().as_ref().to_vec(), |root, receipt| {
+        verify_receipt(&root, receipt).map(|_| receipt.root_hash.clone())
+    });
+    assert_eq!(last.as_ref(), Ok(&root));
+
+    // A receipt only holds against the root just before its append
+    for (i, receipt) in receipts.iter().enumerate().skip(1) {
+        assert_eq!(verify_receipt(&receipts[i - 1].root_hash, receipt), Ok(()));
+        assert_eq!(verify_receipt(&receipt.root_hash, receipt), Err(Error::InvalidProof));
+        assert_eq!(verify_receipt(digest.hash_empty().as_ref(), receipt), Err(Error::InvalidProof));
+    }
+
+    let previous = &receipts[20].root_hash;
+    let receipt = &receipts[21];
+
+    let mut tampered = receipt.clone();
+    tampered.inclusion.value = "forged".to_string();
+    assert_eq!(verify_receipt(previous, &tampered), Err(Error::InvalidProof));
+
+    let mut tampered = receipt.clone();
+    tampered.tree_size -= 1;
+    assert_eq!(
+        verify_receipt(previous, &tampered),
+        Err(Error::InvalidPath("not the path to the last leaf of the tree"))
+    );
+
+    let mut tampered = receipt.clone();
+    tampered.tree_size = 0;
+    assert_eq!(verify_receipt(previous, &tampered), Err(Error::InvalidPath("tree size is out of range")));
+
+    // The inclusion proof of an earlier entry is not the one of the last entry
+    let mut earlier = receipt.clone();
+    earlier.inclusion = receipts[20].inclusion.clone();
+    assert_eq!(
+        verify_receipt(previous, &earlier),
+        Err(Error::InvalidPath("not the path to the last leaf of the tree"))
+    );
+
+    let mut tampered = receipt.clone();
+    tampered.root_hash = receipts[22].root_hash.clone();
+    assert_eq!(verify_receipt(previous, &tampered), Err(Error::InvalidProof));
+
+    let mut tampered = receipt.clone();
+    tampered.consistency.algorithm = &SHA256;
+    assert_eq!(verify_receipt(previous, &tampered), Err(Error::AlgorithmMismatch));
+}
+
+#[test]
+fn test_validation_policy() {
+    let values = (0..16).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let root = tree.root_hash();
+    let proof = tree.gen_nth_proof(5).unwrap();
+
+    let violation = |reason| Err(Error::PolicyViolation(reason));
+
+    // The default policy checks what `validate` does
+    for index in 0..16 {
+        assert!(tree.gen_nth_proof(index).unwrap().validate_with_policy(root, &ValidationPolicy::new()).is_ok());
+    }
+    assert_eq!(proof.validate_with_policy(&[0; 64], &ValidationPolicy::new()), Err(Error::InvalidProof));
+
+    assert_eq!(proof.validate_with_policy(root, &ValidationPolicy::new().max_depth(4)), Ok(()));
+    assert_eq!(
+        proof.validate_with_policy(root, &ValidationPolicy::new().max_depth(3)),
+        violation("path is deeper than the maximum depth")
+    );
+    assert_eq!(ValidationPolicy::new().max_depth(1000), ValidationPolicy::new());
+
+    assert_eq!(proof.validate_with_policy(root, &ValidationPolicy::new().required_depth(4)), Ok(()));
+    assert_eq!(
+        proof.validate_with_policy(root, &ValidationPolicy::new().required_depth(5)),
+        violation("path does not have the required depth")
+    );
+
+    // The promoted leaf of a tree of 17 leaves is right below the root
+    let mut values = values;
+    values.push("16".to_string());
+    let promoted = MerkleTree::from_vec(digest, values.clone());
+    let promoted_proof = promoted.gen_nth_proof(16).unwrap();
+    assert_eq!(
+        promoted_proof.validate_with_policy(promoted.root_hash(), &ValidationPolicy::new().required_depth(5)),
+        violation("path does not have the required depth")
+    );
+
+    let domain_separated = ValidationPolicy::new().required_scheme(HashingScheme::DomainSeparated);
+    let legacy = MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::Unprefixed).build_from_vec(values);
+    let legacy_proof = legacy.gen_nth_proof(5).unwrap();
+
+    assert_eq!(proof.validate_with_policy(root, &domain_separated), Ok(()));
+    assert_eq!(legacy_proof.validate_with_policy(legacy.root_hash(), &ValidationPolicy::new()), Ok(()));
+    assert_eq!(
+        legacy_proof.validate_with_policy(legacy.root_hash(), &domain_separated),
+        Err(Error::SchemeMismatch {
+            proof: HashingScheme::Unprefixed,
+            verifier: HashingScheme::DomainSeparated,
+        })
+    );
+
+    // The other way round
+    let unprefixed = ValidationPolicy::new().required_scheme(HashingScheme::Unprefixed).require_value_check(true);
+    assert_eq!(legacy_proof.validate_with_policy(legacy.root_hash(), &unprefixed), Ok(()));
+    assert_eq!(
+        proof.validate_with_policy(root, &unprefixed),
+        Err(Error::SchemeMismatch {
+            proof: HashingScheme::DomainSeparated,
+            verifier: HashingScheme::Unprefixed,
+        })
+    );
+
+    let value_check = ValidationPolicy::new().require_value_check(true);
+    let mut forged = proof.clone();
+    forged.value = "6".to_string();
+
+    assert_eq!(proof.validate_with_policy(root, &value_check), Ok(()));
+    assert_eq!(forged.validate_with_policy(root, &ValidationPolicy::new()), Ok(()));
+    assert_eq!(forged.validate_with_policy(root, &value_check), Err(Error::InvalidProof));
+
+    // Comparing in constant time gives the same outcome, for valid proofs and forged ones alike
+    let constant_time = ValidationPolicy::new().constant_time(true).require_value_check(true);
+
+    for index in 0..16 {
+        let proof = tree.gen_nth_proof(index).unwrap();
+        assert_eq!(proof.validate_with_policy(root, &constant_time), Ok(()));
+
+        for level in 0..proof.depth() {
+            let mut forged = proof.clone();
+            let mut lemma = &mut forged.lemma;
+
+            for _ in 0..level {
+                lemma = lemma.sub_lemma.as_mut().unwrap();
+            }
+
+            lemma.node_hash[0] ^= 1;
+
+            assert_eq!(forged.validate_with_policy(root, &constant_time), Err(Error::InvalidProof));
+            assert_eq!(forged.validate_with_policy(root, &ValidationPolicy::new()), Err(Error::InvalidProof));
+        }
+    }
+
+    let mut forged = proof.clone();
+    forged.value = "6".to_string();
+    assert_eq!(forged.validate_with_policy(root, &constant_time), Err(Error::InvalidProof));
+}
+
+#[test]
+fn test_strict_validation_rejects_padded_proofs() {
+    let unprefixed = HashingScheme::Unprefixed;
+    let hash = |value: &[u8]| unprefixed.hash_leaf(digest, &value.to_vec()).as_ref().to_vec();
+
+    // Without domain separation, the first leaf of this tree is also the node over `a` and `b`
+    let (a, b) = (hash(b"a"), hash(b"b"));
+    let concatenated = [a.clone(), b.clone()].concat();
+
+    let tree = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(unprefixed)
+        .build_from_vec(vec![concatenated, b"c".to_vec()]);
+    let root = tree.root_hash();
+
+    let mut padded = tree.gen_nth_proof(0).unwrap();
+    padded.value = b"a".to_vec();
+    padded.tree_size = None;
+    padded.leaf_index = None;
+    {
+        let leaf = padded.lemma.sub_lemma.as_mut().unwrap();
+        leaf.sibling_hash = Some(Positioned::Right(b));
+        leaf.sub_lemma = Some(Box::new(Lemma {
+            node_hash: a,
+            sibling_hash: None,
+            sub_lemma: None,
+        }));
+    }
+
+    // Checking hashes alone, the padded proof holds
+    assert_eq!(padded.depth(), 2);
+    assert!(padded.validate(root));
+    assert!(padded.validate_value(root));
+    assert_eq!(padded.validate_with_policy(root, &ValidationPolicy::new().require_value_check(true)), Ok(()));
+
+    // Not once the size of the tree is known
+    assert_eq!(
+        padded.validate_with_policy(root, &ValidationPolicy::new().strict(2)),
+        Err(Error::PolicyViolation("path is deeper than any tree of the given size"))
+    );
+    assert!(padded.validate_with_policy(root, &ValidationPolicy::new().tree_size(2)).is_err());
+
+    // Which it has to be: it could be the proof of a leaf of a larger tree
+    assert_eq!(padded.validate_with_policy(root, &ValidationPolicy::new().strict(3)), Ok(()));
+
+    for index in 0..2 {
+        let proof = tree.gen_nth_proof(index).unwrap();
+        assert_eq!(proof.validate_with_policy(root, &ValidationPolicy::new().strict(2)), Ok(()));
+    }
+
+    // The proofs of every leaf of every tree are as deep as its size allows, or less
+    for count in 1..20 {
+        let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+
+        for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+            let tree = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy).build_from_vec(values.clone());
+            let strict = ValidationPolicy::new().strict(count as u64);
+
+            for index in 0..count {
+                let proof = tree.gen_nth_proof(index).unwrap();
+                assert_eq!(proof.validate_with_policy(tree.root_hash(), &strict), Ok(()));
+            }
+        }
+    }
+
+    // A chain ending with a sibling is malformed, which a strict policy tells before hashing
+    let mut malformed = tree.gen_nth_proof(1).unwrap();
+    malformed.lemma.sub_lemma.as_mut().unwrap().sibling_hash = Some(Positioned::Left(vec![0; digest.output_len]));
+
+    assert!(!malformed.validate(root));
+    assert_eq!(malformed.validate_with_policy(root, &ValidationPolicy::new()), Err(Error::InvalidProof));
+    assert_eq!(
+        malformed.validate_with_policy(root, &ValidationPolicy::new().strict(2)),
+        Err(Error::PolicyViolation("lemma chain is malformed"))
+    );
+
+    assert_eq!(
+        tree.gen_nth_proof(0).unwrap().validate_with_policy(root, &ValidationPolicy::new().strict(0)),
+        Err(Error::PolicyViolation("no proof holds for an empty tree"))
+    );
+}
+
+#[test]
+fn test_validate_bounded() {
+    let values = (0..13).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::Unprefixed),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+    ];
+
+    for builder in builders {
+        let counter = HashCounter::default();
+        let tree = builder.hasher(counter.clone()).build_from_vec(values.clone());
+        let root = tree.root_hash();
+        let hashed = || counter.0.load(AtomicOrdering::SeqCst) as u64;
+
+        let proofs = (0..values.len()).map(|index| tree.gen_nth_proof(index).unwrap()).collect::<Vec<_>>();
+        let mut total = BudgetUsage::default();
+
+        for proof in &proofs {
+            let cost = proof.validation_cost();
+            let prefix = if proof.scheme == HashingScheme::DomainSeparated { 1 } else { 0 };
+            let count = if proof.leaf_counts.is_some() { 8 } else { 0 };
+
+            assert_eq!(cost.hashes, proof.depth() as u64);
+            assert_eq!(cost.bytes, cost.hashes * (prefix + 2 * digest.output_len as u64 + count));
+
+            // The cost is exactly what validating takes, with or without a budget
+            let before = hashed();
+            assert!(proof.validate_with(&counter, root));
+            assert_eq!(hashed() - before, cost.hashes);
+
+            let exact = ValidationBudget::new().max_hashes(cost.hashes).max_bytes(cost.bytes);
+            let before = hashed();
+            assert_eq!(proof.validate_bounded_with(&counter, root, &exact), Ok(()));
+            assert_eq!(hashed() - before, cost.hashes);
+
+            // Anything less is rejected before anything is hashed
+            let too_few = [
+                exact.max_hashes(cost.hashes - 1),
+                exact.max_bytes(cost.bytes - 1),
+                ValidationBudget::new().max_hashes(0),
+            ];
+
+            for budget in &too_few {
+                let before = hashed();
+
+                assert_eq!(
+                    proof.validate_bounded_with(&counter, root, budget),
+                    Err(Error::BudgetExceeded {
+                        needed: cost,
+                        remaining: budget.limits(),
+                    })
+                );
+                assert_eq!(hashed(), before);
+            }
+
+            total.hashes += cost.hashes;
+            total.bytes += cost.bytes;
+        }
+
+        // A shared budget of exactly the cost of the batch validates all of it
+        let budget = ValidationBudget::new().max_hashes(total.hashes).max_bytes(total.bytes);
+        let report = validate_batch_bounded(&proofs, root, &budget);
+
+        assert!(report.all_valid());
+        assert_eq!(report.processed, proofs.len());
+        assert_eq!(report.spent, total);
+
+        // And one byte less stops at the last proof
+        let last = proofs[proofs.len() - 1].validation_cost();
+        let report = validate_batch_bounded(&proofs, root, &budget.max_bytes(total.bytes - 1));
+
+        assert_eq!(report.processed, proofs.len() - 1);
+        assert_eq!(report.spent.hashes, total.hashes - last.hashes);
+        assert_eq!(
+            report.exhausted,
+            Some(Error::BudgetExceeded {
+                needed: last,
+                remaining: BudgetUsage {
+                    hashes: last.hashes,
+                    bytes: last.bytes - 1,
+                },
+            })
+        );
+    }
+
+    // Proofs which do not hold are charged what they hashed, and the batch goes on
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let root = tree.root_hash();
+
+    let mut forged = tree.gen_nth_proof(1).unwrap();
+    forged.value = "forged".to_string();
+    forged.lemma.sub_lemma.as_mut().unwrap().node_hash[0] ^= 1;
+
+    let mut truncated = tree.gen_nth_proof(2).unwrap();
+    truncated.lemma.sibling_hash = Some(Positioned::Left(vec![0; 4]));
+
+    let proofs = vec![tree.gen_nth_proof(0).unwrap(), forged, truncated, tree.gen_nth_proof(3).unwrap()];
+    let report = validate_batch_bounded(&proofs, root, &ValidationBudget::new());
+
+    assert_eq!(report.processed, 4);
+    assert_eq!(report.failures, vec![(1, Error::InvalidProof), (2, Error::HashLengthMismatch { expected: 64, found: 4 })]);
+    assert_eq!(report.spent.hashes, 3 * 4);
+    assert_eq!(report.spent.bytes, 3 * 4 * (1 + 2 * 64));
+    assert!(report.exhausted.is_none() && !report.all_valid());
+
+    // And no budget is needed for an empty batch
+    let report = validate_batch_bounded::<String>(&[], root, &ValidationBudget::new().max_hashes(0));
+    assert!(report.all_valid() && report.processed == 0);
+}
+
+#[test]
+fn test_compare_and_update() {
+    let values = (0..7).map(|i| i.to_string()).collect::<Vec<_>>();
+    let mut tree = MerkleTree::from_vec(digest, values.clone());
+    let old_root = tree.root_hash().clone();
+
+    let receipt = tree.compare_and_update(3, &"3".to_string(), "three".to_string()).unwrap();
+
+    assert_eq!(receipt.old_root, old_root);
+    assert_eq!(&receipt.new_root, tree.root_hash());
+    assert_eq!(receipt.old_proof.value, "3");
+    assert_eq!(receipt.new_proof.value, "three");
+    assert!(receipt.old_proof.validate_value(&receipt.old_root));
+    assert!(receipt.new_proof.validate_value(&receipt.new_root));
+    assert!(!receipt.old_proof.validate(&receipt.new_root));
+    assert!(receipt.old_proof.validate_against(&receipt.old_root, 7));
+    assert!(receipt.new_proof.validate_against(&receipt.new_root, 7));
+
+    let mut expected = values.clone();
+    expected[3] = "three".to_string();
+    assert_eq!(tree, MerkleTree::from_vec(digest, expected));
+
+    // The leaf no longer holds the old value, which leaves the tree untouched
+    let root = tree.root_hash().clone();
+    let res = tree.compare_and_update(3, &"3".to_string(), "four".to_string());
+
+    assert_eq!(res, Err(CasError::Mismatch { actual: "three".to_string() }));
+    assert_eq!(tree.root_hash(), &root);
+    assert_eq!(tree.gen_nth_proof(3).unwrap().value, "three");
+
+    let res = tree.compare_and_update(7, &"7".to_string(), "seven".to_string());
+    assert_eq!(res, Err(CasError::Update(Error::LeafIndexOutOfRange { index: 7, count: 7 })));
+
+    let mut sorted = MerkleTree::from_vec_ordered(digest, values, LeafOrdering::by_value());
+    let res = sorted.compare_and_update(0, &"0".to_string(), "9".to_string());
+    assert_eq!(res, Err(CasError::Update(Error::InvalidUpdate("the leaves of the tree are sorted"))));
+}
+
+/// Counts the hashes it computes, leaves and nodes alike.
+#[derive(Clone, Debug, Default)]
+struct HashCounter(Arc<AtomicUsize>);
+
+impl MerkleHasher for HashCounter {
+    fn algorithm(&self) -> &'static Algorithm {
+        digest
+    }
+
+    fn begin(&self, _context: &mut Context) {
+        self.0.fetch_add(1, AtomicOrdering::SeqCst);
+    }
+}
+
+#[test]
+fn test_subtree() {
+    let values = (0..13).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+        MerkleTreeBuilder::new(digest).fixed_depth(5),
+    ];
+
+    for builder in builders {
+        let counter = HashCounter::default();
+        let tree = builder.clone().hasher(counter.clone()).build_from_vec(values.clone());
+        let padded = tree.fixed_depth().is_some() || tree.odd_leaf_policy() == OddLeafPolicy::Duplicate;
+
+        for level in 0..tree.height() + 1 {
+            for index in 0.. {
+                let hash = match tree.node_hash(level, index) {
+                    Some(hash) => hash.to_vec(),
+                    None => break,
+                };
+
+                let start = index << level;
+                let end = (start + (1 << level)).min(values.len());
+
+                // The last node of a level of a padded tree is not the root of a tree of its own
+                // if it is higher than that tree would be, save for the root
+                let lowest = (end - start).next_power_of_two() == 1 << level && end - start < values.len();
+
+                if padded && level < tree.height() && !lowest {
+                    continue;
+                }
+
+                let hashed = counter.0.load(AtomicOrdering::SeqCst);
+                let subtree = tree.subtree(start..end).unwrap();
+
+                assert_eq!(subtree.root_hash(), &hash);
+                assert_eq!(subtree.count(), end - start);
+                assert_eq!(subtree.iter().collect::<Vec<_>>(), values[start..end].iter().collect::<Vec<_>>());
+                assert_eq!(counter.0.load(AtomicOrdering::SeqCst), hashed);
+
+                // The subtree is the tree its leaves would be built into
+                let mut options = builder.clone();
+
+                if tree.fixed_depth().is_some() {
+                    options = options.fixed_depth(level);
+                }
+
+                let expected = options.build_from_vec(values[start..end].to_vec());
+                assert_eq!(subtree, expected);
+
+                for i in 0..subtree.count() {
+                    assert!(subtree.gen_nth_proof(i).unwrap().validate(&hash));
+                }
+            }
+        }
+
+        // Any range can be rebuilt into a tree of its own
+        for start in 0..values.len() {
+            for end in start..values.len() + 1 {
+                let rebuilt = tree.subtree_rebuild(start..end).unwrap();
+                let expected = builder.clone().build_from_vec(values[start..end].to_vec());
+
+                assert_eq!(rebuilt.root_hash(), expected.root_hash());
+
+                if let Ok(subtree) = tree.subtree(start..end) {
+                    assert!(tree.fixed_depth().is_some() || subtree.root_hash() == rebuilt.root_hash());
+                }
+            }
+        }
+    }
+
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    assert_eq!(tree.subtree(0..13), Ok(tree.clone()));
+    assert_eq!(tree.subtree(12..13).unwrap().root_hash(), tree.node_hash(0, 12).unwrap());
+    assert_eq!(tree.subtree(3..6), Err(Error::UnalignedRange { nearest_aligned: 0..8 }));
+    assert_eq!(tree.subtree(9..10), Ok(tree.subtree_rebuild(9..10).unwrap()));
+    assert_eq!(tree.subtree(9..11), Err(Error::UnalignedRange { nearest_aligned: 8..12 }));
+    assert_eq!(tree.subtree(9..13), Err(Error::UnalignedRange { nearest_aligned: 8..13 }));
+    assert_eq!(tree.subtree(5..14), Err(Error::LeafIndexOutOfRange { index: 13, count: 13 }));
+    assert_eq!(tree.subtree_rebuild(5..14), Err(Error::LeafIndexOutOfRange { index: 13, count: 13 }));
+
+    let mut pruned = tree.clone();
+    pruned.prune_below(1);
+
+    let subtree_root = tree.node_hash(3, 0).unwrap().to_vec();
+
+    assert_eq!(pruned.subtree(2..4), Err(Error::Pruned { subtree_root: subtree_root.clone() }));
+    assert_eq!(pruned.subtree_rebuild(6..10), Err(Error::Pruned { subtree_root: subtree_root.clone() }));
+
+    let subtree = pruned.subtree(0..8).unwrap();
+
+    assert_eq!(subtree.root_hash(), &subtree_root);
+    assert_eq!(subtree.count(), 8);
+    assert_eq!(subtree.iter().count(), 0);
+}
+
+#[test]
+fn test_frontier() {
+    let values = (0..140).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::Unprefixed),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+        MerkleTreeBuilder::new(digest).fixed_depth(8),
+        MerkleTreeBuilder::new(digest).fixed_depth(8).leaf_counts(true),
+    ];
+
+    // Migrations just before, at and just after powers of two
+    let sizes = [0, 1, 2, 3, 4, 5, 7, 8, 9, 15, 16, 17, 31, 32, 33, 63, 64, 65];
+
+    for builder in builders {
+        for &size in &sizes {
+            let mut tree = builder.clone().build_from_vec(values[..size].to_vec());
+
+            let frontier = tree.export_frontier();
+            assert_eq!(frontier.count(), size as u64);
+            assert_eq!(frontier.hashes().len(), size.count_ones() as usize);
+
+            let mut resumed: AppendOnlyTree<String> = MerkleTree::from_frontier(digest, frontier).unwrap();
+            assert_eq!(resumed.root_hash(), tree.root_hash(), "{} leaves", size);
+
+            for (i, value) in values[size..size + 70].iter().enumerate() {
+                // Leaves can be appended by hash as well
+                if i % 3 == 0 {
+                    let hash = tree.options().hash_leaf(digest, value);
+                    resumed.push_leaf_hash(hash.as_ref()).unwrap();
+                } else {
+                    resumed.push(value.clone()).unwrap();
+                }
+
+                tree.push(value.clone());
+
+                assert_eq!(resumed.count(), tree.count());
+                assert_eq!(resumed.root_hash(), tree.root_hash(), "{} then {} leaves", size, i + 1);
+            }
+
+            // A frontier can be exported again and again
+            let frontier = resumed.export_frontier();
+            assert_eq!(frontier, tree.export_frontier());
+        }
+    }
+
+    // The slots of a tree of fixed depth run out all the same
+    let tree = MerkleTreeBuilder::new(digest).fixed_depth(2).build_from_vec(values[..3].to_vec());
+    let mut resumed = MerkleTree::from_frontier(digest, tree.export_frontier()).unwrap();
+
+    assert_eq!(resumed.push("3".to_string()), Ok(()));
+    assert_eq!(resumed.push("4".to_string()), Err(Error::TooManyLeaves { max: 4 }));
+    assert_eq!(resumed.push_leaf_hash(&[0; 32]), Err(Error::HashLengthMismatch { expected: 64, found: 32 }));
+    assert_eq!(resumed.count(), 4);
+
+    let res = MerkleTree::<String>::from_frontier(&SHA256, tree.export_frontier());
+    assert_eq!(res.unwrap_err(), Error::AlgorithmMismatch);
+}
+
+#[test]
+#[should_panic(expected = "the leaves of the tree are sorted")]
+fn test_frontier_of_sorted_tree() {
+    MerkleTree::from_vec_ordered(digest, vec!["b", "a"], LeafOrdering::by_value()).export_frontier();
+}
+
+#[test]
+fn test_transaction() {
+    let values = (0..11).map(|i| i.to_string()).collect::<Vec<_>>();
+    let mut tree = MerkleTree::from_vec(digest, values.clone());
+    let root_hash = tree.root_hash().clone();
+
+    let mut txn = tree.transaction();
+    assert_eq!(txn.projected_root(), root_hash);
+
+    assert_eq!(txn.update(3, "three".to_string()), Ok("3".to_string()));
+    assert_eq!(txn.push("11".to_string()), Ok(true));
+    assert_eq!(txn.remove(0), Ok("0".to_string()));
+    assert_eq!(txn.update(20, "twenty".to_string()), Err(Error::LeafIndexOutOfRange { index: 20, count: 11 }));
+
+    // The staged reads see the changes, and those of the tree the previous state
+    assert_eq!(txn.count(), 11);
+    assert_eq!(txn.get(2), Some(&"three".to_string()));
+    assert_eq!(txn.get(10), Some(&"11".to_string()));
+    assert_eq!(txn.get(11), None);
+
+    assert_eq!(txn.base().count(), 11);
+    assert_eq!(txn.base().root_hash(), &root_hash);
+    assert_eq!(txn.base().iter().cloned().collect::<Vec<_>>(), values);
+    assert!(txn.base().gen_nth_proof(3).unwrap().validate(&root_hash));
+
+    let mut expected = values.clone();
+    expected[3] = "three".to_string();
+    expected.push("11".to_string());
+    expected.remove(0);
+    let expected = MerkleTree::from_vec(digest, expected);
+
+    let projected_root = txn.projected_root();
+    assert_eq!(&projected_root, expected.root_hash());
+    assert_ne!(projected_root, root_hash);
+
+    txn.commit();
+    assert_eq!(tree.root_hash(), &projected_root);
+    assert_eq!(tree, expected);
+
+    // Rolled back and dropped transactions leave the tree as it was
+    let mut txn = tree.transaction();
+    txn.push("12".to_string()).unwrap();
+    txn.rollback();
+    assert_eq!(tree, expected);
+
+    {
+        let mut txn = tree.transaction();
+        txn.remove(4).unwrap();
+        assert_eq!(txn.count(), 10);
+    }
+
+    assert_eq!(tree, expected);
+
+    // A transaction without changes commits nothing
+    tree.transaction().commit();
+    assert_eq!(tree, expected);
+}
+
+/// A value which can be changed in place, behind the back of the tree.
+#[derive(Clone, Debug)]
+struct Mutable(Cell<u32>);
+
+impl Hashable for Mutable {
+    fn update_context(&self, context: &mut Context) {
+        context.update(&self.0.get().to_le_bytes());
+    }
+}
+
+#[test]
+fn test_find_stale_leaves() {
+    let values = (0..13).map(|i| Mutable(Cell::new(i))).collect::<Vec<_>>();
+    let mut tree = MerkleTree::from_vec(digest, values);
+
+    assert_eq!(tree.find_stale_leaves(), Vec::<usize>::new());
+    tree.debug_assert_consistent();
+
+    for index in &[9, 2] {
+        let value = tree.iter().nth(*index).unwrap();
+        value.0.set(value.0.get() + 100);
+    }
+
+    assert_eq!(tree.find_stale_leaves(), vec![2, 9]);
+
+    // Updating a value rehashes it
+    tree.update(2, Mutable(Cell::new(102))).unwrap();
+    assert_eq!(tree.find_stale_leaves(), vec![9]);
+
+    // The leaves of pruned subtrees have no value to check
+    tree.prune_below(1);
+    assert_eq!(tree.find_stale_leaves(), Vec::<usize>::new());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "the values of the leaves [1] were changed without updating the tree")]
+fn test_debug_assert_consistent() {
+    let tree = MerkleTree::from_vec(digest, vec![Mutable(Cell::new(0)), Mutable(Cell::new(1))]);
+    tree.iter().nth(1).unwrap().0.set(7);
+    tree.debug_assert_consistent();
+}
+
+#[test]
+fn test_find_stale_leaves_parallel() {
+    let count = 3 * (1 << 12) + 5;
+    let values = (0..count).map(|i| Mutex::new(i.to_string())).collect::<Vec<_>>();
+
+    struct Shared(Mutex<String>);
+
+    impl Hashable for Shared {
+        fn update_context(&self, context: &mut Context) {
+            self.0.lock().unwrap().update_context(context);
+        }
+    }
+
+    let tree = MerkleTree::from_vec(digest, values.into_iter().map(Shared).collect());
+    assert_eq!(tree.find_stale_leaves_parallel(), Vec::<usize>::new());
+
+    let stale = vec![0, 1 << 12, count - 1];
+
+    for &index in &stale {
+        tree.iter().nth(index).unwrap().0.lock().unwrap().push('!');
+    }
+
+    assert_eq!(tree.find_stale_leaves_parallel(), stale);
+    assert_eq!(tree.find_stale_leaves(), stale);
+}
+
+#[test]
+fn test_sliding_window() {
+    use std::collections::VecDeque;
+
+    // xorshift64, to pick the values pushed and the number of pushes
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    for capacity in (1..18).chain(vec![31, 32, 33, 100]) {
+        let mut window = SlidingWindowTree::new(digest, capacity);
+        let mut expected = VecDeque::new();
+
+        assert!(window.is_empty());
+        assert_eq!(window.root_hash(), MerkleTree::<String>::from_vec(digest, vec![]).root_hash());
+        assert_eq!(window.gen_proof(0), None);
+
+        let pushes = 3 * capacity + (next() % 200) as usize;
+
+        for _ in 0..pushes {
+            let value = (next() % 1000).to_string();
+
+            // The oldest value is evicted first
+            expected.push_back(value.clone());
+            let evicted = if expected.len() > capacity { expected.pop_front() } else { None };
+            assert_eq!(window.push(value), evicted);
+
+            let values = expected.iter().cloned().collect::<Vec<_>>();
+            assert_eq!(window.iter().cloned().collect::<Vec<_>>(), values);
+            assert_eq!(window.len(), values.len());
+
+            let tree = MerkleTree::from_vec(digest, values);
+            assert_eq!(window.root_hash(), tree.root_hash());
+
+            let offset = (next() % tree.count() as u64) as usize;
+            let proof = window.gen_proof(offset).unwrap();
+            let tree_proof = tree.gen_nth_proof(offset).unwrap();
+
+            assert_eq!(proof, tree_proof);
+            assert_eq!(proof.tree_size, tree_proof.tree_size);
+            assert_eq!(proof.leaf_index, tree_proof.leaf_index);
+            assert!(proof.validate_value(tree.root_hash()));
+            assert_eq!(window.gen_proof(tree.count()), None);
+        }
+
+        assert_eq!(window.len(), capacity);
+        assert_eq!(window.capacity(), capacity);
+
+        let tree = MerkleTree::from_vec(digest, window.iter().cloned().collect());
+
+        for offset in 0..capacity {
+            assert_eq!(window.gen_proof(offset), tree.gen_nth_proof(offset));
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "the window has no room for a leaf")]
+fn test_sliding_window_without_capacity() {
+    SlidingWindowTree::<String>::new(digest, 0);
+}
+
+#[test]
+fn test_concurrent_appender() {
+    let appender = ConcurrentAppender::new(MerkleTree::from_vec(digest, vec!["a".to_string()]));
+
+    appender.push("b".to_string());
+    appender.push("c".to_string());
+
+    // Staged values are only seen once committed
+    let before = appender.snapshot();
+    assert_eq!(appender.count(), 1);
+    assert_eq!(appender.commit(), Ok(2));
+    assert_eq!(appender.commit(), Ok(0));
+
+    appender.push("d".to_string());
+
+    let expected = MerkleTree::from_vec(digest, vec!["a", "b", "c"].into_iter().map(String::from).collect());
+    assert_eq!(*appender.snapshot(), expected);
+    assert_eq!(appender.root_hash(), *expected.root_hash());
+    assert_eq!(appender.gen_nth_proof(2), expected.gen_nth_proof(2));
+    assert_eq!(appender.gen_nth_proof(3), None);
+    assert_eq!(before.count(), 1);
+
+    let tree = appender.into_tree().unwrap();
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["a", "b", "c", "d"].into_iter().map(String::from).collect()));
+
+    // A failed commit keeps the staged values for the next one
+    let appender = ConcurrentAppender::new(MerkleTreeBuilder::new(digest).max_leaves(2).build_from_vec(vec!["1"]));
+
+    appender.push("2");
+    appender.push("3");
+    assert_eq!(appender.commit(), Err(Error::TooManyLeaves { max: 2 }));
+    assert_eq!(appender.count(), 1);
+    assert_eq!(appender.commit(), Err(Error::TooManyLeaves { max: 2 }));
+    assert_eq!(appender.discard_pending(), vec!["2", "3"]);
+    assert_eq!(appender.commit(), Ok(0));
+
+    appender.push("4");
+    assert_eq!(appender.commit(), Ok(1));
+    assert_eq!(*appender.snapshot(), MerkleTree::from_vec(digest, vec!["1", "4"]));
+}
+
+#[test]
+fn test_concurrent_appender_stress() {
+    let producers = 8;
+    let values = 500;
+    let appender = ConcurrentAppender::new(MerkleTree::empty(digest));
+    let done = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for producer in 0..producers {
+            let appender = &appender;
+            let done = &done;
+
+            scope.spawn(move || {
+                for value in 0..values {
+                    appender.push([producer as u8, (value >> 8) as u8, value as u8]);
+                }
+
+                done.fetch_add(1, AtomicOrdering::SeqCst);
+            });
+        }
+
+        // A committer and readers run along with the producers
+        scope.spawn(|| {
+            while done.load(AtomicOrdering::SeqCst) < producers {
+                appender.commit().unwrap();
+            }
+        });
+
+        for _ in 0..2 {
+            scope.spawn(|| {
+                while done.load(AtomicOrdering::SeqCst) < producers {
+                    let snapshot = appender.snapshot();
+
+                    if let Some(proof) = snapshot.gen_nth_proof(snapshot.count().saturating_sub(1)) {
+                        assert!(proof.validate(snapshot.root_hash()));
+                    }
+
+                    assert!(appender.count() >= snapshot.count());
+                }
+            });
+        }
+    });
+
+    appender.commit().unwrap();
+    let tree = appender.into_tree().unwrap();
+
+    // No value is lost nor duplicated, and the values of each producer keep their order
+    let mut next = vec![0; producers];
+
+    for &[producer, high, low] in tree.iter() {
+        let (producer, value) = (producer as usize, (high as usize) << 8 | low as usize);
+        assert_eq!(value, next[producer]);
+        next[producer] += 1;
+    }
+
+    assert_eq!(next, vec![values; producers]);
+    assert_eq!(tree, MerkleTree::from_vec(digest, tree.iter().cloned().collect()));
+}
+
+#[test]
+fn test_push_ordered() {
+    let mut tree = MerkleTree::from_vec_ordered(digest, vec!["5", "1", "3"], LeafOrdering::by_value());
+
+    for value in vec!["4", "0", "6", "3", "2"] {
+        tree.push(value);
+    }
+
+    let values = tree.iter().cloned().collect::<Vec<_>>();
+    assert_eq!(values, vec!["0", "1", "2", "3", "3", "4", "5", "6"]);
+
+    let shuffled = vec!["6", "3", "0", "4", "3", "2", "1", "5"];
+    let expected = MerkleTree::from_vec_ordered(digest, shuffled, LeafOrdering::by_value());
+    assert_eq!(tree, expected);
+    assert!(tree.ordering().is_sorted());
+
+    tree.retain(|v| *v != "3");
+    assert!(tree.ordering().is_sorted());
+}
+
+#[test]
+fn test_builder_defaults() {
+    for n in 0..20 {
+        let values = (0..n).map(|x| vec![x]).collect::<Vec<_>>();
+
+        let built = MerkleTreeBuilder::new(digest).build_from_vec(values.clone());
+        let from_iter = MerkleTreeBuilder::new(digest).build_from_iter(values.clone());
+        let with_capacity = MerkleTreeBuilder::new(digest).capacity(64).build_from_iter(values.clone());
+
+        let tree = MerkleTree::from_vec(digest, values);
+
+        assert_eq!(built, tree);
+        assert_eq!(from_iter, tree);
+        assert_eq!(with_capacity, tree);
+        assert_eq!(built.hashing_scheme(), HashingScheme::DomainSeparated);
+        assert_eq!(built.odd_leaf_policy(), OddLeafPolicy::Promote);
+        assert_eq!(built.dedup(), None);
+        assert!(!built.ordering().is_sorted());
+    }
+}
+
+#[test]
+fn test_builder_algorithm() {
+    let values = vec!["a", "b", "c"];
+
+    let tree = MerkleTreeBuilder::new(digest).algorithm(&SHA256).build_from_vec(values.clone());
+
+    assert_eq!(tree, MerkleTree::from_vec(&SHA256, values));
+}
+
+#[test]
+fn test_builder_build_empty() {
+    let mut tree = MerkleTreeBuilder::new(digest).build_empty();
+
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec![]));
+
+    assert!(tree.push("a"));
+    assert!(tree.push("b"));
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["a", "b"]));
+}
+
+#[test]
+fn test_builder_unprefixed() {
+    let values = vec!["a", "b", "c"];
+    let tree = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(HashingScheme::Unprefixed)
+        .build_from_vec(values.clone());
+
+    let plain = |bytes: &[&[u8]]| {
+        let mut ctx = Context::new(digest);
+        for b in bytes {
+            ctx.update(b);
+        }
+        ctx.finish()
+    };
+
+    let ha = plain(&[b"a"]);
+    let hb = plain(&[b"b"]);
+    let hc = plain(&[b"c"]);
+    let hab = plain(&[ha.as_ref(), hb.as_ref()]);
+    let root_hash = plain(&[hab.as_ref(), hc.as_ref()]);
+
+    assert_eq!(tree.root_hash().as_slice(), root_hash.as_ref());
+    assert_eq!(tree.hashing_scheme(), HashingScheme::Unprefixed);
+
+    for value in values {
+        let proof = tree.gen_proof(value).unwrap();
+
+        assert_eq!(proof.scheme, HashingScheme::Unprefixed);
+        assert!(proof.validate(tree.root_hash()));
+
+        let mut default_scheme = proof.clone();
+        default_scheme.scheme = HashingScheme::DomainSeparated;
+        assert!(!default_scheme.validate(tree.root_hash()));
+    }
+}
+
+#[test]
+fn test_builder_duplicate_odd_leaf() {
+    let values = vec!["a", "b", "c"];
+    let tree = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(values.clone());
+
+    let ha = digest.hash_leaf(&"a");
+    let hb = digest.hash_leaf(&"b");
+    let hc = digest.hash_leaf(&"c");
+    let hab = digest.hash_nodes(&ha, &hb);
+    let hcc = digest.hash_nodes(&hc, &hc);
+    let root_hash = digest.hash_nodes(&hab, &hcc);
+
+    assert_eq!(tree.root_hash().as_slice(), root_hash.as_ref());
+    assert_eq!(tree.count(), 3);
+    assert_eq!(tree.height(), 2);
+    assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), values);
+    assert_eq!(tree.clone().into_iter().collect::<Vec<_>>(), values);
+
+    let padded = MerkleTree::from_vec(digest, vec!["a", "b", "c", "c"]);
+    assert_eq!(tree.root_hash(), padded.root_hash());
+
+    for value in values {
+        let proof = tree.gen_proof(value).unwrap();
+        assert!(proof.validate(tree.root_hash()));
+    }
+
+    let mut five = tree.clone();
+    five.push("d");
+    five.push("e");
+
+    assert_eq!(five.iter().cloned().collect::<Vec<_>>(), vec!["a", "b", "c", "d", "e"]);
+    assert_eq!(five.odd_leaf_policy(), OddLeafPolicy::Duplicate);
+    assert!(five.gen_proof("e").unwrap().validate(five.root_hash()));
+}
+
+#[test]
+fn test_builder_sorted() {
+    let values = vec!["c", "a", "b"];
+    let tree = MerkleTreeBuilder::new(digest)
+        .sorted(LeafOrdering::by_value())
+        .build_from_vec(values.clone());
+
+    assert_eq!(tree, MerkleTree::from_vec_ordered(digest, values, LeafOrdering::by_value()));
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["a", "b", "c"]));
+}
+
+#[test]
+fn test_builder_dedup() {
+    let values = vec!["c", "a", "c", "b", "a"];
+
+    let mut tree = MerkleTreeBuilder::new(digest)
+        .dedup(DedupOrder::FirstOccurrence)
+        .build_from_vec(values.clone());
+
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["c", "a", "b"]));
+    assert_eq!(tree.dedup(), Some(DedupOrder::FirstOccurrence));
+
+    assert!(!tree.push("a"));
+    assert!(tree.push("d"));
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["c", "a", "b", "d"]));
+
+    let sorted = MerkleTreeBuilder::new(digest)
+        .dedup(DedupOrder::FirstOccurrence)
+        .sorted(LeafOrdering::by_value())
+        .build_from_vec(values.clone());
+
+    assert_eq!(sorted, MerkleTree::from_vec(digest, vec!["a", "b", "c"]));
+
+    let mut by_hash = MerkleTreeBuilder::new(digest)
+        .dedup(DedupOrder::ByHash)
+        .build_from_vec(values);
+
+    by_hash.push("d");
+    let (expected, _) = MerkleTree::from_vec_dedup(digest, vec!["d", "c", "b", "a"], DedupOrder::ByHash);
+    assert_eq!(by_hash, expected);
+}
+
+#[test]
+fn test_builder_parallel() {
+    let values = (0..10_000u32).map(|x| x.to_string()).collect::<Vec<_>>();
+
+    for policy in vec![OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        let sequential = MerkleTreeBuilder::new(digest)
+            .odd_leaf_policy(policy)
+            .build_from_vec(values.clone());
+
+        let parallel = MerkleTreeBuilder::new(digest)
+            .odd_leaf_policy(policy)
+            .parallel(true)
+            .build_from_vec(values.clone());
+
+        assert_eq!(parallel, sequential);
+    }
+}
+
+#[test]
+fn test_parallel_thresholds() {
+    let small = (0..1500u32).map(|x| x.to_string().into_bytes()).collect::<Vec<_>>();
+
+    // A few large values among many small ones, which split unevenly by count
+    let mut skewed = small[..200].to_vec();
+    skewed.insert(3, vec![1; 1 << 16]);
+    skewed.insert(150, vec![2; 1 << 15]);
+    skewed.push(vec![3; 1 << 16]);
+
+    let thresholds = vec![0, 1, 2, 7, DEFAULT_PARALLEL_THRESHOLD, usize::MAX];
+
+    for values in vec![small, skewed, vec![b"one".to_vec()], Vec::new()] {
+        for policy in vec![OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+            let builder = || MerkleTreeBuilder::new(digest).odd_leaf_policy(policy).leaf_counts(true);
+            let expected = builder().build_from_vec(values.clone());
+
+            for &threshold in &thresholds {
+                let parallel = builder()
+                    .parallel(true)
+                    .parallel_threshold(threshold)
+                    .build_from_vec(values.clone());
+
+                let hashed_in_parallel = builder()
+                    .parallel_threshold(threshold)
+                    .build_from_vec_parallel(values.clone());
+
+                assert_eq!(parallel, expected);
+                assert_eq!(hashed_in_parallel, expected);
+                assert_eq!(hashed_in_parallel.root_hash(), expected.root_hash());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_build_from_vec_parallel_options() {
+    let values = (0..2000u32).map(|x| (x % 1500).to_string()).collect::<Vec<_>>();
+
+    let builder = || {
+        MerkleTreeBuilder::new(digest)
+            .sorted(LeafOrdering::by_value())
+            .dedup(DedupOrder::FirstOccurrence)
+            .parallel_threshold(1)
+    };
+
+    let expected = builder().build_from_vec(values.clone());
+    let tree = builder().build_from_vec_parallel(values.clone());
+
+    assert_eq!(tree, expected);
+    assert_eq!(tree.count(), 1500);
+
+    let limited = MerkleTreeBuilder::new(digest).max_leaves(100).parallel_threshold(1);
+    assert_eq!(limited.try_build_from_vec_parallel(values.clone()), Err(Error::TooManyLeaves { max: 100 }));
+
+    let lazy = MerkleTreeBuilder::new(digest).lazy(true).build_from_vec_parallel(values.clone());
+    assert_eq!(lazy.root_hash(), MerkleTree::from_vec(digest, values).root_hash());
+}
+
+#[test]
+fn test_weighted_runs() {
+    let weights = vec![10, 10, 1000, 10, 10, 10, 500, 500, 10];
+    let runs = weighted_runs(&weights, 3);
+
+    assert_eq!(runs, vec![0..3, 3..8, 8..9]);
+    assert_eq!(weighted_runs(&weights, 1), vec![0..9]);
+    assert_eq!(weighted_runs(&[], 4), vec![]);
+
+    // Every index is in exactly one run, in order, whatever the number of runs
+    for count in 1..12 {
+        let runs = weighted_runs(&weights, count);
+
+        assert!(runs.len() <= count);
+        assert_eq!(runs.iter().flat_map(|run| run.clone()).collect::<Vec<_>>(), (0..weights.len()).collect::<Vec<_>>());
+    }
+}
+
+/// Yields `"x"` forever, while claiming to yield `usize::MAX` values.
+struct Endless;
+
+impl Iterator for Endless {
+    type Item = &'static str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some("x")
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+#[test]
+fn test_builder_max_leaves() {
+    let values = vec!["a", "b", "c", "d"];
+
+    let built = MerkleTreeBuilder::new(digest)
+        .max_leaves(4)
+        .try_build_from_vec(values.clone());
+
+    assert_eq!(built, Ok(MerkleTree::from_vec(digest, values.clone())));
+
+    let too_many = MerkleTreeBuilder::new(digest)
+        .max_leaves(3)
+        .try_build_from_vec(values.clone());
+
+    assert_eq!(too_many, Err(Error::TooManyLeaves { max: 3 }));
+
+    let deduped = MerkleTreeBuilder::new(digest)
+        .max_leaves(2)
+        .dedup(DedupOrder::FirstOccurrence)
+        .try_build_from_vec(vec!["a", "b", "a", "b"]);
+
+    assert_eq!(deduped, Ok(MerkleTree::from_vec(digest, vec!["a", "b"])));
+
+    let endless = MerkleTreeBuilder::new(digest)
+        .max_leaves(100)
+        .try_build_from_iter(Endless);
+
+    assert_eq!(endless, Err(Error::TooManyLeaves { max: 100 }));
+}
+
+#[test]
+fn test_try_push_max_leaves() {
+    let mut tree = MerkleTreeBuilder::new(digest)
+        .max_leaves(3)
+        .build_from_vec(vec!["a", "b"]);
+
+    assert_eq!(tree.try_push("c"), Ok(true));
+    assert_eq!(tree.try_push("d"), Err(Error::TooManyLeaves { max: 3 }));
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["a", "b", "c"]));
+
+    assert_eq!(tree.try_extend(vec!["d", "e"]), Err(Error::TooManyLeaves { max: 3 }));
+    assert_eq!(tree.try_extend(Vec::new()), Ok(0));
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["a", "b", "c"]));
+}
+
+#[test]
+fn test_extend() {
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b"]);
+    tree.extend(vec!["c", "d", "e"]);
+
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["a", "b", "c", "d", "e"]));
+
+    let mut sorted = MerkleTreeBuilder::new(digest)
+        .sorted(LeafOrdering::by_value())
+        .dedup(DedupOrder::FirstOccurrence)
+        .build_from_vec(vec!["d", "b"]);
+
+    assert_eq!(sorted.try_extend(vec!["c", "a", "b", "c"]), Ok(2));
+    assert_eq!(sorted, MerkleTree::from_vec(digest, vec!["a", "b", "c", "d"]));
+}
+
+#[test]
+fn test_validate_max_depth() {
+    let chain = |depth: usize| {
+        let mut lemma = Lemma {
+            node_hash: digest.hash_leaf(&"a").as_ref().into(),
+            sibling_hash: None,
+            sub_lemma: None,
+        };
+
+        for _ in 0..depth {
+            let sibling = digest.hash_leaf(&"b").as_ref().to_vec();
+            let node_hash = digest.hash_nodes(&lemma.node_hash, &sibling).as_ref().into();
+
+            lemma = Lemma {
+                node_hash: node_hash,
+                sibling_hash: Some(Positioned::Right(sibling)),
+                sub_lemma: Some(Box::new(lemma)),
+            };
+        }
+
+        Proof::new(digest, lemma.node_hash.clone(), lemma, "a")
+    };
+
+    let deepest = chain(MAX_TREE_DEPTH);
+    assert!(deepest.validate(&deepest.root_hash));
+
+    let too_deep = chain(MAX_TREE_DEPTH + 1);
+    assert!(!too_deep.validate(&too_deep.root_hash));
+
+    assert!(deepest.into_data().into_proof(digest).is_ok());
+    assert_eq!(
+        too_deep.into_data().into_proof(digest),
+        Err(ConversionError::MalformedLemma { level: MAX_TREE_DEPTH + 1 })
+    );
+}
+
+#[test]
+fn test_memory_footprint() {
+    let values = (0..64u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let empty = MerkleTree::from_vec(digest, Vec::<String>::new()).memory_footprint();
+
+    assert_eq!(empty.nodes, 1);
+    assert_eq!(empty.leaves, 0);
+    assert_eq!(empty.value_bytes, 0);
+
+    let mut previous = empty;
+
+    for count in 1..values.len() {
+        let report = MerkleTree::from_vec(digest, values[..count].to_vec()).memory_footprint();
+
+        assert_eq!(report.leaves, count);
+        assert_eq!(report.nodes, 2 * count - 1);
+        assert_eq!(report.hash_bytes, report.nodes * digest.output_len);
+        assert!(report.value_bytes >= values[..count].iter().map(|v| v.len()).sum());
+        assert!(report.node_bytes >= previous.node_bytes);
+        assert!(report.total() > previous.total());
+
+        previous = report;
+    }
+}
+
+#[test]
+fn test_memory_footprint_hash_length() {
+    let values = vec!["one", "two", "three", "four", "five"];
+
+    let sha256 = MerkleTree::from_vec(&SHA256, values.clone()).memory_footprint();
+    let sha512 = MerkleTree::from_vec(&SHA512, values).memory_footprint();
+
+    assert_eq!(sha256.nodes, sha512.nodes);
+    assert_eq!(sha256.value_bytes, 0);
+    assert_eq!(sha512.hash_bytes, 2 * sha256.hash_bytes);
+
+    // The hashes are held inline, in room for the longest one
+    assert_eq!(sha512.total(), sha256.total());
+}
+
+#[test]
+fn test_memory_footprint_with() {
+    let values = vec!["one", "two", "three"];
+    let tree = MerkleTree::from_vec(digest, values);
+    let report = tree.memory_footprint_with(|v| v.len());
+
+    assert_eq!(report.value_bytes, 11);
+    assert_eq!(report.hash_bytes, tree.memory_footprint().hash_bytes);
+}
+
+#[test]
+fn test_interned_memory_footprint() {
+    // A 4 KB template in 30% of the leaves, distinct small values in the others
+    let template = "x".repeat(4096);
+    let values = (0..1000)
+        .map(|i| if i % 10 < 3 { template.clone() } else { format!("value {}", i) })
+        .collect::<Vec<_>>();
+
+    let plain = MerkleTree::from_vec(digest, values.clone());
+    let interned = MerkleTreeBuilder::new(digest).build_interned(values.clone());
+
+    assert_eq!(interned.root_hash(), plain.root_hash());
+    assert_eq!(interned.count(), plain.count());
+
+    let plain_report = plain.memory_footprint();
+    let interned_report = interned.memory_footprint();
+
+    assert_eq!(interned_report.nodes, plain_report.nodes);
+    assert_eq!(interned_report.hash_bytes, plain_report.hash_bytes);
+    assert!(plain_report.value_bytes >= 300 * template.len());
+
+    // The template is held once, the shares of its handles adding up to a single copy
+    assert!(interned_report.value_bytes < 2 * template.len() + 700 * 64);
+    assert!(interned_report.value_bytes * 10 < plain_report.value_bytes);
+    assert!(interned_report.total() * 5 < plain_report.total());
+
+    // The values are read through the handles, and got back by value
+    assert!(interned.iter().zip(&values).all(|(value, expected)| **value == *expected));
+    assert_eq!(interned.into_iter().map(Interned::into_inner).collect::<Vec<_>>(), values);
+}
+
+#[test]
+fn test_interned_proofs() {
+    let values = vec!["a", "dup", "b", "c", "dup", "d", "dup"];
+
+    let plain = MerkleTree::from_vec(digest, values.clone());
+    let interned = MerkleTreeBuilder::new(digest).build_interned(values.clone());
+
+    let leaves = interned.iter().collect::<Vec<_>>();
+    assert!(Interned::ptr_eq(leaves[1], leaves[4]));
+    assert!(Interned::ptr_eq(leaves[1], leaves[6]));
+    assert!(!Interned::ptr_eq(leaves[0], leaves[2]));
+
+    // The proofs of equal values at different positions have different paths
+    let first = interned.gen_nth_proof(1).unwrap();
+    let second = interned.gen_nth_proof(4).unwrap();
+
+    assert_eq!(*first.value, *second.value);
+    assert_eq!(first.leaf_index, Some(1));
+    assert_eq!(second.leaf_index, Some(4));
+    assert!(!first.same_path(&second));
+
+    for (index, value) in values.iter().enumerate() {
+        let proof = interned.gen_nth_proof(index).unwrap();
+        let expected = plain.gen_nth_proof(index).unwrap();
+
+        assert_eq!(**proof.value, **value);
+        assert!(proof.validate(plain.root_hash()));
+        assert!(proof.validate_against(interned.root_hash(), values.len() as u64));
+        assert!(proof.same_path(&expected));
+    }
+
+    // Values pushed through the same table are shared with the ones already in the tree
+    let mut table = InternTable::new();
+    let mut pushed = MerkleTree::from_vec(digest, values.iter().map(|&value| table.intern(value)).collect());
+    pushed.push(table.intern("dup"));
+    assert_eq!(table.len(), 5);
+
+    let leaves = pushed.iter().collect::<Vec<_>>();
+    assert!(Interned::ptr_eq(leaves[1], leaves[7]));
+
+    drop(leaves);
+    drop(pushed);
+    table.purge();
+    assert!(table.is_empty());
+}
+
+#[test]
+fn test_verify_manifest() {
+    let values = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+    let hashes = tree.leaf_hashes_vec();
+
+    let full = hashes.iter().cloned().enumerate().collect::<Vec<_>>();
+
+    let report = tree.verify_manifest(full.clone());
+    assert_eq!(report.matched, 10);
+    assert!(report.is_valid());
+    assert!(report.failures().is_empty());
+    assert!(tree.verify_manifest_strict(full.clone().into_iter().rev()).is_valid());
+
+    // A partial manifest is valid unless it has to name every leaf
+    let partial = vec![(7, hashes[7].clone()), (2, hashes[2].clone())];
+
+    let report = tree.verify_manifest(partial.clone());
+    assert_eq!(report.entries, vec![(7, ManifestStatus::Match), (2, ManifestStatus::Match)]);
+    assert!(report.is_valid());
+
+    let strict = tree.verify_manifest_strict(partial);
+    assert_eq!(strict.matched, 2);
+    assert_eq!(strict.missing, vec![0, 1, 3, 4, 5, 6, 8, 9]);
+    assert!(!strict.is_valid());
+
+    // Every mismatch is reported, not only the first one
+    let mut wrong = full.clone();
+    wrong[3].1 = hashes[4].clone();
+    wrong[8].1 = hashes[8][..16].to_vec();
+    wrong[9].1 = Vec::new();
+    wrong.push((10, hashes[0].clone()));
+    wrong.push((usize::MAX, hashes[0].clone()));
+
+    let report = tree.verify_manifest(wrong);
+    assert_eq!(report.matched, 7);
+    assert_eq!(report.mismatched, 3);
+    assert_eq!(report.out_of_range, 2);
+    assert_eq!(report.pruned, 0);
+    assert_eq!(
+        report.failures(),
+        vec![
+            &(3, ManifestStatus::Mismatch { found: hashes[3].clone() }),
+            &(8, ManifestStatus::Mismatch { found: hashes[8].clone() }),
+            &(9, ManifestStatus::Mismatch { found: hashes[9].clone() }),
+            &(10, ManifestStatus::OutOfRange),
+            &(usize::MAX, ManifestStatus::OutOfRange),
+        ]
+    );
+
+    // The manifest itself names some leaves several times, and leaves some out
+    let mut duplicated = full.clone();
+    duplicated[5] = (4, hashes[4].clone());
+    duplicated.push((4, hashes[4].clone()));
+    duplicated.push((9, hashes[0].clone()));
+
+    let report = tree.verify_manifest(duplicated.clone());
+    assert_eq!(report.matched, 11);
+    assert_eq!(report.mismatched, 1);
+    assert!(report.duplicated.is_empty());
+
+    let strict = tree.verify_manifest_strict(duplicated);
+    assert_eq!(strict.duplicated, vec![4, 9]);
+    assert_eq!(strict.missing, vec![5]);
+    assert!(!strict.is_valid());
+
+    // The leaves of pruned subtrees have no known hash
+    let mut pruned = tree.clone();
+    pruned.prune_below(1);
+    let report = pruned.verify_manifest(full.clone());
+    assert_eq!(report.pruned + report.matched, 10);
+    assert!(report.pruned > 0);
+
+    let empty = MerkleTree::from_vec(digest, Vec::<String>::new());
+    assert!(empty.verify_manifest_strict(vec![]).is_valid());
+    assert_eq!(empty.verify_manifest(vec![(0, hashes[0].clone())]).out_of_range, 1);
+}
+
+#[test]
+fn test_verify_manifest_parallel() {
+    let values = (0..5000).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let parallel = MerkleTreeBuilder::new(digest).parallel(true).build_from_vec(values);
+
+    let mut manifest = tree.leaf_hashes_vec().into_iter().enumerate().collect::<Vec<_>>();
+    manifest.reverse();
+    manifest[100].1[0] ^= 1;
+    manifest.push((5000, vec![0; 64]));
+
+    let report = tree.verify_manifest_strict(manifest.clone());
+    assert_eq!(parallel.verify_manifest_strict(manifest), report);
+
+    assert_eq!(report.matched, 4999);
+    assert_eq!(report.mismatched, 1);
+    assert_eq!(report.out_of_range, 1);
+    assert_eq!(report.entries[100].0, 4899);
+    assert!(report.missing.is_empty());
+}
+
+#[test]
+fn test_index_overflow() {
+    // A 32-bit target, on which the indices of trees of more than 2^32 leaves do not fit
+    let max = u32::MAX as usize;
+
+    assert_eq!(narrow(0, max), Ok(0));
+    assert_eq!(narrow(u32::MAX as u64, max), Ok(max));
+    assert_eq!(narrow(1 << 32, max), Err(Error::IndexOverflow { index: 1 << 32 }));
+    assert_eq!(narrow(u64::MAX, max), Err(Error::IndexOverflow { index: u64::MAX }));
+
+    assert_eq!(to_index(u32::MAX as u64), Ok(max));
+
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    assert_eq!(tree.count_u64(), 3);
+
+    assert_eq!(tree.gen_nth_proof_u64(2), Ok(tree.gen_nth_proof(2)));
+    assert_eq!(tree.gen_nth_proof_u64(3), Ok(None));
+    assert_eq!(tree.prove_prefix_u64(2), Ok(tree.prove_prefix(2)));
+    assert_eq!(tree.update_u64(1, "B"), Ok("b"));
+    assert_eq!(tree.update_u64(3, "D"), Err(Error::LeafIndexOutOfRange { index: 3, count: 3 }));
+
+    // Past the last leaf, whether it overflows or not
+    assert!(tree.gen_nth_proof_u64(u64::MAX).map_or(true, |proof| proof.is_none()));
+
+    // Consistency proofs between trees of more than 2^32 leaves are verified without truncation:
+    // the older tree of 2^32 leaves is the left child of the newer one of 2^33 leaves
+    let old_root = vec![1; 32];
+    let right = vec![2; 32];
+    let new_root = HashingScheme::DomainSeparated.hash_nodes(&SHA256, &old_root, &right).as_ref().to_vec();
+
+    let proof = PrefixProof {
+        algorithm: &SHA256,
+        hasher: None,
+        scheme: HashingScheme::DomainSeparated,
+        hashes: vec![right],
+    };
+
+    assert!(proof.verify_u64(&old_root, 1 << 32, &new_root, 1 << 33));
+    assert!(!proof.verify_u64(&old_root, (1 << 32) + 1, &new_root, 1 << 33));
+    assert!(!proof.verify_u64(&old_root, 1 << 32, &new_root, 1 << 32));
+}
+
+#[test]
+fn test_stats() {
+    // (leaf_count, height, internal_nodes, min_leaf_depth, max_leaf_depth, is_perfect)
+    let expected = [
+        (1, 0, 0, 0, 0, true),
+        (2, 1, 1, 1, 1, true),
+        (3, 2, 2, 1, 2, false),
+        (4, 2, 3, 2, 2, true),
+        (5, 3, 4, 1, 3, false),
+        (6, 3, 5, 2, 3, false),
+        (7, 3, 6, 2, 3, false),
+        (8, 3, 7, 3, 3, true),
+        (9, 4, 8, 1, 4, false),
+        (10, 4, 9, 2, 4, false),
+        (11, 4, 10, 2, 4, false),
+        (12, 4, 11, 3, 4, false),
+        (13, 4, 12, 2, 4, false),
+        (14, 4, 13, 3, 4, false),
+        (15, 4, 14, 3, 4, false),
+        (16, 4, 15, 4, 4, true),
+        (17, 5, 16, 1, 5, false),
+    ];
+
+    for &(count, height, internal_nodes, min_leaf_depth, max_leaf_depth, is_perfect) in &expected {
+        let tree = MerkleTree::from_vec(digest, (0..count).map(|x: usize| x.to_string()).collect());
+        let stats = tree.stats();
+
+        assert_eq!(
+            stats,
+            TreeStats {
+                leaf_count: count,
+                height: height,
+                internal_nodes: internal_nodes,
+                min_leaf_depth: min_leaf_depth,
+                max_leaf_depth: max_leaf_depth,
+                is_perfect: is_perfect,
+                algorithm_name: "SHA-512".to_string(),
+                root_hex: to_hex(tree.root_hash()),
+            }
+        );
+    }
+}
+
+#[test]
+fn test_stats_shapes() {
+    let empty = MerkleTree::from_vec(&SHA256, Vec::<String>::new()).stats();
+    assert_eq!(empty.leaf_count, 0);
+    assert_eq!(empty.internal_nodes, 0);
+    assert!(!empty.is_perfect);
+    assert_eq!(empty.algorithm_name, "SHA-256");
+    assert_eq!(empty.root_hex, to_hex(SHA256.hash_empty().as_ref()));
+
+    let values = vec!["a", "b", "c", "d", "e"];
+
+    let duplicate = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(values.clone())
+        .stats();
+    assert_eq!((duplicate.internal_nodes, duplicate.min_leaf_depth, duplicate.max_leaf_depth), (6, 3, 3));
+
+    // The copies of the odd nodes are `Empty` ones
+    assert!(!duplicate.is_perfect);
+
+    let mut fixed = MerkleTree::with_fixed_depth(digest, 4);
+    for value in values {
+        fixed.push(value);
+    }
+
+    let stats = fixed.stats();
+    assert_eq!(stats.height, 4);
+    assert_eq!((stats.internal_nodes, stats.min_leaf_depth, stats.max_leaf_depth), (7, 4, 4));
+    assert!(!stats.is_perfect);
+}
+
+#[test]
+fn test_iter_leaf_hashes() {
+    for count in 0..18 {
+        let values = (0..count).map(|x: usize| x.to_string()).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(digest, values.clone());
+        let expected = values.iter().map(|value| digest.hash_leaf(value).as_ref().to_vec()).collect::<Vec<_>>();
+
+        let hashes = tree.iter_leaf_hashes();
+        assert_eq!(hashes.len(), count);
+        assert_eq!(hashes.map(|hash| hash.to_vec()).collect::<Vec<_>>(), expected);
+        assert_eq!(tree.leaf_hashes_vec(), expected);
+
+        let reversed = tree.iter_leaf_hashes().rev().map(|hash| hash.to_vec()).collect::<Vec<_>>();
+        assert_eq!(reversed, expected.iter().rev().cloned().collect::<Vec<_>>());
+
+        let rebuilt = MerkleTree::from_leaf_hashes(digest, tree.leaf_hashes_vec()).unwrap();
+        assert_eq!(rebuilt.root_hash(), tree.root_hash());
+        assert_eq!(rebuilt.leaf_hashes_vec(), expected);
+    }
+
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d", "e"]);
+    let mut hashes = tree.iter_leaf_hashes();
+    assert_eq!(hashes.next(), Some(digest.hash_leaf(&"a").as_ref()));
+    assert_eq!(hashes.next_back(), Some(digest.hash_leaf(&"e").as_ref()));
+    assert_eq!(hashes.len(), 3);
+    assert_eq!(hashes.collect::<Vec<_>>().len(), 3);
+
+    let mut short = tree.leaf_hashes_vec();
+    short[1].pop();
+    assert_eq!(
+        MerkleTree::from_leaf_hashes(digest, short).unwrap_err(),
+        Error::HashLengthMismatch {
+            expected: 64,
+            found: 63,
+        }
+    );
+}
+
+#[test]
+fn test_iter_leaf_hashes_shapes() {
+    let values = vec!["a", "b", "c", "d", "e"];
+    let expected = MerkleTree::from_vec(digest, values.clone()).leaf_hashes_vec();
+
+    let duplicate = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(values.clone());
+    assert_eq!(duplicate.leaf_hashes_vec(), expected);
+    assert_eq!(duplicate.iter_leaf_hashes().len(), 5);
+
+    let mut fixed = MerkleTree::with_fixed_depth(digest, 4);
+    for value in values.clone() {
+        fixed.push(value);
+    }
+    assert_eq!(fixed.leaf_hashes_vec(), expected);
+    assert_eq!(fixed.iter_leaf_hashes().rev().count(), 5);
+
+    let lines = MerkleTree::from_lines_hashed(digest, "a\nb\nc\nd\ne".as_bytes()).unwrap();
+    assert_eq!(lines.leaf_hashes_vec(), expected);
+}
+
+/// Records the nodes it visits, breaking at the internal nodes of the given depth.
+struct Recorder {
+    visits: Vec<String>,
+    break_at: Option<usize>,
+}
+
+impl Recorder {
+    fn new(break_at: Option<usize>) -> Self {
+        Recorder {
+            visits: Vec::new(),
+            break_at: break_at,
+        }
+    }
+}
+
+impl TreeVisitor<&'static str> for Recorder {
+    fn visit_internal(&mut self, depth: usize, hash: &[u8], left_hash: &[u8], right_hash: &[u8]) -> ControlFlow<()> {
+        assert_eq!(hash, digest.hash_nodes(&left_hash, &right_hash).as_ref());
+        self.visits.push(format!("node {}", depth));
+
+        if self.break_at == Some(depth) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+
+    fn visit_leaf(&mut self, depth: usize, index: usize, hash: &[u8], value: &&'static str) {
+        assert_eq!(hash, digest.hash_leaf(value).as_ref());
+        self.visits.push(format!("{} {} {}", value, depth, index));
+    }
+
+    fn visit_pruned(&mut self, depth: usize, _: &[u8], leaves: usize) {
+        self.visits.push(format!("pruned {} {}", depth, leaves));
+    }
+}
+
+fn walk_visits(tree: &MerkleTree<&'static str>, order: TraversalOrder, break_at: Option<usize>) -> Vec<String> {
+    let mut recorder = Recorder::new(break_at);
+    tree.walk(&mut recorder, order);
+    recorder.visits
+}
+
+#[test]
+fn test_walk() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d", "e"]);
+
+    assert_eq!(
+        walk_visits(&tree, TraversalOrder::PreOrder, None),
+        vec!["node 0", "node 1", "node 2", "a 3 0", "b 3 1", "node 2", "c 3 2", "d 3 3", "e 1 4"]
+    );
+    assert_eq!(
+        walk_visits(&tree, TraversalOrder::PostOrder, None),
+        vec!["a 3 0", "b 3 1", "node 2", "c 3 2", "d 3 3", "node 2", "node 1", "e 1 4", "node 0"]
+    );
+    assert_eq!(
+        walk_visits(&tree, TraversalOrder::LevelOrder, None),
+        vec!["node 0", "node 1", "e 1 4", "node 2", "node 2", "a 3 0", "b 3 1", "c 3 2", "d 3 3"]
+    );
+
+    let empty = MerkleTree::from_vec(digest, Vec::<&'static str>::new());
+    for &order in &[TraversalOrder::PreOrder, TraversalOrder::PostOrder, TraversalOrder::LevelOrder] {
+        assert!(walk_visits(&empty, order, None).is_empty());
+    }
+}
+
+#[test]
+fn test_walk_break() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d", "e"]);
+
+    // The leaves after a pruned subtree keep their index
+    assert_eq!(walk_visits(&tree, TraversalOrder::PreOrder, Some(1)), vec!["node 0", "node 1", "e 1 4"]);
+    assert_eq!(walk_visits(&tree, TraversalOrder::LevelOrder, Some(1)), vec!["node 0", "node 1", "e 1 4"]);
+    assert_eq!(
+        walk_visits(&tree, TraversalOrder::PreOrder, Some(2)),
+        vec!["node 0", "node 1", "node 2", "node 2", "e 1 4"]
+    );
+    assert_eq!(walk_visits(&tree, TraversalOrder::PreOrder, Some(0)), vec!["node 0"]);
+
+    // In post-order, breaking ends the walk
+    assert_eq!(
+        walk_visits(&tree, TraversalOrder::PostOrder, Some(2)),
+        vec!["a 3 0", "b 3 1", "node 2"]
+    );
+}
+
+#[test]
+fn test_walk_skips_empty_nodes() {
+    let mut tree = MerkleTree::with_fixed_depth(digest, 3);
+    for value in vec!["a", "b", "c"] {
+        tree.push(value);
+    }
+
+    assert_eq!(
+        walk_visits(&tree, TraversalOrder::PreOrder, None),
+        vec!["node 0", "node 1", "node 2", "a 3 0", "b 3 1", "node 2", "c 3 2"]
+    );
+
+    let duplicate = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(vec!["a", "b", "c"]);
+
+    assert_eq!(
+        walk_visits(&duplicate, TraversalOrder::LevelOrder, None),
+        vec!["node 0", "node 1", "node 1", "a 2 0", "b 2 1", "c 2 2"]
+    );
+}
+
+/// Describes a node as `walk_visits` does.
+fn describe(node: NodeRef<&'static str>) -> String {
+    match node {
+        NodeRef::Internal { depth, .. } => format!("node {}", depth),
+        NodeRef::Leaf { depth, index, value, .. } => format!("{} {} {}", value, depth, index),
+        NodeRef::Pruned { depth, leaves, .. } => format!("pruned {} {}", depth, leaves),
+    }
+}
+
+#[test]
+fn test_iter_nodes() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d", "e"]);
+
+    assert_eq!(
+        tree.iter_nodes_preorder().map(describe).collect::<Vec<_>>(),
+        vec!["node 0", "node 1", "node 2", "a 3 0", "b 3 1", "node 2", "c 3 2", "d 3 3", "e 1 4"]
+    );
+    assert_eq!(
+        tree.iter_nodes_postorder().map(describe).collect::<Vec<_>>(),
+        vec!["a 3 0", "b 3 1", "node 2", "c 3 2", "d 3 3", "node 2", "node 1", "e 1 4", "node 0"]
+    );
+
+    for &order in &[TraversalOrder::PreOrder, TraversalOrder::PostOrder] {
+        let nodes = match order {
+            TraversalOrder::PostOrder => tree.iter_nodes_postorder().map(describe).collect::<Vec<_>>(),
+            _ => tree.iter_nodes_preorder().map(describe).collect(),
+        };
+
+        assert_eq!(nodes, walk_visits(&tree, order, None));
+    }
+
+    let first = tree.iter_nodes_preorder().next().unwrap();
+    assert_eq!(first.hash(), tree.root_hash().as_slice());
+    assert_eq!(first.depth(), 0);
+
+    let last = tree.iter_nodes_postorder().last().unwrap();
+    assert_eq!(last, first);
+
+    // Post-order hashes can be recomputed bottom-up
+    let mut stack: Vec<Vec<u8>> = Vec::new();
+    for node in tree.iter_nodes_postorder() {
+        match node {
+            NodeRef::Leaf { value, .. } => stack.push(digest.hash_leaf(value).as_ref().to_vec()),
+            NodeRef::Pruned { .. } => unreachable!("the tree is not pruned"),
+            NodeRef::Internal { hash, .. } => {
+                let right = stack.pop().unwrap();
+                let left = stack.pop().unwrap();
+                assert_eq!(digest.hash_nodes(&left, &right).as_ref(), hash);
+                stack.push(hash.to_vec());
+            }
+        }
+    }
+    assert_eq!(stack, vec![tree.root_hash().clone()]);
+}
+
+#[test]
+fn test_iter_nodes_edge_cases() {
+    let empty = MerkleTree::from_vec(digest, Vec::<&'static str>::new());
+
+    let mut preorder = empty.iter_nodes_preorder();
+    assert!(preorder.next().is_none());
+    assert!(preorder.next().is_none());
+
+    let mut postorder = empty.iter_nodes_postorder();
+    assert!(postorder.next().is_none());
+    assert!(postorder.next().is_none());
+
+    let single = MerkleTree::from_vec(digest, vec!["a"]);
+    assert_eq!(single.iter_nodes_preorder().map(describe).collect::<Vec<_>>(), vec!["a 0 0"]);
+    assert_eq!(single.iter_nodes_postorder().map(describe).collect::<Vec<_>>(), vec!["a 0 0"]);
+
+    let mut fixed = MerkleTree::with_fixed_depth(digest, 3);
+    for value in vec!["a", "b", "c"] {
+        fixed.push(value);
+    }
+    assert_eq!(
+        fixed.iter_nodes_postorder().map(describe).collect::<Vec<_>>(),
+        vec!["a 3 0", "b 3 1", "node 2", "c 3 2", "node 2", "node 1", "node 0"]
+    );
+
+    let mut deep = MerkleTree::with_fixed_depth(digest, MAX_TREE_DEPTH - 1);
+    deep.push("a");
+    assert_eq!(deep.iter_nodes_preorder().count(), MAX_TREE_DEPTH);
+    assert_eq!(deep.iter_nodes_postorder().last().unwrap().depth(), 0);
+}
+
+#[test]
+fn test_prune_below() {
+    let values = vec!["a", "b", "c", "d", "e"];
+    let original = MerkleTree::from_vec(digest, values.clone());
+    let ab = MerkleTree::from_vec(digest, vec!["a", "b"]);
+
+    let mut tree = original.clone();
+    tree.prune_below(2);
+
+    assert_eq!(tree.root_hash(), original.root_hash());
+    assert_eq!(tree.count(), 5);
+    assert!(tree.memory_footprint().total() < original.memory_footprint().total());
+    assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&"e"]);
+    assert_eq!(tree.iter_leaf_hashes().count(), 1);
+
+    assert_eq!(
+        walk_visits(&tree, TraversalOrder::PreOrder, None),
+        vec!["node 0", "node 1", "pruned 2 2", "pruned 2 2", "e 1 4"]
+    );
+    assert_eq!(
+        tree.iter_nodes_postorder().map(describe).collect::<Vec<_>>(),
+        vec!["pruned 2 2", "pruned 2 2", "node 1", "e 1 4", "node 0"]
+    );
+
+    // The kept leaves are still proven, the pruned ones name the subtree to graft back
+    let proof = tree.try_gen_nth_proof(4).unwrap().unwrap();
+    assert_eq!(proof, original.gen_nth_proof(4).unwrap());
+    assert_eq!(tree.try_gen_nth_proof(5), Ok(None));
+    assert_eq!(
+        tree.try_gen_nth_proof(1),
+        Err(Error::Pruned { subtree_root: ab.root_hash().clone() })
+    );
+    assert_eq!(tree.gen_nth_proof(1), None);
+    assert_eq!(tree.gen_proof("a"), None);
+    assert_eq!(tree.iter_proofs().collect::<Vec<_>>(), vec![proof]);
+
+    assert_eq!(
+        tree.try_extend(vec!["f"]),
+        Err(Error::Pruned { subtree_root: ab.root_hash().clone() })
+    );
+    assert_eq!(tree.count(), 5);
+
+    // Pruning below the leaves does nothing, pruning at the root keeps only its hash
+    let mut unpruned = original.clone();
+    unpruned.prune_below(4);
+    assert_eq!(unpruned, original);
+
+    let mut root = original.clone();
+    root.prune_below(0);
+    assert_eq!(root.root_hash(), original.root_hash());
+    assert_eq!(walk_visits(&root, TraversalOrder::LevelOrder, None), vec!["pruned 0 5"]);
+    assert_eq!(root.iter_proofs().count(), 0);
+}
+
+#[test]
+fn test_graft() {
+    let values = vec!["a", "b", "c", "d", "e"];
+    let original = MerkleTree::from_vec(digest, values.clone());
+
+    let mut tree = original.clone();
+    tree.prune_below(2);
+
+    let ab = MerkleTree::from_vec(digest, vec!["a", "b"]);
+    let cd = MerkleTree::from_vec(digest, vec!["c", "d"]);
+
+    assert_eq!(
+        tree.graft(ab.root_hash(), cd.clone()),
+        Err(Error::InvalidGraft("the subtree does not have the given root hash"))
+    );
+    assert_eq!(
+        tree.graft(&original.root_hash().clone(), original.clone()),
+        Err(Error::InvalidGraft("no pruned subtree has this root hash and as many leaves"))
+    );
+
+    let sha256 = MerkleTree::from_vec(&SHA256, vec!["a", "b"]);
+    assert_eq!(
+        tree.graft(&sha256.root_hash().clone(), sha256),
+        Err(Error::InvalidGraft("the subtree was built with another algorithm or scheme"))
+    );
+
+    tree.graft(&ab.root_hash().clone(), ab).unwrap();
+    assert_eq!(tree.try_gen_nth_proof(0), Ok(original.gen_nth_proof(0)));
+    assert!(tree.try_gen_nth_proof(2).is_err());
+
+    tree.graft(&cd.root_hash().clone(), cd).unwrap();
+    assert_eq!(tree, original);
+    assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), values);
+
+    tree.push("f");
+    assert_eq!(tree.root_hash(), MerkleTree::from_vec(digest, vec!["a", "b", "c", "d", "e", "f"]).root_hash());
+}
+
+#[test]
+fn test_verify_proof() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d", "e"]);
+
+    let duplicate = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(vec!["a", "b", "c", "d", "e"]);
+
+    let mut fixed = MerkleTree::with_fixed_depth(digest, 3);
+    for value in vec!["a", "b", "c", "d", "e"] {
+        fixed.push(value);
+    }
+
+    for tree in &[&tree, &duplicate, &fixed] {
+        for i in 0..tree.count() {
+            assert_eq!(tree.verify_proof(&tree.gen_nth_proof(i).unwrap()), Ok(i));
+        }
+    }
+
+    // A tree of the same shape, with another leaf
+    let other = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d", "f"]);
+    let proof = other.gen_nth_proof(0).unwrap();
+    assert_eq!(
+        tree.verify_proof(&proof),
+        Err(Error::ProofMismatch {
+            depth: 0,
+            reason: "the root hash differs",
+        })
+    );
+
+    let mut forged = proof.clone();
+    forged.root_hash = tree.root_hash().clone();
+    forged.lemma.node_hash = tree.root_hash().clone();
+    assert_eq!(
+        tree.verify_proof(&forged),
+        Err(Error::ProofMismatch {
+            depth: 1,
+            reason: "the sibling hash differs",
+        })
+    );
+
+    // The sibling must be on its side
+    let mut flipped = tree.gen_nth_proof(0).unwrap();
+    flipped.lemma.sibling_hash = flipped.lemma.sibling_hash.map(|sibling| Positioned::Left(sibling.into_inner()));
+    assert_eq!(
+        tree.verify_proof(&flipped),
+        Err(Error::ProofMismatch {
+            depth: 1,
+            reason: "the sibling hash differs",
+        })
+    );
+
+    // A path to an internal node hashes up to the root, but does not end at a leaf
+    let mut truncated = tree.gen_nth_proof(0).unwrap();
+    {
+        let node = truncated.lemma.sub_lemma.as_mut().unwrap().sub_lemma.as_mut().unwrap();
+        node.sibling_hash = None;
+        node.sub_lemma = None;
+    }
+    assert!(truncated.validate(tree.root_hash()));
+    assert_eq!(
+        tree.verify_proof(&truncated),
+        Err(Error::ProofMismatch {
+            depth: 2,
+            reason: "the path ends above a leaf",
+        })
+    );
+
+    let sha256 = MerkleTree::from_vec(&SHA256, vec!["a", "b", "c", "d", "e"]);
+    assert_eq!(tree.verify_proof(&sha256.gen_nth_proof(0).unwrap()), Err(Error::AlgorithmMismatch));
+
+    let mut pruned = tree.clone();
+    pruned.prune_below(2);
+    assert_eq!(pruned.verify_proof(&tree.gen_nth_proof(4).unwrap()), Ok(4));
+    assert!(match pruned.verify_proof(&tree.gen_nth_proof(2).unwrap()) {
+        Err(Error::Pruned { .. }) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn test_validate_with_height() {
+    for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        for count in 1..20usize {
+            let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+            let tree = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy).build_from_vec(values);
+
+            for index in 0..count {
+                let proof = tree.gen_nth_proof(index).unwrap();
+                assert_eq!(proof.validate_with_height(tree.root_hash(), tree.height()), Ok(()));
+
+                if proof.depth() > 0 {
+                    assert!(proof.validate_with_height(tree.root_hash(), proof.depth() - 1).is_err());
+                }
+            }
+        }
+    }
+
+    let values = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()];
+    let tree = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(HashingScheme::Unprefixed)
+        .build_from_vec(values);
+
+    // Without domain separation, the left child of the root passes for a leaf
+    // whose value is the concatenation of the hashes of its children
+    let mut forged = tree.gen_nth_proof(0).unwrap();
+    {
+        let node = forged.lemma.sub_lemma.as_mut().unwrap();
+        let mut value = node.sub_lemma.take().unwrap().node_hash;
+        value.extend(node.sibling_hash.take().unwrap().into_inner());
+
+        assert_eq!(HashingScheme::Unprefixed.hash_leaf(digest, &value).as_ref(), node.node_hash.as_slice());
+        forged.value = value;
+    }
+
+    assert!(forged.validate(tree.root_hash()));
+    assert_eq!(
+        forged.validate_with_height(tree.root_hash(), tree.height()),
+        Err(Error::InvalidPath("path does not end at the depth of a leaf of the tree"))
+    );
+    assert_eq!(
+        tree.verify_proof(&forged),
+        Err(Error::ProofMismatch {
+            depth: 1,
+            reason: "the path ends above a leaf",
+        })
+    );
+
+    let mut too_deep = tree.gen_nth_proof(0).unwrap();
+    assert_eq!(too_deep.validate_with_height(&[0; 64], tree.height()), Err(Error::InvalidProof));
+
+    too_deep.lemma = Lemma {
+        node_hash: tree.root_hash().clone(),
+        sibling_hash: Some(Positioned::Right(tree.root_hash().clone())),
+        sub_lemma: Some(Box::new(too_deep.lemma)),
+    };
+    assert_eq!(
+        tree.verify_proof(&too_deep),
+        Err(Error::ProofMismatch {
+            depth: 2,
+            reason: "the path goes below a leaf",
+        })
+    );
+}
+
+/// Hashes the root hashes, and the wire encodings of every proof, of trees of up to 40 leaves,
+/// of every shape, so that changes to the internal representation of hashes can be checked to
+/// leave them byte for byte the same.
+#[test]
+fn test_roots_and_proofs_are_stable() {
+    let mut context = Context::new(&SHA256);
+
+    for count in 0..41 {
+        let values = (0..count).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+
+        let mut fixed = MerkleTree::with_fixed_depth(digest, 6);
+        for value in values.clone() {
+            fixed.push(value);
+        }
+
+        let trees = vec![
+            MerkleTree::from_vec(digest, values.clone()),
+            MerkleTreeBuilder::new(digest)
+                .odd_leaf_policy(OddLeafPolicy::Duplicate)
+                .build_from_vec(values.clone()),
+            MerkleTreeBuilder::new(digest)
+                .hashing_scheme(HashingScheme::SortedPairs)
+                .build_from_vec(values.clone()),
+            fixed,
+        ];
+
+        for tree in trees {
+            context.update(tree.root_hash());
+
+            for proof in tree.iter_proofs() {
+                context.update(&proof.to_bytes());
+            }
+        }
+    }
+
+    assert_eq!(
+        to_hex(context.finish().as_ref()),
+        "72048dfe0a62a0837577d9c1c41269164ee5be27f697af8b89d2cac7a3963ea1"
+    );
+}
+
+#[test]
+#[should_panic(expected = "cannot push the value")]
+fn test_push_pruned() {
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    tree.prune_below(1);
+    tree.push("d");
+}
+
+/// A value which counts how many times it is hashed.
+#[derive(Clone, Debug)]
+struct Counted(&'static str, &'static AtomicUsize);
+
+impl Hashable for Counted {
+    fn update_context(&self, context: &mut Context) {
+        self.1.fetch_add(1, AtomicOrdering::SeqCst);
+        context.update(self.0.as_bytes());
+    }
+}
+
+impl PartialEq for Counted {
+    fn eq(&self, other: &Counted) -> bool {
+        self.0 == other.0
+    }
+}
+
+fn counted(values: &[&'static str], hashed: &'static AtomicUsize) -> Vec<Counted> {
+    values.iter().map(|value| Counted(value, hashed)).collect()
+}
+
+#[test]
+fn test_builder_lazy() {
+    static HASHED: AtomicUsize = AtomicUsize::new(0);
+
+    let values = ["one", "two", "three", "four", "five"];
+    let eager = MerkleTree::from_vec(digest, values.to_vec());
+    HASHED.store(0, AtomicOrdering::SeqCst);
+
+    let tree = MerkleTreeBuilder::new(digest)
+        .lazy(true)
+        .build_from_vec(counted(&values, &HASHED));
+
+    assert!(!tree.is_hashed());
+    assert_eq!(tree.count(), 5);
+    assert_eq!(tree.height(), eager.height());
+    assert_eq!(HASHED.load(AtomicOrdering::SeqCst), 0);
+
+    assert_eq!(tree.root_hash(), eager.root_hash());
+    assert!(tree.is_hashed());
+    assert_eq!(HASHED.load(AtomicOrdering::SeqCst), 5);
+
+    let proof = tree.gen_proof(Counted("three", &HASHED)).unwrap();
+    assert!(proof.validate(eager.root_hash()));
+    assert_eq!(HASHED.load(AtomicOrdering::SeqCst), 6);
+
+    assert_eq!(tree.iter().map(|v| v.0).collect::<Vec<_>>(), values.to_vec());
+    assert_eq!(HASHED.load(AtomicOrdering::SeqCst), 6);
+}
+
+#[test]
+fn test_builder_lazy_same_as_eager() {
+    let values = (0..100u32).map(|x| (x * 37 % 100).to_string()).collect::<Vec<_>>();
+
+    for policy in vec![OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        let builder = MerkleTreeBuilder::new(digest)
+            .odd_leaf_policy(policy)
+            .sorted(LeafOrdering::by_value());
+
+        let eager = builder.clone().build_from_vec(values.clone());
+        let lazy = builder.lazy(true).build_from_vec(values.clone());
+
+        assert_eq!(lazy.clone(), eager);
+        assert_eq!(lazy, eager);
+        assert_eq!(lazy.into_iter().collect::<Vec<_>>(), eager.into_iter().collect::<Vec<_>>());
+    }
+
+    let mut lazy = MerkleTreeBuilder::new(digest).lazy(true).build_from_vec(vec!["a", "b"]);
+    lazy.push("c");
+
+    assert!(lazy.is_hashed());
+    assert_eq!(lazy, MerkleTree::from_vec(digest, vec!["a", "b", "c"]));
+
+    let empty = MerkleTreeBuilder::new(digest).lazy(true).build_from_vec(Vec::<String>::new());
+    assert_eq!(empty, MerkleTree::from_vec(digest, Vec::new()));
+}
+
+#[test]
+fn test_builder_lazy_threads() {
+    static HASHED: AtomicUsize = AtomicUsize::new(0);
+
+    let values = (0..1000u32).map(|x| &*Box::leak(x.to_string().into_boxed_str())).collect::<Vec<_>>();
+    let eager = MerkleTree::from_vec(digest, values.clone());
+    HASHED.store(0, AtomicOrdering::SeqCst);
+
+    let tree = MerkleTreeBuilder::new(digest)
+        .lazy(true)
+        .build_from_vec(counted(&values, &HASHED));
+
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| assert_eq!(tree.root_hash(), eager.root_hash()));
+        }
+    });
+
+    assert_eq!(HASHED.load(AtomicOrdering::SeqCst), values.len());
+}
+
+#[test]
+fn test_gen_nth_proof() {
+    let values = (0..37u32).map(|x| x.to_string()).collect::<Vec<_>>();
+
+    for policy in vec![OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        for count in 0..values.len() {
+            let tree = MerkleTreeBuilder::new(digest)
+                .odd_leaf_policy(policy)
+                .build_from_vec(values[..count].to_vec());
+
+            for (n, value) in values[..count].iter().enumerate() {
+                let proof = tree.gen_nth_proof(n).unwrap();
+
+                assert_eq!(proof.value, *value);
+                assert!(proof.validate(tree.root_hash()));
+                assert_eq!(Some(proof), tree.gen_proof(value.clone()));
+            }
+
+            assert_eq!(tree.gen_nth_proof(count), None);
+        }
+    }
+}
+
+#[test]
+fn test_proof_same_path() {
+    use std::cmp::Ordering;
+    use std::collections::HashMap;
+
+    let values = (0..11u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    let proof = tree.gen_nth_proof(3).unwrap();
+    let mut upper = proof.clone();
+    upper.value = proof.value.to_uppercase() + "!";
+    let length = Proof::new(digest, proof.root_hash.clone(), proof.lemma.clone(), proof.value.len());
+
+    assert_ne!(proof, upper);
+    assert_ne!(proof.cmp(&upper), Ordering::Equal);
+    assert!(proof.same_path(&upper));
+    assert!(proof.same_path(&length));
+    assert_eq!(proof.path_cmp(&length), Ordering::Equal);
+    assert_eq!(proof.path_key(), length.path_key());
+    assert_eq!(proof.path_key().len(), digest.output_len);
+
+    let mut by_path = HashMap::new();
+
+    for proof in [proof.clone(), upper, tree.gen_nth_proof(3).unwrap()] {
+        by_path.entry(proof.path_key()).or_insert_with(Vec::new).push(proof.value);
+    }
+
+    assert_eq!(by_path.len(), 1);
+
+    // Proofs of other leaves, or of the same leaf in another tree, take other paths
+    let other = tree.gen_nth_proof(4).unwrap();
+    assert!(!proof.same_path(&other));
+    assert_eq!(proof.path_cmp(&other), proof.lemma.cmp(&other.lemma));
+    assert_ne!(proof.path_key(), other.path_key());
+
+    let mut changed = values.clone();
+    changed[7] = "seven".to_string();
+    let moved = MerkleTree::from_vec(digest, changed).gen_nth_proof(3).unwrap();
+
+    assert_eq!(moved.value, proof.value);
+    assert!(!proof.same_path(&moved));
+    assert_eq!(proof.path_cmp(&moved), proof.root_hash.cmp(&moved.root_hash));
+    assert_ne!(proof.path_key(), moved.path_key());
+}
+
+#[test]
+fn test_proof_cache() {
+    let values = (0..20u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let fresh = MerkleTree::from_vec(digest, values.clone());
+
+    let mut tree = MerkleTree::from_vec(digest, values.clone());
+    tree.enable_proof_cache(2);
+
+    for _ in 0..3 {
+        assert_eq!(tree.gen_nth_proof(3), fresh.gen_nth_proof(3));
+        assert_eq!(tree.gen_proof("7".to_string()), fresh.gen_proof("7".to_string()));
+    }
+
+    assert_eq!(tree.gen_proof("3".to_string()), fresh.gen_proof("3".to_string()));
+    assert_eq!(tree.gen_proof("missing".to_string()), None);
+
+    let stats = tree.cache_stats();
+    assert_eq!(stats.hits, 5);
+    assert_eq!(stats.misses, 2);
+    assert_eq!(stats.evictions, 0);
+
+    // `7` is the least recently used, and makes room for `12`
+    assert_eq!(tree.gen_nth_proof(12), fresh.gen_nth_proof(12));
+    assert_eq!(tree.gen_nth_proof(3), fresh.gen_nth_proof(3));
+    assert_eq!(tree.gen_proof("7".to_string()), fresh.gen_proof("7".to_string()));
+
+    let stats = tree.cache_stats();
+    assert_eq!(stats.hits, 6);
+    assert_eq!(stats.misses, 4);
+    assert_eq!(stats.evictions, 2);
+}
+
+#[test]
+fn test_proof_cache_invalidation() {
+    let values = vec!["a", "b", "c"];
+    let mut tree = MerkleTree::from_vec(digest, values);
+    tree.enable_proof_cache(8);
+
+    let stale = tree.gen_proof("a").unwrap();
+    assert_eq!(tree.gen_nth_proof(0), Some(stale.clone()));
+
+    tree.push("d");
+    let fresh = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d"]);
+
+    assert_ne!(tree.gen_proof("a"), Some(stale.clone()));
+    assert_eq!(tree.gen_proof("a"), fresh.gen_proof("a"));
+    assert_eq!(tree.gen_nth_proof(0), fresh.gen_nth_proof(0));
+
+    tree.retain(|v| *v != "b");
+    let fresh = MerkleTree::from_vec(digest, vec!["a", "c", "d"]);
+
+    assert_eq!(tree.gen_proof("a"), fresh.gen_proof("a"));
+    assert_eq!(tree.gen_nth_proof(1), fresh.gen_nth_proof(1));
+    assert_eq!(tree.cache_stats().hits, 3);
+    assert_eq!(tree.cache_stats().misses, 4);
+
+    tree.enable_proof_cache(0);
+    assert_eq!(tree.gen_proof("a"), fresh.gen_proof("a"));
+    assert_eq!(tree.cache_stats().misses, 4);
+}
+
+#[test]
+fn test_checkpoints() {
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+
+    let v0 = tree.checkpoint();
+    let v0_root = tree.root_hash().clone();
+    let v0_again = tree.checkpoint();
+
+    tree.push("d");
+    tree.push("e");
+
+    let v1 = tree.checkpoint();
+    let v1_root = tree.root_hash().clone();
+
+    tree.retain(|v| *v != "a");
+    tree.push("f");
+
+    assert!(v0 < v0_again && v0_again < v1);
+    assert_eq!(tree.root_at(v0), Some(&v0_root));
+    assert_eq!(tree.root_at(v0_again), Some(&v0_root));
+    assert_eq!(tree.root_at(v1), Some(&v1_root));
+
+    for (n, value) in vec!["a", "b", "c"].into_iter().enumerate() {
+        let proof = tree.gen_proof_at(v0, n).unwrap();
+
+        assert_eq!(proof.value, value);
+        assert!(proof.validate(&v0_root));
+        assert!(!proof.validate(tree.root_hash()));
+        assert_eq!(tree.gen_proof_at(v0_again, n), Some(proof));
+    }
+
+    for (n, value) in vec!["a", "b", "c", "d", "e"].into_iter().enumerate() {
+        let proof = tree.gen_proof_at(v1, n).unwrap();
+
+        assert_eq!(proof.value, value);
+        assert!(proof.validate(&v1_root));
+        assert!(!proof.validate(tree.root_hash()));
+    }
+
+    assert_eq!(tree.gen_proof_at(v0, 3), None);
+    assert_eq!(tree, MerkleTree::from_vec(digest, vec!["b", "c", "d", "e", "f"]));
+
+    tree.drop_checkpoints_before(v1);
+
+    assert_eq!(tree.root_at(v0), None);
+    assert_eq!(tree.gen_proof_at(v0_again, 0), None);
+    assert!(tree.gen_proof_at(v1, 0).unwrap().validate(&v1_root));
+}
+
+#[test]
+fn test_checkpoint_unmodified() {
+    let mut tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    let version = tree.checkpoint();
+
+    assert_eq!(tree.root_at(version), Some(tree.root_hash()));
+    assert_eq!(tree.gen_proof_at(version, 1), tree.gen_nth_proof(1));
+
+    // Pushing a duplicate into a deduplicated tree does not modify it
+    let mut deduped = MerkleTreeBuilder::new(digest)
+        .dedup(DedupOrder::FirstOccurrence)
+        .build_from_vec(vec!["a", "b"]);
+
+    let version = deduped.checkpoint();
+    assert!(!deduped.push("a"));
+    assert_eq!(deduped.gen_proof_at(version, 1), deduped.gen_nth_proof(1));
+}
+
+#[test]
+fn test_prove_prefix() {
+    let values = (0..40u32).map(|x| x.to_string()).collect::<Vec<_>>();
+
+    for new_len in 0..values.len() {
+        let tree = MerkleTree::from_vec(digest, values[..new_len].to_vec());
+
+        for old_len in 0..new_len + 1 {
+            let old = MerkleTree::from_vec(digest, values[..old_len].to_vec());
+            let proof = tree.prove_prefix(old_len).unwrap();
+
+            assert!(proof.verify(old.root_hash(), old_len, tree.root_hash(), new_len));
+            assert!(proof.hashes.len() <= 2 * tree.height());
+
+            if old_len > 0 && old_len < new_len {
+                let mut other = values[..new_len].to_vec();
+                other[old_len - 1] = "other".to_string();
+                let other = MerkleTree::from_vec(digest, other);
+
+                assert!(!proof.verify(tree.root_hash(), old_len, tree.root_hash(), new_len));
+                assert!(!proof.verify(old.root_hash(), old_len, other.root_hash(), new_len));
+                assert!(!proof.verify(old.root_hash(), new_len, tree.root_hash(), old_len));
+            }
+        }
+
+        assert_eq!(tree.prove_prefix(new_len + 1), None);
+    }
+}
+
+#[test]
+fn test_prove_prefix_options() {
+    let values = (0..13u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let builder = MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::Unprefixed);
+
+    let old = builder.clone().build_from_vec(values[..6].to_vec());
+    let tree = builder.build_from_vec(values.clone());
+    let mut proof = tree.prove_prefix(6).unwrap();
+
+    assert!(proof.verify(old.root_hash(), 6, tree.root_hash(), 13));
+
+    let prefixed = MerkleTree::from_vec(digest, values[..6].to_vec());
+    assert!(!proof.verify(prefixed.root_hash(), 6, tree.root_hash(), 13));
+
+    proof.hashes[1][0] ^= 1;
+    assert!(!proof.verify(old.root_hash(), 6, tree.root_hash(), 13));
+
+    let duplicated = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(values);
+
+    assert_eq!(duplicated.prove_prefix(6), None);
+}
+
+#[test]
+fn test_audit_path_at_size() {
+    let values = (0..40u32).map(|x| x.to_string()).collect::<Vec<_>>();
+
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::Unprefixed),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+    ];
+
+    for builder in builders {
+        for count in 1..values.len() {
+            let tree = builder.clone().build_from_vec(values[..count].to_vec());
+
+            for index in 0..count {
+                let proof = tree.gen_nth_proof(index).unwrap();
+                let path = proof.siblings().cloned().collect::<Vec<_>>();
+
+                assert_eq!(tree.audit_path(index), Some(path.clone()));
+                assert_eq!(tree.audit_path_at_size(index, count), Some(path));
+            }
+
+            for size in 1..count {
+                let prefix = builder.clone().build_from_vec(values[..size].to_vec());
+
+                for index in 0..size {
+                    assert_eq!(tree.audit_path_at_size(index, size), prefix.audit_path(index));
+                }
+
+                assert_eq!(tree.audit_path_at_size(size, size), None);
+            }
+
+            assert_eq!(tree.audit_path(count), None);
+            assert_eq!(tree.audit_path_at_size(0, count + 1), None);
+        }
+    }
+
+    // The nodes of a smaller tree are not those of a padded one
+    let duplicated = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(values.clone());
+
+    assert!(duplicated.audit_path(3).is_some());
+    assert_eq!(duplicated.audit_path_at_size(3, 7), None);
+
+    // The hashes of pruned subtrees are kept, but not the nodes below them
+    let fresh = MerkleTree::from_vec(digest, values[..11].to_vec());
+    let mut tree = fresh.clone();
+    tree.prune_below(3);
+
+    assert_eq!(tree.audit_path(10), fresh.audit_path(10));
+    assert_eq!(tree.audit_path_at_size(10, 11), fresh.audit_path(10));
+    assert_eq!(tree.audit_path(3), None);
+    assert_eq!(tree.audit_path_at_size(3, 6), None);
+}
+
+/// Queries the node hashes of `remote`, counting the queries.
+fn divergence_with(tree: &MerkleTree<String>, remote: &MerkleTree<String>) -> (Option<usize>, usize) {
+    let mut queries = 0;
+
+    let result = tree.find_first_divergence(|depth, index| -> Result<_, ()> {
+        queries += 1;
+        Ok(remote.node_hash(depth, index).map(<[u8]>::to_vec))
+    });
+
+    (result.unwrap(), queries)
+}
+
+#[test]
+fn test_find_first_divergence() {
+    let values = (0..1000u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    assert_eq!(divergence_with(&tree, &tree), (None, 2));
+
+    for &flipped in &[0, 1, 2, 499, 500, 511, 512, 513, 998, 999] {
+        let mut remote = values.clone();
+        remote[flipped] = "flipped".to_string();
+        let remote = MerkleTree::from_vec(digest, remote);
+
+        let (divergence, queries) = divergence_with(&tree, &remote);
+
+        assert_eq!(divergence, Some(flipped));
+        assert!(queries <= tree.height() + 1);
+    }
+}
+
+#[test]
+fn test_node_hash() {
+    let values = vec!["a", "b", "c", "d", "e"];
+
+    for policy in vec![OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        let tree = MerkleTreeBuilder::new(digest)
+            .odd_leaf_policy(policy)
+            .build_from_vec(values.clone());
+
+        for (i, value) in values.iter().enumerate() {
+            assert_eq!(tree.node_hash(0, i), Some(digest.hash_leaf(value).as_ref()));
+        }
+
+        assert_eq!(tree.node_hash(0, 5), None);
+        assert_eq!(tree.node_hash(tree.height(), 0), Some(tree.root_hash().as_slice()));
+        assert_eq!(tree.node_hash(tree.height() + 3, 0), Some(tree.root_hash().as_slice()));
+        assert_eq!(tree.node_hash(tree.height(), 1), None);
+
+        let ab = digest.hash_nodes(&digest.hash_leaf(&"a"), &digest.hash_leaf(&"b"));
+        assert_eq!(tree.node_hash(1, 0), Some(ab.as_ref()));
+
+        let mut flipped = values.clone();
+        flipped[4] = "f";
+        let remote = MerkleTreeBuilder::new(digest)
+            .odd_leaf_policy(policy)
+            .build_from_vec(flipped);
+
+        let divergence = tree.find_first_divergence(|level, index| -> Result<_, ()> {
+            Ok(remote.node_hash(level, index).map(<[u8]>::to_vec))
+        });
+
+        assert_eq!(divergence, Ok(Some(4)));
+    }
+
+    let promoted = MerkleTree::from_vec(digest, values.clone());
+    assert_eq!(promoted.node_hash(1, 2), promoted.node_hash(0, 4));
+}
+
+#[test]
+fn test_find_first_divergence_lengths() {
+    let values = (0..128u32).map(|x| x.to_string()).collect::<Vec<_>>();
+
+    for &len in &[0, 1, 2, 3, 31, 32, 33, 64, 99] {
+        let prefix = MerkleTree::from_vec(digest, values[..len].to_vec());
+
+        for &other in &[len + 1, len + 2, 100] {
+            let longer = MerkleTree::from_vec(digest, values[..other].to_vec());
+
+            assert_eq!(divergence_with(&prefix, &longer).0, Some(len));
+            assert_eq!(divergence_with(&longer, &prefix).0, Some(len));
+        }
+    }
+}
+
+#[test]
+fn test_find_first_divergence_error() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+
+    let result = tree.find_first_divergence(|level, _| if level > 1 {
+        Ok(Some(vec![0]))
+    } else {
+        Err("unreachable")
+    });
+
+    assert_eq!(result, Err("unreachable"));
+}
+
+#[test]
+fn test_multi_proof() {
+    let values = (0..37u32).map(|x| x.to_string()).collect::<Vec<_>>();
+
+    for policy in vec![OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        for &len in &[1, 2, 3, 5, 8, 13, 37] {
+            let tree = MerkleTreeBuilder::new(digest)
+                .odd_leaf_policy(policy)
+                .build_from_vec(values[..len].to_vec());
+
+            let indices = vec![len - 1, 0, len / 2, len - 1];
+            let proof = tree.gen_multi_proof(&indices).unwrap();
+
+            let mut expected = indices.clone();
+            expected.sort();
+            expected.dedup();
+
+            assert_eq!(proof.indices(), expected);
+            assert!(proof.validate(tree.root_hash()));
+
+            let report = proof.verify_detailed(tree.root_hash());
+            assert_eq!(report.verified, vec![true; expected.len()]);
+            assert_eq!(&report.computed_root, tree.root_hash());
+
+            let all = (0..len).collect::<Vec<_>>();
+            assert_eq!(tree.gen_multi_proof(&all).unwrap().indices(), all);
+
+            assert!(!proof.validate(&digest.hash_leaf(&"other").as_ref().to_vec()));
+            assert_eq!(tree.gen_multi_proof(&[len]), None);
+        }
+    }
+}
+
+/// Returns the hashes of the proven leaves of `node`, from left to right.
+fn multi_proof_leaves(node: &mut MultiProofNode) -> Vec<&mut Vec<u8>> {
+    match *node {
+        MultiProofNode::Leaf { ref mut hash } => vec![hash],
+
+        MultiProofNode::Node { ref mut left, ref mut right, .. } => {
+            let mut leaves = multi_proof_leaves(left);
+            leaves.extend(multi_proof_leaves(right));
+            leaves
+        }
+
+        _ => vec![],
+    }
+}
+
+#[test]
+fn test_multi_proof_verify_detailed() {
+    let values = (0..20u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+    let indices = [1, 4, 9, 16, 19];
+
+    for tampered in 0..indices.len() {
+        let mut proof = tree.gen_multi_proof(&indices).unwrap();
+
+        multi_proof_leaves(&mut proof.root)[tampered][0] ^= 1;
+
+        let report = proof.verify_detailed(tree.root_hash());
+        let expected = (0..indices.len()).map(|i| i != tampered).collect::<Vec<_>>();
+
+        assert_eq!(report.verified, expected);
+        assert_eq!(report.mismatched_nodes.len(), 1);
+        assert_ne!(&report.computed_root, tree.root_hash());
+        assert!(!report.root_matches);
+        assert!(!report.is_valid());
+        assert!(!proof.validate(tree.root_hash()));
+    }
+
+    let mut proof = tree.gen_multi_proof(&[2, 3]).unwrap();
+    *multi_proof_leaves(&mut proof.root)[0] = digest.hash_leaf(&"other").as_ref().to_vec();
+
+    assert_eq!(proof.verify_detailed(tree.root_hash()).verified, vec![false, false]);
+}
+
+#[test]
+fn test_partial_tree() {
+    let values = (0..21u32).map(|x| x.to_string()).collect::<Vec<_>>();
+
+    for policy in vec![OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        let tree = MerkleTreeBuilder::new(digest)
+            .odd_leaf_policy(policy)
+            .build_from_vec(values.clone());
+
+        let indices = [2, 9, 20];
+        let proof = tree.gen_multi_proof(&indices).unwrap();
+        let mut partial = PartialTree::from_multiproof(proof).unwrap();
+
+        assert_eq!(partial.root_hash(), tree.root_hash());
+        assert_eq!(partial.count(), 21);
+
+        for &i in &indices {
+            assert!(partial.is_known(i));
+            assert!(partial.gen_proof(i).is_none());
+
+            partial.insert_leaf(i, values[i].clone()).unwrap();
+
+            let proof = partial.gen_proof(i).unwrap();
+            assert_eq!(proof, tree.gen_nth_proof(i).unwrap());
+            assert!(proof.validate(tree.root_hash()));
+        }
+
+        // Leaf 3 is the sibling of leaf 2, and leaf 0 is in an opaque subtree
+        assert!(!partial.is_known(3));
+        assert_eq!(
+            partial.insert_leaf(3, "other".to_string()),
+            Err(Error::HashMismatch { depth: 5, index: 3 })
+        );
+
+        partial.insert_leaf(3, values[3].clone()).unwrap();
+        assert!(partial.is_known(3));
+        assert_eq!(partial.gen_proof(3), tree.gen_nth_proof(3));
+
+        assert_eq!(partial.insert_leaf(0, values[0].clone()), Err(Error::LeafNotInWitness(0)));
+        assert_eq!(partial.insert_leaf(21, values[0].clone()), Err(Error::LeafNotInWitness(21)));
+        assert!(!partial.is_known(0));
+        assert!(partial.gen_proof(0).is_none());
+        assert!(partial.gen_proof(21).is_none());
+    }
+}
+
+#[test]
+fn test_partial_tree_duplicated_leaf() {
+    let values = (0..5u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(values.clone());
+
+    // The last leaf is paired with copies of itself, up to the root
+    let proof = tree.gen_multi_proof(&[0]).unwrap();
+    let mut partial = PartialTree::from_multiproof(proof).unwrap();
+
+    partial.insert_leaf(4, values[4].clone()).unwrap();
+    assert_eq!(partial.gen_proof(4), tree.gen_nth_proof(4));
+}
+
+#[test]
+fn test_partial_tree_malformed() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c", "d"]);
+    let mut proof = tree.gen_multi_proof(&[1]).unwrap();
+
+    if let MultiProofNode::Node { ref mut hash, .. } = proof.root {
+        hash[0] ^= 1;
+    }
+
+    assert_eq!(
+        PartialTree::<&str>::from_multiproof(proof).unwrap_err(),
+        Error::HashMismatch { depth: 0, index: 0 }
+    );
+
+    let mut proof = tree.gen_multi_proof(&[1]).unwrap();
+    proof.count = 2;
+
+    assert_eq!(
+        PartialTree::<&str>::from_multiproof(proof).unwrap_err(),
+        Error::MalformedTree("a node is at the position of a leaf")
+    );
+
+    let empty = MerkleTree::<&str>::from_vec(digest, vec![]).gen_multi_proof(&[]).unwrap();
+    let partial = PartialTree::<&str>::from_multiproof(empty).unwrap();
+
+    assert_eq!(partial.root_hash(), &digest.hash_empty().as_ref().to_vec());
+    assert!(!partial.is_known(0));
+}
+
+#[test]
+fn test_canonical_proof_data() {
+    let values = (0..11u32).map(|x| x.to_string()).collect::<Vec<_>>();
+
+    for scheme in vec![HashingScheme::DomainSeparated, HashingScheme::Unprefixed] {
+        let tree = MerkleTreeBuilder::new(digest)
+            .hashing_scheme(scheme)
+            .build_from_vec(values.clone());
+
+        for value in &values {
+            let proof = tree.gen_proof(value.clone()).unwrap();
+            let data = proof.clone().into_canonical_data();
+
+            assert_eq!(data.leaf_hash, scheme.hash_leaf(digest, value).as_ref().to_vec());
+
+            let res = data.into_proof_with_scheme(digest, scheme);
+            assert_eq!(res, proof);
+            assert!(res.validate(tree.root_hash()));
+        }
+    }
+
+    let tree = MerkleTree::from_vec(digest, vec!["a"]);
+    let data = tree.gen_proof("a").unwrap().into_canonical_data();
+
+    assert!(data.siblings.is_empty());
+    assert!(data.into_proof(digest).validate(tree.root_hash()));
+}
+
+#[test]
+fn test_validate_rejects_inconsistent_node_hash() {
+    let values = (0..8u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+    let proof = tree.gen_proof("5".to_string()).unwrap();
+
+    for depth in 1..4 {
+        let mut tampered = proof.clone();
+
+        {
+            let mut lemma = &mut tampered.lemma;
+
+            for _ in 0..depth {
+                lemma = lemma.sub_lemma.as_mut().unwrap();
+            }
+
+            lemma.node_hash[0] ^= 1;
+        }
+
+        assert!(!tampered.validate(tree.root_hash()));
+
+        // Only the leaf hash survives the round trip through the canonical form
+        let canonical = tampered.into_canonical_data().into_proof(digest);
+        assert_eq!(canonical == proof, depth < 3);
+    }
+}
+
+
+#[test]
+fn test_compute_root() {
+    let builders = vec![
+        MerkleTreeBuilder::new(digest),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).fixed_depth(4),
+        MerkleTreeBuilder::new(digest).leaf_counts(true),
+        MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::SortedPairs),
+    ];
+
+    for builder in builders {
+        for &count in &[1, 2, 7] {
+            let values = (0..count).map(|i: u32| i.to_string()).collect::<Vec<_>>();
+            let tree = builder.clone().build_from_vec(values);
+
+            for index in 0..count as usize {
+                let proof = tree.gen_nth_proof(index).unwrap();
+                assert_eq!(proof.compute_root().as_ref(), Ok(tree.root_hash()));
+
+                if proof.depth() == 0 {
+                    continue;
+                }
+
+                // A flipped sibling leads to another root, which is not an error
+                let mut tampered = proof.clone();
+
+                match tampered.lemma.sibling_hash {
+                    Some(Positioned::Left(ref mut hash)) | Some(Positioned::Right(ref mut hash)) => hash[0] ^= 1,
+                    None => unreachable!(),
+                }
+
+                let root_hash = tampered.compute_root().unwrap();
+                assert_ne!(root_hash, *tree.root_hash());
+                assert!(!tampered.validate(tree.root_hash()));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_compute_root_rejects_malformed_proofs() {
+    let values = (0..5u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let proof = tree.gen_nth_proof(3).unwrap();
+
+    let mut leaf_with_sibling = proof.clone();
+    {
+        let mut lemma = &mut leaf_with_sibling.lemma;
+
+        while lemma.sub_lemma.is_some() {
+            lemma = lemma.sub_lemma.as_mut().unwrap();
+        }
+
+        lemma.sibling_hash = Some(Positioned::Left(tree.root_hash().clone()));
+    }
+    assert!(match leaf_with_sibling.compute_root() {
+        Err(Error::InvalidPath(_)) => true,
+        _ => false,
+    });
+
+    let mut truncated = proof.clone();
+    truncated.lemma.sub_lemma.as_mut().unwrap().node_hash.pop();
+    assert!(match truncated.compute_root() {
+        Err(Error::HashLengthMismatch { .. }) => true,
+        _ => false,
+    });
+
+    let counted = MerkleTreeBuilder::new(digest).leaf_counts(true).build_from_vec(values);
+    let mut miscounted = counted.gen_nth_proof(3).unwrap();
+    miscounted.leaf_counts.as_mut().unwrap().pop();
+    assert!(match miscounted.compute_root() {
+        Err(Error::InvalidPath(_)) => true,
+        _ => false,
+    });
+}
+#[test]
+fn test_lemma_iter() {
+    for &len in &[1, 2, 5, 16] {
+        let values = (0..len).map(|x: u32| x.to_string()).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(digest, values.clone());
+
+        for value in values {
+            let proof = tree.gen_proof(value.clone()).unwrap();
+
+            let mut lemmas = vec![&proof.lemma];
+            let mut siblings = vec![];
+
+            while let Some(ref sub_lemma) = lemmas[lemmas.len() - 1].sub_lemma {
+                siblings.push(lemmas[lemmas.len() - 1].sibling_hash.as_ref().unwrap());
+                lemmas.push(sub_lemma);
+            }
+
+            let iter = proof.lemmas();
+            assert_eq!(iter.len(), lemmas.len());
+            assert_eq!(iter.collect::<Vec<_>>(), lemmas);
+
+            assert_eq!(proof.depth(), lemmas.len() - 1);
+            assert_eq!(proof.siblings().collect::<Vec<_>>(), siblings);
+            assert_eq!(proof.leaf_hash(), digest.hash_leaf(&value).as_ref());
+
+            let mut iter = proof.lemmas();
+            iter.next();
+            assert_eq!(iter.len(), lemmas.len() - 1);
+        }
+    }
+
+    let tree = MerkleTree::from_vec(digest, vec!["a"]);
+    let proof = tree.gen_proof("a").unwrap();
+
+    assert_eq!(proof.depth(), 0);
+    assert_eq!(proof.lemmas().count(), 1);
+    assert_eq!(proof.siblings().count(), 0);
+    assert_eq!(proof.leaf_hash(), tree.root_hash().as_slice());
+}
+
+#[test]
+fn test_positioned() {
+    let left = Positioned::Left(vec![1u8]);
+    let right = Positioned::Right(vec![2u8]);
+
+    assert!(left.is_left() && !left.is_right());
+    assert!(right.is_right() && !right.is_left());
+    assert_eq!(left.direction(), Direction::Left);
+    assert_eq!(right.direction(), Direction::Right);
+
+    assert_eq!(left.as_ref(), Positioned::Left(&vec![1u8]));
+    assert_eq!(left.clone().map(|hash| hash.len()), Positioned::Left(1));
+    assert_eq!(left.clone().flip(), Positioned::Right(vec![1u8]));
+    assert_eq!(right.clone().flip().flip(), right);
+    assert_eq!(right.clone().into_inner(), vec![2u8]);
+
+    assert_eq!(Positioned::new(Direction::Right, 3), Positioned::Right(3));
+    assert_eq!(Direction::Left.flip(), Direction::Right);
+
+    for &direction in &[Direction::Left, Direction::Right] {
+        assert_eq!(Direction::from_byte(direction.to_byte()), Some(direction));
+    }
+
+    assert_eq!(Direction::Left.to_byte(), 0);
+    assert_eq!(Direction::from_byte(2), None);
+}
+
+#[test]
+fn test_proof_display() {
+    let tree = MerkleTree::from_vec(&SHA256, vec!["a", "b", "c"]);
+    let proof = tree.gen_proof("b").unwrap();
+
+    assert_eq!(
+        proof.to_string(),
+        "proof of \"b\" (SHA-256)\n\
+         root: 3664…c1\n\
+         depth: 2\n\
+         level 2: node=3664…c1\n\
+         level 1: sibling=R 597f…d8, node=b137…eb\n\
+         level 0: sibling=L 022a…3c, node=57eb…31"
+    );
+
+    assert_eq!(
+        proof.display(DisplayOptions { hash_digits: Some(12) }).to_string(),
+        "proof of \"b\" (SHA-256)\n\
+         root: 36642e73…1ec1\n\
+         depth: 2\n\
+         level 2: node=36642e73…1ec1\n\
+         level 1: sibling=R 597fcb31…76d8, node=b137985f…99eb\n\
+         level 0: sibling=L 022a6979…f93c, node=57eb3561…6a31"
+    );
+
+    let full = proof.display_with(DisplayOptions { hash_digits: None }, |value, f| {
+        write!(f, "{} byte", value.len())
+    });
+
+    assert_eq!(
+        full.to_string().lines().collect::<Vec<_>>(),
+        vec![
+            "proof of 1 byte (SHA-256)",
+            "root: 36642e73c2540ab121e3a6bf9545b0a24982cd830eb13d3cd19de3ce6c021ec1",
+            "depth: 2",
+            "level 2: node=36642e73c2540ab121e3a6bf9545b0a24982cd830eb13d3cd19de3ce6c021ec1",
+            "level 1: sibling=R 597fcb31282d34654c200d3418fca5705c648ebf326ec73d8ddef11841f876d8, \
+             node=b137985ff484fb600db93107c77b0365c80d78f5b429ded0fd97361d077999eb",
+            "level 0: sibling=L 022a6979e6dab7aa5ae4c3e5e45f7e977112a7e63593820dbec1ec738a24f93c, \
+             node=57eb35615d47f34ec714cacdf5fd74608a5e8e102724e80b24b287c0c27b6a31",
+        ]
+    );
+
+    let tree = MerkleTree::from_vec(digest, vec![vec![1u8]]);
+    let proof = tree.gen_proof(vec![1u8]).unwrap();
+    let display = proof.display_with(DisplayOptions::default(), |_, f| f.write_str("one"));
+
+    assert_eq!(
+        display.to_string(),
+        "proof of one (SHA-512)\n\
+         root: 8053…b8\n\
+         depth: 0\n\
+         level 0: node=8053…b8"
+    );
+}
+
+#[test]
+fn test_tree_debug() {
+    let values = (0..10_000u32).map(|i| vec![i as u8; 64]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(&SHA256, values);
+
+    let debug = format!("{:?}", tree);
+    let root = &to_hex(tree.root_hash())[..6];
+
+    assert!(debug.len() < 1024, "{} bytes", debug.len());
+    assert!(debug.starts_with("MerkleTree { algorithm: SHA-256, count: 10000, height: 14, root_hash: "));
+    assert!(debug.contains(root));
+    assert!(debug.contains("…"));
+
+    let full = format!("{:?}", tree.debug_full());
+
+    assert!(full.len() > 10_000 * 64);
+    assert!(full.contains(&to_hex(tree.root_hash())));
+
+    let tree = MerkleTree::from_vec(&SHA256, vec!["a".to_string(), "b".repeat(100)]);
+    let debug = format!("{:?}", tree);
+
+    assert!(debug.contains("\"a\""));
+    assert!(!debug.contains(&"b".repeat(100)));
+    assert!(format!("{:?}", tree.debug_full()).contains(&"b".repeat(100)));
+
+    let lazy = MerkleTreeBuilder::new(digest).lazy(true).build_from_vec(vec!["a", "b"]);
+
+    assert!(format!("{:?}", lazy).contains("<not hashed>"));
+    assert!(!lazy.is_hashed());
+}
+
+#[test]
+fn test_typed_root_hash() {
+    let tree = MerkleTree::from_vec(&SHA256, vec!["a", "b", "c"]);
+    let other = MerkleTree::from_vec(&SHA512, vec!["a", "b", "c"]);
+    let proof = tree.gen_proof("b").unwrap();
+
+    let root = tree.root();
+    assert_eq!(root.algorithm(), AlgorithmTag::of(&SHA256));
+    assert_eq!(root.as_bytes(), tree.root_hash().as_slice());
+    assert_eq!(root.to_string(), to_hex(tree.root_hash()));
+
+    assert_eq!(proof.verify(&root), Ok(()));
+    assert_eq!(proof.verify(&other.root()), Err(Error::AlgorithmMismatch));
+    assert_eq!(proof.verify(&MerkleTree::from_vec(&SHA256, vec!["a"]).root()), Err(Error::InvalidProof));
+
+    // The same bytes tagged with another algorithm of the same output length are another hash
+    let relabeled = RootHash::new(&SHA512_256, root.as_bytes().to_vec()).unwrap();
+    assert!(relabeled != root);
+    assert_eq!(proof.verify(&relabeled), Err(Error::AlgorithmMismatch));
+
+    assert_eq!(
+        RootHash::new(&SHA512, root.clone().into_bytes()),
+        Err(Error::HashLengthMismatch { expected: 64, found: 32 })
+    );
+
+    let multi = tree.gen_multi_proof(&[0, 2]).unwrap();
+    assert_eq!(multi.verify(&root), Ok(()));
+    assert_eq!(multi.verify(&other.root()), Err(Error::AlgorithmMismatch));
+
+    let node = tree.node(1, 0).unwrap();
+    assert_eq!(node.as_bytes(), tree.node_hash(1, 0).unwrap());
+    assert_eq!(RootHash::from(tree.node(2, 0).unwrap()), root);
+    assert_eq!(tree.node(0, 3), None);
+}
+
+#[test]
+fn test_algorithm_names() {
+    use digest::{SHA1, SHA384};
+    use registry::{algorithm_name, algorithm_from_name, register_algorithm};
+
+    for &algorithm in &[&SHA1, &SHA256, &SHA384, &SHA512, &SHA512_256] {
+        let name = algorithm_name(algorithm);
+
+        assert_eq!(AlgorithmTag::of(algorithm).to_string(), name);
+        assert_eq!(AlgorithmTag::of(algorithm_from_name(name).unwrap()), AlgorithmTag::of(algorithm));
+    }
+
+    assert_eq!(algorithm_name(&SHA512_256), "SHA-512/256");
+    assert!(algorithm_from_name("sha-256").is_none());
+
+    assert!(register_algorithm("sha512 (test)", &SHA512));
+    assert!(!register_algorithm("sha512 (test)", &SHA256));
+
+    let registered = algorithm_from_name("sha512 (test)").unwrap();
+    assert_eq!(AlgorithmTag::of(registered), AlgorithmTag::of(&SHA512));
+}
+
+#[test]
+fn test_check_algorithm() {
+    let values = (0..8u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(&SHA256, values.clone());
+    let proof = tree.gen_proof("5".to_string()).unwrap();
+
+    assert_eq!(proof.check_algorithm(), Ok(()));
+
+    // A SHA-512 proof read back as a SHA-256 one
+    let sha512 = MerkleTree::from_vec(&SHA512, values).gen_proof("5".to_string()).unwrap();
+    let mixed = sha512.into_data().into_proof_unchecked(&SHA256);
+
+    assert_eq!(mixed.check_algorithm(), Err(Error::HashLengthMismatch { expected: 32, found: 64 }));
+    assert!(!mixed.validate(&mixed.root_hash.clone()));
+    assert_eq!(mixed.verify(&tree.root()), Err(Error::HashLengthMismatch { expected: 32, found: 64 }));
+
+    for depth in 0..4 {
+        for &sibling in &[false, true] {
+            // The lemma of the leaf has no sibling hash
+            if depth == 3 && sibling {
+                continue;
+            }
+
+            let mut tampered = proof.clone();
+
+            {
+                let mut lemma = &mut tampered.lemma;
+
+                for _ in 0..depth {
+                    lemma = lemma.sub_lemma.as_mut().unwrap();
+                }
+
+                let hash = if sibling {
+                    match *lemma.sibling_hash.as_mut().unwrap() {
+                        Positioned::Left(ref mut hash) | Positioned::Right(ref mut hash) => hash,
+                    }
+                } else {
+                    &mut lemma.node_hash
+                };
+
+                hash.push(0);
+            }
+
+            let expected = Err(Error::HashLengthMismatch { expected: 32, found: 33 });
+
+            assert_eq!(tampered.check_algorithm(), expected);
+            assert!(!tampered.validate(&tampered.root_hash.clone()));
+            assert!(!tampered.validate(tree.root_hash()));
+        }
+    }
+}
+
+#[test]
+fn test_proof_data_conversion() {
+    let values = (0..8u32).map(|x| x.to_string()).collect::<Vec<_>>();
+    let proof = MerkleTree::from_vec(&SHA256, values.clone()).gen_proof("5".to_string()).unwrap();
+
+    assert_eq!(proof.clone().into_data().into_proof(&SHA256), Ok(proof.clone()));
+    assert_eq!(proof.clone().into_data().into_named_proof(), Ok(proof.clone()));
+
+    // SHA-512 data paired with SHA-256, with and without the name of its algorithm
+    let sha512 = MerkleTree::from_vec(&SHA512, values).gen_proof("5".to_string()).unwrap();
+    let named = sha512.into_data();
+    let mut unnamed = named.clone();
+    unnamed.algorithm = None;
+
+    assert_eq!(
+        named.clone().into_proof(&SHA256),
+        Err(ConversionError::AlgorithmMismatch {
+            expected: AlgorithmTag::of(&SHA256),
+            named: AlgorithmTag::of(&SHA512),
+        })
+    );
+    assert_eq!(
+        unnamed.clone().into_proof(&SHA256),
+        Err(ConversionError::WrongHashLength { level: 0, expected: 32, got: 64 })
+    );
+    assert_eq!(
+        Error::from(unnamed.clone().into_proof(&SHA256).unwrap_err()),
+        Error::HashLengthMismatch { expected: 32, found: 64 }
+    );
+
+    // The unchecked conversion pairs them anyway, and the proof fails to validate
+    let mixed = unnamed.clone().into_proof_unchecked(&SHA256);
+    assert_eq!(AlgorithmTag::of(mixed.algorithm), AlgorithmTag::of(&SHA256));
+    assert_eq!(mixed.lemma, named.lemma);
+    assert!(!mixed.validate(&mixed.root_hash.clone()));
+
+    // A sibling hash of the wrong length, below the root
+    let mut data = proof.clone().into_data();
+    data.algorithm = None;
+
+    if let Some(Positioned::Left(ref mut hash)) | Some(Positioned::Right(ref mut hash)) =
+        data.lemma.sub_lemma.as_mut().unwrap().sibling_hash
+    {
+        hash.push(0);
+    }
+
+    assert_eq!(data.into_proof(&SHA256), Err(ConversionError::WrongHashLength { level: 1, expected: 32, got: 33 }));
+
+    // A sibling hash without a sub lemma, and a sub lemma without a sibling hash
+    let mut data = proof.clone().into_data();
+    data.lemma.sub_lemma.as_mut().unwrap().sub_lemma = None;
+    assert_eq!(data.into_proof(&SHA256), Err(ConversionError::MalformedLemma { level: 1 }));
+
+    let mut data = proof.clone().into_data();
+    data.lemma.sibling_hash = None;
+    assert_eq!(data.clone().into_proof(&SHA256), Err(ConversionError::MalformedLemma { level: 0 }));
+    assert!(data.into_named_proof().is_err());
+}
+
+#[test]
+fn test_proof_tree_size() {
+    for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        for count in 1..34usize {
+            let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+            let tree = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy).build_from_vec(values);
+
+            for index in 0..count {
+                let proof = tree.gen_nth_proof(index).unwrap();
+
+                assert_eq!(proof.tree_size, Some(count as u64));
+                assert_eq!(proof.leaf_index, Some(index as u64));
+                assert_eq!(proof.lemma.leaf_index(count, policy), Some(index));
+
+                assert!(proof.validate_against(tree.root_hash(), count as u64));
+                assert!(!proof.validate_against(tree.root_hash(), count as u64 + 1));
+
+                let mut other_index = proof.clone();
+                other_index.leaf_index = Some((index as u64 + 1) % count as u64);
+                assert_eq!(other_index.validate_against(tree.root_hash(), count as u64), count == 1);
+
+                // Proofs without the fields are only checked against the shape of the tree
+                let mut legacy = proof.clone();
+                legacy.tree_size = None;
+                legacy.leaf_index = None;
+
+                assert!(legacy.validate(tree.root_hash()));
+                assert!(legacy.validate_against(tree.root_hash(), count as u64));
+                assert!(!legacy.validate_against(tree.root_hash(), 0));
+
+                // The fields are hints, which proofs are not compared by
+                assert_eq!(legacy, proof);
+            }
+        }
+    }
+
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    let proof = tree.gen_proof("c").unwrap();
+
+    assert_eq!(proof.tree_size, Some(3));
+    assert_eq!(proof.leaf_index, Some(2));
+
+    // The leaf promoted to the level below the root has no sibling at the leaf level
+    let mut flipped = proof.clone();
+    flipped.lemma.sibling_hash = flipped.lemma.sibling_hash.map(Positioned::flip);
+    assert_eq!(flipped.lemma.leaf_index(3, OddLeafPolicy::Promote), None);
+
+    let data = proof.clone().into_data();
+    assert_eq!(data.tree_size, Some(3));
+    assert_eq!(data.leaf_index, Some(2));
+    assert!(data.into_proof(digest).unwrap().validate_against(tree.root_hash(), 3));
+
+    // Unlike the scheme and the leaf counts, which the root hash is computed from
+    let mut other_scheme = proof.clone();
+    other_scheme.scheme = HashingScheme::Unprefixed;
+    assert_ne!(other_scheme, proof);
+
+    let mut counted = proof.clone();
+    counted.leaf_counts = Some(vec![3, 2]);
+    assert_ne!(counted, proof);
+    assert!(counted > proof);
+}
+
+#[test]
+fn test_policy_tree_size() {
+    // Reading the sides of the siblings as the bits of the index only works for perfect trees
+    let naive_index = |proof: &Proof<String>| {
+        proof.lemma.siblings().fold(0, |index, sibling| match sibling.direction() {
+            Direction::Left => index << 1 | 1,
+            Direction::Right => index << 1,
+        })
+    };
+
+    for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        for count in 1..34usize {
+            let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+            let tree = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy).build_from_vec(values);
+            let required = ValidationPolicy::new().tree_size(count as u64);
+
+            for index in 0..count {
+                let proof = tree.gen_nth_proof(index).unwrap();
+                assert_eq!(proof.leaf_index, Some(index as u64));
+                assert_eq!(proof.validate_with_policy(tree.root_hash(), &required), Ok(()));
+
+                if policy == OddLeafPolicy::Duplicate || count.is_power_of_two() {
+                    assert_eq!(naive_index(&proof), index);
+                }
+
+                let mut other_index = proof.clone();
+                other_index.leaf_index = Some((index as u64 + 1) % count as u64);
+                assert_eq!(other_index.validate_with_policy(tree.root_hash(), &required).is_ok(), count == 1);
+
+                // Proofs without the fields are checked against the required size
+                let mut legacy = proof.clone();
+                legacy.tree_size = None;
+                legacy.leaf_index = None;
+
+                assert_eq!(legacy.validate_with_policy(tree.root_hash(), &required), Ok(()));
+
+                let larger = ValidationPolicy::new().tree_size(count as u64 + 1);
+                assert!(proof.validate_with_policy(tree.root_hash(), &larger).is_err());
+            }
+        }
+    }
+
+    // The leaves of the last, smaller, subtrees of unbalanced trees are not at the index
+    // their path reads as
+    let values = (0..6).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+    let required = ValidationPolicy::new().tree_size(6);
+
+    for &(index, naive) in &[(4, 2), (5, 3)] {
+        let proof = tree.gen_nth_proof(index).unwrap();
+
+        assert_eq!(proof.leaf_index, Some(index as u64));
+        assert_eq!(proof.lemma.leaf_index(6, OddLeafPolicy::Promote), Some(index));
+        assert_eq!(naive_index(&proof), naive);
+
+        let mut naive_proof = proof.clone();
+        naive_proof.leaf_index = Some(naive as u64);
+
+        assert!(naive_proof.validate(tree.root_hash()));
+        assert_eq!(
+            naive_proof.validate_with_policy(tree.root_hash(), &required),
+            Err(Error::PolicyViolation("path does not lead to the claimed leaf index"))
+        );
+    }
+
+    let proof = MerkleTree::from_vec(digest, vec!["a", "b", "c"]).gen_nth_proof(2).unwrap();
+    assert_eq!(proof.depth(), 1);
+    assert_eq!(proof.leaf_index, Some(2));
+
+    // The same path leads to no leaf of a tree of 4 leaves
+    let mut unsized_proof = proof.clone();
+    unsized_proof.tree_size = None;
+    assert_eq!(
+        unsized_proof.validate_with_policy(&proof.root_hash, &ValidationPolicy::new().tree_size(4)),
+        Err(Error::PolicyViolation("path does not lead to a leaf of a tree of the required size"))
+    );
+    assert_eq!(
+        proof.validate_with_policy(&proof.root_hash, &ValidationPolicy::new().tree_size(4)),
+        Err(Error::PolicyViolation("proof is not from a tree of the required size"))
+    );
+}
+
+/// Computes the root hash of the given leaf hashes and the audit path of the leaf at `index`,
+/// following the definitions of RFC 6962, section 2.1.
+fn rfc6962_path(leaves: &[Vec<u8>], index: usize) -> (Vec<u8>, Vec<Vec<u8>>) {
+    if leaves.len() == 1 {
+        return (leaves[0].clone(), vec![]);
+    }
+
+    let k = leaves.len().next_power_of_two() / 2;
+    let (left, right) = leaves.split_at(k);
+
+    let (left_root, _) = rfc6962_path(left, 0);
+    let (right_root, _) = rfc6962_path(right, 0);
+    let root = digest.hash_nodes(&left_root, &right_root).as_ref().to_vec();
+
+    let (mut path, sibling) = if index < k {
+        (rfc6962_path(left, index).1, right_root)
+    } else {
+        (rfc6962_path(right, index - k).1, left_root)
+    };
+
+    path.push(sibling);
+    (root, path)
+}
+
+#[test]
+fn test_indexed_path_round_trip() {
+    for count in 1..70usize {
+        let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+        let leaves = values.iter().map(|v| digest.hash_leaf(v).as_ref().to_vec()).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(digest, values.clone());
+
+        for index in 0..count {
+            let proof = tree.gen_nth_proof(index).unwrap();
+            let (wire_index, path) = proof.to_indexed_path().unwrap();
+
+            assert_eq!(wire_index, index as u64);
+            assert_eq!(rfc6962_path(&leaves, index), (tree.root_hash().clone(), path.clone()));
+
+            let rebuilt = Proof::from_indexed_path(
+                digest,
+                tree.root_hash().clone(),
+                count as u64,
+                index as u64,
+                path.clone(),
+                values[index].clone(),
+            ).unwrap();
+
+            assert_eq!(rebuilt, proof);
+            assert!(rebuilt.validate_against(tree.root_hash(), count as u64));
+
+            // The path to another leaf only holds if it has the same shape
+            let other = (index + 1) % count;
+            if let Ok(moved) = Proof::from_indexed_path(
+                digest,
+                tree.root_hash().clone(),
+                count as u64,
+                other as u64,
+                path.clone(),
+                values[index].clone(),
+            )
+            {
+                assert_eq!(moved.validate(tree.root_hash()), other == index);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_indexed_path_errors() {
+    let values = (0..5).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let proof = tree.gen_nth_proof(4).unwrap();
+    let (index, path) = proof.to_indexed_path().unwrap();
+
+    assert_eq!((index, path.len()), (4, 1));
+
+    let from_path = |size: u64, index: u64, path: Vec<Vec<u8>>| {
+        Proof::from_indexed_path(digest, tree.root_hash().clone(), size, index, path, "4".to_string())
+    };
+
+    assert!(from_path(5, 4, path.clone()).is_ok());
+    assert_eq!(
+        from_path(8, 4, path.clone()),
+        Err(Error::InvalidPath("path does not have the depth of the leaf"))
+    );
+    assert_eq!(
+        from_path(5, 5, path.clone()),
+        Err(Error::InvalidPath("index is out of the bounds of the tree"))
+    );
+    assert_eq!(
+        from_path(5, 4, vec![vec![0; 3]]),
+        Err(Error::HashLengthMismatch {
+            expected: digest.output_len,
+            found: 3,
+        })
+    );
+
+    let mut unsized_proof = proof.clone();
+    unsized_proof.tree_size = Some(8);
+    unsized_proof.leaf_index = None;
+    assert_eq!(
+        unsized_proof.to_indexed_path(),
+        Err(Error::InvalidPath("not the path to a leaf of the tree"))
+    );
+
+    unsized_proof.tree_size = None;
+    assert_eq!(unsized_proof.to_indexed_path(), Err(Error::MissingField("leaf_index")));
+
+    unsized_proof.leaf_index = Some(4);
+    assert_eq!(unsized_proof.to_indexed_path(), Ok((4, path)));
+}
+
+#[test]
+fn test_huge_tree_sizes() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    let mut proof = tree.gen_proof("a").unwrap();
+
+    // Larger than the largest power of two which fits in a `usize`
+    assert!(!proof.validate_against(tree.root_hash(), u64::MAX));
+
+    proof.leaf_index = None;
+    proof.tree_size = Some(u64::MAX);
+    assert_eq!(proof.to_indexed_path(), Err(Error::InvalidPath("not the path to a leaf of the tree")));
+
+    let path = vec![vec![0; digest.output_len]; MAX_TREE_DEPTH];
+    let proof = Proof::from_indexed_path(digest, vec![0; digest.output_len], u64::MAX, 0, path, "a").unwrap();
+
+    assert_eq!(proof.depth(), MAX_TREE_DEPTH);
+    assert_eq!(proof.lemma.leaf_index(usize::MAX, OddLeafPolicy::Promote), Some(0));
+}
+
+/// The root hash of a tree over the leaves `"a"` to `"h"`, and the proof of each leaf, from the
+/// leaf up, computed with `makeMerkleTree` and `getProof` of the OpenZeppelin merkle-tree
+/// package, given the SHA-256 hashes of the leaves, without sorting them, and SHA-256 sorted
+/// pair hashing in place of keccak256.
+static SORTED_PAIRS_ROOT: &'static str = "ce1fe18c9bbaceb40eccaeba54f5aa85acaa4f5565f9413fdbe87c0b0a731f0d";
+
+static SORTED_PAIRS_PROOFS: [[&'static str; 3]; 8] = [
+    [
+        "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d",
+        "800e03ddb2432933692401d1631850c0af91953fd9c8f3874488c0541dfcf413",
+        "04c541ac1be677f77fa0bf8bfbc5b560a75b7370837668a1d2f25dc1236fb26c",
+    ],
+    [
+        "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb",
+        "800e03ddb2432933692401d1631850c0af91953fd9c8f3874488c0541dfcf413",
+        "04c541ac1be677f77fa0bf8bfbc5b560a75b7370837668a1d2f25dc1236fb26c",
+    ],
+    [
+        "18ac3e7343f016890c510e93f935261169d9e3f565436429830faf0934f4f8e4",
+        "18d79cb747ea174c59f3a3b41768672526d56fecc58360a99d283d0f9b0a3cc0",
+        "04c541ac1be677f77fa0bf8bfbc5b560a75b7370837668a1d2f25dc1236fb26c",
+    ],
+    [
+        "2e7d2c03a9507ae265ecf5b5356885a53393a2029d241394997265a1a25aefc6",
+        "18d79cb747ea174c59f3a3b41768672526d56fecc58360a99d283d0f9b0a3cc0",
+        "04c541ac1be677f77fa0bf8bfbc5b560a75b7370837668a1d2f25dc1236fb26c",
+    ],
+    [
+        "252f10c83610ebca1a059c0bae8255eba2f95be4d1d7bcfa89d7248a82d9f111",
+        "e6219b6cecedd6a36b1bc9223d623839a600beb350dbf9e19e8c661ea877fa82",
+        "4c6aae040ffada3d02598207b8485fcbe161c03f4cb3f660e4d341e7496ff3b2",
+    ],
+    [
+        "3f79bb7b435b05321651daefd374cdc681dc06faa65e374e38337b88ca046dea",
+        "e6219b6cecedd6a36b1bc9223d623839a600beb350dbf9e19e8c661ea877fa82",
+        "4c6aae040ffada3d02598207b8485fcbe161c03f4cb3f660e4d341e7496ff3b2",
+    ],
+    [
+        "aaa9402664f1a41f40ebbc52c9993eb66aeb366602958fdfaa283b71e64db123",
+        "0163e88f03670301d757e64f17f9d99fa3e7567a5f3ce69d58eb72ee0bbb3662",
+        "4c6aae040ffada3d02598207b8485fcbe161c03f4cb3f660e4d341e7496ff3b2",
+    ],
+    [
+        "cd0aa9856147b6c5b4ff2b7dfee5da20aa38253099ef1b4a64aced233c9afe29",
+        "0163e88f03670301d757e64f17f9d99fa3e7567a5f3ce69d58eb72ee0bbb3662",
+        "4c6aae040ffada3d02598207b8485fcbe161c03f4cb3f660e4d341e7496ff3b2",
+    ],
+];
+
+#[test]
+fn test_sorted_pairs_fixtures() {
+    let values = vec!["a", "b", "c", "d", "e", "f", "g", "h"];
+    let tree = MerkleTreeBuilder::new(&SHA256)
+        .hashing_scheme(HashingScheme::SortedPairs)
+        .build_from_vec(values.clone());
+
+    assert_eq!(to_hex(tree.root_hash()), SORTED_PAIRS_ROOT);
+
+    // A perfect tree laid out as in OpenZeppelin's has its leaves in reverse order,
+    // which sorted pair hashing does not tell apart
+    let half = MerkleTreeBuilder::new(&SHA256)
+        .hashing_scheme(HashingScheme::SortedPairs)
+        .build_from_vec(values[..4].to_vec());
+
+    assert_eq!(to_hex(half.root_hash()), SORTED_PAIRS_PROOFS[4][2]);
+
+    for (value, expected) in values.iter().zip(SORTED_PAIRS_PROOFS.iter()) {
+        let path = tree.gen_sorted_proof(*value).unwrap();
+        let expected = expected.iter().map(|hash| from_hex(hash).unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(path, expected);
+
+        let leaf_hash = HashingScheme::SortedPairs.hash_leaf(&SHA256, value);
+        assert!(validate_sorted_path(&SHA256, tree.root_hash(), leaf_hash.as_ref(), &path));
+        assert!(!validate_sorted_path(&SHA256, tree.root_hash(), leaf_hash.as_ref(), &path[1..]));
+
+        let other_hash = HashingScheme::SortedPairs.hash_leaf(&SHA256, &"z");
+        assert!(!validate_sorted_path(&SHA256, tree.root_hash(), other_hash.as_ref(), &path));
+    }
+}
+
+#[test]
+fn test_sorted_pairs_scheme() {
+    for count in 1..20usize {
+        let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+        let tree = MerkleTreeBuilder::new(digest)
+            .hashing_scheme(HashingScheme::SortedPairs)
+            .build_from_vec(values.clone());
+
+        for value in values {
+            let proof = tree.gen_proof(value.clone()).unwrap();
+            assert!(proof.validate(tree.root_hash()));
+
+            // The sides of the siblings make no difference
+            let mut flipped = proof.clone();
+            flipped.lemma = Lemma::from_siblings(
+                digest,
+                HashingScheme::SortedPairs,
+                proof.leaf_hash().to_vec(),
+                proof.siblings().cloned().map(Positioned::flip).collect(),
+            );
+            assert!(flipped.validate(tree.root_hash()));
+
+            let path = tree.gen_sorted_proof(value).unwrap();
+            assert_eq!(path, proof.sorted_path());
+            assert!(validate_sorted_path(digest, tree.root_hash(), proof.leaf_hash(), &path));
+        }
+
+        let multi = tree.gen_multi_proof(&[0, count - 1]).unwrap();
+        let decoded = MultiProof::from_compact_bytes(digest, &multi.to_compact_bytes()).unwrap();
+
+        assert_eq!(decoded.scheme, HashingScheme::SortedPairs);
+        assert!(decoded.validate(tree.root_hash()));
+    }
+
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    assert_eq!(tree.gen_sorted_proof("a"), None);
+}
+
+/// Computes the root hash of a tree of fixed `depth` over the given values,
+/// materializing all its leaves.
+fn naive_fixed_depth_root(values: &[String], depth: usize) -> Vec<u8> {
+    let mut level = values
+        .iter()
+        .map(|value| digest.hash_leaf(value).as_ref().to_vec())
+        .collect::<Vec<_>>();
+
+    level.resize(1 << depth, vec![0; digest.output_len]);
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| digest.hash_nodes(&pair[0], &pair[1]).as_ref().to_vec())
+            .collect();
+    }
+
+    level.remove(0)
+}
+
+#[test]
+fn test_fixed_depth() {
+    for depth in 0..6 {
+        let mut tree = MerkleTree::with_fixed_depth(digest, depth);
+        let mut values = Vec::new();
+
+        assert_eq!(tree.fixed_depth(), Some(depth));
+        assert_eq!(tree.height(), depth);
+        assert_eq!(*tree.root_hash(), naive_fixed_depth_root(&values, depth));
+
+        for i in 0..(1 << depth) {
+            let value = i.to_string();
+
+            assert!(tree.push(value.clone()));
+            values.push(value);
+
+            assert_eq!(tree.count(), values.len());
+            assert_eq!(tree.height(), depth);
+            assert_eq!(*tree.root_hash(), naive_fixed_depth_root(&values, depth));
+
+            let built = MerkleTreeBuilder::new(digest).fixed_depth(depth).build_from_vec(values.clone());
+            assert_eq!(built.root_hash(), tree.root_hash());
+
+            for (index, value) in values.iter().enumerate() {
+                let proof = tree.gen_proof(value.clone()).unwrap();
+
+                assert_eq!(proof.depth(), depth);
+                assert_eq!(proof.tree_size, Some(1 << depth));
+                assert_eq!(proof.leaf_index, Some(index as u64));
+                assert!(proof.validate_against(tree.root_hash(), 1 << depth));
+                assert_eq!(tree.gen_nth_proof(index), Some(proof));
+            }
+
+            assert_eq!(tree.node_hash(depth, 0), Some(tree.root_hash().as_slice()));
+            assert_eq!(*tree.node_hash(0, i).unwrap(), digest.hash_leaf(&i.to_string()).as_ref().to_vec());
+        }
+
+        assert_eq!(tree.try_push("full".to_string()), Err(Error::TooManyLeaves { max: 1 << depth }));
+        assert!(tree.gen_multi_proof(&[0]).is_none());
+        assert!(tree.prove_prefix(1).is_none());
+    }
+}
+
+#[test]
+fn test_fixed_depth_sparse() {
+    // Only the filled leaves and the nodes above them are hashed
+    let tree = MerkleTreeBuilder::new(digest).fixed_depth(32).build_from_vec(vec!["a", "b", "c"]);
+    let proof = tree.gen_proof("c").unwrap();
+
+    assert_eq!(tree.height(), 32);
+    assert_eq!(proof.depth(), 32);
+    assert_eq!(proof.leaf_index, Some(2));
+    assert!(proof.validate_against(tree.root_hash(), 1 << 32));
+
+    let lazy = MerkleTreeBuilder::new(digest).fixed_depth(32).lazy(true).build_from_vec(vec!["a", "b", "c"]);
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+
+    let mut pushed = MerkleTree::with_fixed_depth(digest, 32);
+    pushed.extend(vec!["a", "b", "c"]);
+    assert_eq!(pushed.root_hash(), tree.root_hash());
+}
+
+#[test]
+fn test_default_hashes() {
+    let zero = vec![0; 32];
+
+    let hashes = default_hashes(&SHA256, 3, &zero);
+    let expected = vec![
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "ae0798d0ecaed2b778eddebf18f071a561c53658c05e76cedecc27cafbdbc577",
+        "90534fe0aff6db9edb29eee74e78a386916a581c8e6465349493e1a6c87241e1",
+        "bea16162721bca4b6e1782cbdc695a471522157c6716f508db47c599195340f4",
+    ];
+
+    assert_eq!(hashes.iter().map(|hash| to_hex(hash)).collect::<Vec<_>>(), expected);
+
+    // Cached sequences are extended or truncated as needed
+    assert_eq!(default_hashes(&SHA256, 1, &zero), hashes[..2].to_vec());
+    assert_eq!(default_hashes(&SHA256, 5, &zero)[..4].to_vec(), hashes);
+    assert_eq!(default_hashes(&SHA256, 0, &[1; 32]), vec![vec![1; 32]]);
+
+    // The zero hashes of the deposit contract
+    let unprefixed = default_hashes_with(&SHA256, HashingScheme::Unprefixed, 2, &zero);
+    assert_eq!(to_hex(&unprefixed[1]), "f5a5fd42d16a20302798ef6ed309979b43003d2320d9f0e8ea9831a92759fb4b");
+    assert_eq!(to_hex(&unprefixed[2]), "db56114e00fdd4c1f85c892bf35ac9a89289aaecb1ebd0a96cde606a748b5d71");
+
+    for depth in 0..5 {
+        let tree = MerkleTree::<Vec<u8>>::with_fixed_depth(&SHA256, depth);
+        assert_eq!(*tree.root_hash(), default_hashes(&SHA256, depth, &zero)[depth]);
+
+        let tree = MerkleTreeBuilder::<Vec<u8>>::new(&SHA256)
+            .hashing_scheme(HashingScheme::Unprefixed)
+            .fixed_depth(depth)
+            .build_empty();
+        assert_eq!(*tree.root_hash(), default_hashes_with(&SHA256, HashingScheme::Unprefixed, depth, &zero)[depth]);
+    }
+}
+
+#[test]
+fn test_prove_many() {
+    let count = 50_000;
+    let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    let mut requested = (0..count).step_by(337).map(|i| values[i].clone()).collect::<Vec<_>>();
+    requested.push("missing".to_string());
+    requested.push(values[count - 1].clone());
+    requested.push(values[0].clone());
+    requested.push(values[0].clone());
+
+    let proofs = tree.prove_many(&requested);
+    assert_eq!(proofs.len(), requested.len());
+
+    for (value, proof) in requested.iter().zip(proofs) {
+        assert_eq!(proof, tree.gen_proof(value.clone()));
+    }
+
+    let indices = vec![count - 1, 3, count, 3, 0, 12_345];
+    let proofs = tree.prove_indices(&indices);
+
+    for (&index, proof) in indices.iter().zip(proofs) {
+        assert_eq!(proof, tree.gen_nth_proof(index));
+    }
+
+    assert!(tree.prove_many(&[]).is_empty());
+    assert_eq!(MerkleTree::<String>::empty(digest).prove_indices(&[0]), vec![None]);
+}
+
+#[test]
+fn test_positions_of_hashes() {
+    let count = 10_000;
+    let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+
+    // Half of the queried hashes are those of leaves, the other half those of other values
+    let mut queried = (0..count)
+        .map(|i| if i % 2 == 0 { i } else { count + i })
+        .map(|i| digest.hash_leaf(&i.to_string()).as_ref().to_vec())
+        .collect::<Vec<_>>();
+    queried.push(vec![0; 3]);
+    queried.push(Vec::new());
+
+    let leaf_hashes = tree.leaf_hashes_vec();
+    let naive = queried
+        .iter()
+        .map(|hash| leaf_hashes.iter().position(|leaf_hash| leaf_hash == hash))
+        .collect::<Vec<_>>();
+
+    let positions = tree.positions_of_hashes(&queried);
+
+    assert_eq!(positions, naive);
+    assert_eq!(positions.iter().filter(|position| position.is_some()).count(), count / 2);
+    assert_eq!(positions[count..], [None, None]);
+    assert_eq!(
+        tree.contains_hashes(&queried),
+        naive.iter().map(Option::is_some).collect::<Vec<_>>()
+    );
+
+    // The leftmost leaf with a hash is found
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "a"]);
+    let hashes = vec![digest.hash_leaf(&"a").as_ref().to_vec(), digest.hash_leaf(&"b").as_ref().to_vec()];
+
+    assert_eq!(tree.positions_of_hashes(&hashes), vec![Some(0), Some(1)]);
+    assert!(tree.positions_of_hashes(&[]).is_empty());
+}
+
+#[test]
+fn test_prove_many_duplicate_leaves() {
+    static HASHED: AtomicUsize = AtomicUsize::new(0);
+
+    let values = ["a", "b", "a", "c", "b"];
+    let tree = MerkleTree::from_vec(digest, counted(&values, &HASHED));
+    HASHED.store(0, AtomicOrdering::SeqCst);
+
+    // Each requested value is hashed once, and the leaves of the tree are not rehashed
+    let requested = counted(&["b", "z", "a", "b"], &HASHED);
+    let proofs = tree.prove_many(&requested);
+    assert_eq!(HASHED.load(AtomicOrdering::SeqCst), 4);
+
+    // The leftmost occurrence is proven, as with `gen_proof`
+    let indices = proofs
+        .iter()
+        .map(|proof| proof.as_ref().and_then(|proof| proof.leaf_index))
+        .collect::<Vec<_>>();
+    assert_eq!(indices, vec![Some(1), None, Some(0), Some(1)]);
+
+    for proof in proofs.into_iter().flatten() {
+        assert!(proof.validate(tree.root_hash()));
+    }
+
+    let tree = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(values.to_vec());
+    let proofs = tree.prove_indices(&[4, 2, 0]);
+
+    for (index, proof) in [4, 2, 0].iter().zip(proofs) {
+        assert_eq!(proof, tree.gen_nth_proof(*index));
+    }
+}
+
+/// Returns the lemma `depth` levels below the given one.
+fn lemma_at_mut(lemma: &mut Lemma, depth: usize) -> &mut Lemma {
+    match depth {
+        0 => lemma,
+        _ => lemma_at_mut(lemma.sub_lemma.as_mut().unwrap(), depth - 1),
+    }
+}
+
+/// Returns variants of the given proof, each altered in a single place.
+fn tampered_proofs(proof: &Proof<String>) -> Vec<Proof<String>> {
+    let mut tampered = Vec::new();
+
+    for depth in 0..proof.depth() + 1 {
+        let mut node = proof.clone();
+        lemma_at_mut(&mut node.lemma, depth).node_hash[0] ^= 1;
+        tampered.push(node);
+
+        let mut sibling = proof.clone();
+        {
+            let lemma = lemma_at_mut(&mut sibling.lemma, depth);
+            lemma.sibling_hash = lemma.sibling_hash.take().map(|sibling| {
+                sibling.map(|mut hash| {
+                    hash[0] ^= 1;
+                    hash
+                })
+            });
+        }
+        tampered.push(sibling);
+
+        let mut flipped = proof.clone();
+        {
+            let lemma = lemma_at_mut(&mut flipped.lemma, depth);
+            lemma.sibling_hash = lemma.sibling_hash.take().map(Positioned::flip);
+        }
+        tampered.push(flipped);
+    }
+
+    let mut value = proof.clone();
+    value.value.push('!');
+    tampered.push(value);
+
+    tampered
+}
+
+#[test]
+fn test_caching_verifier() {
+    let values = (0..100).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let other = MerkleTree::from_vec(digest, values[..99].to_vec());
+
+    for &capacity in &[0, 3, 1000] {
+        let mut verifier = CachingVerifier::new(digest, tree.root_hash().clone(), capacity);
+
+        for value in &values {
+            let proof = tree.gen_proof(value.clone()).unwrap();
+            assert!(verifier.validate(&proof));
+
+            // Proofs which share their upper levels with valid ones
+            for tampered in tampered_proofs(&proof) {
+                assert_eq!(verifier.validate(&tampered), tampered.validate(tree.root_hash()));
+            }
+
+            let foreign = other.gen_proof(value.clone());
+            if let Some(foreign) = foreign {
+                assert!(!verifier.validate(&foreign));
+            }
+
+            assert!(verifier.validate(&proof));
+        }
+
+        let stats = verifier.stats();
+
+        match capacity {
+            0 => assert_eq!(stats.hits, 0),
+            3 => assert!(stats.hits > 0),
+            _ => assert!(stats.hit_rate() > 0.5),
+        }
+
+        assert_eq!(stats.evictions > 0, capacity == 3);
+    }
+
+    let mut verifier = CachingVerifier::new(&SHA256, tree.root_hash().clone(), 10);
+    assert!(!verifier.validate(&tree.gen_proof(values[0].clone()).unwrap()));
+}
+
+/// Pushes the given levels, from the leaf up, to an `IncrementalVerify` started at `leaf_hash`,
+/// and checks them against `root_hash`.
+fn verify_levels(
+    scheme: HashingScheme,
+    leaf_hash: &[u8],
+    levels: &[Positioned<Vec<u8>>],
+    root_hash: &[u8],
+) -> bool {
+
+    let mut verify = IncrementalVerify::start_with_scheme(digest, scheme, leaf_hash);
+
+    for level in levels {
+        verify.push_level(level.as_ref().map(Vec::as_slice)).unwrap();
+    }
+
+    verify.finish(root_hash)
+}
+
+#[test]
+fn test_incremental_proofs() {
+    // xorshift64, to pick the sizes of the trees
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    for round in 0..30 {
+        let count = 1 + (next() % 150) as usize;
+        let values = (0..count).map(|i| format!("{}-{}", round, i)).collect::<Vec<_>>();
+
+        let builder = match round % 4 {
+            0 => MerkleTreeBuilder::new(digest),
+            1 => MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+            2 => MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::Unprefixed),
+            _ => MerkleTreeBuilder::new(digest).fixed_depth(8),
+        };
+
+        let tree = builder.build_from_vec(values.clone());
+        let scheme = tree.hashing_scheme();
+        let root_hash = tree.root_hash();
+
+        for index in 0..count {
+            let proof = tree.gen_nth_proof(index).unwrap();
+            let writer = tree.proof_writer(index).unwrap();
+
+            assert_eq!(writer.leaf_hash(), proof.leaf_hash());
+            assert_eq!(writer.root_hash(), root_hash.as_slice());
+            assert_eq!(writer.len(), proof.depth());
+
+            let levels = writer.map(|level| level.map(<[u8]>::to_vec)).collect::<Vec<_>>();
+            let mut siblings = proof.siblings().cloned().collect::<Vec<_>>();
+            siblings.reverse();
+            assert_eq!(levels, siblings);
+
+            assert!(proof.validate(root_hash));
+            assert!(verify_levels(scheme, proof.leaf_hash(), &levels, root_hash));
+
+            for tampered in tampered_proofs(&proof) {
+                let mut levels = tampered.siblings().cloned().collect::<Vec<_>>();
+                levels.reverse();
+
+                let leaf_hash = tampered.leaf_hash();
+                let verified = verify_levels(scheme, leaf_hash, &levels, root_hash);
+
+                // The hashes of the inner nodes of a proof are only checked by `Proof::validate`
+                if leaf_hash == proof.leaf_hash() && levels == siblings {
+                    assert!(verified);
+                } else {
+                    assert_eq!(verified, tampered.validate(root_hash));
+                }
+            }
+        }
+
+        assert!(tree.proof_writer(count).is_none());
+    }
+}
+
+#[test]
+fn test_incremental_verify_errors() {
+    let tree = MerkleTree::from_vec(digest, (0..10).map(|i| i.to_string()).collect::<Vec<_>>());
+    let proof = tree.gen_nth_proof(3).unwrap();
+
+    let mut verify = IncrementalVerify::start(digest, proof.leaf_hash());
+    let mut levels = proof.siblings().cloned().collect::<Vec<_>>();
+    levels.reverse();
+
+    verify.push_level(levels[0].as_ref().map(Vec::as_slice)).unwrap();
+    let hash = verify.current_hash().to_vec();
+
+    // A truncated level is rejected before it is folded in
+    let short = levels[1].as_ref().map(|hash| &hash[1..]);
+    assert_eq!(
+        verify.push_level(short),
+        Err(Error::HashLengthMismatch {
+            expected: digest.output_len,
+            found: digest.output_len - 1,
+        })
+    );
+    assert_eq!(verify.depth(), 1);
+    assert_eq!(verify.current_hash(), hash.as_slice());
+
+    for level in &levels[1..] {
+        verify.push_level(level.as_ref().map(Vec::as_slice)).unwrap();
+    }
+
+    assert_eq!(verify.depth(), proof.depth());
+    assert!(verify.clone().finish(tree.root_hash()));
+    assert!(!verify.finish(&hash));
+
+    let mut verify = IncrementalVerify::start(digest, proof.leaf_hash());
+    let sibling = vec![0; digest.output_len];
+
+    for _ in 0..MAX_TREE_DEPTH {
+        verify.push_level(Positioned::Left(&sibling)).unwrap();
+    }
+
+    assert!(verify.push_level(Positioned::Right(&sibling)).is_err());
+    assert_eq!(verify.depth(), MAX_TREE_DEPTH);
+}
+
+/// Splits `values` into consecutive chunks of `chunk_len` values, each built into a tree by `build`.
+fn build_chunks<F>(values: &[String], chunk_len: usize, build: F) -> Vec<MerkleTree<String>>
+where
+    F: Fn(Vec<String>) -> MerkleTree<String>,
+{
+
+    values.chunks(chunk_len).map(|chunk| build(chunk.to_vec())).collect()
+}
+
+#[test]
+fn test_from_chunks() {
+    let values = (0..70).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    let builders: Vec<fn() -> MerkleTreeBuilder<String>> = vec![
+        || MerkleTreeBuilder::new(digest),
+        || MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        || MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::Unprefixed),
+    ];
+
+    for builder in builders {
+        for count in 1..values.len() + 1 {
+            let values = &values[..count];
+            let whole = builder().build_from_vec(values.to_vec());
+
+            for &chunk_len in &[1, 2, 4, 8, 16, 64, 128] {
+                let chunks = build_chunks(values, chunk_len, |chunk| builder().build_from_vec(chunk));
+                let tree = MerkleTree::from_chunks(digest, chunks).unwrap();
+
+                assert_eq!(tree.root_hash(), whole.root_hash());
+                assert_eq!(tree.count(), whole.count());
+                assert_eq!(tree.height(), whole.height());
+                assert_eq!(tree.hashing_scheme(), whole.hashing_scheme());
+                assert_eq!(tree.odd_leaf_policy(), whole.odd_leaf_policy());
+                assert!(tree.iter().eq(values.iter()));
+
+                for index in 0..count {
+                    assert_eq!(tree.gen_nth_proof(index), whole.gen_nth_proof(index));
+                }
+            }
+        }
+    }
+
+    // The stitched tree can grow like any other
+    let chunks = build_chunks(&values[..48], 16, |chunk| MerkleTree::from_vec(digest, chunk));
+    let mut tree = MerkleTree::from_chunks(digest, chunks).unwrap();
+    tree.extend(values[48..].iter().cloned());
+    assert_eq!(tree.root_hash(), MerkleTree::from_vec(digest, values.clone()).root_hash());
+}
+
+#[test]
+fn test_from_chunks_errors() {
+    let values = (0..20).map(|i| i.to_string()).collect::<Vec<_>>();
+    let from_vec = |chunk| MerkleTree::from_vec(digest, chunk);
+
+    let invalid = |index, reason| {
+        Err::<MerkleTree<String>, _>(Error::InvalidChunk {
+            index: index,
+            reason: reason,
+        })
+    };
+
+    // Chunks which are not a power of two, or of uneven sizes
+    for &chunk_len in &[3, 6, 12] {
+        let chunks = build_chunks(&values, chunk_len, from_vec);
+        assert_eq!(
+            MerkleTree::from_chunks(digest, chunks),
+            invalid(0, "number of leaves is not a power of two")
+        );
+    }
+
+    let mut chunks = build_chunks(&values[..16], 8, from_vec);
+    chunks.insert(1, from_vec(values[16..].to_vec()));
+    assert_eq!(
+        MerkleTree::from_chunks(digest, chunks),
+        invalid(1, "not as many leaves as the first chunk")
+    );
+
+    let chunks = vec![from_vec(values[..4].to_vec()), from_vec(values[4..].to_vec())];
+    assert_eq!(MerkleTree::from_chunks(digest, chunks), invalid(1, "more leaves than the first chunk"));
+
+    let chunks = vec![from_vec(values[..4].to_vec()), MerkleTree::empty(digest)];
+    assert_eq!(MerkleTree::from_chunks(digest, chunks), invalid(1, "chunk is empty"));
+
+    // A single chunk of any size is the whole tree
+    let chunks = vec![from_vec(values[..13].to_vec())];
+    assert_eq!(MerkleTree::from_chunks(digest, chunks).unwrap(), from_vec(values[..13].to_vec()));
+    assert_eq!(MerkleTree::from_chunks(digest, Vec::new()).unwrap(), MerkleTree::<String>::empty(digest));
+
+    // Chunks built otherwise
+    let chunks = vec![from_vec(values[..4].to_vec()), MerkleTree::from_vec(&SHA256, values[4..8].to_vec())];
+    assert_eq!(MerkleTree::from_chunks(digest, chunks), Err(Error::AlgorithmMismatch));
+
+    let duplicate = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(values[4..8].to_vec());
+    let chunks = vec![from_vec(values[..4].to_vec()), duplicate];
+    assert_eq!(
+        MerkleTree::from_chunks(digest, chunks),
+        invalid(1, "built with another hashing scheme or odd leaf policy")
+    );
+
+    let sorted = MerkleTree::from_vec_ordered(digest, values[4..8].to_vec(), LeafOrdering::by_value());
+    let chunks = vec![from_vec(values[..4].to_vec()), sorted];
+    assert_eq!(
+        MerkleTree::from_chunks(digest, chunks),
+        invalid(1, "built with a fixed depth, or with deduplicated or sorted leaves")
+    );
+}
+
+#[test]
+fn test_export_levels() {
+    let values = (0..11).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        let tree = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy).build_from_vec(values.clone());
+
+        for depth in 0..tree.height() + 2 {
+            let export = tree.export_levels(depth);
+
+            assert_eq!(export.count(), 11);
+            assert_eq!(export.depth(), depth.min(tree.height()));
+            assert_eq!(export.root_hash(), tree.root_hash());
+            assert_eq!(export.level(0).unwrap(), &[tree.root_hash().clone()]);
+
+            for d in 1..export.depth() + 1 {
+                let level = tree.height() - d;
+                let hashes = (0..).map_while(|index| tree.node_hash(level, index).map(<[u8]>::to_vec));
+
+                assert_eq!(export.level(d).unwrap(), &hashes.collect::<Vec<_>>()[..]);
+            }
+
+            assert_eq!(export.level(export.depth() + 1), None);
+            assert!(export.diff(&export).is_empty());
+        }
+    }
+
+    let empty = MerkleTree::<String>::empty(digest).export_levels(3);
+    assert_eq!(empty.depth(), 0);
+    assert!(empty.level(0).unwrap().is_empty());
+}
+
+#[test]
+fn test_export_levels_diff() {
+    let values = (0..11).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        let builder = || MerkleTreeBuilder::new(digest).odd_leaf_policy(policy);
+        let tree = builder().build_from_vec(values.clone());
+
+        for changed in 0..values.len() {
+            let mut other = values.clone();
+            other[changed] = "changed".to_string();
+            let other = builder().build_from_vec(other);
+
+            for depth in 0..tree.height() + 2 {
+                let diff = tree.export_levels(depth).diff(&other.export_levels(depth));
+
+                assert_eq!(diff.len(), 1);
+                assert!(diff[0].start <= changed && changed < diff[0].end);
+                assert_eq!(diff[0].level, tree.height() - depth.min(tree.height()));
+            }
+
+            // Exports of different depths are compared at the lowest level of both
+            let diff = tree.export_levels(4).diff(&other.export_levels(2));
+            assert_eq!(diff.len(), 1);
+            assert_eq!(diff[0].level, 2);
+            assert_eq!(diff[0].index, changed / 4);
+        }
+    }
+}
+
+#[test]
+fn test_export_levels_diff_counts() {
+    let values = (0..11).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    // Appended leaves only change the buckets they fall into
+    let shorter = MerkleTree::from_vec(digest, values[..9].to_vec());
+    let diff = tree.export_levels(2).diff(&shorter.export_levels(2));
+
+    let bucket = |index, start, end| {
+        BucketRange {
+            level: 2,
+            index: index,
+            start: start,
+            end: end,
+        }
+    };
+
+    assert_eq!(diff, vec![bucket(2, 8, 11)]);
+    assert_eq!(shorter.export_levels(2).diff(&tree.export_levels(2)), diff);
+
+    // A tree of another height is compared at its lowest exported level, above the root of
+    // the smaller tree
+    let small = MerkleTree::from_vec(digest, values[..3].to_vec());
+    let diff = tree.export_levels(2).diff(&small.export_levels(2));
+    assert_eq!(diff, vec![bucket(0, 0, 4), bucket(1, 4, 8), bucket(2, 8, 11)]);
+
+    let empty = MerkleTree::<String>::empty(digest);
+    assert_eq!(tree.export_levels(1).diff(&empty.export_levels(1)).len(), 2);
+    assert!(empty.export_levels(1).diff(&empty.export_levels(2)).is_empty());
+}
+
+#[test]
+fn test_fingerprint_diff() {
+    for &count in &[1, 2, 11, 16, 100] {
+        let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+
+        for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+            let tree = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy).build_from_vec(values.clone());
+
+            for depth in 0..tree.height() + 2 {
+                let fingerprint = tree.fingerprint(depth);
+                let level = tree.height() - depth.min(tree.height());
+
+                assert_eq!(fingerprint.count(), count);
+                assert_eq!(fingerprint.depth(), depth.min(tree.height()));
+                assert_eq!(fingerprint.root_hash(), tree.root_hash());
+                assert_eq!(fingerprint.hashes().len(), (count - 1) / (1 << level) + 1);
+                assert!(tree.diff_roots_since(&fingerprint).is_unchanged());
+
+                // An update at a leaf flags exactly the bucket holding it
+                for changed in 0..count {
+                    let mut updated = tree.clone();
+                    updated.update(changed, "changed".to_string()).unwrap();
+
+                    let diff = updated.diff_roots_since(&fingerprint);
+                    let bucket = changed >> level;
+
+                    assert!(!diff.resized);
+                    assert_eq!(diff.changed.len(), 1);
+                    assert_eq!(diff.changed[0].level, level);
+                    assert_eq!(diff.changed[0].index, bucket);
+                    assert_eq!(diff.changed[0].start, bucket << level);
+                    assert_eq!(diff.changed[0].end, ((bucket + 1) << level).min(count));
+                    assert!(diff.only_within(changed..changed + 1));
+
+                    // Setting the value back leaves nothing changed
+                    updated.update(changed, changed.to_string()).unwrap();
+                    assert!(updated.diff_roots_since(&fingerprint).is_unchanged());
+                }
+            }
+        }
+    }
+
+    let values = (0..11).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let fingerprint = tree.fingerprint(2);
+
+    // Updates in two buckets flag both, and only them
+    let mut updated = tree.clone();
+    updated.apply_updates(vec![(1, "one".to_string()), (9, "nine".to_string())]).unwrap();
+    let diff = updated.diff_roots_since(&fingerprint);
+
+    assert_eq!(diff.changed.iter().map(|bucket| (bucket.start, bucket.end)).collect::<Vec<_>>(), [(0, 4), (8, 11)]);
+    assert!(!diff.only_within(0..2));
+    assert!(diff.only_within(1..10));
+
+    // Appending flags the last bucket, and the new ones
+    let mut pushed = tree.clone();
+    pushed.push("11".to_string());
+    pushed.push("12".to_string());
+    let diff = pushed.diff_roots_since(&fingerprint);
+
+    assert!(diff.resized);
+    assert_eq!(diff.changed.iter().map(|bucket| (bucket.start, bucket.end)).collect::<Vec<_>>(), [(8, 12), (12, 13)]);
+
+    let mut grown = pushed.clone();
+    grown.extend(vec!["13".to_string(), "14".to_string(), "15".to_string(), "16".to_string()]);
+    let diff = grown.diff_roots_since(&fingerprint);
+    assert_eq!(diff.changed.iter().map(|bucket| bucket.start).collect::<Vec<_>>(), [8, 12, 16]);
+
+    let mut popped = tree.clone();
+    popped.pop();
+    let diff = popped.diff_roots_since(&fingerprint);
+    assert!(diff.resized);
+    assert_eq!(diff.changed.iter().map(|bucket| (bucket.start, bucket.end)).collect::<Vec<_>>(), [(8, 11)]);
+
+    // Every bucket of a tree hashed with another algorithm changed
+    let other = MerkleTree::from_vec(&SHA256, values);
+    let diff = other.diff_roots_since(&fingerprint);
+    assert!(!diff.resized);
+    assert_eq!(diff.changed.len(), 3);
+
+    let empty = MerkleTree::<String>::empty(digest);
+    assert!(empty.fingerprint(3).hashes().is_empty());
+    assert!(empty.diff_roots_since(&empty.fingerprint(3)).is_unchanged());
+    assert_eq!(empty.diff_roots_since(&fingerprint).changed.len(), 3);
+}
+
+#[test]
+fn test_all_proofs() {
+    for count in 0..35 {
+        let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+
+        let trees = vec![
+            MerkleTree::from_vec(digest, values.clone()),
+            MerkleTreeBuilder::new(digest)
+                .odd_leaf_policy(OddLeafPolicy::Duplicate)
+                .build_from_vec(values.clone()),
+            MerkleTreeBuilder::new(digest)
+                .hashing_scheme(HashingScheme::Unprefixed)
+                .build_from_vec(values.clone()),
+            MerkleTreeBuilder::new(digest).fixed_depth(6).build_from_vec(values.clone()),
+        ];
+
+        for tree in trees {
+            let proofs = tree.all_proofs();
+            let naive = (0..count).map(|n| tree.gen_nth_proof(n).unwrap()).collect::<Vec<_>>();
+
+            assert_eq!(proofs, naive);
+            assert_eq!(tree.iter_proofs().len(), count);
+
+            for (n, proof) in proofs.iter().enumerate() {
+                assert_eq!(proof.value, values[n]);
+                assert_eq!(proof.leaf_index, naive[n].leaf_index);
+                assert_eq!(proof.tree_size, naive[n].tree_size);
+                assert!(proof.validate(tree.root_hash()));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_proven_iter() {
+    for count in 0..35 {
+        let values = (0..count).map(|i| i.to_string()).collect::<Vec<_>>();
+
+        let trees = vec![
+            MerkleTree::from_vec(digest, values.clone()),
+            MerkleTreeBuilder::new(digest)
+                .odd_leaf_policy(OddLeafPolicy::Duplicate)
+                .build_from_vec(values.clone()),
+            MerkleTreeBuilder::new(digest).leaf_counts(true).build_from_vec(values.clone()),
+            MerkleTreeBuilder::new(digest).fixed_depth(6).build_from_vec(values.clone()),
+        ];
+
+        for tree in trees {
+            let naive = (0..count).map(|n| tree.gen_nth_proof(n).unwrap()).collect::<Vec<_>>();
+
+            let mut borrowed = tree.proven_iter();
+            assert_eq!(borrowed.size_hint(), (count, Some(count)));
+
+            for n in 0..count {
+                let (value, proof) = borrowed.next().unwrap();
+
+                assert_eq!(value, values[n]);
+                assert_eq!(proof, naive[n]);
+                assert!(proof.validate(tree.root_hash()));
+                assert_eq!(borrowed.len(), count - n - 1);
+            }
+
+            assert!(borrowed.next().is_none());
+
+            let root_hash = tree.root_hash().clone();
+            let mut consumed = tree.into_proven_iter();
+            assert_eq!(consumed.size_hint(), (count, Some(count)));
+
+            for n in 0..count {
+                let (value, proof) = consumed.next().unwrap();
+
+                assert_eq!(value, values[n]);
+                assert_eq!(proof, naive[n]);
+                assert_eq!(proof.leaf_index, naive[n].leaf_index);
+                assert_eq!(proof.leaf_counts, naive[n].leaf_counts);
+                assert!(proof.validate_value(&root_hash));
+                assert_eq!(consumed.len(), count - n - 1);
+            }
+
+            assert!(consumed.next().is_none());
+            assert_eq!(consumed.size_hint(), (0, Some(0)));
+        }
+    }
+
+    // The pruned leaves are skipped
+    let values = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+    let mut tree = MerkleTree::from_vec(digest, values.clone());
+    tree.prune_below(3);
+
+    assert_eq!(tree.proven_iter().len(), 2);
+    assert_eq!(
+        tree.into_proven_iter().map(|(value, _)| value).collect::<Vec<_>>(),
+        vec!["8".to_string(), "9".to_string()]
+    );
+}
+
+/// A value which counts how many times it was cloned.
+#[derive(Debug)]
+struct CountedClone(String, Arc<AtomicUsize>);
+
+impl Clone for CountedClone {
+    fn clone(&self) -> Self {
+        self.1.fetch_add(1, AtomicOrdering::SeqCst);
+        CountedClone(self.0.clone(), self.1.clone())
+    }
+}
+
+impl Hashable for CountedClone {
+    fn update_context(&self, context: &mut Context) {
+        self.0.update_context(context);
+    }
+}
+
+#[test]
+fn test_into_proven_iter_clones_once() {
+    let clones = Arc::new(AtomicUsize::new(0));
+    let values = (0..13).map(|i| CountedClone(i.to_string(), clones.clone())).collect::<Vec<_>>();
+
+    let tree = MerkleTree::from_vec(digest, values);
+    let root_hash = tree.root_hash().clone();
+    assert_eq!(clones.load(AtomicOrdering::SeqCst), 0);
+
+    for (n, (value, proof)) in tree.into_proven_iter().enumerate() {
+        assert_eq!(clones.load(AtomicOrdering::SeqCst), n + 1);
+        assert_eq!(value.0, n.to_string());
+        assert!(proof.validate_value(&root_hash));
+    }
+
+    assert_eq!(clones.load(AtomicOrdering::SeqCst), 13);
+}
+
+/// A value which cannot be cloned without panicking.
+#[derive(Debug, PartialEq)]
+struct NoClone(String);
+
+impl Clone for NoClone {
+    fn clone(&self) -> Self {
+        panic!("{:?} was cloned", self.0)
+    }
+}
+
+impl Hashable for NoClone {
+    fn update_context(&self, context: &mut Context) {
+        self.0.update_context(context);
+    }
+}
+
+#[test]
+fn test_gen_proof_ref() {
+    let values = (0..7).map(|i| i.to_string()).collect::<Vec<_>>();
+
+    for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        let tree = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy).build_from_vec(values.clone());
+
+        for value in &values {
+            let proof = tree.gen_proof_ref(value).unwrap();
+
+            assert_eq!(Some(&proof), tree.gen_proof(value.clone()).as_ref());
+            assert_eq!(proof.leaf_index, tree.gen_proof(value.clone()).unwrap().leaf_index);
+            assert!(proof.validate(tree.root_hash()));
+        }
+
+        assert_eq!(tree.gen_proof_ref(&"7".to_string()), None);
+    }
+
+    // A value which is not found is never cloned
+    let tree = MerkleTree::from_vec(digest, values.iter().cloned().map(NoClone).collect());
+    assert_eq!(tree.gen_proof_ref(&NoClone("7".to_string())), None);
+}
+
+#[test]
+fn test_gen_proof_detached() {
+    let values = (0..7).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.iter().cloned().map(NoClone).collect());
+    let full = MerkleTree::from_vec(digest, values.clone());
+
+    for (n, value) in values.iter().enumerate() {
+        let proof = tree.gen_proof_detached(&NoClone(value.clone())).unwrap();
+        let expected = full.gen_proof(value.clone()).unwrap();
+
+        assert_eq!(proof.lemma, expected.lemma);
+        assert_eq!(proof.root_hash, expected.root_hash);
+        assert_eq!(proof.leaf_index, Some(n as u64));
+        assert!(proof.validate(tree.root_hash()));
+    }
+
+    assert!(tree.gen_proof_detached(&NoClone("7".to_string())).is_none());
+}
+
+#[test]
+fn test_gen_proof_by_hash() {
+    let values = vec!["a", "b", "a", "c", "a"];
+
+    for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        let mut tree = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy).build_from_vec(values.clone());
+
+        for &cached in &[false, true] {
+            if cached {
+                tree.enable_proof_cache(2);
+            }
+
+            for value in &values {
+                let leaf_hash = digest.hash_leaf(value);
+                let proof = tree.gen_proof_by_hash(leaf_hash.as_ref()).unwrap();
+                let expected = tree.gen_proof(value).unwrap();
+
+                assert_eq!(proof.lemma, expected.lemma);
+                assert_eq!(proof.leaf_index, expected.leaf_index);
+                assert!(proof.validate(tree.root_hash()));
+            }
+        }
+
+        // Every occurrence of a duplicate leaf, the first one being that of `gen_proof_by_hash`
+        let leaf_hash = digest.hash_leaf(&"a");
+        let proofs = tree.gen_proofs_by_hash(leaf_hash.as_ref());
+        let indices = proofs.iter().map(|proof| proof.leaf_index).collect::<Vec<_>>();
+
+        assert_eq!(indices, vec![Some(0), Some(2), Some(4)]);
+        assert_eq!(tree.gen_proof_by_hash(leaf_hash.as_ref()).unwrap().leaf_index, Some(0));
+        assert!(proofs.iter().all(|proof| proof.validate(tree.root_hash())));
+
+        // Hashes of no leaf, or of the wrong length
+        let missing = digest.hash_leaf(&"d");
+        assert!(tree.gen_proof_by_hash(missing.as_ref()).is_none());
+        assert!(tree.gen_proofs_by_hash(missing.as_ref()).is_empty());
+
+        assert!(tree.gen_proof_by_hash(&leaf_hash.as_ref()[..32]).is_none());
+        assert!(tree.gen_proofs_by_hash(&[]).is_empty());
+    }
+}
+
+#[test]
+fn test_keyed_hasher() {
+    let values = (0..7).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+    let key = format!("secret {}", values.len()).into_bytes();
+    let hasher = KeyedHasher::new(digest, key.clone());
+
+    let build = |values: Vec<String>| {
+        MerkleTreeBuilder::new(digest).hasher(hasher.clone()).build_from_vec(values)
+    };
+
+    let tree = build(values.clone());
+    let plain = MerkleTree::from_vec(digest, values.clone());
+
+    assert_ne!(tree.root_hash(), plain.root_hash());
+    assert!(!format!("{:?}", hasher).contains("secret"));
+
+    // Leaves are hashed as `H(key || 0x00 || value)`
+    let mut ctx = Context::new(digest);
+    ctx.update(&key);
+    ctx.update(&[0x00]);
+    ctx.update(values[0].as_bytes());
+    assert_eq!(tree.leaf_hashes_vec()[0], ctx.finish().as_ref());
+
+    let other = KeyedHasher::new(digest, b"another key".to_vec());
+
+    for value in &values {
+        let proof = tree.gen_proof(value.clone()).unwrap();
+
+        assert!(proof.validate_with(&hasher, tree.root_hash()));
+        assert!(!proof.validate_with(&other, tree.root_hash()));
+        assert!(!proof.validate_with(&digest, tree.root_hash()));
+
+        // The proof holds on to the hasher of the tree, and validates with it
+        assert!(proof.validate(tree.root_hash()));
+        assert_eq!(proof.compute_root().unwrap(), *tree.root_hash());
+        assert_eq!(proof.verify(&tree.root()), Ok(()));
+        assert_eq!(proof.validate_with_policy(tree.root_hash(), &ValidationPolicy::new()), Ok(()));
+        assert!(proof.validate_against(tree.root_hash(), values.len() as u64));
+        assert_eq!(proof.validate_bounded(tree.root_hash(), &ValidationBudget::new()), Ok(()));
+
+        // But it is not serialized
+        let data = proof.clone().into_data().into_proof(digest).unwrap();
+        assert!(!data.validate(tree.root_hash()));
+        assert!(data.validate_with(&hasher, tree.root_hash()));
+    }
+
+    let proofs = (0..values.len()).map(|index| tree.gen_nth_proof(index).unwrap()).collect::<Vec<_>>();
+    assert!(validate_batch_bounded(&proofs, tree.root_hash(), &ValidationBudget::new()).all_valid());
+
+    let mut verifier = CachingVerifier::with_hasher(hasher.clone(), tree.root_hash().clone(), 16);
+    let mut unkeyed = CachingVerifier::new(digest, tree.root_hash().clone(), 16);
+    assert!(proofs.iter().all(|proof| verifier.validate(proof)));
+    assert!(!proofs.iter().any(|proof| unkeyed.validate(proof)));
+
+    let siblings = tree.proof_writer(5).unwrap();
+    let mut verify = IncrementalVerify::start_with_hasher(hasher.clone(), HashingScheme::default(), siblings.leaf_hash());
+    let mut bare = IncrementalVerify::start(digest, siblings.leaf_hash());
+
+    for sibling in siblings {
+        verify.push_level(sibling.clone()).unwrap();
+        bare.push_level(sibling).unwrap();
+    }
+
+    assert!(verify.finish(tree.root_hash()));
+    assert!(!bare.finish(tree.root_hash()));
+
+    // The bare algorithm is a hasher which feeds nothing
+    let proof = plain.gen_proof(values[3].clone()).unwrap();
+    assert!(proof.validate_with(&digest, plain.root_hash()));
+
+    // Pushing into a keyed tree rehashes it with the same hasher
+    let mut pushed = build(values[..4].to_vec());
+    for value in &values[4..] {
+        pushed.push(value.clone());
+    }
+
+    assert_eq!(pushed.root_hash(), tree.root_hash());
+
+    let lazy = MerkleTreeBuilder::new(digest).hasher(hasher.clone()).lazy(true).build_from_vec(values.clone());
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+
+    let empty: MerkleTree<String> = MerkleTreeBuilder::new(digest).hasher(hasher.clone()).build_empty();
+    assert_eq!(empty.root_hash().as_slice(), hasher.hash_empty().as_ref());
+
+    // Fixed depth trees pad with default hashes computed with the hasher
+    let fixed = MerkleTreeBuilder::new(digest).hasher(hasher.clone()).fixed_depth(4).build_from_vec(values.clone());
+    let plain_fixed = MerkleTreeBuilder::new(digest).fixed_depth(4).build_from_vec(values.clone());
+    assert_ne!(fixed.root_hash(), plain_fixed.root_hash());
+
+    let proof = fixed.gen_proof(values[6].clone()).unwrap();
+    assert!(proof.validate_with(&hasher, fixed.root_hash()));
+
+    // Multiproofs and prefix proofs hold on to the hasher too
+    let multi = tree.gen_multi_proof(&[1, 4]).unwrap();
+    assert!(multi.validate(tree.root_hash()));
+    assert_eq!(multi.verify(&tree.root()), Ok(()));
+
+    let prefix = build(values[..3].to_vec());
+    assert!(tree.prove_prefix(3).unwrap().verify(prefix.root_hash(), 3, tree.root_hash(), 7));
+
+    let compact = multi.to_compact_bytes();
+    assert!(MultiProof::from_compact_bytes_with(hasher.clone(), &compact).unwrap().validate(tree.root_hash()));
+    assert!(!MultiProof::from_compact_bytes(digest, &compact).unwrap().validate(tree.root_hash()));
+
+    // As do the partial trees built from the multiproofs
+    let mut partial = PartialTree::from_multiproof(multi).unwrap();
+    assert!(partial.insert_leaf(0, values[0].clone()).is_ok());
+    assert!(partial.insert_leaf(5, values[4].clone()).is_err());
+    assert!(partial.gen_proof(0).unwrap().validate(tree.root_hash()));
+
+    // Snapshots are read with the same hasher
+    let mut snapshot = Vec::new();
+    tree.write_snapshot(&mut snapshot).unwrap();
+    let restored = MerkleTree::<String>::read_snapshot_with(hasher.clone(), &snapshot[..]).unwrap();
+    assert_eq!(restored.root_hash(), tree.root_hash());
+    assert!(restored.gen_nth_proof(2).unwrap().validate(tree.root_hash()));
+    assert!(MerkleTree::<String>::read_snapshot(digest, &snapshot[..]).is_err());
+
+    // Setting the algorithm drops the hasher
+    let unkeyed = MerkleTreeBuilder::new(digest).hasher(hasher.clone()).algorithm(digest).build_from_vec(values);
+    assert_eq!(unkeyed.root_hash(), plain.root_hash());
+}
+
+/// Records the size of every batch of leaves it hashes.
+#[derive(Clone, Debug, Default)]
+struct BatchRecorder(Arc<Mutex<Vec<usize>>>);
+
+impl MerkleHasher for BatchRecorder {
+    fn algorithm(&self) -> &'static Algorithm {
+        digest
+    }
+
+    fn hash_leaves_batch(&self, leaves: &[&[u8]]) -> Vec<Vec<u8>> {
+        self.0.lock().unwrap().push(leaves.len());
+
+        leaves.iter().map(|leaf| ::digest::digest(digest, leaf).as_ref().to_vec()).collect()
+    }
+}
+
+#[test]
+fn test_leaf_batch_size() {
+    let values = (0..10).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+    let expected = MerkleTree::from_vec(digest, values.clone());
+
+    for &scheme in &[HashingScheme::DomainSeparated, HashingScheme::Unprefixed] {
+        let recorder = BatchRecorder::default();
+        let builder = MerkleTreeBuilder::new(digest)
+            .hasher(recorder.clone())
+            .hashing_scheme(scheme)
+            .leaf_batch_size(4);
+
+        let mut tree = builder.clone().build_from_vec(values.clone());
+        assert_eq!(*recorder.0.lock().unwrap(), vec![4, 4, 2]);
+        assert_eq!(tree, MerkleTreeBuilder::new(digest).hashing_scheme(scheme).build_from_vec(values.clone()));
+
+        // Pushed values are hashed in batches too
+        tree.try_extend(vec!["x".to_string(), "y".to_string()]).unwrap();
+        assert_eq!(*recorder.0.lock().unwrap(), vec![4, 4, 2, 2]);
+
+        // Lazy trees are hashed in batches when first accessed
+        let lazy = builder.lazy(true).build_from_vec(values.clone());
+        assert_eq!(recorder.0.lock().unwrap().len(), 4);
+        assert_eq!(lazy.root_hash(), MerkleTreeBuilder::new(digest).hashing_scheme(scheme).build_from_vec(values.clone()).root_hash());
+        assert_eq!(recorder.0.lock().unwrap()[4..], [4, 4, 2]);
+    }
+
+    // Without a batch size, leaves are hashed one by one
+    let recorder = BatchRecorder::default();
+    let tree = MerkleTreeBuilder::new(digest).hasher(recorder.clone()).build_from_vec(values.clone());
+    assert!(recorder.0.lock().unwrap().is_empty());
+    assert_eq!(tree, expected);
+
+    // Values which do not give their bytes are hashed one by one as well
+    let recorder = BatchRecorder::default();
+    let tree = MerkleTreeBuilder::new(digest)
+        .hasher(recorder.clone())
+        .leaf_batch_size(4)
+        .build_from_vec(values.iter().map(|value| PublicKey::new(vec![value.clone().into_bytes()], vec![])).collect());
+
+    assert!(recorder.0.lock().unwrap().is_empty());
+    assert_eq!(tree.count(), values.len());
+}
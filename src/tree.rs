@@ -1,178 +1,1284 @@
 
-use ring::digest::{Algorithm, Digest};
+use std::cmp::{self, Ordering};
+use std::hash::{Hash, Hasher};
+use std::mem::{self, size_of};
+use std::slice;
+use std::sync::OnceLock;
+use std::vec;
 
-use hashutils::{Hashable, HashUtils};
+use digest::{Algorithm, Digest};
+
+#[cfg(feature = "serialization-serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serialization-serde")]
+use serde::ser::{Error as SerializeError, SerializeStructVariant};
 
-pub use proof::{Proof, Lemma, Positioned};
+#[cfg(feature = "serialization-serde")]
+use error::Error;
+#[cfg(feature = "serialization-serde")]
+use hash_encoding::HashRef;
+use hashutils::{Hashable, HashUtils};
+use small_hash::SmallHash;
+use proof::MAX_TREE_DEPTH;
 
 /// Binary Tree where leaves hold a stand-alone value.
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum Tree<T> {
-    Empty { hash: Vec<u8> },
+///
+/// The nodes are held in a single vector, where the children of a node come before it and are
+/// referred to by their position, so that the root is the last node. The leaves come in order,
+/// from left to right. Nodes are reached through `Subtree` handles, see `Tree::root`.
+///
+/// Its `Debug` output only shows the first levels of the tree, see `display::TreeDebug`.
+#[derive(Clone)]
+pub struct Tree<T> {
+    nodes: Vec<Slot<T>>,
 
-    Leaf { hash: Vec<u8>, value: T },
+    /// The number of leaves under the `Pruned` nodes
+    pruned: usize,
 
-    Node {
-        hash: Vec<u8>,
-        left: Box<Tree<T>>,
-        right: Box<Tree<T>>,
-    },
+    /// The root hash, copied out of the root on the first call to `Tree::hash`, as it is handed
+    /// out as a `Vec<u8>` by the public API
+    root_hash: OnceLock<Vec<u8>>,
 }
 
-impl<T> Tree<T> {
-    /// Create an empty tree
-    pub fn empty(hash: Digest) -> Self {
-        Tree::Empty { hash: hash.as_ref().into() }
+/// A node of a `Tree`, whose children are given by their position in the tree.
+#[derive(Clone)]
+enum Slot<T> {
+    Empty { hash: SmallHash },
+
+    Leaf { hash: SmallHash, value: T },
+
+    Node { hash: SmallHash, left: u32, right: u32 },
+
+    /// A subtree of which only the hash and the number of leaves are kept
+    Pruned { hash: SmallHash, leaves: usize },
+}
+
+impl<T> Slot<T> {
+    fn hash(&self) -> &SmallHash {
+        match *self {
+            Slot::Empty { ref hash } => hash,
+            Slot::Leaf { ref hash, .. } => hash,
+            Slot::Node { ref hash, .. } => hash,
+            Slot::Pruned { ref hash, .. } => hash,
+        }
     }
+}
+
+/// The position of a node in the `Tree` being built.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeId(u32);
 
-    /// Create a new tree
+/// A leaf of a tree to be built, along with its hash.
+#[derive(Clone, Debug)]
+pub struct Leaf<T> {
+    pub hash: SmallHash,
+    pub value: T,
+}
+
+impl<T> Leaf<T> {
+    /// Create a new leaf with the given hash
     pub fn new(hash: Digest, value: T) -> Self {
-        Tree::Leaf {
+        Leaf {
             hash: hash.as_ref().into(),
             value: value,
         }
     }
 
-    /// Create a new leaf
-    pub fn new_leaf(algo: &'static Algorithm, value: T) -> Tree<T>
+    /// Create a new leaf, hashing its value
+    pub fn from_value(algo: &'static Algorithm, value: T) -> Self
     where
         T: Hashable,
     {
 
         let hash = algo.hash_leaf(&value);
-        Tree::new(hash, value)
+        Leaf::new(hash, value)
+    }
+}
+
+impl<T> Tree<T> {
+    /// Create an empty tree
+    pub fn empty(hash: Digest) -> Self {
+        let mut tree = Tree::with_capacity(1);
+        tree.push_empty(hash.as_ref().into());
+        tree
+    }
+
+    /// Starts building a tree of up to `capacity` nodes, from its leaves up with `Tree::push_leaf`
+    /// and `Tree::push_node`. The tree is complete once its root is pushed.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Tree {
+            nodes: Vec::with_capacity(capacity),
+            pruned: 0,
+            root_hash: OnceLock::new(),
+        }
+    }
+
+    /// Empties the tree, keeping the allocation of its nodes, so as to build another tree
+    /// of up to `capacity` nodes in it, as with `Tree::with_capacity`.
+    pub fn recycle(mut self, capacity: usize) -> Self {
+        self.nodes.clear();
+        self.nodes.reserve_exact(capacity);
+        self.pruned = 0;
+        self.root_hash.take();
+        self
+    }
+
+    fn push(&mut self, slot: Slot<T>) -> NodeId {
+        let id = self.nodes.len();
+        assert!(id <= u32::MAX as usize, "too many nodes for a `Tree`");
+
+        self.nodes.push(slot);
+        self.root_hash.take();
+        NodeId(id as u32)
+    }
+
+    /// Adds an empty node, either the right child of a node or the root of an empty tree.
+    pub fn push_empty(&mut self, hash: SmallHash) -> NodeId {
+        self.push(Slot::Empty { hash: hash })
+    }
+
+    /// Adds a leaf, to the right of the leaves added before it.
+    pub fn push_leaf(&mut self, leaf: Leaf<T>) -> NodeId {
+        self.push(Slot::Leaf {
+            hash: leaf.hash,
+            value: leaf.value,
+        })
+    }
+
+    /// Adds a subtree with the given number of leaves, of which only the hash is kept.
+    pub fn push_pruned(&mut self, hash: SmallHash, leaves: usize) -> NodeId {
+        self.pruned += leaves;
+
+        self.push(Slot::Pruned {
+            hash: hash,
+            leaves: leaves,
+        })
+    }
+
+    /// Adds an internal node over the given children.
+    pub fn push_node(&mut self, hash: SmallHash, left: NodeId, right: NodeId) -> NodeId {
+        self.push(Slot::Node {
+            hash: hash,
+            left: left.0,
+            right: right.0,
+        })
+    }
+
+    /// Moves the nodes of `other` into this tree, returning the position of its root.
+    pub fn append(&mut self, other: Tree<T>) -> NodeId {
+        let offset = self.nodes.len() as u32;
+
+        self.nodes.reserve(other.nodes.len());
+        self.pruned += other.pruned;
+
+        for slot in other.nodes {
+            let slot = match slot {
+                Slot::Node { hash, left, right } => {
+                    Slot::Node {
+                        hash: hash,
+                        left: left + offset,
+                        right: right + offset,
+                    }
+                }
+
+                slot => slot,
+            };
+
+            self.push(slot);
+        }
+
+        NodeId(self.nodes.len() as u32 - 1)
+    }
+
+    /// Returns the hash of the given node.
+    pub fn node_hash(&self, id: NodeId) -> &SmallHash {
+        self.nodes[id.0 as usize].hash()
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> Subtree<T> {
+        Subtree {
+            nodes: &self.nodes,
+            index: self.nodes.len() - 1,
+        }
+    }
+
+    /// Returns the subtree whose root is at the given position among the nodes of the tree,
+    /// see `Subtree::position`.
+    pub fn subtree(&self, position: usize) -> Subtree<T> {
+        Subtree {
+            nodes: &self.nodes,
+            index: position,
+        }
+    }
+
+    /// Moves the value out of the leaf at the given position among the nodes of the tree,
+    /// leaving a pruned leaf of the same hash in its place, or returns `None` if the node
+    /// there is not a leaf.
+    pub fn take_value(&mut self, position: usize) -> Option<T> {
+        let hash = match *self.nodes.get(position)? {
+            Slot::Leaf { hash, .. } => hash,
+            _ => return None,
+        };
+
+        self.pruned += 1;
+
+        match mem::replace(&mut self.nodes[position], Slot::Pruned { hash: hash, leaves: 1 }) {
+            Slot::Leaf { value, .. } => Some(value),
+            _ => unreachable!("the node is a leaf"),
+        }
     }
 
     /// Returns a hash from the tree.
     pub fn hash(&self) -> &Vec<u8> {
-        match *self {
-            Tree::Empty { ref hash } => hash,
-            Tree::Leaf { ref hash, .. } => hash,
-            Tree::Node { ref hash, .. } => hash,
-        }
+        self.root_hash.get_or_init(|| self.root().hash().to_vec())
+    }
+
+    /// Returns the number of nodes of the tree, leaves included.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the number of bytes allocated for the nodes, including their hashes but not the heap
+    /// allocations of their values.
+    pub fn node_bytes(&self) -> usize {
+        self.nodes.capacity() * size_of::<Slot<T>>()
     }
 
     /// Returns a borrowing iterator over the leaves of the tree.
     pub fn iter(&self) -> LeavesIterator<T> {
-        LeavesIterator::new(self)
+        LeavesIterator { nodes: self.nodes.iter() }
+    }
+
+    /// Returns the value of the leaf at the given `index`, counting the pruned leaves,
+    /// for modifying it in place, or `None` if there is no such leaf or if it is pruned.
+    ///
+    /// The hash of the leaf is left as is, so the value must keep hashing the same.
+    pub fn nth_value_mut(&mut self, index: usize) -> Option<&mut T> {
+        let mut first = 0;
+
+        for slot in self.nodes.iter_mut() {
+            match *slot {
+                Slot::Leaf { ref mut value, .. } if first == index => return Some(value),
+                Slot::Leaf { .. } => first += 1,
+                Slot::Pruned { leaves, .. } if index < first + leaves => return None,
+                Slot::Pruned { leaves, .. } => first += leaves,
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Replaces the leaves at the given indices, in order, then rehashes every node above them
+    /// exactly once, from the leaves up, with `hash_nodes`, which is given the level and the index
+    /// of the node along with the hashes of its children. If `copy_odd`, an `Empty` node paired
+    /// with a rehashed node first gets its hash, as those of `OddLeafPolicy::Duplicate` do.
+    ///
+    /// Returns the values which were replaced, in order, along with the level and the index of
+    /// every rehashed node, as in `MerkleTree::node_hash`, each below its ancestors.
+    /// The tree must not be pruned, and every index must be lower than its number of leaves.
+    pub fn update_leaves<F>(
+        &mut self,
+        leaves: Vec<(usize, Leaf<T>)>,
+        copy_odd: bool,
+        hash_nodes: F,
+    ) -> (Vec<T>, Vec<(usize, usize)>)
+    where
+        F: FnMut((usize, usize), &[u8], &[u8]) -> SmallHash,
+    {
+
+        let positions = self.leaf_positions();
+        let mut dirty = vec![false; self.nodes.len()];
+        let mut replaced = Vec::with_capacity(leaves.len());
+
+        for (index, leaf) in leaves {
+            let position = positions[index];
+            dirty[position] = true;
+
+            if let Slot::Leaf { ref mut hash, ref mut value } = self.nodes[position] {
+                *hash = leaf.hash;
+                replaced.push(mem::replace(value, leaf.value));
+            }
+        }
+
+        let rehashed = self.rehash(dirty, &positions, copy_odd, hash_nodes);
+
+        (replaced, rehashed)
+    }
+
+    /// Exchanges the leaves at the given indices, along with their hashes, then rehashes
+    /// the nodes above them as `Tree::update_leaves` does, the common ancestors only once.
+    ///
+    /// Returns the level and the index of every rehashed node. The tree must not be pruned,
+    /// and both indices must be lower than its number of leaves.
+    pub fn swap_leaves<F>(&mut self, i: usize, j: usize, copy_odd: bool, hash_nodes: F) -> Vec<(usize, usize)>
+    where
+        F: FnMut((usize, usize), &[u8], &[u8]) -> SmallHash,
+    {
+
+        let positions = self.leaf_positions();
+        let mut dirty = vec![false; self.nodes.len()];
+
+        self.nodes.swap(positions[i], positions[j]);
+        dirty[positions[i]] = true;
+        dirty[positions[j]] = true;
+
+        self.rehash(dirty, &positions, copy_odd, hash_nodes)
+    }
+
+    /// Rehashes every node above a `dirty` one, from the leaves up, see `Tree::update_leaves`,
+    /// returning their level and index.
+    fn rehash<F>(
+        &mut self,
+        mut dirty: Vec<bool>,
+        positions: &[usize],
+        copy_odd: bool,
+        mut hash_nodes: F,
+    ) -> Vec<(usize, usize)>
+    where
+        F: FnMut((usize, usize), &[u8], &[u8]) -> SmallHash,
+    {
+
+        let start = dirty.iter().position(|&dirty| dirty).unwrap_or(self.nodes.len());
+        let mut rehashed = Vec::new();
+
+        // The children of a node come before it, so they are rehashed first
+        for position in start..self.nodes.len() {
+            let (left, right) = match self.nodes[position] {
+                Slot::Node { left, right, .. } if dirty[left as usize] || dirty[right as usize] => {
+                    (left as usize, right as usize)
+                }
+                _ => continue,
+            };
+
+            if copy_odd && dirty[left] {
+                let copy = *self.nodes[left].hash();
+
+                if let Slot::Empty { ref mut hash } = self.nodes[right] {
+                    *hash = copy;
+                }
+            }
+
+            let coordinates = self.coordinates(position, positions);
+            let combined = hash_nodes(coordinates, self.nodes[left].hash(), self.nodes[right].hash());
+
+            if let Slot::Node { ref mut hash, .. } = self.nodes[position] {
+                *hash = combined;
+            }
+
+            dirty[position] = true;
+            rehashed.push(coordinates);
+        }
+
+        self.root_hash = OnceLock::new();
+
+        rehashed
+    }
+
+    /// Returns the positions of the leaves among the nodes, from left to right.
+    fn leaf_positions(&self) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(position, slot)| match *slot {
+                Slot::Leaf { .. } => Some(position),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the level and the index of the node at the given position, which has a leaf below
+    /// it, as in `MerkleTree::node_hash`, given the positions of the leaves of an unpruned tree.
+    ///
+    /// A left child is always a full level below its parent, as only the last node of a level
+    /// is promoted, so the level of a node is the depth of its leftmost leaf.
+    fn coordinates(&self, position: usize, leaf_positions: &[usize]) -> (usize, usize) {
+        let mut level = 0;
+        let mut leftmost = position;
+
+        while let Slot::Node { left, .. } = self.nodes[leftmost] {
+            leftmost = left as usize;
+            level += 1;
+        }
+
+        let first = leaf_positions.binary_search(&leftmost).unwrap_or_else(|index| index);
+
+        (level, first >> level)
+    }
+
+    /// Returns the hashes of the leaves of the tree, from left to right.
+    pub fn leaf_hashes(&self) -> Vec<&SmallHash> {
+        self.nodes
+            .iter()
+            .filter_map(|slot| match *slot {
+                Slot::Leaf { ref hash, .. } => Some(hash),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns a borrowing iterator over the hashes of the leaves of the tree, which has
+    /// `count` leaves, from left to right.
+    pub fn iter_leaf_hashes(&self, count: usize) -> LeafHashes<T> {
+        LeafHashes {
+            nodes: self.nodes.iter(),
+            remaining: count,
+        }
+    }
+
+    /// Returns, for every node of the tree by position, the number of leaves which come before it,
+    /// which is the index of the leaves among them. See `Subtree::position`.
+    pub fn leaf_ranks(&self) -> Vec<usize> {
+        let mut rank = 0;
+
+        self.nodes
+            .iter()
+            .map(|slot| {
+                let before = rank;
+
+                match *slot {
+                    Slot::Leaf { .. } => rank += 1,
+                    Slot::Pruned { leaves, .. } => rank += leaves,
+                    _ => {}
+                }
+
+                before
+            })
+            .collect()
+    }
+
+    /// Returns the hashes of the leaves of the tree, from left to right, along with their index,
+    /// which accounts for the leaves of the pruned subtrees.
+    pub fn indexed_leaf_hashes(&self) -> Vec<(usize, &SmallHash)> {
+        self.leaf_ranks()
+            .into_iter()
+            .zip(&self.nodes)
+            .filter_map(|(rank, slot)| match *slot {
+                Slot::Leaf { ref hash, .. } => Some((rank, hash)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the leaves of the tree, from left to right, along with their index,
+    /// as `Tree::indexed_leaf_hashes` does, and their value.
+    pub fn indexed_leaves(&self) -> Vec<(usize, &SmallHash, &T)> {
+        self.leaf_ranks()
+            .into_iter()
+            .zip(&self.nodes)
+            .filter_map(|(rank, slot)| match *slot {
+                Slot::Leaf { ref hash, ref value } => Some((rank, hash, value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the index of the leftmost leaf whose hash is `needle`.
+    pub fn position(&self, needle: &[u8]) -> Option<usize> {
+        self.indexed_leaf_hashes()
+            .into_iter()
+            .find(|&(_, hash)| *hash == *needle)
+            .map(|(index, _)| index)
+    }
+
+    /// Returns the number of leaves under the pruned subtrees, which are not part of the tree.
+    pub fn pruned_leaves(&self) -> usize {
+        self.pruned
+    }
+
+    /// Returns the hash of the leftmost pruned subtree, if any.
+    pub fn first_pruned(&self) -> Option<&SmallHash> {
+        self.nodes.iter().find_map(|slot| match *slot {
+            Slot::Pruned { ref hash, .. } => Some(hash),
+            _ => None,
+        })
+    }
+
+    /// Replaces the subtrees at the given `depth` below the root, and the leaves above it,
+    /// by `Pruned` nodes, dropping their values. The `Empty` nodes are kept as they are.
+    pub fn prune_below(self, depth: usize) -> Tree<T> {
+        self.transform(|node, node_depth| match *node {
+            Slot::Node { .. } | Slot::Leaf { .. } if node_depth >= depth => Transform::Prune,
+            _ => Transform::Keep,
+        })
+    }
+
+    /// Replaces the leftmost pruned subtree with the given hash by `subtree`, which must have
+    /// as many leaves. Returns the tree unchanged as an error, along with `subtree`, if there is
+    /// no such pruned subtree.
+    pub fn graft(self, hash: &[u8], subtree: Tree<T>) -> Result<Tree<T>, Box<(Tree<T>, Tree<T>)>> {
+        let leaves = subtree.leaf_count();
+
+        let found = self.nodes.iter().any(|slot| match *slot {
+            Slot::Pruned { hash: ref pruned, leaves: count } => *pruned == *hash && count == leaves,
+            _ => false,
+        });
+
+        if !found {
+            return Err(Box::new((self, subtree)));
+        }
+
+        let mut subtree = Some(subtree);
+
+        Ok(self.transform(|node, _| match *node {
+            Slot::Pruned { hash: ref pruned, leaves: count } if *pruned == *hash && count == leaves => {
+                subtree.take().map_or(Transform::Keep, Transform::Replace)
+            }
+            _ => Transform::Keep,
+        }))
+    }
+
+    /// Returns the number of leaves of the tree, including those of the pruned subtrees.
+    pub fn leaf_count(&self) -> usize {
+        self.pruned + self.iter().count()
+    }
+
+    /// Moves the nodes of the tree into a new one, from the root down, the subtree at each node
+    /// being kept, pruned or replaced as told by `f` given the node and its depth. The leaves
+    /// stay in order, and the children before their parent.
+    fn transform<F>(self, mut f: F) -> Tree<T>
+    where
+        F: FnMut(&Slot<T>, usize) -> Transform<T>,
+    {
+
+        let root = self.nodes.len() - 1;
+        let mut slots = self.nodes.into_iter().map(Some).collect::<Vec<_>>();
+        let mut tree = Tree::with_capacity(slots.len());
+
+        move_subtree(&mut slots, root, 0, &mut f, &mut tree);
+        tree
+    }
+
+    /// Consumes the tree, returning its leaves from left to right, along with their hashes.
+    pub fn into_leaves(self) -> Vec<Leaf<T>> {
+        self.nodes
+            .into_iter()
+            .filter_map(|slot| match slot {
+                Slot::Leaf { hash, value } => {
+                    Some(Leaf {
+                        hash: hash,
+                        value: value,
+                    })
+                }
+                _ => None,
+            })
+            .collect()
     }
 }
 
-/// An borrowing iterator over the leaves of a `Tree`.
-/// Adapted from http://codereview.stackexchange.com/q/110283.
-#[allow(missing_debug_implementations)]
-pub struct LeavesIterator<'a, T>
+/// What `Tree::transform` does with a subtree.
+enum Transform<T> {
+    Keep,
+    Prune,
+    Replace(Tree<T>),
+}
+
+/// Moves the subtree at the given `index` of `slots`, which lies `depth` levels below the root,
+/// into `tree`, see `Tree::transform`. Returns the position of its root in `tree`.
+fn move_subtree<T, F>(slots: &mut [Option<Slot<T>>], index: usize, depth: usize, f: &mut F, tree: &mut Tree<T>) -> NodeId
 where
-    T: 'a,
+    F: FnMut(&Slot<T>, usize) -> Transform<T>,
 {
-    current_value: Option<&'a T>,
-    right_nodes: Vec<&'a Tree<T>>,
+
+    let slot = slots[index].take().expect("every node has a single parent");
+
+    match f(&slot, depth) {
+        Transform::Replace(subtree) => tree.append(subtree),
+
+        Transform::Prune => {
+            let leaves = count_leaves(slots, &slot);
+            tree.push_pruned(*slot.hash(), leaves)
+        }
+
+        Transform::Keep => {
+            match slot {
+                Slot::Node { hash, left, right } => {
+                    let left = move_subtree(slots, left as usize, depth + 1, f, tree);
+                    let right = move_subtree(slots, right as usize, depth + 1, f, tree);
+
+                    tree.push_node(hash, left, right)
+                }
+
+                Slot::Pruned { hash, leaves } => tree.push_pruned(hash, leaves),
+
+                slot => tree.push(slot),
+            }
+        }
+    }
 }
 
-impl<'a, T> LeavesIterator<'a, T> {
-    fn new(root: &'a Tree<T>) -> Self {
-        let mut iter = LeavesIterator {
-            current_value: None,
-            right_nodes: Vec::new(),
-        };
+/// Returns the number of leaves below the given node, whose children are still in `slots`.
+fn count_leaves<T>(slots: &[Option<Slot<T>>], slot: &Slot<T>) -> usize {
+    match *slot {
+        Slot::Empty { .. } => 0,
+        Slot::Leaf { .. } => 1,
+        Slot::Pruned { leaves, .. } => leaves,
+
+        Slot::Node { left, right, .. } => {
+            let child = |index: u32| slots[index as usize].as_ref().expect("every node has a single parent");
+            count_leaves(slots, child(left)) + count_leaves(slots, child(right))
+        }
+    }
+}
+
+/// A node of a `Tree`, along with the nodes below it.
+pub struct Subtree<'a, T: 'a> {
+    nodes: &'a [Slot<T>],
+    index: usize,
+}
+
+impl<'a, T> Clone for Subtree<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for Subtree<'a, T> {}
+
+/// The kind of the root of a `Subtree`, along with its hash, and its value or children.
+#[derive(Debug)]
+pub enum View<'a, T: 'a> {
+    Empty { hash: &'a SmallHash },
+
+    Leaf { hash: &'a SmallHash, value: &'a T },
+
+    Node {
+        hash: &'a SmallHash,
+        left: Subtree<'a, T>,
+        right: Subtree<'a, T>,
+    },
+
+    Pruned { hash: &'a SmallHash, leaves: usize },
+}
+
+impl<'a, T> Subtree<'a, T> {
+    /// Returns the root of this subtree, to be matched on.
+    pub fn view(self) -> View<'a, T> {
+        match self.nodes[self.index] {
+            Slot::Empty { ref hash } => View::Empty { hash: hash },
+
+            Slot::Leaf { ref hash, ref value } => {
+                View::Leaf {
+                    hash: hash,
+                    value: value,
+                }
+            }
+
+            Slot::Node {
+                ref hash,
+                left,
+                right,
+            } => {
+                View::Node {
+                    hash: hash,
+                    left: self.child(left),
+                    right: self.child(right),
+                }
+            }
+
+            Slot::Pruned { ref hash, leaves } => {
+                View::Pruned {
+                    hash: hash,
+                    leaves: leaves,
+                }
+            }
+        }
+    }
+
+    fn child(self, index: u32) -> Self {
+        Subtree {
+            nodes: self.nodes,
+            index: index as usize,
+        }
+    }
+
+    /// Returns the position of the root of this subtree among the nodes of the tree.
+    pub fn position(self) -> usize {
+        self.index
+    }
 
-        iter.add_left(root);
+    /// Returns a hash from the tree.
+    pub fn hash(self) -> &'a SmallHash {
+        self.nodes[self.index].hash()
+    }
 
-        iter
+    /// Returns whether the root of this subtree is an `Empty` node.
+    pub fn is_empty(self) -> bool {
+        match self.view() {
+            View::Empty { .. } => true,
+            _ => false,
+        }
     }
 
-    fn add_left(&mut self, mut tree: &'a Tree<T>) {
-        loop {
-            match *tree {
-                Tree::Empty { .. } => {
-                    self.current_value = None;
-                    break;
+    /// Returns the value of the leaf at the given `index` of the tree, which has `count` leaves.
+    pub fn nth_value(self, mut index: usize, mut count: usize) -> Option<&'a T> {
+        let mut tree = self;
+
+        while index < count {
+            match tree.view() {
+                View::Empty { .. } | View::Pruned { .. } => return None,
+
+                View::Leaf { value, .. } => return Some(value),
+
+                View::Node { left, right, .. } => {
+                    let left_count = left_count(right, count);
+
+                    if index < left_count {
+                        tree = left;
+                        count = left_count;
+                    } else {
+                        tree = right;
+                        index -= left_count;
+                        count -= left_count;
+                    }
                 }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the hash of the pruned subtree holding the leaf at the given `index` of the tree,
+    /// which has `count` leaves, if that leaf is pruned.
+    pub fn pruned_subtree(self, mut index: usize, mut count: usize) -> Option<&'a SmallHash> {
+        let mut tree = self;
+
+        while index < count {
+            match tree.view() {
+                View::Pruned { hash, .. } => return Some(hash),
+
+                View::Node { left, right, .. } => {
+                    let left_count = left_count(right, count);
 
-                Tree::Node {
-                    ref left,
-                    ref right,
-                    ..
-                } => {
-                    self.right_nodes.push(right);
-                    tree = left;
+                    if index < left_count {
+                        tree = left;
+                        count = left_count;
+                    } else {
+                        tree = right;
+                        index -= left_count;
+                        count -= left_count;
+                    }
                 }
 
-                Tree::Leaf { ref value, .. } => {
-                    self.current_value = Some(value);
-                    break;
+                View::Empty { .. } | View::Leaf { .. } => return None,
+            }
+        }
+
+        None
+    }
+
+    /// Returns the node of the tree, which has `count` leaves and the given `height`,
+    /// at the given `level` and `index`, see `MerkleTree::node_hash`. `padded` tells whether
+    /// the odd nodes of the tree were paired with an `Empty` node, rather than promoted.
+    pub fn node_at_level(
+        self,
+        level: usize,
+        index: usize,
+        count: usize,
+        height: usize,
+        padded: bool,
+    ) -> Option<Subtree<'a, T>> {
+
+        let start = if level >= MAX_TREE_DEPTH {
+            if index == 0 { 0 } else { return None }
+        } else {
+            index.checked_mul(1 << level)?
+        };
+
+        if start >= count {
+            return None;
+        }
+
+        let mut tree = self;
+        let mut offset = 0;
+        let mut count = count;
+        let mut tree_level = height;
+
+        while tree_level > level {
+            match tree.view() {
+                View::Node { left, right, .. } => {
+                    let left_count = left_count(right, count);
+
+                    if start < offset + left_count {
+                        tree = left;
+                        count = left_count;
+                        tree_level -= 1;
+                    } else {
+                        tree = right;
+                        offset += left_count;
+                        count -= left_count;
+                        tree_level = if padded { tree_level - 1 } else { ceil_log2(count) };
+                    }
+                }
+
+                _ => return None,
+            }
+        }
+
+        Some(tree)
+    }
+
+    /// Returns the hashes of the internal nodes of this subtree, leaving out the `Empty` ones,
+    /// level by level from the leaves up, and from left to right within a level.
+    /// This is the order in which `MerkleTree::from_leaves` builds them.
+    pub fn internal_hashes(self) -> Vec<&'a SmallHash> {
+        self.internal_hash_levels().into_iter().flatten().collect()
+    }
+
+    /// Works like `Subtree::internal_hashes`, but returns the hashes of each level apart,
+    /// from level `1` up.
+    pub fn internal_hash_levels(self) -> Vec<Vec<&'a SmallHash>> {
+        let mut levels = Vec::new();
+        self.collect_internal_hashes(&mut levels);
+        levels
+    }
+
+    /// Adds the hashes of the internal nodes of this subtree to `levels`, returning the level
+    /// of its root. A node is one level above its left child, the leaves being at level `0`,
+    /// whereas a promoted node stays at its level.
+    fn collect_internal_hashes(self, levels: &mut Vec<Vec<&'a SmallHash>>) -> usize {
+        match self.view() {
+            View::Empty { .. } | View::Leaf { .. } | View::Pruned { .. } => 0,
+
+            View::Node { hash, left, right } => {
+                let level = left.collect_internal_hashes(levels) + 1;
+                right.collect_internal_hashes(levels);
+
+                if levels.len() < level {
+                    levels.resize(level, Vec::new());
                 }
+
+                levels[level - 1].push(hash);
+                level
             }
         }
     }
+
+    /// Returns the position of the variant of the root of this subtree, as derived
+    /// implementations of `PartialOrd` and `Hash` would.
+    fn rank(self) -> isize {
+        match self.view() {
+            View::Empty { .. } => 0,
+            View::Leaf { .. } => 1,
+            View::Node { .. } => 2,
+            View::Pruned { .. } => 3,
+        }
+    }
+
+    /// Copies the nodes of this subtree to `tree`, the children before their parent,
+    /// cloning the values of its leaves, and returns the position of its root.
+    pub fn copy_into(self, tree: &mut Tree<T>) -> NodeId
+    where
+        T: Clone,
+    {
+
+        match self.view() {
+            View::Empty { hash } => tree.push_empty(*hash),
+
+            View::Leaf { hash, value } => {
+                tree.push_leaf(Leaf {
+                    hash: *hash,
+                    value: value.clone(),
+                })
+            }
+
+            View::Node { hash, left, right } => {
+                let left = left.copy_into(tree);
+                let right = right.copy_into(tree);
+
+                tree.push_node(*hash, left, right)
+            }
+
+            View::Pruned { hash, leaves } => tree.push_pruned(*hash, leaves),
+        }
+    }
 }
 
-impl<'a, T> Iterator for LeavesIterator<'a, T> {
-    type Item = &'a T;
+impl<'a, T: PartialEq> PartialEq for Subtree<'a, T> {
+    fn eq(&self, other: &Subtree<'a, T>) -> bool {
+        match (self.view(), other.view()) {
+            (View::Empty { hash: a }, View::Empty { hash: b }) => a == b,
 
-    fn next(&mut self) -> Option<&'a T> {
-        let result = self.current_value.take();
+            (View::Leaf { hash: a, value: x }, View::Leaf { hash: b, value: y }) => a == b && x == y,
+
+            (View::Node { hash: a, left: l, right: r }, View::Node { hash: b, left: m, right: s }) => {
+                a == b && l == m && r == s
+            }
+
+            (View::Pruned { hash: a, leaves: x }, View::Pruned { hash: b, leaves: y }) => a == b && x == y,
 
-        if let Some(rest) = self.right_nodes.pop() {
-            self.add_left(rest);
+            _ => false,
         }
+    }
+}
 
-        result
+impl<'a, T: Eq> Eq for Subtree<'a, T> {}
+
+impl<'a, T: Ord> PartialOrd for Subtree<'a, T> {
+    fn partial_cmp(&self, other: &Subtree<'a, T>) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
-/// An iterator over the leaves of a `Tree`.
-#[allow(missing_debug_implementations)]
-pub struct LeavesIntoIterator<T> {
-    current_value: Option<T>,
-    right_nodes: Vec<Tree<T>>,
+impl<'a, T: Ord> Ord for Subtree<'a, T> {
+    fn cmp(&self, other: &Subtree<'a, T>) -> Ordering {
+        match (self.view(), other.view()) {
+            (View::Empty { hash: a }, View::Empty { hash: b }) => a.cmp(b),
+
+            (View::Leaf { hash: a, value: x }, View::Leaf { hash: b, value: y }) => {
+                a.cmp(b).then_with(|| x.cmp(y))
+            }
+
+            (View::Node { hash: a, left: l, right: r }, View::Node { hash: b, left: m, right: s }) => {
+                a.cmp(b).then_with(|| l.cmp(&m)).then_with(|| r.cmp(&s))
+            }
+
+            (View::Pruned { hash: a, leaves: x }, View::Pruned { hash: b, leaves: y }) => {
+                a.cmp(b).then_with(|| x.cmp(&y))
+            }
+
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
 }
 
-impl<T> LeavesIntoIterator<T> {
-    fn new(root: Tree<T>) -> Self {
-        let mut iter = LeavesIntoIterator {
-            current_value: None,
-            right_nodes: Vec::new(),
-        };
+impl<'a, T: Hash> Hash for Subtree<'a, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+
+        match self.view() {
+            View::Empty { hash } => hash.hash(state),
 
-        iter.add_left(root);
+            View::Leaf { hash, value } => {
+                hash.hash(state);
+                value.hash(state);
+            }
 
-        iter
+            View::Node { hash, left, right } => {
+                hash.hash(state);
+                Hash::hash(&left, state);
+                Hash::hash(&right, state);
+            }
+
+            View::Pruned { hash, leaves } => {
+                hash.hash(state);
+                leaves.hash(state);
+            }
+        }
     }
+}
 
-    fn add_left(&mut self, mut tree: Tree<T>) {
-        loop {
-            match tree {
-                Tree::Empty { .. } => {
-                    self.current_value = None;
-                    break;
-                }
+/// Trees are compared by shape, hashes and values, as their roots are.
+impl<T: PartialEq> PartialEq for Tree<T> {
+    fn eq(&self, other: &Tree<T>) -> bool {
+        self.root() == other.root()
+    }
+}
+
+impl<T: Eq> Eq for Tree<T> {}
+
+impl<T: Ord> PartialOrd for Tree<T> {
+    fn partial_cmp(&self, other: &Tree<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Tree<T> {
+    fn cmp(&self, other: &Tree<T>) -> Ordering {
+        self.root().cmp(&other.root())
+    }
+}
+
+impl<T: Hash> Hash for Tree<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Hash::hash(&self.root(), state)
+    }
+}
+
+/// Returns the height of a tree with `count` leaves, built by promoting the odd nodes.
+///
+/// Does not overflow for any `count`, which may come from an untrusted proof.
+pub fn ceil_log2(count: usize) -> usize {
+    match count.checked_next_power_of_two() {
+        Some(power) => power.trailing_zeros() as usize,
+        None => usize::BITS as usize,
+    }
+}
+
+/// Returns the number of leaves beneath the node at the given `level` and `index`, as in
+/// `MerkleTree::node_hash`, of a tree with `count` leaves, leaving out the zero leaves
+/// of trees of a fixed depth.
+///
+/// Every node of a level covers as many leaves as the nodes to its left, save for the last one,
+/// whatever the odd leaf policy.
+pub fn subtree_leaves(level: usize, index: usize, count: usize) -> u64 {
+    let width = 1u64.checked_shl(level as u32).unwrap_or(u64::MAX);
+    let first = (index as u64).saturating_mul(width);
+
+    cmp::min(width, (count as u64).saturating_sub(first))
+}
+
+/// Returns the number of leaves under the left child of a node which has `count` leaves,
+/// given its `right` child.
+///
+/// The left child of a node is a perfect tree, unless the right child is the empty copy
+/// of the left one made by `OddLeafPolicy::Duplicate`, in which case it holds all the leaves.
+pub fn left_count<T>(right: Subtree<T>, count: usize) -> usize {
+    if right.is_empty() {
+        count
+    } else {
+        count.next_power_of_two() / 2
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<'a, T> Subtree<'a, T> {
+    /// Returns the number of leaves and the height of the tree.
+    ///
+    /// Fails with `Error::MalformedTree` if the left subtree of a node does not hold the leaves
+    /// it holds in the trees this crate builds, see `left_count`, even though its hashes
+    /// may be consistent.
+    pub fn shape(self) -> Result<(usize, usize), Error> {
+        match self.view() {
+            View::Empty { .. } => Ok((0, 0)),
+            View::Leaf { .. } => Ok((1, 0)),
+            View::Node { left, right, .. } => {
+                let (left_leaves, left_height) = left.shape()?;
+                let (right_leaves, right_height) = right.shape()?;
+                let count = left_leaves + right_leaves;
 
-                Tree::Node { left, right, .. } => {
-                    self.right_nodes.push(*right);
-                    tree = *left;
+                if left_leaves != left_count(right, count) {
+                    return Err(Error::MalformedTree("left subtree does not hold the leaves it should"));
                 }
 
-                Tree::Leaf { value, .. } => {
-                    self.current_value = Some(value);
-                    break;
+                Ok((count, 1 + left_height.max(right_height)))
+            }
+            View::Pruned { .. } => unreachable!("deserialized trees are not pruned"),
+        }
+    }
+
+    /// Recomputes the hash of every node of the tree, the children before their parent,
+    /// and fails on the first one which does not match the stored hash.
+    /// The tree lies `depth` levels below the root, at the given `index` (see `Error::HashMismatch`).
+    pub fn check_hashes(
+        self,
+        algorithm: &'static Algorithm,
+        depth: usize,
+        index: usize,
+    ) -> Result<(), Error>
+    where
+        T: Hashable,
+    {
+
+        let (hash, expected) = match self.view() {
+            View::Empty { hash } => {
+                if depth > 0 {
+                    return Err(Error::MalformedTree("empty subtree below the root"));
                 }
+
+                (hash, algorithm.hash_empty())
             }
+
+            View::Leaf { hash, value } => (hash, algorithm.hash_leaf(value)),
+
+            View::Node { hash, left, right } => {
+                left.check_hashes(algorithm, depth + 1, index << 1)?;
+                right.check_hashes(algorithm, depth + 1, (index << 1) | 1)?;
+
+                (hash, algorithm.hash_nodes(left.hash(), right.hash()))
+            }
+
+            View::Pruned { .. } => unreachable!("deserialized trees are not pruned"),
+        };
+
+        if hash.as_slice() != expected.as_ref() {
+            return Err(Error::HashMismatch {
+                depth: depth,
+                index: index,
+            });
         }
+
+        Ok(())
     }
 }
 
-impl<T> Iterator for LeavesIntoIterator<T> {
-    type Item = T;
+/// Serialized as the nested enum the nodes of a tree used to be, with the `Empty`, `Leaf`
+/// and `Node` variants. Pruned subtrees cannot be serialized.
+#[cfg(feature = "serialization-serde")]
+impl<'a, T: Serialize> Serialize for Subtree<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.view() {
+            View::Empty { hash } => {
+                let mut node = serializer.serialize_struct_variant("Tree", 0, "Empty", 1)?;
+                node.serialize_field("hash", &HashRef(hash))?;
+                node.end()
+            }
 
-    fn next(&mut self) -> Option<T> {
-        let result = self.current_value.take();
+            View::Leaf { hash, value } => {
+                let mut node = serializer.serialize_struct_variant("Tree", 1, "Leaf", 2)?;
+                node.serialize_field("hash", &HashRef(hash))?;
+                node.serialize_field("value", value)?;
+                node.end()
+            }
 
-        if let Some(rest) = self.right_nodes.pop() {
-            self.add_left(rest);
+            View::Node { hash, left, right } => {
+                let mut node = serializer.serialize_struct_variant("Tree", 2, "Node", 3)?;
+                node.serialize_field("hash", &HashRef(hash))?;
+                node.serialize_field("left", &left)?;
+                node.serialize_field("right", &right)?;
+                node.end()
+            }
+
+            View::Pruned { .. } => Err(S::Error::custom("cannot serialize a pruned subtree")),
         }
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<T: Serialize> Serialize for Tree<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.root().serialize(serializer)
+    }
+}
+
+/// The serialized form of a `Tree`, see `Subtree`.
+#[cfg(feature = "serialization-serde")]
+#[derive(Deserialize)]
+#[serde(rename = "Tree")]
+enum TreeData<T> {
+    Empty {
+        #[serde(with = "::hash_encoding")]
+        hash: Vec<u8>,
+    },
+
+    Leaf {
+        #[serde(with = "::hash_encoding")]
+        hash: Vec<u8>,
+        value: T,
+    },
+
+    Node {
+        #[serde(with = "::hash_encoding")]
+        hash: Vec<u8>,
+        left: Box<TreeData<T>>,
+        right: Box<TreeData<T>>,
+    },
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<T> TreeData<T> {
+    /// Adds the nodes of this tree to `tree`, the children before their parent.
+    fn push_into(self, tree: &mut Tree<T>) -> NodeId {
+        match self {
+            TreeData::Empty { hash } => tree.push_empty(SmallHash::from(hash)),
+
+            TreeData::Leaf { hash, value } => {
+                tree.push_leaf(Leaf {
+                    hash: SmallHash::from(hash),
+                    value: value,
+                })
+            }
+
+            TreeData::Node { hash, left, right } => {
+                let left = left.push_into(tree);
+                let right = right.push_into(tree);
 
-        result
+                tree.push_node(SmallHash::from(hash), left, right)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serialization-serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Tree<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = TreeData::deserialize(deserializer)?;
+
+        let mut tree = Tree::with_capacity(1);
+        data.push_into(&mut tree);
+
+        Ok(tree)
+    }
+}
+
+/// An borrowing iterator over the leaves of a `Tree`.
+#[allow(missing_debug_implementations)]
+pub struct LeavesIterator<'a, T>
+where
+    T: 'a,
+{
+    nodes: slice::Iter<'a, Slot<T>>,
+}
+
+impl<'a, T> Iterator for LeavesIterator<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.nodes.by_ref().filter_map(|slot| match *slot {
+            Slot::Leaf { ref value, .. } => Some(value),
+            _ => None,
+        }).next()
+    }
+}
+
+/// A borrowing iterator over the hashes of the leaves of a `Tree`, from left to right,
+/// as returned by `MerkleTree::iter_leaf_hashes`.
+#[allow(missing_debug_implementations)]
+pub struct LeafHashes<'a, T>
+where
+    T: 'a,
+{
+    nodes: slice::Iter<'a, Slot<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for LeafHashes<'a, T> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let hash = self.nodes.by_ref().filter_map(|slot| match *slot {
+            Slot::Leaf { ref hash, .. } => Some(hash.as_slice()),
+            _ => None,
+        }).next()?;
+
+        self.remaining -= 1;
+        Some(hash)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for LeafHashes<'a, T> {
+    fn next_back(&mut self) -> Option<&'a [u8]> {
+        let hash = self.nodes.by_ref().rev().filter_map(|slot| match *slot {
+            Slot::Leaf { ref hash, .. } => Some(hash.as_slice()),
+            _ => None,
+        }).next()?;
+
+        self.remaining -= 1;
+        Some(hash)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for LeafHashes<'a, T> {}
+
+/// An iterator over the leaves of a `Tree`.
+#[allow(missing_debug_implementations)]
+pub struct LeavesIntoIterator<T> {
+    nodes: vec::IntoIter<Slot<T>>,
+}
+
+impl<T> Iterator for LeavesIntoIterator<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.nodes.by_ref().filter_map(|slot| match slot {
+            Slot::Leaf { value, .. } => Some(value),
+            _ => None,
+        }).next()
     }
 }
 
@@ -181,6 +1287,6 @@ impl<T> IntoIterator for Tree<T> {
     type IntoIter = LeavesIntoIterator<T>;
 
     fn into_iter(self) -> Self::IntoIter {
-        LeavesIntoIterator::new(self)
+        LeavesIntoIterator { nodes: self.nodes.into_iter() }
     }
 }
@@ -0,0 +1,115 @@
+#![deny(clippy::indexing_slicing, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use error::Error;
+use hash::AlgorithmTag;
+use merkletree::MerkleTree;
+use wire::Reader;
+
+/// The latest version of the encoding of `TreeHead::to_signable_bytes`.
+pub const TREE_HEAD_VERSION: u8 = 1;
+
+/// What a tree commits to: its hashing algorithm, its number of leaves and its root hash,
+/// as returned by `MerkleTree::tree_head`, to be signed in the canonical form given by
+/// `TreeHead::to_signable_bytes`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
+pub struct TreeHead {
+    /// The hashing algorithm of the tree
+    pub algorithm: AlgorithmTag,
+
+    /// The number of leaves of the tree
+    pub tree_size: u64,
+
+    /// The root hash of the tree
+    #[cfg_attr(feature = "serialization-serde", serde(with = "::hash_encoding"))]
+    pub root_hash: Vec<u8>,
+}
+
+impl TreeHead {
+    /// Returns the canonical encoding of this tree head, which is what is to be signed,
+    /// so that signers and verifiers agree on the bytes the signature covers.
+    ///
+    /// All integers are big-endian. A tree head is laid out as follows:
+    ///
+    /// | Size | Contents                                                     |
+    /// |------|--------------------------------------------------------------|
+    /// | 1    | the format version, currently `TREE_HEAD_VERSION`            |
+    /// | 1    | the algorithm tag, see `wire::algorithm_tag`                 |
+    /// | 8    | the tree size                                                |
+    /// | 1    | the hash length `h`, ie. the algorithm output length         |
+    /// | `h`  | the root hash                                                |
+    ///
+    /// This encoding will never change without its version being bumped.
+    pub fn to_signable_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(11 + self.root_hash.len());
+
+        bytes.push(TREE_HEAD_VERSION);
+        bytes.push(self.algorithm.to_byte());
+        bytes.extend_from_slice(&self.tree_size.to_be_bytes());
+        bytes.push(self.root_hash.len() as u8);
+        bytes.extend_from_slice(&self.root_hash);
+
+        bytes
+    }
+
+    /// Decodes a tree head encoded by `TreeHead::to_signable_bytes`.
+    ///
+    /// Fails with `Error::UnsupportedVersion` if it was encoded with a newer version than
+    /// `TREE_HEAD_VERSION`, with `Error::UnknownAlgorithm` if its algorithm tag is unknown,
+    /// with `Error::HashLengthMismatch` if its hash length is not the output length of that
+    /// algorithm, with `Error::TruncatedInput` or `Error::TrailingBytes` if it is shorter
+    /// or longer than its hash length tells, and with `Error::Decode` for version `0`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader { bytes: bytes };
+
+        match reader.byte()? {
+            0 => return Err(Error::Decode("tree head version 0 does not exist".to_string())),
+            version if version > TREE_HEAD_VERSION => {
+                return Err(Error::UnsupportedVersion {
+                    found: u32::from(version),
+                    max_supported: u32::from(TREE_HEAD_VERSION),
+                });
+            }
+            _ => {}
+        }
+
+        let tag = reader.byte()?;
+        let algorithm = AlgorithmTag::from_byte(tag).ok_or(Error::UnknownAlgorithm(tag))?;
+
+        let mut size = [0; 8];
+        size.copy_from_slice(reader.take(8)?);
+
+        let hash_len = reader.byte()? as usize;
+        let expected = algorithm.algorithm().output_len;
+
+        if hash_len != expected {
+            return Err(Error::HashLengthMismatch {
+                expected: expected,
+                found: hash_len,
+            });
+        }
+
+        let root_hash = reader.take(hash_len)?.to_vec();
+
+        if !reader.bytes.is_empty() {
+            return Err(Error::TrailingBytes);
+        }
+
+        Ok(TreeHead {
+            algorithm: algorithm,
+            tree_size: u64::from_be_bytes(size),
+            root_hash: root_hash,
+        })
+    }
+}
+
+impl<T> MerkleTree<T> {
+    /// Returns the head of this tree, to be signed in the form of `TreeHead::to_signable_bytes`.
+    pub fn tree_head(&self) -> TreeHead {
+        TreeHead {
+            algorithm: AlgorithmTag::of(self.algorithm),
+            tree_size: self.count() as u64,
+            root_hash: self.root_hash().clone(),
+        }
+    }
+}
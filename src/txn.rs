@@ -0,0 +1,127 @@
+use error::Error;
+use hashutils::Hashable;
+use merkletree::MerkleTree;
+
+/// Changes to a `MerkleTree` staged by `MerkleTree::transaction`, which only reach the tree
+/// once committed with `TreeTxn::commit`.
+///
+/// The changes are made to a copy of the tree, taken when the first of them is staged, so that
+/// the tree itself, still readable with `TreeTxn::base`, keeps its state until the transaction
+/// is committed, and `TreeTxn::projected_root` is the root hash it then gets. Dropping the
+/// transaction without committing it rolls the changes back, as `TreeTxn::rollback` does.
+#[derive(Debug)]
+pub struct TreeTxn<'a, T: 'a> {
+    /// The tree the changes are committed to
+    tree: &'a mut MerkleTree<T>,
+
+    /// The tree with the staged changes, if any
+    staged: Option<MerkleTree<T>>,
+}
+
+impl<T> MerkleTree<T> {
+    /// Starts a transaction, whose changes are staged, to be inspected before they are
+    /// committed to this tree or rolled back, see `TreeTxn`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::MerkleTree;
+    /// # fn main() {
+    /// let mut tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["a", "b", "c"]);
+    /// let root_hash = tree.root_hash().clone();
+    ///
+    /// let mut txn = tree.transaction();
+    /// txn.update(1, "B").unwrap();
+    /// txn.push("d").unwrap();
+    ///
+    /// let projected_root = txn.projected_root();
+    /// assert_eq!(txn.base().root_hash(), &root_hash);
+    ///
+    /// txn.commit();
+    /// assert_eq!(tree.root_hash(), &projected_root);
+    /// # }
+    /// ```
+    pub fn transaction(&mut self) -> TreeTxn<T> {
+        TreeTxn {
+            tree: self,
+            staged: None,
+        }
+    }
+}
+
+impl<'a, T> TreeTxn<'a, T> {
+    /// Returns the tree the transaction was started on, without the staged changes.
+    pub fn base(&self) -> &MerkleTree<T> {
+        self.tree
+    }
+
+    /// Returns the tree with the staged changes.
+    fn current(&self) -> &MerkleTree<T> {
+        self.staged.as_ref().unwrap_or(self.tree)
+    }
+
+    /// Returns the tree with the staged changes, copying the tree on the first change.
+    fn staged(&mut self) -> &mut MerkleTree<T>
+    where
+        T: Clone,
+    {
+
+        let tree = &*self.tree;
+        self.staged.get_or_insert_with(|| tree.clone())
+    }
+
+    /// Returns the number of leaves of the tree with the staged changes.
+    pub fn count(&self) -> usize {
+        self.current().count()
+    }
+
+    /// Returns the value of the leaf at the given `index` of the tree with the staged changes,
+    /// or `None` if there is no such leaf or if it is pruned.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let tree = self.current();
+        tree.tree().root().nth_value(index, tree.count())
+    }
+
+    /// Returns the root hash the tree gets if the transaction is committed.
+    pub fn projected_root(&self) -> Vec<u8> {
+        self.current().root_hash().clone()
+    }
+
+    /// Stages appending the given value, as `MerkleTree::try_push` does.
+    pub fn push(&mut self, value: T) -> Result<bool, Error>
+    where
+        T: Hashable + Clone,
+    {
+
+        self.staged().try_push(value)
+    }
+
+    /// Stages replacing the value of the leaf at the given `index`, as `MerkleTree::update` does.
+    pub fn update(&mut self, index: usize, value: T) -> Result<T, Error>
+    where
+        T: Hashable + Clone,
+    {
+
+        self.staged().update(index, value)
+    }
+
+    /// Stages removing the value at the given `index`, as `MerkleTree::remove` does.
+    pub fn remove(&mut self, index: usize) -> Result<T, Error>
+    where
+        T: Clone,
+    {
+
+        self.staged().remove(index)
+    }
+
+    /// Applies the staged changes to the tree.
+    pub fn commit(self) {
+        if let Some(staged) = self.staged {
+            *self.tree = staged;
+        }
+    }
+
+    /// Drops the staged changes, leaving the tree as it was.
+    pub fn rollback(self) {}
+}
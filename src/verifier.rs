@@ -0,0 +1,192 @@
+
+use std::collections::{BTreeMap, HashMap};
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
+
+use digest::Algorithm;
+
+use hash::AlgorithmTag;
+use hasher::{self, MerkleHasher};
+use options::HashingScheme;
+use proof::{Proof, MAX_TREE_DEPTH};
+
+/// The counters of a `CachingVerifier`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VerifierStats {
+    /// The number of node hashes found in the cache.
+    pub hits: u64,
+
+    /// The number of node hashes which had to be computed.
+    pub misses: u64,
+
+    /// The number of cached node hashes dropped to make room for other ones.
+    pub evictions: u64,
+}
+
+impl VerifierStats {
+    /// Returns the share of the node hashes found in the cache, between `0` and `1`,
+    /// or `0` if no node hash has been looked up yet.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// The hashing scheme and the hashes of the children of a node.
+type Children = (HashingScheme, Vec<u8>, Vec<u8>);
+
+/// Checks inclusion proofs against a single root hash as they come, like `Proof::validate`,
+/// remembering the hash of the nodes it computes, so that the upper levels shared by
+/// successive proofs are only hashed once.
+///
+/// A node hash is cached along with the hashes of both children it was computed from, and
+/// the hashing scheme, so that it is only ever reused for the very same children: a proof is
+/// accepted if and only if `Proof::validate_with` accepts it given the hasher of the verifier.
+/// The cache holds up to `capacity` node hashes, dropping the least recently used ones.
+#[derive(Clone, Debug)]
+pub struct CachingVerifier {
+    algorithm: &'static Algorithm,
+    hasher: Option<Arc<dyn MerkleHasher>>,
+    root_hash: Vec<u8>,
+    capacity: usize,
+    nodes: HashMap<Children, (Vec<u8>, u64)>,
+    by_use: BTreeMap<u64, Children>,
+    clock: u64,
+    stats: VerifierStats,
+}
+
+// The hasher is only ever called through shared references, and keeps no state of the verifier.
+impl UnwindSafe for CachingVerifier {}
+impl RefUnwindSafe for CachingVerifier {}
+
+impl CachingVerifier {
+    /// Creates a verifier of the proofs of trees hashed with `algorithm` whose root hash
+    /// is `root_hash`, caching up to `capacity` node hashes.
+    pub fn new(algorithm: &'static Algorithm, root_hash: Vec<u8>, capacity: usize) -> Self {
+        CachingVerifier {
+            algorithm: algorithm,
+            hasher: None,
+            root_hash: root_hash,
+            capacity: capacity,
+            nodes: HashMap::new(),
+            by_use: BTreeMap::new(),
+            clock: 0,
+            stats: VerifierStats::default(),
+        }
+    }
+
+    /// Works like `CachingVerifier::new`, for the proofs of trees hashed with the given `hasher`,
+    /// see `MerkleTreeBuilder::hasher`, whatever hasher the proofs themselves hold.
+    pub fn with_hasher<H>(hasher: H, root_hash: Vec<u8>, capacity: usize) -> Self
+    where
+        H: MerkleHasher + 'static,
+    {
+
+        let mut verifier = CachingVerifier::new(hasher.algorithm(), root_hash, capacity);
+        verifier.hasher = Some(Arc::new(hasher));
+        verifier
+    }
+
+    /// Returns the root hash the proofs are checked against.
+    pub fn root_hash(&self) -> &[u8] {
+        &self.root_hash
+    }
+
+    /// Returns the counters of the cache.
+    pub fn stats(&self) -> VerifierStats {
+        self.stats
+    }
+
+    /// Checks whether the given inclusion proof is valid for the root hash of this verifier,
+    /// as `Proof::validate` does. Proofs computed with another algorithm are rejected.
+    pub fn validate<T>(&mut self, proof: &Proof<T>) -> bool {
+        if AlgorithmTag::of(proof.algorithm) != AlgorithmTag::of(self.algorithm) {
+            return false;
+        }
+
+        if proof.root_hash != self.root_hash || proof.lemma.node_hash != self.root_hash {
+            return false;
+        }
+
+        if proof.lemma.depth_exceeds(MAX_TREE_DEPTH) || proof.check_algorithm().is_err() {
+            return false;
+        }
+
+        let mut lemma = &proof.lemma;
+
+        loop {
+            match (&lemma.sub_lemma, &lemma.sibling_hash) {
+                (&None, &None) => return true,
+
+                (&Some(ref sub), &Some(ref sibling)) => {
+                    let (left, right) = if sibling.is_left() {
+                        (sibling.as_ref().into_inner(), &sub.node_hash)
+                    } else {
+                        (&sub.node_hash, sibling.as_ref().into_inner())
+                    };
+
+                    let children = (proof.scheme, left.clone(), right.clone());
+
+                    if self.node_hash(children) != lemma.node_hash {
+                        return false;
+                    }
+
+                    lemma = sub;
+                }
+
+                _ => return false,
+            }
+        }
+    }
+
+    /// Returns the hash of the node with the given children, from the cache if it is there.
+    fn node_hash(&mut self, children: Children) -> Vec<u8> {
+        self.clock += 1;
+        let clock = self.clock;
+
+        if let Some(&mut (ref hash, ref mut last_used)) = self.nodes.get_mut(&children) {
+            let children = self.by_use.remove(last_used).expect("every cached node is in use order");
+            self.by_use.insert(clock, children);
+            *last_used = clock;
+            self.stats.hits += 1;
+
+            return hash.clone();
+        }
+
+        self.stats.misses += 1;
+
+        let hash: Vec<u8> = {
+            let (scheme, ref left, ref right) = children;
+            let hasher = hasher::or_algorithm(&self.hasher, &self.algorithm);
+            scheme.hash_nodes_with(hasher, left, right).as_ref().into()
+        };
+
+        if self.capacity == 0 {
+            return hash;
+        }
+
+        if self.nodes.len() >= self.capacity {
+            self.evict();
+        }
+
+        self.by_use.insert(clock, children.clone());
+        self.nodes.insert(children, (hash.clone(), clock));
+
+        hash
+    }
+
+    /// Drops the least recently used node hash.
+    fn evict(&mut self) {
+        let oldest = self.by_use.keys().next().cloned();
+
+        if let Some(children) = oldest.and_then(|clock| self.by_use.remove(&clock)) {
+            self.nodes.remove(&children);
+            self.stats.evictions += 1;
+        }
+    }
+}
@@ -0,0 +1,165 @@
+#![deny(clippy::indexing_slicing, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+#[cfg(feature = "serialization-serde")]
+use std::str;
+
+use digest::Algorithm;
+
+#[cfg(feature = "serialization-serde")]
+use serde_json::{self, Value};
+
+#[cfg(feature = "serialization-serde")]
+use envelope::ProofEnvelope;
+use error::Error;
+#[cfg(feature = "serialization-serde")]
+use hash::AlgorithmTag;
+#[cfg(feature = "serialization-serde")]
+use options::HashingScheme;
+use hash::RootHash;
+use proof::{Proof, MAX_TREE_DEPTH};
+
+/// The encodings of a proof accepted by `verify_proof_bytes`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProofFormat {
+    /// JSON, as read by `verify_json_proof`
+    #[cfg(feature = "serialization-serde")]
+    Json,
+
+    /// The binary format described in the `wire` module, see `Proof::from_bytes`
+    Wire,
+
+    /// Protobuf, see `Proof::parse_protobuf`
+    #[cfg(any(feature = "serialization-protobuf", feature = "serialization-prost"))]
+    Protobuf,
+}
+
+/// Checks that `json` holds a valid proof that its value is part of the tree whose root hash
+/// is `expected_root`, built with the given hashing `algorithm`.
+///
+/// The proof is read as a `ProofEnvelope`, so either as an envelope or as a bare `ProofData`,
+/// and its value must be a string or an array of bytes, which is hashed as the corresponding
+/// `String` or `Vec<u8>` leaf would be. If the proof names its algorithm, it must be `algorithm`,
+/// and if it names its hashing scheme, it must be the default `HashingScheme::DomainSeparated`,
+/// which proofs are read as hashed with otherwise: the proof is rejected with
+/// `Error::SchemeMismatch` before anything is hashed.
+///
+/// Fails with the error of the first check which does not pass, see `verify_proof_bytes`.
+#[cfg(feature = "serialization-serde")]
+pub fn verify_json_proof(algorithm: &'static Algorithm, json: &str, expected_root: &[u8]) -> Result<(), Error> {
+    let json = serde_json::from_str(json).map_err(|err| Error::Decode(err.to_string()))?;
+
+    // Deserializing a deeper lemma chain fails, with a less telling error
+    if lemma_depth_exceeds(&json, MAX_TREE_DEPTH) {
+        return Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"));
+    }
+
+    let data = ProofEnvelope::<Value>::from_json(json)?.proof;
+
+    if data.algorithm.map_or(false, |tag| tag != AlgorithmTag::of(algorithm)) {
+        return Err(Error::AlgorithmMismatch);
+    }
+
+    if data.scheme != HashingScheme::default() {
+        return Err(Error::SchemeMismatch {
+            proof: data.scheme,
+            verifier: HashingScheme::default(),
+        });
+    }
+
+    let value = match data.value {
+        Value::String(ref value) => value.as_bytes().to_vec(),
+
+        Value::Array(ref bytes) => {
+            bytes
+                .iter()
+                .map(|byte| byte.as_u64().filter(|&byte| byte <= 0xff).map(|byte| byte as u8))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| Error::Decode("value is not an array of bytes".to_string()))?
+        }
+
+        _ => return Err(Error::Decode("value is neither a string nor an array of bytes".to_string())),
+    };
+
+    let mut proof = Proof::new(algorithm, data.root_hash, data.lemma, value);
+    proof.tree_size = data.tree_size;
+    proof.leaf_index = data.leaf_index;
+    proof.leaf_counts = data.leaf_counts;
+
+    verify(proof, expected_root)
+}
+
+/// Returns whether the lemma of the given JSON proof, or envelope, has more than `max` nested
+/// sub lemmas, as `Lemma::depth_exceeds` does once decoded.
+#[cfg(feature = "serialization-serde")]
+fn lemma_depth_exceeds(json: &Value, max: usize) -> bool {
+    let proof = json.get("proof").unwrap_or(json);
+    let mut lemma = proof.get("lemma");
+    let mut depth = 0;
+
+    while let Some(sub_lemma) = lemma.and_then(|lemma| lemma.get("sub_lemma")).filter(|sub| !sub.is_null()) {
+        depth += 1;
+
+        if depth > max {
+            return true;
+        }
+
+        lemma = Some(sub_lemma);
+    }
+
+    false
+}
+
+/// Checks that `bytes` holds a valid proof, in the given `format`, that its value is part of
+/// the tree whose root hash is `expected_root`, built with the given hashing `algorithm`.
+///
+/// Once decoded, the proof is rejected:
+///
+/// - with `Error::InvalidPath` if its lemma chain is deeper than `MAX_TREE_DEPTH`;
+/// - with `Error::HashLengthMismatch` if one of its hashes, or `expected_root`,
+///   does not have the output length of `algorithm`;
+/// - with `Error::InvalidProof` if the hash of its value is not its leaf hash,
+///   or if it does not hold for `expected_root`.
+///
+/// Decoding errors are those of `Proof::from_bytes`, `Proof::parse_protobuf`
+/// and `verify_json_proof`.
+pub fn verify_proof_bytes(
+    algorithm: &'static Algorithm,
+    bytes: &[u8],
+    format: ProofFormat,
+    expected_root: &[u8],
+) -> Result<(), Error> {
+
+    let proof = match format {
+        #[cfg(feature = "serialization-serde")]
+        ProofFormat::Json => {
+            let json = str::from_utf8(bytes).map_err(|err| Error::Decode(err.to_string()))?;
+            return verify_json_proof(algorithm, json, expected_root);
+        }
+
+        ProofFormat::Wire => Proof::from_bytes(algorithm, bytes)?,
+
+        #[cfg(any(feature = "serialization-protobuf", feature = "serialization-prost"))]
+        ProofFormat::Protobuf => Proof::parse_protobuf(algorithm, bytes)?,
+    };
+
+    verify(proof, expected_root)
+}
+
+/// Checks a decoded proof, see `verify_proof_bytes`.
+fn verify(proof: Proof<Vec<u8>>, expected_root: &[u8]) -> Result<(), Error> {
+    if proof.lemma.depth_exceeds(MAX_TREE_DEPTH) {
+        return Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"));
+    }
+
+    let root = RootHash::new(proof.algorithm, expected_root.to_vec())?;
+
+    proof.check_algorithm()?;
+
+    let leaf_hash = proof.scheme.hash_leaf(proof.algorithm, &proof.value);
+
+    if leaf_hash.as_ref() != proof.lemma.leaf_hash() {
+        return Err(Error::InvalidProof);
+    }
+
+    proof.verify(&root)
+}
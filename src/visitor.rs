@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+
+use tree::{Tree, Subtree, View};
+
+/// The order in which `MerkleTree::walk` visits the nodes of a tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TraversalOrder {
+    /// Every node is visited before its children, the left one first
+    PreOrder,
+
+    /// Every node is visited after its children, the left one first
+    PostOrder,
+
+    /// The nodes are visited level by level from the root down, each from left to right
+    LevelOrder,
+}
+
+/// Callbacks run by `MerkleTree::walk` at each node of a tree.
+///
+/// Depths are counted from the root, at depth `0`, and leaves are given their index in the tree.
+/// `Empty` nodes, which pad trees of a fixed depth and stand for the copies of odd nodes,
+/// are not visited, but their hash is given as the one of a child of their parent.
+/// The subtrees pruned by `MerkleTree::prune_below` are visited as a whole.
+pub trait TreeVisitor<T> {
+    /// Visits an internal node, given its hash and the ones of its children.
+    ///
+    /// Returning `ControlFlow::Break(())` prunes the walk: with `TraversalOrder::PreOrder` and
+    /// `TraversalOrder::LevelOrder`, the nodes below this one are skipped, and with
+    /// `TraversalOrder::PostOrder`, where they have already been visited, the walk ends.
+    fn visit_internal(&mut self, depth: usize, hash: &[u8], left_hash: &[u8], right_hash: &[u8]) -> ControlFlow<()>;
+
+    /// Visits a leaf, given its hash and its value.
+    fn visit_leaf(&mut self, depth: usize, index: usize, hash: &[u8], value: &T);
+
+    /// Visits a pruned subtree, given its hash and its number of leaves. Does nothing by default.
+    fn visit_pruned(&mut self, _depth: usize, _hash: &[u8], _leaves: usize) {}
+}
+
+/// Walks the given tree in the given `order`, see `MerkleTree::walk`.
+pub(crate) fn walk<T, V>(tree: &Tree<T>, visitor: &mut V, order: TraversalOrder)
+where
+    V: TreeVisitor<T> + ?Sized,
+{
+
+    // The leaves below pruned nodes are skipped, so their index is given by their position
+    let ranks = tree.leaf_ranks();
+
+    match order {
+        TraversalOrder::PreOrder => {
+            let mut stack = vec![(tree.root(), 0)];
+
+            while let Some((node, depth)) = stack.pop() {
+                if let Some((left, right)) = visit(&ranks, visitor, node, depth) {
+                    stack.push((right, depth + 1));
+                    stack.push((left, depth + 1));
+                }
+            }
+        }
+
+        TraversalOrder::PostOrder => {
+            // Nodes are pushed once to expand them, then once more to visit them
+            let mut stack = vec![(tree.root(), 0, false)];
+
+            while let Some((node, depth, expanded)) = stack.pop() {
+                match node.view() {
+                    View::Node { left, right, .. } if !expanded => {
+                        stack.push((node, depth, true));
+                        stack.push((right, depth + 1, false));
+                        stack.push((left, depth + 1, false));
+                    }
+
+                    // The children have been visited already, so breaking ends the walk
+                    View::Node { hash, left, right } => {
+                        if visitor.visit_internal(depth, hash, left.hash(), right.hash()).is_break() {
+                            return;
+                        }
+                    }
+
+                    _ => {
+                        visit(&ranks, visitor, node, depth);
+                    }
+                }
+            }
+        }
+
+        TraversalOrder::LevelOrder => {
+            let mut queue = VecDeque::new();
+            queue.push_back((tree.root(), 0));
+
+            while let Some((node, depth)) = queue.pop_front() {
+                if let Some((left, right)) = visit(&ranks, visitor, node, depth) {
+                    queue.push_back((left, depth + 1));
+                    queue.push_back((right, depth + 1));
+                }
+            }
+        }
+    }
+}
+
+/// Visits the given node, returning its children if the walk goes on below it.
+fn visit<'a, T, V>(
+    ranks: &[usize],
+    visitor: &mut V,
+    node: Subtree<'a, T>,
+    depth: usize,
+) -> Option<(Subtree<'a, T>, Subtree<'a, T>)>
+where
+    V: TreeVisitor<T> + ?Sized,
+{
+
+    match node.view() {
+        View::Empty { .. } => None,
+
+        View::Leaf { hash, value } => {
+            visitor.visit_leaf(depth, ranks[node.position()], hash, value);
+            None
+        }
+
+        View::Pruned { hash, leaves } => {
+            visitor.visit_pruned(depth, hash, leaves);
+            None
+        }
+
+        View::Node { hash, left, right } => {
+            match visitor.visit_internal(depth, hash, left.hash(), right.hash()) {
+                ControlFlow::Continue(()) => Some((left, right)),
+                ControlFlow::Break(()) => None,
+            }
+        }
+    }
+}
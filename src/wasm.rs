@@ -0,0 +1,36 @@
+//! Proof verification for JavaScript, through `wasm-bindgen`.
+//!
+//! The proofs are expected in the binary format described in the `wire` module.
+//!
+//! Note that *ring* 0.12, which this crate uses for hashing by default,
+//! cannot be compiled for the `wasm32-unknown-unknown` target:
+//! building for the browser requires the `backend-rustcrypto` feature, see the `digest` module.
+
+use wasm_bindgen::prelude::*;
+
+use hex::from_hex;
+use proof::Proof;
+use wire::algorithm_from_tag;
+
+/// Checks whether `proof_bytes` holds a valid proof, with respect to the root hash
+/// given as a hex string in `root_hex`, for the algorithm identified by `algorithm_tag`
+/// (see `wire::algorithm_tag`).
+///
+/// Returns `false` if the algorithm tag is unknown, if `root_hex` is not valid hex,
+/// or if `proof_bytes` cannot be decoded.
+#[wasm_bindgen]
+pub fn verify_proof_bytes(algorithm_tag: u8, proof_bytes: &[u8], root_hex: &str) -> bool {
+    let algorithm = match algorithm_from_tag(algorithm_tag) {
+        Some(algorithm) => algorithm,
+        None => return false,
+    };
+
+    let root_hash = match from_hex(root_hex) {
+        Some(root_hash) => root_hash,
+        None => return false,
+    };
+
+    Proof::from_bytes(algorithm, proof_bytes)
+        .map(|proof| proof.validate(&root_hash))
+        .unwrap_or(false)
+}
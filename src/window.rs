@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+use std::collections::vec_deque;
+use std::sync::OnceLock;
+
+use digest::Algorithm;
+
+use hashutils::{Hashable, HashUtils};
+use proof::{Lemma, Positioned, Proof};
+use tree;
+
+/// A tree over the last `capacity` values pushed to it, the oldest one being evicted
+/// when a value is pushed to a full window, whose root hash is at all times that of
+/// `MerkleTree::from_vec` over the values of the window, from the oldest one to the newest one.
+///
+/// For every level, the tree keeps the hashes of the perfect subtrees of that many leaves
+/// starting at every leaf of the window, about `log2(capacity)` hashes per leaf, so that
+/// a push only hashes one node per level, whichever leaf the window starts from, and the root
+/// hash and the proofs are combined from those subtrees, in `O(log2(capacity))` hashes.
+#[derive(Clone, Debug)]
+pub struct SlidingWindowTree<T> {
+    /// The hashing algorithm of the tree
+    pub algorithm: &'static Algorithm,
+
+    /// The maximum number of leaves of the tree
+    capacity: usize,
+
+    /// The values of the window, from the oldest one to the newest one
+    values: VecDeque<T>,
+
+    /// The hashes of the perfect subtrees of `2^level` leaves at each level, from the one
+    /// starting at the oldest leaf to the one ending at the newest leaf
+    levels: Vec<VecDeque<Vec<u8>>>,
+
+    /// The root hash, computed on the first call to `SlidingWindowTree::root_hash`
+    /// since the last push
+    root_hash: OnceLock<Vec<u8>>,
+}
+
+impl<T> SlidingWindowTree<T> {
+    /// Creates an empty window of at most `capacity` leaves.
+    ///
+    /// Panics if `capacity` is zero.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # extern crate merkle;
+    /// # use merkle::{MerkleTree, SlidingWindowTree};
+    /// # fn main() {
+    /// let mut window = SlidingWindowTree::new(&merkle::digest::SHA256, 3);
+    ///
+    /// for value in vec!["a", "b", "c", "d"] {
+    ///     window.push(value);
+    /// }
+    ///
+    /// let tree = MerkleTree::from_vec(&merkle::digest::SHA256, vec!["b", "c", "d"]);
+    /// assert_eq!(window.root_hash(), tree.root_hash());
+    /// # }
+    /// ```
+    pub fn new(algorithm: &'static Algorithm, capacity: usize) -> Self {
+        assert!(capacity > 0, "the window has no room for a leaf");
+
+        SlidingWindowTree {
+            algorithm: algorithm,
+            capacity: capacity,
+            values: VecDeque::with_capacity(capacity),
+            levels: Vec::new(),
+            root_hash: OnceLock::new(),
+        }
+    }
+
+    /// Returns the maximum number of leaves of the window.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of leaves of the window.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether the window has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns an iterator over the values of the window, from the oldest one to the newest one.
+    pub fn iter(&self) -> vec_deque::Iter<T> {
+        self.values.iter()
+    }
+
+    /// Appends the given value to the window, evicting the oldest value if the window is full,
+    /// which is then returned.
+    pub fn push(&mut self, value: T) -> Option<T>
+    where
+        T: Hashable,
+    {
+
+        // Hashed first, so that a panic while hashing leaves the window untouched
+        let leaf = self.algorithm.hash_leaf(&value);
+
+        let evicted = if self.values.len() == self.capacity {
+            // The subtrees starting at the oldest leaf go along with it
+            for level in &mut self.levels {
+                level.pop_front();
+            }
+
+            self.values.pop_front()
+        } else {
+            None
+        };
+
+        self.values.push_back(value);
+
+        if self.levels.is_empty() {
+            self.levels.push(VecDeque::with_capacity(self.capacity));
+        }
+
+        self.levels[0].push_back(leaf.as_ref().to_vec());
+
+        // Every level gets the subtree ending at the new leaf
+        let count = self.values.len();
+        let mut level = 1;
+
+        while level < usize::BITS as usize && 1 << level <= count {
+            let below = &self.levels[level - 1];
+            let last = below.len() - 1;
+            let hash = self.algorithm.hash_nodes(&below[last - (1 << (level - 1))], &below[last]);
+
+            if self.levels.len() == level {
+                self.levels.push(VecDeque::with_capacity(self.capacity - (1 << level) + 1));
+            }
+
+            self.levels[level].push_back(hash.as_ref().to_vec());
+            level += 1;
+        }
+
+        self.root_hash = OnceLock::new();
+
+        evicted
+    }
+
+    /// Returns the root hash of the window, the same as that of `MerkleTree::from_vec`
+    /// over its values.
+    pub fn root_hash(&self) -> &Vec<u8> {
+        self.root_hash.get_or_init(|| {
+            if self.values.is_empty() {
+                self.algorithm.hash_empty().as_ref().to_vec()
+            } else {
+                self.node_hash(0, self.values.len())
+            }
+        })
+    }
+
+    /// Generates the inclusion proof of the value `offset` leaves after the oldest one,
+    /// the same as `MerkleTree::gen_nth_proof` over the values of the window,
+    /// or `None` if the window has no such leaf.
+    pub fn gen_proof(&self, offset: usize) -> Option<Proof<T>>
+    where
+        T: Clone,
+    {
+
+        let value = self.values.get(offset)?.clone();
+        let lemma = self.lemma(0, self.values.len(), offset);
+
+        let mut proof = Proof::new(self.algorithm, self.root_hash().clone(), lemma, value);
+        proof.tree_size = Some(self.values.len() as u64);
+        proof.leaf_index = Some(offset as u64);
+
+        Some(proof)
+    }
+
+    /// Returns the hash of the node over the `count` leaves of the window from the one
+    /// at `start`, split as `MerkleTree::from_vec` splits them: the left subtree is perfect,
+    /// with as many leaves as the largest power of two below `count`.
+    fn node_hash(&self, start: usize, count: usize) -> Vec<u8> {
+        let level = tree::ceil_log2(count);
+
+        if count.is_power_of_two() {
+            return self.levels[level][start].clone();
+        }
+
+        let half = 1 << (level - 1);
+        let left = &self.levels[level - 1][start];
+        let right = self.node_hash(start + half, count - half);
+
+        self.algorithm.hash_nodes(left, &right).as_ref().to_vec()
+    }
+
+    /// Returns the lemma chain of the leaf at `index` below the node over the `count` leaves
+    /// of the window from the one at `start`.
+    fn lemma(&self, start: usize, count: usize, index: usize) -> Lemma {
+        if count == 1 {
+            return Lemma {
+                node_hash: self.levels[0][start].clone(),
+                sibling_hash: None,
+                sub_lemma: None,
+            };
+        }
+
+        let level = tree::ceil_log2(count);
+        let half = 1 << (level - 1);
+
+        let (sub_lemma, sibling_hash) = if index < half {
+            let sibling = self.node_hash(start + half, count - half);
+            (self.lemma(start, half, index), Positioned::Right(sibling))
+        } else {
+            let sibling = self.levels[level - 1][start].clone();
+            (self.lemma(start + half, count - half, index - half), Positioned::Left(sibling))
+        };
+
+        let node_hash = if count.is_power_of_two() {
+            self.levels[level][start].clone()
+        } else {
+            match sibling_hash {
+                Positioned::Left(ref left) => self.algorithm.hash_nodes(left, &sub_lemma.node_hash),
+                Positioned::Right(ref right) => self.algorithm.hash_nodes(&sub_lemma.node_hash, right),
+            }
+            .as_ref()
+            .to_vec()
+        };
+
+        Lemma {
+            node_hash: node_hash,
+            sibling_hash: Some(sibling_hash),
+            sub_lemma: Some(Box::new(sub_lemma)),
+        }
+    }
+}
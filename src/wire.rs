@@ -0,0 +1,417 @@
+//! A stable, versioned binary format for proofs whose value is a byte string,
+//! produced by `Proof::to_bytes` and parsed by `Proof::from_bytes`.
+//!
+//! All integers are big-endian. A proof is laid out as follows:
+//!
+//! | Size               | Contents                                           |
+//! |--------------------|----------------------------------------------------|
+//! | 4                  | the magic bytes `MAGIC`, ie. `"MKLP"`              |
+//! | 1                  | the format version, currently `VERSION`            |
+//! | 1                  | the algorithm tag, see `algorithm_tag`, along with the `LEAF_COUNTS` bit for the proofs of trees with leaf counts |
+//! | 1                  | the hash length `h`, ie. the algorithm output length |
+//! | 1                  | the depth `d`, ie. the number of sibling hashes    |
+//! | `d * (1 + h)`      | for each level, from the root down to the leaf: a direction byte (`0` if the sibling is on the left, `1` if it is on the right) followed by the sibling hash |
+//! |                    | with the `LEAF_COUNTS` bit, the `leaf_counts` of the proof, from the root down, as `d` unsigned LEB128 varints |
+//! | `h`                | the root hash                                      |
+//! | 4                  | the length `n` of the value                        |
+//! | `n`                | the value                                          |
+//!
+//! The node hashes are not part of the format: they are recomputed from the
+//! value and the sibling hashes when parsing.
+//!
+//! Inputs with a version greater than `VERSION` are rejected with
+//! `Error::UnsupportedVersion`, incomplete inputs with `Error::TruncatedInput`, and proofs
+//! deeper than `MAX_TREE_DEPTH` with `Error::InvalidPath`.
+//! This format will never change without its version being bumped.
+//!
+//! Decoding never panics, whatever the input: this module denies the Clippy lints
+//! for indexing, unwrapping and panicking.
+//!
+//! The sibling hashes are laid out as by `encode_siblings`, which Protobuf messages
+//! written by `Proof::write_compact_protobuf` use as well.
+//!
+//! The compact format of multiproofs is described in `MultiProof::to_compact_bytes`.
+
+#![deny(clippy::indexing_slicing, clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::ptr;
+
+use digest::{self, Algorithm};
+
+use error::Error;
+use hashutils::HashUtils;
+use options::HashingScheme;
+use proof::{Proof, Lemma, Positioned, Direction, MAX_TREE_DEPTH};
+
+/// The magic bytes every encoded proof starts with.
+pub const MAGIC: &'static [u8; 4] = b"MKLP";
+
+/// The latest version of the format, which is the one written by `Proof::to_bytes`.
+pub const VERSION: u8 = 1;
+
+/// The bit set in the algorithm tag of the proofs of trees built with
+/// `MerkleTreeBuilder::leaf_counts`, whose encoding holds the leaf counts of the proof.
+pub const LEAF_COUNTS: u8 = 0x80;
+
+/// The magic bytes every compact multiproof starts with.
+pub const MULTIPROOF_MAGIC: &'static [u8; 4] = b"MKLM";
+
+/// The latest version of the compact multiproof format, which is the one written by
+/// `MultiProof::to_compact_bytes`.
+pub const MULTIPROOF_VERSION: u8 = 1;
+
+/// Returns the tag identifying the given hashing algorithm in encoded proofs:
+///
+/// | Algorithm    | Tag |
+/// |--------------|-----|
+/// | `SHA1`       | 1   |
+/// | `SHA256`     | 2   |
+/// | `SHA384`     | 3   |
+/// | `SHA512`     | 4   |
+/// | `SHA512_256` | 5   |
+#[allow(clippy::expect_used)]
+pub fn algorithm_tag(algorithm: &'static Algorithm) -> u8 {
+    ALGORITHMS
+        .iter()
+        .find(|&&(_, algo)| ptr::eq(algo, algorithm))
+        .map(|&(tag, _)| tag)
+        .expect("every ring digest algorithm has a tag")
+}
+
+/// Returns the hashing algorithm identified by the given tag, if any.
+pub fn algorithm_from_tag(tag: u8) -> Option<&'static Algorithm> {
+    ALGORITHMS.iter().find(|&&(t, _)| t == tag).map(|&(_, algo)| algo)
+}
+
+/// Returns the tag identifying the given hashing scheme in encoded proofs: `0` if domain
+/// separated, `1` if unprefixed, `2` if sorted pairs.
+pub fn scheme_tag(scheme: HashingScheme) -> u8 {
+    match scheme {
+        HashingScheme::DomainSeparated => 0,
+        HashingScheme::Unprefixed => 1,
+        HashingScheme::SortedPairs => 2,
+    }
+}
+
+/// Returns the hashing scheme identified by the given tag, if any.
+pub fn scheme_from_tag(tag: u8) -> Option<HashingScheme> {
+    match tag {
+        0 => Some(HashingScheme::DomainSeparated),
+        1 => Some(HashingScheme::Unprefixed),
+        2 => Some(HashingScheme::SortedPairs),
+        _ => None,
+    }
+}
+
+static ALGORITHMS: [(u8, &'static Algorithm); 5] = [
+    (1, &digest::SHA1),
+    (2, &digest::SHA256),
+    (3, &digest::SHA384),
+    (4, &digest::SHA512),
+    (5, &digest::SHA512_256),
+];
+
+impl<T> Proof<T> {
+    /// Encodes this proof in the binary format described in the `wire` module.
+    ///
+    /// Panics if the lemma chain has more than 255 levels, which no tree can produce,
+    /// if the value is longer than `u32::MAX` bytes, or if the proof has `leaf_counts`
+    /// but not one per sibling hash.
+    pub fn to_bytes(&self) -> Vec<u8>
+    where
+        T: AsRef<[u8]>,
+    {
+
+        let siblings = self.siblings().collect::<Vec<_>>();
+
+        assert!(siblings.len() <= 255, "lemma chain is too deep to be encoded");
+
+        let value = self.value.as_ref();
+        assert!(value.len() <= u32::MAX as usize, "value is too long to be encoded");
+
+        let hash_len = self.algorithm.output_len;
+        let mut tag = algorithm_tag(self.algorithm);
+
+        if let Some(ref counts) = self.leaf_counts {
+            assert!(counts.len() == siblings.len(), "one leaf count per sibling hash");
+            tag |= LEAF_COUNTS;
+        }
+
+        let mut bytes = Vec::with_capacity(
+            8 + siblings.len() * (1 + hash_len) + hash_len + 4 + value.len(),
+        );
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(tag);
+        bytes.push(hash_len as u8);
+        bytes.push(siblings.len() as u8);
+
+        for sibling in siblings {
+            write_sibling(&mut bytes, sibling);
+        }
+
+        for &count in self.leaf_counts.iter().flatten() {
+            write_varint(&mut bytes, count);
+        }
+
+        bytes.extend_from_slice(&self.root_hash);
+        bytes.extend_from_slice(&u32_to_bytes(value.len() as u32));
+        bytes.extend_from_slice(value);
+
+        bytes
+    }
+}
+
+impl Proof<Vec<u8>> {
+    /// Decodes a proof in the binary format described in the `wire` module,
+    /// which must have been produced with the given hashing `algorithm`.
+    pub fn from_bytes(algorithm: &'static Algorithm, bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader { bytes: bytes };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+
+        let version = reader.byte()?;
+
+        if version > VERSION {
+            return Err(Error::UnsupportedVersion {
+                found: u32::from(version),
+                max_supported: u32::from(VERSION),
+            });
+        }
+
+        let tag = reader.byte()?;
+        check_algorithm_tag(algorithm, tag & !LEAF_COUNTS)?;
+
+        let hash_len = reader.byte()? as usize;
+
+        if hash_len != algorithm.output_len {
+            return Err(Error::HashLengthMismatch {
+                expected: algorithm.output_len,
+                found: hash_len,
+            });
+        }
+
+        let depth = reader.byte()? as usize;
+
+        if depth > MAX_TREE_DEPTH {
+            return Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"));
+        }
+
+        let mut siblings = Vec::with_capacity(depth);
+
+        for _ in 0..depth {
+            siblings.push(reader.sibling(hash_len)?);
+        }
+
+        let leaf_counts = if tag & LEAF_COUNTS == 0 {
+            None
+        } else {
+            Some((0..depth).map(|_| reader.varint()).collect::<Result<Vec<_>, _>>()?)
+        };
+
+        let root_hash = reader.take(hash_len)?.to_vec();
+        let value_len = u32_from_bytes(reader.take(4)?) as usize;
+        let value = reader.take(value_len)?.to_vec();
+
+        if !reader.bytes.is_empty() {
+            return Err(Error::TrailingBytes);
+        }
+
+        let leaf_hash = algorithm.hash_leaf(&value).as_ref().into();
+        let scheme = HashingScheme::default();
+
+        let lemma = match leaf_counts {
+            Some(ref counts) => Lemma::from_counted_siblings(algorithm, scheme, leaf_hash, siblings, counts),
+            None => Lemma::from_siblings(algorithm, scheme, leaf_hash, siblings),
+        };
+
+        let mut proof = Proof::new(algorithm, root_hash, lemma, value);
+        proof.leaf_counts = leaf_counts;
+
+        Ok(proof)
+    }
+}
+
+/// Encodes the given sibling hashes, from the root down, as a direction byte for each one,
+/// `0` if the sibling lies on the left and `1` if it lies on the right, followed by its hash.
+///
+/// The hash length is not part of the encoding, but implied by the hashing algorithm,
+/// so that it is not repeated for every level.
+pub fn encode_siblings<'a, I>(siblings: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = &'a Positioned<Vec<u8>>>,
+{
+
+    let mut bytes = Vec::new();
+
+    for sibling in siblings {
+        write_sibling(&mut bytes, sibling);
+    }
+
+    bytes
+}
+
+/// Decodes sibling hashes of `hash_len` bytes encoded with `encode_siblings`.
+///
+/// Fails with `Error::InvalidDirection` on a direction byte other than `0` or `1`,
+/// and with `Error::TruncatedInput` if the last hash is cut short.
+pub fn decode_siblings(bytes: &[u8], hash_len: usize) -> Result<Vec<Positioned<Vec<u8>>>, Error> {
+    let mut reader = Reader { bytes: bytes };
+    let mut siblings = Vec::with_capacity(bytes.len() / (1 + hash_len));
+
+    while !reader.bytes.is_empty() {
+        siblings.push(reader.sibling(hash_len)?);
+    }
+
+    Ok(siblings)
+}
+
+/// Rebuilds the lemma chain of a proof whose siblings are encoded with `encode_siblings`,
+/// given the hash of its leaf and the tag of its hashing algorithm, which must be `algorithm`.
+#[cfg(any(feature = "serialization-protobuf", feature = "serialization-prost"))]
+pub(crate) fn decode_compact_lemma(
+    algorithm: &'static Algorithm,
+    tag: u32,
+    leaf_hash: Vec<u8>,
+    siblings: &[u8],
+) -> Result<Lemma, Error> {
+    check_proto_algorithm_tag(algorithm, tag)?;
+    check_hash_len(algorithm, &leaf_hash)?;
+
+    if siblings.len() > MAX_TREE_DEPTH * (1 + algorithm.output_len) {
+        return Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"));
+    }
+
+    let siblings = decode_siblings(siblings, algorithm.output_len)?;
+
+    Ok(Lemma::from_siblings(algorithm, HashingScheme::default(), leaf_hash, siblings))
+}
+
+/// Checks that the algorithm tag of a Protobuf message identifies `algorithm`.
+#[cfg(any(feature = "serialization-protobuf", feature = "serialization-prost"))]
+pub(crate) fn check_proto_algorithm_tag(algorithm: &'static Algorithm, tag: u32) -> Result<(), Error> {
+    use std::convert::TryFrom;
+
+    match u8::try_from(tag) {
+        Ok(tag) => check_algorithm_tag(algorithm, tag),
+        Err(_) => Err(Error::Decode(format!("unknown algorithm tag {}", tag))),
+    }
+}
+
+/// Decodes the hashing scheme of a Protobuf message, see `scheme_tag`.
+#[cfg(any(feature = "serialization-protobuf", feature = "serialization-prost"))]
+pub(crate) fn decode_proto_scheme(tag: u32) -> Result<HashingScheme, Error> {
+    use std::convert::TryFrom;
+
+    u8::try_from(tag)
+        .ok()
+        .and_then(scheme_from_tag)
+        .ok_or_else(|| Error::Decode(format!("unknown hashing scheme {}", tag)))
+}
+
+/// Checks that `hash` has the output length of `algorithm`.
+#[cfg(any(feature = "serialization-protobuf", feature = "serialization-prost"))]
+pub(crate) fn check_hash_len(algorithm: &'static Algorithm, hash: &[u8]) -> Result<(), Error> {
+    if hash.len() != algorithm.output_len {
+        return Err(Error::HashLengthMismatch {
+            expected: algorithm.output_len,
+            found: hash.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Checks that `tag` identifies `algorithm`.
+fn check_algorithm_tag(algorithm: &'static Algorithm, tag: u8) -> Result<(), Error> {
+    match algorithm_from_tag(tag) {
+        None => Err(Error::UnknownAlgorithm(tag)),
+        Some(algo) if algorithm_tag(algo) != algorithm_tag(algorithm) => Err(Error::AlgorithmMismatch),
+        Some(_) => Ok(()),
+    }
+}
+
+fn write_sibling(bytes: &mut Vec<u8>, sibling: &Positioned<Vec<u8>>) {
+    bytes.push(sibling.direction().to_byte());
+    bytes.extend_from_slice(sibling.as_ref().into_inner());
+}
+
+/// Consumes a byte slice from the front, failing with `Error::TruncatedInput`
+/// instead of panicking when not enough bytes are left.
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(Error::TruncatedInput);
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, Error> {
+        let (&byte, rest) = self.bytes.split_first().ok_or(Error::TruncatedInput)?;
+        self.bytes = rest;
+
+        Ok(byte)
+    }
+
+    /// Reads a sibling hash of `hash_len` bytes, preceded by its direction byte.
+    pub(crate) fn sibling(&mut self, hash_len: usize) -> Result<Positioned<Vec<u8>>, Error> {
+        let direction = self.byte()?;
+        let hash = self.take(hash_len)?.to_vec();
+
+        match Direction::from_byte(direction) {
+            Some(direction) => Ok(Positioned::new(direction, hash)),
+            None => Err(Error::InvalidDirection(direction)),
+        }
+    }
+
+    /// Reads an unsigned LEB128 varint, as written by `write_varint`.
+    pub(crate) fn varint(&mut self) -> Result<u64, Error> {
+        let mut n = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+
+            if shift == 63 && byte > 1 {
+                return Err(Error::Decode("varint overflows 64 bits".to_string()));
+            }
+
+            n |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+
+        Err(Error::Decode("varint overflows 64 bits".to_string()))
+    }
+}
+
+/// Appends `n` as an unsigned LEB128 varint, ie. 7 bits at a time from the least significant
+/// ones, the highest bit of each byte telling whether more bytes follow.
+pub(crate) fn write_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+
+    bytes.push(n as u8);
+}
+
+fn u32_to_bytes(n: u32) -> [u8; 4] {
+    [(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]
+}
+
+fn u32_from_bytes(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u32)
+}
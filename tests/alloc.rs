@@ -0,0 +1,155 @@
+extern crate merkle;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use merkle::digest::{Algorithm, SHA256};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, OddLeafPolicy, HashingScheme, ValidationPolicy};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+/// Counts the allocations made by the whole test binary, which therefore only holds one test.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations made by `f`, along with its result.
+fn count_allocations<R, F: FnOnce() -> R>(f: F) -> (R, usize) {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let result = f();
+
+    (result, ALLOCATIONS.load(Ordering::SeqCst) - before)
+}
+
+/// Returns the number of bytes allocated by `f`, along with its result.
+fn count_allocated_bytes<R, F: FnOnce() -> R>(f: F) -> (R, usize) {
+    let before = ALLOCATED_BYTES.load(Ordering::SeqCst);
+    let result = f();
+
+    (result, ALLOCATED_BYTES.load(Ordering::SeqCst) - before)
+}
+
+#[test]
+fn test_allocations() {
+    check_build_allocations();
+    check_all_proofs_allocations();
+    check_rebuild_allocations();
+    check_policy_allocations();
+}
+
+fn check_build_allocations() {
+    let count = 1 << 12;
+
+    for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        for &len in &[count, count - 1] {
+            let values = (0..len as u64).map(u64::to_le_bytes).collect::<Vec<_>>();
+            let builder = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy);
+
+            let (tree, allocations) = count_allocations(|| builder.build_from_vec(values));
+            assert_eq!(tree.count(), len);
+
+            // One hash per node, and a few vectors per level, rather than a box per node
+            assert!(
+                allocations <= 2 * count + 16 * (tree.height() + 1),
+                "{:?}, {} leaves: {} allocations",
+                policy,
+                len,
+                allocations
+            );
+        }
+    }
+
+    let values = (0..count as u64).map(u64::to_le_bytes).collect::<Vec<_>>();
+    let (tree, allocations) = count_allocations(|| MerkleTree::from_vec(digest, values));
+    assert!(allocations <= 2 * count + 16 * (tree.height() + 1));
+}
+
+fn check_all_proofs_allocations() {
+    let count = 1 << 10;
+    let values = (0..count as u64).map(u64::to_le_bytes).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+
+    let (proofs, allocations) = count_allocations(|| tree.all_proofs());
+    let (naive, naive_allocations) = count_allocations(|| {
+        (0..count).map(|n| tree.gen_nth_proof(n).unwrap()).collect::<Vec<_>>()
+    });
+
+    assert_eq!(proofs, naive);
+
+    // The hashes and the box of each lemma of each proof, and nothing per visited node
+    assert!(allocations <= 3 * count * (tree.height() + 1) + 2);
+    assert!(allocations <= naive_allocations + 1);
+}
+
+fn check_rebuild_allocations() {
+    let count = 1 << 12;
+    let values = |seed: u64| (seed..seed + count as u64).map(u64::to_le_bytes).collect::<Vec<_>>();
+
+    for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        let builder = MerkleTreeBuilder::new(digest).odd_leaf_policy(policy);
+        let mut tree = builder.clone().build_from_vec(values(0));
+
+        for seed in 1..4 {
+            let (fresh, fresh_bytes) = count_allocated_bytes(|| builder.clone().build_from_vec(values(seed)));
+            let (next, value_bytes) = count_allocated_bytes(|| values(seed));
+            let build_bytes = fresh_bytes - value_bytes;
+
+            let (((), allocations), bytes) = count_allocated_bytes(|| {
+                count_allocations(|| tree.rebuild_from_vec(next))
+            });
+            assert_eq!(tree, fresh);
+
+            // The nodes, which are all the bytes of a copy of the tree, are laid out
+            // in the previous ones, leaving only the hashing to allocate
+            let node_bytes = count_allocated_bytes(|| tree.clone()).1;
+            assert!(bytes + node_bytes <= build_bytes, "{:?}: {} bytes", policy, bytes);
+            assert!(allocations <= 8 * (tree.height() + 1), "{:?}: {} allocations", policy, allocations);
+        }
+
+        tree.clear();
+        let ((), allocations) = count_allocations(|| tree.rebuild_from_vec(values(0)));
+        assert_eq!(tree, builder.build_from_vec(values(0)));
+        assert!(allocations <= 8 * (tree.height() + 1));
+    }
+}
+
+fn check_policy_allocations() {
+    let tree = MerkleTree::from_vec(digest, (0..1000u64).map(u64::to_le_bytes).collect());
+    let proofs = (0..1000).map(|index| tree.gen_nth_proof(index).unwrap()).collect::<Vec<_>>();
+
+    let policy = ValidationPolicy::new()
+        .max_depth(40)
+        .strict(1000)
+        .required_scheme(HashingScheme::DomainSeparated)
+        .require_value_check(true)
+        .constant_time(true);
+
+    // A policy is reused across proofs, without allocating per proof, once whatever is set up
+    // on first use is
+    assert!(proofs[0].validate_with_policy(tree.root_hash(), &policy).is_ok());
+
+    let (valid, allocations) = count_allocations(|| {
+        proofs.iter().all(|proof| proof.validate_with_policy(tree.root_hash(), &policy).is_ok())
+    });
+
+    assert!(valid);
+    assert_eq!(allocations, 0);
+}
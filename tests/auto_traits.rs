@@ -0,0 +1,155 @@
+//! Compile-time checks that the public types are `Send`, `Sync` and unwind safe whenever
+//! the types they are generic over are, so that no change to their internals, such as
+//! holding an `Rc` or a `Cell`, can take it away unnoticed.
+
+extern crate merkle;
+
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+use merkle::*;
+
+fn send_sync<T: Send + Sync>() {}
+
+fn unwind_safe<T: UnwindSafe + RefUnwindSafe>() {}
+
+/// Holds for any types which are `Send` and `Sync`, rather than for given ones.
+#[allow(dead_code)]
+fn shared<'a, T, U>()
+where
+    T: Send + Sync + 'a,
+    U: Send + Sync + 'a,
+{
+
+    send_sync::<MerkleTree<T>>();
+    send_sync::<MerkleTreeBuilder<T>>();
+    send_sync::<LeafOrdering<T>>();
+    send_sync::<Proof<T>>();
+    send_sync::<ProofData<T>>();
+    send_sync::<CanonicalProofData<T>>();
+    send_sync::<PartialTree<T>>();
+    send_sync::<WithMeta<T, U>>();
+    send_sync::<Forest<T>>();
+    send_sync::<DualAlgorithmTree<T>>();
+    send_sync::<ConcurrentAppender<T>>();
+    send_sync::<AuditLog<T>>();
+    send_sync::<AppendReceipt<T>>();
+    send_sync::<StreamingLeaf<T>>();
+    send_sync::<ProofBundle<T>>();
+    send_sync::<UpdateReceipt<T>>();
+    send_sync::<CasError<T>>();
+    send_sync::<AppendOnlyTree<T>>();
+    send_sync::<TreeTxn<'a, T>>();
+    send_sync::<SlidingWindowTree<T>>();
+    send_sync::<InternTable<T>>();
+    send_sync::<Interned<T>>();
+    send_sync::<MerkleMap<T, U>>();
+    send_sync::<KvProof<T, U>>();
+    send_sync::<KvAbsenceProof<T>>();
+
+    send_sync::<PreOrderNodes<'a, T>>();
+    send_sync::<PostOrderNodes<'a, T>>();
+    send_sync::<NodeRef<'a, T>>();
+    send_sync::<AllProofs<'a, T>>();
+    send_sync::<ProvenIter<'a, T>>();
+    send_sync::<IntoProvenIter<T>>();
+    send_sync::<IncrementalProofWriter<'a, T>>();
+    send_sync::<LeavesIterator<'a, T>>();
+    send_sync::<LeavesIntoIterator<T>>();
+    send_sync::<LeafHashes<'a, T>>();
+}
+
+/// Holds for any types which are unwind safe, rather than for given ones.
+#[allow(dead_code)]
+fn unwind<'a, T, U>()
+where
+    T: UnwindSafe + RefUnwindSafe + 'a,
+    U: UnwindSafe + RefUnwindSafe + 'a,
+{
+
+    unwind_safe::<MerkleTree<T>>();
+    unwind_safe::<MerkleTreeBuilder<T>>();
+    unwind_safe::<LeafOrdering<T>>();
+    unwind_safe::<Proof<T>>();
+    unwind_safe::<ProofData<T>>();
+    unwind_safe::<CanonicalProofData<T>>();
+    unwind_safe::<PartialTree<T>>();
+    unwind_safe::<WithMeta<T, U>>();
+    unwind_safe::<Forest<T>>();
+    unwind_safe::<DualAlgorithmTree<T>>();
+    unwind_safe::<ConcurrentAppender<T>>();
+    unwind_safe::<AuditLog<T>>();
+    unwind_safe::<AppendReceipt<T>>();
+    unwind_safe::<StreamingLeaf<T>>();
+    unwind_safe::<ProofBundle<T>>();
+    unwind_safe::<UpdateReceipt<T>>();
+    unwind_safe::<CasError<T>>();
+    unwind_safe::<AppendOnlyTree<T>>();
+    unwind_safe::<SlidingWindowTree<T>>();
+    unwind_safe::<InternTable<T>>();
+    unwind_safe::<Interned<T>>();
+    unwind_safe::<MerkleMap<T, U>>();
+    unwind_safe::<KvProof<T, U>>();
+    unwind_safe::<KvAbsenceProof<T>>();
+
+    unwind_safe::<PreOrderNodes<'a, T>>();
+    unwind_safe::<PostOrderNodes<'a, T>>();
+    unwind_safe::<NodeRef<'a, T>>();
+    unwind_safe::<AllProofs<'a, T>>();
+    unwind_safe::<ProvenIter<'a, T>>();
+    unwind_safe::<IntoProvenIter<T>>();
+    unwind_safe::<IncrementalProofWriter<'a, T>>();
+    unwind_safe::<LeavesIterator<'a, T>>();
+    unwind_safe::<LeavesIntoIterator<T>>();
+    unwind_safe::<LeafHashes<'a, T>>();
+}
+
+#[test]
+fn test_plain_types_are_shared_and_unwind_safe() {
+    fn both<T: Send + Sync + UnwindSafe + RefUnwindSafe>() {}
+
+    both::<Lemma>();
+    both::<LemmaIter<'static>>();
+    both::<ValidationPolicy>();
+    both::<ValidationBudget>();
+    both::<BudgetUsage>();
+    both::<BatchReport>();
+    both::<MultiProof>();
+    both::<MultiProofReport>();
+    both::<PrefixProof>();
+    both::<CachingVerifier>();
+    both::<IncrementalVerify>();
+    both::<NodeHash>();
+    both::<RootHash>();
+    both::<KeyedHasher>();
+    both::<TreeHead>();
+    both::<TrustPolicy>();
+    both::<Frontier>();
+    both::<Error>();
+    both::<HashError>();
+    both::<IoError>();
+    both::<ConversionError>();
+    both::<RebuildError>();
+}
+
+#[test]
+fn test_generic_types_are_shared_and_unwind_safe() {
+    shared::<Vec<u8>, String>();
+    unwind::<Vec<u8>, String>();
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn test_metrics_are_shared_and_unwind_safe() {
+    fn both<T: Send + Sync + UnwindSafe + RefUnwindSafe>() {}
+
+    both::<Metrics>();
+    both::<MetricsObserver>();
+}
+
+#[cfg(feature = "serialization-serde")]
+#[test]
+fn test_tagged_proofs_are_shared_and_unwind_safe() {
+    fn both<T: Send + Sync + UnwindSafe + RefUnwindSafe>() {}
+
+    both::<TaggedProof<'static, Vec<u8>>>();
+}
@@ -0,0 +1,98 @@
+extern crate merkle;
+
+#[cfg(all(feature = "backend-ring", feature = "backend-rustcrypto"))]
+extern crate ring;
+
+use merkle::digest::{Algorithm, Context, SHA256, SHA384, SHA512};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, HashingScheme, OddLeafPolicy};
+
+/// The hashes of the trees of `fingerprint` with each algorithm, as computed with *ring*,
+/// which every backend must reproduce byte for byte.
+static FINGERPRINTS: [(&'static Algorithm, &'static str); 3] = [
+    (&SHA256, "ab6396630541d4680a3c16c7a539d3fe03b4b1060ff3cd4d9438c1984ed3f71f"),
+    (&SHA384, "538684de066d11ecaebdb2e4c3503aad157c064555e41326f22d498926e03e76"),
+    (&SHA512, "dafe1164675677f5a470c3497b2e26dacda4f55b50bfa75fe15424fa6b07853e"),
+];
+
+/// The root of the tree over `["a", "b", "c"]`, with SHA-256 and the default options.
+static ROOT_ABC: &'static str = "36642e73c2540ab121e3a6bf9545b0a24982cd830eb13d3cd19de3ce6c021ec1";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hashes, with SHA-256, the roots and the encoded proofs of every leaf of trees of several
+/// sizes, hashing schemes and odd leaf policies, built with the given algorithm.
+fn fingerprint(algorithm: &'static Algorithm) -> String {
+    let schemes = [HashingScheme::DomainSeparated, HashingScheme::Unprefixed, HashingScheme::SortedPairs];
+    let policies = [OddLeafPolicy::Promote, OddLeafPolicy::Duplicate];
+    let mut ctx = Context::new(&SHA256);
+
+    for &count in &[1, 2, 3, 5, 8, 13, 200] {
+        let values = (0..count).map(|i| format!("value {}", i).into_bytes()).collect::<Vec<_>>();
+
+        for &scheme in &schemes {
+            for &policy in &policies {
+                let tree = MerkleTreeBuilder::new(algorithm)
+                    .hashing_scheme(scheme)
+                    .odd_leaf_policy(policy)
+                    .build_from_vec(values.clone());
+
+                ctx.update(tree.root_hash());
+
+                for value in &values {
+                    ctx.update(&tree.gen_proof(value.clone()).unwrap().to_bytes());
+                }
+            }
+        }
+    }
+
+    to_hex(ctx.finish().as_ref())
+}
+
+#[test]
+fn test_roots_and_proofs_match_across_backends() {
+    let tree = MerkleTree::from_vec(&SHA256, vec!["a", "b", "c"]);
+    assert_eq!(to_hex(tree.root_hash()), ROOT_ABC);
+
+    for &(algorithm, expected) in &FINGERPRINTS {
+        assert_eq!(fingerprint(algorithm), expected, "{:?}", algorithm);
+    }
+}
+
+/// With both backends compiled in, the RustCrypto one is used, and checked against *ring*.
+#[cfg(all(feature = "backend-ring", feature = "backend-rustcrypto"))]
+#[test]
+fn test_rustcrypto_digests_match_ring() {
+    use merkle::digest as hashing;
+    use ring::digest as ring_digest;
+
+    let algorithms = [
+        (&hashing::SHA1, &ring_digest::SHA1),
+        (&hashing::SHA256, &ring_digest::SHA256),
+        (&hashing::SHA384, &ring_digest::SHA384),
+        (&hashing::SHA512, &ring_digest::SHA512),
+        (&hashing::SHA512_256, &ring_digest::SHA512_256),
+    ];
+
+    for &(algorithm, ring_algorithm) in &algorithms {
+        assert_eq!(algorithm.output_len, ring_algorithm.output_len);
+        assert_eq!(algorithm.block_len, ring_algorithm.block_len);
+
+        for len in (0..300).step_by(7) {
+            let data = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+            let expected = ring_digest::digest(ring_algorithm, &data);
+
+            assert_eq!(hashing::digest(algorithm, &data).as_ref(), expected.as_ref());
+
+            // The same input fed in several steps
+            let mut ctx = Context::new(algorithm);
+            for chunk in data.chunks(13) {
+                ctx.update(chunk);
+            }
+
+            assert_eq!(ctx.finish().as_ref(), expected.as_ref());
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! The serde and Protobuf encodings of multiproofs, prefix proofs and update receipts:
+//! round trips across formats and hash encodings, the golden fixtures in `tests/fixtures`,
+//! and the limits on untrusted input.
+
+#![cfg(all(feature = "serialization-serde", any(feature = "serialization-protobuf", feature = "serialization-prost")))]
+
+extern crate merkle;
+extern crate serde;
+extern crate serde_json;
+
+use std::fmt::Debug;
+use std::fs::File;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use merkle::digest::{Algorithm, SHA256, SHA512};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, MultiProof, MultiProofNode, PrefixProof, UpdateReceipt};
+use merkle::{Encoded, HashEncoding, HashingScheme, OddLeafPolicy, Error, MAX_TREE_DEPTH};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+static ENCODINGS: &'static [HashEncoding] =
+    &[HashEncoding::Bytes, HashEncoding::Hex, HashEncoding::Base64];
+
+// The fixtures were captured from the encoders of version 1 of the schema, for the SHA256 tree
+// of `alpha`, `beta`, `gamma`, `delta` and `epsilon`: the multiproof of its second and last leaves,
+// the proof that its first three leaves are a prefix of it, and the receipt of the update
+// of `beta` to `BETA`. They must keep decoding as the formats evolve.
+
+static MULTIPROOF_V1: &'static [u8] = include_bytes!("fixtures/protobuf/multiproof_v1_sha256.bin");
+static PREFIX_PROOF_V1: &'static [u8] = include_bytes!("fixtures/protobuf/prefix_proof_v1_sha256.bin");
+static UPDATE_RECEIPT_V1: &'static [u8] = include_bytes!("fixtures/protobuf/update_receipt_v1_sha256.bin");
+
+fn fixture_json(name: &str) -> serde_json::Value {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    serde_json::from_reader(File::open(path).unwrap()).unwrap()
+}
+
+fn fixture_tree() -> MerkleTree<Vec<u8>> {
+    let values = ["alpha", "beta", "gamma", "delta", "epsilon"];
+    MerkleTree::from_vec(digest, values.iter().map(|value| value.as_bytes().to_vec()).collect())
+}
+
+fn fixture_receipt() -> UpdateReceipt<Vec<u8>> {
+    fixture_tree().compare_and_update(1, &b"beta".to_vec(), b"BETA".to_vec()).unwrap()
+}
+
+/// Trees of every shape multiproofs and prefix proofs are generated for.
+fn trees() -> Vec<MerkleTree<Vec<u8>>> {
+    let values = (0..11u8).map(|x| vec![x]).collect::<Vec<_>>();
+
+    vec![
+        MerkleTree::from_vec(digest, values.clone()),
+        MerkleTree::from_vec(&SHA512, values.clone()),
+        MerkleTreeBuilder::new(digest).hashing_scheme(HashingScheme::SortedPairs).build_from_vec(values.clone()),
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate).build_from_vec(values.clone()),
+        MerkleTree::from_vec(digest, values[..1].to_vec()),
+    ]
+}
+
+/// Checks that `value` is read back from JSON with every hash encoding, and from Protobuf,
+/// across formats.
+fn assert_round_trips<T, P, W>(value: &T, parse: P, write: W)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+    P: Fn(&[u8]) -> Result<T, Error>,
+    W: Fn(&T) -> Vec<u8>,
+{
+
+    for &encoding in ENCODINGS {
+        let json = serde_json::to_string(&Encoded::new(value, encoding)).unwrap();
+        let from_json = serde_json::from_str::<T>(&json).unwrap();
+
+        assert_eq!(from_json, *value);
+        assert_eq!(write(&from_json), write(value));
+    }
+
+    let from_protobuf = parse(&write(value)).unwrap();
+    assert_eq!(from_protobuf, *value);
+
+    let json = serde_json::to_string(&from_protobuf).unwrap();
+    assert_eq!(serde_json::from_str::<T>(&json).unwrap(), *value);
+}
+
+/// Serializes `value` to JSON, with hex hashes.
+fn to_hex_json<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(Encoded::new(value, HashEncoding::Hex)).unwrap()
+}
+
+#[test]
+fn test_multiproof_round_trips() {
+    for tree in trees() {
+        let algorithm = tree.algorithm;
+
+        for indices in vec![vec![0], vec![1, 4], vec![3, 9, 10], (0..tree.count()).collect()] {
+            let indices = indices.into_iter().filter(|&i| i < tree.count()).collect::<Vec<_>>();
+            let proof = tree.gen_multi_proof(&indices).unwrap();
+
+            assert_round_trips(
+                &proof,
+                |bytes| MultiProof::parse_protobuf(algorithm, bytes),
+                MultiProof::write_protobuf,
+            );
+
+            // The compact format goes along with the others
+            let compact = MultiProof::from_compact_bytes(algorithm, &proof.to_compact_bytes()).unwrap();
+            assert_eq!(MultiProof::parse_protobuf(algorithm, &compact.write_protobuf()).unwrap(), proof);
+
+            let decoded = serde_json::from_value::<MultiProof>(serde_json::to_value(&proof).unwrap()).unwrap();
+            assert_eq!(decoded.algorithm.output_len, algorithm.output_len);
+            assert_eq!(decoded.validate(tree.root_hash()), proof.validate(tree.root_hash()));
+        }
+    }
+}
+
+#[test]
+fn test_prefix_proof_round_trips() {
+    for tree in trees().into_iter().filter(|tree| tree.prove_prefix(1).is_some()) {
+        let algorithm = tree.algorithm;
+
+        for prefix_len in 0..tree.count() + 1 {
+            let proof = tree.prove_prefix(prefix_len).unwrap();
+
+            assert_round_trips(
+                &proof,
+                |bytes| PrefixProof::parse_protobuf(algorithm, bytes),
+                PrefixProof::write_protobuf,
+            );
+
+            let decoded = serde_json::from_value::<PrefixProof>(serde_json::to_value(&proof).unwrap()).unwrap();
+            assert_eq!(decoded.scheme, proof.scheme);
+        }
+    }
+}
+
+#[test]
+fn test_update_receipt_round_trips() {
+    let mut tree = fixture_tree();
+
+    for index in 0..tree.count() {
+        let old = tree.iter().nth(index).unwrap().clone();
+        let receipt = tree.compare_and_update(index, &old, vec![index as u8; 40]).unwrap();
+
+        assert_round_trips(
+            &receipt,
+            |bytes| UpdateReceipt::parse_protobuf(digest, bytes),
+            UpdateReceipt::write_protobuf,
+        );
+
+        let json = serde_json::to_value(&receipt).unwrap();
+        assert_eq!(json["old_proof"]["algorithm"], json["new_proof"]["algorithm"]);
+        assert!(json["old_proof"]["algorithm"].is_string());
+
+        let decoded = serde_json::from_value::<UpdateReceipt<Vec<u8>>>(json).unwrap();
+        assert!(decoded.old_proof.validate(&decoded.old_root));
+        assert!(decoded.new_proof.validate(&decoded.new_root));
+    }
+}
+
+#[test]
+fn test_fixtures_decode() {
+    let tree = fixture_tree();
+    let multi = tree.gen_multi_proof(&[1, 4]).unwrap();
+    let prefix = tree.prove_prefix(3).unwrap();
+    let receipt = fixture_receipt();
+
+    assert_eq!(MultiProof::parse_protobuf(digest, MULTIPROOF_V1).unwrap(), multi);
+    assert_eq!(PrefixProof::parse_protobuf(digest, PREFIX_PROOF_V1).unwrap(), prefix);
+    assert_eq!(UpdateReceipt::<Vec<u8>>::parse_protobuf(digest, UPDATE_RECEIPT_V1).unwrap(), receipt);
+
+    let json_multi = serde_json::from_value::<MultiProof>(fixture_json("multiproof.json")).unwrap();
+    let json_prefix = serde_json::from_value::<PrefixProof>(fixture_json("prefix_proof.json")).unwrap();
+    let json_receipt = serde_json::from_value::<UpdateReceipt<Vec<u8>>>(fixture_json("update_receipt.json")).unwrap();
+
+    assert_eq!(json_multi, multi);
+    assert_eq!(json_prefix, prefix);
+    assert_eq!(json_receipt, receipt);
+
+    assert!(json_multi.validate(tree.root_hash()));
+    assert!(json_prefix.verify(&prefix_root(3), 3, tree.root_hash(), tree.count()));
+}
+
+#[test]
+fn test_encoders_match_fixtures() {
+    let tree = fixture_tree();
+    let multi = tree.gen_multi_proof(&[1, 4]).unwrap();
+    let prefix = tree.prove_prefix(3).unwrap();
+    let receipt = fixture_receipt();
+
+    assert_eq!(multi.write_protobuf(), MULTIPROOF_V1);
+    assert_eq!(prefix.write_protobuf(), PREFIX_PROOF_V1);
+    assert_eq!(receipt.write_protobuf(), UPDATE_RECEIPT_V1);
+
+    assert_eq!(to_hex_json(&multi), fixture_json("multiproof.json"));
+    assert_eq!(to_hex_json(&prefix), fixture_json("prefix_proof.json"));
+    assert_eq!(to_hex_json(&receipt), fixture_json("update_receipt.json"));
+}
+
+fn prefix_root(prefix_len: usize) -> Vec<u8> {
+    let values = fixture_tree().into_iter().take(prefix_len).collect();
+    MerkleTree::from_vec(digest, values).root_hash().clone()
+}
+
+#[test]
+fn test_serde_limits() {
+    let leaf = || MultiProofNode::Leaf { hash: vec![0; 32] };
+
+    let mut root = leaf();
+    for _ in 0..MAX_TREE_DEPTH {
+        root = MultiProofNode::Node {
+            hash: vec![0; 32],
+            left: Box::new(root),
+            right: Box::new(leaf()),
+        };
+    }
+
+    let mut proof = fixture_tree().gen_multi_proof(&[0]).unwrap();
+    proof.root = root.clone();
+
+    // Values, unlike JSON text, are read without a recursion limit of their own
+    let json = serde_json::to_value(&proof).unwrap();
+    assert_eq!(serde_json::from_value::<MultiProof>(json).unwrap(), proof);
+
+    proof.root = MultiProofNode::Node {
+        hash: vec![0; 32],
+        left: Box::new(root),
+        right: Box::new(leaf()),
+    };
+
+    let json = serde_json::to_value(&proof).unwrap();
+    let err = serde_json::from_value::<MultiProof>(json).unwrap_err();
+    assert!(err.to_string().contains("exceeds the maximum depth"), "{}", err);
+
+    let mut prefix = fixture_tree().prove_prefix(3).unwrap();
+    prefix.hashes = vec![vec![0; 32]; MAX_TREE_DEPTH + 2];
+
+    let json = serde_json::to_string(&prefix).unwrap();
+    assert!(serde_json::from_str::<PrefixProof>(&json).is_err());
+
+    // The proofs of a receipt name their algorithm
+    let mut json = serde_json::to_value(fixture_receipt()).unwrap();
+    json["new_proof"]["algorithm"] = "SHA-0".into();
+    assert!(serde_json::from_value::<UpdateReceipt<Vec<u8>>>(json).is_err());
+}
+
+#[test]
+fn test_protobuf_rejects_malformed() {
+    let tree = fixture_tree();
+    let multi = tree.gen_multi_proof(&[1, 4]).unwrap();
+
+    let mut short_hash = multi.clone();
+    short_hash.root = MultiProofNode::Pruned { hash: vec![0; 31] };
+    assert_eq!(
+        MultiProof::parse_protobuf(digest, &short_hash.write_protobuf()),
+        Err(Error::HashLengthMismatch { expected: 32, found: 31 })
+    );
+
+    assert_eq!(MultiProof::parse_protobuf(&SHA512, MULTIPROOF_V1), Err(Error::AlgorithmMismatch));
+    assert_eq!(PrefixProof::parse_protobuf(&SHA512, PREFIX_PROOF_V1), Err(Error::AlgorithmMismatch));
+
+    let mut deep = MultiProofNode::Leaf { hash: vec![0; 32] };
+    for _ in 0..MAX_TREE_DEPTH + 1 {
+        deep = MultiProofNode::Node {
+            hash: vec![0; 32],
+            left: Box::new(deep),
+            right: Box::new(MultiProofNode::Pruned { hash: vec![0; 32] }),
+        };
+    }
+
+    let mut too_deep = multi.clone();
+    too_deep.root = deep;
+    assert_eq!(
+        MultiProof::parse_protobuf(digest, &too_deep.write_protobuf()),
+        Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"))
+    );
+
+    let mut prefix = tree.prove_prefix(3).unwrap();
+    prefix.hashes = vec![vec![0; 32]; MAX_TREE_DEPTH + 2];
+    assert_eq!(
+        PrefixProof::parse_protobuf(digest, &prefix.write_protobuf()),
+        Err(Error::InvalidPath("proof has more hashes than any tree needs"))
+    );
+
+    // Garbage is not a receipt
+    assert!(UpdateReceipt::<Vec<u8>>::parse_protobuf(digest, &[0x12, 0xff]).is_err());
+    assert_eq!(UpdateReceipt::<Vec<u8>>::parse_protobuf(digest, &[]), Err(Error::MissingField("old_root")));
+}
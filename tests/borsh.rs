@@ -0,0 +1,118 @@
+#![cfg(feature="serialization-borsh")]
+
+extern crate merkle;
+extern crate borsh;
+
+use merkle::digest::{Algorithm, SHA256};
+
+use borsh::BorshDeserialize;
+
+use merkle::{MerkleTree, ProofData, Lemma, Positioned, BORSH_MAX_LEMMA_DEPTH};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+/// The proof for `"a"` in the tree over `["a", "b"]`.
+static GOLDEN_PROOF: &'static str = "\
+    20000000b137985ff484fb600db93107c77b0365c80d78f5b429ded0fd97361d077999eb\
+    20000000b137985ff484fb600db93107c77b0365c80d78f5b429ded0fd97361d077999eb\
+    0101\
+    2000000057eb35615d47f34ec714cacdf5fd74608a5e8e102724e80b24b287c0c27b6a31\
+    01\
+    20000000022a6979e6dab7aa5ae4c3e5e45f7e977112a7e63593820dbec1ec738a24f93c\
+    00\
+    00\
+    0100000061";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Drops the fields of `data` which are not part of the encoding.
+fn unencoded_fields_dropped<T>(data: ProofData<T>) -> ProofData<T> {
+    ProofData {
+        algorithm: None,
+        tree_size: None,
+        leaf_index: None,
+        ..data
+    }
+}
+
+#[test]
+fn test_borsh_golden_bytes() {
+    let tree = MerkleTree::from_vec(digest, vec!["a".to_string(), "b".to_string()]);
+    let data = tree.gen_proof("a".to_string()).unwrap().into_data();
+
+    let bytes = borsh::to_vec(&data).unwrap();
+    assert_eq!(to_hex(&bytes), GOLDEN_PROOF);
+
+    let res = ProofData::<String>::try_from_slice(&bytes).unwrap();
+    assert_eq!(res, unencoded_fields_dropped(data));
+}
+
+#[test]
+fn test_borsh_round_trip() {
+    let values = (0..13).map(|i| vec![i; 3]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    for value in values {
+        let data = tree.gen_proof(value).unwrap().into_data();
+        let bytes = borsh::to_vec(&data).unwrap();
+        let res = ProofData::<Vec<u8>>::try_from_slice(&bytes).unwrap();
+
+        assert_eq!(res, unencoded_fields_dropped(data));
+        assert!(res.into_proof(digest).unwrap().validate(tree.root_hash()));
+    }
+}
+
+#[test]
+fn test_borsh_positioned() {
+    let left = Positioned::Left(vec![1, 2, 3]);
+    let right = Positioned::Right(vec![4]);
+
+    assert_eq!(borsh::to_vec(&left).unwrap(), vec![0, 3, 0, 0, 0, 1, 2, 3]);
+    assert_eq!(borsh::to_vec(&right).unwrap(), vec![1, 1, 0, 0, 0, 4]);
+
+    assert_eq!(Positioned::try_from_slice(&[0, 3, 0, 0, 0, 1, 2, 3]).unwrap(), left);
+    assert!(Positioned::<Vec<u8>>::try_from_slice(&[2, 1, 0, 0, 0, 4]).is_err());
+}
+
+fn chain(depth: usize) -> Lemma {
+    let mut lemma = Lemma {
+        node_hash: vec![0],
+        sibling_hash: None,
+        sub_lemma: None,
+    };
+
+    for _ in 0..depth {
+        lemma = Lemma {
+            node_hash: vec![1],
+            sibling_hash: Some(Positioned::Left(vec![2])),
+            sub_lemma: Some(Box::new(lemma)),
+        };
+    }
+
+    lemma
+}
+
+#[test]
+fn test_borsh_depth_limit() {
+    let deepest = chain(BORSH_MAX_LEMMA_DEPTH);
+    let bytes = borsh::to_vec(&deepest).unwrap();
+    assert_eq!(Lemma::try_from_slice(&bytes).unwrap(), deepest);
+
+    let too_deep = chain(BORSH_MAX_LEMMA_DEPTH + 1);
+    let bytes = borsh::to_vec(&too_deep).unwrap();
+    assert!(Lemma::try_from_slice(&bytes).is_err());
+}
+
+#[test]
+fn test_borsh_truncated_input() {
+    let tree = MerkleTree::from_vec(digest, vec!["a".to_string(), "b".to_string()]);
+    let data = tree.gen_proof("b".to_string()).unwrap().into_data();
+    let bytes = borsh::to_vec(&data).unwrap();
+
+    for len in 0..bytes.len() {
+        assert!(ProofData::<String>::try_from_slice(&bytes[..len]).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Proof bundles, from a tree to their verification against each kind of `TrustPolicy`,
+//! through serde and their binary format.
+
+extern crate merkle;
+#[cfg(feature = "serialization-serde")]
+extern crate serde_json;
+
+use std::sync::Arc;
+
+use merkle::digest::{Algorithm, Context, SHA256, SHA512};
+
+use merkle::{MerkleTree, ProofBundle, TrustPolicy, SignatureVerifier, RootHash, Error, BUNDLE_VERSION};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+/// A stand-in for a signature scheme: the signature of a message is the hash of the key
+/// followed by the message, so that only the holder of the key can produce it.
+#[derive(Debug)]
+struct MacKey(Vec<u8>);
+
+impl MacKey {
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        let mut context = Context::new(digest);
+        context.update(&self.0);
+        context.update(message);
+        context.finish().as_ref().to_vec()
+    }
+}
+
+impl SignatureVerifier for MacKey {
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        self.sign(message) == signature
+    }
+}
+
+fn tree() -> MerkleTree<Vec<u8>> {
+    MerkleTree::from_vec(digest, (0..6u8).map(|i| vec![i; 3]).collect())
+}
+
+fn key() -> Arc<MacKey> {
+    Arc::new(MacKey(b"log key".to_vec()))
+}
+
+fn signed_bundle(tree: &MerkleTree<Vec<u8>>, n: usize) -> ProofBundle<Vec<u8>> {
+    let bundle = tree.gen_bundle(n).unwrap();
+    let signature = key().sign(&bundle.tree_head.to_signable_bytes());
+
+    bundle.with_signature(signature)
+}
+
+fn policies(tree: &MerkleTree<Vec<u8>>) -> Vec<TrustPolicy> {
+    vec![
+        TrustPolicy::pinned_root(tree.root()),
+        TrustPolicy::signed_by(key()),
+        TrustPolicy::pinned_and_signed_by(tree.root(), key()),
+    ]
+}
+
+#[test]
+fn test_bundle_verifies_with_every_trust_mode() {
+    let tree = tree();
+
+    for n in 0..tree.count() {
+        let bundle = signed_bundle(&tree, n);
+
+        assert_eq!(bundle.tree_head, tree.tree_head());
+        assert_eq!(bundle.proof.leaf_index, Some(n as u64));
+
+        for policy in policies(&tree) {
+            assert_eq!(bundle.verify(&policy), Ok(()));
+        }
+    }
+
+    assert!(tree.gen_bundle(tree.count()).is_none());
+}
+
+#[test]
+fn test_bundle_trust_failures() {
+    let tree = tree();
+    let bundle = signed_bundle(&tree, 2);
+
+    let other = MerkleTree::from_vec(digest, vec![b"other".to_vec()]);
+    let relabeled = RootHash::new(&SHA512, vec![0; SHA512.output_len]).unwrap();
+
+    assert_eq!(bundle.verify(&TrustPolicy::pinned_root(other.root())), Err(Error::UntrustedRoot));
+    assert_eq!(bundle.verify(&TrustPolicy::pinned_root(relabeled)), Err(Error::UntrustedRoot));
+
+    let unsigned = tree.gen_bundle(2).unwrap();
+    assert_eq!(unsigned.verify(&TrustPolicy::pinned_root(tree.root())), Ok(()));
+    assert_eq!(unsigned.verify(&TrustPolicy::signed_by(key())), Err(Error::MissingField("signature")));
+
+    let stranger = Arc::new(MacKey(b"another key".to_vec()));
+    assert_eq!(bundle.verify(&TrustPolicy::signed_by(stranger)), Err(Error::InvalidSignature));
+
+    // A signed head of another size does not carry the signature of the original one.
+    let mut resized = bundle.clone();
+    resized.tree_head.tree_size = 5;
+    assert_eq!(resized.verify(&TrustPolicy::signed_by(key())), Err(Error::InvalidSignature));
+
+    // The pinned root is checked before the signature.
+    assert_eq!(
+        unsigned.verify(&TrustPolicy::pinned_and_signed_by(other.root(), key())),
+        Err(Error::UntrustedRoot)
+    );
+    assert_eq!(
+        unsigned.verify(&TrustPolicy::pinned_and_signed_by(tree.root(), key())),
+        Err(Error::MissingField("signature"))
+    );
+}
+
+#[test]
+fn test_bundle_proof_failures() {
+    let tree = tree();
+    let policy = TrustPolicy::pinned_and_signed_by(tree.root(), key());
+
+    let mut tampered = signed_bundle(&tree, 3);
+    tampered.proof.value = b"forged".to_vec();
+    assert_eq!(tampered.verify(&policy), Err(Error::InvalidProof));
+
+    // A proof which holds, but for another tree than the trusted one.
+    let other = MerkleTree::from_vec(digest, vec![b"other".to_vec(); 4]);
+    let mut mixed = signed_bundle(&tree, 3);
+    mixed.proof = other.gen_bundle(3).unwrap().proof;
+    assert_eq!(mixed.verify(&policy), Err(Error::InvalidProof));
+
+    let mut renamed = signed_bundle(&tree, 3);
+    renamed.proof.algorithm = Some(merkle::AlgorithmTag::of(&SHA512));
+    assert_eq!(renamed.verify(&policy), Err(Error::AlgorithmMismatch));
+
+    let mut misplaced = signed_bundle(&tree, 3);
+    misplaced.proof.leaf_index = Some(2);
+    assert!(match misplaced.verify(&policy) {
+        Err(Error::PolicyViolation(_)) => true,
+        _ => false,
+    });
+
+    let mut resized = signed_bundle(&tree, 3);
+    resized.proof.tree_size = Some(8);
+    assert!(match resized.verify(&policy) {
+        Err(Error::PolicyViolation(_)) => true,
+        _ => false,
+    });
+
+    // The tree head is trusted before the proof is looked at.
+    let mut untrusted = tampered.clone();
+    untrusted.signature = None;
+    assert_eq!(untrusted.verify(&policy), Err(Error::MissingField("signature")));
+
+    let mut truncated = signed_bundle(&tree, 3);
+    truncated.tree_head.root_hash.pop();
+    assert_eq!(
+        truncated.verify(&policy),
+        Err(Error::HashLengthMismatch {
+            expected: digest.output_len,
+            found: digest.output_len - 1,
+        })
+    );
+}
+
+#[test]
+fn test_bundle_bytes() {
+    let tree = tree();
+
+    for n in 0..tree.count() {
+        let signed = signed_bundle(&tree, n);
+        let unsigned = tree.gen_bundle(n).unwrap();
+
+        for bundle in vec![signed, unsigned] {
+            let bytes = bundle.to_bytes();
+
+            assert_eq!(&bytes[..4], b"MKLB");
+            assert_eq!(bytes[4], BUNDLE_VERSION);
+
+            let decoded = ProofBundle::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded, bundle);
+
+            for policy in policies(&tree).into_iter().take(if bundle.signature.is_some() { 3 } else { 1 }) {
+                assert_eq!(decoded.verify(&policy), Ok(()));
+            }
+
+            for len in 0..bytes.len() {
+                assert!(ProofBundle::from_bytes(&bytes[..len]).is_err());
+            }
+
+            let mut trailing = bytes.clone();
+            trailing.push(0);
+            assert_eq!(ProofBundle::from_bytes(&trailing), Err(Error::TrailingBytes));
+        }
+    }
+
+    let bytes = signed_bundle(&tree, 0).to_bytes();
+
+    let mut magic = bytes.clone();
+    magic[3] = b'P';
+    assert_eq!(ProofBundle::from_bytes(&magic), Err(Error::InvalidMagic));
+
+    let mut version = bytes.clone();
+    version[4] = BUNDLE_VERSION + 1;
+    assert_eq!(
+        ProofBundle::from_bytes(&version),
+        Err(Error::UnsupportedVersion {
+            found: u32::from(BUNDLE_VERSION + 1),
+            max_supported: u32::from(BUNDLE_VERSION),
+        })
+    );
+
+    let mut flags = bytes.clone();
+    flags[5] |= 0x80;
+    assert!(match ProofBundle::from_bytes(&flags) {
+        Err(Error::Decode(_)) => true,
+        _ => false,
+    });
+}
+
+#[cfg(feature = "serialization-serde")]
+#[test]
+fn test_bundle_serde() {
+    let tree = tree();
+    let bundle = signed_bundle(&tree, 4);
+
+    let json = serde_json::to_string(&bundle).unwrap();
+    let decoded: ProofBundle<Vec<u8>> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(decoded, bundle);
+    assert_eq!(decoded.verify(&TrustPolicy::pinned_and_signed_by(tree.root(), key())), Ok(()));
+
+    let unsigned = tree.gen_bundle(4).unwrap();
+    let json = serde_json::to_value(&unsigned).unwrap();
+
+    assert!(json.get("signature").is_none());
+    assert_eq!(json["tree_head"]["tree_size"], 6);
+    assert_eq!(serde_json::from_value::<ProofBundle<Vec<u8>>>(json).unwrap(), unsigned);
+}
@@ -0,0 +1,146 @@
+#![cfg(feature="serialization-serde")]
+
+extern crate merkle;
+extern crate serde_json;
+
+use std::fs::File;
+
+use merkle::digest::{Algorithm, SHA256};
+
+use merkle::{MerkleTree, ProofData, ProofEnvelope, ENVELOPE_VERSION, Error};
+use merkle::{HashingScheme, ValidationPolicy};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+/// The root of the tree of `"a"`, `"b"` and `"c"`, which the fixtures prove `"b"` to be part of.
+static FIXTURE_ROOT: &'static str = "36642e73c2540ab121e3a6bf9545b0a24982cd830eb13d3cd19de3ce6c021ec1";
+
+fn fixture(name: &str) -> serde_json::Value {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    serde_json::from_reader(File::open(path).unwrap()).unwrap()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn tree() -> MerkleTree<String> {
+    MerkleTree::from_vec(digest, vec!["a".to_string(), "b".to_string(), "c".to_string()])
+}
+
+#[test]
+fn test_envelope_legacy_fixtures() {
+    assert_eq!(to_hex(tree().root_hash()), FIXTURE_ROOT);
+
+    for name in &["proof_data_unnamed.json", "proof_data_named.json"] {
+        let envelope: ProofEnvelope<String> = serde_json::from_value(fixture(name)).unwrap();
+
+        assert_eq!(envelope.version, 0);
+        assert!(envelope.extensions.is_empty());
+        assert_eq!(envelope.proof.value, "b");
+
+        let proof = envelope.into_proof(digest).unwrap();
+        assert!(proof.validate(tree().root_hash()));
+
+        // Proofs serialized before their scheme was recorded were domain separated
+        let policy = ValidationPolicy::new().required_scheme(HashingScheme::DomainSeparated);
+        assert_eq!(proof.scheme, HashingScheme::DomainSeparated);
+        assert_eq!(proof.validate_with_policy(tree().root_hash(), &policy), Ok(()));
+    }
+}
+
+#[test]
+fn test_envelope_v1_fixture() {
+    let json = fixture("proof_envelope_v1.json");
+    let envelope: ProofEnvelope<String> = serde_json::from_value(json.clone()).unwrap();
+
+    assert_eq!(envelope.version, 1);
+    assert_eq!(envelope.proof.tree_size, Some(3));
+    assert_eq!(envelope.extensions["salt"], "9f86d081884c7d65");
+    assert_eq!(envelope.extensions["x-origin"]["shard"], 3);
+
+    // Unknown extensions are written back as they were read
+    assert_eq!(serde_json::to_value(&envelope).unwrap(), json);
+
+    assert!(envelope.into_proof(digest).unwrap().validate(tree().root_hash()));
+}
+
+#[test]
+fn test_envelope_round_trip() {
+    let data = tree().gen_proof("c".to_string()).unwrap().into_data();
+    let envelope = data.clone().into_envelope();
+
+    assert_eq!(envelope.version, ENVELOPE_VERSION);
+
+    let json = serde_json::to_value(&envelope).unwrap();
+    assert_eq!(json, serde_json::json!({ "version": ENVELOPE_VERSION, "proof": data }));
+
+    let read: ProofEnvelope<String> = serde_json::from_value(json).unwrap();
+    assert_eq!(read, envelope);
+
+    // A legacy envelope keeps its version
+    let legacy = ProofEnvelope::<String>::from_json(serde_json::to_value(&data).unwrap()).unwrap();
+    let read: ProofEnvelope<String> = serde_json::from_value(serde_json::to_value(&legacy).unwrap()).unwrap();
+    assert_eq!(read.version, 0);
+    assert_eq!(read.proof, data);
+}
+
+#[test]
+fn test_envelope_rejects_newer_versions() {
+    let mut json = fixture("proof_envelope_v1.json");
+
+    json["version"] = serde_json::Value::from(ENVELOPE_VERSION + 1);
+    assert_eq!(
+        ProofEnvelope::<String>::from_json(json.clone()).unwrap_err(),
+        Error::UnsupportedVersion {
+            found: u32::from(ENVELOPE_VERSION) + 1,
+            max_supported: u32::from(ENVELOPE_VERSION),
+        }
+    );
+
+    let err = serde_json::from_value::<ProofEnvelope<String>>(json.clone()).unwrap_err();
+    assert!(err.to_string().contains("version"));
+
+    json["version"] = serde_json::Value::from(u64::max_value());
+    assert_eq!(
+        ProofEnvelope::<String>::from_json(json).unwrap_err(),
+        Error::UnsupportedVersion {
+            found: u32::max_value(),
+            max_supported: u32::from(ENVELOPE_VERSION),
+        }
+    );
+}
+
+#[test]
+fn test_envelope_rejects_malformed() {
+    let json = fixture("proof_envelope_v1.json");
+
+    let mut missing = json.clone();
+    missing.as_object_mut().unwrap().remove("proof");
+    assert_eq!(
+        ProofEnvelope::<String>::from_json(missing).unwrap_err(),
+        Error::MissingField("proof")
+    );
+
+    let mut version = json.clone();
+    version["version"] = serde_json::Value::from("1");
+    assert!(ProofEnvelope::<String>::from_json(version).is_err());
+
+    let mut extensions = json.clone();
+    extensions["extensions"] = serde_json::Value::from(vec![1, 2]);
+    assert!(ProofEnvelope::<String>::from_json(extensions).is_err());
+
+    assert!(ProofEnvelope::<String>::from_json(serde_json::Value::from(1)).is_err());
+
+    // Data which is not a legacy proof either
+    let err = ProofEnvelope::<String>::from_json(serde_json::json!({ "root_hash": [] })).unwrap_err();
+    match err {
+        Error::Decode(_) => {}
+        err => panic!("unexpected error {:?}", err),
+    }
+
+    // Readers of bare data reject envelopes rather than misreading them
+    let data: Result<ProofData<String>, _> = serde_json::from_value(json);
+    assert!(data.is_err());
+}
@@ -0,0 +1,108 @@
+extern crate merkle;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use merkle::digest::{Algorithm, Context, SHA256};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, TryHashable, HashError, Error};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+/// A block whose bytes are read from a storage, which may have lost it.
+#[derive(Clone, Debug, PartialEq)]
+struct StoredBlock {
+    key: usize,
+    storage: Arc<HashMap<usize, Vec<u8>>>,
+}
+
+impl TryHashable for StoredBlock {
+    fn try_update_context(&self, context: &mut Context) -> Result<(), HashError> {
+        let bytes = self.storage.get(&self.key).ok_or_else(|| HashError::new(format!("block {} is lost", self.key)))?;
+
+        context.update(bytes);
+        Ok(())
+    }
+}
+
+/// Blocks `0` to `count - 1`, block `lost` being missing from the storage.
+fn blocks(count: usize, lost: Option<usize>) -> Vec<StoredBlock> {
+    let storage = (0..count)
+        .filter(|&key| Some(key) != lost)
+        .map(|key| (key, format!("block {}", key).into_bytes()))
+        .collect::<HashMap<_, _>>();
+
+    let storage = Arc::new(storage);
+
+    (0..count).map(|key| StoredBlock { key: key, storage: storage.clone() }).collect()
+}
+
+fn contents(count: usize) -> Vec<Vec<u8>> {
+    (0..count).map(|key| format!("block {}", key).into_bytes()).collect()
+}
+
+#[test]
+fn test_try_from_vec() {
+    let tree = MerkleTree::try_from_vec(digest, blocks(7, None)).unwrap();
+    assert_eq!(tree.root_hash(), MerkleTree::from_vec(digest, contents(7)).root_hash());
+
+    // Hashable values never fail
+    let tree = MerkleTree::try_from_vec(digest, contents(7)).unwrap();
+    assert_eq!(tree, MerkleTree::from_vec(digest, contents(7)));
+
+    let err = MerkleTree::try_from_vec(digest, blocks(7, Some(4))).unwrap_err();
+
+    assert_eq!(err, Error::LeafHash { index: 4, cause: HashError::new("block 4 is lost") });
+    assert_eq!(err.to_string(), "cannot hash leaf 4: block 4 is lost");
+}
+
+#[test]
+fn test_try_push() {
+    let lost = blocks(6, Some(5));
+    let mut tree = MerkleTree::try_from_vec(digest, lost[..5].to_vec()).unwrap();
+    let root_hash = tree.root_hash().clone();
+
+    assert_eq!(tree.try_push(lost[5].clone()), Err(Error::LeafHash { index: 5, cause: HashError::new("block 5 is lost") }));
+    assert_eq!(tree.count(), 5);
+    assert_eq!(tree.root_hash(), &root_hash);
+
+    let mut expected = MerkleTree::from_vec(digest, contents(5));
+
+    for block in blocks(8, None).into_iter().skip(5) {
+        assert_eq!(tree.try_push(block), Ok(true));
+    }
+
+    expected.extend(contents(8).into_iter().skip(5));
+    assert_eq!(tree.root_hash(), expected.root_hash());
+
+    // The limit on the number of leaves still applies
+    let mut tree = MerkleTreeBuilder::new(digest).max_leaves(1).build_from_vec(contents(1));
+    assert_eq!(tree.try_push(b"block 1".to_vec()), Err(Error::TooManyLeaves { max: 1 }));
+}
+
+#[test]
+fn test_try_validate_value() {
+    let values = blocks(5, None);
+    let tree = MerkleTree::try_from_vec(digest, values.clone()).unwrap();
+
+    // The tree holds the blocks, whose proofs are checked against the storage they point to
+    let mut proof = tree.gen_nth_proof(2).unwrap();
+    assert_eq!(proof.try_validate_value(tree.root_hash()), Ok(true));
+    assert_eq!(proof.try_validate_value(&[0; 32]), Ok(false));
+
+    proof.value = blocks(5, None).swap_remove(3);
+    assert_eq!(proof.try_validate_value(tree.root_hash()), Ok(false));
+
+    proof.value = blocks(5, Some(3)).swap_remove(3);
+    assert_eq!(proof.try_validate_value(tree.root_hash()), Err(HashError::new("block 3 is lost")));
+
+    let tree = MerkleTree::from_vec(digest, contents(5));
+    let mut proof = tree.gen_nth_proof(2).unwrap();
+
+    assert!(proof.validate_value(tree.root_hash()));
+
+    proof.value = b"block 3".to_vec();
+    assert!(proof.validate(tree.root_hash()));
+    assert!(!proof.validate_value(tree.root_hash()));
+}
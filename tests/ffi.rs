@@ -0,0 +1,82 @@
+#![cfg(feature="ffi")]
+
+extern crate merkle;
+
+use std::ptr;
+
+use merkle::digest::{Algorithm, SHA256, SHA512};
+
+use merkle::MerkleTree;
+use merkle::ffi::*;
+use merkle::wire::algorithm_tag;
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+#[test]
+fn test_ffi_validate() {
+    let values = (0..10).map(|i| vec![i]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let root = tree.root_hash();
+    let tag = algorithm_tag(digest);
+
+    for value in values {
+        let bytes = tree.gen_proof(value).unwrap().to_bytes();
+        let handle = unsafe { merkle_proof_parse(bytes.as_ptr(), bytes.len()) };
+        assert!(!handle.is_null());
+
+        let valid = unsafe { merkle_proof_validate(handle, root.as_ptr(), root.len(), tag) };
+        assert_eq!(valid, MERKLE_PROOF_VALID);
+
+        let wrong_root = vec![0; root.len()];
+        let invalid = unsafe {
+            merkle_proof_validate(handle, wrong_root.as_ptr(), wrong_root.len(), tag)
+        };
+        assert_eq!(invalid, MERKLE_PROOF_INVALID);
+
+        let other_tag = algorithm_tag(&SHA512);
+        let mismatch = unsafe {
+            merkle_proof_validate(handle, root.as_ptr(), root.len(), other_tag)
+        };
+        assert_eq!(mismatch, MERKLE_ERROR_ALGORITHM);
+
+        unsafe { merkle_proof_free(handle) }
+    }
+}
+
+#[test]
+fn test_ffi_parse_errors() {
+    let tree = MerkleTree::from_vec(digest, vec![vec![1], vec![2]]);
+    let bytes = tree.gen_proof(vec![1]).unwrap().to_bytes();
+
+    assert!(unsafe { merkle_proof_parse(ptr::null(), 10) }.is_null());
+
+    for len in 0..bytes.len() {
+        assert!(unsafe { merkle_proof_parse(bytes.as_ptr(), len) }.is_null());
+    }
+
+    let mut unknown = bytes.clone();
+    unknown[5] = 0;
+    assert!(unsafe { merkle_proof_parse(unknown.as_ptr(), unknown.len()) }.is_null());
+}
+
+#[test]
+fn test_ffi_null_pointers() {
+    let tree = MerkleTree::from_vec(digest, vec![vec![1], vec![2]]);
+    let root = tree.root_hash();
+    let bytes = tree.gen_proof(vec![1]).unwrap().to_bytes();
+    let handle = unsafe { merkle_proof_parse(bytes.as_ptr(), bytes.len()) };
+    let tag = algorithm_tag(digest);
+
+    assert_eq!(
+        unsafe { merkle_proof_validate(ptr::null(), root.as_ptr(), root.len(), tag) },
+        MERKLE_ERROR_NULL_POINTER
+    );
+    assert_eq!(
+        unsafe { merkle_proof_validate(handle, ptr::null(), 0, tag) },
+        MERKLE_ERROR_NULL_POINTER
+    );
+
+    unsafe { merkle_proof_free(handle) }
+    unsafe { merkle_proof_free(ptr::null_mut()) }
+}
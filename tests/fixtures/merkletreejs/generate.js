@@ -0,0 +1,77 @@
+// Generates the fixtures of tests/merkletreejs.rs: the root and the proof of every leaf
+// of trees over the same leaves, as merkletreejs 0.3 builds them with SHA-256, the leaves
+// being hashed (`hashLeaves: true`), with its default options and with `sortPairs: true`.
+//
+// It reimplements the construction of `MerkleTree` and `getProof` of merkletreejs with
+// the `crypto` module of Node, so that it runs without the package: an odd node is carried
+// up to the next level as is, a proof skips the levels where the node has no sibling, and
+// its hashes are written as `data.toString('hex')` and its root as `getHexRoot()`.
+//
+// Run with `node generate.js` from this directory.
+
+const crypto = require('crypto')
+const fs = require('fs')
+
+const sha256 = (data) => crypto.createHash('sha256').update(data).digest()
+
+function layers (leaves, sortPairs) {
+  const layers = [leaves.map((leaf) => sha256(Buffer.from(leaf)))]
+
+  while (layers[layers.length - 1].length > 1) {
+    const nodes = layers[layers.length - 1]
+    const next = []
+
+    for (let i = 0; i < nodes.length; i += 2) {
+      if (i + 1 === nodes.length) {
+        next.push(nodes[i])
+        continue
+      }
+
+      const pair = [nodes[i], nodes[i + 1]]
+      if (sortPairs) {
+        pair.sort(Buffer.compare)
+      }
+
+      next.push(sha256(Buffer.concat(pair)))
+    }
+
+    layers.push(next)
+  }
+
+  return layers
+}
+
+function getProof (layers, index) {
+  const proof = []
+
+  for (const layer of layers.slice(0, -1)) {
+    const isRightNode = index % 2
+    const pairIndex = isRightNode ? index - 1 : index + 1
+
+    if (pairIndex < layer.length) {
+      proof.push({
+        position: isRightNode ? 'left' : 'right',
+        data: layer[pairIndex].toString('hex')
+      })
+    }
+
+    index = (index / 2) | 0
+  }
+
+  return proof
+}
+
+const leaves = ['a', 'b', 'c', 'd', 'e', 'f', 'g']
+
+for (const [file, sortPairs] of [['sha256_default.json', false], ['sha256_sort_pairs.json', true]]) {
+  const tree = layers(leaves, sortPairs)
+
+  const fixture = {
+    options: { hashLeaves: true, sortPairs: sortPairs },
+    leaves: leaves,
+    root: '0x' + tree[tree.length - 1][0].toString('hex'),
+    proofs: leaves.map((_, index) => getProof(tree, index))
+  }
+
+  fs.writeFileSync(file, JSON.stringify(fixture, null, 2) + '\n')
+}
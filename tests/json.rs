@@ -0,0 +1,181 @@
+#![cfg(feature="json")]
+
+extern crate merkle;
+extern crate serde_json;
+
+use serde_json::Value;
+
+use merkle::digest::{Algorithm, SHA256};
+
+use merkle::{MerkleTree, JsonLeaf};
+use merkle::json::canonical_json;
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+fn parse(text: &str) -> Value {
+    serde_json::from_str(text).unwrap()
+}
+
+fn documents() -> Vec<Value> {
+    vec![
+        parse(r#"{"name": "alice", "tags": ["a", "b"], "age": 30}"#),
+        parse(r#"[1.5, null, true, "é\n"]"#),
+        parse(r#"{"nested": {"z": 1, "a": {"y": false, "b": []}}}"#),
+    ]
+}
+
+#[test]
+fn test_json_key_order_and_whitespace() {
+    let a = parse(r#"{"b": 1, "a": {"d": [1, 2], "c": "x"}}"#);
+    let b = parse("{\n  \"a\" : { \"c\":\"x\", \"d\":[ 1,2 ] },\n  \"b\":1\n}");
+
+    assert_eq!(canonical_json(&a), r#"{"a":{"c":"x","d":[1,2]},"b":1}"#);
+    assert_eq!(canonical_json(&a), canonical_json(&b));
+
+    let first = MerkleTree::from_vec(digest, vec![a.clone(), parse("[]")]);
+    let second = MerkleTree::from_vec(digest, vec![b, parse("[ ]")]);
+
+    assert_eq!(first.root_hash(), second.root_hash());
+    assert!(first.gen_proof(a).unwrap().validate(second.root_hash()));
+}
+
+#[test]
+fn test_json_key_order_is_utf16() {
+    // U+FF61 sorts before U+1F600 by code point, but after it by UTF-16 code unit,
+    // as the latter is encoded as a surrogate pair starting with 0xD83D
+    let value = parse(r#"{"｡": 1, "😀": 2, "a": 3}"#);
+
+    assert_eq!(canonical_json(&value), "{\"a\":3,\"\u{1f600}\":2,\"\u{ff61}\":1}");
+}
+
+#[test]
+fn test_json_strings() {
+    let value = Value::String("\"\\/\u{8}\t\n\u{c}\r\u{1}\u{1f}\u{7f}é".to_string());
+
+    assert_eq!(canonical_json(&value), "\"\\\"\\\\/\\b\\t\\n\\f\\r\\u0001\\u001f\u{7f}é\"");
+}
+
+#[test]
+fn test_json_numbers() {
+    // `1`, `1.0` and `1e0` are the same double, hence written and hashed the same
+    let ones = vec![parse("1"), parse("1.0"), parse("1e0"), parse("10e-1")];
+
+    for one in &ones {
+        assert_eq!(canonical_json(one), "1");
+    }
+
+    let tree = MerkleTree::from_vec(digest, ones.clone());
+
+    for one in &ones {
+        assert_eq!(tree.gen_proof(one.clone()).unwrap().lemma, tree.gen_nth_proof(0).unwrap().lemma);
+    }
+
+    let cases = vec![
+        ("-0", "0"),
+        ("0.0", "0"),
+        ("-1.50", "-1.5"),
+        ("123456789", "123456789"),
+        ("1e20", "100000000000000000000"),
+        ("1e21", "1e+21"),
+        ("1.5e300", "1.5e+300"),
+        ("0.000001", "0.000001"),
+        ("1e-7", "1e-7"),
+        ("-1.25e-8", "-1.25e-8"),
+        ("0.1", "0.1"),
+        ("333333333.33333329", "333333333.3333333"),
+        ("4.50", "4.5"),
+        ("9007199254740993", "9007199254740992"),
+        ("-9223372036854775808", "-9223372036854776000"),
+    ];
+
+    for (text, canonical) in cases {
+        assert_eq!(canonical_json(&parse(text)), canonical, "{}", text);
+    }
+}
+
+#[test]
+fn test_json_leaf() {
+    let values = documents();
+    let leaves = values.iter().map(JsonLeaf::new).collect::<Vec<_>>();
+
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let leaf_tree = MerkleTree::from_vec(digest, leaves.clone());
+
+    assert_eq!(tree.root_hash(), leaf_tree.root_hash());
+
+    for (value, leaf) in values.iter().zip(&leaves) {
+        assert_eq!(leaf.as_bytes(), canonical_json(value).as_bytes());
+        assert_eq!(&leaf.to_value().unwrap(), value);
+        assert_eq!(&JsonLeaf::from(value.clone()), leaf);
+        assert_eq!(JsonLeaf::from(Vec::from(leaf.clone())), *leaf);
+    }
+
+    // Bytes read back are kept as they are
+    let raw = JsonLeaf::from(b"{\"b\": 1, \"a\": 2}".to_vec());
+
+    assert_eq!(raw.to_string(), "{\"b\": 1, \"a\": 2}");
+    assert_ne!(raw, JsonLeaf::new(&raw.to_value().unwrap()));
+    assert!(JsonLeaf::from(b"{".to_vec()).to_value().is_err());
+}
+
+#[cfg(feature="serialization-serde")]
+#[test]
+fn test_json_leaf_serde_round_trip() {
+    use merkle::ProofData;
+
+    let tree = MerkleTree::from_vec(digest, documents().iter().map(JsonLeaf::new).collect());
+
+    for index in 0..tree.count() {
+        let proof = tree.gen_nth_proof(index).unwrap();
+        let json = serde_json::to_value(&proof).unwrap();
+
+        // The value is embedded as the JSON value itself
+        assert_eq!(json["value"], documents()[index]);
+
+        let data: ProofData<JsonLeaf> = serde_json::from_value(json).unwrap();
+        let res = data.into_proof(digest).unwrap();
+
+        assert_eq!(res.value, proof.value);
+        assert!(res.validate(tree.root_hash()));
+    }
+
+    // An object whose keys are out of order is canonicalized on the way in
+    let leaf: JsonLeaf = serde_json::from_str(r#"{"b": 1, "a": [1.0]}"#).unwrap();
+    assert_eq!(leaf.as_bytes(), br#"{"a":[1],"b":1}"#);
+
+    assert!(serde_json::to_string(&JsonLeaf::from(b"{".to_vec())).is_err());
+}
+
+#[cfg(feature="serialization-protobuf")]
+#[test]
+fn test_json_leaf_protobuf_round_trip() {
+    use merkle::Proof;
+
+    let tree = MerkleTree::from_vec(digest, documents().iter().map(JsonLeaf::new).collect());
+
+    for index in 0..tree.count() {
+        let proof = tree.gen_nth_proof(index).unwrap();
+        let bytes = proof.clone().write_to_bytes().unwrap();
+        let res = Proof::<JsonLeaf>::parse_from_bytes(&bytes, digest).unwrap().unwrap();
+
+        assert_eq!(res.value, proof.value);
+        assert!(res.validate(tree.root_hash()));
+    }
+}
+
+#[cfg(feature="serialization-prost")]
+#[test]
+fn test_json_leaf_prost_round_trip() {
+    use merkle::prost_proto;
+
+    let tree = MerkleTree::from_vec(digest, documents().iter().map(JsonLeaf::new).collect());
+
+    for index in 0..tree.count() {
+        let proof = tree.gen_nth_proof(index).unwrap();
+        let res = prost_proto::parse_proof::<JsonLeaf>(digest, &prost_proto::write_proof(&proof)).unwrap();
+
+        assert_eq!(res, proof);
+        assert!(res.validate(tree.root_hash()));
+    }
+}
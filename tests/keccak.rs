@@ -0,0 +1,92 @@
+#![cfg(feature="keccak")]
+
+extern crate merkle;
+
+use merkle::keccak::{self, KeccakHash};
+
+/// The root hash of the tree over the Keccak-256 hashes of `"a"` to `"e"`, and the proof of
+/// each leaf, from the leaf up, computed independently of this crate, and checked with the
+/// `processProof` algorithm of OpenZeppelin's `MerkleProof`.
+static ROOT: &'static str = "1dd0d2a6ae466d665cb26e1a31f07c57ae5df7d2bc559cd5826d417be9141a5d";
+
+static LEAVES: [&'static str; 5] = [
+    "3ac225168df54212a25c1c01fd35bebfea408fdac2e31ddd6f80a4bbf9a5f1cb",
+    "b5553de315e0edf504d9150af82dafa5c4667fa618ed0a6f19c69b41166c5510",
+    "0b42b6393c1f53060fe3ddbfcd7aadcca894465a5a438f69c87d790b2299b9b2",
+    "f1918e8562236eb17adc8502332f4c9c82bc14e19bfc0aa10ab674ff75b3d2f3",
+    "a8982c89d80987fb9a510e25981ee9170206be21af3c8e0eb312ef1d3382e761",
+];
+
+static PROOFS: [&'static [&'static str]; 5] = [
+    &[
+        "b5553de315e0edf504d9150af82dafa5c4667fa618ed0a6f19c69b41166c5510",
+        "d253a52d4cb00de2895e85f2529e2976e6aaaa5c18106b68ab66813e14415669",
+        "a8982c89d80987fb9a510e25981ee9170206be21af3c8e0eb312ef1d3382e761",
+    ],
+    &[
+        "3ac225168df54212a25c1c01fd35bebfea408fdac2e31ddd6f80a4bbf9a5f1cb",
+        "d253a52d4cb00de2895e85f2529e2976e6aaaa5c18106b68ab66813e14415669",
+        "a8982c89d80987fb9a510e25981ee9170206be21af3c8e0eb312ef1d3382e761",
+    ],
+    &[
+        "f1918e8562236eb17adc8502332f4c9c82bc14e19bfc0aa10ab674ff75b3d2f3",
+        "805b21d846b189efaeb0377d6bb0d201b3872a363e607c25088f025b0c6ae1f8",
+        "a8982c89d80987fb9a510e25981ee9170206be21af3c8e0eb312ef1d3382e761",
+    ],
+    &[
+        "0b42b6393c1f53060fe3ddbfcd7aadcca894465a5a438f69c87d790b2299b9b2",
+        "805b21d846b189efaeb0377d6bb0d201b3872a363e607c25088f025b0c6ae1f8",
+        "a8982c89d80987fb9a510e25981ee9170206be21af3c8e0eb312ef1d3382e761",
+    ],
+    &["68203f90e9d07dc5859259d7536e87a6ba9d345f2552b5b9de2999ddce9ce1bf"],
+];
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_keccak256() {
+    assert_eq!(
+        to_hex(&keccak::keccak256(b"")),
+        "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+    );
+    assert_eq!(
+        to_hex(&keccak::keccak256(b"abc")),
+        "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+    );
+}
+
+#[test]
+fn test_keccak_fixtures() {
+    let leaves = ["a", "b", "c", "d", "e"]
+        .iter()
+        .map(|value| keccak::keccak256(value.as_bytes()))
+        .collect::<Vec<KeccakHash>>();
+
+    assert_eq!(leaves.iter().map(|leaf| to_hex(leaf)).collect::<Vec<_>>(), LEAVES);
+
+    let root = keccak::root(&leaves).unwrap();
+    assert_eq!(to_hex(&root), ROOT);
+
+    for (index, expected) in PROOFS.iter().enumerate() {
+        let path = keccak::sorted_path(&leaves, index).unwrap();
+        assert_eq!(path.iter().map(|hash| to_hex(hash)).collect::<Vec<_>>(), *expected);
+
+        assert!(keccak::validate_sorted_path(&root, &leaves[index], &path));
+        assert!(!keccak::validate_sorted_path(&root, &leaves[(index + 1) % 5], &path));
+        assert!(!keccak::validate_sorted_path(&root, &leaves[index], &path[1..]));
+    }
+
+    assert_eq!(keccak::sorted_path(&leaves, 5), None);
+}
+
+#[test]
+fn test_keccak_empty_and_single() {
+    assert_eq!(keccak::root(&[]), None);
+
+    let leaf = keccak::keccak256(b"a");
+    assert_eq!(keccak::root(&[leaf]), Some(leaf));
+    assert_eq!(keccak::sorted_path(&[leaf], 0), Some(vec![]));
+    assert!(keccak::validate_sorted_path(&leaf, &leaf, &[]));
+}
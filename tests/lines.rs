@@ -0,0 +1,95 @@
+
+extern crate merkle;
+
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+
+use merkle::digest::{Algorithm, SHA256};
+
+use merkle::MerkleTree;
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+/// Root of the tree over the lines `id,amount`, `1,100`, `2,250` and `3,100`.
+static CSV_ROOT: &'static str = "338b25a9a76e4f30ba0048eb3e1608925b9fcf8823e45449cc2adf771f6bf488";
+
+/// Root of the tree over the lines `id,amount`, `1,100`, `` and `2,250\r3,100`.
+static MIXED_ROOT: &'static str = "023a64cab77b0a70333547d3497ac3191f71c2b369f48b1a4381a8b91c11c586";
+
+fn open(name: &str) -> BufReader<File> {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    BufReader::new(File::open(path).unwrap())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_from_lines_fixtures() {
+    let fixtures = [
+        "lines_lf.csv",
+        "lines_crlf.csv",
+        "lines_no_trailing_newline.csv",
+    ];
+
+    for name in &fixtures {
+        let tree = MerkleTree::from_lines(digest, open(name)).unwrap();
+
+        assert_eq!(tree.count(), 4);
+        assert_eq!(to_hex(tree.root_hash()), CSV_ROOT);
+    }
+
+    let tree = MerkleTree::from_lines(digest, open("lines_mixed.csv")).unwrap();
+
+    assert_eq!(tree.count(), 4);
+    assert_eq!(to_hex(tree.root_hash()), MIXED_ROOT);
+}
+
+#[test]
+fn test_from_lines_matches_from_vec() {
+    let tree = MerkleTree::from_lines(digest, open("lines_mixed.csv")).unwrap();
+    let values = vec!["id,amount", "1,100", "", "2,250\r3,100"];
+    let expected = MerkleTree::from_vec(digest, values.clone());
+
+    assert_eq!(tree.root_hash(), expected.root_hash());
+    assert_eq!(tree.iter().collect::<Vec<_>>(), values);
+}
+
+#[test]
+fn test_from_lines_variants_agree() {
+    let fixtures = [
+        "lines_lf.csv",
+        "lines_crlf.csv",
+        "lines_no_trailing_newline.csv",
+        "lines_mixed.csv",
+    ];
+
+    for name in &fixtures {
+        let text = MerkleTree::from_lines(digest, open(name)).unwrap();
+        let bytes = MerkleTree::from_byte_lines(digest, open(name)).unwrap();
+        let hashed = MerkleTree::from_lines_hashed(digest, open(name)).unwrap();
+
+        assert_eq!(text.root_hash(), bytes.root_hash());
+        assert_eq!(text.root_hash(), hashed.root_hash());
+        assert_eq!(text.count(), hashed.count());
+        assert_eq!(text.height(), hashed.height());
+    }
+}
+
+#[test]
+fn test_from_lines_empty_input() {
+    let tree = MerkleTree::from_lines(digest, Cursor::new("")).unwrap();
+
+    assert!(tree.is_empty());
+    assert_eq!(tree.root_hash(), MerkleTree::<String>::from_vec(digest, vec![]).root_hash());
+}
+
+#[test]
+fn test_from_lines_invalid_utf8() {
+    let input: &[u8] = b"valid\n\xff\xfe\n";
+
+    assert!(MerkleTree::from_lines(digest, input).is_err());
+    assert_eq!(MerkleTree::from_byte_lines(digest, input).unwrap().count(), 2);
+}
@@ -0,0 +1,136 @@
+//! The JSON shape of the proofs of merkletreejs: the fixtures in `tests/fixtures/merkletreejs`
+//! verify as proofs of this crate, and the proofs of the same trees built by this crate
+//! convert to the same JSON.
+
+#![cfg(feature = "serialization-serde")]
+
+extern crate merkle;
+extern crate serde_json;
+
+use std::fs::File;
+
+use serde_json::Value;
+
+use merkle::{MerkleTree, MerkleTreeBuilder, Proof, HashingScheme, Error, SHA256, MAX_TREE_DEPTH};
+
+// The fixtures hold the root and the proof of every leaf of the trees of `a` to `g` which
+// merkletreejs 0.3 builds with SHA-256 and `hashLeaves: true`, with its default options and
+// with `sortPairs: true`. They are written by `generate.js`, which follows the construction
+// of the trees and proofs of merkletreejs without depending on it, see the script.
+
+fn fixture(name: &str) -> Value {
+    let path = format!("{}/tests/fixtures/merkletreejs/{}", env!("CARGO_MANIFEST_DIR"), name);
+    serde_json::from_reader(File::open(path).unwrap()).unwrap()
+}
+
+fn leaves(fixture: &Value) -> Vec<String> {
+    fixture["leaves"].as_array().unwrap().iter().map(|leaf| leaf.as_str().unwrap().to_string()).collect()
+}
+
+fn tree(scheme: HashingScheme, leaves: Vec<String>) -> MerkleTree<String> {
+    MerkleTreeBuilder::new(SHA256).hashing_scheme(scheme).build_from_vec(leaves)
+}
+
+fn check_fixture(name: &str, scheme: HashingScheme) {
+    let fixture = fixture(name);
+    let root = fixture["root"].as_str().unwrap();
+    let tree = tree(scheme, leaves(&fixture));
+
+    for (index, (leaf, json)) in leaves(&fixture).into_iter().zip(fixture["proofs"].as_array().unwrap()).enumerate() {
+        let proof = Proof::from_mtjs_json_with_scheme(SHA256, scheme, leaf, root, json).unwrap();
+        assert!(proof.validate(tree.root_hash()), "proof of leaf {}", index);
+
+        let ours = tree.gen_nth_proof(index).unwrap();
+        assert_eq!(ours.to_mtjs_json(), *json, "proof of leaf {}", index);
+        assert_eq!(ours.mtjs_root(), root);
+    }
+}
+
+#[test]
+fn test_default_options_fixture() {
+    check_fixture("sha256_default.json", HashingScheme::Unprefixed);
+}
+
+#[test]
+fn test_sort_pairs_fixture() {
+    check_fixture("sha256_sort_pairs.json", HashingScheme::SortedPairs);
+}
+
+#[test]
+fn test_from_mtjs_json_reads_buffers_and_prefixed_hex() {
+    let fixture = fixture("sha256_default.json");
+    let root = fixture["root"].as_str().unwrap();
+    let expected = Proof::from_mtjs_json(SHA256, "c", root, &fixture["proofs"][2]).unwrap();
+
+    // As `JSON.stringify` writes the proof returned by `getProof`, whose hashes are `Buffer`s
+    let buffers = fixture["proofs"][2]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|sibling| {
+            let hex = sibling["data"].as_str().unwrap();
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| Value::from(u8::from_str_radix(&hex[i..i + 2], 16).unwrap()))
+                .collect::<Vec<_>>();
+
+            serde_json::json!({ "position": sibling["position"], "data": { "type": "Buffer", "data": bytes } })
+        })
+        .collect::<Vec<_>>();
+
+    let proof = Proof::from_mtjs_json(SHA256, "c", root, &Value::Array(buffers)).unwrap();
+    assert_eq!(proof, expected);
+
+    let prefixed = fixture["proofs"][2]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|sibling| {
+            let hex = format!("0x{}", sibling["data"].as_str().unwrap().to_uppercase());
+            serde_json::json!({ "position": sibling["position"], "data": hex })
+        })
+        .collect::<Vec<_>>();
+
+    let proof = Proof::from_mtjs_json(SHA256, "c", &root[2..], &Value::Array(prefixed)).unwrap();
+    assert_eq!(proof, expected);
+}
+
+#[test]
+fn test_from_mtjs_json_rejects_malformed_proofs() {
+    let fixture = fixture("sha256_default.json");
+    let root = fixture["root"].as_str().unwrap();
+    let hash = fixture["proofs"][0][0]["data"].clone();
+
+    let decode = |json: Value| match Proof::from_mtjs_json(SHA256, "a", root, &json) {
+        Err(Error::Decode(_)) => (),
+        other => panic!("{:?} for {}", other, json),
+    };
+
+    decode(serde_json::json!({ "position": "left", "data": hash }));
+    decode(serde_json::json!([{ "position": "up", "data": hash }]));
+    decode(serde_json::json!([{ "data": hash }]));
+    decode(serde_json::json!([{ "position": "left" }]));
+    decode(serde_json::json!([{ "position": "left", "data": "not hex" }]));
+    decode(serde_json::json!([{ "position": "left", "data": { "type": "Buffer", "data": [256] } }]));
+    decode(serde_json::json!([{ "position": "left", "data": 1 }]));
+
+    match Proof::from_mtjs_json(SHA256, "a", root, &serde_json::json!([{ "position": "left", "data": "00ff" }])) {
+        Err(Error::HashLengthMismatch { expected: 32, found: 2 }) => (),
+        other => panic!("{:?}", other),
+    }
+
+    match Proof::from_mtjs_json(SHA256, "a", "0x00", &serde_json::json!([])) {
+        Err(Error::HashLengthMismatch { expected: 32, found: 1 }) => (),
+        other => panic!("{:?}", other),
+    }
+
+    let deep = vec![serde_json::json!({ "position": "left", "data": hash }); MAX_TREE_DEPTH + 1];
+    match Proof::from_mtjs_json(SHA256, "a", root, &Value::Array(deep)) {
+        Err(Error::InvalidPath(_)) => (),
+        other => panic!("{:?}", other),
+    }
+
+    // Well formed, but for another value
+    let proof = Proof::from_mtjs_json(SHA256, "b", root, &fixture["proofs"][0]).unwrap();
+    assert!(!proof.validate(&proof.root_hash));
+}
@@ -0,0 +1,232 @@
+#![cfg(feature="metrics")]
+
+extern crate merkle;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use merkle::digest::{Algorithm, SHA256};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, OddLeafPolicy, TreeObserver, Metrics, MetricsObserver};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Event {
+    LeafHashed(usize),
+    LevelBuilt(usize, usize),
+    ProofGenerated(usize),
+    Validation(bool),
+}
+
+/// Records the events, leaving the durations out.
+#[derive(Debug, Default)]
+struct Recorder(Mutex<Vec<Event>>);
+
+impl Recorder {
+    fn take(&self) -> Vec<Event> {
+        self.0.lock().unwrap().drain(..).collect()
+    }
+
+    fn levels(&self) -> Vec<(usize, usize)> {
+        self.take()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::LevelBuilt(level, nodes) => Some((level, nodes)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl TreeObserver for Recorder {
+    fn on_leaf_hashed(&self, index: usize) {
+        self.0.lock().unwrap().push(Event::LeafHashed(index));
+    }
+
+    fn on_level_built(&self, level: usize, nodes: usize) {
+        self.0.lock().unwrap().push(Event::LevelBuilt(level, nodes));
+    }
+
+    fn on_proof_generated(&self, depth: usize, _duration: Duration) {
+        self.0.lock().unwrap().push(Event::ProofGenerated(depth));
+    }
+
+    fn on_validation(&self, valid: bool, _duration: Duration) {
+        self.0.lock().unwrap().push(Event::Validation(valid));
+    }
+}
+
+fn values(count: usize) -> Vec<String> {
+    (0..count).map(|i| i.to_string()).collect()
+}
+
+#[test]
+fn test_observer_build() {
+    let recorder = Arc::new(Recorder::default());
+    let tree = MerkleTreeBuilder::new(digest).observer(recorder.clone()).build_from_vec(values(13));
+
+    // The leaves are hashed first, in order, then the levels are built from the leaves up
+    let mut expected = (0..13).map(Event::LeafHashed).collect::<Vec<_>>();
+    expected.extend(vec![(0, 13), (1, 7), (2, 4), (3, 2), (4, 1)].into_iter().map(|(l, n)| Event::LevelBuilt(l, n)));
+
+    assert_eq!(recorder.take(), expected);
+    assert_eq!(tree, MerkleTree::from_vec(digest, values(13)));
+
+    let builders = vec![
+        MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate),
+        MerkleTreeBuilder::new(digest).fixed_depth(4),
+    ];
+
+    for builder in builders {
+        builder.observer(recorder.clone()).build_from_vec(values(13));
+        assert_eq!(recorder.levels(), vec![(0, 13), (1, 7), (2, 4), (3, 2), (4, 1)]);
+    }
+
+    // A lazy tree is only hashed when first accessed
+    let tree = MerkleTreeBuilder::new(digest).lazy(true).observer(recorder.clone()).build_from_vec(values(5));
+    assert_eq!(recorder.take(), vec![]);
+    tree.root_hash();
+    assert_eq!(recorder.take().len(), 5 + 4);
+
+    // Pushing hashes the pushed value alone, and rebuilds the levels above it
+    let mut tree = MerkleTreeBuilder::new(digest).observer(recorder.clone()).build_from_vec(values(4));
+    recorder.take();
+    tree.push("4".to_string());
+
+    let events = recorder.take();
+    assert_eq!(events[0], Event::LeafHashed(0));
+    assert_eq!(&events[1..], &[(0, 5), (1, 3), (2, 2), (3, 1)].iter().map(|&(l, n)| Event::LevelBuilt(l, n)).collect::<Vec<_>>()[..]);
+}
+
+#[test]
+fn test_observer_proofs() {
+    let recorder = Arc::new(Recorder::default());
+    let mut tree = MerkleTreeBuilder::new(digest).observer(recorder.clone()).build_from_vec(values(5));
+    recorder.take();
+
+    let proof = tree.gen_nth_proof(4).unwrap();
+    tree.gen_proof("0".to_string()).unwrap();
+    assert_eq!(tree.gen_nth_proof(5), None);
+    assert_eq!(recorder.take(), vec![Event::ProofGenerated(1), Event::ProofGenerated(3)]);
+
+    let mut forged = proof.clone();
+    forged.root_hash = vec![0; digest.output_len];
+
+    assert!(tree.verify_proof(&proof).is_ok());
+    assert!(tree.verify_proof(&forged).is_err());
+    assert!(proof.validate_observed(tree.root_hash(), &*recorder));
+    assert_eq!(recorder.take(), vec![Event::Validation(true), Event::Validation(false), Event::Validation(true)]);
+
+    // Without an observer, nothing gets notified
+    assert!(tree.set_observer(None).is_some());
+    tree.gen_nth_proof(0).unwrap();
+    tree.push("5".to_string());
+    assert_eq!(recorder.take(), vec![]);
+}
+
+#[test]
+fn test_observer_batch_proofs() {
+    let recorder = Arc::new(Recorder::default());
+    let mut tree = MerkleTreeBuilder::new(digest).observer(recorder.clone()).build_from_vec(values(5));
+    recorder.take();
+
+    let proofs = |depths: &[usize]| depths.iter().map(|&depth| Event::ProofGenerated(depth)).collect::<Vec<_>>();
+
+    // One notification per proof, none for the missing ones
+    assert_eq!(tree.prove_indices(&[0, 4, 9, 0]).iter().filter(|proof| proof.is_some()).count(), 3);
+    assert_eq!(recorder.take(), proofs(&[3, 1, 3]));
+
+    assert_eq!(tree.prove_many(&["4".to_string(), "x".to_string(), "1".to_string()]).len(), 3);
+    assert_eq!(recorder.take(), proofs(&[1, 3]));
+
+    assert_eq!(tree.all_proofs().len(), 5);
+    assert_eq!(recorder.take(), proofs(&[3, 3, 3, 3, 1]));
+
+    assert_eq!(tree.iter_proofs().take(2).count(), 2);
+    assert_eq!(recorder.take(), proofs(&[3, 3]));
+
+    let version = tree.checkpoint();
+    tree.push("5".to_string());
+    recorder.take();
+
+    assert!(tree.gen_proof_at(version, 4).is_some());
+    assert!(tree.gen_proof_at(version, 5).is_none());
+    assert_eq!(recorder.take(), proofs(&[1]));
+}
+
+#[test]
+fn test_observer_proofs_by_hash() {
+    let recorder = Arc::new(Recorder::default());
+    let values = vec!["0", "1", "0", "1", "0"].into_iter().map(String::from).collect::<Vec<_>>();
+    let tree = MerkleTreeBuilder::new(digest).observer(recorder.clone()).build_from_vec(values);
+    recorder.take();
+
+    let proofs = |depths: &[usize]| depths.iter().map(|&depth| Event::ProofGenerated(depth)).collect::<Vec<_>>();
+    let hashes = tree.leaf_hashes_vec();
+
+    assert!(tree.gen_proof_by_hash(&hashes[1]).is_some());
+    assert!(tree.gen_proof_by_hash(&[0; 3]).is_none());
+    assert!(tree.gen_proof_by_hash(&[0; 32]).is_none());
+    assert_eq!(recorder.take(), proofs(&[3]));
+
+    assert_eq!(tree.gen_proofs_by_hash(&hashes[0]).len(), 3);
+    assert_eq!(recorder.take(), proofs(&[3, 3, 1]));
+
+    assert_eq!(tree.gen_proofs_for_value("1".to_string()).len(), 2);
+    assert_eq!(recorder.take(), proofs(&[3, 3]));
+
+    assert!(tree.gen_proof_for_occurrence("0".to_string(), 2).is_some());
+    assert!(tree.gen_proof_for_occurrence("0".to_string(), 3).is_none());
+    assert!(tree.gen_proof_detached(&"1".to_string()).is_some());
+    assert!(tree.gen_proof_ref(&"x".to_string()).is_none());
+    assert_eq!(recorder.take(), proofs(&[1, 3]));
+}
+
+#[test]
+fn test_metrics_observer() {
+    let observer = Arc::new(MetricsObserver::new());
+    let tree = MerkleTreeBuilder::new(digest).observer(observer.clone()).build_from_vec(values(1000));
+
+    let metrics = observer.take_metrics();
+    assert_eq!(metrics.leaves_hashed, 1000);
+    assert_eq!(metrics.levels_built, 10);
+    assert_eq!(metrics.nodes_built, 500 + 250 + 125 + 63 + 32 + 16 + 8 + 4 + 2 + 1);
+    assert_eq!(metrics.proofs_generated, 0);
+
+    for index in 0..1000 {
+        let proof = tree.gen_nth_proof(index).unwrap();
+        assert!(proof.validate_observed(tree.root_hash(), &*observer));
+        assert!(!proof.validate_observed(&[0; 32], &*observer));
+    }
+
+    let metrics = observer.metrics();
+    assert_eq!(metrics.leaves_hashed, 0);
+    assert_eq!(metrics.proofs_generated, 1000);
+    assert_eq!(metrics.validations, 2000);
+    assert_eq!(metrics.failed_validations, 1000);
+    assert!(metrics.proof_time > Duration::from_nanos(0));
+    assert!(metrics.validation_time > Duration::from_nanos(0));
+
+    observer.take_metrics();
+    assert_eq!(observer.metrics(), Metrics::default());
+}
+
+#[cfg(feature="metrics-log")]
+#[test]
+fn test_log_observer() {
+    let tree = MerkleTreeBuilder::new(digest).observer(Arc::new(merkle::LogObserver)).build_from_vec(values(3));
+    let proof = tree.gen_nth_proof(2).unwrap();
+
+    assert!(proof.validate_observed(tree.root_hash(), &merkle::LogObserver));
+}
+
+#[cfg(feature="metrics-tracing")]
+#[test]
+fn test_tracing_observer() {
+    let tree = MerkleTreeBuilder::new(digest).observer(Arc::new(merkle::TracingObserver)).build_from_vec(values(3));
+    let proof = tree.gen_nth_proof(2).unwrap();
+
+    assert!(proof.validate_observed(tree.root_hash(), &merkle::TracingObserver));
+}
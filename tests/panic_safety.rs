@@ -0,0 +1,181 @@
+//! A panic in code of the values, or in a closure given to a mutating method, leaves the tree
+//! untouched, with the root hash of the tree built over its values.
+
+extern crate merkle;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use merkle::digest::{Algorithm, Context, SHA256};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, LeafOrdering, Hashable, SlidingWindowTree};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+/// A value which panics when hashed or compared, unless it is `Fine`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Value {
+    Fine(u8),
+    Explosive,
+}
+
+impl Hashable for Value {
+    fn update_context(&self, context: &mut Context) {
+        match *self {
+            Value::Fine(byte) => context.update(&[byte]),
+            Value::Explosive => panic!("cannot hash this value"),
+        }
+    }
+}
+
+fn key(value: &Value) -> u8 {
+    match *value {
+        Value::Fine(byte) => byte,
+        Value::Explosive => panic!("cannot compare this value"),
+    }
+}
+
+fn values(count: u8) -> Vec<Value> {
+    (0..count).rev().map(Value::Fine).collect()
+}
+
+/// Checks that `modify` panics, leaving `tree` with the given values, and consistent with them.
+fn assert_untouched<F>(tree: &mut MerkleTree<Value>, modify: F)
+where
+    F: FnOnce(&mut MerkleTree<Value>),
+{
+
+    let root_hash = tree.root_hash().clone();
+    let values = tree.iter().cloned().collect::<Vec<_>>();
+
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| modify(tree))).is_err());
+
+    assert_eq!(*tree.root_hash(), root_hash);
+    assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), values);
+    assert_eq!(tree.count(), values.len());
+
+    for index in 0..tree.count() {
+        assert!(tree.gen_nth_proof(index).unwrap().validate(&root_hash));
+    }
+
+    // The tree can still be modified afterwards
+    let mut copy = tree.clone();
+    copy.push(Value::Fine(200));
+    assert_eq!(copy.count(), values.len() + 1);
+}
+
+#[test]
+fn test_panicking_value_leaves_tree_untouched() {
+    let mut tree = MerkleTree::from_vec(digest, values(5));
+    let expected = tree.root_hash().clone();
+
+    assert_untouched(&mut tree, |tree| {
+        tree.push(Value::Explosive);
+    });
+    assert_untouched(&mut tree, |tree| {
+        let _ = tree.try_push(Value::Explosive);
+    });
+    assert_untouched(&mut tree, |tree| {
+        let _ = tree.try_extend(vec![Value::Fine(7), Value::Explosive]);
+    });
+    assert_untouched(&mut tree, |tree| {
+        let _ = tree.insert(2, Value::Explosive);
+    });
+    assert_untouched(&mut tree, |tree| {
+        let _ = tree.update(1, Value::Explosive);
+    });
+    assert_untouched(&mut tree, |tree| {
+        let _ = tree.apply_updates(vec![(0, Value::Fine(9)), (3, Value::Explosive)]);
+    });
+    assert_untouched(&mut tree, |tree| tree.rebuild_from_vec(vec![Value::Fine(1), Value::Explosive]));
+
+    assert_eq!(*tree.root_hash(), expected);
+}
+
+#[test]
+fn test_panicking_closure_leaves_tree_untouched() {
+    let mut tree = MerkleTree::from_vec(digest, values(6));
+    let mut calls = 0;
+
+    assert_untouched(&mut tree, |tree| {
+        tree.retain(|_| {
+            calls += 1;
+            assert!(calls < 4, "cannot tell");
+            calls % 2 == 0
+        });
+    });
+
+    assert_untouched(&mut tree, |tree| {
+        tree.retain_collect(|value| key(value) > 2 && panic!("cannot tell"));
+    });
+}
+
+#[test]
+fn test_panicking_comparator_leaves_sorted_tree_untouched() {
+    let mut tree = MerkleTreeBuilder::new(digest)
+        .sorted(LeafOrdering::by_key(key))
+        .build_from_vec(values(5));
+
+    assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), (0..5).map(Value::Fine).collect::<Vec<_>>());
+
+    // Hashing a fine value leaves comparing it to the others, which panics
+    let mut unsortable = MerkleTreeBuilder::new(digest)
+        .sorted(LeafOrdering::by_key(|value: &Value| {
+            assert!(*value != Value::Fine(3), "cannot compare this value");
+            key(value)
+        }))
+        .build_from_vec(vec![Value::Fine(1), Value::Fine(5), Value::Fine(4)]);
+
+    assert_untouched(&mut unsortable, |tree| {
+        tree.push(Value::Fine(3));
+    });
+    assert_untouched(&mut unsortable, |tree| {
+        let _ = tree.try_extend(vec![Value::Fine(0), Value::Fine(3)]);
+    });
+    assert_untouched(&mut unsortable, |tree| tree.rebuild_from_vec(vec![Value::Fine(2), Value::Fine(3)]));
+
+    // Values pushed into a sorted tree are placed as if it were built over all the values
+    for byte in &[9, 2, 0, 2, 7] {
+        tree.push(Value::Fine(*byte));
+    }
+
+    assert_eq!(tree.try_extend(vec![Value::Fine(4), Value::Fine(1), Value::Fine(9)]), Ok(3));
+
+    let mut all = values(5);
+    all.extend([9, 2, 0, 2, 7, 4, 1, 9].iter().map(|&byte| Value::Fine(byte)));
+
+    let rebuilt = MerkleTreeBuilder::new(digest)
+        .sorted(LeafOrdering::by_key(key))
+        .build_from_vec(all);
+
+    assert_eq!(tree.root_hash(), rebuilt.root_hash());
+    assert_eq!(tree.iter().collect::<Vec<_>>(), rebuilt.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_panicking_value_in_lazy_tree() {
+    let tree = MerkleTreeBuilder::new(digest)
+        .lazy(true)
+        .build_from_vec(vec![Value::Fine(1), Value::Explosive]);
+
+    // The tree cannot be built, and never yields a root hash
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| tree.root_hash().clone())).is_err());
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| tree.root_hash().clone())).is_err());
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| tree.gen_nth_proof(0))).is_err());
+}
+
+#[test]
+fn test_panicking_value_leaves_window_untouched() {
+    let mut window = SlidingWindowTree::new(digest, 3);
+
+    for byte in 0..3 {
+        window.push(Value::Fine(byte));
+    }
+
+    let root_hash = window.root_hash().clone();
+
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| window.push(Value::Explosive))).is_err());
+
+    assert_eq!(*window.root_hash(), root_hash);
+    assert_eq!(window.iter().cloned().collect::<Vec<_>>(), (0..3).map(Value::Fine).collect::<Vec<_>>());
+}
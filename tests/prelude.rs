@@ -0,0 +1,41 @@
+//! The prelude brings in what building trees and checking proofs needs, with the algorithms
+//! trees are built with.
+
+extern crate merkle;
+
+use std::ptr;
+
+use merkle::prelude::*;
+
+#[test]
+fn test_algorithm_aliases() {
+    // Algorithms are told apart by their address
+    assert!(ptr::eq(SHA256, &merkle::digest::SHA256));
+    assert!(ptr::eq(merkle::SHA512, &merkle::digest::SHA512));
+
+    let aliases = [SHA1, SHA256, SHA384, SHA512, SHA512_256];
+    let names = aliases.iter().map(|&algorithm| algorithm_name(algorithm)).collect::<Vec<_>>();
+
+    assert_eq!(names, ["SHA-1", "SHA-256", "SHA-384", "SHA-512", "SHA-512/256"]);
+
+    for &algorithm in &aliases {
+        let tree = MerkleTree::from_vec(algorithm, vec!["a", "b", "c"]);
+        let proof = tree.gen_proof("b").unwrap();
+
+        assert_eq!(tree.root_hash().len(), algorithm.output_len);
+        assert!(proof.validate(tree.root_hash()));
+        assert!(ptr::eq(algorithm_from_name(algorithm_name(algorithm)).unwrap(), algorithm));
+    }
+}
+
+#[test]
+fn test_builder_and_errors() -> Result<(), Error> {
+    let mut tree: MerkleTree<Vec<u8>> = MerkleTreeBuilder::new(SHA256).build_empty();
+    tree.try_push(vec![1])?;
+
+    let data: ProofData<Vec<u8>> = tree.gen_nth_proof(0).unwrap().into_data();
+    let proof: Proof<Vec<u8>> = data.into_named_proof()?;
+
+    assert!(proof.validate(tree.root_hash()));
+    Ok(())
+}
@@ -0,0 +1,166 @@
+#![cfg(feature="serialization-prost")]
+
+extern crate merkle;
+
+use merkle::digest::{Algorithm, SHA256, SHA512};
+
+use merkle::{MerkleTree, Proof, Error};
+use merkle::prost_proto::{self, VERSION};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA512;
+
+static PROOF_V0_BETA: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v0_sha256_beta.bin");
+static PROOF_V1_BETA: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v1_sha256_beta.bin");
+static PROOF_V1_UNKNOWN_FIELDS: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v1_unknown_fields.bin");
+static PROOF_V2_BETA: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v2_sha256_beta.bin");
+
+fn fixture_proof() -> Proof<Vec<u8>> {
+    let values = vec![b"alpha".to_vec(), b"beta".to_vec(), b"gamma".to_vec()];
+    let tree = MerkleTree::from_vec(&SHA256, values);
+
+    tree.gen_proof(b"beta".to_vec()).unwrap()
+}
+
+#[test]
+fn test_prost_inverse() {
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    for value in values {
+        let proof = tree.gen_proof(value).unwrap();
+        let bytes = prost_proto::write_proof(&proof);
+        let res = prost_proto::parse_proof::<Vec<u8>>(digest, &bytes).unwrap();
+
+        assert_eq!(proof, res);
+        assert!(res.validate(tree.root_hash()));
+    }
+}
+
+#[test]
+fn test_prost_fixtures() {
+    assert_eq!(prost_proto::parse_proof::<Vec<u8>>(&SHA256, PROOF_V0_BETA).unwrap(), fixture_proof());
+    assert_eq!(prost_proto::parse_proof::<Vec<u8>>(&SHA256, PROOF_V1_BETA).unwrap(), fixture_proof());
+    assert_eq!(prost_proto::write_proof(&fixture_proof()), PROOF_V1_BETA);
+}
+
+#[test]
+fn test_prost_skips_unknown_fields() {
+    let res = prost_proto::parse_proof::<Vec<u8>>(&SHA256, PROOF_V1_UNKNOWN_FIELDS).unwrap();
+
+    assert_eq!(res, fixture_proof());
+}
+
+#[test]
+fn test_prost_rejects_newer_version() {
+    let res = prost_proto::parse_proof::<Vec<u8>>(&SHA256, PROOF_V2_BETA);
+
+    assert_eq!(
+        res,
+        Err(Error::UnsupportedVersion {
+            found: 2,
+            max_supported: VERSION,
+        })
+    );
+}
+
+#[test]
+fn test_prost_rejects_garbage() {
+    let res = prost_proto::parse_proof::<Vec<u8>>(&SHA256, &[0x0a, 0xff]);
+
+    match res {
+        Err(Error::Decode(_)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_protobuf_entry_points() {
+    let proof = fixture_proof();
+    let bytes = proof.write_protobuf();
+
+    assert_eq!(Proof::<Vec<u8>>::parse_protobuf(&SHA256, &bytes).unwrap(), proof);
+}
+
+#[test]
+fn test_prost_compact_round_trip() {
+    let proof = fixture_proof();
+    let bytes = prost_proto::write_compact_proof(&proof);
+
+    assert_eq!(prost_proto::parse_proof::<Vec<u8>>(&SHA256, &bytes).unwrap(), proof);
+    assert!(bytes.len() < prost_proto::write_proof(&proof).len());
+}
+
+#[cfg(feature="serialization-protobuf")]
+mod compat {
+    use merkle::digest::SHA512;
+
+    use merkle::{MerkleTree, Proof, MultiProof, PrefixProof, UpdateReceipt, proto, prost_proto};
+
+    #[test]
+    fn test_same_versions() {
+        assert_eq!(proto::VERSION, prost_proto::VERSION);
+        assert_eq!(proto::LEGACY_VERSION, prost_proto::LEGACY_VERSION);
+    }
+
+    #[test]
+    fn test_wire_compatibility() {
+        let values = (0..17).map(|x| vec![x; x as usize + 1]).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(&SHA512, values.clone());
+
+        for value in values {
+            let proof = tree.gen_proof(value).unwrap();
+
+            let rust_protobuf = proof.clone().write_to_bytes().unwrap();
+            let prost = prost_proto::write_proof(&proof);
+
+            assert_eq!(rust_protobuf, prost);
+
+            let from_prost = Proof::<Vec<u8>>::parse_from_bytes(&prost, &SHA512).unwrap().unwrap();
+            let from_rust_protobuf = prost_proto::parse_proof::<Vec<u8>>(&SHA512, &rust_protobuf).unwrap();
+
+            assert_eq!(from_prost, proof);
+            assert_eq!(from_rust_protobuf, proof);
+        }
+    }
+    #[test]
+    fn test_compact_wire_compatibility() {
+        let values = (0..17).map(|x| vec![x]).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(&SHA512, values.clone());
+
+        for value in values {
+            let proof = tree.gen_proof(value).unwrap();
+
+            let rust_protobuf = proof.write_compact_protobuf();
+            let prost = prost_proto::write_compact_proof(&proof);
+
+            assert_eq!(rust_protobuf, prost);
+            assert_eq!(Proof::<Vec<u8>>::parse_protobuf(&SHA512, &prost).unwrap(), proof);
+        }
+    }
+
+    #[test]
+    fn test_batch_wire_compatibility() {
+        let values = (0..17).map(|x| vec![x]).collect::<Vec<_>>();
+        let mut tree = MerkleTree::from_vec(&SHA512, values);
+
+        let multi = tree.gen_multi_proof(&[0, 5, 6, 16]).unwrap();
+        let prost = prost_proto::write_multi_proof(&multi);
+
+        assert_eq!(multi.write_protobuf(), prost);
+        assert_eq!(MultiProof::parse_protobuf(&SHA512, &prost).unwrap(), multi);
+
+        let prefix = tree.prove_prefix(11).unwrap();
+        let prost = prost_proto::write_prefix_proof(&prefix);
+
+        assert_eq!(prefix.write_protobuf(), prost);
+        assert_eq!(PrefixProof::parse_protobuf(&SHA512, &prost).unwrap(), prefix);
+
+        let receipt = tree.compare_and_update(3, &vec![3], vec![33]).unwrap();
+        let prost = prost_proto::write_update_receipt(&receipt);
+
+        assert_eq!(receipt.write_protobuf(), prost);
+        assert_eq!(UpdateReceipt::parse_protobuf(&SHA512, &prost).unwrap(), receipt);
+    }
+}
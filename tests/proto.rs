@@ -1,13 +1,15 @@
 
 #![cfg(feature="serialization-protobuf")]
 
-extern crate ring;
 extern crate merkle;
 extern crate protobuf;
 
-use ring::digest::{Algorithm, Context, SHA512};
+use merkle::digest::{Algorithm, Context, SHA256, SHA512};
 
-use merkle::{MerkleTree, Proof, Hashable};
+use protobuf::Message;
+
+use merkle::{MerkleTree, Proof, Hashable, Error};
+use merkle::proto::{ProofProto, LEGACY_VERSION, VERSION};
 
 #[allow(non_upper_case_globals)]
 static digest: &'static Algorithm = &SHA512;
@@ -99,3 +101,154 @@ fn test_protobuf_custom_hashable_impl() {
         assert_eq!(proof.lemma, res.lemma);
     }
 }
+
+// The fixtures in `tests/fixtures/protobuf` were captured from the encoder of the
+// schema version in their name, for the SHA256 tree of `alpha`, `beta` and `gamma`,
+// or of `single`. They must keep decoding as the schema evolves.
+
+static PROOF_V0_BETA: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v0_sha256_beta.bin");
+static PROOF_V0_SINGLE: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v0_sha256_single.bin");
+static PROOF_V1_BETA: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v1_sha256_beta.bin");
+static PROOF_V1_SINGLE: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v1_sha256_single.bin");
+
+// `PROOF_V1_BETA` followed by a varint field 15 and a bytes field 16.
+static PROOF_V1_UNKNOWN_FIELDS: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v1_unknown_fields.bin");
+
+// `PROOF_V1_BETA` with its version set to 2.
+static PROOF_V2_BETA: &'static [u8] = include_bytes!("fixtures/protobuf/proof_v2_sha256_beta.bin");
+
+fn fixture_tree() -> MerkleTree<Vec<u8>> {
+    let values = vec![b"alpha".to_vec(), b"beta".to_vec(), b"gamma".to_vec()];
+    MerkleTree::from_vec(&SHA256, values)
+}
+
+#[test]
+fn test_protobuf_fixtures_decode() {
+    let tree = fixture_tree();
+    let single = MerkleTree::from_vec(&SHA256, vec![b"single".to_vec()]);
+
+    let fixtures = [
+        (PROOF_V0_BETA, &tree, b"beta".to_vec()),
+        (PROOF_V1_BETA, &tree, b"beta".to_vec()),
+        (PROOF_V0_SINGLE, &single, b"single".to_vec()),
+        (PROOF_V1_SINGLE, &single, b"single".to_vec()),
+    ];
+
+    for &(bytes, tree, ref value) in fixtures.iter() {
+        let proof = Proof::<Vec<u8>>::parse_protobuf(&SHA256, bytes).unwrap();
+
+        assert_eq!(proof, tree.gen_proof(value.clone()).unwrap());
+        assert!(proof.validate(tree.root_hash()));
+    }
+}
+
+#[test]
+fn test_protobuf_fixture_versions() {
+    let legacy = protobuf::parse_from_bytes::<ProofProto>(PROOF_V0_BETA).unwrap();
+    let current = protobuf::parse_from_bytes::<ProofProto>(PROOF_V1_BETA).unwrap();
+
+    assert_eq!(legacy.get_version(), LEGACY_VERSION);
+    assert_eq!(current.get_version(), VERSION);
+}
+
+#[test]
+fn test_protobuf_encoder_matches_fixture() {
+    let proof = fixture_tree().gen_proof(b"beta".to_vec()).unwrap();
+
+    assert_eq!(proof.write_protobuf(), PROOF_V1_BETA);
+}
+
+#[test]
+fn test_protobuf_rejects_newer_version() {
+    let res = Proof::<Vec<u8>>::parse_protobuf(&SHA256, PROOF_V2_BETA);
+
+    assert_eq!(
+        res,
+        Err(Error::UnsupportedVersion {
+            found: 2,
+            max_supported: VERSION,
+        })
+    );
+
+    let res = Proof::<Vec<u8>>::parse_from_bytes(PROOF_V2_BETA, &SHA256).unwrap();
+    assert!(res.is_none());
+}
+
+#[test]
+fn test_protobuf_accepts_unknown_fields() {
+    let proof = Proof::<Vec<u8>>::parse_protobuf(&SHA256, PROOF_V1_UNKNOWN_FIELDS).unwrap();
+
+    assert_eq!(proof, fixture_tree().gen_proof(b"beta".to_vec()).unwrap());
+}
+
+#[test]
+fn test_protobuf_preserves_unknown_fields() {
+    let proto = protobuf::parse_from_bytes::<ProofProto>(PROOF_V1_UNKNOWN_FIELDS).unwrap();
+
+    assert_eq!(proto.get_unknown_fields().iter().count(), 2);
+
+    // Unknown fields are not necessarily written back in their original order
+    let bytes = proto.write_to_bytes().unwrap();
+    let reparsed = protobuf::parse_from_bytes::<ProofProto>(&bytes).unwrap();
+
+    assert_eq!(bytes.len(), PROOF_V1_UNKNOWN_FIELDS.len());
+    assert_eq!(reparsed, proto);
+}
+
+#[test]
+fn test_protobuf_missing_field() {
+    let mut proto = protobuf::parse_from_bytes::<ProofProto>(PROOF_V1_BETA).unwrap();
+    proto.clear_lemma();
+
+    let res = Proof::<Vec<u8>>::parse_protobuf(&SHA256, &proto.write_to_bytes().unwrap());
+
+    assert_eq!(res, Err(Error::MissingField("lemma")));
+}
+
+#[test]
+fn test_protobuf_compact_round_trip() {
+    let values = (0..64).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    for value in values {
+        let proof = tree.gen_proof(value).unwrap();
+        let compact = proof.write_compact_protobuf();
+        let res = Proof::<Vec<u8>>::parse_protobuf(digest, &compact).unwrap();
+
+        assert!(compact.len() < proof.write_protobuf().len());
+        assert_eq!(res, proof);
+        assert!(res.validate(tree.root_hash()));
+    }
+}
+
+#[test]
+fn test_protobuf_compact_malformed_siblings() {
+    let proof = fixture_tree().gen_proof(b"beta".to_vec()).unwrap();
+    let proto = protobuf::parse_from_bytes::<ProofProto>(&proof.write_compact_protobuf()).unwrap();
+
+    let mut bad_direction = proto.clone();
+    bad_direction.mut_siblings()[0] = 2;
+    let res = Proof::<Vec<u8>>::parse_protobuf(&SHA256, &bad_direction.write_to_bytes().unwrap());
+    assert_eq!(res, Err(Error::InvalidDirection(2)));
+
+    let mut short = proto.clone();
+    short.mut_siblings().pop();
+    let res = Proof::<Vec<u8>>::parse_protobuf(&SHA256, &short.write_to_bytes().unwrap());
+    assert_eq!(res, Err(Error::TruncatedInput));
+
+    let res = Proof::<Vec<u8>>::parse_protobuf(&SHA512, &proto.write_to_bytes().unwrap());
+    assert_eq!(res, Err(Error::AlgorithmMismatch));
+}
+
+#[test]
+fn test_protobuf_compact_rejects_deep_paths() {
+    let proof = fixture_tree().gen_proof(b"beta".to_vec()).unwrap();
+    let mut proto = protobuf::parse_from_bytes::<ProofProto>(&proof.write_compact_protobuf()).unwrap();
+
+    let sibling = proto.get_siblings()[..33].to_vec();
+    let siblings = (0..65).flat_map(|_| sibling.clone()).collect::<Vec<_>>();
+    proto.set_siblings(siblings);
+
+    let res = Proof::<Vec<u8>>::parse_protobuf(&SHA256, &proto.write_to_bytes().unwrap());
+    assert_eq!(res, Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`")));
+}
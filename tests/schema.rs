@@ -0,0 +1,110 @@
+#![cfg(feature="schemars")]
+
+extern crate merkle;
+extern crate schemars;
+extern crate serde_json;
+extern crate jsonschema;
+
+use merkle::digest::{Algorithm, SHA256};
+
+use schemars::JsonSchema;
+use jsonschema::JSONSchema;
+
+use merkle::{MerkleTree, ProofData, CanonicalProofData, Lemma, Positioned, Encoded, HashEncoding};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+static ENCODINGS: &'static [HashEncoding] =
+    &[HashEncoding::Bytes, HashEncoding::Hex, HashEncoding::Base64];
+
+fn compile<T: JsonSchema>() -> JSONSchema {
+    let schema = serde_json::to_value(schemars::schema_for!(T)).unwrap();
+    JSONSchema::compile(&schema).unwrap()
+}
+
+fn proofs() -> Vec<ProofData<String>> {
+    let values = (0..7).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    values
+        .into_iter()
+        .map(|value| tree.gen_proof(value).unwrap().into_data())
+        .collect()
+}
+
+#[test]
+fn test_schema_proof_data() {
+    let schema = compile::<ProofData<String>>();
+
+    for data in proofs() {
+        for &encoding in ENCODINGS {
+            let json = serde_json::to_value(Encoded::new(&data, encoding)).unwrap();
+            assert!(schema.is_valid(&json), "{}", json);
+        }
+
+        // As serialized by `Proof` itself, and without the optional fields
+        let proof = data.clone().into_named_proof().unwrap();
+        assert!(schema.is_valid(&serde_json::to_value(&proof).unwrap()));
+
+        let mut bare = serde_json::to_value(&data).unwrap();
+        for field in &["algorithm", "tree_size", "leaf_index"] {
+            bare.as_object_mut().unwrap().remove(*field);
+        }
+        assert!(schema.is_valid(&bare));
+    }
+}
+
+#[test]
+fn test_schema_rejects_invalid_proof_data() {
+    let schema = compile::<ProofData<String>>();
+    let json = serde_json::to_value(Encoded::new(&proofs()[0], HashEncoding::Hex)).unwrap();
+
+    let mut missing = json.clone();
+    missing.as_object_mut().unwrap().remove("lemma");
+    assert!(!schema.is_valid(&missing));
+
+    let mut value = json.clone();
+    value["value"] = serde_json::Value::from(3);
+    assert!(!schema.is_valid(&value));
+
+    let mut hash = json.clone();
+    hash["root_hash"] = serde_json::Value::from("not a hash!");
+    assert!(!schema.is_valid(&hash));
+
+    let mut byte = json.clone();
+    byte["lemma"]["node_hash"] = serde_json::Value::from(vec![1, 256]);
+    assert!(!schema.is_valid(&byte));
+
+    let mut sibling = json.clone();
+    sibling["lemma"]["sibling_hash"] = serde_json::json!({ "Up": "00" });
+    assert!(!schema.is_valid(&sibling));
+
+    let mut algorithm = json;
+    algorithm["algorithm"] = serde_json::Value::from(256);
+    assert!(!schema.is_valid(&algorithm));
+}
+
+#[test]
+fn test_schema_lemma_and_positioned() {
+    let lemma = compile::<Lemma>();
+    let positioned = compile::<Positioned<Vec<u8>>>();
+    let canonical = compile::<CanonicalProofData<String>>();
+
+    for data in proofs() {
+        for &encoding in ENCODINGS {
+            let json = serde_json::to_value(Encoded::new(&data.lemma, encoding)).unwrap();
+            assert!(lemma.is_valid(&json), "{}", json);
+        }
+
+        let proof = data.into_named_proof().unwrap();
+        let json = serde_json::to_value(Encoded::new(&proof.into_canonical_data(), HashEncoding::Base64)).unwrap();
+        assert!(canonical.is_valid(&json), "{}", json);
+    }
+
+    for sibling in vec![Positioned::Left(vec![1, 2]), Positioned::Right(vec![])] {
+        assert!(positioned.is_valid(&serde_json::to_value(&sibling).unwrap()));
+    }
+
+    assert!(!positioned.is_valid(&serde_json::json!({ "Left": [1], "Right": [2] })));
+}
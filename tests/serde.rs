@@ -0,0 +1,795 @@
+#![cfg(feature="serialization-serde")]
+
+extern crate merkle;
+extern crate serde_json;
+
+use merkle::digest::{Algorithm, Context, SHA256, SHA512};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, Proof, ProofData, CanonicalProofData, Positioned, Encoded, HashEncoding, Error, RootHash};
+use merkle::{Lemma, MAX_TREE_DEPTH};
+use merkle::{algorithm_from_name, register_algorithm, KECCAK256_NAME, LevelExport, TreeFingerprint, TreeStats, WithMeta};
+use merkle::{MerkleMap, KvProof, KvAbsenceProof};
+use merkle::{Frontier, OddLeafPolicy, HashingScheme, ValidationPolicy};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+static ENCODINGS: &'static [HashEncoding] =
+    &[HashEncoding::Bytes, HashEncoding::Hex, HashEncoding::Base64];
+
+fn proofs() -> Vec<ProofData<String>> {
+    let values = (0..7).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    values
+        .into_iter()
+        .map(|value| tree.gen_proof(value).unwrap().into_data())
+        .collect()
+}
+
+#[test]
+fn test_serde_bytes_is_default() {
+    for data in proofs() {
+        let default = serde_json::to_string(&data).unwrap();
+        let bytes = serde_json::to_string(&Encoded::new(&data, HashEncoding::Bytes)).unwrap();
+
+        assert_eq!(default, bytes);
+    }
+}
+
+#[test]
+fn test_serde_proof_matches_data() {
+    let values = vec!["a", "b", "c"];
+    let tree = MerkleTree::from_vec(digest, values);
+    let proof = tree.gen_proof("b").unwrap();
+
+    for encoding in ENCODINGS {
+        let from_proof = serde_json::to_value(Encoded::new(&proof, *encoding)).unwrap();
+        let tagged = serde_json::to_value(Encoded::new(&proof.serialize_tagged(), *encoding)).unwrap();
+        let data = proof.clone().into_data();
+        let mut from_data = serde_json::to_value(Encoded::new(&data, *encoding)).unwrap();
+
+        assert_eq!(tagged, from_data);
+
+        // Only the tagged form names the algorithm
+        from_data.as_object_mut().unwrap().remove("algorithm");
+        assert_eq!(from_proof, from_data);
+    }
+}
+
+#[test]
+fn test_serde_scheme() {
+    let values = vec!["a", "b", "c"];
+    let unprefixed = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(HashingScheme::Unprefixed)
+        .build_from_vec(values.clone());
+    let proof = unprefixed.gen_proof("c").unwrap();
+
+    // The scheme is only serialized if it is not the default one
+    let json = serde_json::to_value(proof.serialize_tagged()).unwrap();
+    assert_eq!(json["scheme"], "Unprefixed");
+    assert_eq!(json, serde_json::to_value(&proof.clone().into_data()).unwrap());
+
+    let plain = MerkleTree::from_vec(digest, values).gen_proof("c").unwrap();
+    assert!(serde_json::to_value(&plain).unwrap().get("scheme").is_none());
+
+    let data: ProofData<String> = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(data.scheme, HashingScheme::Unprefixed);
+
+    let res = data.into_named_proof().unwrap();
+    assert_eq!(res.scheme, HashingScheme::Unprefixed);
+    assert!(res.validate(unprefixed.root_hash()));
+
+    // Checked with the scheme of the verifier, in both directions
+    let domain_separated = ValidationPolicy::new().required_scheme(HashingScheme::DomainSeparated);
+    assert_eq!(
+        res.validate_with_policy(unprefixed.root_hash(), &domain_separated),
+        Err(Error::SchemeMismatch {
+            proof: HashingScheme::Unprefixed,
+            verifier: HashingScheme::DomainSeparated,
+        })
+    );
+
+    let data: ProofData<String> = serde_json::from_value(serde_json::to_value(&plain).unwrap()).unwrap();
+    let unprefixed_policy = ValidationPolicy::new().required_scheme(HashingScheme::Unprefixed);
+    assert_eq!(
+        data.into_proof(digest).unwrap().validate_with_policy(plain.root_hash.as_slice(), &unprefixed_policy),
+        Err(Error::SchemeMismatch {
+            proof: HashingScheme::DomainSeparated,
+            verifier: HashingScheme::Unprefixed,
+        })
+    );
+
+    // Without its scheme, the proof is read as domain separated
+    let mut stripped = json;
+    stripped.as_object_mut().unwrap().remove("scheme");
+
+    let res = serde_json::from_value::<ProofData<String>>(stripped).unwrap().into_named_proof().unwrap();
+    assert_eq!(res.scheme, HashingScheme::DomainSeparated);
+    assert!(!res.validate(unprefixed.root_hash()));
+}
+
+#[test]
+fn test_serde_leaf_counts() {
+    let values = vec!["a", "b", "c"];
+    let tree = MerkleTreeBuilder::new(digest).leaf_counts(true).build_from_vec(values);
+    let proof = tree.gen_proof("c").unwrap();
+
+    let json = serde_json::to_value(proof.serialize_tagged()).unwrap();
+    assert_eq!(json["leaf_counts"], serde_json::Value::from(vec![3]));
+    assert_eq!(json, serde_json::to_value(&proof.clone().into_data()).unwrap());
+
+    let data: ProofData<String> = serde_json::from_value(json).unwrap();
+    let res = data.into_proof(digest).unwrap();
+    assert_eq!(res.leaf_counts, proof.leaf_counts);
+    assert!(res.validate(tree.root_hash()));
+
+    let plain = MerkleTree::from_vec(digest, vec!["a", "b", "c"]).gen_proof("c").unwrap();
+    assert!(serde_json::to_value(&plain).unwrap().get("leaf_counts").is_none());
+}
+
+#[test]
+fn test_serde_encodings_round_trip() {
+    for data in proofs() {
+        for encoding in ENCODINGS {
+            let json = serde_json::to_string(&Encoded::new(&data, *encoding)).unwrap();
+            let res: ProofData<String> = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(res, data);
+            assert!(res.into_proof(digest).unwrap().validate(&data.root_hash));
+        }
+    }
+}
+
+#[test]
+fn test_serde_encodings_cross_decode() {
+    for data in proofs() {
+        let decoded = ENCODINGS
+            .iter()
+            .map(|encoding| {
+                let json = serde_json::to_string(&Encoded::new(&data, *encoding)).unwrap();
+                serde_json::from_str::<ProofData<String>>(&json).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        assert!(decoded.iter().all(|res| *res == decoded[0]));
+    }
+}
+
+#[test]
+fn test_serde_hex_shape() {
+    let values = vec!["a", "b"];
+    let tree = MerkleTree::from_vec(digest, values);
+    let data = tree.gen_proof("a").unwrap().into_data();
+
+    let json = serde_json::to_value(&Encoded::new(&data, HashEncoding::Hex)).unwrap();
+    let root_hex = data.root_hash
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    assert_eq!(json["root_hash"], serde_json::Value::String(root_hex));
+    assert!(json["lemma"]["node_hash"].is_string());
+    assert!(json["lemma"]["sibling_hash"]["Right"].is_string());
+    assert!(json["lemma"]["sub_lemma"]["node_hash"].is_string());
+    assert!(json["lemma"]["sub_lemma"]["sibling_hash"].is_null());
+}
+
+#[test]
+fn test_serde_encoding_is_scoped() {
+    let data = proofs().remove(0);
+
+    let hex = serde_json::to_value(&Encoded::new(&data, HashEncoding::Hex)).unwrap();
+    let default = serde_json::to_value(&data).unwrap();
+
+    assert!(hex["root_hash"].is_string());
+    assert!(default["root_hash"].is_array());
+}
+
+#[test]
+fn test_serde_rejects_invalid_hash_strings() {
+    let json = r#"{"root_hash":"not a hash!","lemma":{"node_hash":[],"sibling_hash":null,"sub_lemma":null},"value":"a"}"#;
+
+    assert!(serde_json::from_str::<ProofData<String>>(json).is_err());
+}
+
+fn tree() -> MerkleTree<String> {
+    let values = (0..7).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+    MerkleTree::from_vec(digest, values)
+}
+
+fn deserialize_verified(json: &serde_json::Value) -> Result<MerkleTree<String>, Error> {
+    MerkleTree::deserialize_verified(digest, json)
+}
+
+fn deserialize_unverified(json: &serde_json::Value) -> Result<MerkleTree<String>, Error> {
+    MerkleTree::deserialize_unverified(digest, json)
+}
+
+#[test]
+fn test_serde_tree_round_trip() {
+    let empty = MerkleTree::<String>::from_vec(digest, vec![]);
+
+    for tree in vec![tree(), empty] {
+        for encoding in ENCODINGS {
+            let json = serde_json::to_string(&Encoded::new(&tree, *encoding)).unwrap();
+            let mut deserializer = serde_json::Deserializer::from_str(&json);
+            let res = MerkleTree::deserialize_verified(digest, &mut deserializer).unwrap();
+
+            assert_eq!(res, tree);
+        }
+    }
+}
+
+#[test]
+fn test_serde_empty_tree_round_trip() {
+    let empty = MerkleTree::<String>::empty(digest);
+    let json = serde_json::to_value(&empty).unwrap();
+
+    for res in vec![deserialize_verified(&json), deserialize_unverified(&json)] {
+        let mut res = res.unwrap();
+
+        assert!(res.is_empty());
+        assert_eq!(res.height(), 0);
+        assert_eq!(res.root_hash(), empty.root_hash());
+
+        res.push("a".to_string());
+        assert_eq!(res, MerkleTree::from_vec(digest, vec!["a".to_string()]));
+    }
+
+    // An empty root does not make an empty tree of a tree which claims to have leaves
+    let mut json = json;
+    json["count"] = serde_json::Value::from(1);
+
+    assert!(deserialize_verified(&json).is_err());
+}
+
+#[test]
+fn test_serde_pruned_tree_is_not_serialized() {
+    let mut tree = tree();
+    tree.prune_below(1);
+
+    let err = serde_json::to_string(&tree).unwrap_err();
+    assert!(err.to_string().contains("cannot serialize a pruned subtree"));
+}
+
+#[test]
+fn test_serde_tree_rejects_corrupted_value() {
+    let tree = tree();
+    let mut json = serde_json::to_value(&tree).unwrap();
+
+    // The third leaf, ie. the left child of the right child of the left child of the root
+    json["root"]["Node"]["left"]["Node"]["right"]["Node"]["left"]["Leaf"]["value"] =
+        serde_json::Value::String("evil".to_string());
+
+    assert_eq!(
+        deserialize_verified(&json).unwrap_err(),
+        Error::HashMismatch { depth: 3, index: 2 }
+    );
+
+    assert!(deserialize_unverified(&json).is_ok());
+}
+
+#[test]
+fn test_serde_tree_rejects_corrupted_internal_hash() {
+    let tree = tree();
+    let mut json = serde_json::to_value(&Encoded::new(&tree, HashEncoding::Hex)).unwrap();
+
+    json["root"]["Node"]["right"]["Node"]["hash"] = serde_json::Value::String("00".repeat(32));
+
+    assert_eq!(
+        deserialize_verified(&json).unwrap_err(),
+        Error::HashMismatch { depth: 1, index: 1 }
+    );
+
+    assert!(deserialize_unverified(&json).is_ok());
+}
+
+#[test]
+fn test_serde_tree_rejects_inconsistent_shape() {
+    let tree = tree();
+
+    let mut json = serde_json::to_value(&tree).unwrap();
+    json["count"] = serde_json::Value::from(8);
+
+    match deserialize_verified(&json) {
+        Err(Error::MalformedTree(_)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let mut json = serde_json::to_value(&tree).unwrap();
+    json["root"] = json["root"]["Node"]["left"].clone();
+
+    match deserialize_verified(&json) {
+        Err(Error::MalformedTree(_)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn test_serde_tree_rejects_non_canonical_shape() {
+    let values = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let mut json = serde_json::to_value(&tree).unwrap();
+
+    // `Node(Leaf a, Node(b, c))` rather than `Node(Node(a, b), Leaf c)`, with consistent hashes
+    let a = json["root"]["Node"]["left"]["Node"]["left"].clone();
+    let bc = serde_json::to_value(MerkleTree::from_vec(digest, values[1..].to_vec())).unwrap()["root"].clone();
+
+    let a_hash: Vec<u8> = serde_json::from_value(a["Leaf"]["hash"].clone()).unwrap();
+    let bc_hash: Vec<u8> = serde_json::from_value(bc["Node"]["hash"].clone()).unwrap();
+
+    let mut ctx = Context::new(digest);
+    ctx.update(&[0x01]);
+    ctx.update(&a_hash);
+    ctx.update(&bc_hash);
+    let root_hash = ctx.finish().as_ref().to_vec();
+
+    json["root"] = serde_json::json!({ "Node": { "hash": root_hash, "left": a, "right": bc } });
+
+    let reshaped = deserialize_unverified(&json).unwrap();
+    assert_eq!(reshaped.root_hash(), &root_hash);
+    assert_ne!(reshaped.root_hash(), tree.root_hash());
+
+    assert_eq!(
+        deserialize_verified(&json).unwrap_err(),
+        Error::MalformedTree("left subtree does not hold the leaves it should")
+    );
+}
+
+#[test]
+fn test_serde_canonical_round_trip() {
+    let values = (0..7).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+
+    for value in values {
+        let proof = tree.gen_proof(value).unwrap();
+        let data = proof.clone().into_canonical_data();
+
+        for encoding in ENCODINGS {
+            let json = serde_json::to_string(&Encoded::new(&data, *encoding)).unwrap();
+            assert!(!json.contains("node_hash"));
+
+            let res: CanonicalProofData<String> = serde_json::from_str(&json).unwrap();
+            assert_eq!(res, data);
+
+            let res = res.into_proof(digest);
+            assert_eq!(res, proof);
+            assert!(res.validate(tree.root_hash()));
+        }
+    }
+}
+
+fn lemma_chain(depth: usize) -> Lemma {
+    let mut lemma = Lemma {
+        node_hash: vec![0; 32],
+        sibling_hash: None,
+        sub_lemma: None,
+    };
+
+    for _ in 0..depth {
+        lemma = Lemma {
+            node_hash: vec![0; 32],
+            sibling_hash: Some(Positioned::Left(vec![1; 32])),
+            sub_lemma: Some(Box::new(lemma)),
+        };
+    }
+
+    lemma
+}
+
+#[test]
+fn test_serde_lemma_depth_limit() {
+    let deepest = serde_json::to_string(&lemma_chain(MAX_TREE_DEPTH)).unwrap();
+    assert_eq!(serde_json::from_str::<Lemma>(&deepest).unwrap().depth(), MAX_TREE_DEPTH);
+
+    let too_deep = serde_json::to_string(&lemma_chain(MAX_TREE_DEPTH + 1)).unwrap();
+    let err = serde_json::from_str::<Lemma>(&too_deep).unwrap_err();
+    assert!(err.to_string().contains("maximum depth"));
+
+    // `from_value` has no recursion limit of its own
+    let far_too_deep = serde_json::to_value(&lemma_chain(4 * MAX_TREE_DEPTH)).unwrap();
+    assert!(serde_json::from_value::<Lemma>(far_too_deep).is_err());
+
+    // The limit applies to every lemma chain, not to all the chains deserialized so far
+    let proofs = serde_json::to_string(&vec![lemma_chain(MAX_TREE_DEPTH); 2]).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<Lemma>>(&proofs).unwrap().len(), 2);
+}
+
+#[test]
+fn test_serde_canonical_siblings_limit() {
+    let data = CanonicalProofData {
+        root_hash: vec![0; 32],
+        leaf_hash: vec![0; 32],
+        siblings: vec![Positioned::Right(vec![1; 32]); MAX_TREE_DEPTH + 1],
+        value: "a".to_string(),
+    };
+
+    let json = serde_json::to_string(&data).unwrap();
+    assert!(serde_json::from_str::<CanonicalProofData<String>>(&json).is_err());
+
+    let mut data = data;
+    data.siblings.pop();
+
+    let json = serde_json::to_string(&data).unwrap();
+    assert_eq!(serde_json::from_str::<CanonicalProofData<String>>(&json).unwrap(), data);
+}
+
+#[test]
+fn test_serde_positioned_shape() {
+    let left = Positioned::Left(vec![1u8, 2]);
+    let right = Positioned::Right(vec![3u8]);
+
+    assert_eq!(serde_json::to_string(&left).unwrap(), r#"{"Left":[1,2]}"#);
+    assert_eq!(serde_json::to_string(&right).unwrap(), r#"{"Right":[3]}"#);
+    assert_eq!(serde_json::from_str::<Positioned<Vec<u8>>>(r#"{"Left":[1,2]}"#).unwrap(), left);
+
+    let data = MerkleTree::from_vec(digest, vec!["a", "b"]).gen_proof("b").unwrap().into_data();
+    let json = serde_json::to_value(&Encoded::new(&data, HashEncoding::Hex)).unwrap();
+    let sibling = data.lemma.sibling_hash.as_ref().unwrap();
+    let sibling_hex = sibling
+        .as_ref()
+        .into_inner()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    assert!(sibling.is_left());
+    assert_eq!(json["lemma"]["sibling_hash"].as_object().unwrap().len(), 1);
+    assert_eq!(json["lemma"]["sibling_hash"]["Left"], serde_json::Value::String(sibling_hex));
+}
+
+#[test]
+fn test_serde_root_hash_shape() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b"]);
+    let root = tree.root();
+
+    let json = serde_json::to_value(&Encoded::new(&root, HashEncoding::Hex)).unwrap();
+    assert_eq!(json, serde_json::json!({ "algorithm": "SHA-256", "hash": root.to_string() }));
+
+    for encoding in ENCODINGS {
+        let json = serde_json::to_string(&Encoded::new(&root, *encoding)).unwrap();
+        assert_eq!(serde_json::from_str::<RootHash>(&json).unwrap(), root);
+    }
+
+    let unknown = serde_json::json!({ "algorithm": "MD5", "hash": root.to_string() });
+    assert!(serde_json::from_value::<RootHash>(unknown).is_err());
+
+    let truncated = serde_json::json!({ "algorithm": "SHA-256", "hash": &root.to_string()[2..] });
+    assert!(serde_json::from_value::<RootHash>(truncated).is_err());
+}
+
+#[test]
+fn test_serde_proof_names_algorithm() {
+    let tree = MerkleTree::from_vec(digest, vec!["a".to_string(), "b".to_string()]);
+    let data = tree.gen_proof("a".to_string()).unwrap().into_data();
+
+    let json = serde_json::to_value(&data).unwrap();
+    assert_eq!(json["algorithm"], "SHA-256");
+
+    let res: ProofData<String> = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(res, data);
+    assert!(res.into_named_proof().unwrap().validate(tree.root_hash()));
+
+    // Data serialized before the algorithm was named
+    let mut legacy = json.clone();
+    legacy.as_object_mut().unwrap().remove("algorithm");
+
+    let res: ProofData<String> = serde_json::from_value(legacy).unwrap();
+    assert_eq!(res.algorithm, None);
+    assert_eq!(res.into_named_proof().unwrap_err(), Error::MissingField("algorithm"));
+
+    let mut unknown = json;
+    unknown["algorithm"] = serde_json::Value::from("sha2-256-unregistered");
+
+    let err = serde_json::from_value::<ProofData<String>>(unknown).unwrap_err();
+    assert!(err.to_string().contains("unknown algorithm `sha2-256-unregistered`"));
+}
+
+#[test]
+fn test_serde_proof_registered_algorithm() {
+    assert!(register_algorithm("sha2-256", digest));
+    assert!(!register_algorithm("sha2-256", &SHA512));
+    assert!(!register_algorithm("SHA-512", digest));
+
+    let tree = MerkleTree::from_vec(digest, vec!["a".to_string(), "b".to_string()]);
+    let data = tree.gen_proof("b".to_string()).unwrap().into_data();
+
+    let mut json = serde_json::to_value(&data).unwrap();
+    json["algorithm"] = serde_json::Value::from("sha2-256");
+
+    let res: ProofData<String> = serde_json::from_value(json).unwrap();
+    assert_eq!(res, data);
+
+    let proof = res.into_named_proof().unwrap();
+    assert!(proof.validate(tree.root_hash()));
+
+    // Proofs are tagged with the canonical name
+    assert_eq!(serde_json::to_value(proof.serialize_tagged()).unwrap()["algorithm"], "SHA-256");
+}
+
+#[test]
+fn test_serde_proof_keccak_name_reserved() {
+    assert!(!register_algorithm(KECCAK256_NAME, digest));
+    assert!(algorithm_from_name(KECCAK256_NAME).is_none());
+
+    let tree = MerkleTree::from_vec(digest, vec!["a".to_string(), "b".to_string()]);
+    let data = tree.gen_proof("b".to_string()).unwrap().into_data();
+
+    let mut json = serde_json::to_value(&data).unwrap();
+    json["algorithm"] = serde_json::Value::from(KECCAK256_NAME);
+
+    let res: Result<ProofData<String>, _> = serde_json::from_value(json);
+    assert!(res.unwrap_err().to_string().contains("unknown algorithm `Keccak-256`"));
+}
+
+#[test]
+fn test_serde_tagged_proof_round_trip() {
+    let tree = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(HashingScheme::Unprefixed)
+        .leaf_counts(true)
+        .build_from_vec(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+    let proof = tree.gen_proof("c".to_string()).unwrap();
+
+    let json = serde_json::to_value(proof.serialize_tagged()).unwrap();
+    assert_eq!(json["algorithm"], "SHA-256");
+    assert_eq!(json["scheme"], "Unprefixed");
+    assert_eq!(json, serde_json::to_value(proof.clone().into_data()).unwrap());
+
+    let res = Proof::<String>::deserialize_tagged(&json).unwrap();
+    assert_eq!(res, proof);
+    assert!(res.validate(tree.root_hash()));
+
+    // Untagged, the proof is serialized as its data without the algorithm
+    let untagged = serde_json::to_value(&proof).unwrap();
+    let mut stripped = json;
+    stripped.as_object_mut().unwrap().remove("algorithm");
+
+    assert!(untagged.get("algorithm").is_none());
+    assert_eq!(untagged, stripped);
+}
+
+#[test]
+fn test_serde_tagged_proof_names() {
+    assert!(register_algorithm("sha2-512-tagged", &SHA512));
+
+    let tree = MerkleTree::from_vec(&SHA512, vec!["a".to_string(), "b".to_string()]);
+    let proof = tree.gen_proof("a".to_string()).unwrap();
+    let json = serde_json::to_value(proof.serialize_tagged()).unwrap();
+
+    let mut registered = json.clone();
+    registered["algorithm"] = serde_json::Value::from("sha2-512-tagged");
+    assert_eq!(Proof::<String>::deserialize_tagged(&registered).unwrap(), proof);
+
+    let mut unknown = json.clone();
+    unknown["algorithm"] = serde_json::Value::from("sha2-512-unregistered");
+
+    let err = Proof::<String>::deserialize_tagged(&unknown).unwrap_err();
+    assert!(err.to_string().contains("unknown algorithm `sha2-512-unregistered`"));
+
+    // An untagged proof cannot be read as a tagged one
+    let untagged = serde_json::to_value(&proof).unwrap();
+
+    let err = Proof::<String>::deserialize_tagged(&untagged).unwrap_err();
+    assert_eq!(err.to_string(), Error::MissingField("algorithm").to_string());
+}
+
+#[test]
+fn test_serde_proof_tree_size() {
+    let tree = MerkleTree::from_vec(digest, vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+    let data = tree.gen_proof("c".to_string()).unwrap().into_data();
+
+    let json = serde_json::to_value(&data).unwrap();
+    assert_eq!(json["tree_size"], 3);
+    assert_eq!(json["leaf_index"], 2);
+
+    let res: ProofData<String> = serde_json::from_value(json).unwrap();
+    assert_eq!(res, data);
+
+    let legacy = ProofData {
+        tree_size: None,
+        leaf_index: None,
+        ..data
+    };
+
+    let json = serde_json::to_value(&legacy).unwrap();
+    assert!(json.get("tree_size").is_none());
+    assert!(json.get("leaf_index").is_none());
+
+    let res: ProofData<String> = serde_json::from_value(json).unwrap();
+    assert_eq!(res, legacy);
+    assert!(res.into_named_proof().unwrap().validate_against(tree.root_hash(), 3));
+}
+
+#[test]
+fn test_snapshot_smaller_than_serde() {
+    let values = (0..1000).map(|i| format!("value {}", i)).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+
+    let json = serde_json::to_vec(&tree).unwrap();
+    let mut snapshot = Vec::new();
+    tree.write_snapshot_with_nodes(&mut snapshot).unwrap();
+
+    // About 300 kB against 77 kB, even though the snapshot stores the internal nodes too
+    assert!(3 * snapshot.len() < json.len());
+
+    let res = MerkleTree::<String>::read_snapshot(digest, &snapshot[..]).unwrap();
+    assert_eq!(res, tree);
+}
+
+#[test]
+fn test_serde_level_export() {
+    let values = (0..11).map(|i| i.to_string()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+    let export = tree.export_levels(2);
+
+    for encoding in ENCODINGS {
+        let json = serde_json::to_string(&Encoded::new(&export, *encoding)).unwrap();
+        let res: LevelExport = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(res, export);
+    }
+
+    let json = serde_json::to_value(&export).unwrap();
+    assert_eq!(json["count"], 11);
+    assert_eq!(json["height"], 4);
+    assert_eq!(json["levels"][1].as_array().unwrap().len(), 3);
+
+    let mut missing = json.clone();
+    missing["levels"][1].as_array_mut().unwrap().pop();
+    let res = serde_json::from_value::<LevelExport>(missing);
+    assert!(res.unwrap_err().to_string().contains("level does not match the leaf count"));
+
+    let mut too_many = json.clone();
+    too_many["count"] = serde_json::Value::from(17);
+    let res = serde_json::from_value::<LevelExport>(too_many);
+    assert!(res.unwrap_err().to_string().contains("too many leaves for the height"));
+
+    let mut short = json.clone();
+    short["levels"][0][0] = serde_json::Value::from(vec![0; 31]);
+    let res = serde_json::from_value::<LevelExport>(short);
+    assert!(res.unwrap_err().to_string().contains("hash has length 31, expected 32"));
+}
+
+#[test]
+fn test_serde_tree_fingerprint() {
+    let values = (0..11).map(|i| i.to_string()).collect::<Vec<_>>();
+    let mut tree = MerkleTree::from_vec(digest, values);
+    let fingerprint = tree.fingerprint(2);
+
+    for encoding in ENCODINGS {
+        let json = serde_json::to_string(&Encoded::new(&fingerprint, *encoding)).unwrap();
+        let res: TreeFingerprint = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(res, fingerprint);
+    }
+
+    // A stored fingerprint tells what changed since
+    let json = serde_json::to_value(&fingerprint).unwrap();
+    tree.update(5, "five".to_string()).unwrap();
+
+    let stored = serde_json::from_value::<TreeFingerprint>(json.clone()).unwrap();
+    let diff = tree.diff_roots_since(&stored);
+    assert_eq!(diff.changed.iter().map(|bucket| (bucket.start, bucket.end)).collect::<Vec<_>>(), [(4, 8)]);
+
+    let diff_json = serde_json::to_value(&diff).unwrap();
+    assert_eq!(diff_json["changed"][0]["index"], 1);
+    assert_eq!(diff_json["resized"], false);
+
+    assert_eq!(json["count"], 11);
+    assert_eq!(json["level"], 2);
+    assert_eq!(json["hashes"].as_array().unwrap().len(), 3);
+
+    let mut missing = json.clone();
+    missing["hashes"].as_array_mut().unwrap().pop();
+    let res = serde_json::from_value::<TreeFingerprint>(missing);
+    assert!(res.unwrap_err().to_string().contains("buckets do not match the leaf count"));
+
+    let mut too_many = json.clone();
+    too_many["count"] = serde_json::Value::from(17);
+    let res = serde_json::from_value::<TreeFingerprint>(too_many);
+    assert!(res.unwrap_err().to_string().contains("too many leaves for the height"));
+
+    let mut too_low = json.clone();
+    too_low["level"] = serde_json::Value::from(5);
+    let res = serde_json::from_value::<TreeFingerprint>(too_low);
+    assert!(res.unwrap_err().to_string().contains("too many levels"));
+
+    let mut short = json.clone();
+    short["hashes"][0] = serde_json::Value::from(vec![0; 31]);
+    let res = serde_json::from_value::<TreeFingerprint>(short);
+    assert!(res.unwrap_err().to_string().contains("hash has length 31, expected 32"));
+}
+
+#[test]
+fn test_serde_frontier() {
+    let values = (0..11).map(|i| i.to_string()).collect::<Vec<_>>();
+    let mut tree = MerkleTreeBuilder::new(digest).odd_leaf_policy(OddLeafPolicy::Duplicate).build_from_vec(values);
+    let frontier = tree.export_frontier();
+
+    for encoding in ENCODINGS {
+        let json = serde_json::to_string(&Encoded::new(&frontier, *encoding)).unwrap();
+        let res: Frontier = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(res, frontier);
+    }
+
+    let json = serde_json::to_value(&frontier).unwrap();
+    assert_eq!(json["count"], 11);
+    assert_eq!(json["odd_leaf_policy"], "Duplicate");
+    assert_eq!(json["hashes"].as_array().unwrap().len(), 3);
+
+    let mut resumed = MerkleTree::from_frontier(digest, serde_json::from_value(json.clone()).unwrap()).unwrap();
+
+    tree.push("11".to_string());
+    resumed.push("11".to_string()).unwrap();
+    assert_eq!(resumed.root_hash(), tree.root_hash());
+
+    let mut missing = json.clone();
+    missing["hashes"].as_array_mut().unwrap().pop();
+    let res = serde_json::from_value::<Frontier>(missing);
+    assert!(res.unwrap_err().to_string().contains("not one hash per bit set in the number of leaves"));
+
+    let mut short = json.clone();
+    short["hashes"][0] = serde_json::Value::from(vec![0; 31]);
+    let res = serde_json::from_value::<Frontier>(short);
+    assert!(res.unwrap_err().to_string().contains("hash has length 31, expected 32"));
+}
+
+#[test]
+fn test_serde_tree_stats() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    let stats = tree.stats();
+
+    let json = serde_json::to_value(&stats).unwrap();
+    assert_eq!(json["leaf_count"], 3);
+    assert_eq!(json["min_leaf_depth"], 1);
+    assert_eq!(json["max_leaf_depth"], 2);
+    assert_eq!(json["is_perfect"], false);
+    assert_eq!(json["algorithm_name"], "SHA-256");
+    assert_eq!(json["root_hex"].as_str().unwrap().len(), 64);
+
+    assert_eq!(serde_json::from_value::<TreeStats>(json).unwrap(), stats);
+}
+
+#[test]
+fn test_serde_tree_with_meta() {
+    let values = (0..5).map(|i| (format!("value {}", i), i * 10)).collect::<Vec<_>>();
+    let mut tree = MerkleTree::from_vec_with_meta(digest, values);
+    tree.set_meta(2, 99);
+
+    let json = serde_json::to_value(&tree).unwrap();
+    assert!(json.to_string().contains(r#"{"meta":99,"value":"value 2"}"#));
+
+    let res: MerkleTree<WithMeta<String, u32>> = MerkleTree::deserialize_verified(digest, &json).unwrap();
+    assert_eq!(res, tree);
+    assert_eq!(res.meta(2), Some(&99));
+    assert_eq!(res.root_hash(), tree.root_hash());
+}
+
+#[test]
+fn test_serde_map_proofs() {
+    let mut map = MerkleMap::new(digest);
+
+    for i in 0..5 {
+        map.insert(format!("key {}", i * 2), format!("value {}", i));
+    }
+
+    let key = "key 4".to_string();
+    let proof = map.prove(&key).unwrap();
+    let json = serde_json::to_string(&proof).unwrap();
+    let res: KvProof<String, String> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(res, proof);
+    assert_eq!(res.verify(&map.root(), &key, &"value 2".to_string()), Ok(()));
+
+    let absent = "key 5".to_string();
+    let proof = map.prove_absent(&absent).unwrap();
+    let json = serde_json::to_value(&proof).unwrap();
+    assert_eq!(json["lower"]["key"], "key 4");
+    assert_eq!(json["upper"]["key"], "key 6");
+
+    let res: KvAbsenceProof<String> = serde_json::from_value(json).unwrap();
+    assert_eq!(res, proof);
+    assert_eq!(res.verify(&map.root(), &absent), Ok(()));
+}
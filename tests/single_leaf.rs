@@ -0,0 +1,328 @@
+//! The behavior of every part of the API on trees with exactly one leaf, whose root is the hash
+//! of that leaf, and whose proofs have no siblings: their lemma is both the root and the leaf.
+
+extern crate merkle;
+#[cfg(feature = "serialization-serde")]
+extern crate serde_json;
+
+use merkle::digest::{Algorithm, SHA256};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, HashingScheme, OddLeafPolicy, ValidationPolicy};
+use merkle::{Proof, MultiProof, PartialTree, AuditLog, SlidingWindowTree, Error, verify_receipt};
+use merkle::wire;
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+fn tree() -> MerkleTree<Vec<u8>> {
+    MerkleTree::from_vec(digest, vec![b"only".to_vec()])
+}
+
+fn leaf_hash() -> Vec<u8> {
+    HashingScheme::DomainSeparated.hash_leaf(digest, &b"only".to_vec()).as_ref().to_vec()
+}
+
+/// Checks that the given proof is the one of the single leaf of a tree, of the given root hash.
+fn assert_single_leaf_proof<T>(proof: &Proof<T>, root_hash: &[u8]) {
+    assert_eq!(proof.depth(), 0);
+    assert_eq!(proof.lemma.sub_lemma, None);
+    assert_eq!(proof.lemma.sibling_hash, None);
+    assert_eq!(proof.lemma.leaf_hash(), root_hash);
+    assert_eq!(proof.lemma.node_hash, root_hash);
+    assert_eq!(proof.root_hash, root_hash);
+    assert_eq!(proof.siblings().count(), 0);
+    assert_eq!(proof.tree_size, Some(1));
+    assert_eq!(proof.leaf_index, Some(0));
+}
+
+#[test]
+fn test_single_leaf_tree() {
+    let tree = tree();
+
+    assert_eq!(tree.count(), 1);
+    assert_eq!(tree.height(), 0);
+    assert!(!tree.is_empty());
+    assert_eq!(tree.root_hash(), &leaf_hash());
+    assert_eq!(tree.leaf_hashes_vec(), vec![leaf_hash()]);
+    assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&b"only".to_vec()]);
+    assert_eq!(tree.node_hash(0, 0), Some(leaf_hash().as_slice()));
+    assert_eq!(tree.node_hash(1, 0), Some(leaf_hash().as_slice()));
+    assert_eq!(tree.node_hash(0, 1), None);
+
+    let stats = tree.stats();
+    assert_eq!(stats.leaf_count, 1);
+    assert_eq!(stats.internal_nodes, 0);
+    assert_eq!(stats.height, 0);
+    assert!(stats.is_perfect);
+
+    let report = tree.memory_footprint();
+    assert_eq!(report.nodes, 1);
+    assert_eq!(report.leaves, 1);
+
+    // The other constructions of the tree agree on its root
+    let slices: &[&[u8]] = &[b"only"];
+    assert_eq!(MerkleTree::from_slices(digest, slices).root_hash(), tree.root_hash());
+    assert_eq!(MerkleTree::from_leaf_hashes(digest, vec![leaf_hash()]).unwrap().root_hash(), tree.root_hash());
+
+    let duplicate = MerkleTreeBuilder::new(digest)
+        .odd_leaf_policy(OddLeafPolicy::Duplicate)
+        .build_from_vec(vec![b"only".to_vec()]);
+    assert_eq!(duplicate.root_hash(), tree.root_hash());
+
+    let lazy = MerkleTreeBuilder::new(digest).lazy(true).build_from_vec(vec![b"only".to_vec()]);
+    assert_eq!(lazy.root_hash(), tree.root_hash());
+
+    let mut empty = MerkleTree::empty(digest);
+    empty.push(b"only".to_vec());
+    assert_eq!(empty.root_hash(), tree.root_hash());
+}
+
+#[test]
+fn test_single_leaf_proofs() {
+    let tree = tree();
+    let root_hash = tree.root_hash();
+
+    let proof = tree.gen_proof(b"only".to_vec()).unwrap();
+    assert_single_leaf_proof(&proof, root_hash);
+    assert_eq!(proof.value, b"only".to_vec());
+
+    assert_eq!(tree.gen_nth_proof(0), Some(proof.clone()));
+    assert_eq!(tree.gen_nth_proof(1), None);
+    assert_eq!(tree.try_gen_nth_proof(0), Ok(Some(proof.clone())));
+    assert_eq!(tree.gen_proof_ref(&b"only".to_vec()), Some(proof.clone()));
+    assert_eq!(tree.gen_proof(b"other".to_vec()), None);
+    assert_eq!(tree.all_proofs(), vec![proof.clone()]);
+    assert_eq!(tree.iter_proofs().collect::<Vec<_>>(), vec![proof.clone()]);
+    assert_eq!(tree.prove_indices(&[0, 1]), vec![Some(proof.clone()), None]);
+    assert_eq!(tree.gen_proofs_for_value(b"only".to_vec()), vec![proof.clone()]);
+    assert_eq!(tree.verify_proof(&proof), Ok(0));
+
+    let detached = tree.gen_proof_detached(&b"only".to_vec()).unwrap();
+    assert_single_leaf_proof(&detached, root_hash);
+    assert_eq!(tree.gen_proof_by_hash(&leaf_hash()), Some(detached));
+
+    assert_eq!(tree.audit_path(0), Some(vec![]));
+    assert_eq!(tree.audit_path_at_size(0, 1), Some(vec![]));
+    assert_eq!(proof.to_indexed_path(), Ok((0, vec![])));
+    assert_eq!(
+        Proof::from_indexed_path(digest, root_hash.clone(), 1, 0, vec![], b"only".to_vec()),
+        Ok(proof.clone())
+    );
+}
+
+#[test]
+fn test_single_leaf_validation() {
+    let tree = tree();
+    let root_hash = tree.root_hash();
+    let proof = tree.gen_nth_proof(0).unwrap();
+
+    assert!(proof.validate(root_hash));
+    assert!(proof.validate_value(root_hash));
+    assert!(proof.validate_against(root_hash, 1));
+    assert!(!proof.validate_against(root_hash, 2));
+    assert_eq!(proof.lemma.leaf_index(1, OddLeafPolicy::Promote), Some(0));
+
+    let policy = ValidationPolicy::new().required_depth(0).tree_size(1).require_value_check(true);
+    assert_eq!(proof.validate_with_policy(root_hash, &policy), Ok(()));
+
+    // The root must be the hash of the leaf
+    let other_root = MerkleTree::from_vec(digest, vec![b"other".to_vec()]).root_hash().clone();
+    assert!(!proof.validate(&other_root));
+    assert_eq!(proof.validate_with_policy(&other_root, &policy), Err(Error::InvalidProof));
+
+    // The value must hash to the leaf, when it is checked
+    let mut other_value = proof.clone();
+    other_value.value = b"other".to_vec();
+    assert!(other_value.validate(root_hash));
+    assert!(!other_value.validate_value(root_hash));
+    assert_eq!(other_value.validate_with_policy(root_hash, &policy), Err(Error::InvalidProof));
+
+    // The lemma must be the root
+    let mut other_lemma = proof.clone();
+    other_lemma.lemma.node_hash = other_root.clone();
+    assert!(!other_lemma.validate(root_hash));
+
+    let mut other_proof_root = proof.clone();
+    other_proof_root.root_hash = other_root;
+    assert!(!other_proof_root.validate(root_hash));
+
+    // The proof of the single leaf of an unprefixed tree is not one of a domain separated tree
+    let unprefixed = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(HashingScheme::Unprefixed)
+        .build_from_vec(vec![b"only".to_vec()]);
+    let unprefixed_proof = unprefixed.gen_nth_proof(0).unwrap();
+
+    assert_single_leaf_proof(&unprefixed_proof, unprefixed.root_hash());
+    assert_ne!(unprefixed.root_hash(), root_hash);
+    assert!(unprefixed_proof.validate_value(unprefixed.root_hash()));
+    assert!(!unprefixed_proof.validate(root_hash));
+}
+
+#[test]
+fn test_single_leaf_options() {
+    // Counted leaves commit to the size of the tree, which no node of a single leaf holds
+    let counted = MerkleTreeBuilder::new(digest).leaf_counts(true).build_from_vec(vec![b"only".to_vec()]);
+    let proof = counted.gen_nth_proof(0).unwrap();
+
+    assert_single_leaf_proof(&proof, counted.root_hash());
+    assert_eq!(proof.leaf_count(), Some(1));
+    assert!(proof.validate(counted.root_hash()));
+
+    // A tree of fixed depth has empty leaves beside the single value
+    let fixed = MerkleTreeBuilder::new(digest).fixed_depth(2).build_from_vec(vec![b"only".to_vec()]);
+    let proof = fixed.gen_nth_proof(0).unwrap();
+
+    assert_eq!(fixed.height(), 2);
+    assert_eq!(proof.depth(), 2);
+    assert_eq!(proof.tree_size, Some(4));
+    assert!(proof.validate(fixed.root_hash()));
+
+    let sorted = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(HashingScheme::SortedPairs)
+        .build_from_vec(vec![b"only".to_vec()]);
+    assert_eq!(sorted.gen_sorted_proof(b"only".to_vec()), Some(vec![]));
+    assert!(sorted.gen_nth_proof(0).unwrap().validate(sorted.root_hash()));
+}
+
+#[test]
+fn test_single_leaf_multiproof() {
+    let tree = tree();
+
+    let multiproof = tree.gen_multi_proof(&[0]).unwrap();
+    assert!(multiproof.validate(tree.root_hash()));
+    assert_eq!(multiproof.indices(), vec![0]);
+
+    let bytes = multiproof.to_compact_bytes();
+    assert_eq!(MultiProof::from_compact_bytes(digest, &bytes).unwrap(), multiproof);
+
+    let partial = PartialTree::<Vec<u8>>::from_multiproof(multiproof).unwrap();
+    assert_eq!(partial.count(), 1);
+    assert_eq!(partial.root_hash(), tree.root_hash());
+    assert!(partial.is_known(0));
+    assert_eq!(partial.gen_proof(0), None);
+
+    let mut partial = partial;
+    assert_eq!(partial.insert_leaf(0, b"other".to_vec()), Err(Error::HashMismatch { depth: 0, index: 0 }));
+    assert_eq!(partial.insert_leaf(0, b"only".to_vec()), Ok(()));
+
+    let proof = partial.gen_proof(0).unwrap();
+    assert_eq!(proof.lemma, tree.gen_nth_proof(0).unwrap().lemma);
+}
+
+#[test]
+fn test_single_leaf_prefix() {
+    let mut tree = tree();
+    let old_root = tree.root_hash().clone();
+
+    let proof = tree.prove_prefix(1).unwrap();
+    assert!(proof.verify(&old_root, 1, &old_root, 1));
+
+    for count in 2..20 {
+        tree.push(format!("value {}", count).into_bytes());
+
+        let proof = tree.prove_prefix(1).unwrap();
+        assert!(proof.verify(&old_root, 1, tree.root_hash(), count));
+        assert!(!proof.verify(tree.root_hash(), 1, tree.root_hash(), count));
+    }
+
+    // The log of a single entry proves it extends the empty log, and is extended by the next one
+    let mut log = AuditLog::new(digest);
+    let empty_root = log.root_hash().clone();
+
+    let first = log.append(b"only".to_vec());
+    assert_eq!(first.tree_size, 1);
+    assert_eq!(&first.root_hash, &old_root);
+    assert_eq!(verify_receipt(&empty_root, &first), Ok(()));
+
+    let second = log.append(b"next".to_vec());
+    assert_eq!(verify_receipt(&old_root, &second), Ok(()));
+}
+
+#[test]
+fn test_single_leaf_changes() {
+    let mut tree = tree();
+    let root_hash = tree.root_hash().clone();
+
+    tree.push(b"next".to_vec());
+    assert_eq!(tree.pop(), Some(b"next".to_vec()));
+    assert_eq!(tree.root_hash(), &root_hash);
+    assert_eq!(tree.height(), 0);
+
+    assert_eq!(tree.update(0, b"other".to_vec()), Ok(b"only".to_vec()));
+    assert_eq!(tree.root_hash(), MerkleTree::from_vec(digest, vec![b"other".to_vec()]).root_hash());
+    assert_eq!(tree.update(1, b"other".to_vec()), Err(Error::LeafIndexOutOfRange { index: 1, count: 1 }));
+    assert_single_leaf_proof(&tree.gen_nth_proof(0).unwrap(), &tree.root_hash().clone());
+
+    assert_eq!(tree.remove(0), Ok(b"other".to_vec()));
+    assert!(tree.is_empty());
+    assert_eq!(tree.gen_nth_proof(0), None);
+
+    tree.push(b"only".to_vec());
+    assert_eq!(tree.root_hash(), &root_hash);
+
+    // The frontier of a single leaf is its hash
+    let frontier = tree.export_frontier();
+    assert_eq!(frontier.hashes(), &[leaf_hash()][..]);
+
+    let mut resumed = MerkleTree::<Vec<u8>>::from_frontier(digest, frontier).unwrap();
+    assert_eq!(resumed.root_hash(), &root_hash);
+
+    tree.push(b"next".to_vec());
+    resumed.push(b"next".to_vec()).unwrap();
+    assert_eq!(resumed.root_hash(), tree.root_hash());
+
+    // A window of a single leaf is the tree of its newest value
+    let mut window = SlidingWindowTree::new(digest, 1);
+    window.push(b"first".to_vec());
+    assert_eq!(window.push(b"only".to_vec()), Some(b"first".to_vec()));
+    assert_eq!(window.root_hash(), &root_hash);
+    assert_single_leaf_proof(&window.gen_proof(0).unwrap(), &root_hash);
+}
+
+#[test]
+fn test_single_leaf_wire() {
+    let tree = tree();
+    let proof = tree.gen_nth_proof(0).unwrap();
+
+    let bytes = proof.to_bytes();
+    let decoded = Proof::from_bytes(digest, &bytes).unwrap();
+
+    assert_eq!(decoded.lemma, proof.lemma);
+    assert_eq!(decoded.value, proof.value);
+    assert!(decoded.validate(tree.root_hash()));
+
+    assert_eq!(wire::encode_siblings(proof.siblings()), Vec::<u8>::new());
+    assert_eq!(wire::decode_siblings(&[], digest.output_len), Ok(vec![]));
+
+    let mut snapshot = Vec::new();
+    tree.write_snapshot(&mut snapshot).unwrap();
+
+    let read = MerkleTree::<Vec<u8>>::read_snapshot(digest, &snapshot[..]).unwrap();
+    assert_eq!(read.root_hash(), tree.root_hash());
+    assert_eq!(read.gen_nth_proof(0), Some(proof));
+}
+
+#[cfg(feature = "serialization-serde")]
+#[test]
+fn test_single_leaf_serde() {
+    let tree = tree();
+    let proof = tree.gen_nth_proof(0).unwrap();
+
+    let json = serde_json::to_string(&proof).unwrap();
+    let decoded = serde_json::from_str::<merkle::ProofData<Vec<u8>>>(&json).unwrap().into_proof(digest).unwrap();
+
+    assert_eq!(decoded, proof);
+    assert_single_leaf_proof(&decoded, tree.root_hash());
+    assert!(decoded.validate(tree.root_hash()));
+    assert_eq!(merkle::verify_json_proof(digest, &json, tree.root_hash()), Ok(()));
+
+    let data = proof.clone().into_data();
+    let decoded = serde_json::from_str::<merkle::ProofData<Vec<u8>>>(&serde_json::to_string(&data).unwrap()).unwrap();
+    assert_eq!(decoded.into_proof(digest), Ok(proof));
+
+    let tree_json = serde_json::to_string(&tree).unwrap();
+    let mut deserializer = serde_json::Deserializer::from_str(&tree_json);
+    let decoded = MerkleTree::<Vec<u8>>::deserialize_verified(digest, &mut deserializer).unwrap();
+    assert_eq!(decoded.root_hash(), tree.root_hash());
+    assert_eq!(decoded.count(), 1);
+}
@@ -0,0 +1,252 @@
+
+extern crate merkle;
+
+use merkle::digest::{Algorithm, SHA256, SHA512};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, HashingScheme, OddLeafPolicy, Error};
+use merkle::snapshot::{MAGIC, VERSION};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+fn values(count: usize) -> Vec<Vec<u8>> {
+    (0..count).map(|i| format!("value {}", i).into_bytes()).collect()
+}
+
+fn snapshot<T: merkle::snapshot::SnapshotValue>(tree: &MerkleTree<T>, nodes: bool) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    if nodes {
+        tree.write_snapshot_with_nodes(&mut bytes).unwrap();
+    } else {
+        tree.write_snapshot(&mut bytes).unwrap();
+    }
+
+    bytes
+}
+
+/// Returns the position and the cause of the error of reading the given snapshot.
+fn read_error(bytes: &[u8]) -> (u64, Error) {
+    match MerkleTree::<Vec<u8>>::read_snapshot(digest, bytes) {
+        Err(Error::InvalidSnapshot { offset, cause }) => (offset, *cause),
+        Err(err) => panic!("unexpected error {:?}", err),
+        Ok(_) => panic!("snapshot should not be read"),
+    }
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    let schemes = [
+        HashingScheme::DomainSeparated,
+        HashingScheme::Unprefixed,
+        HashingScheme::SortedPairs,
+    ];
+
+    for &scheme in &schemes {
+        for &policy in &[OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+            for count in 0..20 {
+                let tree = MerkleTreeBuilder::new(digest)
+                    .hashing_scheme(scheme)
+                    .odd_leaf_policy(policy)
+                    .build_from_vec(values(count));
+
+                for &nodes in &[false, true] {
+                    let bytes = snapshot(&tree, nodes);
+                    let read = MerkleTree::read_snapshot(digest, &bytes[..]).unwrap();
+
+                    assert_eq!(read, tree);
+                    assert_eq!(read.hashing_scheme(), scheme);
+                    assert_eq!(read.odd_leaf_policy(), policy);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_snapshot_fixed_depth() {
+    for count in 0..9 {
+        let tree = MerkleTreeBuilder::new(digest).fixed_depth(3).build_from_vec(values(count));
+
+        for &nodes in &[false, true] {
+            let read = MerkleTree::read_snapshot(digest, &snapshot(&tree, nodes)[..]).unwrap();
+
+            assert_eq!(read, tree);
+            assert_eq!(read.fixed_depth(), Some(3));
+        }
+    }
+}
+
+#[test]
+fn test_snapshot_strings() {
+    let values = vec!["a", "b", "c"].into_iter().map(String::from).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+    let read = MerkleTree::<String>::read_snapshot(digest, &snapshot(&tree, false)[..]).unwrap();
+
+    assert_eq!(read, tree);
+}
+
+#[test]
+fn test_snapshot_layout() {
+    let values = vec!["a", "b", "c"].into_iter().map(String::from).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+    let bytes = snapshot(&tree, false);
+
+    assert_eq!(&bytes[..4], MAGIC);
+    assert_eq!(&bytes[4..19], &[VERSION, 2, 32, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3]);
+    assert_eq!(&bytes[19..51], tree.root_hash().as_slice());
+    assert_eq!(bytes.len(), 51 + 3 * 32 + 3 * (4 + 1));
+    assert_eq!(&bytes[147..], &[0, 0, 0, 1, b'a', 0, 0, 0, 1, b'b', 0, 0, 0, 1, b'c']);
+
+    // Two internal nodes, the third leaf being promoted
+    assert_eq!(snapshot(&tree, true).len(), bytes.len() + 2 * 32);
+}
+
+#[test]
+fn test_snapshot_hash_only() {
+    let input = "a\nb\nc\nd\ne\n";
+    let hashed = MerkleTree::from_lines_hashed(digest, input.as_bytes()).unwrap();
+    let full = MerkleTree::from_byte_lines(digest, input.as_bytes()).unwrap();
+
+    let bytes = snapshot(&hashed, false);
+    assert_eq!(bytes.len(), 51 + 5 * 32);
+
+    let read = MerkleTree::<()>::read_snapshot(digest, &bytes[..]).unwrap();
+    assert_eq!(read, hashed);
+
+    // The values of a snapshot can be dropped, but not made up
+    let dropped = MerkleTree::<()>::read_snapshot(digest, &snapshot(&full, true)[..]).unwrap();
+    assert_eq!(dropped, hashed);
+
+    assert_eq!(
+        read_error(&bytes),
+        (7, Error::Decode("snapshot holds no values".to_string()))
+    );
+}
+
+#[test]
+fn test_snapshot_truncated() {
+    let tree = MerkleTree::from_vec(digest, values(5));
+
+    for &nodes in &[false, true] {
+        let bytes = snapshot(&tree, nodes);
+
+        for len in 0..bytes.len() {
+            let (offset, cause) = read_error(&bytes[..len]);
+
+            assert_eq!(cause, Error::TruncatedInput);
+            assert!(offset <= len as u64);
+        }
+    }
+}
+
+#[test]
+fn test_snapshot_corrupt_header() {
+    let tree = MerkleTree::from_vec(digest, values(5));
+    let bytes = snapshot(&tree, false);
+
+    let corrupt = |offset: usize, byte: u8| {
+        let mut bytes = bytes.clone();
+        bytes[offset] = byte;
+        read_error(&bytes)
+    };
+
+    assert_eq!(corrupt(0, b'X'), (0, Error::InvalidMagic));
+    assert_eq!(
+        corrupt(4, VERSION + 1),
+        (
+            4,
+            Error::UnsupportedVersion {
+                found: u32::from(VERSION) + 1,
+                max_supported: u32::from(VERSION),
+            },
+        )
+    );
+    assert_eq!(corrupt(5, 0), (5, Error::UnknownAlgorithm(0)));
+    assert_eq!(corrupt(5, 4), (5, Error::AlgorithmMismatch));
+    assert_eq!(
+        corrupt(6, 64),
+        (
+            6,
+            Error::HashLengthMismatch {
+                expected: 32,
+                found: 64,
+            },
+        )
+    );
+    assert_eq!(corrupt(7, 0x81), (7, Error::Decode("unknown flags".to_string())));
+    assert_eq!(corrupt(8, 3), (8, Error::Decode("unknown hashing scheme".to_string())));
+    assert_eq!(corrupt(9, 2), (9, Error::Decode("unknown odd leaf policy".to_string())));
+    assert_eq!(corrupt(10, 2), (11, Error::TooManyLeaves { max: 2 }));
+
+    match MerkleTree::<Vec<u8>>::read_snapshot(&SHA512, &bytes[..]) {
+        Err(Error::InvalidSnapshot { offset, cause }) => {
+            assert_eq!((offset, *cause), (5, Error::AlgorithmMismatch));
+        }
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_snapshot_corrupt_hashes() {
+    let tree = MerkleTree::from_vec(digest, values(5));
+    let bytes = snapshot(&tree, false);
+    let values_offset = 51 + 5 * 32;
+
+    // A leaf hash which does not match its value
+    let mut corrupt = bytes.clone();
+    corrupt[51 + 2 * 32] ^= 1;
+    assert_eq!(
+        read_error(&corrupt),
+        (
+            values_offset as u64 + 2 * 11,
+            Error::Decode("leaf hash does not match its value".to_string()),
+        )
+    );
+
+    // A root hash which does not match the tree
+    let mut corrupt = bytes.clone();
+    corrupt[19] ^= 1;
+    assert_eq!(read_error(&corrupt), (19, Error::HashMismatch { depth: 0, index: 0 }));
+
+    // The stored internal nodes are trusted, save for the root
+    let bytes = snapshot(&tree, true);
+    let root_offset = bytes.len() - 32;
+
+    let mut corrupt = bytes.clone();
+    corrupt[root_offset] ^= 1;
+    assert_eq!(read_error(&corrupt), (19, Error::HashMismatch { depth: 0, index: 0 }));
+
+    let mut corrupt = bytes.clone();
+    corrupt[values_offset + 4] = b'V';
+    assert!(MerkleTree::<Vec<u8>>::read_snapshot(digest, &corrupt[..]).is_ok());
+}
+
+#[test]
+fn test_snapshot_invalid_utf8() {
+    let tree = MerkleTree::from_vec(digest, vec![vec![0xff]]);
+    let bytes = snapshot(&tree, true);
+
+    match MerkleTree::<String>::read_snapshot(digest, &bytes[..]) {
+        Err(Error::InvalidSnapshot { offset, cause }) => {
+            assert_eq!(offset, 51 + 32);
+            assert_eq!(*cause, Error::Decode("value is not valid UTF-8".to_string()));
+        }
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_snapshot_stops_at_its_end() {
+    let tree = MerkleTree::from_vec(digest, values(3));
+    let mut bytes = snapshot(&tree, false);
+    let len = bytes.len();
+
+    bytes.extend_from_slice(b"trailing");
+
+    let mut reader = &bytes[..];
+    let read = MerkleTree::<Vec<u8>>::read_snapshot(digest, &mut reader).unwrap();
+
+    assert_eq!(read, tree);
+    assert_eq!(reader, &bytes[len..]);
+}
@@ -0,0 +1,140 @@
+extern crate merkle;
+
+use std::io::{self, Read};
+
+use merkle::digest::{Algorithm, SHA256};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, HashingScheme, StreamingLeaf, Error, IoError};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+/// Yields `len` bytes of a pattern, without holding them, optionally failing once
+/// `fail_at` bytes were read.
+struct Pattern {
+    position: usize,
+    len: usize,
+    fail_at: Option<usize>,
+}
+
+impl Read for Pattern {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.fail_at.map_or(false, |fail_at| self.position >= fail_at) {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "disk is gone"));
+        }
+
+        // Short reads, as readers may yield fewer bytes than asked for
+        let end = self.len.min(self.position + buf.len()).min(self.position + 10_007);
+
+        for (byte, position) in buf.iter_mut().zip(self.position..end) {
+            *byte = byte_at(position);
+        }
+
+        let read = end - self.position;
+        self.position = end;
+
+        Ok(read)
+    }
+}
+
+fn byte_at(position: usize) -> u8 {
+    (position % 251) as u8 ^ (position >> 16) as u8
+}
+
+fn pattern(len: usize, fail_at: Option<usize>) -> impl Fn() -> io::Result<Box<dyn Read>> + Send + Sync {
+    move || {
+        Ok(Box::new(Pattern {
+            position: 0,
+            len: len,
+            fail_at: fail_at,
+        }) as Box<dyn Read>)
+    }
+}
+
+fn bytes(len: usize) -> Vec<u8> {
+    (0..len).map(byte_at).collect()
+}
+
+const LARGE: usize = 24 * 1024 * 1024 + 17;
+
+#[test]
+fn test_streaming_matches_in_memory() {
+    let mut tree = MerkleTreeBuilder::new(digest).build_empty();
+
+    for (index, &len) in [0, 1, 5000, LARGE].iter().enumerate() {
+        assert_eq!(tree.push_streaming(pattern(len, None), format!("file {}", index)), Ok(true));
+    }
+
+    let in_memory = MerkleTree::from_vec(digest, [0, 1, 5000, LARGE].iter().map(|&len| bytes(len)).collect());
+
+    assert_eq!(tree.root_hash(), in_memory.root_hash());
+    assert_eq!(tree.leaf_hashes_vec(), in_memory.leaf_hashes_vec());
+    assert_eq!(tree.iter().map(|leaf| leaf.metadata().clone()).collect::<Vec<_>>(), vec!["file 0", "file 1", "file 2", "file 3"]);
+
+    let proof = tree.gen_nth_proof(3).unwrap();
+    assert_eq!(proof.try_validate_value(tree.root_hash()), Ok(true));
+}
+
+#[test]
+fn test_streaming_chunk_sizes() {
+    let expected = MerkleTree::from_vec(digest, vec![bytes(100_000)]);
+
+    for &chunk_size in &[1, 7, 4096, 1 << 20] {
+        let mut tree = MerkleTreeBuilder::new(digest).build_empty();
+        let leaf = StreamingLeaf::new(pattern(100_000, None), ()).chunk_size(chunk_size);
+
+        assert_eq!(tree.push_streaming_leaf(leaf), Ok(true));
+        assert_eq!(tree.root_hash(), expected.root_hash());
+    }
+
+    // The scheme of the tree is the one the stream is hashed with
+    let mut tree = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(HashingScheme::Unprefixed)
+        .build_empty();
+
+    tree.push_streaming(pattern(100_000, None), ()).unwrap();
+
+    let expected = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(HashingScheme::Unprefixed)
+        .build_from_vec(vec![bytes(100_000)]);
+
+    assert_eq!(tree.root_hash(), expected.root_hash());
+}
+
+#[test]
+fn test_streaming_io_errors() {
+    let mut tree = MerkleTreeBuilder::new(digest).build_empty();
+    tree.push_streaming(pattern(1000, None), "fine").unwrap();
+
+    let before = tree.root_hash().clone();
+
+    let err = tree.push_streaming(pattern(1 << 20, Some(300_000)), "broken").unwrap_err();
+    assert_eq!(
+        err,
+        Error::LeafIo {
+            index: 1,
+            source: IoError {
+                kind: io::ErrorKind::UnexpectedEof,
+                reason: "disk is gone".to_string(),
+            },
+        }
+    );
+
+    let unopenable = || Err(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+    let err = tree.push_streaming(unopenable, "missing").unwrap_err();
+    assert!(match err {
+        Error::LeafIo { index: 1, ref source } => source.kind == io::ErrorKind::NotFound,
+        _ => false,
+    });
+    assert_eq!(err.to_string(), "cannot read leaf 1: no such file");
+
+    assert_eq!(tree.count(), 1);
+    assert_eq!(*tree.root_hash(), before);
+
+    // The other fallible methods report the error as a `HashError`
+    let leaf = StreamingLeaf::new(pattern(1000, Some(0)), "broken");
+    assert!(match tree.try_push(leaf) {
+        Err(Error::LeafHash { index: 1, ref cause }) => cause.reason == "disk is gone",
+        _ => false,
+    });
+}
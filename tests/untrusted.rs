@@ -0,0 +1,317 @@
+//! Throws malformed proofs at every entry point which decodes or checks untrusted input,
+//! which must fail with an error rather than panic, whatever the input.
+//!
+//! The inputs are mutations of valid encodings, so that they mostly get past the first checks,
+//! along with random bytes. Set `MERKLE_UNTRUSTED_ITERATIONS` for longer runs, and
+//! `MERKLE_UNTRUSTED_SEED` to replay the run of a failure, whose seed is printed.
+
+extern crate merkle;
+#[cfg(feature="serialization-serde")]
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature="serialization-borsh")]
+extern crate borsh;
+
+use std::env;
+
+use merkle::digest::{Algorithm, SHA256};
+
+use merkle::{MerkleTree, Proof, ProofFormat, OddLeafPolicy, verify_proof_bytes};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+fn env_var(name: &str, default: u64) -> u64 {
+    env::var(name).ok().and_then(|var| var.parse().ok()).unwrap_or(default)
+}
+
+/// A xorshift generator, seeded from `MERKLE_UNTRUSTED_SEED`.
+struct Rng {
+    seed: u64,
+    state: u64,
+}
+
+impl Rng {
+    fn new() -> Self {
+        let seed = env_var("MERKLE_UNTRUSTED_SEED", 0x2545_f491_4f6c_dd1d);
+
+        Rng {
+            seed: seed,
+            state: seed | 1,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+
+    /// Returns a byte which is likely to be interesting to a decoder.
+    fn byte(&mut self) -> u8 {
+        let edges = [0, 1, 2, 0x20, 0x40, 0x41, 0x7f, 0x80, 0xff];
+
+        match self.below(2) {
+            0 => edges[self.below(edges.len())],
+            _ => self.next() as u8,
+        }
+    }
+
+    /// Returns a tree size which is likely to be interesting to a verifier.
+    fn tree_size(&mut self) -> u64 {
+        let edges = [0, 1, 2, 3, 13, 1 << 32, (1 << 63) - 1, 1 << 63, (1 << 63) + 1, u64::MAX];
+
+        match self.below(2) {
+            0 => edges[self.below(edges.len())],
+            _ => self.next() >> self.below(64),
+        }
+    }
+
+    /// Applies a random number of random edits to a copy of `bytes`.
+    fn mutate(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+
+        for _ in 0..1 + self.below(4) {
+            let at = self.below(bytes.len() + 1);
+
+            match self.below(6) {
+                0 if at < bytes.len() => bytes[at] = self.byte(),
+                1 if at < bytes.len() => bytes[at] ^= 1 << self.below(8),
+                2 => bytes.truncate(at),
+                3 => {
+                    let len = self.below(8);
+                    let inserted = self.bytes(len);
+                    bytes.splice(at..at, inserted);
+                }
+                4 if at < bytes.len() => {
+                    let end = at + self.below(bytes.len() - at + 1);
+                    let copy = bytes[at..end].to_vec();
+                    bytes.splice(at..at, copy);
+                }
+                _ => bytes.push(self.byte()),
+            }
+        }
+
+        bytes
+    }
+}
+
+fn iterations() -> u64 {
+    env_var("MERKLE_UNTRUSTED_ITERATIONS", 2000)
+}
+
+fn tree() -> MerkleTree<Vec<u8>> {
+    MerkleTree::from_vec(digest, (0..13).map(|i| vec![i; i as usize]).collect())
+}
+
+fn proofs(tree: &MerkleTree<Vec<u8>>) -> Vec<Proof<Vec<u8>>> {
+    (0..tree.count()).map(|index| tree.gen_nth_proof(index).unwrap()).collect()
+}
+
+/// Runs every check of a decoded proof, none of which may panic.
+fn check<T>(rng: &mut Rng, proof: &Proof<T>, root_hash: &[u8]) {
+    let _ = proof.validate(root_hash);
+    let _ = proof.validate_against(root_hash, rng.tree_size());
+    let _ = proof.check_algorithm();
+    let _ = proof.to_indexed_path();
+    let _ = proof.sorted_path();
+    let _ = proof.lemma.leaf_index(rng.tree_size() as usize, OddLeafPolicy::Promote);
+    let _ = proof.lemma.leaf_index(rng.tree_size() as usize, OddLeafPolicy::Duplicate);
+}
+
+#[test]
+fn test_untrusted_wire() {
+    let mut rng = Rng::new();
+    let tree = tree();
+    let proofs = proofs(&tree);
+    let encodings = proofs.iter().map(|proof| proof.to_bytes()).collect::<Vec<_>>();
+
+    for _ in 0..iterations() {
+        let bytes = match rng.below(8) {
+            0 => {
+                let len = rng.below(256);
+                rng.bytes(len)
+            }
+            _ => {
+                let index = rng.below(encodings.len());
+                rng.mutate(&encodings[index])
+            }
+        };
+
+        if let Ok(mut proof) = Proof::from_bytes(digest, &bytes) {
+            check(&mut rng, &proof, tree.root_hash());
+
+            proof.tree_size = Some(rng.tree_size());
+            proof.leaf_index = Some(rng.tree_size());
+            check(&mut rng, &proof, tree.root_hash());
+        }
+
+        // Only the proofs of the tree hold
+        if verify_proof_bytes(digest, &bytes, ProofFormat::Wire, tree.root_hash()).is_ok() {
+            let proof = Proof::from_bytes(digest, &bytes).unwrap();
+            assert!(proofs.contains(&proof), "accepted {:?} with seed {}", bytes, rng.seed);
+        }
+    }
+}
+
+#[cfg(any(feature="serialization-protobuf", feature="serialization-prost"))]
+#[test]
+fn test_untrusted_protobuf() {
+    let mut rng = Rng::new();
+    let tree = tree();
+
+    let encodings = proofs(&tree)
+        .iter()
+        .flat_map(|proof| vec![proof.write_protobuf(), proof.write_compact_protobuf()])
+        .collect::<Vec<_>>();
+
+    for _ in 0..iterations() {
+        let index = rng.below(encodings.len());
+        let bytes = rng.mutate(&encodings[index]);
+
+        if let Ok(proof) = Proof::<Vec<u8>>::parse_protobuf(digest, &bytes) {
+            check(&mut rng, &proof, tree.root_hash());
+        }
+
+        let _ = verify_proof_bytes(digest, &bytes, ProofFormat::Protobuf, tree.root_hash());
+    }
+}
+
+#[cfg(feature="serialization-serde")]
+mod json {
+    use serde_json::{self, Value};
+
+    use merkle::{ProofData, CanonicalProofData, ProofEnvelope, verify_json_proof};
+
+    use super::{Rng, digest, tree, proofs, check, iterations};
+
+    /// Returns a random JSON value, nested at most `depth` levels deep.
+    fn value(rng: &mut Rng, depth: usize) -> Value {
+        match rng.below(if depth == 0 { 5 } else { 7 }) {
+            0 => Value::Null,
+            1 => Value::Bool(rng.below(2) == 0),
+            2 => Value::from(rng.tree_size()),
+            3 => Value::from(-(rng.below(1000) as i64)),
+            4 => Value::from(["", "00", "zz", "Left", "Right", "AAAA", "SHA-256"][rng.below(7)]),
+            5 => (0..rng.below(40)).map(|_| Value::from(rng.byte())).collect(),
+            _ => {
+                let keys = ["node_hash", "sibling_hash", "sub_lemma", "Left", "Right", "lemma"];
+
+                (0..rng.below(4))
+                    .map(|_| (keys[rng.below(keys.len())].to_string(), value(rng, depth - 1)))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            }
+        }
+    }
+
+    /// Replaces, removes or nests a random node of `json`.
+    fn mutate(rng: &mut Rng, json: &mut Value) {
+        let children = match *json {
+            Value::Object(ref map) => map.len(),
+            Value::Array(ref array) => array.len(),
+            _ => 0,
+        };
+
+        if children == 0 || rng.below(4) == 0 {
+            let inner = json.take();
+
+            *json = match rng.below(3) {
+                0 => value(rng, 3),
+                1 => json!({ "node_hash": [0], "sub_lemma": inner }),
+                _ => json!([inner]),
+            };
+
+            return;
+        }
+
+        let index = rng.below(children);
+
+        match *json {
+            Value::Object(ref mut map) if rng.below(6) == 0 => {
+                let key = map.keys().nth(index).cloned().unwrap();
+                map.remove(&key);
+            }
+
+            Value::Object(ref mut map) => mutate(rng, map.values_mut().nth(index).unwrap()),
+            Value::Array(ref mut array) => mutate(rng, &mut array[index]),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_untrusted_json() {
+        let mut rng = Rng::new();
+        let tree = tree();
+
+        let encodings = proofs(&tree)
+            .into_iter()
+            .flat_map(|proof| {
+                vec![
+                    serde_json::to_value(&proof.clone().into_data()).unwrap(),
+                    serde_json::to_value(&proof.clone().into_data().into_envelope()).unwrap(),
+                    serde_json::to_value(&proof.into_canonical_data()).unwrap(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        for _ in 0..iterations() {
+            let mut json = encodings[rng.below(encodings.len())].clone();
+
+            for _ in 0..1 + rng.below(3) {
+                mutate(&mut rng, &mut json);
+            }
+
+            if let Ok(data) = serde_json::from_value::<ProofData<Vec<u8>>>(json.clone()) {
+                let _ = data.clone().into_named_proof();
+                let _ = data.clone().into_proof(digest);
+                check(&mut rng, &data.into_proof_unchecked(digest), tree.root_hash());
+            }
+
+            if let Ok(data) = serde_json::from_value::<CanonicalProofData<Vec<u8>>>(json.clone()) {
+                check(&mut rng, &data.into_proof(digest), tree.root_hash());
+            }
+
+            if let Ok(envelope) = ProofEnvelope::<Vec<u8>>::from_json(json.clone()) {
+                check(&mut rng, &envelope.into_proof_unchecked(digest), tree.root_hash());
+            }
+
+            let _ = verify_json_proof(digest, &json.to_string(), tree.root_hash());
+        }
+    }
+}
+
+#[cfg(feature="serialization-borsh")]
+#[test]
+fn test_untrusted_borsh() {
+    use borsh::BorshDeserialize;
+
+    use merkle::ProofData;
+
+    let mut rng = Rng::new();
+    let tree = tree();
+
+    let encodings = proofs(&tree)
+        .into_iter()
+        .map(|proof| borsh::to_vec(&proof.into_data()).unwrap())
+        .collect::<Vec<_>>();
+
+    for _ in 0..iterations() {
+        let index = rng.below(encodings.len());
+        let bytes = rng.mutate(&encodings[index]);
+
+        if let Ok(data) = ProofData::<Vec<u8>>::try_from_slice(&bytes) {
+            let _ = data.clone().into_proof(digest);
+            check(&mut rng, &data.into_proof_unchecked(digest), tree.root_hash());
+        }
+    }
+}
@@ -0,0 +1,235 @@
+
+extern crate merkle;
+#[cfg(feature = "serialization-serde")]
+extern crate serde_json;
+
+use merkle::digest::{Algorithm, SHA256, SHA512};
+
+use merkle::{MerkleTree, ProofFormat, Error, verify_proof_bytes};
+#[cfg(feature = "serialization-serde")]
+use merkle::{MerkleTreeBuilder, HashingScheme};
+#[cfg(feature = "serialization-serde")]
+use merkle::verify_json_proof;
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+fn tree() -> MerkleTree<Vec<u8>> {
+    MerkleTree::from_vec(digest, (0..5).map(|i| format!("value {}", i).into_bytes()).collect())
+}
+
+/// A proof whose lemma chain is one sub lemma deeper than any tree.
+#[cfg(any(feature = "serialization-serde", feature = "serialization-protobuf", feature = "serialization-prost"))]
+fn deep_proof() -> merkle::Proof<Vec<u8>> {
+    use merkle::{Proof, Lemma, Positioned};
+
+    let hash = vec![0; digest.output_len];
+
+    let mut lemma = Lemma {
+        node_hash: hash.clone(),
+        sibling_hash: None,
+        sub_lemma: None,
+    };
+
+    for _ in 0..merkle::MAX_TREE_DEPTH + 1 {
+        lemma = Lemma {
+            node_hash: hash.clone(),
+            sibling_hash: Some(Positioned::Left(hash.clone())),
+            sub_lemma: Some(Box::new(lemma)),
+        };
+    }
+
+    Proof::new(digest, hash, lemma, vec![])
+}
+
+#[test]
+fn test_verify_wire() {
+    let tree = tree();
+    let root = tree.root_hash();
+
+    for i in 0..tree.count() {
+        let bytes = tree.gen_nth_proof(i).unwrap().to_bytes();
+        assert_eq!(verify_proof_bytes(digest, &bytes, ProofFormat::Wire, root), Ok(()));
+    }
+
+    let bytes = tree.gen_nth_proof(2).unwrap().to_bytes();
+
+    let mut value = bytes.clone();
+    *value.last_mut().unwrap() ^= 1;
+    assert_eq!(verify_proof_bytes(digest, &value, ProofFormat::Wire, root), Err(Error::InvalidProof));
+
+    assert_eq!(
+        verify_proof_bytes(digest, &bytes[..bytes.len() - 1], ProofFormat::Wire, root),
+        Err(Error::TruncatedInput)
+    );
+    assert_eq!(
+        verify_proof_bytes(digest, b"not a proof", ProofFormat::Wire, root),
+        Err(Error::InvalidMagic)
+    );
+    assert_eq!(
+        verify_proof_bytes(&SHA512, &bytes, ProofFormat::Wire, root),
+        Err(Error::AlgorithmMismatch)
+    );
+    assert_eq!(
+        verify_proof_bytes(digest, &bytes, ProofFormat::Wire, &root[1..]),
+        Err(Error::HashLengthMismatch {
+            expected: 32,
+            found: 31,
+        })
+    );
+
+    let other = MerkleTree::from_vec(digest, vec![b"other".to_vec()]);
+    assert_eq!(
+        verify_proof_bytes(digest, &bytes, ProofFormat::Wire, other.root_hash()),
+        Err(Error::InvalidProof)
+    );
+}
+
+#[cfg(feature = "serialization-serde")]
+#[test]
+fn test_verify_json() {
+    let tree = tree();
+    let root = tree.root_hash();
+
+    for i in 0..tree.count() {
+        let json = serde_json::to_string(&tree.gen_nth_proof(i).unwrap()).unwrap();
+
+        assert_eq!(verify_json_proof(digest, &json, root), Ok(()));
+        assert_eq!(verify_proof_bytes(digest, json.as_bytes(), ProofFormat::Json, root), Ok(()));
+    }
+
+    // String values, and proofs in an envelope
+    let strings = MerkleTree::from_vec(digest, vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+    let data = strings.gen_proof("c".to_string()).unwrap().into_data();
+    let json = serde_json::to_string(&data.into_envelope()).unwrap();
+    assert_eq!(verify_json_proof(digest, &json, strings.root_hash()), Ok(()));
+
+    let json = serde_json::to_value(&tree.gen_nth_proof(3).unwrap()).unwrap();
+
+    let mut value = json.clone();
+    value["value"][0] = serde_json::Value::from(b'V');
+    assert_eq!(verify_json_proof(digest, &value.to_string(), root), Err(Error::InvalidProof));
+
+    let mut sibling = json.clone();
+    for (_, hash) in sibling["lemma"]["sibling_hash"].as_object_mut().unwrap() {
+        hash[0] = serde_json::Value::from(hash[0].as_u64().unwrap() ^ 1);
+    }
+    assert_eq!(verify_json_proof(digest, &sibling.to_string(), root), Err(Error::InvalidProof));
+
+    let mut algorithm = json.clone();
+    algorithm["algorithm"] = serde_json::Value::from("SHA-512");
+    assert_eq!(verify_json_proof(digest, &algorithm.to_string(), root), Err(Error::AlgorithmMismatch));
+
+    let mut unnamed = json.clone();
+    unnamed.as_object_mut().unwrap().remove("algorithm");
+    assert_eq!(verify_json_proof(digest, &unnamed.to_string(), root), Ok(()));
+
+    let unprefixed = MerkleTreeBuilder::new(digest)
+        .hashing_scheme(HashingScheme::Unprefixed)
+        .build_from_vec(tree.iter().cloned().collect());
+    let unprefixed_json = serde_json::to_string(&unprefixed.gen_nth_proof(3).unwrap()).unwrap();
+    assert_eq!(
+        verify_json_proof(digest, &unprefixed_json, unprefixed.root_hash()),
+        Err(Error::SchemeMismatch {
+            proof: HashingScheme::Unprefixed,
+            verifier: HashingScheme::DomainSeparated,
+        })
+    );
+
+    let mut number = json.clone();
+    number["value"] = serde_json::Value::from(3);
+    assert!(match verify_json_proof(digest, &number.to_string(), root) {
+        Err(Error::Decode(_)) => true,
+        _ => false,
+    });
+
+    let mut bytes = json.clone();
+    bytes["value"] = serde_json::Value::from(vec![1, 256]);
+    assert_eq!(
+        verify_json_proof(digest, &bytes.to_string(), root),
+        Err(Error::Decode("value is not an array of bytes".to_string()))
+    );
+
+    let mut short = json.clone();
+    short["root_hash"] = serde_json::Value::from(vec![0; 31]);
+    assert_eq!(
+        verify_json_proof(digest, &short.to_string(), root),
+        Err(Error::HashLengthMismatch {
+            expected: 32,
+            found: 31,
+        })
+    );
+
+    let mut newer = serde_json::json!({ "version": 1, "proof": json });
+    newer["version"] = serde_json::Value::from(1000);
+    assert_eq!(
+        verify_json_proof(digest, &newer.to_string(), root),
+        Err(Error::UnsupportedVersion {
+            found: 1000,
+            max_supported: 1,
+        })
+    );
+
+    for malformed in &["", "{", "[]", "{\"root_hash\": []}"] {
+        assert!(match verify_json_proof(digest, malformed, root) {
+            Err(Error::Decode(_)) => true,
+            _ => false,
+        });
+    }
+
+    assert!(match verify_proof_bytes(digest, &[0xff, 0xfe], ProofFormat::Json, root) {
+        Err(Error::Decode(_)) => true,
+        _ => false,
+    });
+
+    let deep = serde_json::to_string(&deep_proof()).unwrap();
+    assert_eq!(
+        verify_json_proof(digest, &deep, &vec![0; 32]),
+        Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"))
+    );
+}
+
+#[cfg(any(feature = "serialization-protobuf", feature = "serialization-prost"))]
+#[test]
+fn test_verify_protobuf() {
+    let tree = tree();
+    let root = tree.root_hash();
+
+    for i in 0..tree.count() {
+        let bytes = tree.gen_nth_proof(i).unwrap().write_protobuf();
+        assert_eq!(verify_proof_bytes(digest, &bytes, ProofFormat::Protobuf, root), Ok(()));
+    }
+
+    let mut proof = tree.gen_nth_proof(1).unwrap();
+    proof.value = b"forged".to_vec();
+    assert_eq!(
+        verify_proof_bytes(digest, &proof.write_protobuf(), ProofFormat::Protobuf, root),
+        Err(Error::InvalidProof)
+    );
+
+    let mut proof = tree.gen_nth_proof(1).unwrap();
+    proof.root_hash.pop();
+    assert_eq!(
+        verify_proof_bytes(digest, &proof.write_protobuf(), ProofFormat::Protobuf, root),
+        Err(Error::HashLengthMismatch {
+            expected: 32,
+            found: 31,
+        })
+    );
+
+    assert!(match verify_proof_bytes(digest, &[0xff; 8], ProofFormat::Protobuf, root) {
+        Err(Error::Decode(_)) => true,
+        _ => false,
+    });
+
+    assert_eq!(
+        verify_proof_bytes(digest, &[], ProofFormat::Protobuf, root),
+        Err(Error::MissingField("root_hash"))
+    );
+
+    let deep = deep_proof().write_protobuf();
+    assert_eq!(
+        verify_proof_bytes(digest, &deep, ProofFormat::Protobuf, &vec![0; 32]),
+        Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"))
+    );
+}
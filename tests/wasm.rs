@@ -0,0 +1,56 @@
+#![cfg(feature="wasm")]
+
+extern crate merkle;
+
+#[cfg(target_arch = "wasm32")]
+extern crate wasm_bindgen_test;
+
+use merkle::digest::{Algorithm, SHA256};
+
+use merkle::MerkleTree;
+use merkle::wasm::verify_proof_bytes;
+use merkle::wire::algorithm_tag;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+fn test_wasm_verify_proof_bytes() {
+    let values = (0..10).map(|i| vec![i]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let root_hex = to_hex(tree.root_hash());
+    let tag = algorithm_tag(digest);
+
+    for value in values {
+        let bytes = tree.gen_proof(value).unwrap().to_bytes();
+
+        assert!(verify_proof_bytes(tag, &bytes, &root_hex));
+        assert!(verify_proof_bytes(tag, &bytes, &root_hex.to_uppercase()));
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+fn test_wasm_reject_invalid_input() {
+    let tree = MerkleTree::from_vec(digest, vec![vec![1], vec![2], vec![3]]);
+    let root_hex = to_hex(tree.root_hash());
+    let tag = algorithm_tag(digest);
+    let bytes = tree.gen_proof(vec![2]).unwrap().to_bytes();
+
+    assert!(!verify_proof_bytes(0, &bytes, &root_hex));
+    assert!(!verify_proof_bytes(tag + 1, &bytes, &root_hex));
+    assert!(!verify_proof_bytes(tag, &bytes, "not hex"));
+    assert!(!verify_proof_bytes(tag, &bytes, &root_hex[2..]));
+    assert!(!verify_proof_bytes(tag, &bytes[..bytes.len() - 1], &root_hex));
+
+    let other = MerkleTree::from_vec(digest, vec![vec![4], vec![5], vec![6]]);
+    assert!(!verify_proof_bytes(tag, &bytes, &to_hex(other.root_hash())));
+}
@@ -0,0 +1,380 @@
+
+extern crate merkle;
+
+use merkle::digest::{Algorithm, SHA256, SHA512};
+
+use merkle::{MerkleTree, MerkleTreeBuilder, MultiProof, OddLeafPolicy, Proof, TreeHead, AlgorithmTag, Error};
+use merkle::wire::{self, VERSION, MULTIPROOF_VERSION};
+
+#[allow(non_upper_case_globals)]
+static digest: &'static Algorithm = &SHA256;
+
+/// The proof for `"a"` in the tree over `["a", "b", "c"]`.
+static GOLDEN_PROOF_A: &'static str = "\
+    4d4b4c50\
+    01\
+    02\
+    20\
+    02\
+    01597fcb31282d34654c200d3418fca5705c648ebf326ec73d8ddef11841f876d8\
+    0157eb35615d47f34ec714cacdf5fd74608a5e8e102724e80b24b287c0c27b6a31\
+    36642e73c2540ab121e3a6bf9545b0a24982cd830eb13d3cd19de3ce6c021ec1\
+    00000001\
+    61";
+
+/// The proof for `"c"` in the tree over `["a", "b", "c"]`.
+static GOLDEN_PROOF_C: &'static str = "\
+    4d4b4c50\
+    01\
+    02\
+    20\
+    01\
+    00b137985ff484fb600db93107c77b0365c80d78f5b429ded0fd97361d077999eb\
+    36642e73c2540ab121e3a6bf9545b0a24982cd830eb13d3cd19de3ce6c021ec1\
+    00000001\
+    63";
+
+/// The head of the tree over `["a", "b", "c"]`.
+static GOLDEN_TREE_HEAD: &'static str = "\
+    01\
+    02\
+    0000000000000003\
+    20\
+    36642e73c2540ab121e3a6bf9545b0a24982cd830eb13d3cd19de3ce6c021ec1";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap())
+        .collect()
+}
+
+fn abc_tree() -> MerkleTree<Vec<u8>> {
+    MerkleTree::from_vec(digest, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()])
+}
+
+#[test]
+fn test_wire_golden_bytes() {
+    let tree = abc_tree();
+
+    let proof = tree.gen_proof(b"a".to_vec()).unwrap();
+    assert_eq!(to_hex(&proof.to_bytes()), GOLDEN_PROOF_A);
+
+    let proof = tree.gen_proof(b"c".to_vec()).unwrap();
+    assert_eq!(to_hex(&proof.to_bytes()), GOLDEN_PROOF_C);
+}
+
+#[test]
+fn test_wire_golden_bytes_decode() {
+    let tree = abc_tree();
+
+    for &(golden, value) in &[(GOLDEN_PROOF_A, b"a"), (GOLDEN_PROOF_C, b"c")] {
+        let proof = Proof::from_bytes(digest, &from_hex(golden)).unwrap();
+
+        assert!(proof.validate(tree.root_hash()));
+        assert_eq!(proof, tree.gen_proof(value.to_vec()).unwrap());
+    }
+}
+
+#[test]
+fn test_wire_round_trip() {
+    for count in 1..20 {
+        let values = (0..count).map(|i| vec![i; i as usize]).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(digest, values.clone());
+
+        for value in values {
+            let proof = tree.gen_proof(value).unwrap();
+            let res = Proof::from_bytes(digest, &proof.to_bytes()).unwrap();
+
+            assert_eq!(res, proof);
+            assert!(res.validate(tree.root_hash()));
+        }
+    }
+}
+
+#[test]
+fn test_wire_leaf_counts() {
+    for count in 1..20 {
+        let values = (0..count).map(|i| vec![i; i as usize]).collect::<Vec<_>>();
+        let tree = MerkleTreeBuilder::new(digest).leaf_counts(true).build_from_vec(values);
+
+        for index in 0..tree.count() {
+            let proof = tree.gen_nth_proof(index).unwrap();
+            let bytes = proof.to_bytes();
+            assert_eq!(bytes[5], wire::algorithm_tag(digest) | wire::LEAF_COUNTS);
+
+            let res = Proof::from_bytes(digest, &bytes).unwrap();
+            assert_eq!(res.leaf_counts, proof.leaf_counts);
+            assert_eq!(res.leaf_count(), Some(count as u64));
+            assert!(res.validate(tree.root_hash()));
+
+            // Without the bit, the counts are read as part of the root hash, if there are any
+            let mut bytes = bytes;
+            bytes[5] = wire::algorithm_tag(digest);
+            let valid = Proof::from_bytes(digest, &bytes).map_or(false, |res| res.validate(tree.root_hash()));
+            assert_eq!(valid, count == 1);
+        }
+    }
+}
+
+#[test]
+fn test_wire_unsupported_version() {
+    let mut bytes = from_hex(GOLDEN_PROOF_A);
+    bytes[4] = VERSION + 1;
+
+    assert_eq!(
+        Proof::from_bytes(digest, &bytes),
+        Err(Error::UnsupportedVersion {
+            found: u32::from(VERSION + 1),
+            max_supported: u32::from(VERSION),
+        })
+    );
+}
+
+#[test]
+fn test_wire_truncated_input() {
+    let bytes = from_hex(GOLDEN_PROOF_A);
+
+    for len in 0..bytes.len() {
+        assert_eq!(Proof::from_bytes(digest, &bytes[..len]), Err(Error::TruncatedInput));
+    }
+
+    let mut bytes = bytes;
+    bytes.push(0);
+    assert_eq!(Proof::from_bytes(digest, &bytes), Err(Error::TrailingBytes));
+}
+
+#[test]
+fn test_wire_malformed_input() {
+    let bytes = from_hex(GOLDEN_PROOF_A);
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert_eq!(Proof::from_bytes(digest, &magic), Err(Error::InvalidMagic));
+
+    let mut tag = bytes.clone();
+    tag[5] = 42;
+    assert_eq!(Proof::from_bytes(digest, &tag), Err(Error::UnknownAlgorithm(42)));
+
+    assert_eq!(Proof::from_bytes(&SHA512, &bytes), Err(Error::AlgorithmMismatch));
+
+    let mut direction = bytes.clone();
+    direction[8] = 2;
+    assert_eq!(Proof::from_bytes(digest, &direction), Err(Error::InvalidDirection(2)));
+}
+
+#[test]
+fn test_wire_algorithm_tags() {
+    for tag in 1..6 {
+        let algorithm = wire::algorithm_from_tag(tag).unwrap();
+        assert_eq!(wire::algorithm_tag(algorithm), tag);
+    }
+
+    assert!(wire::algorithm_from_tag(0).is_none());
+    assert!(wire::algorithm_from_tag(6).is_none());
+    assert_eq!(wire::algorithm_tag(digest), 2);
+}
+
+#[test]
+fn test_multiproof_compact_round_trip() {
+    let values = (0..40u8).map(|i| vec![i]).collect::<Vec<_>>();
+
+    for policy in vec![OddLeafPolicy::Promote, OddLeafPolicy::Duplicate] {
+        for &len in &[0, 1, 2, 3, 7, 8, 9, 40] {
+            let tree = MerkleTreeBuilder::new(digest)
+                .odd_leaf_policy(policy)
+                .build_from_vec(values[..len].to_vec());
+
+            let all = (0..len).collect::<Vec<_>>();
+            let some = all.iter().cloned().filter(|i| i % 3 == 1).collect::<Vec<_>>();
+
+            for indices in vec![vec![], some, all] {
+                let proof = tree.gen_multi_proof(&indices).unwrap();
+                let res = MultiProof::from_compact_bytes(digest, &proof.to_compact_bytes()).unwrap();
+
+                assert_eq!(res, proof);
+                assert_eq!(res.indices(), indices);
+                assert_eq!(res.validate(tree.root_hash()), proof.validate(tree.root_hash()));
+                assert_eq!(res.verify_detailed(tree.root_hash()), proof.verify_detailed(tree.root_hash()));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_multiproof_compact_size() {
+    let values = (0..1024u32).map(|i| i.to_string().into_bytes()).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values.clone());
+    let indices = (0..1024).filter(|i| i % 64 < 4).collect::<Vec<_>>();
+
+    let compact = tree.gen_multi_proof(&indices).unwrap().to_compact_bytes().len();
+
+    let independent = indices
+        .iter()
+        .map(|&i| tree.gen_proof(values[i].clone()).unwrap().to_bytes().len())
+        .sum::<usize>();
+
+    assert!(compact * 3 < independent, "{} vs {}", compact, independent);
+}
+
+#[test]
+fn test_multiproof_compact_malformed_input() {
+    let tree = abc_tree();
+    let bytes = tree.gen_multi_proof(&[0]).unwrap().to_compact_bytes();
+
+    // The root, the node over `a` and `b`, the leaf `a`, then the pruned `b` and `c`
+    assert_eq!(&bytes[..14], &from_hex("4d4b4c4d01022000000301000507")[..]);
+    assert_eq!(bytes.len(), 14 + 3 * 32);
+
+    for len in 0..bytes.len() {
+        assert_eq!(MultiProof::from_compact_bytes(digest, &bytes[..len]), Err(Error::TruncatedInput));
+    }
+
+    let mut trailing = bytes.clone();
+    trailing.extend_from_slice(&[0; 32]);
+    assert_eq!(MultiProof::from_compact_bytes(digest, &trailing), Err(Error::TrailingBytes));
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert_eq!(MultiProof::from_compact_bytes(digest, &magic), Err(Error::InvalidMagic));
+
+    let mut version = bytes.clone();
+    version[4] = MULTIPROOF_VERSION + 1;
+    assert_eq!(
+        MultiProof::from_compact_bytes(digest, &version),
+        Err(Error::UnsupportedVersion {
+            found: u32::from(MULTIPROOF_VERSION + 1),
+            max_supported: u32::from(MULTIPROOF_VERSION),
+        })
+    );
+
+    assert_eq!(MultiProof::from_compact_bytes(&SHA512, &bytes), Err(Error::AlgorithmMismatch));
+
+    let malformed = |offset: usize, byte: u8| {
+        let mut bytes = bytes.clone();
+        bytes[offset] = byte;
+
+        match MultiProof::from_compact_bytes(digest, &bytes) {
+            Err(Error::Decode(_)) => {}
+            res => panic!("byte {} set to {}: {:?}", offset, byte, res),
+        }
+    };
+
+    malformed(8, 2);
+    malformed(11, 1);
+    malformed(13, 0b01111);
+    malformed(13, 0b10111);
+    malformed(13, 0b100111);
+
+    let mut fewer_bits = bytes.clone();
+    fewer_bits[12] = 3;
+    fewer_bits[13] = 0b011;
+    assert_eq!(
+        MultiProof::from_compact_bytes(digest, &fewer_bits),
+        Err(Error::Decode("bitfield has fewer bits than the proof has nodes".to_string()))
+    );
+
+    let mut more_bits = bytes.clone();
+    more_bits[12] = 6;
+    assert_eq!(
+        MultiProof::from_compact_bytes(digest, &more_bits),
+        Err(Error::Decode("bitfield has more bits than the proof has nodes".to_string()))
+    );
+}
+
+#[test]
+fn test_siblings_round_trip() {
+    let values = (0..16).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(digest, values);
+    let proof = tree.gen_proof(vec![5]).unwrap();
+
+    let siblings = proof.siblings().cloned().collect::<Vec<_>>();
+    let bytes = wire::encode_siblings(&siblings);
+
+    assert_eq!(bytes.len(), siblings.len() * (1 + digest.output_len));
+    assert_eq!(wire::decode_siblings(&bytes, digest.output_len), Ok(siblings));
+    assert_eq!(wire::decode_siblings(&[], digest.output_len), Ok(vec![]));
+}
+
+#[test]
+fn test_siblings_malformed_input() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    let proof = tree.gen_proof("a").unwrap();
+    let bytes = wire::encode_siblings(proof.siblings());
+
+    let mut bad_direction = bytes.clone();
+    bad_direction[0] = 2;
+    assert_eq!(
+        wire::decode_siblings(&bad_direction, digest.output_len),
+        Err(Error::InvalidDirection(2))
+    );
+
+    assert_eq!(
+        wire::decode_siblings(&bytes[..bytes.len() - 1], digest.output_len),
+        Err(Error::TruncatedInput)
+    );
+    assert_eq!(wire::decode_siblings(&bytes[..1], digest.output_len), Err(Error::TruncatedInput));
+}
+
+#[test]
+fn test_wire_rejects_deep_paths() {
+    let tree = MerkleTree::from_vec(digest, vec!["a", "b", "c"]);
+    let mut bytes = tree.gen_proof("c").unwrap().to_bytes();
+
+    // Claim more levels than any tree has, the input being rejected before it is read
+    bytes[7] = 65;
+
+    assert_eq!(
+        Proof::from_bytes(digest, &bytes),
+        Err(Error::InvalidPath("path is deeper than `MAX_TREE_DEPTH`"))
+    );
+}
+
+#[test]
+fn test_tree_head_golden_bytes() {
+    let head = abc_tree().tree_head();
+
+    assert_eq!(head.algorithm, AlgorithmTag::of(digest));
+    assert_eq!(head.tree_size, 3);
+    assert_eq!(to_hex(&head.to_signable_bytes()), GOLDEN_TREE_HEAD);
+    assert_eq!(TreeHead::parse(&from_hex(GOLDEN_TREE_HEAD)), Ok(head));
+
+    let empty = MerkleTree::<Vec<u8>>::empty(&SHA512).tree_head();
+    let bytes = empty.to_signable_bytes();
+
+    assert_eq!(to_hex(&bytes[..11]), "0104000000000000000040");
+    assert_eq!(bytes.len(), 11 + 64);
+    assert_eq!(TreeHead::parse(&bytes), Ok(empty));
+}
+
+#[test]
+fn test_tree_head_malformed_input() {
+    let bytes = from_hex(GOLDEN_TREE_HEAD);
+
+    for len in 0..bytes.len() {
+        assert_eq!(TreeHead::parse(&bytes[..len]), Err(Error::TruncatedInput));
+    }
+
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert_eq!(TreeHead::parse(&longer), Err(Error::TrailingBytes));
+
+    let mut newer = bytes.clone();
+    newer[0] = 2;
+    assert_eq!(TreeHead::parse(&newer), Err(Error::UnsupportedVersion { found: 2, max_supported: 1 }));
+
+    newer[0] = 0;
+    assert!(TreeHead::parse(&newer).is_err());
+
+    let mut unknown = bytes.clone();
+    unknown[1] = 0xff;
+    assert_eq!(TreeHead::parse(&unknown), Err(Error::UnknownAlgorithm(0xff)));
+
+    // A SHA-256 hash under the tag of SHA-512
+    let mut mismatched = bytes.clone();
+    mismatched[1] = 4;
+    assert_eq!(TreeHead::parse(&mismatched), Err(Error::HashLengthMismatch { expected: 64, found: 32 }));
+}